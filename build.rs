@@ -0,0 +1,50 @@
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Records what `maki version` reports about the build: the commit it was
+/// built from (when built from a git checkout), when, and for which platform
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    // Only this checkout's own commit, not that of a repository it was
+    // unpacked into
+    if Path::new(".git").exists()
+        && let Some(sha) = git(&["rev-parse", "HEAD"])
+    {
+        println!("cargo:rustc-env=MAKI_GIT_SHA={}", sha);
+        // Build again once HEAD moves, without watching paths that don't exist
+        let mut watched = vec![".git/HEAD".to_string(), ".git/packed-refs".to_string()];
+        watched.extend(git(&["symbolic-ref", "HEAD"]).map(|head| format!(".git/{}", head)));
+        for path in watched.iter().filter(|path| Path::new(path).exists()) {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+
+    // Reproducible builds pin the date with SOURCE_DATE_EPOCH
+    let built = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs())
+        });
+    if let Some(secs) = built {
+        println!("cargo:rustc-env=MAKI_BUILD_EPOCH={}", secs);
+    }
+
+    if let Ok(target) = std::env::var("TARGET") {
+        println!("cargo:rustc-env=MAKI_BUILD_TARGET={}", target);
+    }
+}
+
+/// What a git command prints, when it succeeds
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    let text = String::from_utf8(output.stdout).ok()?;
+    let text = text.trim();
+    (output.status.success() && !text.is_empty()).then(|| text.to_string())
+}
@@ -0,0 +1,275 @@
+use anyhow::{Result, bail};
+
+use crate::env;
+use crate::target::RequiredVar;
+
+/// A task from the config's `[alias]` table: targets and variable
+/// assignments, with `{name}` placeholders filled in when it is run
+///
+/// A placeholder may give a hint and a default, as in
+/// `{env:dev|staging|prod=dev}`; make's own `${VAR}` is left alone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alias {
+    pub name: String,
+    /// The template's words, e.g. `deploy` and `ENV={env}`
+    words: Vec<String>,
+    /// Its placeholders, in the order they first appear
+    pub params: Vec<RequiredVar>,
+}
+
+/// What an alias runs once its placeholders are filled in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    /// Targets to run, one after another
+    pub targets: Vec<String>,
+    /// Variables passed to make for every one of them
+    pub variables: Vec<(String, String)>,
+}
+
+/// A piece of a template word
+enum Piece<'a> {
+    Text(&'a str),
+    /// A placeholder, without its braces
+    Param(&'a str),
+}
+
+/// Split `word` into text and placeholders
+fn pieces(word: &str) -> Result<Vec<Piece<'_>>> {
+    let mut pieces = Vec::new();
+    let mut rest = word;
+    while let Some(open) = rest.find('{') {
+        // `${VAR}` is make's
+        if rest[..open].ends_with('$') {
+            let close = rest[open..].find('}').map_or(rest.len(), |i| open + i + 1);
+            pieces.push(Piece::Text(&rest[..close]));
+            rest = &rest[close..];
+            continue;
+        }
+        let Some(close) = rest[open..].find('}').map(|i| open + i) else {
+            bail!("Unclosed {{ in '{}'", word);
+        };
+        pieces.push(Piece::Text(&rest[..open]));
+        pieces.push(Piece::Param(&rest[open + 1..close]));
+        rest = &rest[close + 1..];
+    }
+    pieces.push(Piece::Text(rest));
+    Ok(pieces)
+}
+
+/// A placeholder's name, hint, and default from `name[:hint][=default]`
+fn parse_param(spec: &str) -> RequiredVar {
+    let (rest, default) = match spec.split_once('=') {
+        Some((rest, default)) => (rest, Some(default.to_string())),
+        None => (spec, None),
+    };
+    let (name, hint) = match rest.split_once(':') {
+        Some((name, hint)) => (name, Some(hint.to_string())),
+        None => (rest, None),
+    };
+    RequiredVar {
+        name: name.trim().to_string(),
+        hint,
+        default,
+        secret: None,
+    }
+}
+
+impl Alias {
+    pub fn parse(name: &str, template: &str) -> Result<Self> {
+        let words: Vec<String> = template.split_whitespace().map(str::to_string).collect();
+        let mut params: Vec<RequiredVar> = Vec::new();
+        for word in &words {
+            for piece in pieces(word)? {
+                let Piece::Param(spec) = piece else {
+                    continue;
+                };
+                let param = parse_param(spec);
+                if param.name.is_empty() {
+                    bail!(
+                        "Alias {} has a placeholder without a name: {{{}}}",
+                        name,
+                        spec
+                    );
+                }
+                if !params.iter().any(|p| p.name == param.name) {
+                    params.push(param);
+                }
+            }
+        }
+        if words.iter().all(|word| env::parse_var(word).is_ok()) {
+            bail!("Alias {} runs no target", name);
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            words,
+            params,
+        })
+    }
+
+    /// Values for the placeholders from `args`: `name=value` for one by name,
+    /// the rest in order
+    pub fn fill(&self, args: &[String]) -> Result<Vec<(String, String)>> {
+        let mut values = Vec::new();
+        let mut positional = Vec::new();
+        for arg in args {
+            match arg.split_once('=') {
+                Some((name, value)) if self.params.iter().any(|p| p.name == name) => {
+                    values.push((name.to_string(), value.to_string()));
+                }
+                _ => positional.push(arg),
+            }
+        }
+
+        let open: Vec<&RequiredVar> = self
+            .params
+            .iter()
+            .filter(|p| !values.iter().any(|(name, _)| *name == p.name))
+            .collect();
+        if positional.len() > open.len() {
+            let names: Vec<&str> = self.params.iter().map(|p| p.name.as_str()).collect();
+            bail!(
+                "Too many arguments for alias {}: it takes {}",
+                self.name,
+                match names.len() {
+                    0 => "none".to_string(),
+                    _ => names.join(", "),
+                }
+            );
+        }
+
+        for (param, value) in open.iter().zip(positional) {
+            values.push((param.name.clone(), value.clone()));
+        }
+        Ok(values)
+    }
+
+    /// The placeholders `values` leave without a value
+    pub fn missing(&self, values: &[(String, String)]) -> Vec<RequiredVar> {
+        self.params
+            .iter()
+            .filter(|p| !values.iter().any(|(name, _)| *name == p.name))
+            .cloned()
+            .collect()
+    }
+
+    /// The targets and variables, with `values` in place of the placeholders
+    pub fn invocation(&self, values: &[(String, String)]) -> Invocation {
+        let mut targets = Vec::new();
+        let mut variables = Vec::new();
+        for word in &self.words {
+            // Decided before filling in, since a value may hold an `=`
+            let assignment = env::parse_var(word).is_ok();
+            let word = expand(word, values);
+            match word.split_once('=') {
+                Some((name, value)) if assignment => {
+                    variables.push((name.to_string(), value.to_string()));
+                }
+                _ => targets.push(word),
+            }
+        }
+        Invocation { targets, variables }
+    }
+}
+
+/// `word` with each placeholder replaced by its value
+fn expand(word: &str, values: &[(String, String)]) -> String {
+    let Ok(pieces) = pieces(word) else {
+        return word.to_string();
+    };
+    pieces
+        .into_iter()
+        .map(|piece| match piece {
+            Piece::Text(text) => text,
+            Piece::Param(spec) => {
+                let name = parse_param(spec).name;
+                values
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map_or("", |(_, value)| value.as_str())
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn values(values: &[(&str, &str)]) -> Vec<(String, String)> {
+        values
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_placeholders() {
+        let alias = Alias::parse(
+            "ship",
+            "build deploy ENV={env:dev|prod=dev} TAG={tag} DIR=${HOME}/{env}",
+        )
+        .unwrap();
+
+        assert_eq!(
+            alias.params,
+            vec![
+                RequiredVar {
+                    name: "env".to_string(),
+                    hint: Some("dev|prod".to_string()),
+                    default: Some("dev".to_string()),
+                    secret: None,
+                },
+                RequiredVar {
+                    name: "tag".to_string(),
+                    hint: None,
+                    default: None,
+                    secret: None,
+                },
+            ]
+        );
+        assert!(Alias::parse("x", "deploy ENV={env").is_err());
+        assert!(Alias::parse("x", "ENV={env}").is_err());
+        assert!(Alias::parse("x", "deploy {}").is_err());
+    }
+
+    #[test]
+    fn test_fill_by_position_and_name() {
+        let alias = Alias::parse("deploy-to", "deploy ENV={env} REGION={region}").unwrap();
+
+        let filled = alias.fill(&args(&["prod"])).unwrap();
+        assert_eq!(filled, values(&[("env", "prod")]));
+        let missing = alias.missing(&filled);
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].name, "region");
+
+        let filled = alias.fill(&args(&["region=eu", "prod"])).unwrap();
+        assert_eq!(filled, values(&[("region", "eu"), ("env", "prod")]));
+        assert!(alias.missing(&filled).is_empty());
+
+        let err = alias.fill(&args(&["a", "b", "c"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Too many arguments for alias deploy-to: it takes env, region"
+        );
+    }
+
+    #[test]
+    fn test_invocation() {
+        let alias = Alias::parse("ship", "lint deploy-{env} MSG={msg} DIR=${HOME}").unwrap();
+
+        let invocation = alias.invocation(&values(&[("env", "prod"), ("msg", "a=b c")]));
+
+        assert_eq!(
+            invocation,
+            Invocation {
+                targets: vec!["lint".to_string(), "deploy-prod".to_string()],
+                variables: values(&[("MSG", "a=b c"), ("DIR", "${HOME}")]),
+            }
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::glob::glob_matches;
+use crate::runlog::strip_ansi;
+
+/// How much older than the run a file may look and still count as written by
+/// it, for filesystems that keep coarse modification times
+const CLOCK_SLACK: Duration = Duration::from_secs(2);
+
+/// Characters that surround a path in output, e.g. `'dist/app.tar.gz'` or
+/// `(coverage/index.html)`
+const DELIMITERS: [char; 14] = [
+    '\'', '"', '`', '(', ')', '[', ']', '<', '>', '{', '}', ',', ';', '=',
+];
+
+/// Which of the files a run mentions in its output are kept as its artifacts
+/// (`[artifacts]` table, plus `[target.<name>] artifacts`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArtifactPolicy {
+    /// Globs relative to the directory the target runs in, e.g.
+    /// `dist/*.tar.gz`; without any, nothing is collected
+    pub patterns: Vec<String>,
+    /// Directory to copy them to, relative to the same directory; without
+    /// one they are only listed
+    pub dir: Option<PathBuf>,
+}
+
+impl ArtifactPolicy {
+    pub fn is_enabled(&self) -> bool {
+        !self.patterns.is_empty()
+    }
+}
+
+/// A file a run wrote and mentioned in its output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// The file, relative to the directory the target ran in
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// Its copy in the artifacts directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub copy: Option<PathBuf>,
+}
+
+/// The words of `output` that could be paths, without the punctuation around
+/// them
+fn words(output: &str) -> impl Iterator<Item = &str> {
+    output
+        .split(|c: char| c.is_whitespace() || DELIMITERS.contains(&c))
+        .map(|word| word.trim_end_matches([':', '.']))
+        .filter(|word| !word.is_empty())
+}
+
+/// The files `output` mentions that match the policy's patterns and were
+/// written since `started`, relative to `dir`, in the order first mentioned
+pub fn find(
+    output: &str,
+    policy: &ArtifactPolicy,
+    dir: &Path,
+    started: SystemTime,
+) -> Vec<PathBuf> {
+    let since = started.checked_sub(CLOCK_SLACK).unwrap_or(started);
+    let output = strip_ansi(output);
+    let mut found: Vec<PathBuf> = Vec::new();
+
+    for word in words(&output) {
+        let path = Path::new(word);
+        let relative = match path.strip_prefix(dir) {
+            Ok(relative) => relative,
+            Err(_) if path.is_absolute() => continue,
+            Err(_) => path,
+        };
+        let relative: PathBuf = relative
+            .components()
+            .filter(|c| *c != Component::CurDir)
+            .collect();
+        // Copies stay inside the artifacts directory
+        if relative.components().any(|c| c == Component::ParentDir) {
+            continue;
+        }
+
+        let name = relative.to_string_lossy().replace('\\', "/");
+        if found.contains(&relative) || !policy.patterns.iter().any(|p| glob_matches(p, &name)) {
+            continue;
+        }
+        let written = fs::metadata(dir.join(&relative))
+            .ok()
+            .filter(|meta| meta.is_file())
+            .and_then(|meta| meta.modified().ok());
+        if written.is_some_and(|time| time >= since) {
+            found.push(relative);
+        }
+    }
+    found
+}
+
+/// Copy `found` into the policy's directory under `run`, keeping their paths,
+/// or only list them when it has none
+pub fn collect(
+    found: Vec<PathBuf>,
+    policy: &ArtifactPolicy,
+    dir: &Path,
+    run: &str,
+) -> Result<Vec<Artifact>> {
+    found
+        .into_iter()
+        .map(|path| {
+            let source = dir.join(&path);
+            let bytes = fs::metadata(&source).map(|meta| meta.len()).unwrap_or(0);
+            let copy = match policy.dir {
+                Some(ref artifacts) => {
+                    let copy = dir.join(artifacts).join(run).join(&path);
+                    if let Some(parent) = copy.parent() {
+                        fs::create_dir_all(parent)
+                            .with_context(|| format!("Failed to create {}", parent.display()))?;
+                    }
+                    fs::copy(&source, &copy).with_context(|| {
+                        format!("Failed to copy {} to {}", source.display(), copy.display())
+                    })?;
+                    Some(copy)
+                }
+                None => None,
+            };
+            Ok(Artifact { path, bytes, copy })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn policy(patterns: &[&str], dir: Option<&str>) -> ArtifactPolicy {
+        ArtifactPolicy {
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            dir: dir.map(PathBuf::from),
+        }
+    }
+
+    #[test]
+    fn test_find_mentioned_files() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("dist")).unwrap();
+        fs::create_dir_all(dir.path().join("coverage")).unwrap();
+        fs::write(dir.path().join("dist/app.tar.gz"), "tar").unwrap();
+        fs::write(dir.path().join("coverage/lcov.info"), "lcov").unwrap();
+        fs::write(dir.path().join("dist/notes.txt"), "").unwrap();
+
+        let output = format!(
+            "\x1b[32mwrote\x1b[0m 'dist/app.tar.gz'\nsee ./dist/notes.txt\n\
+             coverage: {}/coverage/lcov.info.\nsame dist/app.tar.gz again, \
+             and dist/missing.tar.gz\n",
+            dir.path().display()
+        );
+        let found = find(
+            &output,
+            &policy(&["dist/*.tar.gz", "coverage/**"], None),
+            dir.path(),
+            SystemTime::now(),
+        );
+
+        assert_eq!(
+            found,
+            vec![
+                PathBuf::from("dist/app.tar.gz"),
+                PathBuf::from("coverage/lcov.info"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_skips_files_from_before_the_run() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("app.zip"), "zip").unwrap();
+
+        let later = SystemTime::now() + Duration::from_secs(60);
+        let found = find("app.zip", &policy(&["*.zip"], None), dir.path(), later);
+
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn test_collect_copies_into_the_run_directory() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("dist")).unwrap();
+        fs::write(dir.path().join("dist/app.tar.gz"), "tar").unwrap();
+        let found = vec![PathBuf::from("dist/app.tar.gz")];
+
+        let listed = collect(found.clone(), &policy(&["dist/*"], None), dir.path(), "run").unwrap();
+        assert_eq!(listed[0].bytes, 3);
+        assert_eq!(listed[0].copy, None);
+
+        let policy = policy(&["dist/*"], Some("artifacts"));
+        let copied = collect(found, &policy, dir.path(), "20260101-120000-dist").unwrap();
+        let copy = dir
+            .path()
+            .join("artifacts/20260101-120000-dist/dist/app.tar.gz");
+        assert_eq!(copied[0].copy.as_ref(), Some(&copy));
+        assert_eq!(fs::read_to_string(copy).unwrap(), "tar");
+    }
+}
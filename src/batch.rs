@@ -0,0 +1,209 @@
+use anyhow::{Result, bail};
+use std::time::Duration;
+
+use crate::env::parse_var;
+use crate::heartbeat::format_elapsed;
+use crate::quote;
+
+/// One line of a batch file: a target and the variables to run it with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// 1-indexed line in the batch file
+    pub line: usize,
+    pub target: String,
+    pub variables: Vec<(String, String)>,
+}
+
+impl Step {
+    /// The step as it would be typed after `maki run`, e.g. `deploy ENV=staging`
+    pub fn label(&self) -> String {
+        std::iter::once(self.target.clone())
+            .chain(self.variables.iter().map(|(k, v)| quote::assignment(k, v)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Parse a batch file: one `target NAME=VALUE...` per line, with blank lines
+/// and `#` comments skipped, and values quoted as in the shell
+pub fn parse(content: &str) -> Result<Vec<Step>> {
+    let mut steps = Vec::new();
+    for (i, text) in content.lines().enumerate() {
+        let line = i + 1;
+        let text = text.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        let Some(words) = split_words(text) else {
+            bail!("line {}: unterminated quote", line);
+        };
+
+        let mut target = None;
+        let mut variables = Vec::new();
+        for word in words {
+            if let Ok(var) = parse_var(&word) {
+                variables.push(var);
+            } else if let Some(first) = &target {
+                bail!(
+                    "line {}: one target per line, found {} and {}",
+                    line,
+                    first,
+                    word
+                );
+            } else {
+                target = Some(word);
+            }
+        }
+        let Some(target) = target else {
+            bail!("line {}: no target, only variables", line);
+        };
+        steps.push(Step {
+            line,
+            target,
+            variables,
+        });
+    }
+    Ok(steps)
+}
+
+/// Split a line into words at whitespace, keeping what is in single or
+/// double quotes together; None when a quote isn't closed
+fn split_words(text: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                in_word = true;
+                loop {
+                    match chars.next()? {
+                        q if q == c => break,
+                        '\\' if c == '"' => word.push(chars.next()?),
+                        other => word.push(other),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                word.push(chars.next()?);
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Some(words)
+}
+
+/// How one step of a batch ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Succeeded(Duration),
+    Failed(Duration),
+    /// Not run, because an earlier step failed
+    Skipped,
+}
+
+/// The closing line of a batch, e.g. `3 succeeded, 1 failed, 2 not run in 1m 05s`
+pub fn summary(outcomes: &[Outcome]) -> String {
+    let count = |f: fn(&Outcome) -> bool| outcomes.iter().filter(|o| f(o)).count();
+    let succeeded = count(|o| matches!(o, Outcome::Succeeded(_)));
+    let failed = count(|o| matches!(o, Outcome::Failed(_)));
+    let skipped = count(|o| matches!(o, Outcome::Skipped));
+    let total: Duration = outcomes
+        .iter()
+        .map(|o| match o {
+            Outcome::Succeeded(d) | Outcome::Failed(d) => *d,
+            Outcome::Skipped => Duration::ZERO,
+        })
+        .sum();
+
+    let mut parts = vec![format!("{} succeeded", succeeded)];
+    if failed > 0 {
+        parts.push(format!("{} failed", failed));
+    }
+    if skipped > 0 {
+        parts.push(format!("{} not run", skipped));
+    }
+    format!("{} in {}", parts.join(", "), format_elapsed(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_steps() {
+        let steps = parse(
+            "# Release\nbuild\n\n  deploy ENV=staging REGION=eu\n\
+             notify MESSAGE=\"Deployed to staging\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            steps,
+            [
+                Step {
+                    line: 2,
+                    target: "build".to_string(),
+                    variables: Vec::new(),
+                },
+                Step {
+                    line: 4,
+                    target: "deploy".to_string(),
+                    variables: vars(&[("ENV", "staging"), ("REGION", "eu")]),
+                },
+                Step {
+                    line: 5,
+                    target: "notify".to_string(),
+                    variables: vars(&[("MESSAGE", "Deployed to staging")]),
+                },
+            ]
+        );
+        assert_eq!(steps[2].label(), "notify MESSAGE='Deployed to staging'");
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_lines() {
+        let error = |content| parse(content).unwrap_err().to_string();
+        assert_eq!(
+            error("build\nbuild test\n"),
+            "line 2: one target per line, found build and test"
+        );
+        assert_eq!(error("ENV=prod\n"), "line 1: no target, only variables");
+        assert_eq!(error("deploy MSG='oops\n"), "line 1: unterminated quote");
+    }
+
+    #[test]
+    fn test_summary() {
+        let outcomes = [
+            Outcome::Succeeded(Duration::from_secs(40)),
+            Outcome::Failed(Duration::from_secs(25)),
+            Outcome::Skipped,
+        ];
+        assert_eq!(
+            summary(&outcomes),
+            "1 succeeded, 1 failed, 1 not run in 1m 05s"
+        );
+        assert_eq!(summary(&[]), "0 succeeded in 0s");
+    }
+}
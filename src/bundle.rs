@@ -0,0 +1,207 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use std::fs;
+use std::path::Path;
+use toml::{Table, Value};
+
+use crate::config::Config;
+
+/// Bumped when the bundle layout changes, so older versions of maki can
+/// refuse bundles they would misread
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Which value wins when an imported setting differs from the project's
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Prefer {
+    /// Keep the project's value
+    Local,
+    /// Take the bundle's value
+    Bundle,
+}
+
+/// A setting both sides define with different values
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// Dotted path of the setting, e.g. `target.deploy.vars.ENV`
+    pub key: String,
+    pub local: Value,
+    pub bundle: Value,
+}
+
+/// The outcome of merging a bundle into the project's settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merge {
+    pub merged: Table,
+    /// Settings only the bundle defines
+    pub added: Vec<String>,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Bundle the project settings in `dir` into a single shareable file
+pub fn export(dir: &Path) -> Result<String> {
+    let path = Config::config_path(dir);
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("No settings to export: {} not found", path.display()))?;
+    Config::parse(&content).with_context(|| format!("Invalid config file: {}", path.display()))?;
+    let config: Table = toml::from_str(&content)?;
+
+    let mut bundle = Table::new();
+    bundle.insert("maki_bundle".to_string(), Value::from(BUNDLE_VERSION));
+    bundle.insert("config".to_string(), Value::Table(config));
+
+    Ok(format!(
+        "# maki settings bundle, import with `maki config import <file>`\n{}",
+        toml::to_string(&bundle)?
+    ))
+}
+
+/// The project settings held in a bundle
+pub fn read(content: &str) -> Result<Table> {
+    let mut bundle: Table = toml::from_str(content).context("Invalid settings bundle")?;
+    let version = bundle
+        .get("maki_bundle")
+        .and_then(Value::as_integer)
+        .context("Not a maki settings bundle (no maki_bundle version)")?;
+    if version > i64::from(BUNDLE_VERSION) {
+        bail!(
+            "The bundle was made by a newer maki (version {}, this one reads {})",
+            version,
+            BUNDLE_VERSION
+        );
+    }
+
+    let config = match bundle.remove("config") {
+        Some(Value::Table(config)) => config,
+        Some(_) => bail!("Invalid settings bundle: config must be a table"),
+        None => Table::new(),
+    };
+    // Refuse settings the project couldn't load before touching anything
+    Config::parse(&toml::to_string(&config)?).context("Invalid settings in the bundle")?;
+    Ok(config)
+}
+
+/// Merge `bundle` into `local` setting by setting, descending into tables;
+/// differing values are kept or replaced according to `prefer`
+pub fn merge(local: &Table, bundle: &Table, prefer: Prefer) -> Merge {
+    let mut merge = Merge {
+        merged: local.clone(),
+        added: Vec::new(),
+        conflicts: Vec::new(),
+    };
+    merge_into(
+        &mut merge.merged,
+        None,
+        bundle,
+        prefer,
+        &mut merge.added,
+        &mut merge.conflicts,
+    );
+    merge
+}
+
+fn merge_into(
+    merged: &mut Table,
+    prefix: Option<&str>,
+    bundle: &Table,
+    prefer: Prefer,
+    added: &mut Vec<String>,
+    conflicts: &mut Vec<Conflict>,
+) {
+    for (name, incoming) in bundle {
+        let key = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, name),
+            None => name.clone(),
+        };
+
+        match (merged.get_mut(name), incoming) {
+            (None, _) => {
+                merged.insert(name.clone(), incoming.clone());
+                added.push(key);
+            }
+            (Some(Value::Table(local)), Value::Table(table)) => {
+                merge_into(local, Some(&key), table, prefer, added, conflicts);
+            }
+            (Some(local), _) if local == incoming => {}
+            (Some(local), _) => {
+                conflicts.push(Conflict {
+                    key,
+                    local: local.clone(),
+                    bundle: incoming.clone(),
+                });
+                if prefer == Prefer::Bundle {
+                    *local = incoming.clone();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn table(content: &str) -> Table {
+        toml::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn test_export_and_read_round_trip() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            Config::config_path(dir.path()),
+            "single_target_shortcut = false\n\n[env]\nRUST_LOG = \"debug\"\n",
+        )
+        .unwrap();
+
+        let bundle = export(dir.path()).unwrap();
+        assert!(bundle.starts_with("# maki settings bundle"));
+
+        let config = read(&bundle).unwrap();
+        assert_eq!(config["single_target_shortcut"], Value::Boolean(false));
+        assert_eq!(config["env"]["RUST_LOG"].as_str(), Some("debug"));
+    }
+
+    #[test]
+    fn test_read_rejects_other_files() {
+        assert!(read("single_target_shortcut = false\n").is_err());
+        assert!(read("maki_bundle = 99\n").is_err());
+        assert!(read("maki_bundle = 1\n[config]\nroots = 3\n").is_err());
+    }
+
+    #[test]
+    fn test_merge_reports_conflicts() {
+        let local = table("[env]\nRUST_LOG = \"info\"\nLANG = \"C\"\n");
+        let bundle = table("roots = [\"../api\"]\n[env]\nRUST_LOG = \"debug\"\nLANG = \"C\"\n");
+
+        let merge = super::merge(&local, &bundle, Prefer::Local);
+
+        assert_eq!(merge.added, vec!["roots"]);
+        assert_eq!(merge.conflicts.len(), 1);
+        assert_eq!(merge.conflicts[0].key, "env.RUST_LOG");
+        assert_eq!(merge.merged["env"]["RUST_LOG"].as_str(), Some("info"));
+        assert!(merge.merged.contains_key("roots"));
+
+        let merge = super::merge(&local, &bundle, Prefer::Bundle);
+        assert_eq!(merge.merged["env"]["RUST_LOG"].as_str(), Some("debug"));
+    }
+
+    #[test]
+    fn test_merge_adds_nested_tables() {
+        let local = table("[target.build.vars]\nMODE = \"debug\"\n");
+        let bundle = table("[target.deploy.vars]\nENV = \"staging\"\n");
+
+        let merge = super::merge(&local, &bundle, Prefer::Local);
+
+        assert_eq!(merge.added, vec!["target.deploy"]);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(
+            merge.merged["target"]["deploy"]["vars"]["ENV"].as_str(),
+            Some("staging")
+        );
+        assert_eq!(
+            merge.merged["target"]["build"]["vars"]["MODE"].as_str(),
+            Some("debug")
+        );
+    }
+}
@@ -1,13 +1,68 @@
-use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
 use std::time::SystemTime;
 
+use crate::error::{MakiError, Result};
+use crate::makefile::{self, Block, Include, ParseOptions};
 use crate::target::Target;
 
+/// Build a cache error from a description and its cause
+fn cache_error(message: impl std::fmt::Display, cause: impl std::fmt::Display) -> MakiError {
+    MakiError::Cache {
+        message: format!("{} ({})", message, cause),
+    }
+}
+
+/// Error for when the platform has no cache directory
+fn no_cache_dir() -> MakiError {
+    MakiError::Cache {
+        message: "Could not determine cache directory".to_string(),
+    }
+}
+
+/// Seconds since the Unix epoch
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// The sources whose code decides what a Makefile parses into
+const PARSER_SOURCES: [&str; 3] = [
+    include_str!("makefile.rs"),
+    include_str!("target.rs"),
+    include_str!("generated.rs"),
+];
+
+/// A hash of the parser's sources, so that a build whose parsing may differ
+/// doesn't trust entries written by another, even with the same version
+static PARSER_FINGERPRINT: LazyLock<String> =
+    LazyLock::new(|| compute_hash(PARSER_SOURCES.concat())[..16].to_string());
+
+/// The maki build that parsed a cached Makefile
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParserVersion {
+    /// The crate version, e.g. `0.4.2`
+    pub maki: String,
+    /// A hash of the parser's sources
+    pub fingerprint: String,
+}
+
+impl ParserVersion {
+    /// The version of this build
+    pub fn current() -> Self {
+        Self {
+            maki: env!("CARGO_PKG_VERSION").to_string(),
+            fingerprint: PARSER_FINGERPRINT.clone(),
+        }
+    }
+}
+
 /// Cache entry for a single Makefile
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheEntry {
@@ -15,8 +70,65 @@ pub struct CacheEntry {
     pub content_hash: String,
     /// Last modification time of the Makefile
     pub modified_time: u64,
+    /// When the entry was last read or written, for LRU eviction
+    #[serde(default)]
+    pub last_accessed: u64,
+    /// The build that parsed it; entries from any other are re-parsed
+    #[serde(default)]
+    pub parsed_by: ParserVersion,
     /// Cached targets from this Makefile
     pub targets: Vec<Target>,
+    /// The blocks they were read from, so that a changed Makefile only has
+    /// its changed rules read again
+    #[serde(default)]
+    pub blocks: Vec<Block>,
+    /// What parsing it warned about
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// The files it includes, whose targets are among its own
+    #[serde(default)]
+    pub includes: Vec<Include>,
+    /// A hash of what they held, so that editing one re-parses the Makefile
+    #[serde(default)]
+    pub includes_hash: String,
+}
+
+/// A Makefile's targets as `Cache::parse_file` found them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parsed {
+    pub targets: Vec<Target>,
+    /// What parsing the file warned about, when it was last parsed
+    pub warnings: Vec<String>,
+    /// Whether they came from the cache rather than a fresh parse
+    pub cached: bool,
+}
+
+/// Limits on how much the cache keeps, set in the `[cache]` config table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CachePolicy {
+    /// Keep at most this many Makefiles, evicting the least recently used
+    pub max_entries: usize,
+    /// Evict entries that haven't been used for this many days
+    pub max_age_days: u64,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            max_entries: 500,
+            max_age_days: 90,
+        }
+    }
+}
+
+/// Running totals of evicted entries, kept in the cache file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Evictions {
+    /// Entries dropped for exceeding `max_age_days`
+    pub expired: u64,
+    /// Entries dropped for exceeding `max_entries`
+    pub over_capacity: u64,
 }
 
 /// The complete cache structure
@@ -26,10 +138,16 @@ pub struct Cache {
     pub version: u32,
     /// Map of absolute file paths to their cache entries
     pub entries: HashMap<String, CacheEntry>,
+    /// Entries evicted so far
+    #[serde(default)]
+    pub evictions: Evictions,
+    /// Eviction limits applied on load and save
+    #[serde(skip)]
+    pub policy: CachePolicy,
 }
 
 impl Cache {
-    const CURRENT_VERSION: u32 = 1;
+    const CURRENT_VERSION: u32 = 18;
     const CACHE_FILENAME: &'static str = "maki_cache.json";
 
     /// Create a new empty cache
@@ -37,6 +155,16 @@ impl Cache {
         Self {
             version: Self::CURRENT_VERSION,
             entries: HashMap::new(),
+            evictions: Evictions::default(),
+            policy: CachePolicy::default(),
+        }
+    }
+
+    /// Create a new empty cache with the given eviction limits
+    pub fn with_policy(policy: CachePolicy) -> Self {
+        Self {
+            policy,
+            ..Self::new()
         }
     }
 
@@ -52,84 +180,207 @@ impl Cache {
 
     /// Load the cache from disk
     pub fn load() -> Result<Self> {
-        let cache_path = Self::cache_file_path().context("Could not determine cache directory")?;
+        Self::load_with_policy(CachePolicy::default())
+    }
+
+    /// Where an unreadable cache file is set aside, for a look at what went
+    /// wrong
+    pub fn quarantine_path(cache_path: &Path) -> PathBuf {
+        cache_path.with_extension("json.corrupt")
+    }
+
+    /// Load the cache from disk, evicting entries beyond the given limits
+    pub fn load_with_policy(policy: CachePolicy) -> Result<Self> {
+        let cache_path = Self::cache_file_path().ok_or_else(no_cache_dir)?;
+        Self::load_from(&cache_path, policy)
+    }
 
+    /// Load the cache from `cache_path`; a file that doesn't parse is moved
+    /// to its quarantine path and a fresh cache used instead
+    fn load_from(cache_path: &Path, policy: CachePolicy) -> Result<Self> {
         if !cache_path.exists() {
-            return Ok(Self::new());
+            return Ok(Self::with_policy(policy));
         }
 
-        let content = fs::read_to_string(&cache_path)
-            .with_context(|| format!("Failed to read cache file: {}", cache_path.display()))?;
+        let content = fs::read_to_string(cache_path).map_err(|e| {
+            cache_error(format!("Failed to read cache file: {}", cache_path.display()), e)
+        })?;
 
-        let cache: Self =
-            serde_json::from_str(&content).with_context(|| "Failed to parse cache file")?;
+        let Ok(mut cache) = serde_json::from_str::<Self>(&content) else {
+            fs::rename(cache_path, Self::quarantine_path(cache_path)).map_err(|e| {
+                cache_error(
+                    format!(
+                        "Failed to set aside corrupt cache file: {}",
+                        cache_path.display()
+                    ),
+                    e,
+                )
+            })?;
+            return Ok(Self::with_policy(policy));
+        };
 
         // Check version compatibility
         if cache.version != Self::CURRENT_VERSION {
             // Incompatible version, return fresh cache
-            return Ok(Self::new());
+            return Ok(Self::with_policy(policy));
         }
 
+        cache.policy = policy;
+        cache.evict(now_secs());
+
         Ok(cache)
     }
 
-    /// Save the cache to disk
-    pub fn save(&self) -> Result<()> {
-        let cache_dir = Self::cache_dir().context("Could not determine cache directory")?;
+    /// Save the cache to disk, evicting entries beyond the limits first
+    pub fn save(&mut self) -> Result<()> {
+        let cache_dir = Self::cache_dir().ok_or_else(no_cache_dir)?;
+        self.save_to(&cache_dir.join(Self::CACHE_FILENAME))
+    }
+
+    /// Save the cache to `cache_path` by writing a file of this process's own
+    /// next to it and renaming that over it, so another maki saving at the
+    /// same time can't interleave its writes with these
+    fn save_to(&mut self, cache_path: &Path) -> Result<()> {
+        self.evict(now_secs());
+
+        let cache_dir = cache_path.parent().unwrap_or(Path::new("."));
 
         // Create cache directory if it doesn't exist
         if !cache_dir.exists() {
-            fs::create_dir_all(&cache_dir).with_context(|| {
-                format!("Failed to create cache directory: {}", cache_dir.display())
+            fs::create_dir_all(cache_dir).map_err(|e| {
+                cache_error(
+                    format!("Failed to create cache directory: {}", cache_dir.display()),
+                    e,
+                )
             })?;
         }
 
-        let cache_path = cache_dir.join(Self::CACHE_FILENAME);
-        let content = serde_json::to_string_pretty(self).context("Failed to serialize cache")?;
-
-        fs::write(&cache_path, content)
-            .with_context(|| format!("Failed to write cache file: {}", cache_path.display()))?;
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| cache_error("Failed to serialize cache", e))?;
+
+        let temp_path = cache_path.with_extension(format!("json.{}.tmp", std::process::id()));
+        fs::write(&temp_path, content)
+            .and_then(|()| fs::rename(&temp_path, cache_path))
+            .map_err(|e| {
+                let _ = fs::remove_file(&temp_path);
+                cache_error(
+                    format!("Failed to write cache file: {}", cache_path.display()),
+                    e,
+                )
+            })?;
 
         Ok(())
     }
 
     /// Get cached targets for a Makefile if the cache is still valid
-    pub fn get(&self, makefile_path: &Path) -> Option<&Vec<Target>> {
+    pub fn get(&mut self, makefile_path: &Path) -> Option<&Vec<Target>> {
         let abs_path = makefile_path.canonicalize().ok()?;
         let path_str = abs_path.to_string_lossy().to_string();
 
         let entry = self.entries.get(&path_str)?;
 
         // Verify the cache is still valid
-        if self.is_entry_valid(makefile_path, entry) {
-            Some(&entry.targets)
-        } else {
-            None
+        if !self.is_entry_valid(makefile_path, entry) {
+            return None;
         }
+
+        let entry = self.entries.get_mut(&path_str)?;
+        entry.last_accessed = now_secs();
+        Some(&entry.targets)
     }
 
     /// Check if a cache entry is still valid
     fn is_entry_valid(&self, makefile_path: &Path, entry: &CacheEntry) -> bool {
+        // An upgrade may parse the same Makefile differently
+        if entry.parsed_by != ParserVersion::current() {
+            return false;
+        }
+
         // Check if file still exists and hash matches
-        if let Ok(content) = fs::read_to_string(makefile_path) {
-            let current_hash = compute_hash(&content);
-            current_hash == entry.content_hash
-        } else {
-            false
+        let unchanged = match fs::read(makefile_path) {
+            Ok(content) => compute_hash(&content) == entry.content_hash,
+            Err(_) => false,
+        };
+        // ... and so do the files it includes
+        unchanged
+            && (entry.includes.is_empty()
+                || makefile::includes_hash(&entry.includes) == entry.includes_hash)
+    }
+
+    /// Get the targets of a Makefile from the cache, or parse it; when it has
+    /// changed since it was cached, the targets whose lines haven't are kept
+    pub fn parse(&mut self, makefile_path: &Path, options: &ParseOptions) -> Result<Vec<Target>> {
+        Ok(self.parse_file(makefile_path, options)?.targets)
+    }
+
+    /// Like `parse`, with what parsing the file warned about
+    pub fn parse_file(&mut self, makefile_path: &Path, options: &ParseOptions) -> Result<Parsed> {
+        if let Some(targets) = self.get(makefile_path) {
+            let targets = targets.clone();
+            let warnings = self.entry(makefile_path).map(|e| e.warnings.clone());
+            return Ok(Parsed {
+                targets,
+                warnings: warnings.unwrap_or_default(),
+                cached: true,
+            });
         }
+
+        let info = makefile::reparse_makefile(makefile_path, options, &self.blocks(makefile_path))?;
+        self.set_with_blocks(
+            makefile_path,
+            info.targets.clone(),
+            info.blocks,
+            info.warnings.clone(),
+            info.includes,
+        )?;
+        Ok(Parsed {
+            targets: info.targets,
+            warnings: info.warnings,
+            cached: false,
+        })
+    }
+
+    fn entry(&self, makefile_path: &Path) -> Option<&CacheEntry> {
+        let abs_path = makefile_path.canonicalize().ok()?;
+        self.entries.get(&abs_path.to_string_lossy().to_string())
+    }
+
+    /// The blocks of a Makefile's entry, if that was written by this build
+    fn blocks(&self, makefile_path: &Path) -> Vec<Block> {
+        makefile_path
+            .canonicalize()
+            .ok()
+            .and_then(|path| self.entries.get(&path.to_string_lossy().to_string()))
+            .filter(|entry| entry.parsed_by == ParserVersion::current())
+            .map(|entry| entry.blocks.clone())
+            .unwrap_or_default()
     }
 
     /// Store targets in the cache for a Makefile
     pub fn set(&mut self, makefile_path: &Path, targets: Vec<Target>) -> Result<()> {
-        let abs_path = makefile_path.canonicalize().with_context(|| {
-            format!(
-                "Failed to get absolute path for: {}",
-                makefile_path.display()
+        self.set_with_blocks(makefile_path, targets, Vec::new(), Vec::new(), Vec::new())
+    }
+
+    fn set_with_blocks(
+        &mut self,
+        makefile_path: &Path,
+        targets: Vec<Target>,
+        blocks: Vec<Block>,
+        warnings: Vec<String>,
+        includes: Vec<Include>,
+    ) -> Result<()> {
+        let abs_path = makefile_path.canonicalize().map_err(|e| {
+            cache_error(
+                format!(
+                    "Failed to get absolute path for: {}",
+                    makefile_path.display()
+                ),
+                e,
             )
         })?;
 
-        let content = fs::read_to_string(makefile_path)
-            .with_context(|| format!("Failed to read Makefile: {}", makefile_path.display()))?;
+        let content =
+            fs::read(makefile_path).map_err(|e| MakiError::unreadable(makefile_path, e))?;
 
         let content_hash = compute_hash(&content);
 
@@ -143,7 +394,13 @@ impl Cache {
         let entry = CacheEntry {
             content_hash,
             modified_time,
+            last_accessed: now_secs(),
+            parsed_by: ParserVersion::current(),
             targets,
+            blocks,
+            warnings,
+            includes_hash: makefile::includes_hash(&includes),
+            includes,
         };
 
         self.entries
@@ -152,7 +409,6 @@ impl Cache {
     }
 
     /// Remove a specific entry from the cache
-    #[allow(dead_code)]
     pub fn invalidate(&mut self, makefile_path: &Path) {
         if let Ok(abs_path) = makefile_path.canonicalize() {
             self.entries.remove(&abs_path.to_string_lossy().to_string());
@@ -171,38 +427,78 @@ impl Cache {
         self.entries.retain(|path, _| Path::new(path).exists());
     }
 
+    /// Drop entries unused for longer than `max_age_days`, then the least
+    /// recently used ones beyond `max_entries`
+    pub fn evict(&mut self, now: u64) -> Evictions {
+        let max_age = self.policy.max_age_days.saturating_mul(24 * 60 * 60);
+        let before = self.entries.len();
+        self.entries
+            .retain(|_, entry| now.saturating_sub(entry.last_accessed) <= max_age);
+        let expired = before - self.entries.len();
+
+        let mut over_capacity = 0;
+        if self.entries.len() > self.policy.max_entries {
+            let mut by_access: Vec<(String, u64)> = self
+                .entries
+                .iter()
+                .map(|(path, entry)| (path.clone(), entry.last_accessed))
+                .collect();
+            by_access.sort_by_key(|(_, last_accessed)| *last_accessed);
+
+            over_capacity = self.entries.len() - self.policy.max_entries;
+            for (path, _) in by_access.into_iter().take(over_capacity) {
+                self.entries.remove(&path);
+            }
+        }
+
+        let evicted = Evictions {
+            expired: expired as u64,
+            over_capacity: over_capacity as u64,
+        };
+        self.evictions.expired += evicted.expired;
+        self.evictions.over_capacity += evicted.over_capacity;
+        evicted
+    }
+
     /// Get cache statistics
-    #[allow(dead_code)]
     pub fn stats(&self) -> CacheStats {
         CacheStats {
             entry_count: self.entries.len(),
             total_targets: self.entries.values().map(|e| e.targets.len()).sum(),
+            evictions: self.evictions,
+            policy: self.policy.clone(),
         }
     }
 }
 
 /// Statistics about the cache
-#[derive(Debug)]
-#[allow(dead_code)]
+#[derive(Debug, Serialize)]
 pub struct CacheStats {
     pub entry_count: usize,
     pub total_targets: usize,
+    pub evictions: Evictions,
+    pub policy: CachePolicy,
 }
 
 /// Compute SHA256 hash of content
-pub fn compute_hash(content: &str) -> String {
+pub fn compute_hash(content: impl AsRef<[u8]>) -> String {
     let mut hasher = Sha256::new();
-    hasher.update(content.as_bytes());
+    hasher.update(content.as_ref());
     hex::encode(hasher.finalize())
 }
 
-/// Delete the cache file from disk
-#[allow(dead_code)]
+/// Delete the cache file from disk, and a corrupt one set aside
 pub fn clear_cache() -> Result<()> {
-    if let Some(cache_path) = Cache::cache_file_path() {
-        if cache_path.exists() {
-            fs::remove_file(&cache_path).with_context(|| {
-                format!("Failed to delete cache file: {}", cache_path.display())
+    let Some(cache_path) = Cache::cache_file_path() else {
+        return Ok(());
+    };
+    for path in [Cache::quarantine_path(&cache_path), cache_path] {
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| {
+                cache_error(
+                    format!("Failed to delete cache file: {}", path.display()),
+                    e,
+                )
             })?;
         }
     }
@@ -286,6 +582,64 @@ mod tests {
         assert!(cache.get(&makefile_path).is_none());
     }
 
+    #[test]
+    fn test_cache_invalidation_on_parser_change() {
+        let mut cache = Cache::new();
+        let temp_dir = TempDir::new().unwrap();
+        let makefile_path = temp_dir.path().join("Makefile");
+        fs::write(&makefile_path, "build:\n\techo building").unwrap();
+        cache.set(&makefile_path, vec![]).unwrap();
+        let key = makefile_path
+            .canonicalize()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+
+        // Written by an older release
+        cache.entries.get_mut(&key).unwrap().parsed_by.maki = "0.0.1".to_string();
+        assert!(cache.get(&makefile_path).is_none());
+
+        // Written by a build of the same version with a different parser
+        let entry = cache.entries.get_mut(&key).unwrap();
+        entry.parsed_by = ParserVersion::current();
+        entry.parsed_by.fingerprint = "0000000000000000".to_string();
+        assert!(cache.get(&makefile_path).is_none());
+
+        cache.entries.get_mut(&key).unwrap().parsed_by = ParserVersion::current();
+        assert!(cache.get(&makefile_path).is_some());
+    }
+
+    #[test]
+    fn test_cache_invalidation_on_included_file_change() {
+        let mut cache = Cache::new();
+        let temp_dir = TempDir::new().unwrap();
+        let makefile_path = temp_dir.path().join("Makefile");
+        let included = temp_dir.path().join("common.mk");
+        fs::write(&makefile_path, "include common.mk\nbuild:\n\ttrue\n").unwrap();
+        fs::write(&included, "lint:\n\ttrue\n").unwrap();
+        let options = ParseOptions::default();
+
+        let parsed = cache.parse_file(&makefile_path, &options).unwrap();
+        assert_eq!(parsed.targets.len(), 2);
+        assert!(cache.parse_file(&makefile_path, &options).unwrap().cached);
+
+        // Only the included file changed
+        fs::write(&included, "lint:\n\ttrue\ntest:\n\ttrue\n").unwrap();
+        let parsed = cache.parse_file(&makefile_path, &options).unwrap();
+        assert!(!parsed.cached);
+        assert_eq!(parsed.targets.len(), 3);
+    }
+
+    #[test]
+    fn test_entries_without_a_parser_version_are_stale() {
+        let entry: CacheEntry =
+            serde_json::from_str(r#"{"content_hash": "abc", "modified_time": 0, "targets": []}"#)
+                .unwrap();
+
+        assert_eq!(entry.parsed_by, ParserVersion::default());
+        assert_ne!(entry.parsed_by, ParserVersion::current());
+    }
+
     #[test]
     fn test_cache_prune() {
         let mut cache = Cache::new();
@@ -297,7 +651,13 @@ mod tests {
             CacheEntry {
                 content_hash: "abc123".to_string(),
                 modified_time: 0,
+                last_accessed: 0,
+                parsed_by: ParserVersion::current(),
+                blocks: vec![],
                 targets: vec![],
+                warnings: vec![],
+                includes: vec![],
+                includes_hash: String::new(),
             },
         );
 
@@ -318,7 +678,13 @@ mod tests {
             CacheEntry {
                 content_hash: "abc".to_string(),
                 modified_time: 0,
+                last_accessed: 0,
+                parsed_by: ParserVersion::current(),
+                blocks: vec![],
                 targets: vec![],
+                warnings: vec![],
+                includes: vec![],
+                includes_hash: String::new(),
             },
         );
 
@@ -338,10 +704,16 @@ mod tests {
             CacheEntry {
                 content_hash: "abc".to_string(),
                 modified_time: 0,
+                last_accessed: 0,
+                parsed_by: ParserVersion::current(),
+                blocks: vec![],
                 targets: vec![
                     Target::new("a".to_string(), None, PathBuf::from("f"), 1),
                     Target::new("b".to_string(), None, PathBuf::from("f"), 2),
                 ],
+                warnings: vec![],
+                includes: vec![],
+                includes_hash: String::new(),
             },
         );
 
@@ -350,7 +722,13 @@ mod tests {
             CacheEntry {
                 content_hash: "def".to_string(),
                 modified_time: 0,
+                last_accessed: 0,
+                parsed_by: ParserVersion::current(),
+                blocks: vec![],
                 targets: vec![Target::new("c".to_string(), None, PathBuf::from("f"), 1)],
+                warnings: vec![],
+                includes: vec![],
+                includes_hash: String::new(),
             },
         );
 
@@ -359,6 +737,133 @@ mod tests {
         assert_eq!(stats.total_targets, 3);
     }
 
+    fn entry_accessed_at(last_accessed: u64) -> CacheEntry {
+        CacheEntry {
+            content_hash: "abc".to_string(),
+            modified_time: 0,
+            last_accessed,
+            parsed_by: ParserVersion::current(),
+            blocks: vec![],
+            targets: vec![],
+            warnings: vec![],
+            includes: vec![],
+            includes_hash: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_evict_expired_entries() {
+        let day = 24 * 60 * 60;
+        let now = 100 * day;
+        let mut cache = Cache::with_policy(CachePolicy {
+            max_entries: 10,
+            max_age_days: 30,
+        });
+
+        cache
+            .entries
+            .insert("recent".to_string(), entry_accessed_at(now - day));
+        cache
+            .entries
+            .insert("stale".to_string(), entry_accessed_at(now - 31 * day));
+
+        let evicted = cache.evict(now);
+
+        assert_eq!(evicted.expired, 1);
+        assert_eq!(evicted.over_capacity, 0);
+        assert!(cache.entries.contains_key("recent"));
+        assert!(!cache.entries.contains_key("stale"));
+    }
+
+    #[test]
+    fn test_evict_least_recently_used_over_capacity() {
+        let mut cache = Cache::with_policy(CachePolicy {
+            max_entries: 2,
+            max_age_days: 30,
+        });
+
+        for (path, accessed) in [("a", 10), ("b", 30), ("c", 20)] {
+            cache
+                .entries
+                .insert(path.to_string(), entry_accessed_at(accessed));
+        }
+
+        let evicted = cache.evict(40);
+
+        assert_eq!(evicted.over_capacity, 1);
+        assert!(!cache.entries.contains_key("a"));
+        assert_eq!(cache.entries.len(), 2);
+
+        // Totals accumulate across evictions and show up in stats
+        cache.policy.max_entries = 1;
+        cache.evict(40);
+        let stats = cache.stats();
+        assert_eq!(stats.evictions.over_capacity, 2);
+        assert!(cache.entries.contains_key("b"));
+    }
+
+    #[test]
+    fn test_parse_after_an_edit() {
+        let mut cache = Cache::new();
+        let temp_dir = TempDir::new().unwrap();
+        let makefile_path = temp_dir.path().join("Makefile");
+        let options = ParseOptions::default();
+        fs::write(&makefile_path, "# Build it\nbuild:\n\tcc\n").unwrap();
+
+        let targets = cache.parse(&makefile_path, &options).unwrap();
+        assert_eq!(targets[0].line, 2);
+        let entry = cache.entries.values().next().unwrap();
+        assert_eq!(entry.blocks.len(), 1);
+
+        fs::write(
+            &makefile_path,
+            "\n\n# Build it\nbuild:\n\tcc\n# Test it\ntest:\n",
+        )
+        .unwrap();
+        let targets = cache.parse(&makefile_path, &options).unwrap();
+        assert_eq!(
+            targets,
+            makefile::parse_makefile(&makefile_path, &options).unwrap()
+        );
+        assert_eq!(cache.get(&makefile_path).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_file_keeps_warnings() {
+        let mut cache = Cache::new();
+        let temp_dir = TempDir::new().unwrap();
+        let makefile_path = temp_dir.path().join("Makefile");
+        let options = ParseOptions::default();
+        fs::write(&makefile_path, b"# Caf\xe9\nbuild:\n\tcc\n").unwrap();
+
+        let parsed = cache.parse_file(&makefile_path, &options).unwrap();
+        assert!(!parsed.cached);
+        assert_eq!(parsed.warnings.len(), 1);
+        assert_eq!(parsed.targets[0].description, Some("Café".to_string()));
+
+        // A cache hit still knows what the parse warned about
+        let cached = cache.parse_file(&makefile_path, &options).unwrap();
+        assert!(cached.cached);
+        assert_eq!(cached.warnings, parsed.warnings);
+        assert_eq!(cached.targets, parsed.targets);
+    }
+
+    #[test]
+    fn test_get_updates_last_accessed() {
+        let mut cache = Cache::new();
+        let temp_dir = TempDir::new().unwrap();
+        let makefile_path = temp_dir.path().join("Makefile");
+        fs::write(&makefile_path, "build:\n\techo building").unwrap();
+
+        cache.set(&makefile_path, vec![]).unwrap();
+        for entry in cache.entries.values_mut() {
+            entry.last_accessed = 0;
+        }
+
+        assert!(cache.get(&makefile_path).is_some());
+        assert!(cache.entries.values().all(|e| e.last_accessed > 0));
+    }
+
     #[test]
     fn test_cache_serialization() {
         let mut cache = Cache::new();
@@ -368,12 +873,18 @@ mod tests {
             CacheEntry {
                 content_hash: "abc123".to_string(),
                 modified_time: 1234567890,
+                last_accessed: 1234567890,
+                parsed_by: ParserVersion::current(),
+                blocks: vec![],
                 targets: vec![Target::new(
                     "build".to_string(),
                     Some("Build it".to_string()),
                     PathBuf::from("/test/Makefile"),
                     1,
                 )],
+                warnings: vec![],
+                includes: vec![],
+                includes_hash: String::new(),
             },
         );
 
@@ -387,4 +898,42 @@ mod tests {
         assert_eq!(loaded.entries.len(), 1);
         assert!(loaded.entries.contains_key("/test/Makefile"));
     }
+
+    #[test]
+    fn test_save_replaces_the_file_whole() {
+        let dir = TempDir::new().unwrap();
+        let cache_path = dir.path().join("maki").join(Cache::CACHE_FILENAME);
+        let makefile = dir.path().join("Makefile");
+        fs::write(&makefile, "build:\n\ttrue\n").unwrap();
+
+        let mut cache = Cache::new();
+        let targets = vec![Target::new("build".to_string(), None, makefile.clone(), 1)];
+        cache.set(&makefile, targets).unwrap();
+        cache.save_to(&cache_path).unwrap();
+        cache.save_to(&cache_path).unwrap();
+
+        let files: Vec<String> = fs::read_dir(cache_path.parent().unwrap())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(files, [Cache::CACHE_FILENAME]);
+        let mut loaded = Cache::load_from(&cache_path, CachePolicy::default()).unwrap();
+        assert!(loaded.get(&makefile).is_some());
+    }
+
+    #[test]
+    fn test_corrupt_cache_is_quarantined() {
+        let dir = TempDir::new().unwrap();
+        let cache_path = dir.path().join(Cache::CACHE_FILENAME);
+        // What two saves interleaving used to leave behind
+        fs::write(&cache_path, "{\"version\": 17, \"entries\": {}}\n}").unwrap();
+
+        let cache = Cache::load_from(&cache_path, CachePolicy::default()).unwrap();
+        assert!(cache.entries.is_empty());
+        assert!(!cache_path.exists());
+        assert_eq!(
+            fs::read_to_string(Cache::quarantine_path(&cache_path)).unwrap(),
+            "{\"version\": 17, \"entries\": {}}\n}"
+        );
+    }
 }
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+use crate::executor::CapturedRun;
+use crate::noop;
+
+/// The JSON object `maki run --json` prints once the target has finished
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunResult {
+    pub target: String,
+    /// The command that was run, e.g. `make deploy ENV=prod`
+    pub command: String,
+    /// Exit code, `None` if the run was killed by a signal
+    pub exit_code: Option<i32>,
+    pub success: bool,
+    /// make had nothing to do: the target was already up to date
+    pub noop: bool,
+    pub duration_ms: u64,
+    pub stdout: CapturedOutput,
+    pub stderr: CapturedOutput,
+}
+
+/// One of a run's output streams
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedOutput {
+    /// Size of the whole stream, before truncation
+    pub bytes: usize,
+    /// The output as text, invalid UTF-8 replaced; only the end of it when
+    /// `truncated`
+    pub text: String,
+    pub truncated: bool,
+}
+
+impl CapturedOutput {
+    /// Keep the last `max_bytes` of `output`, where errors usually are
+    pub fn new(output: &[u8], max_bytes: Option<usize>) -> Self {
+        let start = match max_bytes {
+            Some(max) if output.len() > max => {
+                // Don't start in the middle of a UTF-8 character
+                let mut start = output.len() - max;
+                while start < output.len() && (output[start] & 0xC0) == 0x80 {
+                    start += 1;
+                }
+                start
+            }
+            _ => 0,
+        };
+
+        Self {
+            bytes: output.len(),
+            text: String::from_utf8_lossy(&output[start..]).into_owned(),
+            truncated: start > 0,
+        }
+    }
+}
+
+impl RunResult {
+    pub fn new(
+        target: &str,
+        command: String,
+        run: &CapturedRun,
+        duration_ms: u64,
+        max_output: Option<usize>,
+    ) -> Self {
+        Self {
+            target: target.to_string(),
+            command,
+            exit_code: run.status.code(),
+            success: run.status.success(),
+            noop: run.status.success() && noop::is_noop(&run.stdout),
+            duration_ms,
+            stdout: CapturedOutput::new(&run.stdout, max_output),
+            stderr: CapturedOutput::new(&run.stderr, max_output),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::ExitStatus;
+
+    #[test]
+    fn test_captured_output_keeps_everything_by_default() {
+        let output = CapturedOutput::new(b"line 1\nline 2\n", None);
+
+        assert_eq!(output.bytes, 14);
+        assert_eq!(output.text, "line 1\nline 2\n");
+        assert!(!output.truncated);
+    }
+
+    #[test]
+    fn test_captured_output_keeps_the_end() {
+        let output = CapturedOutput::new(b"compiling\nerror: oops\n", Some(12));
+
+        assert_eq!(output.bytes, 22);
+        assert_eq!(output.text, "error: oops\n");
+        assert!(output.truncated);
+    }
+
+    #[test]
+    fn test_run_result_reports_noop() {
+        let run = |stdout: &[u8]| CapturedRun {
+            status: ExitStatus::default(),
+            stdout: stdout.to_vec(),
+            stderr: Vec::new(),
+        };
+
+        let result = RunResult::new(
+            "all",
+            "make all".to_string(),
+            &run(b"make: Nothing to be done for 'all'.\n"),
+            3,
+            None,
+        );
+        assert!(result.noop);
+
+        let result = RunResult::new("all", "make all".to_string(), &run(b"cc main.c\n"), 3, None);
+        assert!(!result.noop);
+    }
+
+    #[test]
+    fn test_captured_output_respects_utf8_boundaries() {
+        // "é" is two bytes; cutting after its first byte skips it whole
+        let output = CapturedOutput::new("aé!".as_bytes(), Some(2));
+
+        assert_eq!(output.text, "!");
+        assert!(output.truncated);
+    }
+}
@@ -0,0 +1,446 @@
+use clap::ValueEnum;
+use std::path::Path;
+
+use crate::graph::Graph;
+use crate::makefile;
+use crate::overview::category_of;
+use crate::target::{Provider, Target};
+
+/// Which CI service a pipeline is written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum CiFormat {
+    /// A GitHub Actions workflow (`.github/workflows/*.yml`)
+    #[default]
+    Github,
+    /// A GitLab CI pipeline (`.gitlab-ci.yml`)
+    Gitlab,
+}
+
+/// One CI job, running one target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    /// The job's key, made of the characters both services allow
+    pub id: String,
+    pub target: String,
+    pub description: Option<String>,
+    /// The shell command running the target, e.g. `make -C api test`
+    pub command: String,
+    /// The group of jobs it belongs to: its name's category, or else its name
+    pub stage: String,
+    /// The ids of the jobs it waits for
+    pub needs: Vec<String>,
+    /// Variables the target needs that have no default, and whether each
+    /// is secret
+    pub variables: Vec<(String, bool)>,
+}
+
+/// The jobs running `selected` (indices into `targets`), grouped by stage in
+/// an order where every stage comes after those it needs, and each job after
+/// the jobs it needs
+///
+/// A job needs another when its target needs the other's, directly or
+/// through targets that have no job of their own. `base` is where the
+/// pipeline runs make from.
+pub fn jobs(targets: &[Target], selected: &[usize], base: &Path) -> Vec<Job> {
+    let graph = Graph::new(targets).with_recursive_make();
+    let order: Vec<usize> = match graph.topological_order() {
+        Ok(order) => order.into_iter().filter(|i| selected.contains(i)).collect(),
+        Err(_) => selected.to_vec(),
+    };
+
+    let mut ids: Vec<(usize, String)> = Vec::new();
+    for &node in &order {
+        let mut id = job_id(&targets[node].name);
+        let taken = |id: &str| ids.iter().any(|(_, other)| other == id);
+        if taken(&id) {
+            let mut n = 2;
+            while taken(&format!("{}-{}", id, n)) {
+                n += 1;
+            }
+            id = format!("{}-{}", id, n);
+        }
+        ids.push((node, id));
+    }
+    let id_of = |node: usize| {
+        ids.iter()
+            .find(|(n, _)| *n == node)
+            .map(|(_, id)| id.clone())
+    };
+
+    let mut jobs: Vec<Job> = order
+        .iter()
+        .map(|&node| {
+            let target = &targets[node];
+            Job {
+                id: id_of(node).unwrap_or_default(),
+                target: target.name.clone(),
+                description: target.description.clone(),
+                command: make_command(target, base),
+                stage: category_of(&target.name)
+                    .map(job_id)
+                    .unwrap_or_else(|| job_id(&target.name)),
+                needs: selected_needs(&graph, node, selected)
+                    .into_iter()
+                    .filter_map(id_of)
+                    .collect(),
+                variables: target
+                    .required_vars
+                    .iter()
+                    .filter(|v| v.default.is_none())
+                    .map(|v| (v.name.clone(), v.secret.is_some()))
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let stages = stage_order(&jobs);
+    jobs.sort_by_key(|job| stages.iter().position(|s| *s == job.stage));
+    jobs
+}
+
+/// The selected targets `node` needs, looking through the ones that aren't
+fn selected_needs(graph: &Graph, node: usize, selected: &[usize]) -> Vec<usize> {
+    let mut found = Vec::new();
+    let mut seen = vec![node];
+    let mut next: Vec<usize> = graph.needs(node).map(|(n, _)| n).collect();
+    while let Some(need) = next.pop() {
+        if seen.contains(&need) {
+            continue;
+        }
+        seen.push(need);
+        if selected.contains(&need) {
+            found.push(need);
+        } else {
+            next.extend(graph.needs(need).map(|(n, _)| n));
+        }
+    }
+    found.sort();
+    found
+}
+
+/// The stages of `jobs`, each after the stages of the jobs its jobs need,
+/// otherwise in the order they first come up; stages that need each other
+/// keep that first order
+pub fn stage_order(jobs: &[Job]) -> Vec<String> {
+    let mut stages: Vec<String> = Vec::new();
+    for job in jobs {
+        if !stages.contains(&job.stage) {
+            stages.push(job.stage.clone());
+        }
+    }
+    let stage_of = |id: &str| jobs.iter().find(|j| j.id == id).map(|j| j.stage.as_str());
+    let needs = |stage: &str| -> Vec<&str> {
+        jobs.iter()
+            .filter(|j| j.stage == stage)
+            .flat_map(|j| j.needs.iter().filter_map(|id| stage_of(id)))
+            .filter(|s| *s != stage)
+            .collect()
+    };
+
+    let mut ordered: Vec<String> = Vec::new();
+    while ordered.len() < stages.len() {
+        let ready = stages.iter().find(|s| {
+            !ordered.contains(s) && needs(s).iter().all(|n| ordered.iter().any(|o| o == n))
+        });
+        // Stages needing each other: take the first one left
+        let next = ready
+            .or_else(|| stages.iter().find(|s| !ordered.contains(s)))
+            .cloned();
+        ordered.extend(next);
+    }
+    ordered
+}
+
+/// Top-level GitLab CI keys, which can't name a job
+const GITLAB_KEYWORDS: [&str; 10] = [
+    "after_script",
+    "before_script",
+    "cache",
+    "default",
+    "image",
+    "include",
+    "services",
+    "stages",
+    "variables",
+    "workflow",
+];
+
+/// A name as a job key: `docker/build` becomes `docker-build`, and one that
+/// would be a GitLab keyword becomes `make-image`
+fn job_id(name: &str) -> String {
+    let id: String = name
+        .trim_start_matches(['_', '.'])
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                true => c,
+                false => '-',
+            },
+        )
+        .collect();
+    if id.is_empty() {
+        return "job".to_string();
+    }
+    match GITLAB_KEYWORDS.contains(&id.as_str()) {
+        true => format!("make-{}", id),
+        false => id,
+    }
+}
+
+/// The make command running `target` from `base`, with `-C` for a Makefile
+/// in a subdirectory and `-f` for one with a name make doesn't look for
+fn make_command(target: &Target, base: &Path) -> String {
+    let file = target.file.strip_prefix(base).unwrap_or(&target.file);
+    let mut words = vec!["make".to_string()];
+    if let Some(dir) = file.parent()
+        && !dir.as_os_str().is_empty()
+    {
+        words.push("-C".to_string());
+        words.push(dir.display().to_string());
+    }
+    let name = file
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if !makefile::MAKEFILE_NAMES.contains(&name.as_str()) {
+        words.push("-f".to_string());
+        words.push(name);
+    }
+    words.push(target.name.clone());
+    words.join(" ")
+}
+
+/// The targets a pipeline can have a job for: Makefile targets that aren't
+/// patterns or special targets like `.PHONY`
+pub fn can_run(target: &Target) -> bool {
+    target.provider == Provider::Make && !target.is_pattern() && !target.name.starts_with('.')
+}
+
+/// A YAML scalar for `text`, quoted when it would otherwise read as
+/// something else
+fn scalar(text: &str) -> String {
+    let plain = !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || " _-./=$(){}".contains(c))
+        && !text.starts_with([' ', '-', '{'])
+        && !text.ends_with(' ');
+    match plain {
+        true => text.to_string(),
+        false => format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"")),
+    }
+}
+
+/// The pipeline running `jobs` as YAML for `format`
+pub fn render(jobs: &[Job], format: CiFormat) -> String {
+    let mut out = String::from(
+        "# Generated by maki export ci from the Makefile's targets; adjust the\n\
+         # triggers, images, and setup steps to the project.\n",
+    );
+    match format {
+        CiFormat::Github => render_github(jobs, &mut out),
+        CiFormat::Gitlab => render_gitlab(jobs, &mut out),
+    }
+    out
+}
+
+fn render_github(jobs: &[Job], out: &mut String) {
+    out.push_str("name: CI\n\non:\n  push:\n    branches: [main]\n  pull_request:\n\njobs:\n");
+    for (i, job) in jobs.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        if let Some(ref description) = job.description {
+            out.push_str(&format!("  # {}\n", description));
+        }
+        out.push_str(&format!("  {}:\n", job.id));
+        if job.id != job.target {
+            out.push_str(&format!("    name: {}\n", scalar(&job.target)));
+        }
+        if !job.needs.is_empty() {
+            out.push_str(&format!("    needs: [{}]\n", job.needs.join(", ")));
+        }
+        out.push_str("    runs-on: ubuntu-latest\n");
+        // make reads variables it isn't given from the environment
+        if !job.variables.is_empty() {
+            out.push_str("    env:\n");
+            for (name, secret) in &job.variables {
+                let from = if *secret { "secrets" } else { "vars" };
+                out.push_str(&format!("      {}: ${{{{ {}.{} }}}}\n", name, from, name));
+            }
+        }
+        out.push_str("    steps:\n");
+        out.push_str("      - uses: actions/checkout@v4\n");
+        out.push_str(&format!("      - run: {}\n", scalar(&job.command)));
+    }
+}
+
+fn render_gitlab(jobs: &[Job], out: &mut String) {
+    out.push_str("stages:\n");
+    for stage in stage_order(jobs) {
+        out.push_str(&format!("  - {}\n", stage));
+    }
+    for job in jobs {
+        out.push('\n');
+        if let Some(ref description) = job.description {
+            out.push_str(&format!("# {}\n", description));
+        }
+        // GitLab puts the project's CI/CD variables in the environment
+        if !job.variables.is_empty() {
+            let names: Vec<&str> = job.variables.iter().map(|(n, _)| n.as_str()).collect();
+            out.push_str(&format!(
+                "# Needs {} from the project's CI/CD variables\n",
+                names.join(", ")
+            ));
+        }
+        out.push_str(&format!("{}:\n", job.id));
+        out.push_str(&format!("  stage: {}\n", job.stage));
+        // Without needs, a job would wait for every earlier stage
+        out.push_str(&format!("  needs: [{}]\n", job.needs.join(", ")));
+        out.push_str("  script:\n");
+        out.push_str(&format!("    - {}\n", scalar(&job.command)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::target::RequiredVar;
+    use std::path::PathBuf;
+
+    fn project() -> Vec<Target> {
+        let mut deploy = Target::with_deps("deploy", &["docker-push"]);
+        deploy.description = Some("Ship it".to_string());
+        deploy.required_vars = vec![RequiredVar {
+            name: "ENV".to_string(),
+            hint: None,
+            default: None,
+            secret: None,
+        }];
+        vec![
+            deploy,
+            Target::with_deps("docker-push", &["docker-build"]),
+            Target::with_deps("docker-build", &["gen"]),
+            Target::with_deps("gen", &[]),
+            Target::with_deps("test", &["gen"]),
+            Target::with_deps("lint", &[]),
+        ]
+    }
+
+    fn ids(jobs: &[Job]) -> Vec<&str> {
+        jobs.iter().map(|j| j.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_jobs_follow_their_needs() {
+        let targets = project();
+        // Every target but gen, which its dependents run themselves
+        let jobs = jobs(&targets, &[0, 1, 2, 4, 5], Path::new("/app"));
+
+        assert_eq!(
+            ids(&jobs),
+            vec!["docker-build", "docker-push", "deploy", "test", "lint"]
+        );
+        assert_eq!(stage_order(&jobs), vec!["docker", "deploy", "test", "lint"]);
+        let deploy = &jobs[2];
+        assert_eq!(deploy.needs, vec!["docker-push"]);
+        assert_eq!(deploy.command, "make deploy");
+        assert_eq!(deploy.variables, vec![("ENV".to_string(), false)]);
+        assert!(jobs[3].needs.is_empty());
+    }
+
+    #[test]
+    fn test_needs_look_through_unselected_targets() {
+        let targets = project();
+        let jobs = jobs(&targets, &[0, 2], Path::new("/app"));
+        assert_eq!(ids(&jobs), vec!["docker-build", "deploy"]);
+        assert_eq!(jobs[1].needs, vec!["docker-build"]);
+    }
+
+    #[test]
+    fn test_make_commands_and_ids() {
+        let nested = Target::new(
+            "db:migrate".to_string(),
+            None,
+            PathBuf::from("/app/services/api/tasks.mk"),
+            1,
+        );
+        assert_eq!(
+            make_command(&nested, Path::new("/app")),
+            "make -C services/api -f tasks.mk db:migrate"
+        );
+        assert_eq!(job_id("db:migrate"), "db-migrate");
+        assert_eq!(job_id("_ci/setup"), "ci-setup");
+        assert_eq!(job_id("image"), "make-image");
+    }
+
+    #[test]
+    fn test_render_github() {
+        let targets = project();
+        let jobs = jobs(&targets, &[1, 0], Path::new("/app"));
+        let yaml = render(&jobs, CiFormat::Github);
+        assert!(
+            yaml.ends_with(
+                "\
+jobs:
+  docker-push:
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v4
+      - run: make docker-push
+
+  # Ship it
+  deploy:
+    needs: [docker-push]
+    runs-on: ubuntu-latest
+    env:
+      ENV: ${{ vars.ENV }}
+    steps:
+      - uses: actions/checkout@v4
+      - run: make deploy
+"
+            ),
+            "{}",
+            yaml
+        );
+    }
+
+    #[test]
+    fn test_render_gitlab() {
+        let targets = project();
+        let jobs = jobs(&targets, &[1, 0, 5], Path::new("/app"));
+        let yaml = render(&jobs, CiFormat::Gitlab);
+        assert!(
+            yaml.ends_with(
+                "\
+stages:
+  - docker
+  - deploy
+  - lint
+
+docker-push:
+  stage: docker
+  needs: []
+  script:
+    - make docker-push
+
+# Ship it
+# Needs ENV from the project's CI/CD variables
+deploy:
+  stage: deploy
+  needs: [docker-push]
+  script:
+    - make deploy
+
+lint:
+  stage: lint
+  needs: []
+  script:
+    - make lint
+"
+            ),
+            "{}",
+            yaml
+        );
+    }
+}
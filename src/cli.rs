@@ -1,10 +1,20 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
+use crate::bundle::Prefer;
+use crate::ci::CiFormat;
+use crate::env;
+use crate::hook::Shell;
+use crate::multirun::OutputMode;
+use crate::rank::RankMode;
+use crate::repeat::RepeatMode;
+use crate::schema::SchemaKind;
+use crate::target::Target;
+
 /// A cross-platform fuzzy Makefile task finder
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(name = "maki")]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, disable_version_flag = true)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
@@ -13,6 +23,20 @@ pub struct Cli {
     #[arg(short = 'f', long = "file", global = true)]
     pub file: Option<PathBuf>,
 
+    /// Search DIR for included makefiles; passed on to nested $(MAKE) calls
+    #[arg(short = 'I', long = "include-dir", value_name = "DIR", global = true)]
+    pub include_dirs: Vec<PathBuf>,
+
+    /// Look for Makefiles called NAME, e.g. tasks.mk, instead of the names
+    /// in `makefile_names`; repeat it for more names
+    #[arg(
+        long = "makefile-name",
+        value_name = "NAME",
+        value_parser = crate::makefile::parse_makefile_name,
+        global = true
+    )]
+    pub makefile_names: Vec<String>,
+
     /// Include private targets (those starting with _)
     #[arg(long = "all", global = true)]
     pub all: bool,
@@ -21,6 +45,19 @@ pub struct Cli {
     #[arg(long = "patterns", global = true)]
     pub patterns: bool,
 
+    /// Refuse to run targets whose recipe uses sudo (for CI and scripts)
+    #[arg(long = "no-sudo", global = true)]
+    pub no_sudo: bool,
+
+    /// Don't post run summaries to the config's webhooks
+    #[arg(long = "no-webhooks", global = true)]
+    pub no_webhooks: bool,
+
+    /// Include every rule of generated Makefiles (CMake, automake), not just
+    /// their entry points
+    #[arg(long = "generated", global = true)]
+    pub generated: bool,
+
     /// Output results as JSON
     #[arg(long = "json", global = true)]
     pub json: bool,
@@ -29,46 +66,764 @@ pub struct Cli {
     #[arg(long = "no-ui", global = true)]
     pub no_ui: bool,
 
+    /// Show each target's prerequisites in list output
+    #[arg(long = "with-deps", global = true)]
+    pub with_deps: bool,
+
     /// Scan subdirectories for Makefiles
     #[arg(long = "recursive", short = 'r', global = true)]
     pub recursive: bool,
 
+    /// Scan recursively without asking, even from the home directory or /,
+    /// and run targets at times their guards warn about
+    #[arg(long = "force", global = true)]
+    pub force: bool,
+
     /// Print command without executing
     #[arg(long = "dry-run", global = true)]
     pub dry_run: bool,
 
-    /// Set the working directory
+    /// In dry runs and the picker's preview, also list the commands make
+    /// doesn't echo (`@` or `.SILENT`), marked as silent
+    #[arg(long = "show-silent", global = true)]
+    pub show_silent: bool,
+
+    /// Set the working directory; repeat to gather targets from several projects
     #[arg(long = "cwd", global = true)]
-    pub cwd: Option<PathBuf>,
+    pub cwd: Vec<PathBuf>,
 
     /// Skip the cache and re-parse Makefiles
     #[arg(long = "no-cache", global = true)]
     pub no_cache: bool,
+
+    /// Run the only target without confirmation when there is exactly one
+    #[arg(long = "auto", global = true)]
+    pub auto: bool,
+
+    /// Use each variable's default (from maki.toml or the Makefile) without prompting
+    #[arg(long = "defaults", global = true)]
+    pub defaults: bool,
+
+    /// Run targets in a new tmux/zellij pane, keeping the picker open
+    #[arg(long = "split", global = true)]
+    pub split: bool,
+
+    /// Set an environment variable for the run (repeatable)
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = crate::env::parse_var, global = true)]
+    pub vars: Vec<(String, String)>,
+
+    /// Load environment variables for the run from a dotenv-style file
+    #[arg(long = "env-file", global = true)]
+    pub env_file: Option<PathBuf>,
+
+    /// Load the `.env` and `.envrc` next to the target's Makefile for the run,
+    /// as direnv would on entering its directory
+    #[arg(long = "auto-env", global = true)]
+    pub auto_env: bool,
+
+    /// Print extra detail, such as how the run's environment differs from the shell
+    #[arg(long = "verbose", short = 'v', global = true)]
+    pub verbose: bool,
+
+    /// Print how long each startup phase took and how often the cache was
+    /// hit, on stderr before a target runs (with --json, in its output)
+    #[arg(long = "timings", global = true)]
+    pub timings: bool,
+
+    /// Save each run's output to this directory, as raw and plain-text logs
+    #[arg(long = "log-dir", value_name = "DIR", global = true)]
+    pub log_dir: Option<PathBuf>,
+
+    /// Show a status line while a running target prints nothing for SECS
+    /// seconds (30 if not given)
+    #[arg(
+        long = "heartbeat",
+        value_name = "SECS",
+        num_args = 0..=1,
+        default_missing_value = "30",
+        value_parser = clap::value_parser!(u64).range(1..),
+        global = true
+    )]
+    pub heartbeat: Option<u64>,
+
+    /// When make has nothing to do, say so in a status line of its own
+    /// instead of make's "Nothing to be done" message
+    #[arg(long = "quiet-noop", global = true)]
+    pub quiet_noop: bool,
+
+    /// Collect the file:line:col errors and warnings a run prints, list them
+    /// when it ends, and keep them for `maki errors`
+    #[arg(long = "errors", global = true)]
+    pub errors: bool,
+
+    /// Point TMPDIR and MAKI_SCRATCH at a fresh directory for the run,
+    /// removed when it ends
+    #[arg(long = "scratch", global = true)]
+    pub scratch: bool,
+
+    /// Leave the --scratch directory behind after the run
+    #[arg(long = "keep-scratch", requires = "scratch", global = true)]
+    pub keep_scratch: bool,
+
+    /// When the output passes through maki (--log-dir, --heartbeat,
+    /// --errors, ...), still run targets on a terminal, so they keep their
+    /// colors and progress bars
+    #[arg(long = "pty", global = true)]
+    pub pty: bool,
+
+    /// Run targets on a terminal of their own and record it, colors and
+    /// all, as an asciinema cast for `maki replay`
+    #[arg(
+        long = "record",
+        conflicts_with_all = ["heartbeat", "quiet_noop", "errors"],
+        global = true
+    )]
+    pub record: bool,
+
+    /// Open the picked target in $EDITOR instead of running it
+    #[arg(long = "edit", global = true, conflicts_with_all = ["show", "copy", "queue"])]
+    pub edit: bool,
+
+    /// Print details for the picked target instead of running it
+    #[arg(long = "show", global = true, conflicts_with_all = ["copy", "queue"])]
+    pub show: bool,
+
+    /// Copy the picked target's command to the clipboard instead of running it
+    #[arg(long = "copy", global = true, conflicts_with = "queue")]
+    pub copy: bool,
+
+    /// Add the picked targets to the run queue instead of running them
+    #[arg(long = "queue", global = true)]
+    pub queue: bool,
+
+    /// Picker to use: the full-screen fuzzy finder, or a plain numbered list
+    /// that works with screen readers
+    #[arg(long = "picker", value_enum, default_value_t = PickerKind::Skim, global = true)]
+    pub picker: PickerKind,
+
+    /// How to order targets: alphabetically, or as they appear in the Makefiles
+    #[arg(long = "sort", value_enum, default_value_t = SortOrder::Name, global = true)]
+    pub sort: SortOrder,
+
+    /// How the picker ranks matches: by fuzzy score, by past runs, or by
+    /// score with past runs breaking ties (defaults to `[picker] rank`)
+    #[arg(long = "rank", value_enum, value_name = "MODE", global = true)]
+    pub rank: Option<RankMode>,
+
+    /// When to use colors; `auto` turns them off for NO_COLOR, CI, and pipes
+    #[arg(long = "color", value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto, global = true)]
+    pub color: ColorChoice,
+
+    /// Print the help of every command, with examples
+    #[arg(long = "help-all")]
+    pub help_all: bool,
+
+    /// Print version; with --json, the build details of `maki version`
+    #[arg(short = 'V', long = "version")]
+    pub version: bool,
+}
+
+/// The `--sort` setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum SortOrder {
+    /// Alphabetically by name
+    #[default]
+    Name,
+    /// In declaration order: Makefile by Makefile, then Procfile and compose entries
+    FileOrder,
+}
+
+impl SortOrder {
+    /// Order targets that are in discovery order
+    pub fn apply(self, targets: &mut [Target]) {
+        match self {
+            // Same-named targets from different roots stay next to each other
+            SortOrder::Name => {
+                targets.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.root.cmp(&b.root)))
+            }
+            // Discovery follows the files line by line already
+            SortOrder::FileOrder => {}
+        }
+    }
+}
+
+/// The `--color` setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal, unless NO_COLOR or CI is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to force colors on or off, given the values of `NO_COLOR` and
+    /// `CI`; `None` leaves it to terminal detection
+    pub fn resolve(self, no_color: Option<&str>, ci: Option<&str>) -> Option<bool> {
+        match self {
+            ColorChoice::Always => Some(true),
+            ColorChoice::Never => Some(false),
+            ColorChoice::Auto => {
+                // NO_COLOR counts when set to anything but an empty string
+                let no_color = no_color.is_some_and(|v| !v.is_empty());
+                let ci = ci.is_some_and(|v| !matches!(v, "" | "0" | "false"));
+                (no_color || ci).then_some(false)
+            }
+        }
+    }
+}
+
+/// Interactive picker implementations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum PickerKind {
+    /// Full-screen fuzzy finder with a Makefile preview
+    #[default]
+    Skim,
+    /// Numbered list with a line-based filter prompt; no alternate screen or cursor movement
+    Plain,
+}
+
+/// What to do with the target chosen in the picker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickAction {
+    Run,
+    Edit,
+    Show,
+    Copy,
+    Queue,
 }
 
-#[derive(Subcommand, Debug)]
+#[derive(Subcommand, Debug, Clone)]
 pub enum Commands {
     /// Interactive fuzzy search to pick a target
     Pick,
 
     /// List all available targets
-    List,
+    #[command(visible_alias = "targets")]
+    List {
+        /// Group namespaced targets (`docker/build`, `docs.serve`) into a tree
+        #[arg(long = "tree")]
+        tree: bool,
+        /// List the targets of a remote git repository, fetching only its
+        /// Makefiles instead of cloning it
+        #[arg(long = "repo", value_name = "URL")]
+        repo: Option<String>,
+        /// With --repo, the branch or tag to read (default: the default branch)
+        #[arg(long = "ref", value_name = "REF", requires = "repo")]
+        git_ref: Option<String>,
+        /// Only list the targets with this `@tags` tag
+        #[arg(long = "tag", value_name = "TAG")]
+        tag: Option<String>,
+        /// Only list the targets `.PHONY` declares, the Makefile's commands
+        /// rather than the files it makes
+        #[arg(long = "phony-only")]
+        phony_only: bool,
+        /// Number the targets, so `maki 3` or `maki run @3` runs the third
+        /// until the next numbered list
+        #[arg(long = "numbered", conflicts_with_all = ["tree", "repo"])]
+        numbered: bool,
+    },
 
-    /// Run a specific target directly
+    /// Run a specific target directly; with more than one, run them one
+    /// after another (or, with --parallel, as a graph with their prerequisites)
     Run {
         /// The target name to run
         target: String,
+
+        /// More targets to run after it, and NAME=VALUE variables passed to
+        /// make for all of them (e.g. `maki run bump V=patch`)
+        #[arg(value_name = "MORE")]
+        more: Vec<String>,
+
+        /// Run the targets and their shared prerequisites as a graph, each as
+        /// soon as what it needs has succeeded, instead of one after another
+        #[arg(long = "parallel")]
+        parallel: bool,
+
+        /// How to show the output of several targets: a live dashboard on a
+        /// terminal, or each line prefixed with its target's name
+        #[arg(long = "output", value_enum, default_value_t, value_name = "MODE")]
+        output: OutputMode,
+
+        #[command(flatten)]
+        repeat: RepeatArgs,
+
+        /// With --json, keep only the last BYTES of stdout and of stderr
+        #[arg(long = "max-output", value_name = "BYTES")]
+        max_output: Option<usize>,
+
+        /// Only check whether the target is up to date (make -q): exits 0 if
+        /// it is, 1 if it isn't, without running anything
+        #[arg(long = "question", conflicts_with_all = ["touch", "repeat"])]
+        question: bool,
+
+        /// Mark the target up to date without running its recipe (make -t)
+        #[arg(long = "touch", conflicts_with = "repeat")]
+        touch: bool,
+
+        /// Run it in the background, detached from the terminal, for
+        /// long-lived targets like dev servers (see maki ps)
+        #[arg(
+            long = "detach",
+            conflicts_with_all = ["repeat", "max_output", "question", "touch"]
+        )]
+        detach: bool,
+
+        /// Start it in the background as --detach does, and return once the
+        /// probe in its `@ready` comment answers, e.g. before running tests
+        /// against a server
+        #[arg(
+            long = "wait-ready",
+            conflicts_with_all = ["repeat", "max_output", "question", "touch"]
+        )]
+        wait_ready: bool,
+
+        /// How long --wait-ready waits before giving up and stopping the target
+        #[arg(
+            long = "ready-timeout",
+            value_name = "SECS",
+            default_value_t = 60,
+            value_parser = clap::value_parser!(u64).range(1..),
+            requires = "wait_ready"
+        )]
+        ready_timeout: u64,
+    },
+
+    /// List the targets running in the background (maki run --detach)
+    Ps,
+
+    /// Print the latest output of a target running in the background, then
+    /// follow it until the target exits (Ctrl-C stops following, not the target)
+    Attach {
+        /// The session's id, or its target's name
+        id: String,
+        /// How many lines of earlier output to print
+        #[arg(long = "lines", short = 'n', default_value_t = 20)]
+        lines: usize,
+        /// Only print the earlier output
+        #[arg(long = "no-follow")]
+        no_follow: bool,
+    },
+
+    /// Stop a target running in the background and forget it
+    Stop {
+        /// The session's id, or its target's name
+        #[arg(required_unless_present = "all")]
+        id: Option<String>,
+        /// Stop every session
+        #[arg(long = "all", conflicts_with = "id")]
+        all: bool,
+    },
+
+    /// Show details for a target (prerequisites, variables, search paths)
+    Show {
+        /// The target name to show
+        target: String,
+    },
+
+    /// Run the Makefile's own `help` target, which maki leaves out of its
+    /// lists when it only prints the `##` comments maki shows already
+    HelpTarget,
+
+    /// Attach a personal note to a target, shown in the list, preview, and
+    /// detail view; without a note, print the current one
+    Note {
+        /// The target to note
+        target: String,
+        /// The note, e.g. "needs VPN connected"
+        note: Option<String>,
+        /// Remove the target's note
+        #[arg(long = "remove", conflicts_with = "note")]
+        remove: bool,
+    },
+
+    /// Queue targets to run later, and run the queue
+    Queue {
+        #[command(subcommand)]
+        action: QueueCommand,
+    },
+
+    /// Run the targets listed in a file, one `target NAME=VALUE...` per
+    /// line, in order, and sum up how each went
+    Batch {
+        /// The batch file, or `-` to read it from stdin
+        #[arg(value_name = "FILE")]
+        path: PathBuf,
+        /// Go on with the next lines after one fails, as make -k does
+        #[arg(long = "keep-going", short = 'k')]
+        keep_going: bool,
+    },
+
+    /// Turn the targets into configuration for other tools
+    Export {
+        #[command(subcommand)]
+        action: ExportCommand,
+    },
+
+    /// Share how often you run each target with the team, through a usage
+    /// file in the repo that the picker ranks by
+    Usage {
+        #[command(subcommand)]
+        action: UsageCommand,
+    },
+
+    /// Suggest targets relevant to the files changed in git, in a picker
+    Suggest,
+
+    /// Summarise the project: Makefiles, providers, categories, and top-level targets
+    Overview,
+
+    /// Compare the targets with those at another git ref: which were added
+    /// or removed, and whose description or recipe changed
+    Diff {
+        /// The branch, tag, or commit to compare with
+        #[arg(long = "ref", value_name = "REF", default_value = "HEAD")]
+        git_ref: String,
+    },
+
+    /// Check the Makefiles for targets missing from `.PHONY`, `##`
+    /// descriptions without one space after them, and recipes indented with
+    /// spaces; exits nonzero when there are any
+    Validate {
+        /// Show the changes that fix them, and write them once confirmed
+        #[arg(long = "fix")]
+        fix: bool,
+        /// Write the fixes without asking for confirmation
+        #[arg(long = "yes", short = 'y', requires = "fix")]
+        yes: bool,
+    },
+
+    /// Print the targets matching a filter as JSON, e.g. `name=docker/*
+    /// has_required_vars=true` or `{"provider": "make"}`
+    Query {
+        /// `key=value` conditions (name, category, provider,
+        /// has_required_vars, file), or one JSON document with those keys
+        filter: Vec<String>,
+    },
+
+    /// List the providers found (Makefile, Procfile, docker compose), the
+    /// targets each contributed, and how long each took to parse
+    Providers,
+
+    /// Show recent runs
+    History {
+        /// How many runs to show
+        #[arg(long = "limit", short = 'n', default_value_t = 20)]
+        limit: usize,
+    },
+
+    /// Open an error from the last run with --errors in $EDITOR, the first
+    /// one unless given its number
+    Errors {
+        /// Which error to open, counting from 1 as --list numbers them
+        #[arg(value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        number: Option<usize>,
+
+        /// List the errors instead of opening one
+        #[arg(long = "list", short = 'l')]
+        list: bool,
+    },
+
+    /// Play back a run recorded with --record, the latest unless given its
+    /// run ID
+    Replay {
+        /// The run ID --record printed, e.g. 20260101-120000-build, or the
+        /// path of a cast file
+        run: Option<String>,
+
+        /// Play it this many times as fast
+        #[arg(long = "speed", default_value_t = 1.0, value_parser = crate::record::parse_speed)]
+        speed: f64,
+    },
+
+    /// Print the JSON Schema for one of maki's JSON formats
+    Schema {
+        /// Which format to describe
+        #[arg(value_enum)]
+        kind: SchemaKind,
+    },
+
+    /// Inspect or clear the parse cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
+
+    /// Show the effective settings, or share them: export `maki.toml` as a
+    /// bundle, or import one
+    #[command(args_conflicts_with_subcommands = true)]
+    Config {
+        /// Show where each setting comes from: the defaults, `maki.toml`,
+        /// `.maki.local.toml`, a `MAKI_*` environment variable, or a flag
+        #[arg(long = "origin")]
+        origin: bool,
+
+        #[command(subcommand)]
+        action: Option<ConfigCommand>,
+    },
+
+    /// Set up maki for this project: a few questions, written to `maki.toml`,
+    /// then the picker
+    Setup,
+
+    /// Print a shell hook adding an `mk` alias for `maki run` with cached
+    /// target completion
+    Hook {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    /// Completion candidates for the shell hook: one target per line, or
+    /// with a target its variables' `NAME=VALUE` assignments
+    #[command(name = "__complete", hide = true)]
+    Complete { target: Option<String> },
+
+    /// Open the Makefile in $EDITOR, picking one when several are found
+    Open {
+        /// Show it in the file manager instead
+        #[arg(long = "reveal")]
+        reveal: bool,
+    },
+
+    /// Rewrite targets across the discovered Makefiles
+    Refactor {
+        #[command(subcommand)]
+        action: RefactorCommand,
+    },
+
+    /// Print the man page (maki.1), e.g. for packaging
+    Man,
+
+    /// Show the version with build details (commit, build date, platform,
+    /// providers, cache path), e.g. for bug reports
+    Version,
+
+    /// An alias from the config's `[alias]` table, with its arguments
+    #[command(external_subcommand)]
+    Alias(Vec<String>),
+}
+
+/// Options for running a target repeatedly, e.g. to flush out flaky tests
+#[derive(Args, Debug, Clone, Default)]
+pub struct RepeatArgs {
+    /// Run the target again and again until it fails
+    #[arg(long = "until-failure", group = "repeat")]
+    pub until_failure: bool,
+
+    /// Run the target again and again until it succeeds
+    #[arg(long = "until-success", group = "repeat")]
+    pub until_success: bool,
+
+    /// Stop repeating after N runs
+    #[arg(
+        long = "max-iterations",
+        value_name = "N",
+        requires = "repeat",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub max_iterations: Option<u32>,
+}
+
+impl RepeatArgs {
+    /// The repeat mode, if one was asked for
+    pub fn mode(&self) -> Option<RepeatMode> {
+        if self.until_failure {
+            Some(RepeatMode::UntilFailure)
+        } else if self.until_success {
+            Some(RepeatMode::UntilSuccess)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum CacheCommand {
+    /// Show cache size, eviction totals, and limits
+    Stats,
+
+    /// Delete the cache file
+    Clear,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum QueueCommand {
+    /// Add targets to the end of the queue
+    Add {
+        /// The targets to queue, and NAME=VALUE variables to run them with
+        #[arg(required = true)]
+        words: Vec<String>,
+    },
+
+    /// Show the queued targets, first to run first
+    List,
+
+    /// Take a target off the queue
+    Remove {
+        /// The queued target
+        target: String,
+    },
+
+    /// Empty the queue
+    Clear,
+
+    /// Run the queued targets in order, taking each off once it succeeds
+    Run,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ExportCommand {
+    /// Print a CI pipeline with one job per target, ordered by category and
+    /// by what each target needs
+    Ci {
+        /// The CI service to write the pipeline for
+        #[arg(long = "format", value_enum, default_value_t)]
+        format: CiFormat,
+        /// The targets to make jobs of (default: the documented ones, or
+        /// all when none are)
+        targets: Vec<String>,
+    },
+    /// Print a starter Makefile with the build, test, run, and lint targets
+    /// inferred from the project's Cargo.toml, package.json, go.mod, or
+    /// pyproject.toml
+    Makefile {
+        /// Write it to ./Makefile, unless the project has a Makefile already
+        #[arg(long = "write")]
+        write: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum UsageCommand {
+    /// Write your run counts of each target to `.maki/usage.json`, under a
+    /// random ID in place of your name, replacing those you exported before
+    Export,
+
+    /// Show the team's run counts from `.maki/usage.json`, most run first
+    Show,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommand {
+    /// Bundle the project settings into a single file (stdout by default)
+    Export {
+        /// Where to write the bundle
+        file: Option<PathBuf>,
+    },
+
+    /// Merge a settings bundle into the project's `maki.toml`
+    Import {
+        /// The bundle to import
+        file: PathBuf,
+
+        /// Which value to keep when a setting differs
+        #[arg(long = "prefer", value_enum, default_value_t = Prefer::Local)]
+        prefer: Prefer,
+
+        /// Write the changes without asking
+        #[arg(long = "yes", short = 'y')]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum RefactorCommand {
+    /// Rename a target, updating its prerequisite references and `$(MAKE)` calls
+    Rename {
+        /// The current target name
+        old: String,
+        /// The new target name
+        new: String,
+        /// Apply the changes without asking for confirmation
+        #[arg(long = "yes", short = 'y')]
+        yes: bool,
     },
 }
 
 impl Cli {
+    /// The `NAME=VALUE` variables given after `maki run`'s target
+    pub fn run_vars(&self) -> Vec<(String, String)> {
+        match self.command {
+            Some(Commands::Run { ref more, .. }) => {
+                more.iter().filter_map(|w| env::parse_var(w).ok()).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Check the options of `maki run` that need a single target, or several;
+    /// the words after the target may be either, so clap can't
+    pub fn check_run(&self) -> Result<(), String> {
+        let Some(Commands::Run {
+            parallel,
+            ref repeat,
+            max_output,
+            question,
+            touch,
+            detach,
+            wait_ready,
+            ..
+        }) = self.command
+        else {
+            return Ok(());
+        };
+        let several = !self.run_targets().is_empty();
+        let single = question
+            || touch
+            || detach
+            || wait_ready
+            || repeat.mode().is_some()
+            || max_output.is_some();
+        if single && several {
+            return Err(
+                "--question, --touch, --detach, --wait-ready, --max-output, and repeating only work when running a single target"
+                    .to_string(),
+            );
+        }
+        if parallel && !several {
+            return Err("--parallel needs more than one target".to_string());
+        }
+        Ok(())
+    }
+
+    /// The targets given after `maki run`'s first one, without its variables
+    pub fn run_targets(&self) -> Vec<&str> {
+        match self.command {
+            Some(Commands::Run { ref more, .. }) => more
+                .iter()
+                .filter(|w| env::parse_var(w).is_err())
+                .map(String::as_str)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
     /// Get the working directory, defaulting to current directory
+    ///
+    /// With several --cwd roots this is the first one.
     pub fn working_dir(&self) -> PathBuf {
         self.cwd
-            .clone()
+            .first()
+            .cloned()
             .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
     }
 
+    /// The action for the picked target, from --edit/--show/--copy/--queue
+    pub fn pick_action(&self) -> PickAction {
+        if self.queue {
+            PickAction::Queue
+        } else if self.edit {
+            PickAction::Edit
+        } else if self.show {
+            PickAction::Show
+        } else if self.copy {
+            PickAction::Copy
+        } else {
+            PickAction::Run
+        }
+    }
+
     /// Get the Makefile path if explicitly specified
     #[allow(dead_code)]
     pub fn makefile_path(&self) -> Option<PathBuf> {
@@ -96,38 +851,705 @@ mod tests {
     #[test]
     fn test_parse_list_command() {
         let cli = Cli::parse_from(["maki", "list"]);
-        assert!(matches!(cli.command, Some(Commands::List)));
+        assert!(matches!(
+            cli.command,
+            Some(Commands::List {
+                tree: false,
+                repo: None,
+                ..
+            })
+        ));
+
+        let cli = Cli::parse_from(["maki", "targets", "--tree"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::List { tree: true, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_list_repo() {
+        let cli = Cli::parse_from([
+            "maki",
+            "list",
+            "--repo",
+            "https://github.com/org/project",
+            "--ref",
+            "v2",
+        ]);
+        let Some(Commands::List { repo, git_ref, .. }) = cli.command else {
+            panic!("Expected List command");
+        };
+        assert_eq!(repo.as_deref(), Some("https://github.com/org/project"));
+        assert_eq!(git_ref.as_deref(), Some("v2"));
+
+        assert!(Cli::try_parse_from(["maki", "list", "--ref", "v2"]).is_err());
     }
 
     #[test]
     fn test_parse_run_command() {
         let cli = Cli::parse_from(["maki", "run", "build"]);
-        if let Some(Commands::Run { target }) = cli.command {
+        if let Some(Commands::Run { target, .. }) = cli.command {
             assert_eq!(target, "build");
         } else {
             panic!("Expected Run command");
         }
     }
 
+    #[test]
+    fn test_parse_run_several_targets() {
+        let cli = Cli::parse_from([
+            "maki",
+            "run",
+            "lint",
+            "test",
+            "--parallel",
+            "--output",
+            "prefixed",
+        ]);
+        assert!(cli.check_run().is_ok());
+        let Some(Commands::Run {
+            target,
+            more,
+            parallel,
+            output,
+            ..
+        }) = cli.command
+        else {
+            panic!("Expected Run command");
+        };
+        assert_eq!(target, "lint");
+        assert_eq!(more, vec!["test"]);
+        assert!(parallel);
+        assert_eq!(output, OutputMode::Prefixed);
+
+        let check = |args: &[&str]| Cli::parse_from(args).check_run();
+        assert!(check(&["maki", "run", "lint", "--parallel"]).is_err());
+        assert!(check(&["maki", "run", "a", "b", "--question"]).is_err());
+        assert!(check(&["maki", "run", "a", "b", "--until-failure"]).is_err());
+        assert!(check(&["maki", "run", "a", "V=1", "--question"]).is_ok());
+    }
+
+    #[test]
+    fn test_parse_run_variables() {
+        let cli = Cli::parse_from(["maki", "run", "bump", "V=patch", "docs", "MSG=a b"]);
+        assert_eq!(
+            cli.run_vars(),
+            vec![
+                ("V".to_string(), "patch".to_string()),
+                ("MSG".to_string(), "a b".to_string()),
+            ]
+        );
+        assert_eq!(cli.run_targets(), vec!["docs"]);
+    }
+
+    #[test]
+    fn test_parse_run_detach() {
+        let cli = Cli::parse_from(["maki", "run", "serve", "--detach"]);
+        let Some(Commands::Run { detach, .. }) = cli.command else {
+            panic!("Expected Run command");
+        };
+        assert!(detach);
+
+        assert!(
+            Cli::parse_from(["maki", "run", "a", "b", "--detach"])
+                .check_run()
+                .is_err()
+        );
+        assert!(Cli::try_parse_from(["maki", "run", "a", "--detach", "--touch"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_run_wait_ready() {
+        let cli = Cli::parse_from(["maki", "run", "serve", "--wait-ready"]);
+        let Some(Commands::Run {
+            wait_ready,
+            ready_timeout,
+            ..
+        }) = cli.command
+        else {
+            panic!("Expected Run command");
+        };
+        assert!(wait_ready);
+        assert_eq!(ready_timeout, 60);
+
+        let cli = Cli::parse_from([
+            "maki",
+            "run",
+            "serve",
+            "--wait-ready",
+            "--ready-timeout",
+            "5",
+        ]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Run {
+                ready_timeout: 5,
+                ..
+            })
+        ));
+        assert!(Cli::try_parse_from(["maki", "run", "serve", "--ready-timeout", "5"]).is_err());
+        assert!(
+            Cli::parse_from(["maki", "run", "a", "b", "--wait-ready"])
+                .check_run()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_sessions() {
+        let cli = Cli::parse_from(["maki", "attach", "serve", "-n", "5", "--no-follow"]);
+        let Some(Commands::Attach {
+            id,
+            lines,
+            no_follow,
+        }) = cli.command
+        else {
+            panic!("Expected Attach command");
+        };
+        assert_eq!(id, "serve");
+        assert_eq!(lines, 5);
+        assert!(no_follow);
+
+        let cli = Cli::parse_from(["maki", "stop", "--all"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Stop {
+                id: None,
+                all: true
+            })
+        ));
+        assert!(Cli::try_parse_from(["maki", "stop"]).is_err());
+        assert!(Cli::try_parse_from(["maki", "stop", "1", "--all"]).is_err());
+        assert!(matches!(
+            Cli::parse_from(["maki", "ps"]).command,
+            Some(Commands::Ps)
+        ));
+    }
+
+    #[test]
+    fn test_parse_alias() {
+        let cli = Cli::parse_from(["maki", "--dry-run", "deploy-to", "prod"]);
+        let Some(Commands::Alias(args)) = cli.command else {
+            panic!("Expected an alias");
+        };
+        assert_eq!(args, vec!["deploy-to", "prod"]);
+        assert!(cli.dry_run);
+    }
+
+    #[test]
+    fn test_parse_run_max_output() {
+        let cli = Cli::parse_from(["maki", "run", "build", "--json", "--max-output", "4096"]);
+        let Some(Commands::Run { max_output, .. }) = cli.command else {
+            panic!("Expected Run command");
+        };
+        assert!(cli.json);
+        assert_eq!(max_output, Some(4096));
+    }
+
+    #[test]
+    fn test_parse_question_and_touch() {
+        let cli = Cli::parse_from(["maki", "run", "out.txt", "--question"]);
+        let Some(Commands::Run {
+            question, touch, ..
+        }) = cli.command
+        else {
+            panic!("Expected Run command");
+        };
+        assert!(question && !touch);
+
+        assert!(Cli::try_parse_from(["maki", "run", "a", "--question", "--touch"]).is_err());
+        assert!(Cli::try_parse_from(["maki", "run", "a", "--touch", "--until-failure"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_repeat_options() {
+        let cli = Cli::parse_from(["maki", "run", "test", "--until-failure"]);
+        let Some(Commands::Run { repeat, .. }) = cli.command else {
+            panic!("Expected Run command");
+        };
+        assert_eq!(repeat.mode(), Some(RepeatMode::UntilFailure));
+        assert_eq!(repeat.max_iterations, None);
+
+        let cli = Cli::parse_from([
+            "maki",
+            "run",
+            "test",
+            "--until-success",
+            "--max-iterations",
+            "5",
+        ]);
+        let Some(Commands::Run { repeat, .. }) = cli.command else {
+            panic!("Expected Run command");
+        };
+        assert_eq!(repeat.mode(), Some(RepeatMode::UntilSuccess));
+        assert_eq!(repeat.max_iterations, Some(5));
+
+        let conflicting = ["maki", "run", "t", "--until-failure", "--until-success"];
+        assert!(Cli::try_parse_from(conflicting).is_err());
+        assert!(Cli::try_parse_from(["maki", "run", "t", "--max-iterations", "3"]).is_err());
+        assert!(
+            Cli::try_parse_from([
+                "maki",
+                "run",
+                "t",
+                "--until-failure",
+                "--max-iterations",
+                "0"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_note_command() {
+        let cli = Cli::parse_from(["maki", "note", "deploy", "needs VPN connected"]);
+        if let Some(Commands::Note {
+            target,
+            note,
+            remove,
+        }) = cli.command
+        {
+            assert_eq!(target, "deploy");
+            assert_eq!(note.as_deref(), Some("needs VPN connected"));
+            assert!(!remove);
+        } else {
+            panic!("Expected Note command");
+        }
+
+        let cli = Cli::parse_from(["maki", "note", "deploy", "--remove"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Note {
+                note: None,
+                remove: true,
+                ..
+            })
+        ));
+        assert!(Cli::try_parse_from(["maki", "note", "deploy", "x", "--remove"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_show_command() {
+        let cli = Cli::parse_from(["maki", "show", "deploy"]);
+        if let Some(Commands::Show { target }) = cli.command {
+            assert_eq!(target, "deploy");
+        } else {
+            panic!("Expected Show command");
+        }
+    }
+
     #[test]
     fn test_parse_global_flags() {
         let cli = Cli::parse_from([
             "maki",
             "--all",
             "--patterns",
+            "--generated",
+            "--no-sudo",
             "--json",
             "--recursive",
+            "--force",
             "--dry-run",
             "--no-cache",
+            "--split",
+            "--auto",
+            "--defaults",
+            "--verbose",
+            "--with-deps",
             "list",
         ]);
 
         assert!(cli.all);
         assert!(cli.patterns);
+        assert!(cli.generated);
+        assert!(cli.no_sudo);
         assert!(cli.json);
         assert!(cli.recursive);
+        assert!(cli.force);
         assert!(cli.dry_run);
         assert!(cli.no_cache);
+        assert!(cli.split);
+        assert!(cli.auto);
+        assert!(cli.defaults);
+        assert!(cli.verbose);
+        assert!(cli.with_deps);
+    }
+
+    #[test]
+    fn test_parse_cache_commands() {
+        let cli = Cli::parse_from(["maki", "cache", "stats"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Cache {
+                action: CacheCommand::Stats
+            })
+        ));
+
+        let cli = Cli::parse_from(["maki", "cache", "clear"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Cache {
+                action: CacheCommand::Clear
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_refactor_rename() {
+        let cli = Cli::parse_from(["maki", "refactor", "rename", "build", "compile", "-y"]);
+        match cli.command {
+            Some(Commands::Refactor {
+                action: RefactorCommand::Rename { old, new, yes },
+            }) => {
+                assert_eq!(old, "build");
+                assert_eq!(new, "compile");
+                assert!(yes);
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_config_commands() {
+        let cli = Cli::parse_from(["maki", "config", "export", "team.toml"]);
+        let Some(Commands::Config {
+            action: Some(ConfigCommand::Export { file }),
+            ..
+        }) = cli.command
+        else {
+            panic!("Expected config export");
+        };
+        assert_eq!(file, Some(PathBuf::from("team.toml")));
+
+        let cli = Cli::parse_from([
+            "maki",
+            "config",
+            "import",
+            "team.toml",
+            "--prefer",
+            "bundle",
+        ]);
+        let Some(Commands::Config {
+            action: Some(ConfigCommand::Import { prefer, yes, .. }),
+            ..
+        }) = cli.command
+        else {
+            panic!("Expected config import");
+        };
+        assert_eq!(prefer, Prefer::Bundle);
+        assert!(!yes);
+
+        let cli = Cli::parse_from(["maki", "config", "--origin"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Config {
+                origin: true,
+                action: None
+            })
+        ));
+        assert!(Cli::try_parse_from(["maki", "config", "--origin", "export"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_version() {
+        let cli = Cli::parse_from(["maki", "version", "--json"]);
+        assert!(matches!(cli.command, Some(Commands::Version)));
+        assert!(cli.json);
+
+        let cli = Cli::parse_from(["maki", "--version", "--json"]);
+        assert!(cli.version);
+        assert!(cli.json);
+        assert!(Cli::parse_from(["maki", "-V"]).version);
+    }
+
+    #[test]
+    fn test_parse_validate_command() {
+        let cli = Cli::parse_from(["maki", "validate"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Validate {
+                fix: false,
+                yes: false
+            })
+        ));
+
+        let cli = Cli::parse_from(["maki", "validate", "--fix", "-y"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Validate {
+                fix: true,
+                yes: true
+            })
+        ));
+        assert!(Cli::try_parse_from(["maki", "validate", "--yes"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors_command() {
+        let cli = Cli::parse_from(["maki", "errors"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Errors {
+                number: None,
+                list: false
+            })
+        ));
+
+        let cli = Cli::parse_from(["maki", "errors", "3"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Errors {
+                number: Some(3),
+                ..
+            })
+        ));
+
+        let cli = Cli::parse_from(["maki", "run", "build", "--errors"]);
+        assert!(cli.errors);
+        assert!(Cli::try_parse_from(["maki", "errors", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_setup_command() {
+        let cli = Cli::parse_from(["maki", "setup"]);
+        assert!(matches!(cli.command, Some(Commands::Setup)));
+    }
+
+    #[test]
+    fn test_parse_hook_commands() {
+        let cli = Cli::parse_from(["maki", "hook", "fish"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Hook { shell: Shell::Fish })
+        ));
+
+        let cli = Cli::parse_from(["maki", "__complete"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Complete { target: None })
+        ));
+        let cli = Cli::parse_from(["maki", "__complete", "bump"]);
+        assert!(
+            matches!(cli.command, Some(Commands::Complete { target: Some(ref t) }) if t == "bump")
+        );
+        assert!(Cli::try_parse_from(["maki", "hook", "tcsh"]).is_err());
+    }
+
+    #[test]
+    fn test_pick_action_flags() {
+        assert_eq!(Cli::parse_from(["maki"]).pick_action(), PickAction::Run);
+        assert_eq!(
+            Cli::parse_from(["maki", "--edit"]).pick_action(),
+            PickAction::Edit
+        );
+        assert_eq!(
+            Cli::parse_from(["maki", "pick", "--show"]).pick_action(),
+            PickAction::Show
+        );
+        assert_eq!(
+            Cli::parse_from(["maki", "--copy"]).pick_action(),
+            PickAction::Copy
+        );
+        assert_eq!(
+            Cli::parse_from(["maki", "--queue"]).pick_action(),
+            PickAction::Queue
+        );
+
+        // Only one action at a time
+        assert!(Cli::try_parse_from(["maki", "--edit", "--copy"]).is_err());
+        assert!(Cli::try_parse_from(["maki", "--show", "--copy"]).is_err());
+        assert!(Cli::try_parse_from(["maki", "--copy", "--queue"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_queue_command() {
+        let cli = Cli::parse_from(["maki", "queue", "add", "build", "deploy", "ENV=prod"]);
+        if let Some(Commands::Queue {
+            action: QueueCommand::Add { words },
+        }) = cli.command
+        {
+            assert_eq!(words, vec!["build", "deploy", "ENV=prod"]);
+        } else {
+            panic!("Expected Queue Add command");
+        }
+
+        let cli = Cli::parse_from(["maki", "queue", "remove", "test"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Queue {
+                action: QueueCommand::Remove { ref target }
+            }) if target == "test"
+        ));
+        assert!(matches!(
+            Cli::parse_from(["maki", "queue", "run"]).command,
+            Some(Commands::Queue {
+                action: QueueCommand::Run
+            })
+        ));
+        assert!(Cli::try_parse_from(["maki", "queue", "add"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_export_ci_command() {
+        let cli = Cli::parse_from(["maki", "export", "ci", "--format", "gitlab", "test", "lint"]);
+        if let Some(Commands::Export {
+            action: ExportCommand::Ci { format, targets },
+        }) = cli.command
+        {
+            assert_eq!(format, CiFormat::Gitlab);
+            assert_eq!(targets, vec!["test", "lint"]);
+        } else {
+            panic!("Expected Export Ci command");
+        }
+        assert!(matches!(
+            Cli::parse_from(["maki", "export", "ci"]).command,
+            Some(Commands::Export {
+                action: ExportCommand::Ci {
+                    format: CiFormat::Github,
+                    ..
+                }
+            })
+        ));
+        assert!(Cli::try_parse_from(["maki", "export", "ci", "--format", "jenkins"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_export_makefile_command() {
+        assert!(matches!(
+            Cli::parse_from(["maki", "export", "makefile", "--write"]).command,
+            Some(Commands::Export {
+                action: ExportCommand::Makefile { write: true }
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_usage_command() {
+        assert!(matches!(
+            Cli::parse_from(["maki", "usage", "export"]).command,
+            Some(Commands::Usage {
+                action: UsageCommand::Export
+            })
+        ));
+        assert!(matches!(
+            Cli::parse_from(["maki", "usage", "show"]).command,
+            Some(Commands::Usage {
+                action: UsageCommand::Show
+            })
+        ));
+        assert!(Cli::try_parse_from(["maki", "usage"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_diff_command() {
+        let cli = Cli::parse_from(["maki", "diff", "--ref", "main"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Diff { ref git_ref }) if git_ref == "main"
+        ));
+        // Without --ref, uncommitted changes
+        assert!(matches!(
+            Cli::parse_from(["maki", "diff"]).command,
+            Some(Commands::Diff { ref git_ref }) if git_ref == "HEAD"
+        ));
+    }
+
+    #[test]
+    fn test_parse_query_command() {
+        let cli = Cli::parse_from(["maki", "query", "name=docker/*", "provider=make"]);
+        if let Some(Commands::Query { filter }) = cli.command {
+            assert_eq!(filter, vec!["name=docker/*", "provider=make"]);
+        } else {
+            panic!("Expected Query command");
+        }
+
+        // No filter matches every target
+        assert!(matches!(
+            Cli::parse_from(["maki", "query"]).command,
+            Some(Commands::Query { ref filter }) if filter.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_parse_env_options() {
+        let cli = Cli::parse_from([
+            "maki",
+            "--var",
+            "ENV=prod",
+            "--var",
+            "V=patch",
+            "--env-file",
+            ".env",
+            "run",
+            "deploy",
+        ]);
+
+        assert_eq!(
+            cli.vars,
+            vec![
+                ("ENV".to_string(), "prod".to_string()),
+                ("V".to_string(), "patch".to_string())
+            ]
+        );
+        assert_eq!(cli.env_file, Some(PathBuf::from(".env")));
+        assert!(Cli::try_parse_from(["maki", "--var", "oops", "list"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_picker_option() {
+        let cli = Cli::parse_from(["maki"]);
+        assert_eq!(cli.picker, PickerKind::Skim);
+
+        let cli = Cli::parse_from(["maki", "--picker", "plain"]);
+        assert_eq!(cli.picker, PickerKind::Plain);
+        assert!(Cli::try_parse_from(["maki", "--picker", "tui"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_rank_option() {
+        assert_eq!(Cli::parse_from(["maki"]).rank, None);
+
+        let cli = Cli::parse_from(["maki", "--rank", "pure-fuzzy"]);
+        assert_eq!(cli.rank, Some(RankMode::PureFuzzy));
+        let cli = Cli::parse_from(["maki", "list", "--rank", "frecency"]);
+        assert_eq!(cli.rank, Some(RankMode::Frecency));
+        assert!(Cli::try_parse_from(["maki", "--rank", "random"]).is_err());
+    }
+
+    #[test]
+    fn test_sort_order() {
+        let target =
+            |name: &str, line| Target::new(name.to_string(), None, "Makefile".into(), line);
+        let discovered = vec![target("setup", 1), target("build", 4), target("test", 9)];
+
+        let cli = Cli::parse_from(["maki", "--sort", "file-order", "list"]);
+        assert_eq!(cli.sort, SortOrder::FileOrder);
+        let mut targets = discovered.clone();
+        cli.sort.apply(&mut targets);
+        assert_eq!(targets, discovered);
+
+        let mut targets = discovered.clone();
+        SortOrder::Name.apply(&mut targets);
+        let names: Vec<&str> = targets.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["build", "setup", "test"]);
+    }
+
+    #[test]
+    fn test_color_choice() {
+        let cli = Cli::parse_from(["maki", "list", "--color", "never"]);
+        assert_eq!(cli.color, ColorChoice::Never);
+
+        assert_eq!(ColorChoice::Auto.resolve(None, None), None);
+        assert_eq!(ColorChoice::Auto.resolve(Some("1"), None), Some(false));
+        assert_eq!(ColorChoice::Auto.resolve(Some(""), None), None);
+        assert_eq!(ColorChoice::Auto.resolve(None, Some("true")), Some(false));
+        assert_eq!(ColorChoice::Auto.resolve(None, Some("false")), None);
+        assert_eq!(
+            ColorChoice::Always.resolve(Some("1"), Some("true")),
+            Some(true)
+        );
+        assert_eq!(ColorChoice::Never.resolve(None, None), Some(false));
     }
 
     #[test]
@@ -136,10 +1558,63 @@ mod tests {
         assert_eq!(cli.file, Some(PathBuf::from("custom.mk")));
     }
 
+    #[test]
+    fn test_parse_makefile_names() {
+        assert!(Cli::parse_from(["maki", "list"]).makefile_names.is_empty());
+
+        let cli = Cli::parse_from([
+            "maki",
+            "--makefile-name",
+            "Makefile",
+            "--makefile-name",
+            "tasks.mk",
+            "list",
+        ]);
+        assert_eq!(cli.makefile_names, vec!["Makefile", "tasks.mk"]);
+        let cli = Cli::parse_from(["maki", "list", "--makefile-name", "build.mk"]);
+        assert_eq!(cli.makefile_names, vec!["build.mk"]);
+        assert!(Cli::try_parse_from(["maki", "--makefile-name", "mk/tasks.mk", "list"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_heartbeat() {
+        let cli = Cli::parse_from(["maki", "run", "build"]);
+        assert_eq!(cli.heartbeat, None);
+
+        let cli = Cli::parse_from(["maki", "run", "build", "--heartbeat"]);
+        assert_eq!(cli.heartbeat, Some(30));
+
+        let cli = Cli::parse_from(["maki", "--heartbeat", "10", "run", "build"]);
+        assert_eq!(cli.heartbeat, Some(10));
+
+        assert!(Cli::try_parse_from(["maki", "--heartbeat", "0", "list"]).is_err());
+    }
+
+    #[test]
+    fn test_keep_scratch_needs_scratch() {
+        let cli = Cli::parse_from(["maki", "run", "build", "--scratch", "--keep-scratch"]);
+        assert!(cli.scratch && cli.keep_scratch);
+
+        assert!(Cli::try_parse_from(["maki", "run", "build", "--keep-scratch"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_include_dirs() {
+        let cli = Cli::parse_from(["maki", "-I", "mk", "--include-dir", "/opt/mk", "list"]);
+        assert_eq!(
+            cli.include_dirs,
+            vec![PathBuf::from("mk"), PathBuf::from("/opt/mk")]
+        );
+    }
+
     #[test]
     fn test_parse_cwd_option() {
         let cli = Cli::parse_from(["maki", "--cwd", "/tmp", "list"]);
-        assert_eq!(cli.cwd, Some(PathBuf::from("/tmp")));
+        assert_eq!(cli.cwd, vec![PathBuf::from("/tmp")]);
+
+        let cli = Cli::parse_from(["maki", "--cwd", "api", "--cwd", "web", "list"]);
+        assert_eq!(cli.cwd, vec![PathBuf::from("api"), PathBuf::from("web")]);
+        assert_eq!(cli.working_dir(), PathBuf::from("api"));
     }
 
     #[test]
@@ -0,0 +1,157 @@
+use anyhow::{Result, bail};
+use std::io::{ErrorKind, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+/// How copied text reached the clipboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Copied {
+    /// A clipboard utility, e.g. pbcopy or xclip
+    Utility(&'static str),
+    /// An OSC 52 escape sequence, which the terminal copies when it allows
+    /// it, even over SSH
+    Terminal,
+}
+
+/// Clipboard utilities to try, in order, for the current platform
+fn candidates() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        vec![("pbcopy", &[])]
+    } else if cfg!(windows) {
+        vec![("clip", &[])]
+    } else {
+        let mut tools: Vec<(&'static str, &'static [&'static str])> = Vec::new();
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            tools.push(("wl-copy", &[]));
+        }
+        tools.push(("xclip", &["-selection", "clipboard"]));
+        tools.push(("xsel", &["--clipboard", "--input"]));
+        tools
+    }
+}
+
+/// Copy text to the system clipboard, or when no clipboard utility works
+/// (headless or over SSH), through the terminal with OSC 52
+pub fn copy(text: &str) -> Result<Copied> {
+    let tools = candidates();
+    if let Some(program) = copy_with(text, &tools)? {
+        return Ok(Copied::Utility(program));
+    }
+
+    let sequence = osc52(text, std::env::var_os("TMUX").is_some());
+    if std::io::stderr().is_terminal() {
+        let mut stderr = std::io::stderr();
+        stderr.write_all(sequence.as_bytes())?;
+        stderr.flush()?;
+        return Ok(Copied::Terminal);
+    }
+    if std::io::stdout().is_terminal() {
+        let mut stdout = std::io::stdout();
+        stdout.write_all(sequence.as_bytes())?;
+        stdout.flush()?;
+        return Ok(Copied::Terminal);
+    }
+
+    let tried: Vec<&str> = tools.iter().map(|(program, _)| *program).collect();
+    bail!(
+        "No clipboard utility found (tried {}), and no terminal to copy through",
+        tried.join(", ")
+    )
+}
+
+/// Pipe text to the first of `tools` that is installed and succeeds
+fn copy_with(text: &str, tools: &[(&'static str, &[&str])]) -> Result<Option<&'static str>> {
+    for &(program, args) in tools {
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => bail!("Failed to run {}: {}", program, e),
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+
+        if child.wait()?.success() {
+            return Ok(Some(program));
+        }
+    }
+    Ok(None)
+}
+
+/// The OSC 52 sequence setting the clipboard to `text`, wrapped for tmux to
+/// pass on to the terminal outside it
+fn osc52(text: &str, tmux: bool) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+    if tmux {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    }
+}
+
+/// Standard base64 with padding, as OSC 52 expects
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates_not_empty() {
+        assert!(!candidates().is_empty());
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(
+            base64(b"make deploy ENV=prod"),
+            "bWFrZSBkZXBsb3kgRU5WPXByb2Q="
+        );
+    }
+
+    #[test]
+    fn test_osc52() {
+        assert_eq!(osc52("make", false), "\x1b]52;c;bWFrZQ==\x07");
+        assert_eq!(
+            osc52("make", true),
+            "\x1bPtmux;\x1b\x1b]52;c;bWFrZQ==\x07\x1b\\"
+        );
+    }
+
+    #[test]
+    fn test_missing_utilities_are_skipped() {
+        let tools: [(&'static str, &[&str]); 1] = [("maki-no-such-clipboard", &[])];
+        assert_eq!(copy_with("make", &tools).unwrap(), None);
+    }
+}
@@ -0,0 +1,708 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+use crate::artifact::ArtifactPolicy;
+use crate::cache::CachePolicy;
+use crate::guard::Guard;
+use crate::makefile;
+use crate::providers::ProviderPolicy;
+use crate::rank::RankPolicy;
+use crate::target::Target;
+use crate::webhook::Webhook;
+
+/// Project configuration, layered from the defaults, the team's `maki.toml`,
+/// a personal `.maki.local.toml`, and `MAKI_*` environment variables
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Skip the picker when the project has exactly one target
+    pub single_target_shortcut: bool,
+    /// Environment variables set for every run (`[env]` table)
+    pub env: BTreeMap<String, String>,
+    /// Cache eviction limits (`[cache]` table)
+    pub cache: CachePolicy,
+    /// Project directories to gather targets from, relative to the config file
+    pub roots: Vec<PathBuf>,
+    /// File names taken for Makefiles when looking for them, in order
+    pub makefile_names: Vec<String>,
+    /// Scan subdirectories for Makefiles, as -r does
+    pub recursive: bool,
+    /// Fill in descriptions from what the Makefile's `help` target prints,
+    /// for targets without a `##` comment
+    pub help_descriptions: bool,
+    /// Load the `.env` and `.envrc` next to each target's Makefile for its
+    /// runs, as --auto-env does
+    pub auto_env: bool,
+    /// Providers to use and their priority (`[providers]` table)
+    pub providers: ProviderPolicy,
+    /// How the picker ranks targets (`[picker]` table)
+    pub picker: RankPolicy,
+    /// Per-target settings (`[target.<name>]` tables)
+    #[serde(rename = "target")]
+    pub targets: BTreeMap<String, TargetConfig>,
+    /// Tasks made of targets and variables, run as `maki <name>` (`[alias]`
+    /// table)
+    #[serde(rename = "alias")]
+    pub aliases: BTreeMap<String, String>,
+    /// Files to keep from runs that mention them (`[artifacts]` table)
+    pub artifacts: ArtifactPolicy,
+    /// Where to post summaries of finished runs (`[[webhook]]` tables)
+    #[serde(rename = "webhook")]
+    pub webhooks: Vec<Webhook>,
+    /// When targets should and shouldn't run (`[[guard]]` tables)
+    #[serde(rename = "guard")]
+    pub guards: Vec<Guard>,
+}
+
+/// Settings for one target
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TargetConfig {
+    /// Default values for the target's variables (`[target.<name>.vars]`)
+    pub vars: BTreeMap<String, String>,
+    /// More artifact patterns, for this target's runs only
+    pub artifacts: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            single_target_shortcut: true,
+            env: BTreeMap::new(),
+            cache: CachePolicy::default(),
+            roots: Vec::new(),
+            makefile_names: makefile::default_makefile_names(),
+            recursive: false,
+            help_descriptions: false,
+            auto_env: false,
+            providers: ProviderPolicy::default(),
+            picker: RankPolicy::default(),
+            targets: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            artifacts: ArtifactPolicy::default(),
+            webhooks: Vec::new(),
+            guards: Vec::new(),
+        }
+    }
+}
+
+/// Where a setting's value comes from, from the lowest precedence to the highest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Default,
+    /// The team's settings, committed with the project
+    Shared(PathBuf),
+    /// Personal overrides, kept out of version control
+    Local(PathBuf),
+    /// A `MAKI_*` environment variable
+    Env(String),
+    /// A command-line flag
+    Flag(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::Shared(path) | Self::Local(path) => {
+                let name = path.file_name().unwrap_or(path.as_os_str());
+                write!(f, "{}", name.to_string_lossy())
+            }
+            Self::Env(name) | Self::Flag(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Settings from one source
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub origin: Origin,
+    pub table: Table,
+}
+
+impl Layer {
+    /// A layer with the single setting `key` (dotted, e.g. `picker.rank`)
+    pub fn setting(origin: Origin, key: &str, value: Value) -> Self {
+        Self {
+            origin,
+            table: nested(key, value),
+        }
+    }
+}
+
+/// Settings merged from several layers, each remembering the layer that set it
+#[derive(Debug, Clone, Default)]
+pub struct Layered {
+    pub table: Table,
+    /// The origin of each value, by dotted key (e.g. `cache.max_entries`)
+    origins: BTreeMap<String, Origin>,
+}
+
+impl Layered {
+    /// Merge `layers` in order, later ones overriding earlier ones setting by
+    /// setting, descending into tables
+    pub fn merge(layers: &[Layer]) -> Self {
+        let mut layered = Self::default();
+        for layer in layers {
+            merge_layer(
+                &mut layered.table,
+                None,
+                &layer.table,
+                &layer.origin,
+                &mut layered.origins,
+            );
+        }
+        layered
+    }
+
+    /// Every value with its dotted key and origin, in key order
+    pub fn settings(&self) -> Vec<(String, &Value, &Origin)> {
+        let mut settings = Vec::new();
+        for (key, value) in leaves(&self.table, None) {
+            let origin = self.origins.get(&key).unwrap_or(&Origin::Default);
+            settings.push((key, value, origin));
+        }
+        settings
+    }
+
+    pub fn config(&self) -> Result<Config> {
+        Ok(Value::Table(self.table.clone()).try_into()?)
+    }
+}
+
+fn merge_layer(
+    merged: &mut Table,
+    prefix: Option<&str>,
+    table: &Table,
+    origin: &Origin,
+    origins: &mut BTreeMap<String, Origin>,
+) {
+    for (name, value) in table {
+        let key = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, name),
+            None => name.clone(),
+        };
+
+        if let (Some(Value::Table(into)), Value::Table(table)) = (merged.get_mut(name), value) {
+            merge_layer(into, Some(&key), table, origin, origins);
+            continue;
+        }
+
+        // The value replaces whatever was there, tables included
+        let nested = format!("{}.", key);
+        origins.retain(|k, _| *k != key && !k.starts_with(&nested));
+        match value {
+            Value::Table(table) => {
+                for (leaf, _) in leaves(table, Some(&key)) {
+                    origins.insert(leaf, origin.clone());
+                }
+            }
+            _ => {
+                origins.insert(key, origin.clone());
+            }
+        }
+        merged.insert(name.clone(), value.clone());
+    }
+}
+
+/// The values in `table` that aren't tables themselves, with their dotted keys
+fn leaves<'a>(table: &'a Table, prefix: Option<&str>) -> Vec<(String, &'a Value)> {
+    let mut leaves = Vec::new();
+    for (name, value) in table {
+        let key = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, name),
+            None => name.clone(),
+        };
+        match value {
+            Value::Table(table) => leaves.extend(self::leaves(table, Some(&key))),
+            _ => leaves.push((key, value)),
+        }
+    }
+    leaves
+}
+
+/// A setting from a `MAKI_*` environment variable, if `name` is one: the rest
+/// of the name is its dotted key, in any case, with `__` for the dots
+/// (`MAKI_CACHE__MAX_ENTRIES`)
+///
+/// Only settings with a default can be set this way, since keys like target
+/// names and `[env]` variables can't be told apart once upper-cased.
+fn env_setting(name: &str, raw: &str, defaults: &[(String, &Value)]) -> Option<(String, Value)> {
+    let rest = name.strip_prefix(Config::ENV_PREFIX)?;
+    let key = rest.to_lowercase().replace("__", ".");
+    if !defaults.iter().any(|(default, _)| *default == key) {
+        return None;
+    }
+
+    // `false`, `200`, and `[".", "../api"]` are read as TOML, anything else
+    // as a string
+    let value = toml::from_str::<Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(raw.to_string()));
+    Some((key, value))
+}
+
+/// A table holding `value` at the dotted `key`
+fn nested(key: &str, value: Value) -> Table {
+    let mut table = Table::new();
+    match key.split_once('.') {
+        Some((name, rest)) => table.insert(name.to_string(), Value::Table(nested(rest, value))),
+        None => table.insert(key.to_string(), value),
+    };
+    table
+}
+
+impl Config {
+    const CONFIG_FILENAME: &'static str = "maki.toml";
+    /// The name the shared settings had before `maki.toml`, still read
+    const LEGACY_FILENAME: &'static str = ".maki.toml";
+    const LOCAL_FILENAME: &'static str = ".maki.local.toml";
+    const ENV_PREFIX: &'static str = "MAKI_";
+
+    /// Get the path to the project's shared config file: `maki.toml`, or
+    /// `.maki.toml` in projects that still use that name
+    pub fn config_path(dir: &Path) -> PathBuf {
+        let legacy = dir.join(Self::LEGACY_FILENAME);
+        let path = dir.join(Self::CONFIG_FILENAME);
+        if !path.exists() && legacy.exists() {
+            legacy
+        } else {
+            path
+        }
+    }
+
+    /// Get the path to the personal overrides file
+    pub fn local_path(dir: &Path) -> PathBuf {
+        dir.join(Self::LOCAL_FILENAME)
+    }
+
+    /// Load the project config, falling back to defaults for the settings no
+    /// layer sets
+    pub fn load(dir: &Path) -> Result<Self> {
+        Self::load_layered(dir, std::env::vars())?.config()
+    }
+
+    /// Merge the layers of settings for `dir`, reading `MAKI_*` settings from
+    /// `vars`
+    pub fn load_layered(
+        dir: &Path,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Layered> {
+        Ok(Layered::merge(&Self::layers(dir, vars)?))
+    }
+
+    /// The layers of settings for `dir`, lowest precedence first: the
+    /// defaults, the shared file, the local file, and environment variables
+    pub fn layers(
+        dir: &Path,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Vec<Layer>> {
+        if dir.join(Self::CONFIG_FILENAME).exists() && dir.join(Self::LEGACY_FILENAME).exists() {
+            bail!(
+                "Both {} and {} exist in {}; move the settings into {}",
+                Self::CONFIG_FILENAME,
+                Self::LEGACY_FILENAME,
+                dir.display(),
+                Self::CONFIG_FILENAME
+            );
+        }
+
+        let defaults = Table::try_from(Self::default()).context("Failed to list the defaults")?;
+        let mut layers = vec![Layer {
+            origin: Origin::Default,
+            table: defaults.clone(),
+        }];
+
+        let (shared, local) = (Self::config_path(dir), Self::local_path(dir));
+        let files = [
+            (shared.clone(), Origin::Shared(shared)),
+            (local.clone(), Origin::Local(local)),
+        ];
+        for (path, origin) in files {
+            if !path.exists() {
+                continue;
+            }
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+            Self::parse(&content)
+                .with_context(|| format!("Invalid config file: {}", path.display()))?;
+            layers.push(Layer {
+                table: toml::from_str(&content)?,
+                origin,
+            });
+        }
+
+        let defaults = leaves(&defaults, None);
+        let mut vars: Vec<(String, String)> = vars.into_iter().collect();
+        vars.sort();
+        for (name, raw) in vars {
+            let Some((key, value)) = env_setting(&name, &raw, &defaults) else {
+                continue;
+            };
+            let layer = Layer::setting(Origin::Env(name.clone()), &key, value);
+            Value::Table(layer.table.clone())
+                .try_into::<Self>()
+                .with_context(|| format!("Invalid setting in ${}: {}", name, raw))?;
+            layers.push(layer);
+        }
+
+        Ok(layers)
+    }
+
+    /// The configured roots, resolved against the directory holding the config
+    pub fn resolved_roots(&self, dir: &Path) -> Vec<PathBuf> {
+        self.roots.iter().map(|root| dir.join(root)).collect()
+    }
+
+    /// Use the configured `[target.<name>.vars]` values as the defaults of the
+    /// targets' required variables, taking precedence over the Makefile's
+    pub fn apply_var_defaults(&self, targets: &mut [Target]) {
+        for target in targets {
+            let Some(config) = self.targets.get(&target.name) else {
+                continue;
+            };
+            for var in &mut target.required_vars {
+                if let Some(value) = config.vars.get(&var.name) {
+                    var.default = Some(value.clone());
+                }
+            }
+        }
+    }
+
+    /// Give each target the `[artifacts]` patterns, plus its own from
+    /// `[target.<name>] artifacts`
+    pub fn apply_artifacts(&self, targets: &mut [Target]) {
+        for target in targets {
+            let mut policy = self.artifacts.clone();
+            if let Some(config) = self.targets.get(&target.name) {
+                policy.patterns.extend(config.artifacts.iter().cloned());
+            }
+            target.artifacts = policy;
+        }
+    }
+
+    /// Give each target the webhooks whose `targets` cover it
+    pub fn apply_webhooks(&self, targets: &mut [Target]) {
+        for target in targets {
+            target.webhooks = self
+                .webhooks
+                .iter()
+                .filter(|hook| hook.covers(&target.name))
+                .cloned()
+                .collect();
+        }
+    }
+
+    /// Give each target the guards that cover it
+    pub fn apply_guards(&self, targets: &mut [Target]) {
+        for target in targets {
+            target.guards = self
+                .guards
+                .iter()
+                .filter(|guard| guard.covers(target))
+                .cloned()
+                .collect();
+        }
+    }
+
+    /// Parse config from TOML content
+    pub fn parse(content: &str) -> Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_default_config() {
+        let config = Config::default();
+        assert!(config.single_target_shortcut);
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse("single_target_shortcut = false").unwrap();
+        assert!(!config.single_target_shortcut);
+
+        // Missing keys fall back to defaults
+        let config = Config::parse("").unwrap();
+        assert!(config.single_target_shortcut);
+    }
+
+    #[test]
+    fn test_parse_env_table() {
+        let config = Config::parse("[env]\nRUST_LOG = \"debug\"\n").unwrap();
+        assert_eq!(config.env.get("RUST_LOG").map(String::as_str), Some("debug"));
+    }
+
+    #[test]
+    fn test_parse_cache_policy() {
+        let config = Config::parse("[cache]\nmax_entries = 50\n").unwrap();
+        assert_eq!(config.cache.max_entries, 50);
+        assert_eq!(
+            config.cache.max_age_days,
+            CachePolicy::default().max_age_days
+        );
+    }
+
+    #[test]
+    fn test_parse_makefile_names() {
+        assert_eq!(
+            Config::default().makefile_names,
+            vec!["Makefile", "makefile", "GNUmakefile"]
+        );
+        let config = Config::parse("makefile_names = [\"Makefile\", \"tasks.mk\"]\n").unwrap();
+        assert_eq!(config.makefile_names, vec!["Makefile", "tasks.mk"]);
+    }
+
+    #[test]
+    fn test_parse_roots() {
+        let config = Config::parse("roots = [\"../api\", \"/src/web\"]\n").unwrap();
+        assert_eq!(
+            config.resolved_roots(Path::new("/src/platform")),
+            vec![
+                PathBuf::from("/src/platform/../api"),
+                PathBuf::from("/src/web")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_var_defaults() {
+        use crate::target::RequiredVar;
+
+        let config =
+            Config::parse("[target.deploy.vars]\nENV = \"staging\"\nREGION = \"eu\"\n").unwrap();
+        let var = |name: &str, default: Option<&str>| RequiredVar {
+            name: name.to_string(),
+            hint: None,
+            default: default.map(str::to_string),
+            secret: None,
+        };
+        let mut targets = vec![
+            Target::with_required_vars(
+                "deploy".to_string(),
+                None,
+                PathBuf::from("Makefile"),
+                1,
+                vec![var("ENV", Some("prod")), var("TAG", None)],
+            ),
+            Target::with_required_vars(
+                "release".to_string(),
+                None,
+                PathBuf::from("Makefile"),
+                5,
+                vec![var("ENV", None)],
+            ),
+        ];
+
+        config.apply_var_defaults(&mut targets);
+
+        assert_eq!(
+            targets[0].required_vars,
+            vec![var("ENV", Some("staging")), var("TAG", None)]
+        );
+        assert_eq!(targets[1].required_vars, vec![var("ENV", None)]);
+    }
+
+    #[test]
+    fn test_apply_artifacts() {
+        let config = Config::parse(
+            "[artifacts]\npatterns = [\"dist/*.tar.gz\"]\ndir = \"artifacts\"\n\
+             [target.test]\nartifacts = [\"coverage/**\"]\n",
+        )
+        .unwrap();
+        let mut targets = vec![
+            Target::new("dist".to_string(), None, PathBuf::from("Makefile"), 1),
+            Target::new("test".to_string(), None, PathBuf::from("Makefile"), 4),
+        ];
+
+        config.apply_artifacts(&mut targets);
+
+        assert_eq!(targets[0].artifacts.patterns, vec!["dist/*.tar.gz"]);
+        assert_eq!(
+            targets[1].artifacts.patterns,
+            vec!["dist/*.tar.gz", "coverage/**"]
+        );
+        assert_eq!(targets[1].artifacts.dir, Some(PathBuf::from("artifacts")));
+        assert!(Config::parse("[artifacts]\nglob = \"*\"\n").is_err());
+    }
+
+    #[test]
+    fn test_apply_webhooks() {
+        let config = Config::parse(
+            "[[webhook]]\nurl = \"https://example.com/all\"\n\
+             [[webhook]]\nurl = \"https://example.com/deploys\"\non = \"always\"\n\
+             targets = [\"deploy*\"]\n",
+        )
+        .unwrap();
+        let mut targets = vec![
+            Target::new("build".to_string(), None, PathBuf::from("Makefile"), 1),
+            Target::new(
+                "deploy-prod".to_string(),
+                None,
+                PathBuf::from("Makefile"),
+                4,
+            ),
+        ];
+
+        config.apply_webhooks(&mut targets);
+
+        let urls =
+            |t: &Target| -> Vec<String> { t.webhooks.iter().map(|h| h.url.clone()).collect() };
+        assert_eq!(urls(&targets[0]), vec!["https://example.com/all"]);
+        assert_eq!(
+            urls(&targets[1]),
+            vec!["https://example.com/all", "https://example.com/deploys"]
+        );
+    }
+
+    #[test]
+    fn test_apply_guards() {
+        let config = Config::parse(
+            "[[guard]]\ntags = [\"deploy\"]\nhours = \"09:00-17:00\"\nnot_on = [\"fri\"]\n",
+        )
+        .unwrap();
+        let mut targets = vec![
+            Target::new("build".to_string(), None, PathBuf::from("Makefile"), 1),
+            Target::new("ship".to_string(), None, PathBuf::from("Makefile"), 4),
+        ];
+        targets[1].tags = vec!["deploy".to_string()];
+
+        config.apply_guards(&mut targets);
+
+        assert!(targets[0].guards.is_empty());
+        assert_eq!(targets[1].guards, config.guards);
+        assert!(Config::parse("[[guard]]\nhours = \"9-5\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_aliases() {
+        let config = Config::parse("alias.deploy-to = \"deploy ENV={env}\"\n").unwrap();
+        assert_eq!(
+            config.aliases.get("deploy-to").map(String::as_str),
+            Some("deploy ENV={env}")
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_config() {
+        assert!(Config::parse("single_target_shortcut = \"yes\"").is_err());
+    }
+
+    #[test]
+    fn test_load_missing_config() {
+        let dir = TempDir::new().unwrap();
+        let config = Config::load(dir.path()).unwrap();
+        assert!(config.single_target_shortcut);
+    }
+
+    fn layered(dir: &Path, vars: &[(&str, &str)]) -> Layered {
+        let vars = vars.iter().map(|(n, v)| (n.to_string(), v.to_string()));
+        Config::load_layered(dir, vars).unwrap()
+    }
+
+    fn origin_of(layered: &Layered, key: &str) -> String {
+        layered
+            .settings()
+            .into_iter()
+            .find(|(k, _, _)| k == key)
+            .map(|(_, _, origin)| origin.to_string())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("maki.toml"),
+            "roots = [\".\", \"../api\"]\n[cache]\nmax_entries = 50\nmax_age_days = 10\n[env]\nRUST_LOG = \"info\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join(".maki.local.toml"),
+            "[cache]\nmax_entries = 20\n[env]\nRUST_LOG = \"debug\"\n",
+        )
+        .unwrap();
+
+        let layered = layered(
+            dir.path(),
+            &[
+                ("MAKI_CACHE__MAX_AGE_DAYS", "3"),
+                ("MAKI_PICKER__RANK", "frecency"),
+                ("MAKI_HOOK_VERSION", "2"),
+                ("HOME", "/home/me"),
+            ],
+        );
+        let config = layered.config().unwrap();
+
+        assert_eq!(config.cache.max_entries, 20);
+        assert_eq!(config.cache.max_age_days, 3);
+        assert_eq!(config.picker.rank, crate::rank::RankMode::Frecency);
+        assert_eq!(config.env["RUST_LOG"], "debug");
+        assert_eq!(config.roots.len(), 2);
+        assert!(config.single_target_shortcut);
+
+        assert_eq!(origin_of(&layered, "cache.max_entries"), ".maki.local.toml");
+        assert_eq!(origin_of(&layered, "env.RUST_LOG"), ".maki.local.toml");
+        assert_eq!(
+            origin_of(&layered, "cache.max_age_days"),
+            "MAKI_CACHE__MAX_AGE_DAYS"
+        );
+        assert_eq!(origin_of(&layered, "roots"), "maki.toml");
+        assert_eq!(origin_of(&layered, "single_target_shortcut"), "default");
+    }
+
+    #[test]
+    fn test_invalid_environment_setting() {
+        let dir = TempDir::new().unwrap();
+        let vars = [("MAKI_CACHE__MAX_ENTRIES".to_string(), "many".to_string())];
+
+        let err = Config::load_layered(dir.path(), vars).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid setting in $MAKI_CACHE__MAX_ENTRIES: many"
+        );
+    }
+
+    #[test]
+    fn test_legacy_config_file() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join(".maki.toml"),
+            "single_target_shortcut = false\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Config::config_path(dir.path()),
+            dir.path().join(".maki.toml")
+        );
+        assert!(!Config::load(dir.path()).unwrap().single_target_shortcut);
+
+        fs::write(dir.path().join("maki.toml"), "").unwrap();
+        assert!(Config::load(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_load_config_file() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            Config::config_path(dir.path()),
+            "single_target_shortcut = false\n",
+        )
+        .unwrap();
+
+        let config = Config::load(dir.path()).unwrap();
+        assert!(!config.single_target_shortcut);
+    }
+}
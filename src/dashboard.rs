@@ -0,0 +1,452 @@
+use ratatui::Terminal;
+use ratatui::backend::CrosstermBackend;
+use ratatui::buffer::Buffer;
+use ratatui::crossterm::event::{self, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::{execute, terminal};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::Stylize;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Paragraph, Widget};
+use std::io::{self, Stdout};
+use std::time::{Duration, Instant};
+
+use crate::heartbeat::format_elapsed;
+use crate::multirun::{self, Event, Flow, JobState, JobStatus, Renderer};
+use crate::runlog::strip_ansi;
+use crate::terminal::RawMode;
+
+/// How often the screen is redrawn at most while output is streaming in
+const REDRAW: Duration = Duration::from_millis(50);
+
+/// How long the dashboard waits for a key once the run is over
+const KEY_WAIT: Duration = Duration::from_millis(100);
+
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Back,
+    Quit,
+}
+
+/// What a key press means to the dashboard; other keys mean nothing
+fn key(event: KeyEvent) -> Option<Key> {
+    if event.kind == KeyEventKind::Release {
+        return None;
+    }
+    match event.code {
+        // Raw mode turns Ctrl-C into a plain key
+        KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) => Some(Key::Quit),
+        KeyCode::Up | KeyCode::Char('k') => Some(Key::Up),
+        KeyCode::Down | KeyCode::Char('j') => Some(Key::Down),
+        KeyCode::PageUp => Some(Key::PageUp),
+        KeyCode::PageDown => Some(Key::PageDown),
+        KeyCode::Home | KeyCode::Char('g') => Some(Key::Home),
+        KeyCode::End | KeyCode::Char('G') => Some(Key::End),
+        KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => Some(Key::Enter),
+        KeyCode::Esc | KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h') => Some(Key::Back),
+        KeyCode::Char('q') => Some(Key::Quit),
+        _ => None,
+    }
+}
+
+/// What is on screen: the list of targets, or one target's output
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct View {
+    selected: usize,
+    /// The target whose output is shown
+    focus: Option<usize>,
+    /// Lines scrolled up from the end of the output; 0 follows new output
+    scroll: usize,
+}
+
+impl View {
+    /// Act on `key`, with `page` lines of output on screen; returns whether
+    /// the dashboard should close
+    fn handle(&mut self, key: Key, jobs: &[JobState], page: usize) -> bool {
+        let last = jobs.len().saturating_sub(1);
+        match (self.focus, key) {
+            (_, Key::Quit) => return true,
+            (None, Key::Up) => self.selected = self.selected.saturating_sub(1),
+            (None, Key::Down) => self.selected = (self.selected + 1).min(last),
+            (None, Key::Home) => self.selected = 0,
+            (None, Key::End) => self.selected = last,
+            (None, Key::Enter) => {
+                self.focus = Some(self.selected);
+                self.scroll = 0;
+            }
+            (None, _) => {}
+            (Some(job), key) => {
+                let max = jobs[job].output.len().saturating_sub(page);
+                self.scroll = match key {
+                    Key::Up => self.scroll + 1,
+                    Key::Down => self.scroll.saturating_sub(1),
+                    Key::PageUp => self.scroll + page,
+                    Key::PageDown => self.scroll.saturating_sub(page),
+                    Key::Home => max,
+                    Key::End => 0,
+                    Key::Back => {
+                        self.focus = None;
+                        0
+                    }
+                    Key::Enter | Key::Quit => self.scroll,
+                }
+                .min(max);
+            }
+        }
+        false
+    }
+}
+
+/// `text` without colors, tabs or other control characters
+fn clean(text: &str) -> String {
+    strip_ansi(text)
+        .replace('\t', "    ")
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect()
+}
+
+/// Lines of output that fit between the focus view's header and footer
+fn page_height(rows: usize) -> usize {
+    rows.saturating_sub(3).max(1)
+}
+
+fn icon(state: &JobState) -> Span<'static> {
+    match state.status {
+        JobStatus::Waiting => "·".dim(),
+        JobStatus::Running => {
+            let elapsed = state.elapsed().unwrap_or_default().as_millis() / 100;
+            SPINNER[elapsed as usize % SPINNER.len()].cyan()
+        }
+        JobStatus::Finished(s) if s.success() => "✓".green(),
+        JobStatus::Finished(_) | JobStatus::Failed => "✗".red(),
+        JobStatus::Stopped => "■".yellow(),
+        JobStatus::Skipped => "-".dim(),
+    }
+}
+
+/// Draw the screen for `view` into `area`: the list or a target's output,
+/// with the keys on the last line
+fn render(jobs: &[JobState], view: &View, area: Rect, buf: &mut Buffer) {
+    let [body, footer] = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(area);
+    let lines = match view.focus {
+        Some(job) => focus_lines(
+            &jobs[job],
+            view.scroll,
+            page_height(area.height as usize),
+            body.width,
+        ),
+        None => list_lines(jobs, view.selected, body),
+    };
+    Paragraph::new(lines).render(body, buf);
+
+    let keys = match view.focus {
+        Some(_) => "↑/↓ scroll · PgUp/PgDn page · End follow · Esc back",
+        None => "↑/↓ select · Enter output",
+    };
+    let running = jobs.iter().any(|job| !job.is_done());
+    let quit = if running { "q stop" } else { "q quit" };
+    Line::from(format!(" {} · {}", keys, quit).dim()).render(footer, buf);
+}
+
+fn list_lines(jobs: &[JobState], selected: usize, area: Rect) -> Vec<Line<'static>> {
+    let count = |f: fn(&JobState) -> bool| jobs.iter().filter(|job| f(job)).count();
+    let header = format!(
+        " maki run · {} targets · {} running · {} succeeded · {} failed",
+        jobs.len(),
+        count(|job| job.status == JobStatus::Running),
+        count(JobState::succeeded),
+        count(JobState::failed),
+    );
+    let mut lines = vec![Line::from(header.bold()), Line::default()];
+
+    // Keep the selected target on screen when they don't all fit
+    let visible = (area.height as usize).saturating_sub(2).max(1);
+    let first = selected.saturating_sub(visible - 1);
+    let width = jobs
+        .iter()
+        .map(|job| job.target.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    for (i, job) in jobs.iter().enumerate().skip(first).take(visible) {
+        let elapsed = match job.status {
+            JobStatus::Waiting | JobStatus::Skipped => String::new(),
+            _ => format_elapsed(job.elapsed().unwrap_or_default()),
+        };
+        let marker = if i == selected { "›" } else { " " };
+        let name = Span::raw(format!("{:<width$}", job.target));
+        let name = if i == selected { name.bold() } else { name };
+        let detail = match job.status {
+            JobStatus::Waiting => "waiting".to_string(),
+            JobStatus::Skipped => "skipped".to_string(),
+            _ => job
+                .last_line()
+                .map(str::to_string)
+                .or_else(|| multirun::outcome(job))
+                .unwrap_or_default(),
+        };
+        lines.push(Line::from(vec![
+            format!(" {} ", marker).into(),
+            icon(job),
+            " ".into(),
+            name,
+            format!("  {:>7}  ", elapsed).into(),
+            clean(&detail).dim(),
+        ]));
+    }
+    lines
+}
+
+fn focus_lines(job: &JobState, scroll: usize, page: usize, width: u16) -> Vec<Line<'static>> {
+    let elapsed = job.elapsed().map(format_elapsed).unwrap_or_default();
+    let header = format!(" {} · {} · {}", job.target, elapsed, job.command);
+    let mut lines = vec![
+        Line::from(vec![icon(job), " ".into(), clean(&header).bold()]),
+        Line::from("─".repeat(width as usize).dim()),
+    ];
+
+    let end = job.output.len().saturating_sub(scroll);
+    let start = end.saturating_sub(page);
+    lines.extend(
+        job.output
+            .range(start..end)
+            .map(|line| Line::from(clean(line))),
+    );
+    lines
+}
+
+/// A live view of a run on the terminal's alternate screen: every target's
+/// status, elapsed time, and last output line, with a target's full output
+/// a key away
+///
+/// When a target failed, the dashboard stays open once the run is over so
+/// its output can be read; otherwise, or when the run was stopped from it, it
+/// closes by itself.
+pub struct Dashboard {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+    view: View,
+    drawn: Option<Instant>,
+    stopped: bool,
+    _raw: RawMode,
+}
+
+impl Dashboard {
+    /// Take over the terminal until the dashboard is dropped
+    pub fn start() -> io::Result<Self> {
+        let raw = RawMode::enable()?;
+        let mut out = io::stdout();
+        execute!(out, terminal::EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(out))?;
+        terminal.hide_cursor()?;
+        Ok(Self {
+            terminal,
+            view: View::default(),
+            drawn: None,
+            stopped: false,
+            _raw: raw,
+        })
+    }
+
+    fn draw(&mut self, jobs: &[JobState]) -> io::Result<()> {
+        let view = &self.view;
+        self.terminal
+            .draw(|frame| render(jobs, view, frame.area(), frame.buffer_mut()))?;
+        self.drawn = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Act on what happens on the terminal within `timeout`; returns whether
+    /// to close, and whether to redraw
+    fn keys(&mut self, jobs: &[JobState], timeout: Duration) -> io::Result<(bool, bool)> {
+        let mut keys = Vec::new();
+        let mut happened = false;
+        let mut wait = timeout;
+        while event::poll(wait)? {
+            // A resize needs a redraw as much as a key does
+            happened = true;
+            if let event::Event::Key(pressed) = event::read()? {
+                keys.extend(key(pressed));
+            }
+            wait = Duration::ZERO;
+        }
+        let page = page_height(self.terminal.size()?.height as usize);
+        let quit = keys.iter().any(|&key| self.view.handle(key, jobs, page));
+        Ok((quit, happened))
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        let _ = self.terminal.show_cursor();
+        let _ = execute!(self.terminal.backend_mut(), terminal::LeaveAlternateScreen);
+    }
+}
+
+impl Renderer for Dashboard {
+    fn update(&mut self, jobs: &[JobState], event: Event) -> io::Result<Flow> {
+        let (quit, happened) = self.keys(jobs, Duration::ZERO)?;
+        if quit {
+            self.stopped = true;
+            return Ok(Flow::Stop);
+        }
+        let due = self.drawn.is_none_or(|at| at.elapsed() >= REDRAW);
+        if happened || due || !matches!(event, Event::Line { .. }) {
+            self.draw(jobs)?;
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn finish(&mut self, jobs: &[JobState]) -> io::Result<()> {
+        if self.stopped || jobs.iter().all(JobState::succeeded) {
+            return Ok(());
+        }
+        loop {
+            self.draw(jobs)?;
+            if self.keys(jobs, KEY_WAIT)?.0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::ExecuteOptions;
+    use crate::multirun::Job;
+
+    fn jobs(names: &[&str]) -> Vec<JobState> {
+        names
+            .iter()
+            .map(|name| {
+                JobState::new(&Job {
+                    target: name.to_string(),
+                    options: ExecuteOptions::default(),
+                    needs: Vec::new(),
+                })
+            })
+            .collect()
+    }
+
+    /// The text of the screen for `view`, `rows` lines of `cols` characters
+    fn screen(jobs: &[JobState], view: &View, (rows, cols): (u16, u16)) -> Vec<String> {
+        let area = Rect::new(0, 0, cols, rows);
+        let mut buf = Buffer::empty(area);
+        render(jobs, view, area, &mut buf);
+        (0..rows)
+            .map(|y| {
+                let line: String = (0..cols).map(|x| buf[(x, y)].symbol()).collect();
+                line.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_keys() {
+        let press = |code| key(KeyEvent::from(code));
+        assert_eq!(press(KeyCode::Up), Some(Key::Up));
+        assert_eq!(press(KeyCode::Char('j')), Some(Key::Down));
+        assert_eq!(press(KeyCode::PageUp), Some(Key::PageUp));
+        assert_eq!(press(KeyCode::Enter), Some(Key::Enter));
+        assert_eq!(press(KeyCode::Esc), Some(Key::Back));
+        assert_eq!(press(KeyCode::Char('q')), Some(Key::Quit));
+        assert_eq!(press(KeyCode::Char('x')), None);
+        assert_eq!(
+            key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+            Some(Key::Quit)
+        );
+    }
+
+    #[test]
+    fn test_select_focus_and_scroll() {
+        let mut jobs = jobs(&["build", "test"]);
+        for i in 0..10 {
+            jobs[1].output.push_back(format!("line {}", i));
+        }
+        let mut view = View::default();
+
+        view.handle(Key::Down, &jobs, 4);
+        view.handle(Key::Down, &jobs, 4);
+        assert_eq!(view.selected, 1);
+
+        view.handle(Key::Enter, &jobs, 4);
+        assert_eq!(view.focus, Some(1));
+        view.handle(Key::PageUp, &jobs, 4);
+        view.handle(Key::PageUp, &jobs, 4);
+        assert_eq!(view.scroll, 6, "can't scroll past the first line");
+        view.handle(Key::Down, &jobs, 4);
+        assert_eq!(view.scroll, 5);
+        view.handle(Key::End, &jobs, 4);
+        assert_eq!(view.scroll, 0);
+
+        view.handle(Key::Back, &jobs, 4);
+        assert_eq!(view.focus, None);
+        assert!(view.handle(Key::Quit, &jobs, 4));
+    }
+
+    #[test]
+    fn test_list_screen() {
+        let mut jobs = jobs(&["build", "lint"]);
+        jobs[0].status = JobStatus::Failed;
+        jobs[0].duration = Some(Duration::from_secs(3));
+        jobs[0]
+            .output
+            .push_back("\x1b[31merror\x1b[0m: no such file".to_string());
+        let view = View {
+            selected: 1,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            screen(&jobs, &view, (6, 60)),
+            vec![
+                " maki run · 2 targets · 0 running · 0 succeeded · 1 failed",
+                "",
+                "   ✗ build       3s  error: no such file",
+                " › · lint            waiting",
+                "",
+                " ↑/↓ select · Enter output · q stop",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_focus_screen_follows_the_output() {
+        let mut jobs = jobs(&["test"]);
+        jobs[0].duration = Some(Duration::from_secs(1));
+        jobs[0].status = JobStatus::Skipped;
+        for i in 0..5 {
+            jobs[0].output.push_back(format!("line {}\tend", i));
+        }
+        let mut view = View {
+            focus: Some(0),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            screen(&jobs, &view, (5, 20)),
+            vec![
+                "-  test · 1s · make",
+                "────────────────────",
+                "line 3    end",
+                "line 4    end",
+                " ↑/↓ scroll · PgUp/P",
+            ]
+        );
+
+        view.scroll = 3;
+        assert_eq!(
+            screen(&jobs, &view, (5, 20))[2..4],
+            ["line 0    end", "line 1    end"]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use colored::Colorize;
+
+use crate::error::MakiError;
+
+/// An error as maki prints it: what went wrong, what led to it, and for
+/// common failures what to do about it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    /// The errors underneath, outermost first, leaving out those the
+    /// message already quotes
+    pub causes: Vec<String>,
+    pub hint: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(err: &anyhow::Error) -> Self {
+        let message = err.to_string();
+        let mut causes: Vec<String> = Vec::new();
+        for cause in err.chain().skip(1).map(|e| e.to_string()) {
+            if !message.contains(&cause) && !causes.iter().any(|c| c.contains(&cause)) {
+                causes.push(cause);
+            }
+        }
+        let hint = err
+            .chain()
+            .find_map(|e| e.downcast_ref::<MakiError>())
+            .and_then(MakiError::hint)
+            .map(String::from);
+        Self {
+            message,
+            causes,
+            hint,
+        }
+    }
+
+    /// The lines printed on stderr, e.g.
+    ///
+    /// ```text
+    /// error: No Makefile found in /tmp/app
+    ///   hint: Create a Makefile there, pass one with -f FILE, or search subdirectories with -r
+    /// ```
+    pub fn render(&self) -> String {
+        let mut out = format!("{} {}\n", "error:".red().bold(), self.message);
+        for cause in &self.causes {
+            out.push_str(&format!("  {} {}\n", "caused by:".dimmed(), cause));
+        }
+        if let Some(ref hint) = self.hint {
+            out.push_str(&format!("  {} {}\n", "hint:".cyan().bold(), hint));
+        }
+        out
+    }
+}
+
+/// How to install `program`, found missing when maki tried to start it
+pub fn install_hint(program: &str) -> String {
+    let os_release = std::fs::read_to_string("/etc/os-release").unwrap_or_default();
+    install_hint_on(program, std::env::consts::OS, &distro(&os_release))
+}
+
+/// The distribution `/etc/os-release` names and those it is like, e.g.
+/// `["ubuntu", "debian"]`
+fn distro(os_release: &str) -> Vec<String> {
+    os_release
+        .lines()
+        .filter_map(|line| {
+            line.strip_prefix("ID=")
+                .or_else(|| line.strip_prefix("ID_LIKE="))
+        })
+        .flat_map(|ids| ids.trim_matches('"').split_whitespace())
+        .map(str::to_string)
+        .collect()
+}
+
+fn install_hint_on(program: &str, os: &str, distro: &[String]) -> String {
+    match (program, os) {
+        ("make", "macos") => {
+            "Install make with the Xcode command line tools: xcode-select --install".to_string()
+        }
+        ("make", "linux") => {
+            let command = distro.iter().find_map(|id| match id.as_str() {
+                "debian" | "ubuntu" => Some("sudo apt install make"),
+                "alpine" => Some("apk add make"),
+                "fedora" | "rhel" | "centos" => Some("sudo dnf install make"),
+                "arch" => Some("sudo pacman -S make"),
+                "suse" | "opensuse" => Some("sudo zypper install make"),
+                _ => None,
+            });
+            match command {
+                Some(command) => format!("Install make: {}", command),
+                None => {
+                    "Install make with your package manager, e.g. sudo apt install make".to_string()
+                }
+            }
+        }
+        ("make", "freebsd") => "Install GNU make: pkg install gmake".to_string(),
+        ("make", "windows") => {
+            "Install make, e.g. winget install GnuWin32.Make, and add it to PATH".to_string()
+        }
+        _ => format!("Install {}, or check that it is on PATH", program),
+    }
+}
+
+/// The name in `known` closest to `name`, when one is only a typo or two
+/// away from it
+pub fn closest<'a>(name: &str, known: &[&'a str]) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    let allowed = (name.chars().count() / 3).max(1);
+    known
+        .iter()
+        .map(|candidate| (distance(&name, &candidate.to_lowercase()), *candidate))
+        .filter(|&(distance, _)| distance <= allowed)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// How many characters must be inserted, removed, replaced, or swapped with
+/// the next one to turn `a` into `b`
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // d[i][j] is the distance between the first i of a and the first j of b
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runlog::strip_ansi;
+    use anyhow::Context;
+    use std::path::Path;
+
+    #[test]
+    fn test_closest() {
+        let known = ["build", "test", "deploy", "docs"];
+
+        assert_eq!(closest("biuld", &known), Some("build"));
+        assert_eq!(closest("Deploi", &known), Some("deploy"));
+        assert_eq!(closest("tst", &known), Some("test"));
+        assert_eq!(closest("release", &known), None);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("biuld", "build"), 1);
+    }
+
+    #[test]
+    fn test_install_hint() {
+        assert!(install_hint_on("make", "macos", &[]).contains("xcode-select --install"));
+        assert!(install_hint_on("make", "linux", &[]).contains("apt install make"));
+        let alpine = distro("NAME=\"Alpine Linux\"\nID=alpine\nVERSION_ID=3.20.0\n");
+        assert_eq!(
+            install_hint_on("make", "linux", &alpine),
+            "Install make: apk add make"
+        );
+        let rocky = distro("ID=\"rocky\"\nID_LIKE=\"rhel centos fedora\"\n");
+        assert_eq!(rocky, vec!["rocky", "rhel", "centos", "fedora"]);
+        assert_eq!(
+            install_hint_on("make", "linux", &rocky),
+            "Install make: sudo dnf install make"
+        );
+        assert_eq!(
+            install_hint_on("overmind", "linux", &[]),
+            "Install overmind, or check that it is on PATH"
+        );
+    }
+
+    #[test]
+    fn test_render_with_hint_and_causes() {
+        let err = anyhow::Error::new(MakiError::no_makefile(Path::new("/tmp/app")));
+        assert_eq!(
+            strip_ansi(&Diagnostic::new(&err).render()),
+            "error: No Makefile found in /tmp/app\n  hint: Create a Makefile there, pass one \
+             with -f FILE, or search subdirectories with -r\n"
+        );
+
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        let err = Err::<(), _>(io)
+            .context("Failed to read maki.toml")
+            .unwrap_err();
+        let diagnostic = Diagnostic::new(&err);
+        assert_eq!(diagnostic.causes, vec!["no such file"]);
+        assert_eq!(diagnostic.hint, None);
+    }
+}
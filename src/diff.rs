@@ -0,0 +1,438 @@
+use anyhow::{Result, anyhow};
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::makefile::{self, ParseOptions};
+use crate::remote::git;
+use crate::target::Target;
+
+/// A target as one side of the comparison has it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Version {
+    pub line: usize,
+    pub description: Option<String>,
+    pub recipe: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Added,
+    Removed,
+    /// Its description or recipe differs
+    Changed,
+}
+
+/// A target that differs between a git ref and the working tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TargetChange {
+    pub name: String,
+    /// The Makefile it is in, relative to the working directory
+    pub file: PathBuf,
+    pub status: Status,
+    /// At the ref, unless it was added since
+    pub before: Option<Version>,
+    /// In the working tree, unless it was removed
+    pub after: Option<Version>,
+}
+
+/// A line of a recipe diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// The targets that differ between `git_ref` and the working tree, by name
+/// within each Makefile
+///
+/// The working tree's Makefiles are `makefiles`. `search` is how they were
+/// found: `Some(recursive)` compares the ref's files called one of `names`
+/// too, so one deleted since shows its targets as removed, and `None` (a
+/// Makefile given with -f) compares only them.
+pub fn compare(
+    git_ref: &str,
+    base_dir: &Path,
+    makefiles: &[PathBuf],
+    search: Option<bool>,
+    names: &[String],
+    options: &ParseOptions,
+) -> Result<Vec<TargetChange>> {
+    git(
+        Some(base_dir),
+        &["rev-parse", "--verify", &format!("{}^{{commit}}", git_ref)],
+    )
+    .map_err(|e| anyhow!("Unknown git ref {} ({})", git_ref, e))?;
+
+    let relative = |path: &Path| path.strip_prefix(base_dir).unwrap_or(path).to_path_buf();
+    let mut files: BTreeSet<PathBuf> = makefiles.iter().map(|p| relative(p)).collect();
+    if let Some(recursive) = search {
+        let listed = git(
+            Some(base_dir),
+            &["ls-tree", "-r", "-z", "--name-only", git_ref],
+        )?;
+        files.extend(
+            listed
+                .split('\0')
+                .filter(|path| recursive || !path.contains('/'))
+                .filter(|path| makefile::is_makefile(path, names))
+                .map(PathBuf::from),
+        );
+    }
+
+    let mut changes = Vec::new();
+    for file in files {
+        let path = base_dir.join(&file);
+        let spec = format!("{}:./{}", git_ref, file.to_string_lossy());
+        let before = git(Some(base_dir), &["show", &spec]).ok();
+        let after = match path.exists() {
+            true => Some(makefile::read_makefile(&path)?.0),
+            false => None,
+        };
+        changes.extend(compare_file(
+            &file,
+            &path,
+            before.as_deref(),
+            after.as_deref(),
+            options,
+        )?);
+    }
+    Ok(changes)
+}
+
+/// The targets that differ between two versions of the Makefile at `path`,
+/// `None` where it doesn't exist
+fn compare_file(
+    file: &Path,
+    path: &Path,
+    before: Option<&str>,
+    after: Option<&str>,
+    options: &ParseOptions,
+) -> Result<Vec<TargetChange>> {
+    let before = versions(before, path, options)?;
+    let after = versions(after, path, options)?;
+
+    let names: BTreeSet<&String> = before.iter().chain(&after).map(|(name, _)| name).collect();
+    let find = |side: &[(String, Version)], name: &str| {
+        side.iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, version)| version.clone())
+    };
+
+    let mut changes = Vec::new();
+    for name in names {
+        let before = find(&before, name);
+        let after = find(&after, name);
+        let status = match (&before, &after) {
+            (None, Some(_)) => Status::Added,
+            (Some(_), None) => Status::Removed,
+            (Some(b), Some(a)) if b.description != a.description || b.recipe != a.recipe => {
+                Status::Changed
+            }
+            _ => continue,
+        };
+        changes.push(TargetChange {
+            name: name.clone(),
+            file: file.to_path_buf(),
+            status,
+            before,
+            after,
+        });
+    }
+    Ok(changes)
+}
+
+/// Each Makefile rule in `content` with its description and recipe, the
+/// first of rules with the same name
+fn versions(
+    content: Option<&str>,
+    path: &Path,
+    options: &ParseOptions,
+) -> Result<Vec<(String, Version)>> {
+    let Some(content) = content else {
+        return Ok(Vec::new());
+    };
+    let lines: Vec<&str> = content.lines().collect();
+    let targets: Vec<Target> = makefile::parse_makefile_content(content, path, options)?;
+
+    let mut versions: Vec<(String, Version)> = Vec::new();
+    for target in targets {
+        if versions.iter().any(|(name, _)| *name == target.name) {
+            continue;
+        }
+        let recipe = makefile::recipe_lines(&lines, target.line.saturating_sub(1));
+        versions.push((
+            target.name,
+            Version {
+                line: target.line,
+                description: target.description,
+                recipe: recipe.iter().map(|l| l.to_string()).collect(),
+            },
+        ));
+    }
+    Ok(versions)
+}
+
+/// The lines of `after` against `before`, keeping the longest run of lines
+/// both have
+pub fn line_diff<'a>(before: &'a [String], after: &'a [String]) -> Vec<DiffLine<'a>> {
+    // common[i][j] is how many lines before[i..] and after[j..] share
+    let mut common = vec![vec![0; after.len() + 1]; before.len() + 1];
+    for i in (0..before.len()).rev() {
+        for j in (0..after.len()).rev() {
+            common[i][j] = if before[i] == after[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < before.len() || j < after.len() {
+        if i < before.len() && j < after.len() && before[i] == after[j] {
+            lines.push(DiffLine::Same(&before[i]));
+            i += 1;
+            j += 1;
+        } else if i < before.len() && (j == after.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(DiffLine::Removed(&before[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(&after[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// Print the changes since `git_ref`, one line per target and the
+/// description and recipe differences below changed ones
+pub fn render(changes: &[TargetChange], git_ref: &str) {
+    if changes.is_empty() {
+        println!(
+            "{}",
+            format!("No target changes since {}.", git_ref).yellow()
+        );
+        return;
+    }
+
+    let count = |status: Status| changes.iter().filter(|c| c.status == status).count();
+    println!(
+        "{} ({} added, {} removed, {} changed)",
+        format!("Targets changed since {}", git_ref).bold(),
+        count(Status::Added),
+        count(Status::Removed),
+        count(Status::Changed)
+    );
+    println!();
+
+    let width = changes.iter().map(|c| c.name.len()).max().unwrap_or(0);
+    for change in changes {
+        let version = change.after.as_ref().or(change.before.as_ref());
+        let name = format!("{:<width$}", change.name);
+        let location = format!(
+            "{}:{}",
+            change.file.display(),
+            version.map_or(0, |v| v.line)
+        );
+        let marker = match change.status {
+            Status::Added => format!("+ {}", name).green(),
+            Status::Removed => format!("- {}", name).red(),
+            Status::Changed => format!("~ {}", name).yellow(),
+        };
+        let description = match change.status {
+            Status::Changed => None,
+            _ => version.and_then(|v| v.description.as_deref()),
+        };
+        match description {
+            Some(description) => println!("{}  {}  {}", marker, location.dimmed(), description),
+            None => println!("{}  {}", marker, location.dimmed()),
+        }
+
+        if let (Some(before), Some(after)) = (&change.before, &change.after) {
+            if before.description != after.description {
+                let show = |d: &Option<String>| d.clone().unwrap_or_else(|| "(none)".to_string());
+                println!(
+                    "    description: {} → {}",
+                    show(&before.description).red(),
+                    show(&after.description).green()
+                );
+            }
+            if before.recipe != after.recipe {
+                println!("    recipe:");
+                for line in line_diff(&before.recipe, &after.recipe) {
+                    match line {
+                        DiffLine::Same(text) => println!("        {}", text.trim()),
+                        DiffLine::Removed(text) => {
+                            println!("      {}", format!("- {}", text.trim()).red())
+                        }
+                        DiffLine::Added(text) => {
+                            println!("      {}", format!("+ {}", text.trim()).green())
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    const BEFORE: &str = "\
+## Build it
+build:
+\tcargo build
+
+## Ship it
+deploy: build
+\t./deploy.sh
+
+test:
+\tcargo test
+";
+
+    const AFTER: &str = "\
+## Build the app
+build:
+\tcargo build --release
+
+test:
+\tcargo test
+
+## Tag and push a release
+release:
+\tgit tag v1
+";
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    fn statuses(changes: &[TargetChange]) -> Vec<(&str, Status)> {
+        changes
+            .iter()
+            .map(|c| (c.name.as_str(), c.status))
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_file() {
+        let file = Path::new("Makefile");
+        let changes = compare_file(
+            file,
+            file,
+            Some(BEFORE),
+            Some(AFTER),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            statuses(&changes),
+            vec![
+                ("build", Status::Changed),
+                ("deploy", Status::Removed),
+                ("release", Status::Added),
+            ]
+        );
+        let build = &changes[0];
+        assert_eq!(
+            build.before.as_ref().unwrap().description.as_deref(),
+            Some("Build it")
+        );
+        assert_eq!(
+            build.after.as_ref().unwrap().recipe,
+            vec!["cargo build --release"]
+        );
+        // Only moving down the file isn't a change
+        assert!(!changes.iter().any(|c| c.name == "test"));
+
+        let added = compare_file(file, file, None, Some(BEFORE), &ParseOptions::default()).unwrap();
+        assert!(added.iter().all(|c| c.status == Status::Added));
+        assert_eq!(added.len(), 3);
+    }
+
+    #[test]
+    fn test_line_diff() {
+        let before = lines(&["a", "b", "c"]);
+        let after = lines(&["a", "x", "c", "d"]);
+
+        assert_eq!(
+            line_diff(&before, &after),
+            vec![
+                DiffLine::Same("a"),
+                DiffLine::Removed("b"),
+                DiffLine::Added("x"),
+                DiffLine::Same("c"),
+                DiffLine::Added("d"),
+            ]
+        );
+        assert_eq!(line_diff(&before, &[]).len(), 3);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_compare_against_a_ref() {
+        let dir = TempDir::new().unwrap();
+        let run = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(dir.path())
+                .args(["-c", "user.name=maki", "-c", "user.email=maki@example.com"])
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        fs::create_dir(dir.path().join("api")).unwrap();
+        fs::write(dir.path().join("Makefile"), BEFORE).unwrap();
+        fs::write(dir.path().join("api/Makefile"), "serve:\n\t@true\n").unwrap();
+        run(&["init", "--quiet", "--initial-branch", "main"]);
+        run(&["add", "."]);
+        run(&["commit", "--quiet", "-m", "first"]);
+        fs::write(dir.path().join("Makefile"), AFTER).unwrap();
+        fs::remove_file(dir.path().join("api/Makefile")).unwrap();
+
+        let names = makefile::default_makefile_names();
+        let makefiles = makefile::find_makefiles(dir.path(), true, &names);
+        let options = ParseOptions::default();
+        let changes =
+            compare("main", dir.path(), &makefiles, Some(true), &names, &options).unwrap();
+        assert_eq!(
+            statuses(&changes),
+            vec![
+                ("build", Status::Changed),
+                ("deploy", Status::Removed),
+                ("release", Status::Added),
+                ("serve", Status::Removed),
+            ]
+        );
+        assert_eq!(changes[3].file, PathBuf::from("api/Makefile"));
+
+        // Without -r, only the top-level Makefile is compared
+        let changes = compare(
+            "main",
+            dir.path(),
+            &makefiles[..1],
+            Some(false),
+            &names,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(changes.len(), 3);
+
+        let err =
+            compare("nope", dir.path(), &makefiles, Some(true), &names, &options).unwrap_err();
+        assert!(err.to_string().starts_with("Unknown git ref nope"));
+    }
+}
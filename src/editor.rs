@@ -0,0 +1,145 @@
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+use std::process::Command;
+
+/// The user's editor: $VISUAL, then $EDITOR, then a platform default
+pub fn editor() -> String {
+    std::env::var("VISUAL")
+        .ok()
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| {
+            if cfg!(windows) {
+                "notepad".to_string()
+            } else {
+                "vi".to_string()
+            }
+        })
+}
+
+/// Build the command that opens `file` at `line` in `editor`
+///
+/// Most terminal editors accept `+LINE FILE`; GUI editors that don't are
+/// given `FILE:LINE` instead.
+pub fn editor_command(editor: &str, file: &Path, line: usize) -> Vec<String> {
+    let mut args: Vec<String> = editor.split_whitespace().map(str::to_string).collect();
+    let program = args
+        .first()
+        .map(|p| {
+            Path::new(p)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default()
+        })
+        .unwrap_or_default();
+    let file = file.display().to_string();
+
+    match program.as_str() {
+        "code" | "code-insiders" | "codium" | "cursor" => {
+            args.push("-g".to_string());
+            args.push(format!("{}:{}", file, line));
+        }
+        "subl" | "zed" => args.push(format!("{}:{}", file, line)),
+        "notepad" => args.push(file),
+        _ => {
+            args.push(format!("+{}", line));
+            args.push(file);
+        }
+    }
+
+    args
+}
+
+/// Open a file at a line in the user's editor and wait for it to exit
+pub fn open(file: &Path, line: usize) -> Result<()> {
+    let editor = editor();
+    let command = editor_command(&editor, file, line);
+
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .status()
+        .with_context(|| format!("Failed to launch editor: {}", editor))?;
+
+    if !status.success() {
+        bail!("Editor exited with {}", status);
+    }
+
+    Ok(())
+}
+
+/// Build the command that shows `file` in the platform's file manager
+///
+/// Finder and Explorer select the file; on other systems its directory is
+/// opened with `xdg-open`.
+pub fn reveal_command(file: &Path) -> Vec<String> {
+    let file_arg = file.display().to_string();
+    if cfg!(target_os = "macos") {
+        vec!["open".to_string(), "-R".to_string(), file_arg]
+    } else if cfg!(windows) {
+        vec!["explorer".to_string(), format!("/select,{}", file_arg)]
+    } else {
+        let dir = file
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        vec!["xdg-open".to_string(), dir.display().to_string()]
+    }
+}
+
+/// Show a file in the platform's file manager
+pub fn reveal(file: &Path) -> Result<()> {
+    let command = reveal_command(file);
+
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .status()
+        .with_context(|| format!("Failed to launch {}", command[0]))?;
+
+    // Explorer exits with 1 even when it succeeds
+    if !status.success() && !cfg!(windows) {
+        bail!("{} exited with {}", command[0], status);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terminal_editor_command() {
+        let cmd = editor_command("nvim", Path::new("Makefile"), 12);
+        assert_eq!(cmd, vec!["nvim", "+12", "Makefile"]);
+    }
+
+    #[test]
+    fn test_editor_with_arguments() {
+        let cmd = editor_command("emacs -nw", Path::new("Makefile"), 3);
+        assert_eq!(cmd, vec!["emacs", "-nw", "+3", "Makefile"]);
+    }
+
+    #[test]
+    fn test_gui_editor_commands() {
+        let cmd = editor_command("/usr/local/bin/code --wait", Path::new("Makefile"), 7);
+        assert_eq!(
+            cmd,
+            vec!["/usr/local/bin/code", "--wait", "-g", "Makefile:7"]
+        );
+
+        let cmd = editor_command("subl", Path::new("Makefile"), 7);
+        assert_eq!(cmd, vec!["subl", "Makefile:7"]);
+    }
+
+    #[test]
+    fn test_reveal_command() {
+        let cmd = reveal_command(Path::new("/src/app/Makefile"));
+        if cfg!(target_os = "macos") {
+            assert_eq!(cmd, vec!["open", "-R", "/src/app/Makefile"]);
+        } else if cfg!(windows) {
+            assert_eq!(cmd, vec!["explorer", "/select,/src/app/Makefile"]);
+        } else {
+            assert_eq!(cmd, vec!["xdg-open", "/src/app"]);
+        }
+    }
+}
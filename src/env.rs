@@ -0,0 +1,374 @@
+use anyhow::{Context, Result, bail};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::quote;
+
+/// Where an environment variable for a run came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvSource {
+    Config,
+    /// A `.env` or `.envrc` next to the Makefile, with `--auto-env`
+    AutoEnv,
+    EnvFile,
+    Var,
+    Scratch,
+}
+
+impl EnvSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Config => "config",
+            Self::AutoEnv => "auto-env",
+            Self::EnvFile => "env-file",
+            Self::Var => "--var",
+            Self::Scratch => "--scratch",
+        }
+    }
+}
+
+/// An environment variable maki sets for the make process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvVar {
+    pub name: String,
+    pub value: String,
+    pub source: EnvSource,
+}
+
+/// How a variable differs from the parent shell's environment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvChange {
+    Added,
+    Overridden { previous: String },
+}
+
+/// Parse a `NAME=VALUE` pair, as given to --var
+pub fn parse_var(s: &str) -> std::result::Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if is_valid_name(name.trim()) => {
+            Ok((name.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected NAME=VALUE, got '{}'", s)),
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parse dotenv-style content: `NAME=VALUE` lines, with optional `export`
+/// prefixes, quoted values, comments, and blank lines
+pub fn parse_env_file(content: &str) -> Result<Vec<(String, String)>> {
+    let mut vars = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Ok((name, value)) = parse_var(line) else {
+            bail!("line {}: expected NAME=VALUE", index + 1);
+        };
+
+        vars.push((name, unquote(value.trim()).to_string()));
+    }
+
+    Ok(vars)
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
+
+/// Load an env file from disk
+pub fn load_env_file(path: &Path) -> Result<Vec<(String, String)>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read env file: {}", path.display()))?;
+
+    parse_env_file(&content).with_context(|| format!("Invalid env file: {}", path.display()))
+}
+
+/// The files next to a Makefile that `--auto-env` reads, the later winning
+pub const AUTO_ENV_FILES: [&str; 2] = [".env", ".envrc"];
+
+/// The `export NAME=VALUE` and `NAME=VALUE` lines of a direnv `.envrc`; the
+/// rest, such as `dotenv` or `layout python`, and values that expand
+/// variables or run commands need direnv's shell, so they are skipped
+pub fn parse_envrc(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (name, value) = parse_var(line).ok()?;
+            let value = value.trim();
+            let literal = value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'');
+            if !literal && value.contains(['$', '`']) {
+                return None;
+            }
+            Some((name, unquote(value).to_string()))
+        })
+        .collect()
+}
+
+/// A `.env` or `.envrc` that `--auto-env` read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutoEnvFile {
+    pub path: PathBuf,
+    pub vars: Vec<(String, String)>,
+}
+
+/// The `.env` and `.envrc` in `dir`, in the order they apply
+pub fn load_auto_env(dir: &Path) -> Result<Vec<AutoEnvFile>> {
+    let mut loaded = Vec::new();
+    for name in AUTO_ENV_FILES {
+        let path = dir.join(name);
+        if !path.is_file() {
+            continue;
+        }
+        let vars = match name {
+            ".envrc" => parse_envrc(
+                &fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read env file: {}", path.display()))?,
+            ),
+            _ => load_env_file(&path)?,
+        };
+        loaded.push(AutoEnvFile { path, vars });
+    }
+    Ok(loaded)
+}
+
+/// Add the variables of the Makefile's directory to a run's environment:
+/// they win over `[env]`, and --env-file, --var, and --scratch over them
+pub fn with_auto_env(env: &[EnvVar], vars: &[(String, String)]) -> Vec<EnvVar> {
+    let mut env = env.to_vec();
+    for (name, value) in vars {
+        let var = EnvVar {
+            name: name.clone(),
+            value: value.clone(),
+            source: EnvSource::AutoEnv,
+        };
+        match env.iter_mut().find(|v| v.name == *name) {
+            Some(existing) if matches!(existing.source, EnvSource::Config | EnvSource::AutoEnv) => {
+                *existing = var
+            }
+            Some(_) => {}
+            None => env.push(var),
+        }
+    }
+    env
+}
+
+/// Merge the environment for a run. Later sources win: config env, then the
+/// env file, then --var.
+pub fn collect(
+    config_env: &BTreeMap<String, String>,
+    env_file: &[(String, String)],
+    vars: &[(String, String)],
+) -> Vec<EnvVar> {
+    let mut merged: Vec<EnvVar> = Vec::new();
+
+    let sources = [
+        (EnvSource::Config, config_env.iter().collect::<Vec<_>>()),
+        (EnvSource::EnvFile, env_file.iter().map(|(k, v)| (k, v)).collect()),
+        (EnvSource::Var, vars.iter().map(|(k, v)| (k, v)).collect()),
+    ];
+
+    for (source, pairs) in sources {
+        for (name, value) in pairs {
+            let var = EnvVar {
+                name: name.clone(),
+                value: value.clone(),
+                source,
+            };
+            match merged.iter_mut().find(|v| v.name == *name) {
+                Some(existing) => *existing = var,
+                None => merged.push(var),
+            }
+        }
+    }
+
+    merged
+}
+
+/// Compare the run's environment with the parent's, keeping only variables
+/// that are added or changed
+pub fn diff(
+    env: &[EnvVar],
+    parent: impl Fn(&str) -> Option<String>,
+) -> Vec<(&EnvVar, EnvChange)> {
+    env.iter()
+        .filter_map(|var| match parent(&var.name) {
+            None => Some((var, EnvChange::Added)),
+            Some(previous) if previous != var.value => {
+                Some((var, EnvChange::Overridden { previous }))
+            }
+            Some(_) => None,
+        })
+        .collect()
+}
+
+/// Print how the run's environment differs from the current shell
+pub fn print_diff(env: &[EnvVar]) {
+    let changes = diff(env, |name| std::env::var(name).ok());
+
+    if changes.is_empty() {
+        println!("{} no changes from the shell", "Environment:".cyan());
+        return;
+    }
+
+    println!("{}", "Environment:".cyan());
+    for (var, change) in changes {
+        let source = format!("({})", var.source.name());
+        match change {
+            EnvChange::Added => {
+                println!(
+                    "  {} {} {}",
+                    "+".green(),
+                    quote::assignment(&var.name, &var.value),
+                    source.dimmed()
+                );
+            }
+            EnvChange::Overridden { previous } => {
+                println!(
+                    "  {} {} {} {}",
+                    "~".yellow(),
+                    quote::assignment(&var.name, &var.value),
+                    format!("was {}", quote::word(&previous)).dimmed(),
+                    source.dimmed()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_parse_var() {
+        assert_eq!(parse_var("ENV=prod"), Ok(pair("ENV", "prod")));
+        assert_eq!(parse_var("URL=a=b"), Ok(pair("URL", "a=b")));
+        assert_eq!(parse_var("EMPTY="), Ok(pair("EMPTY", "")));
+        assert!(parse_var("novalue").is_err());
+        assert!(parse_var("=value").is_err());
+        assert!(parse_var("1X=value").is_err());
+    }
+
+    #[test]
+    fn test_parse_env_file() {
+        let content = "# comment\n\nexport A=1\nB=\"two words\"\nC='3'\nD = 4\n";
+        let vars = parse_env_file(content).unwrap();
+
+        assert_eq!(
+            vars,
+            vec![pair("A", "1"), pair("B", "two words"), pair("C", "3"), pair("D", "4")]
+        );
+    }
+
+    #[test]
+    fn test_parse_env_file_reports_line() {
+        let err = parse_env_file("A=1\nbogus\n").unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+    }
+
+    #[test]
+    fn test_collect_later_sources_win() {
+        let config_env = BTreeMap::from([pair("A", "config"), pair("B", "config")]);
+        let env = collect(&config_env, &[pair("B", "file")], &[pair("A", "var")]);
+
+        assert_eq!(env.len(), 2);
+        assert_eq!(env[0].value, "var");
+        assert_eq!(env[0].source, EnvSource::Var);
+        assert_eq!(env[1].value, "file");
+        assert_eq!(env[1].source, EnvSource::EnvFile);
+    }
+
+    #[test]
+    fn test_auto_env_from_envrc_and_env() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".env"), "PORT=3000\nDB=dev\n").unwrap();
+        fs::write(
+            dir.path().join(".envrc"),
+            "dotenv\nexport DB=\"postgres://localhost\"\nPATH_add bin\n\
+             export HOME_BIN=$HOME/bin\nGREETING='$not expanded'\n",
+        )
+        .unwrap();
+
+        let loaded = load_auto_env(dir.path()).unwrap();
+        assert_eq!(
+            loaded,
+            vec![
+                AutoEnvFile {
+                    path: dir.path().join(".env"),
+                    vars: vec![pair("PORT", "3000"), pair("DB", "dev")],
+                },
+                AutoEnvFile {
+                    path: dir.path().join(".envrc"),
+                    vars: vec![
+                        pair("DB", "postgres://localhost"),
+                        pair("GREETING", "$not expanded")
+                    ],
+                },
+            ]
+        );
+
+        let config_env = BTreeMap::from([pair("PORT", "80")]);
+        let env = collect(&config_env, &[], &[pair("DB", "var")]);
+        let env = with_auto_env(&env, &[pair("PORT", "3000"), pair("DB", "dev")]);
+        let sources: Vec<(&str, &str, EnvSource)> = env
+            .iter()
+            .map(|v| (v.name.as_str(), v.value.as_str(), v.source))
+            .collect();
+        assert_eq!(
+            sources,
+            [
+                ("PORT", "3000", EnvSource::AutoEnv),
+                ("DB", "var", EnvSource::Var)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_against_parent() {
+        let env = collect(
+            &BTreeMap::new(),
+            &[],
+            &[pair("NEW", "1"), pair("SAME", "x"), pair("CHANGED", "2")],
+        );
+        let parent = |name: &str| match name {
+            "SAME" => Some("x".to_string()),
+            "CHANGED" => Some("1".to_string()),
+            _ => None,
+        };
+
+        let changes = diff(&env, parent);
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].0.name, "NEW");
+        assert_eq!(changes[0].1, EnvChange::Added);
+        assert_eq!(changes[1].0.name, "CHANGED");
+        assert_eq!(
+            changes[1].1,
+            EnvChange::Overridden {
+                previous: "1".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use serde::Serialize;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::diagnostic;
+
+/// Errors returned by maki's parsing, caching, and execution APIs
+#[derive(Debug, Error, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MakiError {
+    /// A Makefile could not be read or parsed
+    #[error("{message}")]
+    Parse {
+        file: PathBuf,
+        /// The offending line, when the error is tied to one
+        line: Option<usize>,
+        message: String,
+        /// What to do about it, when maki can tell
+        #[serde(skip_serializing_if = "Option::is_none")]
+        hint: Option<String>,
+    },
+
+    /// The target cache could not be read or written
+    #[error("{message}")]
+    Cache { message: String },
+
+    /// make could not be started, or exited unsuccessfully
+    #[error("{message}")]
+    Exec {
+        target: String,
+        /// make's exit code, or None if it never ran
+        status: Option<i32>,
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        hint: Option<String>,
+    },
+
+    /// No Makefile or target could be found
+    #[error("{message}")]
+    Discovery {
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        hint: Option<String>,
+    },
+}
+
+impl MakiError {
+    /// Short machine-readable name of the failure class
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Parse { .. } => "parse",
+            Self::Cache { .. } => "cache",
+            Self::Exec { .. } => "exec",
+            Self::Discovery { .. } => "discovery",
+        }
+    }
+
+    /// What to do about the error, when maki can tell
+    pub fn hint(&self) -> Option<&str> {
+        match self {
+            Self::Parse { hint, .. } | Self::Exec { hint, .. } | Self::Discovery { hint, .. } => {
+                hint.as_deref()
+            }
+            Self::Cache { .. } => None,
+        }
+    }
+
+    /// An error for a directory without any Makefile
+    pub fn no_makefile(dir: &Path) -> Self {
+        Self::Discovery {
+            message: format!("No Makefile found in {}", dir.display()),
+            hint: Some(
+                "Create a Makefile there, pass one with -f FILE, or search subdirectories with -r"
+                    .to_string(),
+            ),
+        }
+    }
+
+    /// An error for a `-f` Makefile that doesn't exist
+    pub fn makefile_not_found(file: &Path) -> Self {
+        Self::Discovery {
+            message: format!("Makefile not found: {}", file.display()),
+            hint: Some("Check the path given to -f; it is relative to --cwd".to_string()),
+        }
+    }
+
+    /// An error for a target none of `known` is called, suggesting the
+    /// closest one
+    pub fn target_not_found(name: &str, known: &[&str]) -> Self {
+        Self::Discovery {
+            message: format!("Target not found: {}", name),
+            hint: Some(match diagnostic::closest(name, known) {
+                Some(similar) => format!("Did you mean {}?", similar),
+                None => "Run maki list to see the targets".to_string(),
+            }),
+        }
+    }
+
+    /// An error for a Makefile that could not be read
+    pub fn unreadable(file: impl Into<PathBuf>, err: std::io::Error) -> Self {
+        let file = file.into();
+        let hint = match err.kind() {
+            ErrorKind::PermissionDenied => {
+                Some(format!("Check who may read it: ls -l {}", file.display()))
+            }
+            _ => None,
+        };
+        Self::Parse {
+            message: format!("Failed to read Makefile: {} ({})", file.display(), err),
+            file,
+            line: None,
+            hint,
+        }
+    }
+
+    /// An error for a program that could not be started, with how to
+    /// install it when it is missing
+    pub fn spawn_failed(target: &str, command: &str, err: std::io::Error) -> Self {
+        let program = command.split_whitespace().next().unwrap_or(command);
+        let hint = (err.kind() == ErrorKind::NotFound).then(|| diagnostic::install_hint(program));
+        Self::Exec {
+            target: target.to_string(),
+            status: None,
+            message: format!("Failed to execute '{}' ({})", command, err),
+            hint,
+        }
+    }
+
+    /// An error for a target whose recipe only make can run, with make not
+    /// installed
+    pub fn make_missing(target: &str) -> Self {
+        Self::Exec {
+            target: target.to_string(),
+            status: None,
+            message: format!(
+                "make is not installed, and {} needs it: its recipe has prerequisites or make variables",
+                target
+            ),
+            hint: Some(diagnostic::install_hint("make")),
+        }
+    }
+
+    /// An error for make exiting with a non-zero status
+    pub fn exec_failed(target: &str, status: std::process::ExitStatus) -> Self {
+        let code = status.code();
+        Self::Exec {
+            target: target.to_string(),
+            status: code,
+            message: match code {
+                Some(code) => format!("'make {}' exited with status {}", target, code),
+                None => format!("'make {}' was terminated by a signal", target),
+            },
+            hint: None,
+        }
+    }
+}
+
+/// Result type used across maki's library modules
+pub type Result<T> = std::result::Result<T, MakiError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_kinds() {
+        let err = MakiError::Discovery {
+            message: "No Makefile found in /tmp".to_string(),
+            hint: None,
+        };
+        assert_eq!(err.kind(), "discovery");
+        assert_eq!(err.to_string(), "No Makefile found in /tmp");
+
+        let err = MakiError::Cache {
+            message: "Failed to write cache file".to_string(),
+        };
+        assert_eq!(err.kind(), "cache");
+    }
+
+    #[test]
+    fn test_unreadable_is_parse_error() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "not found");
+        let err = MakiError::unreadable("Makefile", io);
+
+        assert_eq!(err.kind(), "parse");
+        assert_eq!(err.to_string(), "Failed to read Makefile: Makefile (not found)");
+    }
+
+    #[test]
+    fn test_error_serializes_with_kind() {
+        let err = MakiError::Exec {
+            target: "build".to_string(),
+            status: Some(2),
+            message: "'make build' exited with status 2".to_string(),
+            hint: None,
+        };
+
+        let json = serde_json::to_value(&err).unwrap();
+        assert_eq!(json["kind"], "exec");
+        assert_eq!(json["target"], "build");
+        assert_eq!(json["status"], 2);
+        assert!(json.get("hint").is_none());
+    }
+
+    #[test]
+    fn test_hints() {
+        let err = MakiError::target_not_found("biuld", &["build", "test"]);
+        assert_eq!(err.to_string(), "Target not found: biuld");
+        assert_eq!(err.hint(), Some("Did you mean build?"));
+        let err = MakiError::target_not_found("release", &["build", "test"]);
+        assert_eq!(err.hint(), Some("Run maki list to see the targets"));
+
+        let io = std::io::Error::new(ErrorKind::PermissionDenied, "denied");
+        let err = MakiError::unreadable("Makefile", io);
+        assert_eq!(err.hint(), Some("Check who may read it: ls -l Makefile"));
+
+        let io = std::io::Error::new(ErrorKind::NotFound, "not found");
+        let err = MakiError::spawn_failed("build", "make build", io);
+        assert_eq!(err.hint(), Some(diagnostic::install_hint("make").as_str()));
+        assert_eq!(
+            serde_json::to_value(&err).unwrap()["hint"],
+            diagnostic::install_hint("make")
+        );
+
+        let err = MakiError::make_missing("build");
+        assert_eq!(err.hint(), Some(diagnostic::install_hint("make").as_str()));
+
+        let io = std::io::Error::other("busy");
+        assert_eq!(MakiError::spawn_failed("build", "make", io).hint(), None);
+    }
+}
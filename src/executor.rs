@@ -1,11 +1,63 @@
-use anyhow::{Context, Result};
 use colored::Colorize;
+use std::io::{Read, Write};
 use std::path::Path;
-use std::process::{Command, ExitStatus, Stdio};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+use crate::artifact::{self, Artifact, ArtifactPolicy};
+use crate::env::{self, EnvVar};
+use crate::error::{MakiError, Result};
+use crate::heartbeat::{Activity, Heartbeat};
+use crate::infer;
+use crate::makefile::RecipeLine;
+use crate::noop::NoopFilter;
+use crate::problems::{self, Report};
+use crate::providers;
+#[cfg(unix)]
+use crate::pty;
+use crate::quote;
+use crate::record;
+use crate::runlog::{self, RunLog};
+use crate::secret::Redactor;
+use crate::target::Provider;
+#[cfg(unix)]
+use crate::terminal::RawMode;
+use crate::webhook::Webhook;
+
+/// Error for when the command could not be started at all
+fn spawn_error(target: &str, command: &str, err: std::io::Error) -> MakiError {
+    MakiError::spawn_failed(target, command, err)
+}
+
+/// Error for when the run's output could not be logged
+fn log_error(target: &str, err: impl std::fmt::Display) -> MakiError {
+    MakiError::Exec {
+        target: target.to_string(),
+        status: None,
+        message: format!("Failed to write run log for '{}' ({:#})", target, err),
+        hint: None,
+    }
+}
+
+/// What make does with the target instead of running its recipe
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MakeMode {
+    /// Bring it up to date, running recipes as needed
+    #[default]
+    Run,
+    /// Only report through the exit status whether it is up to date (`-q`)
+    Question,
+    /// Mark it up to date without running recipes (`-t`)
+    Touch,
+}
 
 /// Options for executing a make target
 #[derive(Debug, Clone, Default)]
 pub struct ExecuteOptions {
+    /// How the target is run (make, a Procfile runner, docker compose)
+    pub provider: Provider,
     /// Print the command without executing
     pub dry_run: bool,
     /// Always print the command before executing
@@ -14,17 +66,57 @@ pub struct ExecuteOptions {
     pub cwd: Option<std::path::PathBuf>,
     /// Custom Makefile to use
     pub makefile: Option<std::path::PathBuf>,
+    /// Directories make searches for included makefiles (`-I`)
+    pub include_dirs: Vec<std::path::PathBuf>,
+    /// Whether to run the target, or only check or touch it
+    pub mode: MakeMode,
+    /// Prerequisites maki has already run, which make is told not to remake
+    /// (`-o`)
+    pub assume_old: Vec<String>,
     /// Variables to pass to make (e.g., [("V", "patch"), ("ENV", "prod")])
     pub variables: Vec<(String, String)>,
+    /// Names of the variables whose values are secret, masked wherever the
+    /// command is shown
+    pub secrets: Vec<String>,
+    /// Environment variables to set for make
+    pub env: Vec<EnvVar>,
+    /// Print how the environment differs from the current shell
+    pub verbose: bool,
+    /// Directory to save the run's output to, as raw and plain-text logs
+    pub log_dir: Option<std::path::PathBuf>,
+    /// Show a status line once the run has printed nothing for this long
+    pub heartbeat: Option<Duration>,
+    /// Replace make's "Nothing to be done" message with a status of its own
+    pub quiet_noop: bool,
+    /// Files to keep when the run's output mentions them
+    pub artifacts: ArtifactPolicy,
+    /// Collect the errors the run's output points at, for `maki errors`
+    pub errors: bool,
+    /// The target's recipe, which a dry run lists as make would echo it
+    pub plan: Vec<RecipeLine>,
+    /// List the recipe's silent commands too, marked as such
+    pub show_silent: bool,
+    /// Where to post a summary of the run once it ends
+    pub webhooks: Vec<Webhook>,
+    /// Directory to save a recording of the run's terminal to (`--record`)
+    pub record: Option<std::path::PathBuf>,
+    /// When the output passes through maki, run the target on a terminal of
+    /// its own all the same, so it prints as it would on maki's (`--pty`)
+    pub pty: bool,
 }
 
 /// Execute a make target
 pub fn execute_target(target: &str, options: &ExecuteOptions) -> Result<ExitStatus> {
-    let cmd = build_command(target, options);
-    let cmd_str = format_command(&cmd);
+    let cmd_str = shown_command(target, options);
 
     if options.dry_run {
         println!("{} {}", "Would run:".yellow(), cmd_str);
+        for line in plan_lines(&options.plan, options.show_silent) {
+            println!("{}", line);
+        }
+        if options.verbose {
+            env::print_diff(&options.env);
+        }
         return Ok(ExitStatus::default());
     }
 
@@ -32,11 +124,59 @@ pub fn execute_target(target: &str, options: &ExecuteOptions) -> Result<ExitStat
         println!("{} {}", "Running:".green(), cmd_str);
     }
 
+    if options.verbose {
+        env::print_diff(&options.env);
+    }
+
     run_make_command(target, options)
 }
 
+/// A dry run's recipe as the run would show it: the commands make echoes,
+/// then either the silent ones dimmed and marked, or how many there are
+pub fn plan_lines(plan: &[RecipeLine], show_silent: bool) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut hidden = 0;
+    for line in plan {
+        // A `.ONESHELL` recipe is one command of several lines
+        let text = line.text.replace('\n', "\n  ");
+        if !line.silent {
+            lines.push(format!("  {}", text));
+        } else if show_silent {
+            lines.push(format!("  {} (silent)", text).dimmed().to_string());
+        } else {
+            hidden += 1;
+        }
+    }
+    if hidden > 0 {
+        let note = format!("  ({} silent command(s); --show-silent lists them)", hidden);
+        lines.push(note.dimmed().to_string());
+    }
+    lines
+}
+
 /// Build the command arguments
-fn build_command(target: &str, options: &ExecuteOptions) -> Vec<String> {
+pub fn build_command(target: &str, options: &ExecuteOptions) -> Vec<String> {
+    match options.provider {
+        Provider::Make => {}
+        Provider::Procfile => {
+            return vec![
+                providers::procfile_runner().to_string(),
+                "start".to_string(),
+                target.to_string(),
+            ];
+        }
+        Provider::Compose => {
+            return ["docker", "compose", "up", target]
+                .iter()
+                .map(|s| s.to_string())
+                .collect();
+        }
+        Provider::Inferred => {
+            let dir = options.cwd.as_deref().unwrap_or(Path::new("."));
+            return infer::command(target, dir).unwrap_or_else(|| vec![target.to_string()]);
+        }
+    }
+
     let mut args = vec!["make".to_string()];
 
     if let Some(ref makefile) = options.makefile {
@@ -44,6 +184,24 @@ fn build_command(target: &str, options: &ExecuteOptions) -> Vec<String> {
         args.push(makefile.display().to_string());
     }
 
+    // make passes -I on to recursive makes through MAKEFLAGS, so it has to
+    // hold up after `$(MAKE) -C dir`
+    for dir in &options.include_dirs {
+        args.push("-I".to_string());
+        args.push(run_path(dir, options).display().to_string());
+    }
+
+    for prerequisite in &options.assume_old {
+        args.push("-o".to_string());
+        args.push(prerequisite.clone());
+    }
+
+    match options.mode {
+        MakeMode::Run => {}
+        MakeMode::Question => args.push("-q".to_string()),
+        MakeMode::Touch => args.push("-t".to_string()),
+    }
+
     args.push(target.to_string());
 
     // Add variables (e.g., V=patch, ENV=prod)
@@ -54,53 +212,548 @@ fn build_command(target: &str, options: &ExecuteOptions) -> Vec<String> {
     args
 }
 
-/// Format command for display
-fn format_command(cmd: &[String]) -> String {
-    cmd.join(" ")
+/// Build the command arguments, prefixed with `env NAME=VALUE ...` when the
+/// run sets environment variables, for running it through a shell
+pub fn build_command_with_env(target: &str, options: &ExecuteOptions) -> Vec<String> {
+    let cmd = build_command(target, options);
+    if options.env.is_empty() {
+        return cmd;
+    }
+
+    std::iter::once("env".to_string())
+        .chain(options.env.iter().map(|v| format!("{}={}", v.name, v.value)))
+        .chain(cmd)
+        .collect()
+}
+
+/// A path given relative to the run's directory, made absolute
+fn run_path(path: &Path, options: &ExecuteOptions) -> std::path::PathBuf {
+    let path = match options.cwd {
+        Some(ref cwd) => cwd.join(path),
+        None => path.to_path_buf(),
+    };
+    std::path::absolute(&path).unwrap_or(path)
+}
+
+/// What a secret variable's value is shown as
+pub const MASK: &str = "***";
+
+/// The full command line for a target, as it would be typed in a shell,
+/// with secret values masked
+pub fn command_line(target: &str, options: &ExecuteOptions) -> String {
+    format_command(&build_command_with_env(target, &masked(options)))
+}
+
+/// The command maki runs for a target, as it is shown: in the Running
+/// banner, dry runs, errors, run logs, and webhooks
+pub fn shown_command(target: &str, options: &ExecuteOptions) -> String {
+    format_command(&build_command(target, &masked(options)))
+}
+
+/// `options` with the values of secret variables replaced by [`MASK`]
+fn masked(options: &ExecuteOptions) -> ExecuteOptions {
+    let mut masked = options.clone();
+    for (name, value) in &mut masked.variables {
+        if options.secrets.contains(name) {
+            *value = MASK.to_string();
+        }
+    }
+    for var in &mut masked.env {
+        if options.secrets.contains(&var.name) {
+            var.value = MASK.to_string();
+        }
+    }
+    masked
+}
+
+/// What masks the values of the run's secret variables in its output
+fn redactor(options: &ExecuteOptions) -> Redactor {
+    let variables = options
+        .variables
+        .iter()
+        .filter(|(name, _)| options.secrets.contains(name))
+        .map(|(_, value)| value.clone());
+    let env = options
+        .env
+        .iter()
+        .filter(|var| options.secrets.contains(&var.name))
+        .map(|var| var.value.clone());
+    Redactor::new(variables.chain(env))
+}
+
+/// Format command for display, quoting the words a shell would split or expand
+pub fn format_command(cmd: &[String]) -> String {
+    quote::command(cmd)
+}
+
+/// The output of a run whose stdout and stderr were captured
+#[derive(Debug, Clone)]
+pub struct CapturedRun {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// Execute a target with its output captured instead of shown, for `--json`
+///
+/// Nothing is printed and stdin is closed, since no one is there to answer
+/// prompts.
+pub fn execute_target_captured(target: &str, options: &ExecuteOptions) -> Result<CapturedRun> {
+    let args = build_command(target, options);
+    let command = shown_command(target, options);
+
+    let output = make_command(&args, options)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| spawn_error(target, &command, e))?;
+
+    // The output ends up in JSON, which may well be kept
+    let redactor = redactor(options);
+    Ok(CapturedRun {
+        status: output.status,
+        stdout: redactor.redact(&output.stdout),
+        stderr: redactor.redact(&output.stderr),
+    })
+}
+
+/// Start a target with its output piped back and stdin closed, for runs of
+/// several targets at once
+pub fn spawn_target_piped(target: &str, options: &ExecuteOptions) -> Result<Child> {
+    let args = build_command(target, options);
+    let command = shown_command(target, options);
+
+    make_command(&args, options)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| spawn_error(target, &command, e))
+}
+
+/// Start the command in a session of its own, detached from the terminal so
+/// that it outlives maki, with its output going to `log`
+pub fn spawn_target_detached(
+    target: &str,
+    options: &ExecuteOptions,
+    log: &std::fs::File,
+) -> Result<Child> {
+    let args = build_command(target, options);
+    let command = shown_command(target, options);
+    let mut cmd = make_command(&args, options);
+
+    let stdout = log
+        .try_clone()
+        .map_err(|e| spawn_error(target, &command, e))?;
+    let stderr = log
+        .try_clone()
+        .map_err(|e| spawn_error(target, &command, e))?;
+    cmd.stdin(Stdio::null()).stdout(stdout).stderr(stderr);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: setsid is async-signal-safe, as pre_exec requires
+        unsafe {
+            cmd.pre_exec(|| {
+                libc::setsid();
+                Ok(())
+            });
+        }
+    }
+
+    cmd.spawn().map_err(|e| spawn_error(target, &command, e))
 }
 
 /// Run the make command
 fn run_make_command(target: &str, options: &ExecuteOptions) -> Result<ExitStatus> {
+    let args = build_command(target, options);
+    let command = shown_command(target, options);
+    let mut cmd = make_command(&args, options);
+
+    if let Some(ref dir) = options.record {
+        return run_recorded(cmd, target, &command, dir);
+    }
+
+    if options.log_dir.is_some()
+        || options.heartbeat.is_some()
+        || quiet_noop(options)
+        || options.artifacts.is_enabled()
+        || options.errors
+    {
+        return run_piped(cmd, target, &command, options);
+    }
+
+    // Inherit stdio for interactive output
+    cmd.stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .stdin(Stdio::inherit());
+
+    let status = cmd.status().map_err(|e| spawn_error(target, &command, e))?;
+
+    Ok(status)
+}
+
+/// Run the make command on a terminal of its own, recording it to a cast
+/// file in `dir` named after the run, as its log would be
+fn run_recorded(cmd: Command, target: &str, command: &str, dir: &Path) -> Result<ExitStatus> {
+    let cast = |run: &str| dir.join(format!("{}.{}", run, record::CAST_EXTENSION));
+    // Claimed by creating it, so a run started in the same second gets a
+    // name of its own
+    std::fs::create_dir_all(dir).map_err(|e| spawn_error(target, command, e))?;
+    let (run, _) = runlog::claim_stem(&chrono::Local::now(), target, |run| {
+        std::fs::File::create_new(cast(run))
+    })
+    .map_err(|e| spawn_error(target, command, e))?;
+    let path = cast(&run);
+    let status = record::run(cmd, &path, command).map_err(|e| spawn_error(target, command, e))?;
+
+    println!(
+        "{} {} (maki replay {})",
+        "Recorded:".dimmed(),
+        path.display(),
+        run
+    );
+    Ok(status)
+}
+
+/// Whether make's output is watched for its "Nothing to be done" message
+fn quiet_noop(options: &ExecuteOptions) -> bool {
+    options.quiet_noop && options.provider == Provider::Make && options.mode == MakeMode::Run
+}
+
+/// The process for a built command, with the run's environment and directory
+fn make_command(args: &[String], options: &ExecuteOptions) -> Command {
     let mut cmd = if cfg!(windows) {
         let mut c = Command::new("cmd");
-        c.arg("/C").arg("make");
+        c.arg("/C").args(args);
         c
     } else {
-        Command::new("make")
+        let mut c = Command::new(&args[0]);
+        c.args(&args[1..]);
+        c
     };
 
-    // Add makefile option if specified
-    if let Some(ref makefile) = options.makefile {
-        cmd.arg("-f").arg(makefile);
+    cmd.envs(options.env.iter().map(|v| (&v.name, &v.value)));
+
+    // Set working directory if specified
+    if let Some(ref cwd) = options.cwd {
+        cmd.current_dir(cwd);
     }
 
-    // Add the target
-    cmd.arg(target);
+    cmd
+}
 
-    // Add variables (e.g., V=patch, ENV=prod)
-    for (name, value) in &options.variables {
-        cmd.arg(format!("{}={}", name, value));
+/// Run the make command with its output passing through maki, to write it
+/// to a log, watch it for the heartbeat, catch make having nothing to do,
+/// and/or find the artifacts and errors it mentions
+fn run_piped(
+    cmd: Command,
+    target: &str,
+    command: &str,
+    options: &ExecuteOptions,
+) -> Result<ExitStatus> {
+    let started = SystemTime::now();
+    let log = match options.log_dir {
+        Some(ref log_dir) => {
+            let log = RunLog::create(log_dir, target, command, &masked(options).variables)
+                .map_err(|e| log_error(target, e))?
+                .with_redactor(redactor(options));
+            Some(Arc::new(Mutex::new(log)))
+        }
+        None => None,
+    };
+
+    let (mut child, terminal) =
+        spawn_teed(cmd, options).map_err(|e| spawn_error(target, command, e))?;
+
+    let heartbeat = options
+        .heartbeat
+        .map(|quiet_after| Heartbeat::start(target, quiet_after));
+    let activity = heartbeat.as_ref().map(Heartbeat::activity);
+    // make prints "Nothing to be done" on stdout
+    let filter = quiet_noop(options).then(NoopFilter::default);
+    let output = (options.artifacts.is_enabled() || options.errors)
+        .then(|| Arc::new(Mutex::new(Vec::new())));
+    // On a terminal, stdout and stderr are one stream, which ends once every
+    // process on it has closed it
+    let stdout = match terminal {
+        Some(ref terminal) => {
+            let screen = terminal
+                .screen()
+                .map_err(|e| spawn_error(target, command, e))?;
+            let (log, output, activity) = (log.clone(), output.clone(), activity.clone());
+            Some(tee(
+                screen,
+                std::io::stdout(),
+                log,
+                output,
+                activity,
+                filter,
+            ))
+        }
+        None => child.stdout.take().map(|s| {
+            let (log, output, activity) = (log.clone(), output.clone(), activity.clone());
+            tee(s, std::io::stdout(), log, output, activity, filter)
+        }),
+    };
+    let stderr = child.stderr.take().map(|s| {
+        let (log, output) = (log.clone(), output.clone());
+        tee(s, std::io::stderr(), log, output, activity, None)
+    });
+
+    let status = child.wait().map_err(|e| spawn_error(target, command, e))?;
+    let mut noop = false;
+    for handle in [stdout, stderr].into_iter().flatten() {
+        noop |= handle.join().unwrap_or(false);
+    }
+    drop(terminal);
+    if let Some(heartbeat) = heartbeat {
+        heartbeat.stop();
     }
 
-    // Set working directory if specified
-    if let Some(ref cwd) = options.cwd {
-        cmd.current_dir(cwd);
+    let log = match log {
+        Some(log) => Some(
+            Arc::into_inner(log)
+                .and_then(|log| log.into_inner().ok())
+                .ok_or_else(|| log_error(target, "log is still in use"))?,
+        ),
+        None => None,
+    };
+
+    let output = output
+        .map(|output| output.lock().map(|o| o.clone()).unwrap_or_default())
+        .map(|output| String::from_utf8_lossy(&output).into_owned());
+    let artifacts = match output {
+        Some(ref output) if options.artifacts.is_enabled() => {
+            let run = log.as_ref().map(|log| log.stem.as_str());
+            artifacts(output, target, run, options, started)
+        }
+        _ => Vec::new(),
+    };
+
+    if let Some(log) = log {
+        let text_path = log.text_path.clone();
+        log.finish(&status, artifacts)
+            .map_err(|e| log_error(target, e))?;
+
+        println!("{} {}", "Log:".dimmed(), text_path.display());
     }
 
-    // Inherit stdio for interactive output
-    cmd.stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .stdin(Stdio::inherit());
+    if noop && status.success() {
+        println!(
+            "{} {} is up to date, nothing to be done",
+            "✓".green(),
+            target.bold()
+        );
+    }
 
-    let status = cmd
-        .status()
-        .with_context(|| format!("Failed to execute 'make {}'", target))?;
+    if let Some(ref output) = output
+        && options.errors
+    {
+        report_errors(target, output, options);
+    }
 
     Ok(status)
 }
 
+/// The files a run wrote and mentioned in its output, copied to the
+/// artifacts directory under `run`, after listing each one
+///
+/// Not being able to copy them only warns, since the run itself is over.
+fn artifacts(
+    output: &str,
+    target: &str,
+    run: Option<&str>,
+    options: &ExecuteOptions,
+    started: SystemTime,
+) -> Vec<Artifact> {
+    let dir = match options.cwd {
+        Some(ref cwd) => cwd.clone(),
+        None => std::env::current_dir().unwrap_or_default(),
+    };
+    let found = artifact::find(output, &options.artifacts, &dir, started);
+    let run = match (run, &options.artifacts.dir) {
+        (Some(run), _) => Ok(run.to_string()),
+        // Without a log to name the run after, claim a directory of its own
+        (None, Some(copies)) if !found.is_empty() => {
+            let copies = dir.join(copies);
+            std::fs::create_dir_all(&copies).and_then(|()| {
+                runlog::claim_stem(&chrono::Local::now(), target, |run| {
+                    std::fs::create_dir(copies.join(run))
+                })
+                .map(|(run, ())| run)
+            })
+        }
+        (None, _) => Ok(runlog::stem(&chrono::Local::now(), target)),
+    };
+    let collected = run
+        .map_err(anyhow::Error::from)
+        .and_then(|run| artifact::collect(found, &options.artifacts, &dir, &run));
+    match collected {
+        Ok(artifacts) => {
+            for artifact in &artifacts {
+                let path = artifact.copy.as_ref().unwrap_or(&artifact.path);
+                println!("{} {}", "Artifact:".dimmed(), path.display());
+            }
+            artifacts
+        }
+        Err(e) => {
+            eprintln!("{} {:#}", "Artifacts not saved:".yellow(), e);
+            Vec::new()
+        }
+    }
+}
+
+/// List the errors a run's output points at, and keep them for `maki errors`
+/// in place of the last run's, even when there are none
+fn report_errors(target: &str, output: &str, options: &ExecuteOptions) {
+    let dir = match options.cwd {
+        Some(ref cwd) => cwd.clone(),
+        None => std::env::current_dir().unwrap_or_default(),
+    };
+    let report = Report::new(target, &dir, output);
+    for line in report.summary() {
+        eprintln!("{}", line);
+    }
+    if let Err(e) = problems::save(&report) {
+        eprintln!("{} {:#}", "Errors not saved:".yellow(), e);
+    }
+}
+
+/// The terminal a teed run is on with --pty, kept in the mode it needs until
+/// dropped
+#[cfg(unix)]
+struct TeedTerminal {
+    screen: std::os::fd::OwnedFd,
+    _keyboard: pty::Keyboard,
+    _raw: Option<RawMode>,
+}
+
+#[cfg(unix)]
+impl TeedTerminal {
+    /// Its output, which tells the end of the run apart from a failed read
+    fn screen(&self) -> std::io::Result<PtyScreen> {
+        Ok(PtyScreen(std::fs::File::from(self.screen.try_clone()?)))
+    }
+}
+
+/// Reads of a terminal's output that end where the run does
+#[cfg(unix)]
+struct PtyScreen(std::fs::File);
+
+#[cfg(unix)]
+impl Read for PtyScreen {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            match self.0.read(buf) {
+                Err(ref e) if pty::is_closed(e) => return Ok(0),
+                Err(_) => continue,
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct TeedTerminal;
+
+#[cfg(not(unix))]
+impl TeedTerminal {
+    fn screen(&self) -> std::io::Result<std::io::Empty> {
+        Ok(std::io::empty())
+    }
+}
+
+/// Start a run whose output passes through maki: piped, or with --pty on a
+/// terminal of its own that is passed the keyboard
+fn spawn_teed(
+    mut cmd: Command,
+    options: &ExecuteOptions,
+) -> std::io::Result<(Child, Option<TeedTerminal>)> {
+    #[cfg(unix)]
+    if options.pty {
+        let (screen, child) = pty::spawn(cmd)?;
+        let terminal = TeedTerminal {
+            _raw: RawMode::enable().ok().map(RawMode::with_newlines),
+            _keyboard: pty::Keyboard::forward(&screen)?,
+            screen,
+        };
+        return Ok((child, Some(terminal)));
+    }
+    #[cfg(not(unix))]
+    if options.pty {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "--pty needs a Unix terminal",
+        ));
+    }
+
+    let child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .stdin(Stdio::inherit())
+        .spawn()?;
+    Ok((child, None))
+}
+
+/// Copy a child's output stream to the terminal, and to the run log and the
+/// output buffer if there are any, telling the heartbeat about it
+///
+/// With a `filter`, make's "Nothing to be done" message is left off the
+/// terminal (not the log), and the thread returns whether it was seen.
+fn tee(
+    mut source: impl Read + Send + 'static,
+    mut sink: impl Write + Send + 'static,
+    log: Option<Arc<Mutex<RunLog>>>,
+    output: Option<Arc<Mutex<Vec<u8>>>>,
+    activity: Option<Activity>,
+    mut filter: Option<NoopFilter>,
+) -> JoinHandle<bool> {
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 8192];
+        let mut show = |output: &[u8]| {
+            if output.is_empty() {
+                return;
+            }
+            let mut write = || {
+                let _ = sink.write_all(output);
+                let _ = sink.flush();
+            };
+            match activity {
+                Some(ref activity) => activity.output(output, write),
+                None => write(),
+            }
+        };
+        while let Ok(n) = source.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            match filter {
+                Some(ref mut filter) => show(&filter.feed(&buf[..n])),
+                None => show(&buf[..n]),
+            }
+            if let Some(ref log) = log
+                && let Ok(mut log) = log.lock()
+            {
+                let _ = log.write(&buf[..n]);
+            }
+            if let Some(ref output) = output
+                && let Ok(mut output) = output.lock()
+            {
+                output.extend_from_slice(&buf[..n]);
+            }
+        }
+        match filter {
+            Some(ref mut filter) => {
+                show(&filter.finish());
+                filter.is_noop()
+            }
+            None => false,
+        }
+    })
+}
+
 /// Check if make is available on the system
-#[allow(dead_code)]
 pub fn check_make_available() -> bool {
     let result = if cfg!(windows) {
         Command::new("cmd")
@@ -166,7 +819,7 @@ pub fn execute_target_capture(
 
     let output = cmd
         .output()
-        .with_context(|| format!("Failed to execute 'make {}'", target))?;
+        .map_err(|e| spawn_error(target, &format!("make {}", target), e))?;
 
     let stdout = String::from_utf8_lossy(&output.stdout).to_string();
     let stderr = String::from_utf8_lossy(&output.stderr).to_string();
@@ -217,6 +870,19 @@ mod tests {
         assert_eq!(cmd, vec!["make", "bump", "V=patch"]);
     }
 
+    #[test]
+    fn test_build_command_assumes_prerequisites_made() {
+        let options = ExecuteOptions {
+            assume_old: vec!["build".to_string(), "gen".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            build_command("test", &options),
+            vec!["make", "-o", "build", "-o", "gen", "test"]
+        );
+    }
+
     #[test]
     fn test_build_command_with_multiple_variables() {
         let options = ExecuteOptions {
@@ -245,6 +911,250 @@ mod tests {
         assert_eq!(cmd, vec!["make", "-f", "custom.mk", "build", "MODE=release"]);
     }
 
+    #[test]
+    fn test_build_command_for_providers() {
+        let options = ExecuteOptions {
+            provider: Provider::Compose,
+            makefile: Some(std::path::PathBuf::from("custom.mk")),
+            ..Default::default()
+        };
+        assert_eq!(
+            build_command("web", &options),
+            vec!["docker", "compose", "up", "web"]
+        );
+
+        let options = ExecuteOptions {
+            provider: Provider::Procfile,
+            ..Default::default()
+        };
+        let cmd = build_command("worker", &options);
+        assert!(cmd[0] == "overmind" || cmd[0] == "foreman");
+        assert_eq!(&cmd[1..], ["start", "worker"]);
+    }
+
+    #[test]
+    fn test_build_command_with_env() {
+        let options = ExecuteOptions {
+            env: env::collect(
+                &Default::default(),
+                &[],
+                &[("ENV".to_string(), "prod".to_string())],
+            ),
+            ..Default::default()
+        };
+
+        assert_eq!(build_command("deploy", &options), vec!["make", "deploy"]);
+        assert_eq!(
+            build_command_with_env("deploy", &options),
+            vec!["env", "ENV=prod", "make", "deploy"]
+        );
+    }
+
+    #[test]
+    fn test_plan_lines_leave_out_silent_commands() {
+        let line = |text: &str, silent: bool| RecipeLine {
+            text: text.to_string(),
+            silent,
+        };
+        let plan = vec![
+            line("cargo build", false),
+            line("echo Built", true),
+            line("true", true),
+        ];
+
+        assert_eq!(
+            runlog::strip_ansi(&plan_lines(&plan, false).join("\n")),
+            "  cargo build\n  (2 silent command(s); --show-silent lists them)"
+        );
+        assert_eq!(
+            runlog::strip_ansi(&plan_lines(&plan, true).join("\n")),
+            "  cargo build\n  echo Built (silent)\n  true (silent)"
+        );
+        assert!(plan_lines(&[], false).is_empty());
+    }
+
+    #[test]
+    fn test_secrets_are_masked_where_shown() {
+        let options = ExecuteOptions {
+            variables: vec![
+                ("TOKEN".to_string(), "s3cr3t".to_string()),
+                ("REGION".to_string(), "eu".to_string()),
+            ],
+            env: env::collect(
+                &Default::default(),
+                &[],
+                &[("TOKEN".to_string(), "s3cr3t".to_string())],
+            ),
+            secrets: vec!["TOKEN".to_string()],
+            ..Default::default()
+        };
+
+        // make gets the value, but it is never printed
+        assert!(build_command("deploy", &options).contains(&"TOKEN=s3cr3t".to_string()));
+        assert_eq!(
+            command_line("deploy", &options),
+            "env TOKEN='***' make deploy TOKEN='***' REGION=eu"
+        );
+        assert_eq!(
+            shown_command("deploy", &options),
+            "make deploy TOKEN='***' REGION=eu"
+        );
+    }
+
+    #[test]
+    fn test_build_command_with_include_dirs() {
+        let options = ExecuteOptions {
+            cwd: Some(std::path::PathBuf::from("/project")),
+            include_dirs: vec![
+                std::path::PathBuf::from("mk"),
+                std::path::PathBuf::from("/opt/mk"),
+            ],
+            ..Default::default()
+        };
+        let cmd = build_command("build", &options);
+
+        assert_eq!(
+            cmd,
+            vec!["make", "-I", "/project/mk", "-I", "/opt/mk", "build"]
+        );
+    }
+
+    #[test]
+    fn test_build_command_with_mode() {
+        let mut options = ExecuteOptions {
+            mode: MakeMode::Question,
+            ..Default::default()
+        };
+        assert_eq!(
+            build_command("build", &options),
+            vec!["make", "-q", "build"]
+        );
+
+        options.mode = MakeMode::Touch;
+        options.variables = vec![("V".to_string(), "1".to_string())];
+        assert_eq!(
+            build_command("build", &options),
+            vec!["make", "-t", "build", "V=1"]
+        );
+    }
+
+    #[test]
+    fn test_question_reports_staleness() {
+        if !check_make_available() {
+            return;
+        }
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("in.txt"), "").unwrap();
+        std::fs::write(
+            dir.path().join("Makefile"),
+            "out.txt: in.txt\n\tcp in.txt out.txt\n",
+        )
+        .unwrap();
+        let question = ExecuteOptions {
+            cwd: Some(dir.path().to_path_buf()),
+            mode: MakeMode::Question,
+            ..Default::default()
+        };
+        let touch = ExecuteOptions {
+            mode: MakeMode::Touch,
+            ..question.clone()
+        };
+
+        let stale = execute_target_captured("out.txt", &question).unwrap();
+        assert_eq!(stale.status.code(), Some(1));
+
+        // Touching creates the file without running cp
+        let touched = execute_target_captured("out.txt", &touch).unwrap();
+        assert!(touched.status.success());
+        let fresh = execute_target_captured("out.txt", &question).unwrap();
+        assert_eq!(fresh.status.code(), Some(0));
+    }
+
+    #[test]
+    fn test_make_reports_nothing_to_do() {
+        if !check_make_available() {
+            return;
+        }
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("Makefile"),
+            "all:\nout.txt:\n\ttouch out.txt\n",
+        )
+        .unwrap();
+        // make's messages are translated in other locales
+        let options = ExecuteOptions {
+            cwd: Some(dir.path().to_path_buf()),
+            env: vec![EnvVar {
+                name: "LC_ALL".to_string(),
+                value: "C".to_string(),
+                source: env::EnvSource::Config,
+            }],
+            ..Default::default()
+        };
+
+        let all = execute_target_captured("all", &options).unwrap();
+        assert!(crate::noop::is_noop(&all.stdout));
+
+        let built = execute_target_captured("out.txt", &options).unwrap();
+        assert!(!crate::noop::is_noop(&built.stdout));
+        let up_to_date = execute_target_captured("out.txt", &options).unwrap();
+        assert!(crate::noop::is_noop(&up_to_date.stdout));
+    }
+
+    #[test]
+    fn test_recursive_make_in_a_subdirectory_receives_include_dirs() {
+        if !check_make_available() {
+            return;
+        }
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("mk")).unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("mk/common.mk"), "GREETING := hello\n").unwrap();
+        std::fs::write(
+            dir.path().join("custom.mk"),
+            "include common.mk\ntop:\n\t@$(MAKE) --no-print-directory -C sub hello\n",
+        )
+        .unwrap();
+        // `$(MAKE) -C sub` reads sub's own Makefile, as under plain make, but
+        // still finds the include dir given on the command line
+        std::fs::write(
+            dir.path().join("sub/Makefile"),
+            "include common.mk\nhello:\n\t@echo $(GREETING) from sub\n",
+        )
+        .unwrap();
+
+        let options = ExecuteOptions {
+            cwd: Some(dir.path().to_path_buf()),
+            makefile: Some(std::path::PathBuf::from("custom.mk")),
+            include_dirs: vec![std::path::PathBuf::from("mk")],
+            ..Default::default()
+        };
+        let args = build_command("top", &options);
+        let output = make_command(&args, &options).output().unwrap();
+
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "hello from sub\n");
+    }
+
+    #[test]
+    fn test_format_command_quotes_words() {
+        let cmd = vec![
+            "make".to_string(),
+            "deploy".to_string(),
+            "MSG=hello world".to_string(),
+            "V=it's".to_string(),
+        ];
+
+        assert_eq!(
+            format_command(&cmd),
+            r"make deploy MSG='hello world' V='it'\''s'"
+        );
+    }
+
     #[test]
     fn test_format_command() {
         let cmd = vec![
@@ -257,6 +1167,81 @@ mod tests {
         assert_eq!(format_command(&cmd), "make -f Makefile build");
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_pty_run_sees_a_terminal_and_is_logged() {
+        if !check_make_available() {
+            return;
+        }
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("Makefile"),
+            "check:\n\t@test -t 1 && echo terminal || echo pipe\n",
+        )
+        .unwrap();
+        let options = ExecuteOptions {
+            cwd: Some(dir.path().to_path_buf()),
+            log_dir: Some(dir.path().join("logs")),
+            print_cmd: false,
+            pty: true,
+            ..Default::default()
+        };
+
+        let status = execute_target("check", &options).unwrap();
+        assert!(status.success());
+        let log = std::fs::read_dir(dir.path().join("logs"))
+            .unwrap()
+            .flatten()
+            .find(|e| e.path().extension().is_some_and(|x| x == "txt"))
+            .unwrap();
+        let text = std::fs::read_to_string(log.path()).unwrap();
+        assert!(text.contains("terminal"));
+        assert!(!text.contains("pipe"));
+    }
+
+    #[test]
+    fn test_secret_values_are_masked_in_logs_and_captured_output() {
+        if !check_make_available() {
+            return;
+        }
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("Makefile"),
+            "deploy:\n\t@echo token=$(TOKEN) region=$(REGION)\n",
+        )
+        .unwrap();
+        let options = ExecuteOptions {
+            cwd: Some(dir.path().to_path_buf()),
+            log_dir: Some(dir.path().join("logs")),
+            variables: vec![
+                ("TOKEN".to_string(), "hunter2".to_string()),
+                ("REGION".to_string(), "eu".to_string()),
+            ],
+            secrets: vec!["TOKEN".to_string()],
+            print_cmd: false,
+            ..Default::default()
+        };
+
+        assert!(execute_target("deploy", &options).unwrap().success());
+        let logs: Vec<_> = std::fs::read_dir(dir.path().join("logs"))
+            .unwrap()
+            .flatten()
+            .filter(|e| e.path().extension().is_some_and(|x| x != "json"))
+            .collect();
+        assert_eq!(logs.len(), 2);
+        for log in logs {
+            let text = std::fs::read_to_string(log.path()).unwrap();
+            assert!(text.contains("token=*** region=eu"), "{}", text);
+            assert!(!text.contains("hunter2"), "{}", text);
+        }
+
+        let captured = execute_target_captured("deploy", &options).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&captured.stdout),
+            "token=*** region=eu\n"
+        );
+    }
+
     #[test]
     fn test_dry_run_does_not_execute() {
         let options = ExecuteOptions {
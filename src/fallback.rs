@@ -0,0 +1,239 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::process::{Command, ExitStatus};
+
+use crate::executor::ExecuteOptions;
+use crate::makefile::{self, Silenced};
+use crate::target::{Provider, Target};
+
+/// A recipe command as the shell runs it in make's place
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShellCommand {
+    /// The command, with make's `$$` turned back into `$`
+    pub text: String,
+    /// Printed before it runs, as make echoes it
+    pub echo: bool,
+    /// Its failure doesn't stop the recipe, because of a `-` prefix
+    pub ignore_errors: bool,
+}
+
+/// The shell recipes are run with when make isn't there: `$SHELL`, or `sh`
+pub fn shell() -> String {
+    std::env::var("SHELL")
+        .ok()
+        .filter(|shell| !shell.is_empty())
+        .unwrap_or_else(|| "sh".to_string())
+}
+
+/// The commands of `target`'s recipe when it is plain shell a shell can run
+/// as well as make: no prerequisites to bring up to date first, and no
+/// variables, functions, or automatic variables for make to expand, and no
+/// `ifeq`/`ifdef` blocks for make to pick commands from
+///
+/// In a Makefile with `.ONESHELL` the recipe is one command, run as a single
+/// script so that a `cd` carries over to the lines after it.
+pub fn plain_recipe(target: &Target) -> Option<Vec<ShellCommand>> {
+    if target.provider != Provider::Make || target.is_pattern() {
+        return None;
+    }
+    if target.prerequisites().next().is_some() {
+        return None;
+    }
+    let (content, _) = makefile::read_makefile(&target.file).ok()?;
+    let lines: Vec<&str> = content.lines().collect();
+    let header = target.line.saturating_sub(1);
+    if makefile::recipe_is_conditional(&lines, header) {
+        return None;
+    }
+    let recipe = makefile::recipe_lines(&lines, header);
+    if recipe.is_empty() || recipe.iter().any(|l| l.replace("$$", "").contains('$')) {
+        return None;
+    }
+
+    let silent = Silenced::of(&content).covers(&target.name);
+    if makefile::is_oneshell(&content) {
+        let script = makefile::oneshell_script(&recipe)?;
+        return Some(vec![ShellCommand {
+            text: script.text.replace("$$", "$"),
+            echo: !silent && !script.prefixes.contains('@'),
+            ignore_errors: script.prefixes.contains('-'),
+        }]);
+    }
+    let mut commands: Vec<ShellCommand> = Vec::new();
+    let mut continued = false;
+    for line in recipe {
+        let text = line.replace("$$", "$");
+        if continued && let Some(last) = commands.last_mut() {
+            last.text = format!("{}\n{}", last.text, text);
+        } else {
+            let command = text.trim_start_matches(|c| "@-+ \t".contains(c));
+            let prefixes = &text[..text.len() - command.len()];
+            commands.push(ShellCommand {
+                text: command.to_string(),
+                echo: !silent && !prefixes.contains('@'),
+                ignore_errors: prefixes.contains('-'),
+            });
+        }
+        continued = line.trim_end().ends_with('\\');
+    }
+    Some(commands)
+}
+
+/// Run `commands` one after another, each in its own `shell` as make would,
+/// with the run's directory, environment, and variables; stops at the first
+/// failure, except of commands marked to ignore theirs
+pub fn run(
+    target: &str,
+    commands: &[ShellCommand],
+    shell: &str,
+    options: &ExecuteOptions,
+) -> Result<ExitStatus> {
+    let mut status = ExitStatus::default();
+    for command in commands {
+        if command.echo {
+            println!("{}", command.text);
+        }
+        let mut cmd = Command::new(shell);
+        cmd.arg("-c").arg(&command.text);
+        // make hands the variables given on its command line to recipes
+        cmd.envs(options.env.iter().map(|v| (&v.name, &v.value)));
+        cmd.envs(options.variables.iter().map(|(name, value)| (name, value)));
+        if let Some(ref cwd) = options.cwd {
+            cmd.current_dir(cwd);
+        }
+
+        status = cmd
+            .status()
+            .with_context(|| format!("Failed to run {}", shell))?;
+        if !status.success() {
+            if !command.ignore_errors {
+                return Ok(status);
+            }
+            println!(
+                "{}",
+                format!(
+                    "maki: [{}] Error {} (ignored)",
+                    target,
+                    status.code().unwrap_or(1)
+                )
+                .dimmed()
+            );
+            status = ExitStatus::default();
+        }
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn parse(dir: &Path, content: &str) -> Vec<Target> {
+        let file = dir.join("Makefile");
+        std::fs::write(&file, content).unwrap();
+        makefile::parse_makefile(&file, &makefile::ParseOptions::default()).unwrap()
+    }
+
+    fn plain(targets: &[Target], name: &str) -> Option<Vec<ShellCommand>> {
+        plain_recipe(targets.iter().find(|t| t.name == name).unwrap())
+    }
+
+    #[test]
+    fn test_plain_recipes() {
+        let dir = tempfile::tempdir().unwrap();
+        let targets = parse(
+            dir.path(),
+            "OUT := out\n\
+             clean:\n\t@rm -rf out\n\t-rm -f *.log\n\techo \"$$HOME\" \\\n\t  done\n\
+             build: gen\n\tcc main.c\n\
+             gen:\n\tmkdir -p $(OUT)\n\
+             dist:\n\ttar czf $@ src\n\
+             reset:\n\techo start\nifdef CI\n\techo ci-rm\nendif\n\techo done\n",
+        );
+
+        assert_eq!(
+            plain(&targets, "clean").unwrap(),
+            vec![
+                ShellCommand {
+                    text: "rm -rf out".to_string(),
+                    echo: false,
+                    ignore_errors: false,
+                },
+                ShellCommand {
+                    text: "rm -f *.log".to_string(),
+                    echo: true,
+                    ignore_errors: true,
+                },
+                ShellCommand {
+                    text: "echo \"$HOME\" \\\n  done".to_string(),
+                    echo: true,
+                    ignore_errors: false,
+                },
+            ]
+        );
+        // Prerequisites, make variables, and automatic variables need make
+        assert_eq!(plain(&targets, "build"), None);
+        assert_eq!(plain(&targets, "gen"), None);
+        assert_eq!(plain(&targets, "dist"), None);
+        // So does picking the commands of an `ifdef` block
+        assert_eq!(plain(&targets, "reset"), None);
+
+        let targets = parse(
+            dir.path(),
+            ".ONESHELL:\nclean:\n\t-cd out\n\t@rm -f *.o \"$$HOME\"\n",
+        );
+        assert_eq!(
+            plain(&targets, "clean").unwrap(),
+            vec![ShellCommand {
+                text: "cd out\nrm -f *.o \"$HOME\"".to_string(),
+                echo: true,
+                ignore_errors: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_oneshell_recipe_keeps_its_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("out")).unwrap();
+        let targets = parse(dir.path(), ".ONESHELL:\nmark:\n\t@cd out\n\ttouch marked\n");
+        let options = ExecuteOptions {
+            cwd: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let commands = plain(&targets, "mark").unwrap();
+        assert!(run("mark", &commands, "sh", &options).unwrap().success());
+        assert!(dir.path().join("out/marked").exists());
+    }
+
+    #[test]
+    fn test_run_stops_at_the_first_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let command = |text: &str, ignore_errors: bool| ShellCommand {
+            text: text.to_string(),
+            echo: false,
+            ignore_errors,
+        };
+        let options = ExecuteOptions {
+            cwd: Some(dir.path().to_path_buf()),
+            variables: vec![("NAME".to_string(), "maki".to_string())],
+            ..Default::default()
+        };
+
+        let commands = [
+            command("false", true),
+            command("echo \"$NAME\" > out", false),
+            command("exit 3", false),
+            command("touch never", false),
+        ];
+        let status = run("clean", &commands, "sh", &options).unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("out")).unwrap(),
+            "maki\n"
+        );
+        assert!(!dir.path().join("never").exists());
+    }
+}
@@ -3,46 +3,144 @@ use skim::prelude::*;
 use std::borrow::Cow;
 use std::fs;
 use std::io::Cursor;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use syntect::easy::HighlightLines;
-use syntect::highlighting::{Style, ThemeSet};
+use syntect::highlighting::{Style, Theme, ThemeSet};
 use syntect::parsing::SyntaxSet;
 use syntect::util::{LinesWithEndings, as_24_bit_terminal_escaped};
 
-use crate::target::Target;
+use crate::infer;
+use crate::makefile::{self, Expansion, ParseOptions};
+use crate::quote;
+use crate::rank::{Frecency, RankMode};
+use crate::target::{Provider, Target};
+use crate::timings::{self, Phase};
+use crate::width;
+
+/// Options for the interactive picker
+#[derive(Debug, Clone, Default)]
+pub struct PickerOptions {
+    /// Extra header lines shown below the key hints
+    pub header: Option<String>,
+    /// Show private targets (those starting with _) initially
+    pub include_private: bool,
+    /// Show pattern rules (e.g., %.o: %.c) initially
+    pub include_patterns: bool,
+    /// Show the bookkeeping rules of generated Makefiles initially
+    pub include_generated: bool,
+    /// How targets are ordered as the query is typed
+    pub rank: RankMode,
+    /// Past runs, for the frecency and hybrid ranks
+    pub frecency: Frecency,
+    /// Show each target's Makefile beside it when they come from several
+    pub show_paths: bool,
+    /// Directory those paths are relative to, for targets without a root
+    pub dir: PathBuf,
+    /// List the commands make runs without echoing them in the preview
+    pub show_silent: bool,
+    /// Syntax highlighting theme of the preview, `DEFAULT_THEME` when None
+    pub theme: Option<String>,
+}
+
+impl PickerOptions {
+    /// The filters the picker's toggles start from
+    pub fn filter(&self) -> ParseOptions {
+        ParseOptions {
+            include_private: self.include_private,
+            include_patterns: self.include_patterns,
+            include_generated: self.include_generated,
+        }
+    }
+}
+
+/// The preview's syntax highlighting theme unless `[picker] theme` is set
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+/// The names of the themes the preview can be highlighted with
+pub fn themes() -> Vec<String> {
+    ThemeSet::load_defaults().themes.into_keys().collect()
+}
+
+/// How the preview is drawn, as the picker's keys toggle it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PreviewMode {
+    /// Wrap long lines to the preview's width, rather than leave them for
+    /// scrolling sideways
+    wrap: bool,
+    /// List the commands make won't echo (`@`, `.SILENT`), marked as such
+    show_silent: bool,
+}
 
 /// A skim item that holds a target and provides syntax-highlighted preview
 struct TargetItem {
     target: Target,
     display: String,
+    /// Its Makefile relative to the project, when shown in the list
+    path: Option<String>,
+    /// Position in the picker's list, skim's `Index` tiebreak
+    index: usize,
+    /// Every target, for expanding aggregate targets' prerequisites
+    targets: Arc<Vec<Target>>,
     syntax_set: Arc<SyntaxSet>,
-    theme_set: Arc<ThemeSet>,
+    theme: Arc<Theme>,
+    mode: PreviewMode,
 }
 
 impl TargetItem {
-    fn new(target: Target, syntax_set: Arc<SyntaxSet>, theme_set: Arc<ThemeSet>) -> Self {
-        let display = target.display_name();
+    fn new(
+        target: Target,
+        path: Option<String>,
+        index: usize,
+        targets: Arc<Vec<Target>>,
+        syntax_set: Arc<SyntaxSet>,
+        theme: Arc<Theme>,
+        mode: PreviewMode,
+    ) -> Self {
+        // Tags are in the text as `#docker` chips, so typing one finds them
+        let display = match target.tags.is_empty() {
+            true => target.picker_name(),
+            false => format!("{} {}", target.picker_name(), target.tag_chips()),
+        };
         Self {
             target,
             display,
+            path,
+            index,
+            targets,
             syntax_set,
-            theme_set,
+            theme,
+            mode,
         }
     }
 
-    fn get_highlighted_preview(&self) -> String {
-        let content = match fs::read_to_string(&self.target.file) {
-            Ok(c) => c,
+    /// The preview, with lines wrapped to `width` columns when given
+    fn get_highlighted_preview(&self, width: Option<usize>) -> String {
+        // An inferred target has no rule in its manifest to show
+        if self.target.provider == Provider::Inferred {
+            return fit(&self.inferred_preview(), width);
+        }
+
+        let content = match makefile::read_makefile(&self.target.file) {
+            Ok((c, _)) => c,
             Err(_) => return "Error reading file".to_string(),
         };
 
         let lines: Vec<&str> = content.lines().collect();
         let target_line = self.target.line.saturating_sub(1); // Convert to 0-indexed
 
+        // Compose services are indented YAML keys; their body is everything
+        // indented further
+        let indent_of = |line: &str| line.len() - line.trim_start().len();
+        let target_indent = lines.get(target_line).map_or(0, |l| indent_of(l));
+
         // Find the end of this target's recipe by looking for the next target or end of file
         let mut end = target_line + 1;
         while end < lines.len() {
             let line = lines[end];
+            if target_indent > 0 && !line.trim().is_empty() && indent_of(line) <= target_indent {
+                break;
+            }
             // Skip empty lines and lines that start with whitespace (recipe lines)
             if !line.is_empty() && !line.starts_with('\t') && !line.starts_with(' ') {
                 // Stop at non-indented comments (these are descriptions for the next target)
@@ -66,23 +164,58 @@ impl TargetItem {
 
         let snippet = lines[start..end].join("\n");
 
-        // Use Makefile syntax highlighting
-        let syntax = self
-            .syntax_set
-            .find_syntax_by_extension("mk")
-            .or_else(|| self.syntax_set.find_syntax_by_name("Makefile"))
-            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
+        // Use Makefile syntax highlighting (YAML for compose files)
+        let syntax = match self.target.provider {
+            Provider::Compose => self.syntax_set.find_syntax_by_extension("yaml"),
+            _ => self
+                .syntax_set
+                .find_syntax_by_extension("mk")
+                .or_else(|| self.syntax_set.find_syntax_by_name("Makefile")),
+        }
+        .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
 
-        let theme = &self.theme_set.themes["base16-ocean.dark"];
-        let mut highlighter = HighlightLines::new(syntax, theme);
+        let mut highlighter = HighlightLines::new(syntax, &self.theme);
 
-        let mut result = String::new();
+        let mut header = String::new();
 
         // Add description at the top if present (in cyan color)
         if let Some(ref description) = self.target.description {
-            result.push_str(&format!("\x1b[36m{}\x1b[0m\n\n", description));
+            header.push_str(&format!("\x1b[36m{}\x1b[0m\n\n", description));
+        }
+
+        // The user's own note (`maki note`), in yellow
+        if let Some(ref note) = self.target.note {
+            header.push_str(&format!("\x1b[33m✎ {}\x1b[0m\n\n", note));
+        }
+
+        // A command rather than a file, which make runs every time
+        if self.target.is_phony {
+            header.push_str("\x1b[2mPhony (.PHONY): always runs\x1b[0m\n\n");
+        }
+
+        // Target-specific variables may be set far from the rule, so list them up front
+        if !self.target.target_vars.is_empty() {
+            for var in &self.target.target_vars {
+                header.push_str(&format!("\x1b[33m{}\x1b[0m\n", var.assignment()));
+            }
+            header.push('\n');
         }
 
+        // What else runs, before the recipe itself
+        if self.target.prerequisites().next().is_some() {
+            header.push_str(&render_dependency_tree(&self.target, &self.targets));
+            header.push('\n');
+        }
+
+        // Recipe lines otherwise each get a shell of their own
+        if self.target.provider == Provider::Make && makefile::is_oneshell(&content) {
+            header.push_str("\x1b[2mRuns in one shell (.ONESHELL)\x1b[0m\n\n");
+        }
+
+        let mut result = fit(&header, width);
+
+        // Long lines continue below the gutter, so the code stays in its column
+        let code_width = width.map(|w| w.saturating_sub(GUTTER_WIDTH + 1).max(MIN_CODE_WIDTH));
         for (i, line) in LinesWithEndings::from(&snippet).enumerate() {
             let line_num = start + i + 1;
             let marker = if line_num == self.target.line {
@@ -95,33 +228,93 @@ impl TargetItem {
                 .highlight_line(line, &self.syntax_set)
                 .unwrap_or_default();
             let escaped = as_24_bit_terminal_escaped(&ranges[..], false);
+            let escaped = escaped.trim_end_matches(['\n', '\r']);
 
-            result.push_str(&format!("{} {:4} │ {}", marker, line_num, escaped));
+            let rows = match code_width {
+                Some(w) => wrap_ansi(escaped, w, MAX_WRAPPED_ROWS),
+                None => wrap_ansi(escaped, usize::MAX, 1),
+            };
+            for (j, row) in rows.iter().enumerate() {
+                if j == 0 {
+                    result.push_str(&format!("{} {:4} │ {}\n", marker, line_num, row));
+                } else {
+                    result.push_str(&format!("{:7}┆ {}\n", "", row));
+                }
+            }
         }
         result.push_str("\x1b[0m"); // Reset colors
 
+        // An aggregate target has no recipe of its own; show what it runs instead
+        if self.target.aggregate {
+            let expansion = makefile::expand_aggregate(&self.target, &self.targets);
+            let rendered = render_expansion(&expansion, self.mode.show_silent);
+            result.push_str(&fit(&rendered, width));
+        }
+
         result
     }
+
+    /// What an inferred target runs, and the manifest it was inferred from
+    fn inferred_preview(&self) -> String {
+        let mut preview = String::new();
+        if let Some(ref description) = self.target.description {
+            preview.push_str(&format!("\x1b[36m{}\x1b[0m\n\n", description));
+        }
+        let file = self.target.file.file_name().unwrap_or_default();
+        let inferred = format!("Inferred from {} (no Makefile)", file.to_string_lossy());
+        preview.push_str(&format!("\x1b[2m{}\x1b[0m\n\n", inferred));
+        let dir = self.target.file.parent().unwrap_or(Path::new("."));
+        if let Some(command) = infer::command(&self.target.name, dir) {
+            preview.push_str(&format!("$ {}\n", quote::command(&command)));
+        }
+        preview
+    }
 }
 
 impl SkimItem for TargetItem {
     fn text(&self) -> Cow<'_, str> {
-        // Return plain text for matching
-        Cow::Borrowed(&self.target.name)
+        // Return plain text for matching, including the root and provider tags
+        Cow::Borrowed(&self.display)
     }
 
-    fn display<'a>(&'a self, _context: DisplayContext<'a>) -> AnsiString<'a> {
-        // Return ANSI-formatted string for display
-        AnsiString::parse(&self.display)
+    fn display<'a>(&'a self, context: DisplayContext<'a>) -> AnsiString<'a> {
+        let colorize = colored::control::SHOULD_COLORIZE.should_colorize();
+        let shown = match self.target.tags.is_empty() || !colorize {
+            true => self.display.clone(),
+            false => {
+                let chips = self.target.tag_chips();
+                let name = &self.display[..self.display.len() - chips.len()];
+                format!("{}\x1b[35m{}\x1b[0m", name, chips)
+            }
+        };
+        let Some(ref path) = self.path else {
+            return AnsiString::parse(&shown);
+        };
+        let padding = padding(&self.display, path, context.container_width);
+        let path = if colorize {
+            format!("\x1b[2m{}\x1b[0m", path)
+        } else {
+            path.clone()
+        };
+        AnsiString::parse(&format!("{}{}{}", shown, padding, path))
     }
 
-    fn preview(&self, _context: PreviewContext) -> ItemPreview {
-        ItemPreview::AnsiText(self.get_highlighted_preview())
+    fn preview(&self, context: PreviewContext) -> ItemPreview {
+        let preview = self.get_highlighted_preview(self.mode.wrap.then_some(context.width));
+        if colored::control::SHOULD_COLORIZE.should_colorize() {
+            ItemPreview::AnsiText(preview)
+        } else {
+            ItemPreview::Text(strip_ansi(&preview))
+        }
     }
 
     fn output(&self) -> Cow<'_, str> {
         Cow::Borrowed(&self.target.name)
     }
+
+    fn get_index(&self) -> usize {
+        self.index
+    }
 }
 
 /// Run the fuzzy finder UI and return the selected target
@@ -184,77 +377,534 @@ pub fn select_target(targets: &[Target]) -> Result<Option<Target>> {
     Ok(None)
 }
 
+/// Pick one of `items` in the fuzzy finder, returning its index
+pub fn select_from(header: &str, items: &[String]) -> Result<Option<usize>> {
+    let options = SkimOptionsBuilder::default()
+        .height("50%".to_string())
+        .multi(false)
+        .reverse(true)
+        .prompt("Select > ".to_string())
+        .header(Some(format!("{} (ESC to cancel)", header)))
+        .color(skim_color())
+        .build()
+        .unwrap();
+
+    let item_reader = SkimItemReader::default();
+    let input = Cursor::new(items.join("\n"));
+    let selected = Skim::run_with(&options, Some(item_reader.of_bufread(input)));
+
+    // Clear the screen after skim exits to remove the TUI
+    print!("\x1B[2J\x1B[H");
+
+    let Some(output) = selected else {
+        return Ok(None);
+    };
+    if output.is_abort {
+        return Ok(None);
+    }
+
+    Ok(output.selected_items.first().and_then(|item| {
+        let text = item.output();
+        items.iter().position(|i| *i == text)
+    }))
+}
+
+/// Levels of prerequisites shown in the preview's dependency tree
+const TREE_DEPTH: usize = 2;
+
+/// Key that toggles private (`_*`) targets in the picker
+const TOGGLE_PRIVATE_KEY: &str = "alt-a";
+/// Key that toggles pattern rules in the picker
+const TOGGLE_PATTERNS_KEY: &str = "alt-p";
+
+/// Key that toggles generated Makefiles' bookkeeping rules in the picker
+const TOGGLE_GENERATED_KEY: &str = "alt-g";
+
+/// Key that toggles the column of Makefile paths in the picker
+const TOGGLE_PATHS_KEY: &str = "alt-f";
+
+/// Key that toggles wrapping long lines in the preview
+const TOGGLE_WRAP_KEY: &str = "alt-w";
+
+/// Key that toggles listing silenced commands in the preview
+const TOGGLE_SILENT_KEY: &str = "alt-s";
+
+/// Key that narrows the picker to each `@tags` tag in turn, then to all
+const CYCLE_TAG_KEY: &str = "alt-t";
+
+/// Keys that scroll the preview sideways, and by how many columns
+const SCROLL_LEFT_KEY: &str = "shift-left";
+const SCROLL_RIGHT_KEY: &str = "shift-right";
+const SCROLL_COLUMNS: usize = 8;
+
+/// Columns taken by the preview's `> 1234 │ ` gutter
+const GUTTER_WIDTH: usize = 9;
+
+/// Narrowest a wrapped line of code gets, however small the preview
+const MIN_CODE_WIDTH: usize = 20;
+
+/// Rows a wrapped line of code takes at most before it is cut off with `…`
+const MAX_WRAPPED_ROWS: usize = 4;
+
+/// Columns between tab stops in the preview
+const TAB_WIDTH: usize = 4;
+
 /// Run the fuzzy finder with preview showing the Makefile context
-pub fn select_target_with_preview(targets: &[Target]) -> Result<Option<Target>> {
+///
+/// `targets` should include private targets and pattern rules; which of them
+/// are shown is controlled by the picker options and can be toggled live.
+pub fn select_target_with_preview(
+    targets: &[Target],
+    picker_options: &PickerOptions,
+) -> Result<Option<Target>> {
     if targets.is_empty() {
         return Ok(None);
     }
 
-    // Create a map for quick lookup
-    let target_map: std::collections::HashMap<String, &Target> =
-        targets.iter().map(|t| (t.name.clone(), t)).collect();
+    let mut ui_started = Some(std::time::Instant::now());
 
     // Load syntax highlighting resources (shared across all items)
+    let all_targets = Arc::new(targets.to_vec());
     let syntax_set = Arc::new(SyntaxSet::load_defaults_newlines());
-    let theme_set = Arc::new(ThemeSet::load_defaults());
+    let theme_name = picker_options.theme.as_deref().unwrap_or(DEFAULT_THEME);
+    let Some(theme) = ThemeSet::load_defaults().themes.remove(theme_name) else {
+        anyhow::bail!(
+            "Unknown preview theme `{}` in [picker] theme (themes: {})",
+            theme_name,
+            themes().join(", ")
+        );
+    };
+    let theme = Arc::new(theme);
 
-    // Create skim items with syntax highlighting support
-    let items: Vec<Arc<dyn SkimItem>> = targets
-        .iter()
-        .map(|t| {
-            Arc::new(TargetItem::new(
-                t.clone(),
-                Arc::clone(&syntax_set),
-                Arc::clone(&theme_set),
-            )) as Arc<dyn SkimItem>
-        })
-        .collect();
+    let mut filter = picker_options.filter();
+    // The generated toggle is only offered when it would change something
+    let has_generated = targets.iter().any(|t| t.boilerplate);
+    // ... and the paths toggle when the targets come from several Makefiles
+    let has_several_files = targets.iter().any(|t| t.file != targets[0].file);
+    let mut show_paths = picker_options.show_paths;
+    // ... and the silent toggle when a preview lists what a target runs
+    let has_aggregate = targets.iter().any(|t| t.aggregate);
+    let mut mode = PreviewMode {
+        wrap: true,
+        show_silent: picker_options.show_silent,
+    };
+    // ... and the tag filter when targets have tags to filter by
+    let tags = tags_of(targets);
+    let mut tag: Option<usize> = None;
+    let mut query: Option<String> = None;
 
-    // Configure skim options with preview
-    let options = SkimOptionsBuilder::default()
-        .height("80%".to_string())
-        .multi(false)
-        .reverse(true)
-        .prompt("Select target > ".to_string())
-        .header(Some(
-            "Make targets (ESC to cancel, ↑/↓ navigate, Enter select)".to_string(),
-        ))
-        .preview(Some("".to_string())) // Enable preview window (content comes from SkimItem)
-        .preview_window("right:70%:wrap".to_string())
-        .build()
-        .unwrap();
+    loop {
+        let tag_name = tag.map(|i| tags[i].as_str());
+        let mut visible: Vec<&Target> = targets
+            .iter()
+            .filter(|t| filter.allows(t))
+            .filter(|t| tag_name.is_none_or(|name| t.has_tag(name)))
+            .collect();
+        if picker_options.rank != RankMode::PureFuzzy {
+            picker_options.frecency.sort(&mut visible);
+        }
+
+        // Create skim items with syntax highlighting support
+        let items: Vec<Arc<dyn SkimItem>> = visible
+            .into_iter()
+            .enumerate()
+            .map(|(index, t)| {
+                let path =
+                    (has_several_files && show_paths).then(|| source_path(t, &picker_options.dir));
+                Arc::new(TargetItem::new(
+                    t.clone(),
+                    path,
+                    index,
+                    Arc::clone(&all_targets),
+                    Arc::clone(&syntax_set),
+                    Arc::clone(&theme),
+                    mode,
+                )) as Arc<dyn SkimItem>
+            })
+            .collect();
+
+        let mut header = "Make targets (ESC to cancel, ↑/↓ navigate, Enter select)".to_string();
+        header.push('\n');
+        header.push_str(&toggle_header(
+            filter.include_private,
+            filter.include_patterns,
+            has_generated.then_some(filter.include_generated),
+            has_several_files.then_some(show_paths),
+            has_aggregate.then_some(mode.show_silent),
+            (!tags.is_empty()).then_some(tag_name),
+            mode.wrap,
+        ));
+        if let Some(ref extra) = picker_options.header {
+            header.push('\n');
+            header.push_str(extra);
+        }
+
+        // Configure skim options with preview
+        let options = SkimOptionsBuilder::default()
+            .height("80%".to_string())
+            .multi(false)
+            .reverse(true)
+            .prompt("Select target > ".to_string())
+            .header(Some(header))
+            .query(query.clone())
+            .bind(vec![
+                format!("{0}:accept({0})", TOGGLE_PRIVATE_KEY),
+                format!("{0}:accept({0})", TOGGLE_PATTERNS_KEY),
+                format!("{0}:accept({0})", TOGGLE_GENERATED_KEY),
+                format!("{0}:accept({0})", TOGGLE_PATHS_KEY),
+                format!("{0}:accept({0})", TOGGLE_WRAP_KEY),
+                format!("{0}:accept({0})", TOGGLE_SILENT_KEY),
+                format!("{0}:accept({0})", CYCLE_TAG_KEY),
+                format!("{}:preview-left({})", SCROLL_LEFT_KEY, SCROLL_COLUMNS),
+                format!("{}:preview-right({})", SCROLL_RIGHT_KEY, SCROLL_COLUMNS),
+            ])
+            .preview(Some("".to_string())) // Enable preview window (content comes from SkimItem)
+            // The preview wraps its own lines, under the gutter rather than into it
+            .preview_window("right:70%:nowrap".to_string())
+            .color(skim_color())
+            .tiebreak(tiebreak(picker_options.rank))
+            .no_sort(picker_options.rank == RankMode::Frecency)
+            .build()
+            .unwrap();
 
-    // Run skim with our custom items
-    let (tx, rx): (SkimItemSender, SkimItemReceiver) = unbounded();
-    for item in items {
-        let _ = tx.send(item);
+        // Run skim with our custom items
+        let (tx, rx): (SkimItemSender, SkimItemReceiver) = unbounded();
+        for item in items {
+            let _ = tx.send(item);
+        }
+        drop(tx); // Close the sender
+
+        if let Some(started) = ui_started.take() {
+            timings::record(Phase::UiInit, started);
+        }
+        let selected = Skim::run_with(&options, Some(rx));
+
+        // Clear the screen after skim exits to remove the TUI
+        print!("\x1B[2J\x1B[H");
+
+        let Some(output) = selected else {
+            return Ok(None);
+        };
+
+        if output.is_abort {
+            return Ok(None);
+        }
+
+        // Toggle keys re-open the picker with the new filter and the same query
+        if let Event::EvActAccept(Some(ref key)) = output.final_event {
+            match key.as_str() {
+                TOGGLE_PRIVATE_KEY => filter.include_private = !filter.include_private,
+                TOGGLE_PATTERNS_KEY => filter.include_patterns = !filter.include_patterns,
+                TOGGLE_GENERATED_KEY => filter.include_generated = !filter.include_generated,
+                TOGGLE_PATHS_KEY => show_paths = !show_paths,
+                TOGGLE_WRAP_KEY => mode.wrap = !mode.wrap,
+                TOGGLE_SILENT_KEY => mode.show_silent = !mode.show_silent,
+                CYCLE_TAG_KEY => tag = next_tag(tag, tags.len()),
+                _ => {}
+            }
+            query = Some(output.query.clone());
+            continue;
+        }
+
+        // Get the selected item; names can repeat across project roots, so
+        // take the target from the item itself rather than looking it up
+        let target = output.selected_items.first().and_then(|item| {
+            item.as_any()
+                .downcast_ref::<TargetItem>()
+                .map(|item| item.target.clone())
+        });
+        return Ok(target);
+    }
+}
+
+/// How skim orders matches; the items' index is their position in the list,
+/// so equally ranked matches never swap places between keystrokes
+fn tiebreak(rank: RankMode) -> Vec<RankCriteria> {
+    match rank {
+        RankMode::PureFuzzy => vec![
+            RankCriteria::Score,
+            RankCriteria::Begin,
+            RankCriteria::End,
+            RankCriteria::Index,
+        ],
+        RankMode::Frecency | RankMode::Hybrid => vec![RankCriteria::Score, RankCriteria::Index],
     }
-    drop(tx); // Close the sender
+}
 
-    let selected = Skim::run_with(&options, Some(rx));
+/// Skim's color scheme: black and white when colors are turned off
+fn skim_color() -> Option<String> {
+    (!colored::control::SHOULD_COLORIZE.should_colorize()).then(|| "bw".to_string())
+}
 
-    // Clear the screen after skim exits to remove the TUI
-    print!("\x1B[2J\x1B[H");
+/// Remove ANSI color codes, for previews when colors are turned off
+fn strip_ansi(text: &str) -> String {
+    let escape = regex::Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+    escape.replace_all(text, "").into_owned()
+}
 
-    match selected {
-        Some(output) => {
-            if output.is_abort {
-                return Ok(None);
+/// The preview section listing what an aggregate target runs: the commands
+/// make echoes, and with `show_silent` the ones it doesn't, dimmed
+fn render_expansion(expansion: &[Expansion], show_silent: bool) -> String {
+    if expansion.is_empty() {
+        return "\n\n\x1b[33mNo recipe and no prerequisites: this target does nothing\x1b[0m\n"
+            .to_string();
+    }
+
+    let mut result = String::from("\n\n\x1b[36mRuns its prerequisites:\x1b[0m\n");
+    for step in expansion {
+        let indent = "  ".repeat(step.depth);
+        match step.recipe {
+            None => result.push_str(&format!(
+                "{}\x1b[32m{}\x1b[0m \x1b[2m(no rule)\x1b[0m\n",
+                indent, step.name
+            )),
+            Some(ref recipe) => {
+                result.push_str(&format!("{}\x1b[32m{}:\x1b[0m\n", indent, step.name));
+                let mut hidden = 0;
+                for line in recipe {
+                    let text = line.text.replace('\n', &format!("\n{}    ", indent));
+                    if !line.silent {
+                        result.push_str(&format!("{}    {}\n", indent, text));
+                    } else if show_silent {
+                        result
+                            .push_str(&format!("{}    \x1b[2m{} (silent)\x1b[0m\n", indent, text));
+                    } else {
+                        hidden += 1;
+                    }
+                }
+                if hidden > 0 {
+                    result.push_str(&format!(
+                        "{}    \x1b[2m({} silent, {} shows)\x1b[0m\n",
+                        indent, hidden, TOGGLE_SILENT_KEY
+                    ));
+                }
             }
+        }
+    }
+    result
+}
 
-            // Get the selected item
-            if let Some(item) = output.selected_items.first() {
-                let selected_text = item.output().to_string();
+/// An ASCII tree of a target's prerequisites, `TREE_DEPTH` levels deep
+///
+/// Prerequisites with more below the cut-off end in `…`; ones that aren't
+/// rules (usually files) are dimmed, and order-only ones are marked.
+fn render_dependency_tree(target: &Target, targets: &[Target]) -> String {
+    let mut result = String::from("\x1b[36mDepends on:\x1b[0m\n");
+    let mut ancestors = vec![target.name.as_str()];
+    tree_into(target, targets, "", &mut ancestors, &mut result);
+    result
+}
 
-                // Find the matching target
-                let target = target_map.get(&selected_text).map(|t| (*t).clone());
-                return Ok(target);
+fn tree_into<'a>(
+    target: &'a Target,
+    targets: &'a [Target],
+    prefix: &str,
+    ancestors: &mut Vec<&'a str>,
+    result: &mut String,
+) {
+    let count = target.deps.len() + target.order_only.len();
+    for (i, dep) in target.prerequisites().enumerate() {
+        let last = i + 1 == count;
+        let branch = if last { "└── " } else { "├── " };
+        let rule = makefile::find_rule(target, dep, targets);
+
+        let mut label = match rule {
+            None => format!("\x1b[2m{}\x1b[0m", dep),
+            Some(_) if ancestors.contains(&dep.as_str()) => {
+                format!("\x1b[32m{}\x1b[0m \x1b[2m(cycle)\x1b[0m", dep)
             }
+            Some(rule)
+                if ancestors.len() == TREE_DEPTH && rule.prerequisites().next().is_some() =>
+            {
+                format!("\x1b[32m{}\x1b[0m …", dep)
+            }
+            Some(_) => format!("\x1b[32m{}\x1b[0m", dep),
+        };
+        if i >= target.deps.len() {
+            label.push_str(" \x1b[2m(order-only)\x1b[0m");
+        }
+        result.push_str(&format!("{}{}{}\n", prefix, branch, label));
+
+        if let Some(rule) = rule
+            && ancestors.len() < TREE_DEPTH
+            && !ancestors.contains(&dep.as_str())
+        {
+            let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            ancestors.push(dep);
+            tree_into(rule, targets, &child_prefix, ancestors, result);
+            ancestors.pop();
         }
-        None => return Ok(None),
     }
+}
 
-    Ok(None)
+/// Every tag of `targets`, sorted, each once whatever its case
+fn tags_of(targets: &[Target]) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in targets.iter().flat_map(|t| &t.tags) {
+        if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.clone());
+        }
+    }
+    tags.sort_by_key(|t| t.to_lowercase());
+    tags
+}
+
+/// The tag the picker narrows to after `current`: the next one, and after
+/// the last none, showing every target again
+fn next_tag(current: Option<usize>, count: usize) -> Option<usize> {
+    let next = current.map_or(0, |i| i + 1);
+    (next < count).then_some(next)
+}
+
+/// A target's Makefile relative to its project root, or else to `dir`
+pub(crate) fn source_path(target: &Target, dir: &Path) -> String {
+    let base = target.root.as_deref().unwrap_or(dir);
+    let file = target.file.strip_prefix(base).unwrap_or(&target.file);
+    file.display().to_string()
+}
+
+/// Spaces that push `path` to the right edge of a `width`-wide list after
+/// `display`, or two when there isn't room
+fn padding(display: &str, path: &str, width: usize) -> String {
+    let used = width::columns(display) + width::columns(path);
+    // Keep clear of the last column, where skim would start scrolling
+    " ".repeat(width.saturating_sub(used + 1).max(2))
+}
+
+/// Header line describing the toggle keys and their state; the generated,
+/// paths, and silent toggles are left out when they are `None`
+fn toggle_header(
+    include_private: bool,
+    include_patterns: bool,
+    include_generated: Option<bool>,
+    show_paths: Option<bool>,
+    show_silent: Option<bool>,
+    tag: Option<Option<&str>>,
+    wrap: bool,
+) -> String {
+    let state = |on: bool| if on { "on" } else { "off" };
+    let mut header = format!(
+        "{} private: {}  {} patterns: {}",
+        TOGGLE_PRIVATE_KEY,
+        state(include_private),
+        TOGGLE_PATTERNS_KEY,
+        state(include_patterns)
+    );
+    if let Some(on) = include_generated {
+        header.push_str(&format!(
+            "  {} generated: {}",
+            TOGGLE_GENERATED_KEY,
+            state(on)
+        ));
+    }
+    if let Some(on) = show_paths {
+        header.push_str(&format!("  {} paths: {}", TOGGLE_PATHS_KEY, state(on)));
+    }
+    if let Some(on) = show_silent {
+        header.push_str(&format!("  {} silent: {}", TOGGLE_SILENT_KEY, state(on)));
+    }
+    if let Some(tag) = tag {
+        let tag = tag.map_or("all".to_string(), |t| format!("#{}", t));
+        header.push_str(&format!("  {} tag: {}", CYCLE_TAG_KEY, tag));
+    }
+    header.push_str(&format!("  {} wrap: {}", TOGGLE_WRAP_KEY, state(wrap)));
+    if !wrap {
+        header.push_str(" (shift-←/→ scroll)");
+    }
+    header
+}
+
+/// Each line of `text` wrapped to `width` columns, when given
+fn fit(text: &str, width: Option<usize>) -> String {
+    let Some(width) = width else {
+        return text.to_string();
+    };
+    let mut result = String::new();
+    for line in LinesWithEndings::from(text) {
+        let content = line.trim_end_matches('\n');
+        let rows = wrap_ansi(
+            content,
+            width.saturating_sub(1).max(MIN_CODE_WIDTH),
+            usize::MAX,
+        );
+        result.push_str(&rows.join("\n"));
+        if line.ends_with('\n') {
+            result.push('\n');
+        }
+    }
+    result
+}
+
+/// One line of possibly colored text cut into rows of at most `width`
+/// columns, with tabs expanded; a row starts in the color the last one ended
+/// in, and a line needing more than `max_rows` rows ends the last one in `…`
+fn wrap_ansi(text: &str, width: usize, max_rows: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut rows = vec![String::new()];
+    // The color in effect, and where in the row the last character starts
+    let mut color = String::new();
+    let mut last_char: Option<usize> = None;
+    let mut column = 0;
+    let mut line_column = 0;
+
+    let mut chars = text.chars();
+    'text: while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            let mut escape = String::from(c);
+            for next in chars.by_ref() {
+                escape.push(next);
+                if next.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            if escape.ends_with('m') {
+                color = if escape == "\x1b[0m" {
+                    String::new()
+                } else {
+                    escape.clone()
+                };
+            }
+            rows.last_mut().unwrap().push_str(&escape);
+            continue;
+        }
+
+        // A tab is as many spaces as reach the next tab stop; wide characters
+        // take two columns, combining marks none
+        let (glyph, cells, count) = match c {
+            '\t' => (' ', 1, TAB_WIDTH - line_column % TAB_WIDTH),
+            c => (c, width::columns(c.encode_utf8(&mut [0; 4])), 1),
+        };
+        for _ in 0..count {
+            if column > 0 && column + cells > width {
+                let full = rows.len() == max_rows;
+                let row = rows.last_mut().unwrap();
+                if full {
+                    if let Some(at) = last_char {
+                        let len = row[at..].chars().next().map_or(0, char::len_utf8);
+                        row.replace_range(at..at + len, "…");
+                    }
+                    break 'text;
+                }
+                if !color.is_empty() {
+                    row.push_str("\x1b[0m");
+                }
+                rows.push(color.clone());
+                column = 0;
+            }
+            let row = rows.last_mut().unwrap();
+            if cells > 0 {
+                last_char = Some(row.len());
+            }
+            row.push(glyph);
+            column += cells;
+            line_column += cells;
+        }
+    }
+
+    if !color.is_empty() {
+        rows.last_mut().unwrap().push_str("\x1b[0m");
+    }
+    rows
 }
 
 /// Get a snippet of the Makefile around a target for display
@@ -282,6 +932,7 @@ pub fn get_target_snippet(target: &Target, context_lines: usize) -> Result<Strin
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::makefile::RecipeLine;
     use std::path::PathBuf;
 
     #[test]
@@ -292,6 +943,24 @@ mod tests {
         assert!(result.unwrap().is_none());
     }
 
+    #[test]
+    fn test_unknown_theme_is_an_error() {
+        assert!(themes().iter().any(|t| t == DEFAULT_THEME));
+
+        let targets = [Target::new(
+            "build".to_string(),
+            None,
+            PathBuf::from("Makefile"),
+            1,
+        )];
+        let options = PickerOptions {
+            theme: Some("neon".to_string()),
+            ..Default::default()
+        };
+        let err = select_target_with_preview(&targets, &options).unwrap_err();
+        assert!(err.to_string().contains("Unknown preview theme `neon`"));
+    }
+
     #[test]
     fn test_get_target_snippet() {
         use std::io::Write;
@@ -301,7 +970,7 @@ mod tests {
         writeln!(file, "# Comment").unwrap();
         writeln!(file, "build:").unwrap();
         writeln!(file, "\techo building").unwrap();
-        writeln!(file, "").unwrap();
+        writeln!(file).unwrap();
         writeln!(file, "test:").unwrap();
         writeln!(file, "\techo testing").unwrap();
 
@@ -328,4 +997,238 @@ mod tests {
         assert_eq!(target_with_desc.display_name(), "build");
         assert_eq!(target_without_desc.display_name(), "clean");
     }
+
+    #[test]
+    fn test_render_expansion() {
+        let expansion = vec![
+            Expansion {
+                name: "build".to_string(),
+                depth: 0,
+                recipe: Some(vec![
+                    RecipeLine {
+                        text: "cargo build".to_string(),
+                        silent: false,
+                    },
+                    RecipeLine {
+                        text: "echo Built".to_string(),
+                        silent: true,
+                    },
+                ]),
+            },
+            Expansion {
+                name: "docs".to_string(),
+                depth: 1,
+                recipe: None,
+            },
+        ];
+        let rendered = render_expansion(&expansion, false);
+
+        assert!(rendered.contains("Runs its prerequisites"));
+        assert!(rendered.contains(
+            "\x1b[32mbuild:\x1b[0m\n    cargo build\n    \x1b[2m(1 silent, alt-s shows)"
+        ));
+        assert!(rendered.contains("  \x1b[32mdocs\x1b[0m \x1b[2m(no rule)"));
+        assert!(render_expansion(&[], false).contains("does nothing"));
+
+        // Shown, silent commands are marked as such
+        let rendered = strip_ansi(&render_expansion(&expansion, true));
+        assert!(rendered.contains("    cargo build\n    echo Built (silent)\n"));
+    }
+
+    #[test]
+    fn test_render_dependency_tree() {
+        let target = |name: &str, deps: &[&str]| {
+            let mut t = Target::new(name.to_string(), None, PathBuf::from("Makefile"), 1);
+            t.deps = deps.iter().map(|d| d.to_string()).collect();
+            t
+        };
+        let mut targets = vec![
+            target("deploy", &["build", "test"]),
+            target("build", &["gen", "main.c"]),
+            target("gen", &["schema"]),
+            target("schema", &[]),
+            target("test", &["deploy"]),
+        ];
+        targets[0].order_only = vec!["out".to_string()];
+
+        let tree = strip_ansi(&render_dependency_tree(&targets[0], &targets));
+
+        assert_eq!(
+            tree,
+            "\
+Depends on:
+├── build
+│   ├── gen …
+│   └── main.c
+├── test
+│   └── deploy (cycle)
+└── out (order-only)
+"
+        );
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            strip_ansi("\x1b[36mBuild\x1b[0m\n> 1 │ \x1b[38;2;1;2;3mbuild:\x1b[0m"),
+            "Build\n> 1 │ build:"
+        );
+    }
+
+    #[test]
+    fn test_toggle_header() {
+        assert_eq!(
+            toggle_header(true, false, None, None, None, None, true),
+            "alt-a private: on  alt-p patterns: off  alt-w wrap: on"
+        );
+        assert_eq!(
+            toggle_header(false, false, Some(false), None, None, Some(None), true),
+            "alt-a private: off  alt-p patterns: off  alt-g generated: off  alt-t tag: all  \
+             alt-w wrap: on"
+        );
+        assert_eq!(
+            toggle_header(
+                false,
+                true,
+                None,
+                Some(true),
+                Some(false),
+                Some(Some("docker")),
+                false
+            ),
+            "alt-a private: off  alt-p patterns: on  alt-f paths: on  alt-s silent: off  \
+             alt-t tag: #docker  alt-w wrap: off (shift-←/→ scroll)"
+        );
+    }
+
+    #[test]
+    fn test_tags_cycle_then_show_all() {
+        let mut image = Target::new("image".to_string(), None, PathBuf::from("Makefile"), 1);
+        image.tags = vec!["docker".to_string(), "Release".to_string()];
+        let mut push = Target::new("push".to_string(), None, PathBuf::from("Makefile"), 4);
+        push.tags = vec!["Docker".to_string(), "ci".to_string()];
+
+        assert_eq!(tags_of(&[image, push]), ["ci", "docker", "Release"]);
+        assert_eq!(next_tag(None, 3), Some(0));
+        assert_eq!(next_tag(Some(1), 3), Some(2));
+        assert_eq!(next_tag(Some(2), 3), None);
+        assert_eq!(next_tag(None, 0), None);
+    }
+
+    #[test]
+    fn test_wrap_ansi() {
+        assert_eq!(wrap_ansi("make build", 20, 4), vec!["make build"]);
+        assert_eq!(
+            wrap_ansi("cargo build --release", 8, 4),
+            vec!["cargo bu", "ild --re", "lease"]
+        );
+        // Tabs are expanded, and the last row allowed ends in …
+        assert_eq!(
+            wrap_ansi("\tcargo build --release", 8, 2),
+            vec!["    carg", "o build…"]
+        );
+        assert_eq!(wrap_ansi("short", usize::MAX, 1), vec!["short"]);
+        // Wide characters take two columns, and one that doesn't fit in what
+        // is left of a row starts the next
+        assert_eq!(wrap_ansi("echo 构建完成", 8, 4), vec!["echo 构", "建完成"]);
+        assert_eq!(wrap_ansi("🚀🚀🚀", 5, 4), vec!["🚀🚀", "🚀"]);
+        assert_eq!(
+            wrap_ansi("cafe\u{301} ok", 5, 4),
+            vec!["cafe\u{301} ", "ok"]
+        );
+    }
+
+    #[test]
+    fn test_wrap_ansi_carries_colors() {
+        let rows = wrap_ansi("\x1b[32mabcdef\x1b[0mgh", 4, 4);
+
+        assert_eq!(rows, vec!["\x1b[32mabcd\x1b[0m", "\x1b[32mef\x1b[0mgh"]);
+        assert_eq!(strip_ansi(&rows.concat()), "abcdefgh");
+    }
+
+    #[test]
+    fn test_preview_fits_its_width() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("Makefile");
+        let long = "docker build --tag registry.example.com/app:latest --file Dockerfile .";
+        fs::write(&file, format!("## Build the image\nimage:\n\t{}\n", long)).unwrap();
+        let target = Target::new(
+            "image".to_string(),
+            Some("Build the image".to_string()),
+            file,
+            2,
+        );
+        let item = TargetItem::new(
+            target.clone(),
+            None,
+            0,
+            Arc::new(vec![target]),
+            Arc::new(SyntaxSet::load_defaults_newlines()),
+            Arc::new(ThemeSet::load_defaults().themes[DEFAULT_THEME].clone()),
+            PreviewMode {
+                wrap: true,
+                show_silent: false,
+            },
+        );
+
+        let wrapped = strip_ansi(&item.get_highlighted_preview(Some(40)));
+        assert!(
+            wrapped.lines().all(|l| l.chars().count() < 40),
+            "{}",
+            wrapped
+        );
+        assert!(wrapped.contains("\n       ┆ "));
+        assert_eq!(wrapped.replace("\n       ┆ ", "").matches(long).count(), 1);
+
+        // Unwrapped, the line is left whole for scrolling sideways
+        let whole = strip_ansi(&item.get_highlighted_preview(None));
+        assert!(whole.contains(&format!("     3 │     {}\n", long)));
+    }
+
+    #[test]
+    fn test_fit_wraps_each_line() {
+        let text = format!("{}\nshort\n", "x".repeat(30));
+
+        assert_eq!(fit(&text, None), text);
+        assert_eq!(
+            fit(&text, Some(26)),
+            format!("{}\n{}\nshort\n", "x".repeat(25), "x".repeat(5))
+        );
+    }
+
+    #[test]
+    fn test_source_path_is_relative_to_the_project() {
+        let mut target = Target::new(
+            "serve".to_string(),
+            None,
+            PathBuf::from("/src/app/api/Makefile"),
+            1,
+        );
+        assert_eq!(source_path(&target, Path::new("/src/app")), "api/Makefile");
+        assert_eq!(
+            source_path(&target, Path::new("/elsewhere")),
+            "/src/app/api/Makefile"
+        );
+
+        target.root = Some(PathBuf::from("/src/app/api"));
+        assert_eq!(source_path(&target, Path::new("/src/app")), "Makefile");
+    }
+
+    #[test]
+    fn test_padding_right_aligns_the_path() {
+        assert_eq!(padding("build", "api/Makefile", 20), "  ");
+        assert_eq!(padding("build", "Makefile", 20).len(), 6);
+        assert_eq!(
+            format!(
+                "{}{}{}",
+                "build",
+                padding("build", "Makefile", 40),
+                "Makefile"
+            )
+            .len(),
+            39
+        );
+        // Wide characters take two columns each
+        assert_eq!(padding("构建", "Makefile", 20), " ".repeat(7));
+    }
 }
@@ -0,0 +1,121 @@
+use std::collections::HashSet;
+
+/// A tool that writes Makefiles full of its own bookkeeping rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Generator {
+    CMake,
+    Automake,
+}
+
+/// Targets worth running in any generated Makefile
+const ENTRY_POINTS: &[&str] = &[
+    "all",
+    "install",
+    "install-strip",
+    "uninstall",
+    "check",
+    "installcheck",
+    "test",
+    "clean",
+    "distclean",
+    "dist",
+    "distcheck",
+    "package",
+    "help",
+];
+
+/// Rules CMake adds to every Makefile besides the entry points
+const CMAKE_BOILERPLATE: &[&str] = &[
+    "depend",
+    "edit_cache",
+    "rebuild_cache",
+    "preinstall",
+    "list_install_components",
+    "package_source",
+];
+
+/// Lines at the top of a file searched for a generator's banner
+const BANNER_LINES: usize = 5;
+
+/// The tool that generated a Makefile, from the banner it writes at the top
+pub fn detect(content: &str) -> Option<Generator> {
+    content.lines().take(BANNER_LINES).find_map(|line| {
+        if line.contains("CMAKE generated file") {
+            Some(Generator::CMake)
+        } else if line.contains("generated by automake") {
+            Some(Generator::Automake)
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether `name` is one of the generator's bookkeeping rules rather than
+/// an entry point or a target the project defines itself
+///
+/// `custom` holds the rules the project wrote by hand, e.g. those in the
+/// `Makefile.am` an automake Makefile was generated from.
+pub fn is_boilerplate(generator: Generator, name: &str, custom: &HashSet<String>) -> bool {
+    if ENTRY_POINTS.contains(&name) || custom.contains(name) {
+        return false;
+    }
+
+    match generator {
+        // CMake names executables and custom targets after the project's own
+        // targets, so only its known helpers and per-file rules are noise
+        Generator::CMake => {
+            name.starts_with('.')
+                || name.starts_with("cmake_")
+                || name.contains('/')
+                || CMAKE_BOILERPLATE.contains(&name)
+                || [".o", ".obj", ".i", ".s"]
+                    .iter()
+                    .any(|ext| name.ends_with(ext))
+        }
+        // Every other rule automake writes is internal (`install-am`,
+        // `mostlyclean-compile`, `ctags`, ...)
+        Generator::Automake => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let cmake = "# CMAKE generated file: DO NOT EDIT!\n# Generated by \"Unix Makefiles\"\n";
+        let automake = "# Makefile.in generated by automake 1.16.5 from Makefile.am.\n";
+
+        assert_eq!(detect(cmake), Some(Generator::CMake));
+        assert_eq!(detect(automake), Some(Generator::Automake));
+        assert_eq!(detect("build:\n\tcargo build\n"), None);
+    }
+
+    #[test]
+    fn test_cmake_boilerplate() {
+        let none = HashSet::new();
+        for name in [
+            "cmake_check_build_system",
+            "app/fast",
+            "main.c.o",
+            "main.i",
+            "edit_cache",
+        ] {
+            assert!(is_boilerplate(Generator::CMake, name, &none), "{}", name);
+        }
+        for name in ["all", "install", "test", "app", "docs"] {
+            assert!(!is_boilerplate(Generator::CMake, name, &none), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_automake_keeps_custom_targets() {
+        let custom = HashSet::from(["release".to_string()]);
+
+        assert!(!is_boilerplate(Generator::Automake, "check", &custom));
+        assert!(!is_boilerplate(Generator::Automake, "release", &custom));
+        assert!(is_boilerplate(Generator::Automake, "install-am", &custom));
+        assert!(is_boilerplate(Generator::Automake, "ctags", &custom));
+    }
+}
@@ -0,0 +1,42 @@
+use regex::Regex;
+
+/// Whether a shell glob (`*`, `**`) or make pattern (`%`) matches `path`
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches no directories at all
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '%' => regex.push_str(".*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+
+    Regex::new(&regex).is_ok_and(|re| re.is_match(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("proto/*.proto", "proto/api.proto"));
+        assert!(!glob_matches("proto/*.proto", "proto/v1/api.proto"));
+        assert!(glob_matches("proto/**/*.proto", "proto/v1/api.proto"));
+        assert!(glob_matches("proto/**/*.proto", "proto/api.proto"));
+        assert!(glob_matches("%.c", "src/main.c"));
+    }
+}
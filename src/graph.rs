@@ -0,0 +1,349 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::makefile;
+use crate::target::{Provider, Target};
+
+/// How one target needs another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// Listed after the colon (`deploy: build`)
+    Prerequisite,
+    /// Listed after a `|` (`build: | out`)
+    OrderOnly,
+    /// Made by a `$(MAKE) name` call in the recipe
+    RecursiveMake,
+}
+
+/// `from` needs `to`, both indices into the graph's targets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// The targets and how they need each other
+///
+/// Only Makefile targets have edges, each to a target of the same project
+/// root. Prerequisites that are files or pattern rules aren't nodes, and
+/// special targets like `.PHONY` list other targets without needing them.
+#[derive(Debug, Clone, Serialize)]
+pub struct Graph<'a> {
+    targets: &'a [Target],
+    edges: Vec<Edge>,
+}
+
+impl<'a> Graph<'a> {
+    /// The graph of the targets' prerequisites and order-only prerequisites
+    pub fn new(targets: &'a [Target]) -> Self {
+        let mut graph = Self {
+            targets,
+            edges: Vec::new(),
+        };
+        for (from, target) in targets.iter().enumerate() {
+            if target.provider != Provider::Make || target.name.starts_with('.') {
+                continue;
+            }
+            let kinds = std::iter::repeat_n(EdgeKind::Prerequisite, target.deps.len())
+                .chain(std::iter::repeat(EdgeKind::OrderOnly));
+            for (name, kind) in target.prerequisites().zip(kinds) {
+                graph.connect(from, name, kind);
+            }
+        }
+        graph
+    }
+
+    /// The graph with edges for the `$(MAKE) name` calls of each recipe too,
+    /// reading the targets' Makefiles
+    pub fn with_recursive_make(mut self) -> Self {
+        let targets = self.targets;
+        let mut contents: HashMap<&Path, String> = HashMap::new();
+        for (from, target) in targets.iter().enumerate() {
+            if target.provider != Provider::Make || target.name.starts_with('.') {
+                continue;
+            }
+            if !contents.contains_key(target.file.as_path()) {
+                let content = makefile::read_makefile(&target.file)
+                    .map(|(content, _)| content)
+                    .unwrap_or_default();
+                contents.insert(&target.file, content);
+            }
+            let lines: Vec<&str> = contents[target.file.as_path()].lines().collect();
+            for line in makefile::recipe_lines(&lines, target.line.saturating_sub(1)) {
+                for goal in make_goals(line) {
+                    self.connect(from, goal, EdgeKind::RecursiveMake);
+                }
+            }
+        }
+        self
+    }
+
+    /// Add an edge from `from` to the target called `name` in its root, if
+    /// there is one and the two aren't joined already
+    fn connect(&mut self, from: usize, name: &str, kind: EdgeKind) {
+        let root = &self.targets[from].root;
+        let Some(to) = self
+            .targets
+            .iter()
+            .position(|t| t.name == name && t.provider == Provider::Make && t.root == *root)
+        else {
+            return;
+        };
+        if !self.edges.iter().any(|e| e.from == from && e.to == to) {
+            self.edges.push(Edge { from, to, kind });
+        }
+    }
+
+    pub fn targets(&self) -> &'a [Target] {
+        self.targets
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// The first target called `name`
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.targets.iter().position(|t| t.name == name)
+    }
+
+    /// What `node` needs, in the order the Makefile lists it
+    pub fn needs(&self, node: usize) -> impl Iterator<Item = (usize, EdgeKind)> + '_ {
+        self.edges
+            .iter()
+            .filter(move |e| e.from == node)
+            .map(|e| (e.to, e.kind))
+    }
+
+    /// The targets that need `node` directly
+    pub fn dependents(&self, node: usize) -> Vec<usize> {
+        self.edges
+            .iter()
+            .filter(|e| e.to == node)
+            .map(|e| e.from)
+            .collect()
+    }
+
+    /// Every target that needs `node`, directly or through others, nearest
+    /// first
+    pub fn all_dependents(&self, node: usize) -> Vec<usize> {
+        let mut found = self.dependents(node);
+        let mut next = 0;
+        while next < found.len() {
+            for dependent in self.dependents(found[next]) {
+                if dependent != node && !found.contains(&dependent) {
+                    found.push(dependent);
+                }
+            }
+            next += 1;
+        }
+        found
+    }
+
+    /// Every target, each after all it needs, otherwise in the order of the
+    /// targets; or a cycle, each target in it needing the next and the last
+    /// needing the first
+    pub fn topological_order(&self) -> Result<Vec<usize>, Vec<usize>> {
+        let mut state = vec![Visit::New; self.targets.len()];
+        let mut order = Vec::with_capacity(self.targets.len());
+        let mut path = Vec::new();
+        for node in 0..self.targets.len() {
+            self.visit(node, &mut state, &mut path, &mut order)?;
+        }
+        Ok(order)
+    }
+
+    /// A cycle, if the targets need each other in one
+    pub fn cycle(&self) -> Option<Vec<usize>> {
+        self.topological_order().err()
+    }
+
+    /// Put `node` in `order` after what it needs; `path` holds the targets
+    /// being visited, to tell the cycle when one of them comes up again
+    fn visit(
+        &self,
+        node: usize,
+        state: &mut [Visit],
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), Vec<usize>> {
+        match state[node] {
+            Visit::Done => return Ok(()),
+            Visit::Open => {
+                let start = path.iter().position(|&n| n == node).unwrap_or(0);
+                return Err(path[start..].to_vec());
+            }
+            Visit::New => {}
+        }
+        state[node] = Visit::Open;
+        path.push(node);
+        for (need, _) in self.needs(node) {
+            self.visit(need, state, path, order)?;
+        }
+        path.pop();
+        state[node] = Visit::Done;
+        order.push(node);
+        Ok(())
+    }
+}
+
+/// How far `Graph::topological_order` has got with a target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    New,
+    Open,
+    Done,
+}
+
+/// The goals of the `$(MAKE)` / `${MAKE}` calls in a recipe line, leaving
+/// out calls with `-C` or `-f`, which are another Makefile's
+fn make_goals(line: &str) -> Vec<&str> {
+    let mut goals = Vec::new();
+    let mut rest = line;
+    while let Some(pos) = ["$(MAKE)", "${MAKE}"]
+        .iter()
+        .filter_map(|call| rest.find(call))
+        .min()
+    {
+        rest = &rest[pos + "$(MAKE)".len()..];
+        // The call's arguments run up to the next shell separator
+        let end = [";", "&&", "||", "|", ")", "`"]
+            .iter()
+            .filter_map(|sep| rest.find(sep))
+            .min()
+            .unwrap_or(rest.len());
+        let words: Vec<&str> = rest[..end].split_whitespace().collect();
+        rest = &rest[end..];
+
+        let elsewhere = |word: &&str| {
+            ["-C", "-f", "--directory", "--file"]
+                .iter()
+                .any(|flag| word.starts_with(flag))
+        };
+        if words.iter().any(elsewhere) {
+            continue;
+        }
+        let mut takes_value = false;
+        for word in words {
+            if !takes_value && !word.starts_with('-') && !word.contains(['=', '$']) {
+                goals.push(word);
+            }
+            takes_value = matches!(word, "-I" | "-o" | "-W");
+        }
+    }
+    goals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn names(graph: &Graph, nodes: &[usize]) -> Vec<String> {
+        nodes
+            .iter()
+            .map(|&n| graph.targets()[n].name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_edges() {
+        let mut build = Target::with_deps("build", &["gen", "src/main.c"]);
+        build.order_only = vec!["out".to_string()];
+        let targets = [
+            Target::with_deps("gen", &[]),
+            Target::with_deps("out", &[]),
+            build,
+            Target::with_deps("test", &["build", "build"]),
+            Target::with_deps(".PHONY", &["build", "test"]),
+        ];
+
+        let graph = Graph::new(&targets);
+        assert_eq!(
+            graph.needs(2).collect::<Vec<_>>(),
+            vec![(0, EdgeKind::Prerequisite), (1, EdgeKind::OrderOnly)]
+        );
+        assert_eq!(graph.edges().len(), 3);
+        assert_eq!(graph.dependents(2), vec![3]);
+        assert_eq!(
+            names(&graph, &graph.all_dependents(0)),
+            vec!["build", "test"]
+        );
+
+        let json = serde_json::to_value(&graph).unwrap();
+        assert_eq!(json["edges"][1]["kind"], "order_only");
+    }
+
+    #[test]
+    fn test_edges_stay_in_their_root() {
+        let mut api_build = Target::with_deps("build", &[]);
+        api_build.root = Some(PathBuf::from("/src/api"));
+        let mut web_build = Target::with_deps("build", &[]);
+        web_build.root = Some(PathBuf::from("/src/web"));
+        let mut web_test = Target::with_deps("test", &["build"]);
+        web_test.root = Some(PathBuf::from("/src/web"));
+
+        let targets = [api_build, web_build, web_test];
+        let graph = Graph::new(&targets);
+        assert_eq!(
+            graph.needs(2).collect::<Vec<_>>(),
+            vec![(1, EdgeKind::Prerequisite)]
+        );
+    }
+
+    #[test]
+    fn test_topological_order_and_cycles() {
+        let targets = [
+            Target::with_deps("deploy", &["test", "build"]),
+            Target::with_deps("test", &["build"]),
+            Target::with_deps("build", &[]),
+        ];
+        let graph = Graph::new(&targets);
+        let order = graph.topological_order().unwrap();
+        assert_eq!(names(&graph, &order), vec!["build", "test", "deploy"]);
+        assert_eq!(graph.cycle(), None);
+
+        let targets = [
+            Target::with_deps("docs", &[]),
+            Target::with_deps("a", &["b"]),
+            Target::with_deps("b", &["c"]),
+            Target::with_deps("c", &["a"]),
+        ];
+        let graph = Graph::new(&targets);
+        let cycle = graph.cycle().unwrap();
+        assert_eq!(names(&graph, &cycle), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_recursive_make_edges() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("Makefile");
+        std::fs::write(
+            &file,
+            "release:\n\t$(MAKE) build V=1 && ${MAKE} -j 4 test\n\t$(MAKE) -C docs html\n\
+             build:\n\t@cc main.c\ntest:\n\t@./test.sh\nhtml:\n\t@true\n",
+        )
+        .unwrap();
+        let targets = makefile::parse_makefile(&file, &makefile::ParseOptions::default()).unwrap();
+
+        let graph = Graph::new(&targets).with_recursive_make();
+        let release = graph.find("release").unwrap();
+        let needs: Vec<(String, EdgeKind)> = graph
+            .needs(release)
+            .map(|(n, kind)| (targets[n].name.clone(), kind))
+            .collect();
+        assert_eq!(
+            needs,
+            vec![
+                ("build".to_string(), EdgeKind::RecursiveMake),
+                ("test".to_string(), EdgeKind::RecursiveMake)
+            ]
+        );
+
+        assert_eq!(make_goals("$(MAKE) -f other.mk all"), Vec::<&str>::new());
+        assert_eq!(make_goals("+$(MAKE) -I inc notify"), vec!["notify"]);
+    }
+}
@@ -0,0 +1,252 @@
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::glob::glob_matches;
+use crate::target::Target;
+
+/// A rule that asks before targets run at a bad time (`[[guard]]` tables),
+/// e.g. no deploys outside office hours or on Fridays
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Guard {
+    /// Tags of the targets it covers, e.g. `deploy`
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Globs over target names, e.g. `release*`; without these or tags,
+    /// every target
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// The local time window runs are fine in, e.g. `09:00-17:00`
+    #[serde(default)]
+    pub hours: Option<Hours>,
+    /// Days runs aren't fine on, e.g. `["fri"]`
+    #[serde(default)]
+    pub not_on: Vec<Day>,
+    /// What to say instead of the rule, e.g. why it is there
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+impl Guard {
+    /// Whether runs of `target` are checked against it
+    pub fn covers(&self, target: &Target) -> bool {
+        (self.tags.is_empty() && self.targets.is_empty())
+            || self.tags.iter().any(|tag| target.has_tag(tag))
+            || self.targets.iter().any(|p| glob_matches(p, &target.name))
+    }
+
+    /// What the rule says when a run at `now` breaks it, or None when the
+    /// run is fine
+    pub fn violation(&self, now: NaiveDateTime) -> Option<String> {
+        let outside = self
+            .hours
+            .as_ref()
+            .is_some_and(|hours| !hours.contains(now.time()));
+        let day_off = self.not_on.iter().any(|day| day.0 == now.weekday());
+        if !outside && !day_off {
+            return None;
+        }
+        Some(match &self.message {
+            Some(message) => message.clone(),
+            None => self.rule(),
+        })
+    }
+
+    /// The rule in words, e.g. "targets tagged deploy shouldn't run outside
+    /// 09:00-17:00 or on Fridays"
+    pub fn rule(&self) -> String {
+        let mut covered = Vec::new();
+        if !self.tags.is_empty() {
+            covered.push(format!("targets tagged {}", self.tags.join(", ")));
+        }
+        if !self.targets.is_empty() {
+            covered.push(self.targets.join(", "));
+        }
+        let who = match covered.is_empty() {
+            true => "targets".to_string(),
+            false => covered.join(" and "),
+        };
+
+        let mut when = Vec::new();
+        if let Some(hours) = &self.hours {
+            when.push(format!("outside {hours}"));
+        }
+        if !self.not_on.is_empty() {
+            let days: Vec<String> = self.not_on.iter().map(|d| format!("{d}s")).collect();
+            when.push(format!("on {}", days.join(" or ")));
+        }
+        match when.is_empty() {
+            true => format!("{who} shouldn't run"),
+            false => format!("{who} shouldn't run {}", when.join(" or ")),
+        }
+    }
+}
+
+/// The rules `guards` has that a run at `now` breaks
+pub fn violations(guards: &[Guard], now: NaiveDateTime) -> Vec<String> {
+    guards.iter().filter_map(|g| g.violation(now)).collect()
+}
+
+/// A window of the day, `HH:MM-HH:MM`; one that ends before it starts
+/// runs past midnight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Hours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl Hours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        // Seconds don't matter, so 17:00:30 is already past 17:00
+        let time = time.with_second(0).unwrap_or(time);
+        match self.start <= self.end {
+            true => self.start <= time && time < self.end,
+            false => time >= self.start || time < self.end,
+        }
+    }
+}
+
+impl TryFrom<String> for Hours {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let parse = |s: &str| NaiveTime::parse_from_str(s.trim(), "%H:%M").ok();
+        value
+            .split_once('-')
+            .and_then(|(start, end)| Some((parse(start)?, parse(end)?)))
+            .map(|(start, end)| Self { start, end })
+            .ok_or_else(|| format!("invalid hours {value:?}, expected e.g. \"09:00-17:00\""))
+    }
+}
+
+impl From<Hours> for String {
+    fn from(hours: Hours) -> Self {
+        hours.to_string()
+    }
+}
+
+impl fmt::Display for Hours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+/// A day of the week, `mon` or `monday`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Day(pub Weekday);
+
+impl TryFrom<String> for Day {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value
+            .parse()
+            .map(Day)
+            .map_err(|_| format!("invalid day {value:?}, expected e.g. \"fri\""))
+    }
+}
+
+impl From<Day> for String {
+    fn from(day: Day) -> Self {
+        day.0.to_string().to_lowercase()
+    }
+}
+
+impl fmt::Display for Day {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.0 {
+            Weekday::Mon => "Monday",
+            Weekday::Tue => "Tuesday",
+            Weekday::Wed => "Wednesday",
+            Weekday::Thu => "Thursday",
+            Weekday::Fri => "Friday",
+            Weekday::Sat => "Saturday",
+            Weekday::Sun => "Sunday",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn guard(toml: &str) -> Guard {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn at(date: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_violation_outside_hours_or_on_days_off() {
+        let guard = guard("tags = [\"deploy\"]\nhours = \"09:00-17:00\"\nnot_on = [\"fri\"]\n");
+        let rule = "targets tagged deploy shouldn't run outside 09:00-17:00 or on Fridays";
+
+        // 2026-10-14 is a Wednesday
+        assert_eq!(guard.violation(at("2026-10-14 10:30")), None);
+        assert_eq!(
+            guard.violation(at("2026-10-14 08:59")),
+            Some(rule.to_string())
+        );
+        assert_eq!(
+            guard.violation(at("2026-10-14 17:00")),
+            Some(rule.to_string())
+        );
+        assert_eq!(
+            guard.violation(at("2026-10-16 10:30")),
+            Some(rule.to_string())
+        );
+
+        let guard = Guard {
+            message: Some("Ship on Monday instead".to_string()),
+            ..guard
+        };
+        assert_eq!(
+            guard.violation(at("2026-10-16 10:30")),
+            Some("Ship on Monday instead".to_string())
+        );
+    }
+
+    #[test]
+    fn test_hours_past_midnight() {
+        let hours = Hours::try_from("22:00-06:00".to_string()).unwrap();
+        let time = |s| NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+        assert!(hours.contains(time("23:15")));
+        assert!(hours.contains(time("05:59")));
+        assert!(!hours.contains(time("12:00")));
+
+        assert!(Hours::try_from("9am-5pm".to_string()).is_err());
+        assert!(toml::from_str::<Guard>("not_on = [\"someday\"]").is_err());
+    }
+
+    #[test]
+    fn test_covers_tags_and_globs() {
+        let mut deploy = Target::new("ship".to_string(), None, PathBuf::from("Makefile"), 1);
+        deploy.tags = vec!["deploy".to_string()];
+        let release = Target::new(
+            "release-prod".to_string(),
+            None,
+            PathBuf::from("Makefile"),
+            1,
+        );
+        let build = Target::new("build".to_string(), None, PathBuf::from("Makefile"), 1);
+
+        let guard = guard("tags = [\"Deploy\"]\ntargets = [\"release*\"]\n");
+        assert!(guard.covers(&deploy));
+        assert!(guard.covers(&release));
+        assert!(!guard.covers(&build));
+
+        assert!(self::guard("hours = \"09:00-17:00\"").covers(&build));
+    }
+}
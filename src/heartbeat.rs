@@ -0,0 +1,211 @@
+use colored::Colorize;
+use std::io::{IsTerminal, Write};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How often the heartbeat checks whether the run has gone quiet
+const TICK: Duration = Duration::from_secs(1);
+
+/// Erases the current terminal line
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// A status line on stderr shown while a running target has printed nothing
+/// for a while, so a slow step can be told apart from a hung one
+///
+/// On a terminal the line is redrawn in place every second and erased as soon
+/// as the target prints again; elsewhere (CI logs) a line is printed for every
+/// quiet period. Nothing is shown while the output stops mid-line, which
+/// usually means the target is waiting at a prompt.
+pub struct Heartbeat {
+    state: Arc<Mutex<State>>,
+    stop: Sender<()>,
+    ticker: JoinHandle<()>,
+}
+
+/// A handle for reporting a run's output to its heartbeat
+#[derive(Clone)]
+pub struct Activity(Arc<Mutex<State>>);
+
+struct State {
+    started: Instant,
+    last_output: Instant,
+    /// Whether the last output ended with a newline
+    at_line_start: bool,
+    /// When a status line was last shown in the current quiet period
+    reported: Option<Instant>,
+    /// Whether a status line is on screen and has to be erased before output
+    shown: bool,
+}
+
+impl State {
+    fn new(now: Instant) -> Self {
+        Self {
+            started: now,
+            last_output: now,
+            at_line_start: true,
+            reported: None,
+            shown: false,
+        }
+    }
+
+    /// Whether to show the status line at `now`; `redraw` keeps refreshing it
+    /// instead of showing it once per quiet period
+    fn due(&self, now: Instant, quiet_after: Duration, redraw: bool) -> bool {
+        if !self.at_line_start || now - self.last_output < quiet_after {
+            return false;
+        }
+        match self.reported {
+            Some(at) if !redraw => now - at >= quiet_after,
+            _ => true,
+        }
+    }
+}
+
+impl Heartbeat {
+    /// Start watching a run of `target`, showing the status line once it has
+    /// been quiet for `quiet_after`
+    pub fn start(target: &str, quiet_after: Duration) -> Self {
+        let state = Arc::new(Mutex::new(State::new(Instant::now())));
+        let (stop, stopped) = mpsc::channel::<()>();
+        let terminal = std::io::stderr().is_terminal();
+        let target = target.to_string();
+
+        let ticker = {
+            let state = Arc::clone(&state);
+            std::thread::spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(TICK) {
+                    let Ok(mut state) = state.lock() else {
+                        break;
+                    };
+                    let now = Instant::now();
+                    if !state.due(now, quiet_after, terminal) {
+                        continue;
+                    }
+                    let line = status_line(&target, now - state.started, now - state.last_output);
+                    let mut err = std::io::stderr().lock();
+                    if terminal {
+                        let _ = write!(err, "{}{}", CLEAR_LINE, line.dimmed());
+                        state.shown = true;
+                    } else {
+                        let _ = writeln!(err, "{}", line);
+                    }
+                    let _ = err.flush();
+                    state.reported = Some(now);
+                }
+            })
+        };
+
+        Self {
+            state,
+            stop,
+            ticker,
+        }
+    }
+
+    /// A handle for the threads copying the run's output
+    pub fn activity(&self) -> Activity {
+        Activity(Arc::clone(&self.state))
+    }
+
+    /// Stop watching, erasing the status line if it is shown
+    pub fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.ticker.join();
+        if let Ok(state) = self.state.lock()
+            && state.shown
+        {
+            eprint!("{}", CLEAR_LINE);
+        }
+    }
+}
+
+impl Activity {
+    /// Record that the run printed `output`, which `write` shows; the status
+    /// line is erased first and can't be drawn in the middle of it
+    pub fn output<R>(&self, output: &[u8], write: impl FnOnce() -> R) -> R {
+        let Ok(mut state) = self.0.lock() else {
+            return write();
+        };
+        if state.shown {
+            let mut err = std::io::stderr().lock();
+            let _ = write!(err, "{}", CLEAR_LINE);
+            let _ = err.flush();
+            state.shown = false;
+        }
+        if let Some(&last) = output.last() {
+            state.at_line_start = last == b'\n';
+        }
+        state.last_output = Instant::now();
+        state.reported = None;
+        write()
+    }
+}
+
+/// The status line, e.g. `… build still running (3m 12s, no output for 45s)`
+fn status_line(target: &str, elapsed: Duration, quiet: Duration) -> String {
+    format!(
+        "… {} still running ({}, no output for {})",
+        target,
+        format_elapsed(elapsed),
+        format_elapsed(quiet)
+    )
+}
+
+/// A duration to the second, e.g. `45s`, `3m 12s`, `1h 05m`
+pub fn format_elapsed(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUIET: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(Duration::from_millis(45_900)), "45s");
+        assert_eq!(format_elapsed(Duration::from_secs(192)), "3m 12s");
+        assert_eq!(format_elapsed(Duration::from_secs(3900)), "1h 05m");
+    }
+
+    #[test]
+    fn test_status_line() {
+        assert_eq!(
+            status_line("build", Duration::from_secs(192), Duration::from_secs(45)),
+            "… build still running (3m 12s, no output for 45s)"
+        );
+    }
+
+    #[test]
+    fn test_due_after_a_quiet_period() {
+        let start = Instant::now();
+        let mut state = State::new(start);
+
+        assert!(!state.due(start + Duration::from_secs(29), QUIET, true));
+        assert!(state.due(start + QUIET, QUIET, true));
+
+        // Off a terminal it is shown once per quiet period
+        state.reported = Some(start + QUIET);
+        assert!(!state.due(start + Duration::from_secs(31), QUIET, false));
+        assert!(state.due(start + QUIET * 2, QUIET, false));
+        assert!(state.due(start + Duration::from_secs(31), QUIET, true));
+    }
+
+    #[test]
+    fn test_not_due_mid_line() {
+        let start = Instant::now();
+        let heartbeat = Activity(Arc::new(Mutex::new(State::new(start))));
+
+        heartbeat.output(b"Password: ", || ());
+        let state = heartbeat.0.lock().unwrap();
+        assert!(!state.due(Instant::now() + QUIET, QUIET, true));
+    }
+}
@@ -0,0 +1,176 @@
+use regex::Regex;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use crate::runlog::strip_ansi;
+use crate::target::{Provider, Target};
+
+/// The name of the rule Makefiles print their own help with
+pub const HELP_TARGET: &str = "help";
+
+/// How long `make help` may take before its descriptions are given up on
+const HELP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A line of help output naming a target and what it does: `build   Build
+/// it`, `build: Build it`, `make build - Build it`, or tab-separated
+static HELP_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*(?:make\s+)?([^\s:#=]+)\s*(?::+\s*|\s+-+\s+|\t\s*|\s{2,})(\S.*?)\s*$").unwrap()
+});
+
+/// The Makefile's own help target among `targets`, if it has one
+pub fn find(targets: &[Target]) -> Option<&Target> {
+    targets.iter().find(|t| is_help(t))
+}
+
+fn is_help(target: &Target) -> bool {
+    target.name == HELP_TARGET && target.provider == Provider::Make
+}
+
+/// The targets and descriptions in what a help target printed, in order
+pub fn parse(output: &str) -> Vec<(String, String)> {
+    strip_ansi(output)
+        .lines()
+        .filter_map(|line| {
+            let caps = HELP_LINE.captures(line)?;
+            Some((caps[1].to_string(), caps[2].to_string()))
+        })
+        .collect()
+}
+
+/// Fill in the descriptions of targets with no `##` comment from what the
+/// `help` target of their Makefile prints
+///
+/// Each help target covers the targets of its root whose files are in its
+/// directory or below, the nearest one first.
+pub fn import_descriptions(targets: &mut [Target]) {
+    let mut helps: Vec<Target> = targets.iter().filter(|t| is_help(t)).cloned().collect();
+    helps.sort_by_key(|t| std::cmp::Reverse(t.file.components().count()));
+
+    for help in helps {
+        let dir = help.file.parent().unwrap_or(Path::new(""));
+        let covers = |t: &Target| {
+            t.description.is_none()
+                && t.provider == Provider::Make
+                && t.root == help.root
+                && t.file.starts_with(dir)
+        };
+        if !targets.iter().any(covers) {
+            continue;
+        }
+        let Some(output) = run(&help.file) else {
+            continue;
+        };
+        let described = parse(&output);
+        for target in targets.iter_mut().filter(|t| covers(t)) {
+            target.description = described
+                .iter()
+                .find(|(name, _)| *name == target.name)
+                .map(|(_, description)| description.clone());
+        }
+    }
+}
+
+/// What `make help` prints for `makefile`, or None when it fails or takes
+/// longer than `HELP_TIMEOUT`
+fn run(makefile: &Path) -> Option<String> {
+    let dir = makefile
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut child = Command::new("make")
+        .arg("-s")
+        .arg("--no-print-directory")
+        .arg("-f")
+        .arg(makefile.file_name()?)
+        .arg(HELP_TARGET)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    // Read as it prints, so a long help can't fill the pipe and stall
+    let mut stdout = child.stdout.take()?;
+    let reader = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = stdout.read_to_end(&mut output);
+        output
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() < HELP_TIMEOUT => {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    };
+    let output = reader.join().ok()?;
+    status
+        .success()
+        .then(|| String::from_utf8_lossy(&output).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_parse_help_output() {
+        let output = "Targets:\n\n\
+                      \x1b[36mbuild          \x1b[0m Build the project\n\
+                      test: Run the tests\n  \
+                      make deploy - Ship it to production\n\
+                      lint\tLint the code\n\
+                      clean\n";
+
+        assert_eq!(
+            parse(output),
+            [
+                ("build".to_string(), "Build the project".to_string()),
+                ("test".to_string(), "Run the tests".to_string()),
+                ("deploy".to_string(), "Ship it to production".to_string()),
+                ("lint".to_string(), "Lint the code".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_descriptions_from_help() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("Makefile");
+        fs::write(
+            &file,
+            "help:\n\t@echo 'build    Build the project'\n\t@echo 'lint     Lint it'\n\
+             build:\n\ttrue\nlint: ## Run the linters\n\ttrue\ntest:\n\ttrue\n",
+        )
+        .unwrap();
+        let mut targets: Vec<Target> = ["help", "build", "lint", "test"]
+            .into_iter()
+            .map(|name| Target::new(name.to_string(), None, file.clone(), 1))
+            .collect();
+        targets[2].description = Some("Run the linters".to_string());
+
+        import_descriptions(&mut targets);
+
+        assert_eq!(
+            targets[1].description,
+            Some("Build the project".to_string())
+        );
+        // A `##` comment wins over the help
+        assert_eq!(targets[2].description, Some("Run the linters".to_string()));
+        assert_eq!(targets[3].description, None);
+    }
+}
@@ -0,0 +1,172 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::repeat::RepeatSummary;
+
+/// Oldest entries are dropped once the history grows past this
+const MAX_ENTRIES: usize = 1000;
+
+const HISTORY_FILENAME: &str = "history.jsonl";
+
+/// One run of a target, as recorded in the history file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub target: String,
+    /// The command that was run, e.g. `make deploy ENV=prod`
+    pub command: String,
+    /// Directory the run was started from
+    pub dir: PathBuf,
+    /// Seconds since the Unix epoch when the run started
+    pub started_at: u64,
+    pub duration_ms: u64,
+    /// Exit code of the (last) run, `None` if it was killed by a signal
+    pub exit_code: Option<i32>,
+    pub success: bool,
+    /// Iteration results for `--until-failure` / `--until-success` runs
+    #[serde(default)]
+    pub repeat: Option<RepeatSummary>,
+}
+
+/// Seconds since the Unix epoch
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Where run history is kept: `<data dir>/maki/history.jsonl`
+pub fn history_file_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|p| p.join("maki").join(HISTORY_FILENAME))
+}
+
+/// Record a run in the history file
+pub fn append(entry: &HistoryEntry) -> Result<()> {
+    let path = history_file_path().context("Could not determine data directory")?;
+    append_to(&path, entry)
+}
+
+/// The most recent `limit` runs, oldest first
+pub fn load(limit: usize) -> Result<Vec<HistoryEntry>> {
+    match history_file_path() {
+        Some(path) => load_from(&path, limit),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn append_to(path: &Path, entry: &HistoryEntry) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create history directory: {}", dir.display()))?;
+    }
+
+    let line = serde_json::to_string(entry).context("Failed to serialize history entry")?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open history file: {}", path.display()))?;
+    writeln!(file, "{}", line)?;
+    drop(file);
+
+    // Trim in bulk so most runs only append a line
+    let entries = load_from(path, usize::MAX)?;
+    if entries.len() > MAX_ENTRIES + MAX_ENTRIES / 10 {
+        write_all(path, &entries[entries.len() - MAX_ENTRIES..])?;
+    }
+
+    Ok(())
+}
+
+fn load_from(path: &Path, limit: usize) -> Result<Vec<HistoryEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read history file: {}", path.display()))?;
+
+    // Skip lines that don't parse (e.g. from an interrupted write)
+    let entries: Vec<HistoryEntry> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+
+    let skip = entries.len().saturating_sub(limit);
+    Ok(entries.into_iter().skip(skip).collect())
+}
+
+fn write_all(path: &Path, entries: &[HistoryEntry]) -> Result<()> {
+    let mut content = String::new();
+    for entry in entries {
+        content.push_str(&serde_json::to_string(entry)?);
+        content.push('\n');
+    }
+    fs::write(path, content)
+        .with_context(|| format!("Failed to write history file: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(target: &str, success: bool) -> HistoryEntry {
+        HistoryEntry {
+            target: target.to_string(),
+            command: format!("make {}", target),
+            dir: PathBuf::from("/project"),
+            started_at: 1_700_000_000,
+            duration_ms: 1200,
+            exit_code: Some(if success { 0 } else { 2 }),
+            success,
+            repeat: None,
+        }
+    }
+
+    #[test]
+    fn test_append_and_load_recent() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("maki").join(HISTORY_FILENAME);
+
+        append_to(&path, &entry("build", true)).unwrap();
+        append_to(&path, &entry("test", false)).unwrap();
+        append_to(&path, &entry("deploy", true)).unwrap();
+
+        let recent = load_from(&path, 2).unwrap();
+        let targets: Vec<&str> = recent.iter().map(|e| e.target.as_str()).collect();
+        assert_eq!(targets, vec!["test", "deploy"]);
+        assert_eq!(recent[0].exit_code, Some(2));
+    }
+
+    #[test]
+    fn test_load_skips_corrupt_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(HISTORY_FILENAME);
+        let good = serde_json::to_string(&entry("build", true)).unwrap();
+        fs::write(&path, format!("{}\n{{\"target\": \"tru\n", good)).unwrap();
+
+        let entries = load_from(&path, 10).unwrap();
+        assert_eq!(entries, vec![entry("build", true)]);
+    }
+
+    #[test]
+    fn test_history_is_trimmed() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(HISTORY_FILENAME);
+        let many: Vec<HistoryEntry> = (0..MAX_ENTRIES + MAX_ENTRIES / 10)
+            .map(|i| entry(&format!("t{}", i), true))
+            .collect();
+        write_all(&path, &many).unwrap();
+
+        append_to(&path, &entry("last", true)).unwrap();
+
+        let entries = load_from(&path, usize::MAX).unwrap();
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries.last().unwrap().target, "last");
+    }
+}
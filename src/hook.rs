@@ -0,0 +1,303 @@
+use clap::ValueEnum;
+
+use crate::target::Target;
+
+/// Bumped whenever the generated hook changes, so an outdated one can be spotted
+pub const HOOK_VERSION: u32 = 2;
+
+/// Shells `maki hook` can generate a hook for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Zsh,
+    Bash,
+    Fish,
+}
+
+impl Shell {
+    /// The shell `$SHELL` names, e.g. `/bin/zsh`
+    pub fn detect(shell: &str) -> Option<Self> {
+        let name = shell.rsplit('/').next().unwrap_or(shell);
+        Self::from_str(name, true).ok()
+    }
+
+    /// The line that loads the hook, for the shell's startup file
+    pub fn install_line(self) -> &'static str {
+        match self {
+            Self::Zsh => "eval \"$(maki hook zsh)\"",
+            Self::Bash => "eval \"$(maki hook bash)\"",
+            Self::Fish => "maki hook fish | source",
+        }
+    }
+
+    /// The shell's startup file, relative to the home directory
+    pub fn startup_file(self) -> &'static str {
+        match self {
+            Self::Zsh => ".zshrc",
+            Self::Bash => ".bashrc",
+            Self::Fish => ".config/fish/config.fish",
+        }
+    }
+}
+
+/// The hook script for `shell`: an `mk` function running `maki run`, with
+/// target completion cached until the next prompt and completion of the
+/// values of the target's variables after it
+pub fn script(shell: Shell) -> String {
+    let body = match shell {
+        Shell::Zsh => ZSH,
+        Shell::Bash => BASH,
+        Shell::Fish => FISH,
+    };
+
+    format!(
+        "# maki shell hook v{version}, generated by maki {maki}\n# Install: {install}  # in ~/{file}\n{body}",
+        version = HOOK_VERSION,
+        maki = env!("CARGO_PKG_VERSION"),
+        install = shell.install_line(),
+        file = shell.startup_file(),
+        body = body.replace("{version}", &HOOK_VERSION.to_string()),
+    )
+}
+
+const ZSH: &str = r#"typeset -g MAKI_HOOK_VERSION={version}
+typeset -g _maki_cache_dir=""
+typeset -ga _maki_cache_targets
+
+# Forget the cached targets at every prompt; they are loaded again on the next <TAB>
+_maki_hook() { _maki_cache_dir="" }
+autoload -Uz add-zsh-hook
+add-zsh-hook precmd _maki_hook
+
+_maki_refresh() {
+  if [[ "$_maki_cache_dir" != "$PWD" ]]; then
+    _maki_cache_dir="$PWD"
+    _maki_cache_targets=("${(@f)$(command maki __complete 2>/dev/null)}")
+  fi
+}
+
+mk() { command maki run "$@" }
+
+_mk() {
+  _maki_refresh
+  local line name
+  local -a targets values open
+  for line in "${_maki_cache_targets[@]}"; do
+    [[ -n "$line" ]] || continue
+    targets+=("${${line%%$'\t'*}//:/\\:}:${line#*$'\t'}")
+  done
+
+  # After the target, its variables: V=patch, or V= for a free-form value
+  if (( CURRENT > 2 )); then
+    for line in "${(@f)$(command maki __complete "${words[2]}" 2>/dev/null)}"; do
+      [[ -n "$line" ]] || continue
+      name="${${line%%$'\t'*}//:/\\:}"
+      [[ "$line" == *$'\t'* ]] && name+=":${line#*$'\t'}"
+      if [[ "${line%%$'\t'*}" == *= ]]; then
+        open+=("$name")
+      else
+        values+=("$name")
+      fi
+    done
+    _describe 'value' values
+    _describe 'variable' open -S ''
+  fi
+  _describe 'target' targets
+}
+(( $+functions[compdef] )) && compdef _mk mk
+"#;
+
+const BASH: &str = r#"MAKI_HOOK_VERSION={version}
+_maki_cache_dir=""
+_maki_cache_targets=""
+
+# Forget the cached targets at every prompt; they are loaded again on the next <TAB>
+_maki_hook() { _maki_cache_dir=""; }
+if [[ ";${PROMPT_COMMAND:-};" != *";_maki_hook;"* ]]; then
+  PROMPT_COMMAND="_maki_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi
+
+_maki_refresh() {
+  if [[ "$_maki_cache_dir" != "$PWD" ]]; then
+    _maki_cache_dir="$PWD"
+    _maki_cache_targets="$(command maki __complete 2>/dev/null | cut -f1)"
+  fi
+}
+
+mk() { command maki run "$@"; }
+
+_mk() {
+  _maki_refresh
+  if (( COMP_CWORD == 1 )); then
+    COMPREPLY=($(compgen -W "$_maki_cache_targets" -- "${COMP_WORDS[COMP_CWORD]}"))
+    return 0
+  fi
+
+  # After the target, its variables; bash splits words at `=`, so the word
+  # being completed is read from the line itself
+  local cur="${COMP_LINE:0:COMP_POINT}"
+  cur="${cur##*[[:space:]]}"
+  local vars="$(command maki __complete "${COMP_WORDS[1]}" 2>/dev/null | cut -f1)"
+  COMPREPLY=($(compgen -W "$vars $_maki_cache_targets" -- "$cur"))
+  if [[ "$cur" == *=* && "$COMP_WORDBREAKS" == *=* ]]; then
+    COMPREPLY=("${COMPREPLY[@]#"${cur%=*}="}")
+  fi
+  [[ ${#COMPREPLY[@]} -eq 1 && "${COMPREPLY[0]}" == *= ]] && compopt -o nospace
+  return 0
+}
+complete -F _mk mk
+"#;
+
+const FISH: &str = r#"set -g MAKI_HOOK_VERSION {version}
+set -g __maki_cache_dir ""
+set -g __maki_cache_targets
+
+# Forget the cached targets at every prompt; they are loaded again on the next <TAB>
+function __maki_hook --on-event fish_prompt
+    set -g __maki_cache_dir ""
+end
+
+function __maki_targets
+    if test "$__maki_cache_dir" != "$PWD"
+        set -g __maki_cache_dir $PWD
+        set -g __maki_cache_targets (command maki __complete 2>/dev/null)
+    end
+    printf '%s\n' $__maki_cache_targets
+end
+
+function mk --wraps 'maki run' --description 'Run a maki target'
+    command maki run $argv
+end
+
+function __maki_variables
+    set -l words (commandline -opc)
+    command maki __complete $words[2] 2>/dev/null
+end
+
+complete -c mk -f -n 'test (count (commandline -opc)) -eq 1' -a '(__maki_targets)'
+complete -c mk -f -n 'test (count (commandline -opc)) -ge 2' -a '(__maki_variables) (__maki_targets)'
+"#;
+
+/// Completion candidates for `maki __complete`: one target per line, with its
+/// description after a tab
+pub fn completion_lines<'a>(targets: impl IntoIterator<Item = &'a Target>) -> Vec<String> {
+    targets
+        .into_iter()
+        .map(|t| match t.description {
+            // Descriptions are shown on one line next to the name
+            Some(ref desc) => format!("{}\t{}", t.name, desc.replace(['\t', '\n'], " ")),
+            None => t.name.clone(),
+        })
+        .collect()
+}
+
+/// Completion candidates for `maki __complete TARGET`: `NAME=value` for each
+/// value a required variable's hint lists, otherwise `NAME=` with the hint
+/// after a tab
+pub fn variable_lines(target: &Target) -> Vec<String> {
+    let mut lines = Vec::new();
+    for var in &target.required_vars {
+        let values: Vec<&str> = var
+            .hint
+            .as_deref()
+            .map(|hint| hint.split('|').collect())
+            .unwrap_or_default();
+        if values.len() > 1 {
+            for value in values {
+                let line = format!("{}={}", var.name, value);
+                lines.push(match var.default.as_deref() {
+                    Some(default) if default == value => format!("{}\tdefault", line),
+                    _ => line,
+                });
+            }
+        } else {
+            lines.push(match var.hint {
+                Some(ref hint) => format!("{}=\t{}", var.name, hint),
+                None => format!("{}=", var.name),
+            });
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::target::RequiredVar;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_scripts_are_versioned() {
+        for shell in Shell::value_variants() {
+            let script = script(*shell);
+            assert!(
+                script.starts_with(&format!("# maki shell hook v{}", HOOK_VERSION)),
+                "{:?}",
+                shell
+            );
+            assert!(script.contains("maki __complete"), "{:?}", shell);
+            assert!(script.contains("maki run"), "{:?}", shell);
+            assert!(!script.contains("{version}"), "{:?}", shell);
+        }
+        let zsh = script(Shell::Zsh);
+        assert!(zsh.contains(&format!("typeset -g MAKI_HOOK_VERSION={}\n", HOOK_VERSION)));
+    }
+
+    #[test]
+    fn test_detect_shell() {
+        assert_eq!(Shell::detect("/bin/zsh"), Some(Shell::Zsh));
+        assert_eq!(Shell::detect("/usr/local/bin/fish"), Some(Shell::Fish));
+        assert_eq!(Shell::detect("bash"), Some(Shell::Bash));
+        assert_eq!(Shell::detect("/bin/tcsh"), None);
+        assert!(
+            script(Shell::Zsh).contains("# Install: eval \"$(maki hook zsh)\"  # in ~/.zshrc\n")
+        );
+    }
+
+    #[test]
+    fn test_completion_lines() {
+        let build = Target::new(
+            "build".to_string(),
+            Some("Build\tit".to_string()),
+            PathBuf::from("Makefile"),
+            1,
+        );
+        let clean = Target::new("clean".to_string(), None, PathBuf::from("Makefile"), 4);
+
+        assert_eq!(
+            completion_lines(&[build, clean]),
+            vec!["build\tBuild it", "clean"]
+        );
+    }
+
+    #[test]
+    fn test_variable_lines() {
+        let var = |name: &str, hint: Option<&str>, default: Option<&str>| RequiredVar {
+            name: name.to_string(),
+            hint: hint.map(str::to_string),
+            default: default.map(str::to_string),
+            secret: None,
+        };
+        let bump = Target::with_required_vars(
+            "bump".to_string(),
+            None,
+            PathBuf::from("Makefile"),
+            1,
+            vec![
+                var("V", Some("patch|minor|major"), Some("minor")),
+                var("TAG", Some("v1.2.3"), None),
+                var("MSG", None, None),
+            ],
+        );
+
+        assert_eq!(
+            variable_lines(&bump),
+            vec![
+                "V=patch",
+                "V=minor\tdefault",
+                "V=major",
+                "TAG=\tv1.2.3",
+                "MSG=",
+            ]
+        );
+    }
+}
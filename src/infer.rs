@@ -0,0 +1,368 @@
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::providers::Discovery;
+use crate::quote;
+use crate::target::{Provider, Target};
+
+/// A kind of project whose common tasks maki can infer from its manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ecosystem {
+    Rust,
+    Node,
+    Go,
+    Python,
+}
+
+impl Ecosystem {
+    /// Every ecosystem, in the order their manifests are looked for
+    pub const ALL: [Ecosystem; 4] = [Self::Rust, Self::Node, Self::Go, Self::Python];
+
+    /// The file that marks a project of this kind
+    pub fn manifest(&self) -> &'static str {
+        match self {
+            Self::Rust => "Cargo.toml",
+            Self::Node => "package.json",
+            Self::Go => "go.mod",
+            Self::Python => "pyproject.toml",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Rust => "Rust",
+            Self::Node => "Node.js",
+            Self::Go => "Go",
+            Self::Python => "Python",
+        }
+    }
+}
+
+/// A common task of a project, run with its ecosystem's own tools
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Task {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub command: Vec<String>,
+}
+
+impl Task {
+    fn new(name: &'static str, description: &'static str, command: &[impl AsRef<str>]) -> Self {
+        Self {
+            name,
+            description,
+            command: command.iter().map(|s| s.as_ref().to_string()).collect(),
+        }
+    }
+}
+
+/// The kind of project in `dir`, from the first manifest found there
+pub fn detect(dir: &Path) -> Option<Ecosystem> {
+    Ecosystem::ALL
+        .into_iter()
+        .find(|e| dir.join(e.manifest()).is_file())
+}
+
+/// The build, test, run, and lint tasks of the `ecosystem` project in `dir`,
+/// leaving out those it has nothing for (no run task for a library, no
+/// script for it in package.json)
+pub fn tasks(ecosystem: Ecosystem, dir: &Path) -> Vec<Task> {
+    match ecosystem {
+        Ecosystem::Rust => {
+            let mut tasks = vec![
+                Task::new("build", "Build the project", &["cargo", "build"]),
+                Task::new("test", "Run the tests", &["cargo", "test"]),
+            ];
+            let manifest = fs::read_to_string(dir.join("Cargo.toml")).unwrap_or_default();
+            if dir.join("src/main.rs").is_file() || manifest.contains("[[bin]]") {
+                tasks.push(Task::new("run", "Run the project", &["cargo", "run"]));
+            }
+            tasks.push(Task::new("lint", "Lint the code", &["cargo", "clippy"]));
+            tasks
+        }
+        Ecosystem::Node => {
+            let manager = node_package_manager(dir);
+            let scripts = node_scripts(dir);
+            let script = |name: &'static str, description: &'static str, scripts_: &[&str]| {
+                let found = scripts_.iter().find(|s| scripts.iter().any(|n| n == *s))?;
+                Some(Task::new(name, description, &[manager, "run", found]))
+            };
+            [
+                script("build", "Build the project", &["build"]),
+                script("test", "Run the tests", &["test"]),
+                script("run", "Run the project", &["start", "dev"]),
+                script("lint", "Lint the code", &["lint"]),
+            ]
+            .into_iter()
+            .flatten()
+            .collect()
+        }
+        Ecosystem::Go => {
+            let mut tasks = vec![
+                Task::new("build", "Build the project", &["go", "build", "./..."]),
+                Task::new("test", "Run the tests", &["go", "test", "./..."]),
+            ];
+            if dir.join("main.go").is_file() {
+                tasks.push(Task::new("run", "Run the project", &["go", "run", "."]));
+            }
+            tasks.push(Task::new("lint", "Lint the code", &["go", "vet", "./..."]));
+            tasks
+        }
+        Ecosystem::Python => {
+            let runner: &[&str] = if dir.join("uv.lock").is_file() {
+                &["uv", "run"]
+            } else if dir.join("poetry.lock").is_file() {
+                &["poetry", "run"]
+            } else {
+                &[]
+            };
+            let with_runner = |args: &[&str]| -> Vec<String> {
+                runner.iter().chain(args).map(|s| s.to_string()).collect()
+            };
+            let build: &[&str] = match runner.first() {
+                Some(tool) => &[tool, "build"],
+                None => &["python", "-m", "build"],
+            };
+            let mut tasks = vec![
+                Task::new("build", "Build the package", build),
+                Task::new("test", "Run the tests", &with_runner(&["pytest"])),
+            ];
+            if let [script] = python_scripts(dir).as_slice() {
+                tasks.push(Task::new(
+                    "run",
+                    "Run the project",
+                    &with_runner(&[script.as_str()]),
+                ));
+            }
+            tasks.push(Task::new(
+                "lint",
+                "Lint the code",
+                &with_runner(&["ruff", "check", "."]),
+            ));
+            tasks
+        }
+    }
+}
+
+/// The package manager a Node.js project uses, by its lockfile
+fn node_package_manager(dir: &Path) -> &'static str {
+    [
+        ("pnpm-lock.yaml", "pnpm"),
+        ("yarn.lock", "yarn"),
+        ("bun.lockb", "bun"),
+        ("bun.lock", "bun"),
+    ]
+    .into_iter()
+    .find(|(lockfile, _)| dir.join(lockfile).is_file())
+    .map_or("npm", |(_, manager)| manager)
+}
+
+/// The names of the scripts in package.json
+fn node_scripts(dir: &Path) -> Vec<String> {
+    let Ok(content) = fs::read_to_string(dir.join("package.json")) else {
+        return Vec::new();
+    };
+    let Ok(package) = serde_json::from_str::<serde_json::Value>(&content) else {
+        return Vec::new();
+    };
+    package
+        .get("scripts")
+        .and_then(|s| s.as_object())
+        .map(|scripts| scripts.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// The commands pyproject.toml's `[project.scripts]` installs
+fn python_scripts(dir: &Path) -> Vec<String> {
+    let Ok(content) = fs::read_to_string(dir.join("pyproject.toml")) else {
+        return Vec::new();
+    };
+    let Ok(pyproject) = content.parse::<toml::Table>() else {
+        return Vec::new();
+    };
+    pyproject
+        .get("project")
+        .and_then(|p| p.get("scripts"))
+        .and_then(|s| s.as_table())
+        .map(|scripts| scripts.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// The inferred targets of the project in `dir`, as the `inferred` provider
+/// finds them; None when there is no manifest to infer them from
+pub fn discover(dir: &Path) -> Option<Discovery> {
+    let started = Instant::now();
+    let ecosystem = detect(dir)?;
+    let file = dir.join(ecosystem.manifest());
+    let targets = tasks(ecosystem, dir)
+        .into_iter()
+        .map(|task| {
+            let mut target = Target::new(
+                task.name.to_string(),
+                Some(task.description.to_string()),
+                file.clone(),
+                1,
+            );
+            target.provider = Provider::Inferred;
+            target
+        })
+        .collect();
+
+    Some(Discovery {
+        provider: Provider::Inferred,
+        files: vec![file],
+        targets,
+        duration: started.elapsed(),
+        warnings: Vec::new(),
+    })
+}
+
+/// The command an inferred target runs in `dir`
+pub fn command(target: &str, dir: &Path) -> Option<Vec<String>> {
+    let ecosystem = detect(dir)?;
+    tasks(ecosystem, dir)
+        .into_iter()
+        .find(|task| task.name == target)
+        .map(|task| task.command)
+}
+
+/// A starter Makefile with `tasks` as documented, phony targets; `.PHONY`
+/// comes last so the header comment isn't taken for its description
+pub fn starter_makefile(ecosystem: Ecosystem, tasks: &[Task]) -> String {
+    let mut makefile = format!(
+        "# Common tasks of this {} project, inferred from {} by maki\n",
+        ecosystem.name(),
+        ecosystem.manifest()
+    );
+    for task in tasks {
+        makefile.push_str(&format!(
+            "\n{}: ## {}\n\t{}\n",
+            task.name,
+            task.description,
+            quote::command(&task.command).replace('$', "$$")
+        ));
+    }
+    let names: Vec<&str> = tasks.iter().map(|t| t.name).collect();
+    makefile.push_str(&format!("\n.PHONY: {}\n", names.join(" ")));
+    makefile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in files {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn commands(dir: &Path) -> Vec<(&'static str, String)> {
+        tasks(detect(dir).unwrap(), dir)
+            .into_iter()
+            .map(|t| (t.name, quote::command(&t.command)))
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_tasks() {
+        let dir = project(&[
+            ("Cargo.toml", "[package]\nname = \"app\"\n"),
+            ("src/main.rs", ""),
+        ]);
+        assert_eq!(
+            commands(dir.path()),
+            [
+                ("build", "cargo build".to_string()),
+                ("test", "cargo test".to_string()),
+                ("run", "cargo run".to_string()),
+                ("lint", "cargo clippy".to_string()),
+            ]
+        );
+
+        // A library has nothing to run
+        let dir = project(&[("Cargo.toml", "[package]\nname = \"lib\"\n")]);
+        assert!(!commands(dir.path()).iter().any(|(name, _)| *name == "run"));
+    }
+
+    #[test]
+    fn test_node_tasks_follow_the_scripts_and_lockfile() {
+        let dir = project(&[
+            (
+                "package.json",
+                r#"{"scripts": {"build": "tsc", "dev": "vite", "lint": "eslint ."}}"#,
+            ),
+            ("pnpm-lock.yaml", ""),
+        ]);
+        assert_eq!(
+            commands(dir.path()),
+            [
+                ("build", "pnpm run build".to_string()),
+                ("run", "pnpm run dev".to_string()),
+                ("lint", "pnpm run lint".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_python_tasks_use_the_projects_runner() {
+        let dir = project(&[
+            (
+                "pyproject.toml",
+                "[project]\nname = \"app\"\n\n[project.scripts]\napp = \"app:main\"\n",
+            ),
+            ("uv.lock", ""),
+        ]);
+        assert_eq!(
+            commands(dir.path()),
+            [
+                ("build", "uv build".to_string()),
+                ("test", "uv run pytest".to_string()),
+                ("run", "uv run app".to_string()),
+                ("lint", "uv run ruff check .".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_discover_and_command() {
+        let dir = project(&[("go.mod", "module example.com/app\n"), ("main.go", "")]);
+        let discovery = discover(dir.path()).unwrap();
+        assert_eq!(discovery.files, [dir.path().join("go.mod")]);
+        assert!(
+            discovery
+                .targets
+                .iter()
+                .all(|t| t.provider == Provider::Inferred)
+        );
+        assert_eq!(
+            command("test", dir.path()),
+            Some(vec!["go".into(), "test".into(), "./...".into()])
+        );
+        assert_eq!(command("deploy", dir.path()), None);
+
+        assert!(discover(project(&[]).path()).is_none());
+    }
+
+    #[test]
+    fn test_starter_makefile() {
+        let tasks = [
+            Task::new("build", "Build the project", &["go", "build", "./..."]),
+            Task::new("lint", "Lint the code", &["go", "vet", "./..."]),
+        ];
+        assert_eq!(
+            starter_makefile(Ecosystem::Go, &tasks),
+            "# Common tasks of this Go project, inferred from go.mod by maki\n\n\
+             build: ## Build the project\n\tgo build ./...\n\n\
+             lint: ## Lint the code\n\tgo vet ./...\n\n\
+             .PHONY: build lint\n"
+        );
+    }
+}
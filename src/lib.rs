@@ -0,0 +1,80 @@
+//! maki: a cross-platform fuzzy Makefile task finder.
+//!
+//! The `maki` binary is built on these modules; parsing, caching, and
+//! execution are usable on their own and report failures as [`MakiError`].
+
+pub mod alias;
+pub mod artifact;
+pub mod batch;
+pub mod bundle;
+pub mod cache;
+pub mod capture;
+pub mod ci;
+pub mod cli;
+pub mod clipboard;
+pub mod config;
+pub mod dashboard;
+pub mod diagnostic;
+pub mod diff;
+pub mod editor;
+pub mod env;
+pub mod error;
+pub mod executor;
+pub mod fallback;
+pub mod fuzzy;
+pub mod generated;
+pub mod glob;
+pub mod graph;
+pub mod guard;
+pub mod heartbeat;
+pub mod help;
+pub mod history;
+pub mod hook;
+pub mod infer;
+pub mod lookup;
+pub mod makefile;
+pub mod manual;
+pub mod multirun;
+pub mod noop;
+pub mod notes;
+pub mod numbering;
+pub mod overview;
+pub mod plain;
+pub mod problems;
+pub mod prompt;
+pub mod providers;
+#[cfg(unix)]
+pub mod pty;
+pub mod query;
+pub mod queue;
+pub mod quote;
+pub mod rank;
+pub mod ready;
+pub mod recipe;
+pub mod record;
+pub mod refactor;
+pub mod remote;
+pub mod repeat;
+pub mod runlog;
+pub mod scan;
+pub mod schedule;
+pub mod schema;
+pub mod scratch;
+pub mod secret;
+pub mod session;
+pub mod setup;
+pub mod show;
+pub mod split;
+pub mod store;
+pub mod suggest;
+pub mod target;
+pub mod terminal;
+pub mod timings;
+pub mod tree;
+pub mod usage;
+pub mod validate;
+pub mod version;
+pub mod webhook;
+pub mod width;
+
+pub use error::MakiError;
@@ -0,0 +1,247 @@
+use anyhow::{Result, bail};
+use std::path::{Component, Path, PathBuf};
+
+use crate::target::Target;
+
+/// A target as `maki run` names it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetName<'a> {
+    /// `deploy`, or a prefix only one target's name starts with, like `dep`
+    Plain(&'a str),
+    /// `api:test`: the target of the project root or directory called `api`
+    Scoped { scope: &'a str, name: &'a str },
+    /// `services/api/Makefile::test`: the target of that file
+    InFile { file: &'a str, name: &'a str },
+}
+
+impl<'a> TargetName<'a> {
+    /// Make target names can't hold an unescaped `:`, so one always
+    /// qualifies the name
+    pub fn parse(text: &'a str) -> Self {
+        if let Some((file, name)) = text.split_once("::")
+            && !file.is_empty()
+            && !name.is_empty()
+        {
+            return Self::InFile { file, name };
+        }
+        if let Some((scope, name)) = text.split_once(':')
+            && !scope.is_empty()
+            && !name.is_empty()
+        {
+            return Self::Scoped { scope, name };
+        }
+        Self::Plain(text)
+    }
+
+    /// The target name without its qualifier
+    pub fn name(&self) -> &'a str {
+        match *self {
+            Self::Plain(name) | Self::Scoped { name, .. } | Self::InFile { name, .. } => name,
+        }
+    }
+}
+
+/// A path as given, without `./` parts
+fn normalized(path: &str) -> PathBuf {
+    Path::new(path)
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect()
+}
+
+/// A target's Makefile relative to its project root, or else to `base`
+fn relative_file<'a>(target: &'a Target, base: &Path) -> &'a Path {
+    let root = target.root.as_deref().unwrap_or(base);
+    target.file.strip_prefix(root).unwrap_or(&target.file)
+}
+
+/// Whether `target` is in the root, directory, or file `name` is qualified
+/// with
+fn in_scope(name: TargetName, target: &Target, base: &Path) -> bool {
+    match name {
+        TargetName::Plain(_) => true,
+        TargetName::Scoped { scope, .. } => {
+            let dir = relative_file(target, base)
+                .parent()
+                .unwrap_or(Path::new(""));
+            target.root_label().as_deref() == Some(scope)
+                || (!dir.as_os_str().is_empty() && dir.ends_with(normalized(scope)))
+        }
+        TargetName::InFile { file, .. } => {
+            let file = normalized(file);
+            relative_file(target, base) == file || target.file == file
+        }
+    }
+}
+
+/// The targets `name` could mean: those it names exactly, or else those
+/// whose names it begins
+pub fn candidates<'a>(name: TargetName, targets: &'a [Target], base: &Path) -> Vec<&'a Target> {
+    let in_scope: Vec<&Target> = targets.iter().filter(|t| in_scope(name, t, base)).collect();
+    let exact: Vec<&Target> = in_scope
+        .iter()
+        .copied()
+        .filter(|t| t.name == name.name())
+        .collect();
+    if !exact.is_empty() {
+        return exact;
+    }
+    in_scope
+        .into_iter()
+        .filter(|t| !t.is_pattern() && t.name.starts_with(name.name()))
+        .collect()
+}
+
+/// How to name `target` so that only it matches: `api:test` for one of
+/// several roots, `services/api:test` for a nested Makefile's
+pub fn qualified_name(target: &Target, base: &Path) -> String {
+    if let Some(label) = target.root_label() {
+        return format!("{}:{}", label, target.name);
+    }
+    match relative_file(target, base).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => {
+            format!("{}:{}", dir.display(), target.name)
+        }
+        _ => target.name.clone(),
+    }
+}
+
+/// The one target `text` names, or None when no target matches; a plain
+/// name may still be a file make knows how to build
+pub fn resolve<'a>(text: &str, targets: &'a [Target], base: &Path) -> Result<Option<&'a Target>> {
+    let name = TargetName::parse(text);
+    match candidates(name, targets, base).as_slice() {
+        [] => Ok(None),
+        [target] => Ok(Some(target)),
+        several => {
+            let same_name = several.iter().all(|t| t.name == several[0].name);
+            let names: Vec<String> = several
+                .iter()
+                .map(|t| match same_name {
+                    true => qualified_name(t, base),
+                    false => t.name.clone(),
+                })
+                .collect();
+            if same_name {
+                bail!(
+                    "Target {} is defined in several places; name one of them: {}",
+                    name.name(),
+                    names.join(", ")
+                );
+            }
+            bail!(
+                "{} could be any of {} (type more of the name)",
+                text,
+                names.join(", ")
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(name: &str, file: &str, root: Option<&str>) -> Target {
+        let mut target = Target::new(name.to_string(), None, PathBuf::from(file), 1);
+        target.root = root.map(PathBuf::from);
+        target
+    }
+
+    fn resolved(text: &str, targets: &[Target]) -> Result<Option<String>> {
+        let found = resolve(text, targets, Path::new("/app"))?;
+        Ok(found.map(|t| format!("{}", t.file.display())))
+    }
+
+    #[test]
+    fn test_parse_names() {
+        assert_eq!(TargetName::parse("deploy"), TargetName::Plain("deploy"));
+        assert_eq!(
+            TargetName::parse("api:test"),
+            TargetName::Scoped {
+                scope: "api",
+                name: "test"
+            }
+        );
+        assert_eq!(
+            TargetName::parse("services/api/Makefile::test"),
+            TargetName::InFile {
+                file: "services/api/Makefile",
+                name: "test"
+            }
+        );
+        assert_eq!(TargetName::parse(":test"), TargetName::Plain(":test"));
+        assert_eq!(TargetName::parse("api:test").name(), "test");
+    }
+
+    #[test]
+    fn test_resolve_prefixes() {
+        let targets = vec![
+            target("deploy", "/app/Makefile", None),
+            target("docs", "/app/Makefile", None),
+            target("docs-serve", "/app/Makefile", None),
+            target("%.o", "/app/Makefile", None),
+        ];
+
+        assert_eq!(
+            resolve("dep", &targets, Path::new("/app"))
+                .unwrap()
+                .map(|t| t.name.as_str()),
+            Some("deploy")
+        );
+        // An exact name wins over the longer ones it begins
+        assert_eq!(
+            resolve("docs", &targets, Path::new("/app"))
+                .unwrap()
+                .map(|t| t.name.as_str()),
+            Some("docs")
+        );
+        let err = resolved("d", &targets).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "d could be any of deploy, docs, docs-serve (type more of the name)"
+        );
+        assert_eq!(resolved("dist/app.tar.gz", &targets).unwrap(), None);
+        assert_eq!(resolved("%", &targets).unwrap(), None);
+    }
+
+    #[test]
+    fn test_resolve_qualified_names() {
+        let targets = vec![
+            target("test", "/src/api/Makefile", Some("/src/api")),
+            target("test", "/src/web/Makefile", Some("/src/web")),
+            target("lint", "/app/services/api/Makefile", None),
+            target("lint", "/app/services/web/tasks.mk", None),
+        ];
+
+        assert_eq!(
+            resolved("api:test", &targets).unwrap(),
+            Some("/src/api/Makefile".to_string())
+        );
+        assert_eq!(
+            resolved("web:te", &targets).unwrap(),
+            Some("/src/web/Makefile".to_string())
+        );
+        assert_eq!(
+            resolved("services/api:lint", &targets).unwrap(),
+            Some("/app/services/api/Makefile".to_string())
+        );
+        assert_eq!(
+            resolved("./services/web/tasks.mk::lint", &targets).unwrap(),
+            Some("/app/services/web/tasks.mk".to_string())
+        );
+        assert_eq!(resolved("docs:test", &targets).unwrap(), None);
+
+        let err = resolved("test", &targets).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "Target test is defined in several places; name one of them: api:test, web:test"
+        );
+        let err = resolved("lint", &targets).unwrap_err().to_string();
+        assert!(
+            err.contains("services/api:lint, services/web:lint"),
+            "{}",
+            err
+        );
+    }
+}
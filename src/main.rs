@@ -1,29 +1,142 @@
-mod cache;
-mod cli;
-mod executor;
-mod fuzzy;
-mod makefile;
-mod prompt;
-mod target;
-
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 use colored::Colorize;
+use std::fs;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::Instant;
+
+use maki_cli::{
+    MakiError, alias, batch, bundle, cache, capture, ci, cli, clipboard, config, diagnostic, diff,
+    editor, env, executor, fallback, fuzzy, guard, heartbeat, help, history, hook, infer, lookup,
+    makefile, manual, multirun, notes, numbering, overview, plain, problems, prompt, providers,
+    query, queue, rank, ready, recipe, record, refactor, remote, repeat, scan, schedule, schema,
+    scratch, secret, session, setup, show, split, store, suggest, target, timings, tree, usage,
+    validate, version, webhook, width,
+};
 
-use cache::Cache;
-use cli::{Cli, Commands};
-use executor::ExecuteOptions;
+use cache::{Cache, CachePolicy};
+use cli::{
+    CacheCommand, Cli, Commands, ConfigCommand, ExportCommand, PickAction, PickerKind,
+    QueueCommand, RefactorCommand, RepeatArgs, UsageCommand,
+};
+use config::Config;
+use diagnostic::Diagnostic;
+use env::EnvVar;
+use executor::{ExecuteOptions, MakeMode};
+use fuzzy::PickerOptions;
 use makefile::ParseOptions;
+use multirun::OutputMode;
+use rank::{Frecency, RankMode};
+use store::Store;
+use version::BuildInfo;
 
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("{} {}", "error:".red().bold(), e);
-        std::process::exit(1);
+    let cli = Cli::parse();
+
+    let no_color = std::env::var("NO_COLOR").ok();
+    let ci = std::env::var("CI").ok();
+    if let Some(enabled) = cli.color.resolve(no_color.as_deref(), ci.as_deref()) {
+        colored::control::set_override(enabled);
+    }
+
+    // --json --verbose has the metrics too, for bug reports
+    if cli.timings || (cli.json && cli.verbose) {
+        timings::want_report();
+    }
+    let result = run(&cli);
+    // Commands that ran no target and printed no JSON report them last
+    if cli.timings {
+        report_timings();
+    }
+    if let Err(e) = result {
+        std::process::exit(report_error(&e, cli.json));
     }
 }
 
-fn run() -> Result<()> {
-    let cli = Cli::parse();
+/// Print the startup timings and cache counts on stderr, unless they were
+/// already reported or weren't asked for
+fn report_timings() {
+    if let Some(timings) = timings::take_report() {
+        for line in timings.render() {
+            eprintln!("{}", line.dimmed());
+        }
+    }
+}
+
+/// Print a command's `--json` output, with the startup metrics in it when
+/// --timings (or --verbose) asked for them
+fn print_json<T: serde::Serialize + ?Sized>(value: &T) -> Result<()> {
+    let json = match timings::take_report() {
+        Some(timings) => {
+            serde_json::to_string_pretty(&timings.with_metrics(serde_json::to_value(value)?))?
+        }
+        None => serde_json::to_string_pretty(value)?,
+    };
+    println!("{}", json);
+    Ok(())
+}
+
+/// Print an error for humans, or as a JSON object on stderr with --json,
+/// and return the exit code to use
+fn report_error(err: &anyhow::Error, json_output: bool) -> i32 {
+    let maki_error = err.downcast_ref::<MakiError>();
+
+    // make has already explained why it failed, so just pass its code on
+    let make_status = match maki_error {
+        Some(MakiError::Exec {
+            status: Some(code), ..
+        }) => Some(*code),
+        _ => None,
+    };
+
+    if json_output {
+        let error = match maki_error {
+            Some(e) => serde_json::to_value(e).unwrap_or_default(),
+            None => serde_json::json!({ "kind": "other", "message": err.to_string() }),
+        };
+        eprintln!("{}", serde_json::json!({ "error": error }));
+    } else if make_status.is_none() {
+        eprint!("{}", Diagnostic::new(err).render());
+    }
+
+    make_status.unwrap_or(1)
+}
+
+fn run(cli: &Cli) -> Result<()> {
+    // The manual is generated from the CLI definition, like --help
+    if cli.help_all {
+        print!("{}", manual::help_all(Cli::command()));
+        return Ok(());
+    }
+    if let Some(Commands::Man) = cli.command {
+        std::io::stdout().write_all(&manual::man_page(Cli::command())?)?;
+        return Ok(());
+    }
+    if cli.version || matches!(cli.command, Some(Commands::Version)) {
+        return handle_version(cli);
+    }
+
+    // A remote repository has nothing to do with the working directory
+    if let Some(Commands::List {
+        tree,
+        repo: Some(ref url),
+        ref git_ref,
+        ref tag,
+        phony_only,
+        ..
+    }) = cli.command
+    {
+        return handle_list_repo(
+            url,
+            git_ref.as_deref(),
+            tree,
+            tag.as_deref(),
+            phony_only,
+            cli,
+        );
+    }
 
     // Set up working directory
     let working_dir = cli.working_dir();
@@ -34,14 +147,205 @@ fn run() -> Result<()> {
         );
     }
 
+    let started = Instant::now();
+    let config = Config::load(&working_dir)?;
+    timings::record(timings::Phase::Config, started);
+
+    // Cache maintenance, history, and schemas don't need a Makefile
+    match cli.command {
+        Some(Commands::Cache { ref action }) => return handle_cache(action, &config, cli.json),
+        Some(Commands::Config {
+            origin,
+            action: None,
+        }) => return handle_settings(&working_dir, cli, origin),
+        Some(Commands::Config {
+            action: Some(ref action),
+            ..
+        }) => return handle_config(action, &working_dir, cli),
+        Some(Commands::History { limit }) => return handle_history(limit, cli.json),
+        Some(Commands::Errors { number, list }) => return handle_errors(number, list, cli.json),
+        Some(Commands::Replay { ref run, speed }) => {
+            return handle_replay(run.as_deref(), speed, cli);
+        }
+        Some(Commands::Export {
+            action: ExportCommand::Makefile { write },
+        }) => return handle_export_makefile(write, &working_dir, cli, &config),
+        Some(Commands::Schema { kind }) => {
+            print!("{}", schema::schema(kind));
+            return Ok(());
+        }
+        Some(Commands::Hook { shell }) => {
+            print!("{}", hook::script(shell));
+            return Ok(());
+        }
+        Some(Commands::Setup) => {
+            if setup::run(&working_dir, &makefile_names(cli, &config))? {
+                println!();
+                // Load the settings just written, as any other run would
+                return run(&Cli {
+                    command: None,
+                    ..cli.clone()
+                });
+            }
+            return Ok(());
+        }
+        Some(Commands::Ps) => return handle_ps(cli.json),
+        Some(Commands::Attach {
+            ref id,
+            lines,
+            no_follow,
+        }) => return handle_attach(id, lines, !no_follow),
+        Some(Commands::Stop { ref id, all }) => return handle_stop(id.as_deref(), all),
+        Some(Commands::Queue {
+            action: QueueCommand::List,
+        }) => return handle_queue_list(&working_dir, cli.json),
+        Some(Commands::Queue {
+            action: QueueCommand::Remove { ref target },
+        }) => return handle_queue_remove(target, &working_dir),
+        Some(Commands::Queue {
+            action: QueueCommand::Clear,
+        }) => return handle_queue_clear(&working_dir),
+        // Anything clap doesn't know is an alias, a number from `maki list
+        // --numbered`, or a typo
+        Some(Commands::Alias(ref args))
+            if !config.aliases.contains_key(&args[0])
+                && numbering::bare_index(&args[0]).is_none() =>
+        {
+            let aliases: Vec<&str> = config.aliases.keys().map(String::as_str).collect();
+            anyhow::bail!(
+                "Unknown command or alias: {} (see maki --help{})",
+                args[0],
+                match aliases.len() {
+                    0 => String::new(),
+                    _ => format!("; aliases: {}", aliases.join(", ")),
+                }
+            );
+        }
+        _ => {}
+    }
+
+    // `maki 3 V=patch` is `maki run @3 V=patch`, unless an alias is called 3
+    let numbered;
+    let cli = match cli.command {
+        Some(Commands::Alias(ref args))
+            if !config.aliases.contains_key(&args[0])
+                && numbering::bare_index(&args[0]).is_some() =>
+        {
+            // The words clap left to the alias end the command line, so its
+            // options before and after the number are read again as given
+            let mut words: Vec<String> = std::env::args().collect();
+            let at = words.len().saturating_sub(args.len());
+            words.splice(at.., ["run".to_string(), format!("@{}", args[0])]);
+            words.extend(args[1..].iter().cloned());
+            numbered = Cli::try_parse_from(words).unwrap_or_else(|e| e.exit());
+            &numbered
+        }
+        _ => cli,
+    };
+
+    // `recursive = true` and `auto_env = true` in the config are the same as
+    // passing -r and --auto-env
+    let with_config;
+    let cli = if (config.recursive && !cli.recursive) || (config.auto_env && !cli.auto_env) {
+        with_config = Cli {
+            recursive: cli.recursive || config.recursive,
+            auto_env: cli.auto_env || config.auto_env,
+            ..cli.clone()
+        };
+        &with_config
+    } else {
+        cli
+    };
+
+    let mut run_env = run_env(cli, &config)?;
+    // Dropped, and so removed, once every run started here is done
+    let scratch = scratch_dir(cli)?;
+    if let Some(ref scratch) = scratch {
+        run_env = scratch::with_scratch(run_env, scratch);
+    }
+
+    let roots = project_roots(cli, &config, &working_dir);
+    for root in &roots {
+        if !root.exists() {
+            anyhow::bail!("Project root does not exist: {}", root.display());
+        }
+        if cli.recursive && cli.file.is_none() && !cli.force {
+            confirm_broad_scan(root, cli)?;
+        }
+    }
+
+    // Opening a Makefile only needs to find it
+    if let Some(Commands::Open { reveal }) = cli.command {
+        return handle_open(reveal, &roots, cli, &config);
+    }
+    if let Some(Commands::Providers) = cli.command {
+        return handle_providers(&roots, cli, &config);
+    }
+    if let Some(Commands::Diff { ref git_ref }) = cli.command {
+        return handle_diff(git_ref, &working_dir, cli, &config);
+    }
+    if let Some(Commands::Validate { fix, yes }) = cli.command {
+        return handle_validate(fix, yes, &roots, cli, &config);
+    }
+
     // Parse options
     let parse_options = ParseOptions {
         include_private: cli.all,
         include_patterns: cli.patterns,
+        include_generated: cli.generated,
     };
 
-    // Get targets (with caching unless --no-cache is specified)
-    let targets = get_targets(&cli, &working_dir, &parse_options)?;
+    // Get every target (with caching unless --no-cache is specified). Private
+    // targets and pattern rules are filtered afterwards so cached results are
+    // complete and the picker can toggle them live.
+    let mut all_targets = if roots == [working_dir.clone()] {
+        get_targets(cli, &working_dir, &ParseOptions::everything(), &config)?
+    } else {
+        get_targets_in_roots(cli, &roots, &ParseOptions::everything(), &config)?
+    };
+    if config.help_descriptions {
+        help::import_descriptions(&mut all_targets);
+    }
+    config.apply_var_defaults(&mut all_targets);
+    config.apply_artifacts(&mut all_targets);
+    config.apply_webhooks(&mut all_targets);
+    config.apply_guards(&mut all_targets);
+    // Notes are an extra, so an unreadable notes file shouldn't stop maki
+    notes::Notes::load()
+        .unwrap_or_default()
+        .apply(&mut all_targets, &working_dir);
+    cli.sort.apply(&mut all_targets);
+    let targets: Vec<target::Target> = all_targets
+        .iter()
+        .filter(|t| parse_options.allows(t))
+        .cloned()
+        .collect();
+
+    // Completion output is read by the shell, which only wants target names
+    if let Some(Commands::Complete { ref target }) = cli.command {
+        let lines = match target {
+            Some(name) => targets
+                .iter()
+                .find(|t| t.name == *name)
+                .map(hook::variable_lines)
+                .unwrap_or_default(),
+            None => hook::completion_lines(&targets),
+        };
+        for line in lines {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+
+    // A query is read by scripts, which want `[]` rather than a message
+    if let Some(Commands::Query { ref filter }) = cli.command {
+        return handle_query(filter, &targets, &working_dir);
+    }
+
+    // The help target is often the one hidden, so it may be all there is
+    if let Some(Commands::HelpTarget) = cli.command {
+        return handle_help_target(&all_targets, cli, &run_env);
+    }
 
     if targets.is_empty() {
         println!("{}", "No targets found.".yellow());
@@ -50,21 +354,163 @@ fn run() -> Result<()> {
 
     // Handle commands
     match cli.command {
-        Some(Commands::List) => {
-            handle_list(&targets, cli.json)?;
+        Some(Commands::List {
+            tree,
+            ref tag,
+            phony_only,
+            numbered,
+            ..
+        }) => {
+            let mut targets = tagged(&targets, tag.as_deref())?;
+            targets.retain(|t| t.is_phony || !phony_only);
+            if numbered {
+                let mut numbering = numbering::Numbering::load()?;
+                let names = targets.iter().map(|t| t.name.clone()).collect();
+                numbering.remember(&working_dir, names);
+                numbering.save()?;
+            }
+            handle_list(&targets, cli.json, cli.with_deps, tree, numbered)?;
         }
         Some(Commands::Pick) => {
-            handle_pick(&targets, &cli)?;
+            handle_pick(&targets, &all_targets, cli, &config, &run_env)?;
+        }
+        Some(Commands::Run {
+            ref target,
+            parallel,
+            output,
+            ref repeat,
+            max_output,
+            question,
+            touch,
+            detach,
+            wait_ready,
+            ready_timeout,
+            ..
+        }) => {
+            cli.check_run().map_err(anyhow::Error::msg)?;
+            // NAME=VALUE words are variables for make, not more targets
+            let given: Vec<&str> = std::iter::once(target.as_str())
+                .chain(cli.run_targets())
+                .collect();
+            let (names, targets) = resolve_run_targets(&given, &targets, &roots, cli, &config)?;
+            let target = &names[0];
+            let more = &names[1..];
+
+            if detach || wait_ready {
+                let wait = wait_ready.then(|| std::time::Duration::from_secs(ready_timeout));
+                handle_run_detached(target, &targets, cli, &run_env, wait)?;
+            } else if more.is_empty() {
+                let mode = if question {
+                    MakeMode::Question
+                } else if touch {
+                    MakeMode::Touch
+                } else {
+                    MakeMode::Run
+                };
+                handle_run(target, &targets, cli, &run_env, repeat, max_output, mode)?;
+            } else {
+                let names: Vec<&str> = names.iter().map(String::as_str).collect();
+                handle_run_many(&names, &targets, cli, &run_env, parallel, output)?;
+            }
+        }
+        Some(Commands::Show { ref target }) => {
+            handle_show(target, &all_targets, cli.json)?;
+        }
+        Some(Commands::Note {
+            ref target,
+            ref note,
+            remove,
+        }) => {
+            handle_note(target, note.as_deref(), remove, &all_targets, &working_dir)?;
+        }
+        Some(Commands::Queue {
+            action: QueueCommand::Add { ref words },
+        }) => {
+            handle_queue_add(words, &all_targets, &working_dir)?;
+        }
+        Some(Commands::Queue {
+            action: QueueCommand::Run,
+        }) => {
+            // Queued targets may be private, so look them up among all of them
+            handle_queue_run(&all_targets, cli, &run_env, &working_dir)?;
+        }
+        Some(Commands::Batch {
+            ref path,
+            keep_going,
+        }) => {
+            // Like queued ones, batch steps may name private targets
+            handle_batch(path, keep_going, &all_targets, cli, &run_env)?;
         }
-        Some(Commands::Run { ref target }) => {
-            handle_run(target, &targets, &cli)?;
+        Some(Commands::Export {
+            action:
+                ExportCommand::Ci {
+                    format,
+                    targets: ref names,
+                },
+        }) => {
+            handle_export_ci(names, format, &targets, &working_dir)?;
+        }
+        Some(Commands::Usage {
+            action: UsageCommand::Export,
+        }) => {
+            handle_usage_export(&targets, &roots, &working_dir)?;
+        }
+        Some(Commands::Usage {
+            action: UsageCommand::Show,
+        }) => {
+            handle_usage_show(&roots, cli.json)?;
+        }
+        Some(Commands::Suggest) => {
+            handle_suggest(&targets, cli, &config, &run_env)?;
+        }
+        Some(Commands::Alias(ref args)) => {
+            handle_alias(args, &targets, cli, &config, &run_env)?;
+        }
+        Some(Commands::Overview) => {
+            handle_overview(&targets, &working_dir, cli.json)?;
+        }
+        Some(Commands::Refactor {
+            action:
+                RefactorCommand::Rename {
+                    ref old,
+                    ref new,
+                    yes,
+                },
+        }) => {
+            handle_rename(old, new, yes, &roots, cli, &config)?;
+        }
+        Some(Commands::Cache { .. })
+        | Some(Commands::Config { .. })
+        | Some(Commands::History { .. })
+        | Some(Commands::Errors { .. })
+        | Some(Commands::Replay { .. })
+        | Some(Commands::Export {
+            action: ExportCommand::Makefile { .. },
+        })
+        | Some(Commands::Schema { .. })
+        | Some(Commands::Setup)
+        | Some(Commands::Hook { .. })
+        | Some(Commands::Ps)
+        | Some(Commands::Attach { .. })
+        | Some(Commands::Stop { .. })
+        | Some(Commands::Queue { .. })
+        | Some(Commands::Complete { .. })
+        | Some(Commands::Query { .. })
+        | Some(Commands::HelpTarget)
+        | Some(Commands::Open { .. })
+        | Some(Commands::Providers)
+        | Some(Commands::Diff { .. })
+        | Some(Commands::Validate { .. })
+        | Some(Commands::Man)
+        | Some(Commands::Version) => {
+            // Handled before target discovery
         }
         None => {
             // Default behavior: start interactive picker (unless --json or --no-ui)
             if cli.json || cli.no_ui {
-                handle_list(&targets, cli.json)?;
+                handle_list(&targets, cli.json, cli.with_deps, false, false)?;
             } else {
-                handle_pick(&targets, &cli)?;
+                handle_pick(&targets, &all_targets, cli, &config, &run_env)?;
             }
         }
     }
@@ -72,53 +518,127 @@ fn run() -> Result<()> {
     Ok(())
 }
 
-/// Get targets with caching support
+/// Get targets with caching support, in the order they were discovered
 fn get_targets(
     cli: &Cli,
     working_dir: &std::path::Path,
     parse_options: &ParseOptions,
+    config: &Config,
 ) -> Result<Vec<target::Target>> {
     // If a specific file is provided
     if let Some(ref makefile) = cli.file {
         if !makefile.exists() {
-            anyhow::bail!("Makefile not found: {}", makefile.display());
+            return Err(MakiError::makefile_not_found(makefile).into());
+        }
+        return get_targets_for_file(makefile, parse_options, &config.cache, cli.no_cache);
+    }
+
+    let discoveries = discover(cli, working_dir, parse_options, config, false)?;
+    if discoveries.is_empty() {
+        return Err(MakiError::no_makefile(working_dir).into());
+    }
+    if let [inferred] = discoveries.as_slice()
+        && inferred.provider == target::Provider::Inferred
+    {
+        note_inferred(cli, inferred);
+    }
+
+    Ok(providers::merge(discoveries, &config.providers))
+}
+
+/// Find the targets of each provider in `dir`, leaving out the providers
+/// that found nothing and, unless `include_disabled`, those the config
+/// disables
+fn discover(
+    cli: &Cli,
+    dir: &std::path::Path,
+    parse_options: &ParseOptions,
+    config: &Config,
+    include_disabled: bool,
+) -> Result<Vec<providers::Discovery>> {
+    let mut discoveries = Vec::new();
+    for provider in target::Provider::ALL {
+        if !include_disabled && !config.providers.is_enabled(provider) {
+            continue;
         }
-        return get_targets_for_file(makefile, parse_options, cli.no_cache);
+        let discovery = match provider {
+            target::Provider::Make => discover_makefiles(cli, dir, parse_options, config)?,
+            // Only a project with nothing else to run gets inferred targets
+            target::Provider::Inferred if discoveries.is_empty() => {
+                let started = Instant::now();
+                let discovery = infer::discover(dir);
+                timings::record(timings::Phase::Discovery, started);
+                discovery
+            }
+            target::Provider::Inferred => None,
+            _ => {
+                let started = Instant::now();
+                let discovery = providers::discover(provider, dir);
+                timings::record(timings::Phase::Discovery, started);
+                discovery
+            }
+        };
+        discoveries.extend(discovery);
+    }
+
+    Ok(discoveries)
+}
+
+/// Say on stderr that the targets were inferred, and how to keep them,
+/// unless the output is read by a program
+fn note_inferred(cli: &Cli, inferred: &providers::Discovery) {
+    let read_by_program = cli.json
+        || matches!(
+            cli.command,
+            Some(Commands::Complete { .. }) | Some(Commands::Query { .. })
+        );
+    if read_by_program {
+        return;
     }
+    let manifest = inferred
+        .files
+        .first()
+        .and_then(|f| f.file_name())
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    eprintln!(
+        "{}",
+        format!(
+            "No Makefile; these [inferred] targets are from {} (keep them with: maki export makefile --write)",
+            manifest
+        )
+        .dimmed()
+    );
+}
 
-    // Find all Makefiles
-    let makefiles = makefile::find_makefiles(working_dir, cli.recursive);
+/// Find and parse the Makefiles in `dir`, through the cache unless
+/// `--no-cache` is given
+fn discover_makefiles(
+    cli: &Cli,
+    dir: &std::path::Path,
+    parse_options: &ParseOptions,
+    config: &Config,
+) -> Result<Option<providers::Discovery>> {
+    let started = std::time::Instant::now();
+    let makefiles = scan::find_makefiles(dir, cli.recursive, &makefile_names(cli, config))?;
+    timings::record(timings::Phase::Discovery, started);
     if makefiles.is_empty() {
-        anyhow::bail!("No Makefile found in {}", working_dir.display());
+        return Ok(None);
     }
 
     // Load cache
-    let mut cache = if cli.no_cache {
-        Cache::new()
-    } else {
-        Cache::load().unwrap_or_else(|_| Cache::new())
-    };
+    let mut cache = load_cache(&config.cache, cli.no_cache);
+    let parse_started = Instant::now();
 
     let mut all_targets = Vec::new();
     let mut seen_names = std::collections::HashSet::new();
-    let mut cache_modified = false;
+    let mut warnings = Vec::new();
 
     for makefile_path in &makefiles {
-        let targets = if cli.no_cache {
-            // Skip cache, parse directly
-            makefile::parse_makefile(makefile_path, parse_options)?
-        } else if let Some(cached_targets) = cache.get(makefile_path) {
-            // Use cached targets
-            cached_targets.clone()
-        } else {
-            // Parse and cache
-            let parsed = makefile::parse_makefile(makefile_path, parse_options)?;
-            cache.set(makefile_path, parsed.clone())?;
-            cache_modified = true;
-            parsed
-        };
+        let parsed = parse_makefile(&mut cache, makefile_path, parse_options, cli.no_cache)?;
+        warnings.extend(parsed.warnings);
 
-        for target in targets {
+        for target in parsed.targets {
             if !seen_names.contains(&target.name) {
                 seen_names.insert(target.name.clone());
                 all_targets.push(target);
@@ -126,13 +646,112 @@ fn get_targets(
         }
     }
 
-    // Save cache if modified
-    if cache_modified && !cli.no_cache {
-        let _ = cache.save(); // Ignore save errors, caching is best-effort
+    timings::record(timings::Phase::Parse, parse_started);
+
+    // Save the cache, which also records access times for eviction
+    if !cli.no_cache {
+        save_cache(&mut cache);
+    }
+
+    Ok(Some(providers::Discovery {
+        provider: target::Provider::Make,
+        files: makefiles,
+        targets: all_targets,
+        duration: started.elapsed(),
+        warnings,
+    }))
+}
+
+/// Parse a Makefile, through the cache unless `no_cache`, warning on stderr
+/// about what parsing it found when it wasn't cached
+fn parse_makefile(
+    cache: &mut Cache,
+    makefile: &Path,
+    parse_options: &ParseOptions,
+    no_cache: bool,
+) -> Result<cache::Parsed> {
+    let parsed = if no_cache {
+        // Skip cache, parse directly
+        let info = makefile::parse_makefile_info(makefile, parse_options)?;
+        cache::Parsed {
+            targets: info.targets,
+            warnings: info.warnings,
+            cached: false,
+        }
+    } else {
+        // Use cached targets, parsing what changed
+        cache.parse_file(makefile, parse_options)?
+    };
+
+    timings::cache_lookup(parsed.cached);
+    if !parsed.cached {
+        for warning in &parsed.warnings {
+            eprintln!("{} {}", "warning:".yellow().bold(), warning);
+        }
+    }
+    Ok(parsed)
+}
+
+/// Ask before scanning the home directory or a filesystem root recursively,
+/// refusing when there is no terminal to ask at
+fn confirm_broad_scan(root: &Path, cli: &Cli) -> Result<()> {
+    if !scan::is_too_broad(root, dirs::home_dir().as_deref()) {
+        return Ok(());
+    }
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!(
+            "Not scanning all of {} for Makefiles without --force",
+            root.display()
+        );
+    }
+
+    let message = format!(
+        "Scan all of {} for Makefiles? This can take a long time",
+        root.display()
+    );
+    let confirmed = match cli.picker {
+        PickerKind::Skim => prompt::confirm(&message)?,
+        PickerKind::Plain => plain::confirm(&message)?,
+    };
+    if !confirmed {
+        anyhow::bail!("Scan cancelled (run from the project directory, or pass --force)");
+    }
+    Ok(())
+}
+
+/// The project directories to gather targets from: every `--cwd` when more
+/// than one is given, otherwise the config's `roots`, otherwise just the
+/// working directory
+fn project_roots(cli: &Cli, config: &Config, working_dir: &std::path::Path) -> Vec<PathBuf> {
+    if cli.file.is_some() {
+        return vec![working_dir.to_path_buf()];
+    }
+    if cli.cwd.len() > 1 {
+        return cli.cwd.clone();
+    }
+
+    let roots = config.resolved_roots(working_dir);
+    if roots.is_empty() {
+        vec![working_dir.to_path_buf()]
+    } else {
+        roots
     }
+}
 
-    // Sort targets alphabetically
-    all_targets.sort_by(|a, b| a.name.cmp(&b.name));
+/// Get the targets of several project roots, each tagged with its root
+fn get_targets_in_roots(
+    cli: &Cli,
+    roots: &[PathBuf],
+    parse_options: &ParseOptions,
+    config: &Config,
+) -> Result<Vec<target::Target>> {
+    let mut all_targets = Vec::new();
+    for root in roots {
+        for mut target in get_targets(cli, root, parse_options, config)? {
+            target.root = Some(root.clone());
+            all_targets.push(target);
+        }
+    }
 
     Ok(all_targets)
 }
@@ -141,43 +760,107 @@ fn get_targets(
 fn get_targets_for_file(
     makefile: &std::path::Path,
     parse_options: &ParseOptions,
+    cache_policy: &CachePolicy,
     no_cache: bool,
 ) -> Result<Vec<target::Target>> {
-    if no_cache {
-        return makefile::parse_makefile(makefile, parse_options);
+    let mut cache = load_cache(cache_policy, no_cache);
+
+    let started = Instant::now();
+    let parsed = parse_makefile(&mut cache, makefile, parse_options, no_cache)?;
+    timings::record(timings::Phase::Parse, started);
+    if !no_cache {
+        save_cache(&mut cache);
     }
 
-    let mut cache = Cache::load().unwrap_or_else(|_| Cache::new());
+    Ok(parsed.targets)
+}
 
-    if let Some(cached_targets) = cache.get(makefile) {
-        return Ok(cached_targets.clone());
+/// The cache, or an empty one with `no_cache` or when it can't be read
+fn load_cache(policy: &CachePolicy, no_cache: bool) -> Cache {
+    if no_cache {
+        return Cache::new();
     }
+    let started = Instant::now();
+    let cache = Cache::load_with_policy(policy.clone())
+        .unwrap_or_else(|_| Cache::with_policy(policy.clone()));
+    timings::record(timings::Phase::CacheLoad, started);
+    cache
+}
 
-    let targets = makefile::parse_makefile(makefile, parse_options)?;
-    cache.set(makefile, targets.clone())?;
+/// Save the cache, ignoring errors since caching is best-effort
+fn save_cache(cache: &mut Cache) {
+    let started = Instant::now();
     let _ = cache.save();
-
-    Ok(targets)
+    timings::record(timings::Phase::CacheSave, started);
 }
 
 /// Handle the list command
-fn handle_list(targets: &[target::Target], json_output: bool) -> Result<()> {
-    if json_output {
-        let json =
-            serde_json::to_string_pretty(targets).context("Failed to serialize targets to JSON")?;
-        println!("{}", json);
+fn handle_list(
+    targets: &[target::Target],
+    json_output: bool,
+    with_deps: bool,
+    as_tree: bool,
+    numbered: bool,
+) -> Result<()> {
+    if as_tree && json_output {
+        print_json(&tree::build(targets)).context("Failed to serialize target tree to JSON")?;
+    } else if as_tree {
+        for line in tree::render(&tree::collapse(tree::build(targets)), with_deps) {
+            println!("{}", line);
+        }
+
+        println!();
+        println!(
+            "{} {} target(s) found",
+            "→".blue(),
+            targets.len().to_string().bold()
+        );
+    } else if json_output {
+        print_json(targets).context("Failed to serialize targets to JSON")?;
     } else {
-        let max_name_len = targets.iter().map(|t| t.name.len()).max().unwrap_or(20);
+        let max_name_len = targets
+            .iter()
+            .map(|t| width::columns(&t.picker_name()))
+            .max()
+            .unwrap_or(20);
 
-        for target in targets {
-            let name = format!("{:<width$}", target.name, width = max_name_len);
-            match &target.description {
-                Some(desc) => {
-                    println!("  {}  {}", name.green(), desc.dimmed());
-                }
-                None => {
-                    println!("  {}", name.green());
-                }
+        let number_width = targets.len().to_string().len();
+        for (i, target) in targets.iter().enumerate() {
+            // What `maki 3` and `maki run @3` go by
+            let number = match numbered {
+                true => format!("{:>number_width$}. ", i + 1).dimmed().to_string(),
+                false => String::new(),
+            };
+            let name = width::pad(&target.picker_name(), max_name_len);
+            // Aggregate targets only run their prerequisites, so always show them
+            let name = if target.aggregate {
+                name.cyan()
+            } else {
+                name.green()
+            };
+            let mut line = match &target.description {
+                Some(desc) => format!("  {}{}  {}", number, name, desc.dimmed()),
+                None => format!("  {}{}", number, name),
+            };
+            if !target.tags.is_empty() {
+                line.push_str(&format!("  {}", target.tag_chips().magenta()));
+            }
+            let prerequisites = target.prerequisite_list();
+            if (with_deps || target.aggregate) && !prerequisites.is_empty() {
+                let deps = format!("← {}", prerequisites);
+                line.push_str(&format!("  {}", deps.dimmed()));
+            } else if target.aggregate {
+                line.push_str(&format!("  {}", "(no recipe or prerequisites)".yellow()));
+            }
+            println!("{}", line);
+            // The note goes below, under the description
+            if let Some(ref note) = target.note {
+                println!(
+                    "  {:<width$}  {}",
+                    "",
+                    format!("✎ {}", note).yellow(),
+                    width = number.len() + max_name_len
+                );
             }
         }
 
@@ -192,121 +875,2930 @@ fn handle_list(targets: &[target::Target], json_output: bool) -> Result<()> {
     Ok(())
 }
 
-/// Handle the pick command (fuzzy finder)
-fn handle_pick(targets: &[target::Target], cli: &Cli) -> Result<()> {
-    if cli.no_ui || cli.json {
-        return handle_list(targets, cli.json);
+/// Handle `maki help-target`: run the Makefile's `help` target, which may
+/// be hidden from the other commands
+fn handle_help_target(targets: &[target::Target], cli: &Cli, env: &[EnvVar]) -> Result<()> {
+    if help::find(targets).is_none() {
+        anyhow::bail!(
+            "The Makefile has no {} target; `maki list` shows what it does have",
+            help::HELP_TARGET
+        );
     }
+    handle_run(
+        help::HELP_TARGET,
+        targets,
+        cli,
+        env,
+        &RepeatArgs::default(),
+        None,
+        MakeMode::Run,
+    )
+}
 
-    let selected = fuzzy::select_target_with_preview(targets)?;
+/// List the targets of a remote repository's Makefiles, fetched without
+/// cloning it
+fn handle_list_repo(
+    url: &str,
+    git_ref: Option<&str>,
+    tree: bool,
+    tag: Option<&str>,
+    phony_only: bool,
+    cli: &Cli,
+) -> Result<()> {
+    let parse_options = ParseOptions {
+        include_private: cli.all,
+        include_patterns: cli.patterns,
+        include_generated: cli.generated,
+    };
+    // The remote repository's own config isn't read, only the flag
+    let names = match cli.makefile_names.is_empty() {
+        true => makefile::default_makefile_names(),
+        false => cli.makefile_names.clone(),
+    };
+    let checkout = remote::Checkout::fetch(url, git_ref, &names)?;
+    let mut targets = checkout.targets(cli.recursive, &parse_options)?;
+    cli.sort.apply(&mut targets);
 
-    match selected {
-        Some(target) => {
-            println!("{} {}", "Selected:".green(), target.name.bold());
+    let mut targets = tagged(&targets, tag)?;
+    targets.retain(|t| t.is_phony || !phony_only);
+    handle_list(&targets, cli.json, cli.with_deps, tree, false)
+}
 
-            // Prompt for required variables if any
-            let variables = if target.has_required_vars() {
-                prompt::prompt_for_variables(&target.required_vars)?
-            } else {
-                Vec::new()
-            };
+/// The targets with the `@tags` tag `tag`, or all of them without one
+fn tagged(targets: &[target::Target], tag: Option<&str>) -> Result<Vec<target::Target>> {
+    let Some(tag) = tag else {
+        return Ok(targets.to_vec());
+    };
+    let found: Vec<target::Target> = targets.iter().filter(|t| t.has_tag(tag)).cloned().collect();
+    if found.is_empty() {
+        let mut known: Vec<&str> = Vec::new();
+        for tag in targets.iter().flat_map(|t| &t.tags) {
+            if !known.iter().any(|k| k.eq_ignore_ascii_case(tag)) {
+                known.push(tag);
+            }
+        }
+        match known.is_empty() {
+            true => anyhow::bail!(
+                "No targets tagged {}; tag them with `## @tags {}`",
+                tag,
+                tag
+            ),
+            false => anyhow::bail!("No targets tagged {} (tags: {})", tag, known.join(", ")),
+        }
+    }
+    Ok(found)
+}
 
-            if !cli.dry_run {
-                let exec_options = ExecuteOptions {
-                    dry_run: cli.dry_run,
-                    print_cmd: true,
-                    cwd: Some(cli.working_dir()),
-                    makefile: cli.file.clone(),
-                    variables,
-                };
+/// Handle the cache command
+fn handle_cache(action: &CacheCommand, config: &Config, json_output: bool) -> Result<()> {
+    match action {
+        CacheCommand::Stats => {
+            let stats = Cache::load_with_policy(config.cache.clone())?.stats();
 
-                let status = executor::execute_target(&target.name, &exec_options)?;
+            if json_output {
+                print_json(&stats).context("Failed to serialize cache stats to JSON")?;
+                return Ok(());
+            }
 
-                if !status.success() {
-                    std::process::exit(status.code().unwrap_or(1));
+            if let Some(path) = Cache::cache_file_path() {
+                println!("{} {}", "Cache:".bold(), path.display());
+                let quarantined = Cache::quarantine_path(&path);
+                if quarantined.exists() {
+                    println!(
+                        "  {} {} (unreadable, set aside)",
+                        "corrupt".yellow(),
+                        quarantined.display()
+                    );
                 }
-            } else {
-                let vars_str = if !variables.is_empty() {
-                    format!(
-                        " {}",
-                        variables
-                            .iter()
-                            .map(|(k, v)| format!("{}={}", k, v))
-                            .collect::<Vec<_>>()
-                            .join(" ")
-                    )
-                } else {
-                    String::new()
-                };
-                println!("{} make {}{}", "Would run:".yellow(), target.name, vars_str);
             }
+            println!(
+                "  {:<10} {} of {} max",
+                "entries", stats.entry_count, stats.policy.max_entries
+            );
+            println!("  {:<10} {}", "targets", stats.total_targets);
+            println!(
+                "  {:<10} {} days unused",
+                "max age", stats.policy.max_age_days
+            );
+            println!(
+                "  {:<10} {} expired, {} over capacity",
+                "evicted", stats.evictions.expired, stats.evictions.over_capacity
+            );
         }
-        None => {
-            println!("{}", "No target selected.".yellow());
+        CacheCommand::Clear => {
+            cache::clear_cache()?;
+            println!("{}", "Cache cleared.".green());
         }
     }
 
     Ok(())
 }
 
-/// Handle the run command
-fn handle_run(target_name: &str, targets: &[target::Target], cli: &Cli) -> Result<()> {
-    // Find the target to check for required variables
-    let target = targets.iter().find(|t| t.name == target_name);
+/// Print the effective settings, each with where it comes from with `origin`
+fn handle_settings(dir: &Path, cli: &Cli, origin: bool) -> Result<()> {
+    let mut layers = Config::layers(dir, std::env::vars())?;
+    layers.extend(flag_layers(cli));
+    let layered = config::Layered::merge(&layers);
+    let settings = layered.settings();
 
-    // Prompt for required variables if any
-    let variables = if let Some(t) = target {
-        if t.has_required_vars() {
-            prompt::prompt_for_variables(&t.required_vars)?
+    if cli.json {
+        let settings: Vec<serde_json::Value> = settings
+            .iter()
+            .map(|(key, value, origin)| {
+                serde_json::json!({ "key": key, "value": value, "origin": origin.to_string() })
+            })
+            .collect();
+        print_json(&settings).context("Failed to serialize settings to JSON")?;
+        return Ok(());
+    }
+
+    let lines: Vec<String> = settings
+        .iter()
+        .map(|(key, value, _)| format!("{} = {}", key, value))
+        .collect();
+    let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    for (line, (_, _, from)) in lines.iter().zip(&settings) {
+        if origin {
+            println!(
+                "{:<width$}  {}",
+                line,
+                from.to_string().dimmed(),
+                width = width
+            );
         } else {
-            Vec::new()
+            println!("{}", line);
         }
-    } else {
-        Vec::new()
-    };
-
-    let exec_options = ExecuteOptions {
-        dry_run: cli.dry_run,
-        print_cmd: true,
-        cwd: Some(cli.working_dir()),
-        makefile: cli.file.clone(),
-        variables,
-    };
-
-    let status = executor::execute_target(target_name, &exec_options)?;
-
-    if !status.success() {
-        std::process::exit(status.code().unwrap_or(1));
     }
 
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::path::PathBuf;
+/// The settings flags override, each as a layer above the config files and
+/// the environment
+fn flag_layers(cli: &Cli) -> Vec<config::Layer> {
+    let flag = |name: &str| config::Origin::Flag(name.to_string());
+    let mut layers = Vec::new();
+    if cli.cwd.len() > 1 {
+        let roots = cli
+            .cwd
+            .iter()
+            .map(|dir| toml::Value::from(dir.display().to_string()))
+            .collect();
+        layers.push(config::Layer::setting(
+            flag("--cwd"),
+            "roots",
+            toml::Value::Array(roots),
+        ));
+    }
+    if !cli.makefile_names.is_empty() {
+        let names = cli
+            .makefile_names
+            .iter()
+            .map(|name| toml::Value::from(name.as_str()))
+            .collect();
+        layers.push(config::Layer::setting(
+            flag("--makefile-name"),
+            "makefile_names",
+            toml::Value::Array(names),
+        ));
+    }
+    if let Some(rank) = cli.rank
+        && let Ok(rank) = toml::Value::try_from(rank)
+    {
+        layers.push(config::Layer::setting(flag("--rank"), "picker.rank", rank));
+    }
+    for (name, value) in &cli.vars {
+        layers.push(config::Layer::setting(
+            flag("--var"),
+            &format!("env.{}", name),
+            toml::Value::from(value.as_str()),
+        ));
+    }
+    layers
+}
 
-    #[test]
-    fn test_parse_options_from_cli() {
-        let cli = Cli::parse_from(["maki", "--all", "--patterns", "list"]);
+/// Handle `maki config export` and `maki config import`
+fn handle_config(action: &ConfigCommand, dir: &Path, cli: &Cli) -> Result<()> {
+    match action {
+        ConfigCommand::Export { file } => {
+            let bundle = bundle::export(dir)?;
+            match file {
+                Some(path) => {
+                    fs::write(path, bundle)
+                        .with_context(|| format!("Failed to write {}", path.display()))?;
+                    println!("{} Exported settings to {}", "✓".green(), path.display());
+                }
+                None => print!("{}", bundle),
+            }
+        }
+        ConfigCommand::Import { file, prefer, yes } => {
+            let content = fs::read_to_string(file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let incoming = bundle::read(&content)?;
 
-        let parse_options = ParseOptions {
-            include_private: cli.all,
-            include_patterns: cli.patterns,
-        };
+            let path = Config::config_path(dir);
+            let local: toml::Table = if path.exists() {
+                toml::from_str(&fs::read_to_string(&path)?)
+                    .with_context(|| format!("Invalid config file: {}", path.display()))?
+            } else {
+                toml::Table::new()
+            };
 
-        assert!(parse_options.include_private);
-        assert!(parse_options.include_patterns);
-    }
+            let merge = bundle::merge(&local, &incoming, *prefer);
+            for key in &merge.added {
+                println!("{} {}", "+".green(), key);
+            }
+            for conflict in &merge.conflicts {
+                let kept = match prefer {
+                    bundle::Prefer::Local => "keeping yours",
+                    bundle::Prefer::Bundle => "taking the bundle's",
+                };
+                println!(
+                    "{} {}: {} here, {} in the bundle ({})",
+                    "!".yellow(),
+                    conflict.key,
+                    conflict.local,
+                    conflict.bundle,
+                    kept
+                );
+            }
 
-    #[test]
-    fn test_default_working_dir() {
-        let cli = Cli::parse_from(["maki"]);
-        let wd = cli.working_dir();
+            if merge.merged == local {
+                println!("{}", "Settings are already up to date.".green());
+                return Ok(());
+            }
+            if cli.dry_run {
+                println!("{}", "Dry run: no files were changed.".yellow());
+                return Ok(());
+            }
+
+            let message = format!("Write the changes to {}?", path.display());
+            let confirmed = *yes
+                || match cli.picker {
+                    PickerKind::Skim => prompt::confirm(&message)?,
+                    PickerKind::Plain => plain::confirm(&message)?,
+                };
+            if !confirmed {
+                println!("{}", "No files were changed.".yellow());
+                return Ok(());
+            }
+
+            // Comments in the old file aren't carried over, so keep a copy
+            if path.exists() {
+                fs::copy(&path, path.with_extension("toml.bak"))
+                    .context("Failed to back up the config file")?;
+            }
+            fs::write(&path, toml::to_string(&merge.merged)?)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            println!("{} Imported settings into {}", "✓".green(), path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Handle the version command and --version
+fn handle_version(cli: &Cli) -> Result<()> {
+    if cli.version && !cli.json {
+        println!("maki {}", version::VERSION);
+        return Ok(());
+    }
+
+    // A broken maki.toml shouldn't stop a bug report about it
+    let providers = Config::load(&cli.working_dir())
+        .map(|config| config.providers)
+        .unwrap_or_default();
+    let info = BuildInfo::current(&providers);
+    if cli.json {
+        print_json(&info).context("Failed to serialize version to JSON")?;
+    } else {
+        print!("{}", info.render());
+    }
+    Ok(())
+}
+
+/// Handle the errors command: open one of the last run's errors in the
+/// editor, or list them
+fn handle_errors(number: Option<usize>, list: bool, json_output: bool) -> Result<()> {
+    let Some(report) = problems::load()? else {
+        anyhow::bail!("No errors collected yet; run a target with --errors first");
+    };
+
+    if json_output {
+        print_json(&report).context("Failed to serialize errors to JSON")?;
+        return Ok(());
+    }
+
+    if report.problems.is_empty() {
+        println!(
+            "{} The last run with --errors ({}) printed no errors",
+            "✓".green(),
+            report.target.bold()
+        );
+        return Ok(());
+    }
+
+    if list {
+        let width = report.problems.len().to_string().len();
+        for (i, problem) in report.problems.iter().enumerate() {
+            let severity = match problem.severity {
+                problems::Severity::Error => "error".red(),
+                problems::Severity::Warning => "warning".yellow(),
+            };
+            println!(
+                "  {:>width$}  {}  {}: {}",
+                i + 1,
+                problem.location(&report.dir).bold(),
+                severity,
+                problem.message,
+                width = width
+            );
+        }
+        return Ok(());
+    }
+
+    let index = number.unwrap_or(1);
+    let Some(problem) = report.problems.get(index - 1) else {
+        anyhow::bail!(
+            "The last run of {} printed only {} error(s) and warning(s) (maki errors --list)",
+            report.target,
+            report.problems.len()
+        );
+    };
+    println!(
+        "{} {}  {}",
+        "Opening".dimmed(),
+        problem.location(&report.dir).bold(),
+        problem.message
+    );
+    editor::open(&problem.file, problem.line)
+}
+
+/// Play back a recording made with --record, from --log-dir if given
+fn handle_replay(run: Option<&str>, speed: f64, cli: &Cli) -> Result<()> {
+    let dir = record::recordings_dir(cli.log_dir.as_deref())
+        .context("Could not determine data directory")?;
+    let path = record::find(&dir, run)?;
+    let recording = record::load(&path)?;
+
+    let title = recording.header.title.as_deref().unwrap_or_default();
+    println!(
+        "{} {} {}",
+        "Replaying:".green(),
+        title,
+        format!(
+            "(recorded at {}x{})",
+            recording.header.width, recording.header.height
+        )
+        .dimmed()
+    );
+    record::replay(&recording, speed, &mut std::io::stdout())
+        .context("Failed to replay the recording")?;
+    println!();
+    println!("{} {}", "Replayed:".dimmed(), path.display());
+    Ok(())
+}
+
+/// Handle the history command
+fn handle_history(limit: usize, json_output: bool) -> Result<()> {
+    let entries = history::load(limit)?;
+
+    if json_output {
+        print_json(&entries).context("Failed to serialize history to JSON")?;
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("{}", "No runs recorded yet.".yellow());
+        return Ok(());
+    }
+
+    let width = entries.iter().map(|e| e.target.len()).max().unwrap_or(0);
+    for entry in &entries {
+        let when = chrono::DateTime::from_timestamp(entry.started_at as i64, 0)
+            .map(|t| {
+                t.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        let marker = if entry.success {
+            "✓".green()
+        } else {
+            "✗".red()
+        };
+        let target = format!("{:<width$}", entry.target, width = width);
+        let duration = format!("{:>7.1}s", entry.duration_ms as f64 / 1000.0);
+
+        let mut line = format!(
+            "  {}  {} {}  {}  {}",
+            when.dimmed(),
+            marker,
+            target.bold(),
+            duration,
+            entry.command
+        );
+        if let Some(ref summary) = entry.repeat {
+            line.push_str(&format!("  {}", repeat::describe(summary).dimmed()));
+        }
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+/// The error for a target none of `targets` is called
+fn not_found(name: &str, targets: &[target::Target]) -> MakiError {
+    let names: Vec<&str> = targets.iter().map(|t| t.name.as_str()).collect();
+    MakiError::target_not_found(name, &names)
+}
+
+/// Handle the show command
+fn handle_show(target_name: &str, targets: &[target::Target], json_output: bool) -> Result<()> {
+    let target = targets
+        .iter()
+        .find(|t| t.name == target_name)
+        .ok_or_else(|| not_found(target_name, targets))?;
+
+    show_target(target, json_output)
+}
+
+/// Handle the note command
+fn handle_note(
+    target_name: &str,
+    note: Option<&str>,
+    remove: bool,
+    targets: &[target::Target],
+    working_dir: &std::path::Path,
+) -> Result<()> {
+    let matches: Vec<&target::Target> = targets.iter().filter(|t| t.name == target_name).collect();
+    if matches.len() > 1 {
+        let roots: Vec<String> = matches.iter().filter_map(|t| t.root_label()).collect();
+        anyhow::bail!(
+            "Target {} is defined in several project roots ({}); pass a single --cwd to pick one",
+            target_name,
+            roots.join(", ")
+        );
+    }
+    let target = matches
+        .first()
+        .ok_or_else(|| not_found(target_name, targets))?;
+    let dir = target.root.as_deref().unwrap_or(working_dir);
+    let mut notes = notes::Notes::load()?;
+
+    if remove {
+        if notes.remove(dir, target_name) {
+            notes.save()?;
+            println!("{} Removed the note on {}", "✓".green(), target_name.bold());
+        } else {
+            println!("{}", format!("No note on {}.", target_name).yellow());
+        }
+        return Ok(());
+    }
+
+    match note {
+        Some(note) if note.trim().is_empty() => {
+            anyhow::bail!("The note is empty; use --remove to delete it")
+        }
+        Some(note) => {
+            notes.set(dir, target_name, note);
+            notes.save()?;
+            println!(
+                "{} Noted {}: {}",
+                "✓".green(),
+                target_name.bold(),
+                note.trim()
+            );
+        }
+        None => match notes.get(dir, target_name) {
+            Some(note) => println!("{}", note),
+            None => println!("{}", format!("No note on {}.", target_name).yellow()),
+        },
+    }
+    Ok(())
+}
+
+/// Handle `maki export ci`: print a pipeline with a job for each of the
+/// targets named, or else for each documented one
+fn handle_export_ci(
+    names: &[String],
+    format: ci::CiFormat,
+    targets: &[target::Target],
+    working_dir: &std::path::Path,
+) -> Result<()> {
+    let mut selected = Vec::new();
+    for name in names {
+        let Some(target) = lookup::resolve(name, targets, working_dir)? else {
+            let known: Vec<&str> = targets.iter().map(|t| t.name.as_str()).collect();
+            return Err(MakiError::target_not_found(name, &known).into());
+        };
+        if !ci::can_run(target) {
+            anyhow::bail!(
+                "{} isn't a Makefile target, so a pipeline can't run it with make",
+                name
+            );
+        }
+        let index = targets
+            .iter()
+            .position(|t| std::ptr::eq(t, target))
+            .unwrap_or_default();
+        if !selected.contains(&index) {
+            selected.push(index);
+        }
+    }
+    if names.is_empty() {
+        let runnable: Vec<usize> = (0..targets.len())
+            .filter(|&i| ci::can_run(&targets[i]))
+            .collect();
+        let documented: Vec<usize> = runnable
+            .iter()
+            .copied()
+            .filter(|&i| targets[i].description.is_some())
+            .collect();
+        selected = match documented.is_empty() {
+            true => runnable,
+            false => documented,
+        };
+    }
+    if selected.is_empty() {
+        anyhow::bail!("No Makefile targets to make jobs of");
+    }
+
+    let jobs = ci::jobs(targets, &selected, working_dir);
+    print!("{}", ci::render(&jobs, format));
+    Ok(())
+}
+
+/// Handle `maki export makefile`: print, or with `--write` create, a starter
+/// Makefile of the targets inferred from the project's manifest
+fn handle_export_makefile(
+    write: bool,
+    working_dir: &std::path::Path,
+    cli: &Cli,
+    config: &Config,
+) -> Result<()> {
+    let Some(ecosystem) = infer::detect(working_dir) else {
+        anyhow::bail!(
+            "No Cargo.toml, package.json, go.mod, or pyproject.toml in {} to infer targets from",
+            working_dir.display()
+        );
+    };
+    let tasks = infer::tasks(ecosystem, working_dir);
+    let makefile = infer::starter_makefile(ecosystem, &tasks);
+    if !write {
+        print!("{}", makefile);
+        return Ok(());
+    }
+
+    if let Some(existing) = makefile_names(cli, config)
+        .iter()
+        .map(|name| working_dir.join(name))
+        .find(|path| path.exists())
+    {
+        anyhow::bail!(
+            "{} already exists; run maki export makefile to print the targets to add by hand",
+            existing.display()
+        );
+    }
+    let path = working_dir.join("Makefile");
+    fs::write(&path, makefile).with_context(|| format!("Failed to write {}", path.display()))?;
+    println!(
+        "{} Wrote {} with {} target(s) inferred from {}",
+        "✓".green(),
+        path.display(),
+        tasks.len(),
+        ecosystem.manifest()
+    );
+    Ok(())
+}
+
+/// Handle `maki usage export`: write the run counts of each root's targets
+/// to its usage file
+fn handle_usage_export(
+    targets: &[target::Target],
+    roots: &[PathBuf],
+    working_dir: &std::path::Path,
+) -> Result<()> {
+    let entries = history::load(usize::MAX)?;
+    let contributor = usage::contributor_id()?;
+
+    let mut exported = false;
+    for root in roots {
+        let names: Vec<&str> = targets
+            .iter()
+            .filter(|t| t.root.as_deref().unwrap_or(working_dir) == root)
+            .filter(|t| !t.is_pattern())
+            .map(|t| t.name.as_str())
+            .collect();
+        let counts = usage::count_runs(&entries, root, &names);
+        let mut team = usage::TeamUsage::load(root)?;
+        // Empty counts only matter to drop ones exported before
+        if counts.is_empty() && !team.has(&contributor) {
+            continue;
+        }
+
+        let runs: u64 = counts.values().sum();
+        let count = counts.len();
+        let path = usage::usage_file_path(root);
+        team.record(&contributor, counts);
+        team.save(root)?;
+        exported = true;
+        println!(
+            "{} Exported {} run(s) of {} target(s) to {} ({} teammate(s) so far)",
+            "✓".green(),
+            runs,
+            count,
+            path.strip_prefix(working_dir).unwrap_or(&path).display(),
+            team.contributors()
+        );
+    }
+
+    if !exported {
+        println!(
+            "{}",
+            "Your history has no runs of these targets to export yet.".yellow()
+        );
+        return Ok(());
+    }
+    println!(
+        "{}",
+        "  Commit the file to share it; the picker ranks by it for everyone.".dimmed()
+    );
+    Ok(())
+}
+
+/// Handle `maki usage show`: the team's run counts, most run first
+fn handle_usage_show(roots: &[PathBuf], json_output: bool) -> Result<()> {
+    let mut rows = Vec::new();
+    for root in roots {
+        let team = usage::TeamUsage::load(root)?;
+        let mut totals: Vec<(String, u64)> = team
+            .totals()
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .collect();
+        totals.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        rows.extend(totals.into_iter().map(|(name, runs)| (root, name, runs)));
+    }
+
+    if json_output {
+        let rows: Vec<serde_json::Value> = rows
+            .iter()
+            .map(|(root, name, runs)| {
+                serde_json::json!({ "root": root, "target": name, "runs": runs })
+            })
+            .collect();
+        print_json(&rows).context("Failed to serialize usage to JSON")?;
+        return Ok(());
+    }
+
+    if rows.is_empty() {
+        println!(
+            "{}",
+            format!(
+                "No team usage yet; maki usage export writes yours to {}.",
+                usage::USAGE_FILE
+            )
+            .yellow()
+        );
+        return Ok(());
+    }
+    let width = rows
+        .iter()
+        .map(|(_, name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    for (root, name, runs) in &rows {
+        let name = format!("{:width$}", name, width = width);
+        match roots.len() {
+            1 => println!("  {}  {} run(s)", name.bold(), runs),
+            _ => println!(
+                "  {}  {} run(s)  {}",
+                name.bold(),
+                runs,
+                root.display().to_string().dimmed()
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Handle `maki queue add`: queue the targets, with the NAME=VALUE variables
+/// among them
+fn handle_queue_add(
+    words: &[String],
+    targets: &[target::Target],
+    working_dir: &std::path::Path,
+) -> Result<()> {
+    let (variables, names): (Vec<_>, Vec<_>) =
+        words.iter().partition(|w| env::parse_var(w).is_ok());
+    let variables: Vec<(String, String)> = variables
+        .into_iter()
+        .filter_map(|w| env::parse_var(w).ok())
+        .collect();
+    if names.is_empty() {
+        anyhow::bail!("No targets to queue, only variables");
+    }
+    // Check them all first, so a typo queues nothing
+    for name in &names {
+        if !targets.iter().any(|t| t.name == **name) {
+            return Err(not_found(name, targets).into());
+        }
+    }
+
+    for name in names {
+        queue_target(name, &variables, working_dir)?;
+    }
+    Ok(())
+}
+
+/// Add a target to the project's queue and say so
+fn queue_target(
+    name: &str,
+    variables: &[(String, String)],
+    working_dir: &std::path::Path,
+) -> Result<()> {
+    let mut queue = queue::Queue::load()?;
+    let item = queue::QueuedTarget::new(name, variables);
+    let label = item.label();
+    if !queue.push(working_dir, item) {
+        println!("{}", format!("{} is already queued.", label).yellow());
+        return Ok(());
+    }
+    queue.save()?;
+    println!(
+        "{} Queued {} ({} in the queue)",
+        "✓".green(),
+        label.bold(),
+        queue.items(working_dir).len()
+    );
+    Ok(())
+}
+
+/// Handle `maki queue list`
+fn handle_queue_list(working_dir: &std::path::Path, json_output: bool) -> Result<()> {
+    let queue = queue::Queue::load()?;
+    let items = queue.items(working_dir);
+
+    if json_output {
+        print_json(items).context("Failed to serialize queue to JSON")?;
+        return Ok(());
+    }
+
+    if items.is_empty() {
+        println!("{}", "The queue is empty.".yellow());
+        return Ok(());
+    }
+    for (i, item) in items.iter().enumerate() {
+        println!("  {}. {}", i + 1, item.label());
+    }
+    Ok(())
+}
+
+/// Handle `maki queue remove`
+fn handle_queue_remove(target_name: &str, working_dir: &std::path::Path) -> Result<()> {
+    let mut queue = queue::Queue::load()?;
+    if queue.remove(working_dir, target_name) == 0 {
+        println!("{}", format!("{} is not queued.", target_name).yellow());
+        return Ok(());
+    }
+    queue.save()?;
+    println!("{} Took {} off the queue", "✓".green(), target_name.bold());
+    Ok(())
+}
+
+/// Handle `maki queue clear`
+fn handle_queue_clear(working_dir: &std::path::Path) -> Result<()> {
+    let mut queue = queue::Queue::load()?;
+    let cleared = queue.clear(working_dir);
+    if cleared == 0 {
+        println!("{}", "The queue is empty.".yellow());
+        return Ok(());
+    }
+    queue.save()?;
+    println!("{} Cleared {} queued target(s)", "✓".green(), cleared);
+    Ok(())
+}
+
+/// Handle `maki queue run`: run the queued targets one at a time, taking
+/// each off the queue once it succeeds, and stop at the first that fails
+fn handle_queue_run(
+    targets: &[target::Target],
+    cli: &Cli,
+    env: &[EnvVar],
+    working_dir: &std::path::Path,
+) -> Result<()> {
+    if cli.json || cli.split {
+        anyhow::bail!("--json and --split only work when running a single target");
+    }
+    let items = queue::Queue::load()?.items(working_dir).to_vec();
+    if items.is_empty() {
+        println!("{}", "The queue is empty.".yellow());
+        return Ok(());
+    }
+
+    for (i, item) in items.iter().enumerate() {
+        println!(
+            "{} {}",
+            format!("[{}/{}]", i + 1, items.len()).cyan().bold(),
+            item.label().bold()
+        );
+        let given = item.variables.clone().into_iter().collect();
+        if let Err(e) = run_given(&item.target, given, targets, cli, env) {
+            eprintln!(
+                "{} Stopped at {}; {} target(s) left in the queue (maki queue run to go on)",
+                "✗".red(),
+                item.label().bold(),
+                items.len() - i
+            );
+            return Err(e);
+        }
+        // Reloaded, to keep what was queued while this one ran
+        if !cli.dry_run {
+            let mut queue = queue::Queue::load()?;
+            queue.finish(working_dir, item);
+            queue.save()?;
+        }
+    }
+
+    println!("{} Ran {} queued target(s)", "✓".green(), items.len());
+    Ok(())
+}
+
+/// Run one queued or batched target with the variables it was given,
+/// prompting for the required ones still missing
+fn run_given(
+    target_name: &str,
+    given: Vec<(String, String)>,
+    targets: &[target::Target],
+    cli: &Cli,
+    env: &[EnvVar],
+) -> Result<()> {
+    let target = find_run_target(target_name, targets)?;
+    if let Some(t) = target {
+        check_sudo(t, cli)?;
+        check_guards(t, cli)?;
+    }
+    let variables = complete_variables(target, given, cli, env)?;
+    let exec_options = exec_options(cli, env, target, variables);
+
+    let run = RunTimer::start();
+    let status = executor::execute_target(target_name, &exec_options)?;
+    if !cli.dry_run {
+        run.record(target_name, &exec_options, &status, None);
+    }
+    if !status.success() {
+        return Err(MakiError::exec_failed(target_name, status).into());
+    }
+    Ok(())
+}
+
+/// Handle `maki batch`: run each line's target in turn, saying how each
+/// went, and stop at the first that fails unless --keep-going
+fn handle_batch(
+    file: &std::path::Path,
+    keep_going: bool,
+    targets: &[target::Target],
+    cli: &Cli,
+    env: &[EnvVar],
+) -> Result<()> {
+    if cli.json || cli.split {
+        anyhow::bail!("--json and --split only work when running a single target");
+    }
+    let from_stdin = file == std::path::Path::new("-");
+    let (name, content) = if from_stdin {
+        let mut content = String::new();
+        std::io::stdin()
+            .read_to_string(&mut content)
+            .context("Failed to read the batch from stdin")?;
+        ("stdin".to_string(), content)
+    } else {
+        let content = std::fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        (file.display().to_string(), content)
+    };
+    let steps = batch::parse(&content).with_context(|| format!("Invalid batch in {}", name))?;
+    if steps.is_empty() {
+        println!("{}", format!("No targets in {}.", name).yellow());
+        return Ok(());
+    }
+    // Check them all first, so a typo runs nothing
+    let base = cli.working_dir();
+    let mut resolved = Vec::new();
+    for step in &steps {
+        let at = || format!("line {} of {}", step.line, name);
+        match lookup::resolve(&step.target, targets, &base).with_context(at)? {
+            Some(target) => resolved.push(target),
+            None => {
+                return Err(anyhow::Error::from(not_found(&step.target, targets)).context(at()));
+            }
+        }
+    }
+    // Prompts would read from stdin, which the batch itself used up
+    if from_stdin {
+        for (step, target) in steps.iter().zip(&resolved) {
+            let missing: Vec<&str> = target
+                .required_vars
+                .iter()
+                .filter(|var| !set_by_environment(target, &var.name, env))
+                .filter(|var| !step.variables.iter().any(|(name, _)| *name == var.name))
+                .filter(|var| !(cli.defaults && var.default.is_some()))
+                .filter(|var| var.secret.as_ref().is_none_or(|s| s.command.is_none()))
+                .map(|var| var.name.as_str())
+                .collect();
+            if !missing.is_empty() {
+                anyhow::bail!(
+                    "line {} of stdin: {} needs {}, which can't be asked for while the batch is read from stdin; set them on the line",
+                    step.line,
+                    target.name,
+                    missing.join(", ")
+                );
+            }
+        }
+    }
+
+    let mut outcomes = Vec::new();
+    for (i, (step, target)) in steps.iter().zip(&resolved).enumerate() {
+        let label = step.label();
+        println!(
+            "{} {}",
+            format!("[{}/{}]", i + 1, steps.len()).cyan().bold(),
+            label.bold()
+        );
+        let started = std::time::Instant::now();
+        let result = run_given(
+            &target.name,
+            step.variables.clone(),
+            std::slice::from_ref(*target),
+            cli,
+            env,
+        );
+        let elapsed = started.elapsed();
+        match result {
+            Ok(()) => {
+                println!(
+                    "{} {} ({})",
+                    "✓".green(),
+                    label,
+                    heartbeat::format_elapsed(elapsed)
+                );
+                outcomes.push(batch::Outcome::Succeeded(elapsed));
+            }
+            Err(e) => {
+                eprintln!("{} {} (line {}): {}", "✗".red(), label, step.line, e);
+                outcomes.push(batch::Outcome::Failed(elapsed));
+                if !keep_going {
+                    outcomes.resize(steps.len(), batch::Outcome::Skipped);
+                    break;
+                }
+            }
+        }
+    }
+
+    let failed = outcomes
+        .iter()
+        .filter(|o| matches!(o, batch::Outcome::Failed(_)))
+        .count();
+    let icon = match failed {
+        0 => "✓".green(),
+        _ => "✗".red(),
+    };
+    println!("{} Batch: {}", icon, batch::summary(&outcomes));
+    if failed > 0 {
+        anyhow::bail!("{} of {} batch step(s) failed", failed, steps.len());
+    }
+    Ok(())
+}
+
+/// Handle the overview command
+fn handle_overview(
+    targets: &[target::Target],
+    working_dir: &std::path::Path,
+    json_output: bool,
+) -> Result<()> {
+    let overview = overview::Overview::build(targets);
+
+    if json_output {
+        print_json(&overview).context("Failed to serialize overview to JSON")?;
+    } else {
+        overview::render(&overview, working_dir);
+    }
+
+    Ok(())
+}
+
+/// Handle the diff command: the targets added, removed, or changed since
+/// `git_ref` in the working directory's Makefiles
+fn handle_diff(
+    git_ref: &str,
+    working_dir: &std::path::Path,
+    cli: &Cli,
+    config: &Config,
+) -> Result<()> {
+    let options = ParseOptions {
+        include_private: cli.all,
+        include_patterns: cli.patterns,
+        include_generated: cli.generated,
+    };
+    let names = makefile_names(cli, config);
+    let makefiles = discovered_makefiles(&[working_dir.to_path_buf()], cli, config)?;
+    let search = cli.file.is_none().then_some(cli.recursive);
+    let changes = diff::compare(git_ref, working_dir, &makefiles, search, &names, &options)?;
+
+    if cli.json {
+        print_json(&changes).context("Failed to serialize target changes to JSON")?;
+    } else {
+        diff::render(&changes, git_ref);
+    }
+    Ok(())
+}
+
+/// Handle the query command: the matching targets, always as JSON
+fn handle_query(
+    filter: &[String],
+    targets: &[target::Target],
+    working_dir: &std::path::Path,
+) -> Result<()> {
+    let query = query::Query::parse(filter)?;
+    let matching = query.filter(targets, working_dir);
+    print_json(&matching).context("Failed to serialize targets to JSON")?;
+    Ok(())
+}
+
+/// The file names taken for Makefiles: those given with --makefile-name, or
+/// else the config's `makefile_names`
+fn makefile_names(cli: &Cli, config: &Config) -> Vec<String> {
+    if cli.makefile_names.is_empty() {
+        config.makefile_names.clone()
+    } else {
+        cli.makefile_names.clone()
+    }
+}
+
+/// The Makefiles `--file`, or discovery in each project root, turns up
+fn discovered_makefiles(roots: &[PathBuf], cli: &Cli, config: &Config) -> Result<Vec<PathBuf>> {
+    match cli.file {
+        Some(ref file) => Ok(vec![file.clone()]),
+        None => {
+            let names = makefile_names(cli, config);
+            let mut makefiles = Vec::new();
+            for root in roots {
+                makefiles.extend(scan::find_makefiles(root, cli.recursive, &names)?);
+            }
+            Ok(makefiles)
+        }
+    }
+}
+
+/// Handle the providers command: what each provider found in every root,
+/// highest priority first
+fn handle_providers(roots: &[PathBuf], cli: &Cli, config: &Config) -> Result<()> {
+    let mut reports = Vec::new();
+    for root in roots {
+        let discoveries = discover(cli, root, &ParseOptions::everything(), config, true)?;
+        reports.extend(providers::report(root, &discoveries, &config.providers));
+    }
+
+    if cli.json {
+        print_json(&reports).context("Failed to serialize providers to JSON")?;
+        return Ok(());
+    }
+
+    println!("{}", "Providers, highest priority first:".bold());
+    for (i, report) in reports.iter().enumerate() {
+        if roots.len() > 1 && (i == 0 || reports[i - 1].root != report.root) {
+            println!("{}", report.root.display().to_string().blue());
+        }
+
+        let name = format!("{:<9}", report.provider.name());
+        let files = report
+            .files
+            .iter()
+            .map(|f| {
+                f.strip_prefix(&report.root)
+                    .unwrap_or(f)
+                    .display()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        if report.files.is_empty() {
+            let status = if report.enabled {
+                "not found"
+            } else {
+                "disabled"
+            };
+            println!("  {} {}", name.dimmed(), status.dimmed());
+        } else if !report.enabled {
+            println!(
+                "  {} {}  {}",
+                name.dimmed(),
+                "disabled".yellow(),
+                files.dimmed()
+            );
+        } else {
+            let mut count = format!("{} target(s)", report.contributed);
+            if report.contributed < report.targets {
+                count.push_str(&format!(
+                    " ({} shadowed)",
+                    report.targets - report.contributed
+                ));
+            }
+            println!(
+                "  {} {}  {}  {}",
+                name.green(),
+                count,
+                format!("{:.1} ms", report.duration_ms).dimmed(),
+                files.dimmed()
+            );
+        }
+        for warning in &report.warnings {
+            println!("    {} {}", "warning:".yellow(), warning);
+        }
+    }
+
+    Ok(())
+}
+
+/// Handle the open command: open (or reveal) the Makefile, asking which one
+/// when several are found
+fn handle_open(reveal: bool, roots: &[PathBuf], cli: &Cli, config: &Config) -> Result<()> {
+    let makefiles = discovered_makefiles(roots, cli, config)?;
+    let base_dir = cli.working_dir();
+
+    let makefile = match makefiles.as_slice() {
+        [] => {
+            let dir = roots.first().unwrap_or(&base_dir);
+            return Err(MakiError::no_makefile(dir).into());
+        }
+        [only] => only.clone(),
+        several => {
+            let labels: Vec<String> = several
+                .iter()
+                .map(|p| p.strip_prefix(&base_dir).unwrap_or(p).display().to_string())
+                .collect();
+            let selected = match cli.picker {
+                PickerKind::Skim => fuzzy::select_from("Makefiles", &labels)?,
+                PickerKind::Plain => plain::select_from("Makefiles", &labels)?,
+            };
+            let Some(index) = selected else {
+                println!("{}", "No Makefile selected.".yellow());
+                return Ok(());
+            };
+            several[index].clone()
+        }
+    };
+
+    if reveal {
+        editor::reveal(&makefile)
+    } else {
+        editor::open(&makefile, 1)
+    }
+}
+
+/// Handle `maki refactor rename`: show the diff, then rewrite the Makefiles
+fn handle_rename(
+    old: &str,
+    new: &str,
+    yes: bool,
+    roots: &[PathBuf],
+    cli: &Cli,
+    config: &Config,
+) -> Result<()> {
+    let makefiles = discovered_makefiles(roots, cli, config)?;
+    // Each Makefile's own rules, where the listed targets keep only the first
+    // of a name
+    let mut rules = Vec::new();
+    for path in &makefiles {
+        rules.extend(makefile::parse_makefile(path, &ParseOptions::everything())?);
+    }
+    // `sub/Makefile::build` names the one of several Makefiles' `build`
+    let target = match lookup::resolve(old, &rules, &cli.working_dir())? {
+        Some(target) if target.name == lookup::TargetName::parse(old).name() => target,
+        _ => return Err(not_found(old, &rules).into()),
+    };
+    let old = target.name.as_str();
+    if !refactor::is_valid_name(new) {
+        anyhow::bail!("Invalid target name: {}", new);
+    }
+
+    // The Makefile make reads for the target, and the fragments it includes
+    let top = |t: &target::Target| t.included_from.clone().unwrap_or_else(|| t.file.clone());
+    let makefile = top(target);
+    let mut files = vec![makefile.clone()];
+    for rule in rules.iter().filter(|t| top(t) == makefile) {
+        if !files.contains(&rule.file) {
+            files.push(rule.file.clone());
+        }
+    }
+    let definition = refactor::Definition { makefile, files };
+    if rules
+        .iter()
+        .any(|t| t.name == new && definition.files.contains(&t.file))
+    {
+        anyhow::bail!("Target {} already exists", new);
+    }
+
+    let edits = refactor::rename(&makefiles, &definition, old, new)?;
+
+    for edit in &edits {
+        print_diff(edit, cli);
+    }
+
+    if cli.dry_run {
+        println!("{}", "Dry run: no files were changed.".yellow());
+        return Ok(());
+    }
+
+    let message = format!("Rename {} to {} in {} file(s)?", old, new, edits.len());
+    let confirmed = yes
+        || match cli.picker {
+            PickerKind::Skim => prompt::confirm(&message)?,
+            PickerKind::Plain => plain::confirm(&message)?,
+        };
+    if !confirmed {
+        println!("{}", "No files were changed.".yellow());
+        return Ok(());
+    }
+
+    let mut cache = Cache::load_with_policy(config.cache.clone())
+        .unwrap_or_else(|_| Cache::with_policy(config.cache.clone()));
+    for edit in &edits {
+        edit.apply()?;
+        cache.invalidate(&edit.path);
+    }
+    let _ = cache.save();
+
+    println!(
+        "{} Renamed {} to {} in {} file(s)",
+        "✓".green(),
+        old.bold(),
+        new.bold(),
+        edits.len()
+    );
+    Ok(())
+}
+
+/// Print `edit` as a colored unified diff
+fn print_diff(edit: &refactor::FileEdit, cli: &Cli) {
+    for line in edit.diff(&cli.working_dir()).lines() {
+        if line.starts_with("+++") || line.starts_with("---") {
+            println!("{}", line.bold());
+        } else if line.starts_with("@@") {
+            println!("{}", line.cyan());
+        } else if line.starts_with('+') {
+            println!("{}", line.green());
+        } else if line.starts_with('-') {
+            println!("{}", line.red());
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Handle the validate command: list what each Makefile should fix, or with
+/// `fix` show the fixes and write them once confirmed
+fn handle_validate(
+    fix: bool,
+    yes: bool,
+    roots: &[PathBuf],
+    cli: &Cli,
+    config: &Config,
+) -> Result<()> {
+    let makefiles = discovered_makefiles(roots, cli, config)?;
+    if makefiles.is_empty() {
+        return Err(MakiError::no_makefile(&cli.working_dir()).into());
+    }
+    let mut validations = Vec::new();
+    for makefile in &makefiles {
+        validations.push(validate::validate(makefile)?);
+    }
+    let findings: Vec<&validate::Finding> = validations.iter().flat_map(|v| &v.findings).collect();
+
+    if cli.json && !fix {
+        print_json(&findings).context("Failed to serialize findings to JSON")?;
+    } else if !fix {
+        let base = cli.working_dir();
+        for finding in &findings {
+            let file = finding.file.strip_prefix(&base).unwrap_or(&finding.file);
+            let location = format!("{}:{}:", file.display(), finding.line);
+            println!("{} {}", location.bold(), finding.message);
+        }
+    }
+    if findings.is_empty() {
+        if !cli.json {
+            println!(
+                "{} Nothing to fix in {} Makefile(s)",
+                "✓".green(),
+                makefiles.len()
+            );
+        }
+        return Ok(());
+    }
+    if !fix {
+        anyhow::bail!(
+            "{} problem(s) found; maki validate --fix fixes them",
+            findings.len()
+        );
+    }
+    if !yes && !cli.dry_run && !std::io::stdin().is_terminal() {
+        anyhow::bail!("No terminal to confirm the fixes on; pass --yes to write them");
+    }
+
+    let edits: Vec<&refactor::FileEdit> = validations
+        .iter()
+        .map(|v| &v.fix)
+        .filter(|edit| edit.updated != edit.original)
+        .collect();
+    for edit in &edits {
+        print_diff(edit, cli);
+    }
+    if cli.dry_run {
+        println!("{}", "Dry run: no files were changed.".yellow());
+        return Ok(());
+    }
+
+    let message = format!(
+        "Fix {} problem(s) in {} file(s)?",
+        findings.len(),
+        edits.len()
+    );
+    let confirmed = yes
+        || match cli.picker {
+            PickerKind::Skim => prompt::confirm(&message)?,
+            PickerKind::Plain => plain::confirm(&message)?,
+        };
+    if !confirmed {
+        println!("{}", "No files were changed.".yellow());
+        return Ok(());
+    }
+
+    let mut cache = Cache::load_with_policy(config.cache.clone())
+        .unwrap_or_else(|_| Cache::with_policy(config.cache.clone()));
+    for edit in &edits {
+        edit.apply()?;
+        cache.invalidate(&edit.path);
+    }
+    let _ = cache.save();
+
+    println!(
+        "{} Fixed {} problem(s) in {} file(s)",
+        "✓".green(),
+        findings.len(),
+        edits.len()
+    );
+    Ok(())
+}
+
+/// Print the detail view for a target, or its JSON with --json
+fn show_target(target: &target::Target, json_output: bool) -> Result<()> {
+    let detail = show::TargetDetail::build(target)?;
+
+    if json_output {
+        print_json(&detail).context("Failed to serialize target details to JSON")?;
+    } else {
+        show::render(&detail);
+    }
+
+    Ok(())
+}
+
+/// Handle the pick command (fuzzy finder)
+///
+/// `targets` is filtered by --all/--patterns; `all_targets` feeds the picker,
+/// which applies the same filters but lets them be toggled live.
+fn handle_pick(
+    targets: &[target::Target],
+    all_targets: &[target::Target],
+    cli: &Cli,
+    config: &Config,
+    env: &[EnvVar],
+) -> Result<()> {
+    if cli.no_ui || cli.json {
+        return handle_list(targets, cli.json, cli.with_deps, false, false);
+    }
+
+    let action = cli.pick_action();
+
+    // A lone target doesn't need a picker
+    if action == PickAction::Run
+        && targets.len() == 1
+        && config.single_target_shortcut
+        && !cli.split
+    {
+        return handle_single_target(&targets[0], cli, env);
+    }
+
+    if action == PickAction::Run && cli.split {
+        return handle_pick_split(all_targets, cli, config, env);
+    }
+    if action == PickAction::Queue {
+        return handle_pick_queue(all_targets, cli, config);
+    }
+
+    let selected = select_target(all_targets, &picker_options(cli, config), cli)?;
+
+    let Some(target) = selected else {
+        println!("{}", "No target selected.".yellow());
+        return Ok(());
+    };
+
+    act_on_target(&target, action, cli, env)
+}
+
+/// Run, edit, show, or copy a target chosen in a picker
+fn act_on_target(
+    target: &target::Target,
+    action: PickAction,
+    cli: &Cli,
+    env: &[EnvVar],
+) -> Result<()> {
+    match action {
+        PickAction::Run => run_selected_target(target, cli, env),
+        PickAction::Edit => editor::open(&target.file, target.line),
+        PickAction::Show => show_target(target, false),
+        PickAction::Copy => copy_target_command(target, cli, env),
+        PickAction::Queue => queue_target(&target.name, &[], &cli.working_dir()),
+    }
+}
+
+/// Handle the suggest command: a picker of targets relevant to the git changes
+fn handle_suggest(
+    targets: &[target::Target],
+    cli: &Cli,
+    config: &Config,
+    env: &[EnvVar],
+) -> Result<()> {
+    let roots: std::collections::BTreeSet<PathBuf> = targets
+        .iter()
+        .map(|t| t.root.clone().unwrap_or_else(|| cli.working_dir()))
+        .collect();
+    let mut changed = Vec::new();
+    for root in &roots {
+        for path in suggest::changed_files(root)? {
+            // Roots in the same repository report the same changes
+            if !changed.contains(&path) {
+                changed.push(path);
+            }
+        }
+    }
+    let suggestions = suggest::suggest(targets, &changed);
+
+    if cli.json {
+        print_json(&suggestions).context("Failed to serialize suggestions to JSON")?;
+        return Ok(());
+    }
+
+    if changed.is_empty() {
+        println!("{}", "No changed files.".yellow());
+        return Ok(());
+    }
+    if suggestions.is_empty() {
+        println!(
+            "{}",
+            format!(
+                "No targets reference the {} changed file(s).",
+                changed.len()
+            )
+            .yellow()
+        );
+        return Ok(());
+    }
+
+    if cli.no_ui {
+        let width = suggestions
+            .iter()
+            .map(|s| s.target.name.len())
+            .max()
+            .unwrap_or(0);
+        for suggestion in &suggestions {
+            let name = format!("{:<width$}", suggestion.target.name, width = width);
+            println!(
+                "  {}  {}",
+                name.green(),
+                suggestion.reasons.join(", ").dimmed()
+            );
+        }
+        return Ok(());
+    }
+
+    let suggested: Vec<target::Target> = suggestions.into_iter().map(|s| s.target).collect();
+    let picker_options = PickerOptions {
+        header: Some(format!("Suggested for {} changed file(s)", changed.len())),
+        // Suggestions are already filtered: show all of them
+        include_private: true,
+        include_patterns: true,
+        include_generated: true,
+        // ... in order of relevance
+        rank: RankMode::PureFuzzy,
+        frecency: Frecency::default(),
+        show_paths: config.picker.paths,
+        dir: cli.working_dir(),
+        show_silent: cli.show_silent,
+        theme: Some(config.picker.theme.clone()),
+    };
+
+    let Some(target) = select_target(&suggested, &picker_options, cli)? else {
+        println!("{}", "No target selected.".yellow());
+        return Ok(());
+    };
+
+    act_on_target(&target, cli.pick_action(), cli, env)
+}
+
+/// Copy the command that would run a target, prompting for its variables
+fn copy_target_command(target: &target::Target, cli: &Cli, env: &[EnvVar]) -> Result<()> {
+    let variables = prompt_variables(target, cli, env)?;
+    let exec_options = exec_options(cli, env, Some(target), variables);
+    let command = executor::command_line(&target.name, &exec_options);
+
+    let copied = clipboard::copy(&command)?;
+    println!("{} {}", "Copied:".green(), command);
+    if copied == clipboard::Copied::Terminal {
+        println!(
+            "{}",
+            "  No clipboard utility found, so it was sent to the terminal (OSC 52), \
+             which copies it if clipboard access is allowed"
+                .dimmed()
+        );
+    }
+
+    Ok(())
+}
+
+/// Initial picker state from the command line flags and `[picker]` config
+fn picker_options(cli: &Cli, config: &Config) -> PickerOptions {
+    let rank = cli.rank.unwrap_or(config.picker.rank);
+    let frecency = match rank {
+        RankMode::PureFuzzy => Frecency::default(),
+        _ => Frecency::load(&cli.working_dir(), config.picker.half_life_days)
+            .with_team_usage(&project_roots(cli, config, &cli.working_dir())),
+    };
+    PickerOptions {
+        include_private: cli.all,
+        include_patterns: cli.patterns,
+        include_generated: cli.generated,
+        rank,
+        frecency,
+        show_paths: config.picker.paths,
+        dir: cli.working_dir(),
+        show_silent: cli.show_silent,
+        theme: Some(config.picker.theme.clone()),
+        ..Default::default()
+    }
+}
+
+/// Open the picker chosen with --picker
+fn select_target(
+    targets: &[target::Target],
+    picker_options: &PickerOptions,
+    cli: &Cli,
+) -> Result<Option<target::Target>> {
+    match cli.picker {
+        PickerKind::Skim => fuzzy::select_target_with_preview(targets, picker_options),
+        PickerKind::Plain => plain::select_target(targets, picker_options),
+    }
+}
+
+/// Run the only available target, confirming first unless --auto is given
+fn handle_single_target(target: &target::Target, cli: &Cli, env: &[EnvVar]) -> Result<()> {
+    let confirmed = cli.auto
+        || match cli.picker {
+            PickerKind::Skim => {
+                prompt::confirm(&format!("Run the only target, {}?", target.name.bold()))?
+            }
+            PickerKind::Plain => plain::confirm(&format!("Run the only target, {}?", target.name))?,
+        };
+
+    if !confirmed {
+        println!("{}", "No target selected.".yellow());
+        return Ok(());
+    }
+
+    run_selected_target(target, cli, env)
+}
+
+/// Warn before running a target that will ask for a password, or refuse to
+/// with --no-sudo
+fn check_sudo(target: &target::Target, cli: &Cli) -> Result<()> {
+    if !target.needs_sudo {
+        return Ok(());
+    }
+    if cli.no_sudo {
+        anyhow::bail!(
+            "Target {} runs sudo; not running it because of --no-sudo",
+            target.name
+        );
+    }
+    // stderr, so it doesn't end up in --json output
+    eprintln!(
+        "{} {} runs sudo and will ask for your password",
+        "⚠".yellow(),
+        target.name.bold()
+    );
+    Ok(())
+}
+
+/// Ask before running a target at a time one of its guards says it
+/// shouldn't run, showing the rule; --force runs it anyway
+fn check_guards(target: &target::Target, cli: &Cli) -> Result<()> {
+    if cli.dry_run {
+        return Ok(());
+    }
+    let now = chrono::Local::now().naive_local();
+    let rules = guard::violations(&target.guards, now);
+    if rules.is_empty() {
+        return Ok(());
+    }
+    // stderr, so it doesn't end up in --json output
+    for rule in &rules {
+        eprintln!(
+            "{} {}: {} (it's {})",
+            "⚠".yellow(),
+            target.name.bold(),
+            rule,
+            now.format("%A %H:%M")
+        );
+    }
+    if cli.force {
+        return Ok(());
+    }
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!(
+            "Not running {} now without --force: {}",
+            target.name,
+            rules.join("; ")
+        );
+    }
+
+    let message = format!("Run {} anyway?", target.name);
+    let confirmed = match cli.picker {
+        PickerKind::Skim => prompt::confirm(&message)?,
+        PickerKind::Plain => plain::confirm(&message)?,
+    };
+    if !confirmed {
+        anyhow::bail!("Not running {}", target.name);
+    }
+    Ok(())
+}
+
+/// Prompt for variables and run a target chosen interactively
+fn run_selected_target(target: &target::Target, cli: &Cli, env: &[EnvVar]) -> Result<()> {
+    println!("{} {}", "Selected:".green(), target.name.bold());
+    check_sudo(target, cli)?;
+    check_guards(target, cli)?;
+
+    let variables = prompt_variables(target, cli, env)?;
+    let exec_options = exec_options(cli, env, Some(target), variables);
+
+    let run = RunTimer::start();
+    let status = executor::execute_target(&target.name, &exec_options)?;
+    if !cli.dry_run {
+        run.record(&target.name, &exec_options, &status, None);
+    }
+
+    if !status.success() {
+        return Err(MakiError::exec_failed(&target.name, status).into());
+    }
+
+    Ok(())
+}
+
+/// The run's environment with what the `.env` and `.envrc` next to its
+/// Makefile set, saying on stderr which file set which variables
+fn auto_env(cli: &Cli, env: &[EnvVar], target: Option<&target::Target>, cwd: &Path) -> Vec<EnvVar> {
+    let makefile = cli.file.as_deref().or(target.map(|t| t.makefile()));
+    let dir = auto_env_dir(makefile, cwd);
+    let loaded = match env::load_auto_env(&dir) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{} {:#}", "⚠".yellow(), e);
+            return env.to_vec();
+        }
+    };
+
+    let mut env = env.to_vec();
+    for file in loaded {
+        if file.vars.is_empty() {
+            continue;
+        }
+        let names: Vec<&str> = file.vars.iter().map(|(name, _)| name.as_str()).collect();
+        eprintln!(
+            "{} {} from {}",
+            "Env:".cyan(),
+            names.join(", "),
+            file.path.display()
+        );
+        env = env::with_auto_env(&env, &file.vars);
+    }
+    env
+}
+
+/// The directory of `makefile`, as it is named from where maki started, or
+/// else of the run
+fn auto_env_dir(makefile: Option<&Path>, cwd: &Path) -> PathBuf {
+    let dir = match makefile.and_then(Path::parent) {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => cwd,
+    };
+    std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf())
+}
+
+/// Environment for a run, from the config, --env-file, and --var
+fn run_env(cli: &Cli, config: &Config) -> Result<Vec<EnvVar>> {
+    let env_file = match cli.env_file {
+        Some(ref path) => env::load_env_file(path)?,
+        None => Vec::new(),
+    };
+
+    Ok(env::collect(&config.env, &env_file, &cli.vars))
+}
+
+/// A fresh directory for the run's temporary files, with --scratch
+///
+/// Runs that go on after maki exits (--split, --detach, --wait-ready) keep
+/// theirs, which would otherwise be removed from under them.
+fn scratch_dir(cli: &Cli) -> Result<Option<scratch::Scratch>> {
+    if !cli.scratch || cli.dry_run {
+        return Ok(None);
+    }
+    let detached = matches!(
+        cli.command,
+        Some(Commands::Run { detach: true, .. })
+            | Some(Commands::Run {
+                wait_ready: true,
+                ..
+            })
+    );
+    let scratch = scratch::Scratch::create(cli.keep_scratch || cli.split || detached)?;
+    if scratch.is_kept() {
+        // stderr, so it doesn't end up in --json output
+        eprintln!(
+            "{} {}",
+            "Scratch directory (kept):".cyan(),
+            scratch.path().display()
+        );
+    }
+    Ok(Some(scratch))
+}
+
+/// Variables for a target of `maki run`: the `NAME=VALUE` words given after
+/// it, and answers for the required ones still missing
+fn run_variables(
+    target: Option<&target::Target>,
+    cli: &Cli,
+    env: &[EnvVar],
+) -> Result<Vec<(String, String)>> {
+    complete_variables(target, cli.run_vars(), cli, env)
+}
+
+/// `values`, and answers for the target's required variables that neither
+/// they nor the run's environment set
+fn complete_variables(
+    target: Option<&target::Target>,
+    mut values: Vec<(String, String)>,
+    cli: &Cli,
+    env: &[EnvVar],
+) -> Result<Vec<(String, String)>> {
+    if let Some(t) = target {
+        let missing = t
+            .required_vars
+            .iter()
+            .filter(|var| !set_by_environment(t, &var.name, env))
+            .filter(|var| !values.iter().any(|(name, _)| *name == var.name))
+            .cloned()
+            .collect();
+        values.extend(prompt_values(
+            &t.name,
+            missing,
+            &recipe::Recipe::of(t),
+            cli,
+        )?);
+    }
+    Ok(values)
+}
+
+/// Prompt for the target's required variables that the run's environment
+/// doesn't already set
+fn prompt_variables(
+    target: &target::Target,
+    cli: &Cli,
+    env: &[EnvVar],
+) -> Result<Vec<(String, String)>> {
+    let missing: Vec<_> = target
+        .required_vars
+        .iter()
+        .filter(|var| !set_by_environment(target, &var.name, env))
+        .cloned()
+        .collect();
+
+    prompt_values(&target.name, missing, &recipe::Recipe::of(target), cli)
+}
+
+/// Whether the run's environment sets `name`: maki sets it (`[env]`,
+/// `--env-file`, `--var`), or the Makefile exports it and maki's own
+/// environment gives it a value
+fn set_by_environment(target: &target::Target, name: &str, env: &[EnvVar]) -> bool {
+    env.iter().any(|e| e.name == name)
+        || (target.exported.iter().any(|n| n == name)
+            && std::env::var_os(name).is_some_and(|value| !value.is_empty()))
+}
+
+/// Prompt for `missing`, the variables of the target or alias `name`, or with
+/// --defaults only for those without a default, showing where `recipe` uses
+/// each
+///
+/// Secrets with a command are read from it instead, except in a dry run,
+/// which only shows them masked.
+fn prompt_values(
+    name: &str,
+    missing: Vec<target::RequiredVar>,
+    recipe: &recipe::Recipe,
+    cli: &Cli,
+) -> Result<Vec<(String, String)>> {
+    let (resolved, missing): (Vec<_>, Vec<_>) = missing
+        .into_iter()
+        .partition(|var| var.secret.as_ref().is_some_and(|s| s.command.is_some()));
+    let mut secrets = Vec::new();
+    for var in resolved {
+        let command = var.secret.and_then(|s| s.command).unwrap_or_default();
+        let value = match cli.dry_run {
+            true => executor::MASK.to_string(),
+            false => secret::resolve(&var.name, &command)?,
+        };
+        secrets.push((var.name, value));
+    }
+
+    // With --defaults, only variables without a default are asked for
+    let (mut values, missing): (Vec<(String, String)>, Vec<_>) = if cli.defaults {
+        let (defaulted, missing): (Vec<_>, Vec<_>) =
+            missing.into_iter().partition(|var| var.default.is_some());
+        let values = defaulted
+            .into_iter()
+            .map(|var| (var.name, var.default.unwrap_or_default()))
+            .collect();
+        (values, missing)
+    } else {
+        (Vec::new(), missing)
+    };
+    values.extend(secrets);
+
+    if missing.is_empty() {
+        return Ok(values);
+    }
+
+    values.extend(match cli.picker {
+        PickerKind::Skim => prompt::prompt_for_variables(name, &missing, recipe)?,
+        PickerKind::Plain => plain::prompt_for_variables(name, &missing, recipe)?,
+    });
+    Ok(values)
+}
+
+/// Execution options shared by every way of running a target
+///
+/// The target (if it is known) supplies the provider, and its project root
+/// when targets were gathered from several.
+fn exec_options(
+    cli: &Cli,
+    env: &[EnvVar],
+    target: Option<&target::Target>,
+    variables: Vec<(String, String)>,
+) -> ExecuteOptions {
+    // Startup is over once a target is about to run; --json puts the
+    // timings in its output instead
+    if !cli.json {
+        report_timings();
+    }
+    let mut cwd = target
+        .and_then(|t| t.root.clone())
+        .unwrap_or_else(|| cli.working_dir());
+    // A target from a Makefile below the project, found with -r, runs where
+    // that Makefile is, as `make -C` would run it
+    if cli.file.is_none()
+        && let Some(t) = target
+        && t.provider == target::Provider::Make
+        && let Some(dir) = t.makefile().parent()
+        && dir != cwd
+        && dir.starts_with(&cwd)
+    {
+        cwd = dir.to_path_buf();
+    }
+    let env = match cli.auto_env {
+        true => auto_env(cli, env, target, &cwd),
+        false => env.to_vec(),
+    };
+    ExecuteOptions {
+        provider: target.map(|t| t.provider).unwrap_or_default(),
+        dry_run: cli.dry_run,
+        print_cmd: true,
+        makefile: cli
+            .file
+            .clone()
+            .or_else(|| target.and_then(|t| makefile_flag(t, &cwd))),
+        cwd: Some(cwd),
+        include_dirs: cli.include_dirs.clone(),
+        mode: MakeMode::Run,
+        assume_old: Vec::new(),
+        variables,
+        secrets: target.map(|t| t.secret_names()).unwrap_or_default(),
+        env,
+        verbose: cli.verbose,
+        log_dir: cli.log_dir.clone(),
+        heartbeat: cli.heartbeat.map(std::time::Duration::from_secs),
+        quiet_noop: cli.quiet_noop,
+        artifacts: target.map(|t| t.artifacts.clone()).unwrap_or_default(),
+        errors: cli.errors,
+        // Only a dry run lists the recipe, so only it reads it
+        plan: match target {
+            Some(t) if cli.dry_run => makefile::target_plan(t),
+            _ => Vec::new(),
+        },
+        show_silent: cli.show_silent,
+        webhooks: match cli.no_webhooks {
+            true => Vec::new(),
+            false => target.map(|t| t.webhooks.clone()).unwrap_or_default(),
+        },
+        record: match cli.record {
+            true => record::recordings_dir(cli.log_dir.as_deref()),
+            false => None,
+        },
+        pty: cli.pty,
+    }
+}
+
+/// The Makefile to pass with -f for `target` when make wouldn't read it on
+/// its own, like a `tasks.mk` found through `makefile_names`
+fn makefile_flag(target: &target::Target, cwd: &Path) -> Option<PathBuf> {
+    let makefile = target.makefile();
+    let own = makefile::MAKEFILE_NAMES.map(String::from);
+    if target.provider != target::Provider::Make
+        || makefile::is_makefile(&makefile.to_string_lossy(), &own)
+    {
+        return None;
+    }
+    Some(makefile.strip_prefix(cwd).unwrap_or(makefile).to_path_buf())
+}
+
+/// Keep the picker open, launching each selected target in a new pane
+fn handle_pick_split(
+    targets: &[target::Target],
+    cli: &Cli,
+    config: &Config,
+    env: &[EnvVar],
+) -> Result<()> {
+    let mux = split::Multiplexer::detect()
+        .context("--split requires running inside a tmux or zellij session")?;
+    let mut runs = Vec::new();
+
+    loop {
+        let picker_options = PickerOptions {
+            header: split::status_header(&runs),
+            ..picker_options(cli, config)
+        };
+
+        let Some(target) = select_target(targets, &picker_options, cli)? else {
+            break;
+        };
+
+        // Refusing one target shouldn't end the session
+        if let Err(e) = check_sudo(&target, cli).and_then(|_| check_guards(&target, cli)) {
+            eprintln!("{} {}", "error:".red().bold(), e);
+            continue;
+        }
+        let variables = prompt_variables(&target, cli, env)?;
+        let exec_options = exec_options(cli, env, Some(&target), variables);
+
+        if cli.dry_run {
+            executor::execute_target(&target.name, &exec_options)?;
+            continue;
+        }
+
+        if cli.verbose {
+            env::print_diff(env);
+        }
+
+        let command = executor::build_command_with_env(&target.name, &exec_options);
+        runs.push(split::launch(
+            mux,
+            &target.name,
+            &command,
+            &run_dir(&exec_options),
+        )?);
+    }
+
+    for run in &runs {
+        println!("  {}", run.label());
+    }
+
+    Ok(())
+}
+
+/// Pick targets one after another, adding each to the run queue, until the
+/// picker is closed
+fn handle_pick_queue(targets: &[target::Target], cli: &Cli, config: &Config) -> Result<()> {
+    let dir = cli.working_dir();
+    let mut queue = queue::Queue::load()?;
+    let mut added = 0;
+
+    loop {
+        let picker_options = PickerOptions {
+            header: queue::status_header(queue.items(&dir)),
+            ..picker_options(cli, config)
+        };
+
+        let Some(target) = select_target(targets, &picker_options, cli)? else {
+            break;
+        };
+        if queue.push(&dir, queue::QueuedTarget::new(&target.name, &[])) {
+            queue.save()?;
+            added += 1;
+        }
+    }
+
+    if added == 0 {
+        println!("{}", "No target queued.".yellow());
+    } else {
+        println!(
+            "{} Queued {} target(s); run them with maki queue run",
+            "✓".green(),
+            added
+        );
+    }
+    Ok(())
+}
+
+/// The directory a target runs in
+fn run_dir(exec_options: &ExecuteOptions) -> PathBuf {
+    exec_options
+        .cwd
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// When a run started, for recording it in the history
+struct RunTimer {
+    started_at: u64,
+    started: Instant,
+}
+
+impl RunTimer {
+    fn start() -> Self {
+        Self {
+            started_at: history::now_secs(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Record the finished run; like the cache, history is best-effort
+    fn record(
+        &self,
+        target: &str,
+        exec_options: &ExecuteOptions,
+        status: &ExitStatus,
+        repeat: Option<repeat::RepeatSummary>,
+    ) {
+        record_run(
+            target,
+            exec_options,
+            status,
+            self.started_at,
+            self.started.elapsed(),
+            repeat,
+        );
+    }
+}
+
+/// Add a finished run to the history, and post it to the target's webhooks;
+/// like the cache, history is best-effort
+fn record_run(
+    target: &str,
+    exec_options: &ExecuteOptions,
+    status: &ExitStatus,
+    started_at: u64,
+    duration: std::time::Duration,
+    repeat: Option<repeat::RepeatSummary>,
+) {
+    let entry = history::HistoryEntry {
+        target: target.to_string(),
+        command: executor::command_line(target, exec_options),
+        dir: exec_options.cwd.clone().unwrap_or_default(),
+        started_at,
+        duration_ms: duration.as_millis() as u64,
+        exit_code: status.code(),
+        success: status.success(),
+        repeat,
+    };
+    let _ = history::append(&entry);
+
+    if !exec_options.webhooks.is_empty() {
+        let summary = webhook::Summary {
+            target: entry.target,
+            success: entry.success,
+            exit_code: entry.exit_code,
+            duration_ms: entry.duration_ms,
+            host: webhook::host(),
+            dir: entry.dir,
+            command: executor::shown_command(target, exec_options),
+        };
+        webhook::notify(&exec_options.webhooks, &summary);
+    }
+}
+
+/// The names `maki run` was given as make knows them, with prefixes and
+/// `api:test` or `services/api/Makefile::test` forms resolved, and the
+/// targets to run them from: the one each name chose stands in for the
+/// others of the same name
+fn resolve_run_targets(
+    given: &[&str],
+    targets: &[target::Target],
+    roots: &[PathBuf],
+    cli: &Cli,
+    config: &Config,
+) -> Result<(Vec<String>, Vec<target::Target>)> {
+    let base = cli.working_dir();
+    let mut targets = targets.to_vec();
+    let mut names = Vec::new();
+    let mut hidden = None;
+    // `@3` is the third target of the last `maki list --numbered`
+    let numbering = match given.iter().any(|t| numbering::index(t).is_some()) {
+        true => numbering::Numbering::load()?,
+        false => numbering::Numbering::default(),
+    };
+    for &text in given {
+        let text = match numbering::index(text) {
+            Some(index) => numbering.target(&base, index)?,
+            None => text,
+        };
+        let name = lookup::TargetName::parse(text);
+        let found = match lookup::resolve(text, &targets, &base)? {
+            Some(found) => found.clone(),
+            // Maybe a file make knows how to build
+            None if matches!(name, lookup::TargetName::Plain(_)) => {
+                names.push(text.to_string());
+                continue;
+            }
+            // The list keeps the first of several targets with one name, so
+            // a qualified name may mean one it left out
+            None => {
+                if hidden.is_none() {
+                    hidden = Some(make_targets(roots, cli, config)?);
+                }
+                let hidden = hidden.as_deref().unwrap_or_default();
+                match (lookup::resolve(text, hidden, &base)?, name) {
+                    (Some(found), _) => found.clone(),
+                    // A Makefile the scan didn't read, like one below the
+                    // project without -r
+                    (None, lookup::TargetName::InFile { file, name }) => {
+                        target_in_file(&base.join(file), name, config)?
+                    }
+                    (None, _) if !cli.recursive => anyhow::bail!(
+                        "No target {} (pass -r to read the Makefiles below this one too)",
+                        text
+                    ),
+                    (None, _) => anyhow::bail!("No target {}", text),
+                }
+            }
+        };
+        targets.retain(|t| t.name != found.name);
+        names.push(found.name.clone());
+        targets.push(found);
+    }
+    Ok((names, targets))
+}
+
+/// Every target of the Makefiles in `roots`, with those a Makefile read
+/// earlier has one of the same name of
+fn make_targets(roots: &[PathBuf], cli: &Cli, config: &Config) -> Result<Vec<target::Target>> {
+    let names = makefile_names(cli, config);
+    let mut targets = Vec::new();
+    for root in roots {
+        let makefiles = match cli.file {
+            Some(ref file) => vec![file.clone()],
+            None => scan::find_makefiles(root, cli.recursive, &names)?,
+        };
+        for makefile in makefiles {
+            for mut target in makefile::parse_makefile(&makefile, &ParseOptions::everything())? {
+                if roots.len() > 1 {
+                    target.root = Some(root.clone());
+                }
+                targets.push(target);
+            }
+        }
+    }
+    config.apply_var_defaults(&mut targets);
+    config.apply_artifacts(&mut targets);
+    config.apply_webhooks(&mut targets);
+    config.apply_guards(&mut targets);
+    Ok(targets)
+}
+
+/// The target `name` names in the Makefile at `path`
+fn target_in_file(path: &Path, name: &str, config: &Config) -> Result<target::Target> {
+    if !path.is_file() {
+        anyhow::bail!("No Makefile at {}", path.display());
+    }
+    let mut targets = makefile::parse_makefile(path, &ParseOptions::everything())?;
+    config.apply_var_defaults(&mut targets);
+    config.apply_artifacts(&mut targets);
+    config.apply_webhooks(&mut targets);
+    config.apply_guards(&mut targets);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    match lookup::resolve(name, &targets, dir)? {
+        Some(found) => Ok(found.clone()),
+        None => anyhow::bail!("No target {} in {}", name, path.display()),
+    }
+}
+
+/// The target to run by name; a name not among the targets may still be a
+/// file make knows how to build
+fn find_run_target<'a>(
+    target_name: &str,
+    targets: &'a [target::Target],
+) -> Result<Option<&'a target::Target>> {
+    let matches: Vec<&target::Target> = targets.iter().filter(|t| t.name == target_name).collect();
+    if matches.len() > 1 {
+        let roots: Vec<String> = matches.iter().filter_map(|t| t.root_label()).collect();
+        anyhow::bail!(
+            "Target {} is defined in several project roots ({}); pass a single --cwd to pick one",
+            target_name,
+            roots.join(", ")
+        );
+    }
+    Ok(matches.first().copied())
+}
+
+/// Handle the run command
+fn handle_run(
+    target_name: &str,
+    targets: &[target::Target],
+    cli: &Cli,
+    env: &[EnvVar],
+    repeat: &RepeatArgs,
+    max_output: Option<usize>,
+    mode: MakeMode,
+) -> Result<()> {
+    // Find the target to check for required variables
+    let target = find_run_target(target_name, targets)?;
+    if mode != MakeMode::Run && target.is_some_and(|t| t.provider != target::Provider::Make) {
+        anyhow::bail!("--question and --touch only work with Makefile targets");
+    }
+    // Checking whether a target is up to date runs none of its recipe
+    if let Some(t) = target
+        && mode != MakeMode::Question
+    {
+        check_sudo(t, cli)?;
+        check_guards(t, cli)?;
+    }
+
+    // Prompt for required variables if any
+    let variables = run_variables(target, cli, env)?;
+
+    let mut exec_options = exec_options(cli, env, target, variables);
+
+    if mode != MakeMode::Run {
+        if cli.json || cli.split {
+            anyhow::bail!("--question and --touch can't be combined with --json or --split");
+        }
+        exec_options.mode = mode;
+        return handle_make_mode(target_name, &exec_options);
+    }
+
+    if cli.split && repeat.mode().is_some() {
+        anyhow::bail!("--split can't be combined with --until-failure or --until-success");
+    }
+
+    // Without make, a recipe that is plain shell can still be run
+    if let Some(t) = target
+        && t.provider == target::Provider::Make
+        && !cli.dry_run
+        && !cli.json
+        && !cli.split
+        && repeat.mode().is_none()
+        && !executor::check_make_available()
+    {
+        return handle_run_without_make(target_name, t, &exec_options, cli);
+    }
+
+    if cli.json {
+        return handle_run_json(target_name, &exec_options, cli, repeat, max_output);
+    }
+
+    if cli.split && !cli.dry_run {
+        let mux = split::Multiplexer::detect()
+            .context("--split requires running inside a tmux or zellij session")?;
+        if cli.verbose {
+            env::print_diff(env);
+        }
+
+        let command = executor::build_command_with_env(target_name, &exec_options);
+        split::launch(mux, target_name, &command, &run_dir(&exec_options))?;
+        return Ok(());
+    }
+
+    let run = RunTimer::start();
+
+    // A dry run never fails, so there is nothing to repeat
+    let (status, summary) = match repeat.mode() {
+        Some(mode) if !cli.dry_run => {
+            let mut last_status = ExitStatus::default();
+            let summary = repeat::repeat(target_name, mode, repeat.max_iterations, |_| {
+                last_status = executor::execute_target(target_name, &exec_options)?;
+                Ok(last_status.success())
+            })?;
+            repeat::print_summary(target_name, &summary);
+            (last_status, Some(summary))
+        }
+        _ => (executor::execute_target(target_name, &exec_options)?, None),
+    };
+
+    if !cli.dry_run {
+        run.record(target_name, &exec_options, &status, summary);
+    }
+
+    if !status.success() {
+        return Err(MakiError::exec_failed(target_name, status).into());
+    }
+
+    Ok(())
+}
+
+/// Offer to run `target`'s recipe with the shell, make not being installed,
+/// when the recipe is plain shell; otherwise fail with how to install make
+fn handle_run_without_make(
+    target_name: &str,
+    target: &target::Target,
+    exec_options: &ExecuteOptions,
+    cli: &Cli,
+) -> Result<()> {
+    let Some(commands) = fallback::plain_recipe(target) else {
+        return Err(MakiError::make_missing(target_name).into());
+    };
+    let shell = fallback::shell();
+
+    println!(
+        "{} make is not installed, but {}'s recipe is plain shell:",
+        "!".yellow(),
+        target_name.bold()
+    );
+    for command in &commands {
+        println!("    {}", command.text.replace('\n', "\n    ").dimmed());
+    }
+    println!(
+        "{}",
+        format!(
+            "  maki can run it with {} instead, one shell per line as make would, \
+             but won't check whether {} is up to date.",
+            shell, target_name
+        )
+        .dimmed()
+    );
+    println!(
+        "  {} {}",
+        "hint:".dimmed(),
+        diagnostic::install_hint("make").dimmed()
+    );
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!(
+            "Not running {} without make, as there is no terminal to confirm it on",
+            target_name
+        );
+    }
+
+    let message = format!("Run the recipe directly with {} (without make)?", shell);
+    let confirmed = match cli.picker {
+        PickerKind::Skim => prompt::confirm(&message)?,
+        PickerKind::Plain => plain::confirm(&message)?,
+    };
+    if !confirmed {
+        println!("{}", format!("{} was not run.", target_name).yellow());
+        return Ok(());
+    }
+
+    let run = RunTimer::start();
+    let status = fallback::run(target_name, &commands, &shell, exec_options)?;
+    run.record(target_name, exec_options, &status, None);
+    if !status.success() {
+        return Err(MakiError::exec_failed(target_name, status).into());
+    }
+    Ok(())
+}
+
+/// Start a target in the background, in a session `maki ps`, `maki attach`,
+/// and `maki stop` can find
+fn handle_run_detached(
+    target_name: &str,
+    targets: &[target::Target],
+    cli: &Cli,
+    env: &[EnvVar],
+    wait: Option<std::time::Duration>,
+) -> Result<()> {
+    if cli.json || cli.split {
+        anyhow::bail!("--detach can't be combined with --json or --split");
+    }
+    if cli.log_dir.is_some() || cli.heartbeat.is_some() || cli.quiet_noop || cli.errors {
+        anyhow::bail!(
+            "--log-dir, --heartbeat, --quiet-noop, and --errors don't apply with --detach; its output goes to the session's log"
+        );
+    }
+    let target = find_run_target(target_name, targets)?;
+    // A detached target has no terminal for sudo to ask for a password on
+    if let Some(t) = target
+        && t.needs_sudo
+        && !cli.dry_run
+    {
+        anyhow::bail!(
+            "Target {} runs sudo, which needs the terminal; run it without --detach",
+            target_name
+        );
+    }
+    let probe = match wait {
+        Some(_) => {
+            let Some(probe) = target.and_then(|t| t.ready.as_deref()) else {
+                anyhow::bail!(
+                    "Target {} has no @ready probe to wait for (e.g. `## @ready http://localhost:8080/health`)",
+                    target_name
+                );
+            };
+            Some(ready::Probe::parse(probe)?)
+        }
+        None => None,
+    };
+    let variables = run_variables(target, cli, env)?;
+    let exec_options = exec_options(cli, env, target, variables);
+
+    if cli.dry_run {
+        executor::execute_target(target_name, &exec_options)?;
+        if let Some(probe) = probe {
+            println!("{} {}", "Would wait for:".yellow(), probe);
+        }
+        return Ok(());
+    }
+    if cli.verbose {
+        env::print_diff(env);
+    }
+
+    let sessions = session::Sessions::open()?;
+    let session = sessions.start(target_name, &exec_options)?;
+    println!(
+        "{} {} in the background (session {}, pid {})",
+        "Started".green(),
+        target_name.bold(),
+        session.id,
+        session.pid
+    );
+    if let (Some(probe), Some(timeout)) = (probe, wait) {
+        wait_ready(&sessions, &session, &probe, timeout)?;
+    }
+    println!(
+        "  {}",
+        format!(
+            "maki attach {} to follow its output, maki stop {} to stop it",
+            session.id, session.id
+        )
+        .dimmed()
+    );
+    Ok(())
+}
+
+/// Wait for a detached target's `@ready` probe to answer; one that exits
+/// first has its output shown, and one still not ready in time is stopped
+fn wait_ready(
+    sessions: &session::Sessions,
+    session: &session::Session,
+    probe: &ready::Probe,
+    timeout: std::time::Duration,
+) -> Result<()> {
+    println!(
+        "{} {} (up to {})",
+        "Waiting for".dimmed(),
+        probe,
+        heartbeat::format_elapsed(timeout)
+    );
+    match ready::wait(probe, timeout, || session.is_running())? {
+        ready::Outcome::Ready(after) => {
+            println!(
+                "{} {} is ready after {}",
+                "✓".green(),
+                session.target.bold(),
+                heartbeat::format_elapsed(after)
+            );
+            Ok(())
+        }
+        ready::Outcome::Exited => {
+            let log = std::fs::read(sessions.log_path(session.id)).unwrap_or_default();
+            let _ = std::io::stderr().write_all(session::tail(&log, 20));
+            sessions.stop(session)?;
+            anyhow::bail!("{} exited before {} answered", session.target, probe);
+        }
+        ready::Outcome::TimedOut => {
+            sessions.stop(session)?;
+            anyhow::bail!(
+                "{} wasn't ready after {} ({} never answered); stopped it",
+                session.target,
+                heartbeat::format_elapsed(timeout),
+                probe
+            );
+        }
+    }
+}
+
+/// List the sessions started with `maki run --detach`
+fn handle_ps(json_output: bool) -> Result<()> {
+    let sessions = session::Sessions::open()?;
+    let list = sessions.list()?;
+
+    if json_output {
+        let statuses: Vec<session::SessionStatus> =
+            list.iter().map(|s| sessions.status(s)).collect();
+        print_json(&statuses).context("Failed to serialize sessions to JSON")?;
+        return Ok(());
+    }
+
+    if list.is_empty() {
+        println!("{}", "No targets running in the background.".yellow());
+        return Ok(());
+    }
+
+    let width = list.iter().map(|s| s.target.len()).max().unwrap_or(0);
+    for session in &list {
+        let status = if session.is_running() {
+            let uptime = history::now_secs().saturating_sub(session.started_at);
+            format!(
+                "{} {:<8}",
+                "●".green(),
+                heartbeat::format_elapsed(std::time::Duration::from_secs(uptime))
+            )
+        } else {
+            format!("{} {:<8}", "○".dimmed(), "exited".dimmed())
+        };
+        println!(
+            "  {:>3}  {}  {}  {}",
+            session.id,
+            format!("{:<width$}", session.target, width = width).bold(),
+            status,
+            session.dir.display().to_string().dimmed()
+        );
+    }
+
+    Ok(())
+}
+
+/// Print a session's latest output, then follow it
+fn handle_attach(id: &str, lines: usize, follow: bool) -> Result<()> {
+    let sessions = session::Sessions::open()?;
+    let session = sessions.find(id)?;
+    sessions.attach(&session, lines, follow, &mut std::io::stdout())?;
+
+    if !session.is_running() {
+        eprintln!(
+            "{}",
+            format!(
+                "{} (session {}) has exited; maki stop {} forgets it",
+                session.target, session.id, session.id
+            )
+            .dimmed()
+        );
+    }
+    Ok(())
+}
+
+/// Stop one session, or all of them
+fn handle_stop(id: Option<&str>, all: bool) -> Result<()> {
+    let sessions = session::Sessions::open()?;
+    let stopping = match id {
+        Some(id) if !all => vec![sessions.find(id)?],
+        _ => sessions.list()?,
+    };
+    if stopping.is_empty() {
+        println!("{}", "No targets running in the background.".yellow());
+    }
+
+    for session in &stopping {
+        let verb = if sessions.stop(session)? {
+            "Stopped"
+        } else {
+            "Forgot exited"
+        };
+        println!(
+            "{} {} (session {})",
+            verb.green(),
+            session.target.bold(),
+            session.id
+        );
+    }
+    Ok(())
+}
+
+/// Run an alias from the config: its placeholders are filled from the
+/// arguments or prompted for, then its targets run one after another until
+/// one fails
+fn handle_alias(
+    args: &[String],
+    targets: &[target::Target],
+    cli: &Cli,
+    config: &Config,
+    env: &[EnvVar],
+) -> Result<()> {
+    let (name, args) = args.split_first().context("No alias given")?;
+    let template = config
+        .aliases
+        .get(name)
+        .with_context(|| format!("Alias not found: {}", name))?;
+    if cli.json || cli.split {
+        anyhow::bail!("--json and --split can't be used with aliases");
+    }
+
+    let alias = alias::Alias::parse(name, template)?;
+    let mut values = alias.fill(args)?;
+    // An alias has no recipe of its own to show
+    let recipe = recipe::Recipe::default();
+    values.extend(prompt_values(name, alias.missing(&values), &recipe, cli)?);
+    let invocation = alias.invocation(&values);
+
+    // Ask for everything before anything runs
+    let mut runs = Vec::new();
+    for target_name in &invocation.targets {
+        let target = find_run_target(target_name, targets)?;
+        let mut variables = invocation.variables.clone();
+        if let Some(t) = target {
+            check_sudo(t, cli)?;
+            check_guards(t, cli)?;
+            // Variables the alias sets aren't asked for again
+            let mut t = t.clone();
+            t.required_vars
+                .retain(|var| !variables.iter().any(|(name, _)| *name == var.name));
+            variables.extend(prompt_variables(&t, cli, env)?);
+        }
+        runs.push((target_name, exec_options(cli, env, target, variables)));
+    }
+
+    for (target_name, exec_options) in runs {
+        let run = RunTimer::start();
+        let status = executor::execute_target(target_name, &exec_options)?;
+        if !cli.dry_run {
+            run.record(target_name, &exec_options, &status, None);
+        }
+        if !status.success() {
+            return Err(MakiError::exec_failed(target_name, status).into());
+        }
+    }
+    Ok(())
+}
+
+/// Run several targets, one after another until one fails or with
+/// --parallel as a graph alongside their prerequisites, showing their output
+/// as --output says
+fn handle_run_many(
+    names: &[&str],
+    targets: &[target::Target],
+    cli: &Cli,
+    env: &[EnvVar],
+    parallel: bool,
+    output: OutputMode,
+) -> Result<()> {
+    if cli.json || cli.split {
+        anyhow::bail!("--json and --split only work when running a single target");
+    }
+    if cli.log_dir.is_some() || cli.heartbeat.is_some() || cli.quiet_noop || cli.errors {
+        anyhow::bail!(
+            "--log-dir, --heartbeat, --quiet-noop, and --errors only work when running a single target"
+        );
+    }
+
+    // In parallel, what several targets need runs once, before all of them,
+    // and the rest as soon as it can
+    let steps = if parallel {
+        schedule::plan(names, targets)
+    } else {
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| schedule::Step {
+                target: name.to_string(),
+                needs: Vec::new(),
+                below: Vec::new(),
+                wanted_by: vec![i],
+            })
+            .collect()
+    };
+
+    for step in &steps {
+        let name = &step.target;
+        let target = find_run_target(name, targets)?;
+        // The targets' stdin is closed, so sudo couldn't ask for a password
+        if let Some(t) = target
+            && t.needs_sudo
+            && !cli.dry_run
+        {
+            anyhow::bail!(
+                "Target {} runs sudo, which needs the terminal; run it on its own",
+                name
+            );
+        }
+        if let Some(t) = target
+            && t.serial
+            && parallel
+            && !cli.dry_run
+        {
+            anyhow::bail!(
+                "Target {} must not run alongside others (.NOTPARALLEL or @serial in {}); \
+                 run the targets without --parallel",
+                name,
+                t.file.display()
+            );
+        }
+    }
+
+    let mut requested = Vec::new();
+    for &name in names {
+        requested.push(run_variables(find_run_target(name, targets)?, cli, env)?);
+    }
+
+    let mut jobs = Vec::new();
+    for step in steps {
+        let target = find_run_target(&step.target, targets)?;
+        // A prerequisite gets the variables of the targets it is run for
+        let variables = match names.iter().position(|&name| name == step.target) {
+            Some(i) => requested[i].clone(),
+            None => {
+                let mut variables: Vec<(String, String)> = Vec::new();
+                for var in step.wanted_by.iter().flat_map(|&i| &requested[i]) {
+                    if !variables.iter().any(|(name, _)| *name == var.0) {
+                        variables.push(var.clone());
+                    }
+                }
+                variables
+            }
+        };
+        let mut options = exec_options(cli, env, target, variables);
+        options.assume_old = step.below;
+        jobs.push(multirun::Job {
+            target: step.target,
+            options,
+            needs: step.needs,
+        });
+    }
+
+    if cli.dry_run {
+        for job in &jobs {
+            executor::execute_target(&job.target, &job.options)?;
+        }
+        return Ok(());
+    }
+    if cli.verbose {
+        env::print_diff(env);
+    }
+
+    let mode = output.resolve();
+    let states = match mode {
+        OutputMode::Dashboard => {
+            let mut dashboard = maki_cli::dashboard::Dashboard::start()
+                .context("--output dashboard needs a terminal")?;
+            multirun::run(&jobs, parallel, &mut dashboard)?
+        }
+        _ => multirun::run(&jobs, parallel, &mut multirun::Prefixed::new(&jobs))?,
+    };
+
+    for (job, state) in jobs.iter().zip(&states) {
+        if let multirun::JobStatus::Finished(status) = state.status {
+            record_run(
+                &job.target,
+                &job.options,
+                &status,
+                state.started_at.unwrap_or_default(),
+                state.duration.unwrap_or_default(),
+                None,
+            );
+        }
+    }
+
+    // The dashboard's screen is gone, so show how the failures ended
+    let tail = if mode == OutputMode::Dashboard { 20 } else { 0 };
+    println!();
+    for line in multirun::summary(&states, tail) {
+        println!("{}", line);
+    }
+
+    for state in &states {
+        match state.status {
+            multirun::JobStatus::Finished(status) if !status.success() => {
+                return Err(MakiError::exec_failed(&state.target, status).into());
+            }
+            multirun::JobStatus::Failed => {
+                anyhow::bail!("{}", state.last_line().unwrap_or("Failed to start"));
+            }
+            multirun::JobStatus::Stopped => {
+                anyhow::bail!("Stopped before every target finished");
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Check whether a target is up to date, or touch it, explaining the result
+///
+/// make's exit status is passed on (0 up to date, 1 out of date, 2 error for
+/// `--question`), so scripts can gate work on it.
+fn handle_make_mode(target_name: &str, exec_options: &ExecuteOptions) -> Result<()> {
+    let status = executor::execute_target(target_name, exec_options)?;
+    if exec_options.dry_run {
+        return Ok(());
+    }
+
+    match (exec_options.mode, status.code()) {
+        (MakeMode::Question, Some(0)) => {
+            println!("{} {} is up to date", "✓".green(), target_name.bold());
+        }
+        (MakeMode::Question, Some(1)) => {
+            println!("{} {} is out of date", "✗".yellow(), target_name.bold());
+        }
+        (MakeMode::Touch, Some(0)) => {
+            println!(
+                "{} Marked {} up to date without running its recipe",
+                "✓".green(),
+                target_name.bold()
+            );
+        }
+        _ => {}
+    }
+
+    if !status.success() {
+        return Err(MakiError::exec_failed(target_name, status).into());
+    }
+    Ok(())
+}
+
+/// Run a target with its output captured, printing the result as JSON
+fn handle_run_json(
+    target_name: &str,
+    exec_options: &ExecuteOptions,
+    cli: &Cli,
+    repeat: &RepeatArgs,
+    max_output: Option<usize>,
+) -> Result<()> {
+    // Anything else would print to stdout around the JSON
+    let conflict = if cli.dry_run {
+        Some("--dry-run")
+    } else if cli.split {
+        Some("--split")
+    } else if repeat.mode().is_some() {
+        Some("--until-failure and --until-success")
+    } else if exec_options.log_dir.is_some() {
+        Some("--log-dir")
+    } else {
+        None
+    };
+    if let Some(flags) = conflict {
+        anyhow::bail!("run --json can't be combined with {}", flags);
+    }
+
+    let run = RunTimer::start();
+    let captured = executor::execute_target_captured(target_name, exec_options)?;
+    run.record(target_name, exec_options, &captured.status, None);
+
+    let result = capture::RunResult::new(
+        target_name,
+        executor::command_line(target_name, exec_options),
+        &captured,
+        run.started.elapsed().as_millis() as u64,
+        max_output,
+    );
+    print_json(&result)?;
+
+    if !captured.status.success() {
+        return Err(MakiError::exec_failed(target_name, captured.status).into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_parse_options_from_cli() {
+        let cli = Cli::parse_from(["maki", "--all", "--patterns", "list"]);
+
+        let parse_options = ParseOptions {
+            include_private: cli.all,
+            include_patterns: cli.patterns,
+            include_generated: cli.generated,
+        };
+
+        assert!(parse_options.include_private);
+        assert!(parse_options.include_patterns);
+    }
+
+    #[test]
+    fn test_default_working_dir() {
+        let cli = Cli::parse_from(["maki"]);
+        let wd = cli.working_dir();
 
         // Should return current directory when not specified
-        assert!(wd.exists() || wd == PathBuf::from("."));
+        assert!(wd.exists() || wd == Path::new("."));
     }
 
     #[test]
@@ -323,4 +3815,50 @@ mod tests {
         assert!(!cli.json);
         assert!(!cli.no_ui);
     }
+
+    #[test]
+    fn test_makefile_flag_for_other_names() {
+        let target =
+            |file: &str| target::Target::new("dev".to_string(), None, PathBuf::from(file), 1);
+        let cwd = Path::new("/app");
+
+        assert_eq!(makefile_flag(&target("/app/Makefile"), cwd), None);
+        assert_eq!(
+            makefile_flag(&target("/app/tasks.mk"), cwd),
+            Some(PathBuf::from("tasks.mk"))
+        );
+        let mut web = target("/app/Procfile");
+        web.provider = target::Provider::Procfile;
+        assert_eq!(makefile_flag(&web, cwd), None);
+    }
+
+    #[test]
+    fn test_auto_env_with_relative_cwd() {
+        let dir = tempfile::tempdir_in(".").unwrap();
+        let sub = PathBuf::from(dir.path().file_name().unwrap());
+        std::fs::write(sub.join("Makefile"), "build:\n\t@echo $$FOO\n").unwrap();
+        std::fs::write(sub.join(".env"), "FOO=bar\n").unwrap();
+
+        let cli = Cli::parse_from([
+            "maki",
+            "--cwd",
+            sub.to_str().unwrap(),
+            "--auto-env",
+            "run",
+            "build",
+        ]);
+        let target = target::Target::new("build".to_string(), None, sub.join("Makefile"), 1);
+        let options = exec_options(&cli, &[], Some(&target), Vec::new());
+
+        assert!(
+            options
+                .env
+                .iter()
+                .any(|var| var.name == "FOO" && var.value == "bar")
+        );
+        assert_eq!(
+            auto_env_dir(None, &sub),
+            std::env::current_dir().unwrap().join(&sub)
+        );
+    }
 }
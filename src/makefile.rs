@@ -1,11 +1,48 @@
-use anyhow::{Context, Result};
 use regex::Regex;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
-use crate::target::{RequiredVar, Target};
+use crate::cache;
+use crate::error::{MakiError, Result};
+use crate::generated;
+use crate::glob::glob_matches;
+use crate::target::{Provider, RequiredVar, Secret, Target, TargetVar};
+
+/// Variables make sets itself, never prompted for
+const BUILTIN_VARS: [&str; 32] = [
+    "CC", "CXX", "CFLAGS", "CXXFLAGS", "LDFLAGS", "LDLIBS", "AR", "AS",
+    "CPP", "FC", "M2C", "PC", "CO", "GET", "LEX", "YACC", "LINT",
+    "MAKEFLAGS", "MAKECMDGOALS", "CURDIR", "SHELL", "MAKE", "MAKELEVEL", "MAKEFILE_LIST",
+    "@", "<", "^", "?", "*", "%", "+", "|",
+];
+
+/// A mark in a target's comment about how maki treats it rather than what
+/// it does, left out of its description
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Annotation {
+    /// `@serial`: it must not run alongside other targets
+    Serial,
+    /// `@hidden`: it is left out of lists and the picker unless `--all`
+    Hidden,
+    /// `@name Deploy to production`: what the picker calls it, taking the
+    /// rest of the comment line
+    Name(String),
+    /// `@secret TOKEN pass show deploy/token`: a variable whose value is
+    /// hidden, and the rest of the line a command that prints it
+    Secret {
+        name: String,
+        command: Option<String>,
+    },
+    /// `@ready http://localhost:8080/health`: what answers once it is up,
+    /// for `maki run --wait-ready`
+    Ready(String),
+    /// `@tags docker,release`: tags grouping it across name prefixes
+    Tags(Vec<String>),
+}
 
 /// Options for parsing Makefiles
 #[derive(Debug, Clone, Default)]
@@ -14,28 +51,177 @@ pub struct ParseOptions {
     pub include_private: bool,
     /// Include pattern rules (e.g., %.o: %.c)
     pub include_patterns: bool,
+    /// Include the bookkeeping rules of generated Makefiles (CMake, automake)
+    pub include_generated: bool,
+}
+
+impl ParseOptions {
+    /// Options that keep every target, for filtering later with `allows`
+    pub fn everything() -> Self {
+        Self {
+            include_private: true,
+            include_patterns: true,
+            include_generated: true,
+        }
+    }
+
+    /// Check if a parsed target passes these filters
+    pub fn allows(&self, target: &Target) -> bool {
+        (self.include_private || !target.is_private())
+            && (self.include_patterns || !target.is_pattern())
+            && (self.include_generated || !target.boilerplate)
+    }
+}
+
+/// A `VPATH = dirs` or `vpath pattern dirs` search path declaration
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct VpathDirective {
+    /// The file pattern for `vpath` (e.g., "%.c"), or None for the global `VPATH`
+    pub pattern: Option<String>,
+    /// Directories searched for matching prerequisites
+    pub dirs: Vec<String>,
+    /// The line number where the directive is declared
+    pub line: usize,
+}
+
+/// Everything extracted from a single Makefile
+#[derive(Debug, Clone, Default)]
+pub struct MakefileInfo {
+    /// Targets that pass the parse options filters
+    pub targets: Vec<Target>,
+    /// Every rule name defined in the file, including filtered ones
+    pub rule_names: Vec<String>,
+    /// VPATH/vpath search path declarations, in file order
+    pub vpath: Vec<VpathDirective>,
+    /// What each target's own lines said, for reuse once the file changes
+    pub blocks: Vec<Block>,
+    /// How many targets were taken from earlier blocks instead of read again
+    pub reused: usize,
+    /// Problems that didn't stop the file from being parsed, such as it not
+    /// being UTF-8
+    pub warnings: Vec<String>,
+    /// The files it includes, and those they include in turn once followed
+    pub includes: Vec<Include>,
+    /// The names `.PHONY` declares, which may be rules of another file
+    pub phony: Vec<String>,
+}
+
+/// An `include`, `-include`, or `sinclude` of a file named without variables
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Include {
+    /// The file, relative to the including file's directory like the
+    /// including file's own path; its name may have `*` wildcards
+    pub path: PathBuf,
+    /// `-include` and `sinclude` don't mind the file being missing
+    pub optional: bool,
 }
 
-/// Find Makefiles in the given directory
-pub fn find_makefiles(dir: &Path, recursive: bool) -> Vec<PathBuf> {
-    let makefile_names = ["Makefile", "makefile", "GNUmakefile"];
+/// How many files one Makefile may pull in through includes
+const MAX_INCLUDES: usize = 64;
+
+/// What a rule's own lines say about it: the comments above it, its header,
+/// and its recipe, whatever their position in the file
+///
+/// A target whose block hashes the same as before is taken from the earlier
+/// parse; what depends on the rest of the file is worked out again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Block {
+    /// SHA256 hash of the lines the block was read from
+    pub hash: String,
+    pub description: Option<String>,
+    pub required_vars: Vec<RequiredVar>,
+    pub deps: Vec<String>,
+    #[serde(default)]
+    pub order_only: Vec<String>,
+}
+
+/// How a prerequisite name is satisfied
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "kind", content = "source", rename_all = "snake_case")]
+pub enum Prerequisite {
+    /// Another rule in the Makefile
+    Target,
+    /// Built by a pattern rule (e.g., "%.o")
+    PatternRule(String),
+    /// An existing file, possibly found through VPATH/vpath
+    File(PathBuf),
+    /// Neither a rule nor a file that can be found
+    Missing,
+}
+
+/// Names of the files make reads, which discovery looks for unless
+/// `makefile_names` or --makefile-name say otherwise
+pub const MAKEFILE_NAMES: [&str; 3] = ["Makefile", "makefile", "GNUmakefile"];
+
+/// The default `MAKEFILE_NAMES`, owned
+pub fn default_makefile_names() -> Vec<String> {
+    MAKEFILE_NAMES.iter().map(|name| name.to_string()).collect()
+}
+
+/// Whether the last part of `path` is one of `names`
+pub fn is_makefile(path: &str, names: &[String]) -> bool {
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    names.iter().any(|n| n == name)
+}
 
+/// Parse a name given to --makefile-name, which is a file name rather than
+/// a path (that is what -f is for)
+pub fn parse_makefile_name(s: &str) -> std::result::Result<String, String> {
+    match s {
+        "" => Err("expected a file name".to_string()),
+        _ if s.contains(['/', '\\']) => Err(format!(
+            "expected a file name, got the path '{}' (use -f for one Makefile)",
+            s
+        )),
+        _ => Ok(s.to_string()),
+    }
+}
+
+/// Find the Makefiles called one of `names` in the given directory
+pub fn find_makefiles(dir: &Path, recursive: bool, names: &[String]) -> Vec<PathBuf> {
+    find_makefiles_with(dir, recursive, names, |_| true)
+}
+
+/// Like `find_makefiles`, calling `visit` with the number of directories
+/// entered so far; a recursive walk stops early once it returns false
+pub fn find_makefiles_with(
+    dir: &Path,
+    recursive: bool,
+    names: &[String],
+    mut visit: impl FnMut(usize) -> bool,
+) -> Vec<PathBuf> {
     if recursive {
+        let mut dirs = 0;
+        // Files before subdirectories, each by name, so the top-level Makefile
+        // comes first and the order is the same on every platform
         WalkDir::new(dir)
             .follow_links(true)
+            .sort_by(|a, b| {
+                a.file_type()
+                    .is_dir()
+                    .cmp(&b.file_type().is_dir())
+                    .then_with(|| a.file_name().cmp(b.file_name()))
+            })
             .into_iter()
             .filter_map(|e| e.ok())
+            .take_while(|e| {
+                if !e.file_type().is_dir() {
+                    return true;
+                }
+                dirs += 1;
+                visit(dirs)
+            })
             .filter(|e| e.file_type().is_file())
             .filter(|e| {
                 e.file_name()
                     .to_str()
-                    .map(|name| makefile_names.contains(&name))
+                    .map(|name| is_makefile(name, names))
                     .unwrap_or(false)
             })
             .map(|e| e.path().to_path_buf())
             .collect()
     } else {
-        makefile_names
+        names
             .iter()
             .map(|name| dir.join(name))
             .filter(|p| p.exists())
@@ -43,16 +229,230 @@ pub fn find_makefiles(dir: &Path, recursive: bool) -> Vec<PathBuf> {
     }
 }
 
-/// Parse a single Makefile and extract all targets
+/// Read a Makefile's text; one that isn't UTF-8, like many older ones with
+/// accented names in their comments, is read as Latin-1 with a warning
+pub fn read_makefile(path: &Path) -> Result<(String, Option<String>)> {
+    let bytes = fs::read(path).map_err(|e| MakiError::unreadable(path, e))?;
+    Ok(decode(bytes, path))
+}
+
+/// `bytes` as UTF-8 text, or else as Latin-1, where each byte is the
+/// character of the same number, warning about the first line that isn't
+fn decode(mut bytes: Vec<u8>, path: &Path) -> (String, Option<String>) {
+    if bytes.starts_with(BOM.as_bytes()) {
+        bytes.drain(..BOM.len());
+    }
+    let (text, warning) = match String::from_utf8(bytes) {
+        Ok(text) => (text, None),
+        Err(e) => {
+            let valid = e.utf8_error().valid_up_to();
+            let bytes = e.into_bytes();
+            let line = bytes[..valid].iter().filter(|&&b| b == b'\n').count() + 1;
+            let warning = format!(
+                "{} is not UTF-8 (line {}); read it as Latin-1",
+                path.display(),
+                line
+            );
+            let text = bytes.iter().map(|&b| char::from(b)).collect();
+            (text, Some(warning))
+        }
+    };
+    let text = match normalize(&text) {
+        Cow::Owned(normalized) => normalized,
+        Cow::Borrowed(_) => text,
+    };
+    (text, warning)
+}
+
+/// The byte order mark some Windows editors start UTF-8 files with
+const BOM: &str = "\u{feff}";
+
+/// `text` without a byte order mark or the `\r` of Windows line endings, so
+/// a Makefile saved on Windows parses as it would anywhere else
+fn normalize(text: &str) -> Cow<'_, str> {
+    let text = text.strip_prefix(BOM).unwrap_or(text);
+    match text.contains("\r\n") {
+        true => Cow::Owned(text.replace("\r\n", "\n")),
+        false => Cow::Borrowed(text),
+    }
+}
+
+/// Parse a single Makefile and extract all targets, with those of the files
+/// it includes
 pub fn parse_makefile(path: &Path, options: &ParseOptions) -> Result<Vec<Target>> {
-    let content = fs::read_to_string(path)
-        .with_context(|| format!("Failed to read Makefile: {}", path.display()))?;
+    Ok(parse_makefile_info(path, options)?.targets)
+}
+
+/// Parse a single Makefile, keeping file-level metadata alongside the targets
+pub fn parse_makefile_info(path: &Path, options: &ParseOptions) -> Result<MakefileInfo> {
+    let (content, warning) = read_makefile(path)?;
+
+    let mut info = parse_makefile_info_content(&content, path, options)?;
+    info.warnings.extend(warning);
+    follow_includes(&mut info, path, options, &HashMap::new());
+    Ok(info)
+}
 
-    parse_makefile_content(&content, path, options)
+/// Parse a Makefile again after it changed, taking the targets whose lines
+/// haven't from `blocks`
+pub fn reparse_makefile(
+    path: &Path,
+    options: &ParseOptions,
+    blocks: &[Block],
+) -> Result<MakefileInfo> {
+    let (content, warning) = read_makefile(path)?;
+    let earlier = blocks.iter().map(|b| (b.hash.as_str(), b)).collect();
+
+    let mut info = parse_content(&content, path, options, &earlier)?;
+    info.warnings.extend(warning);
+    follow_includes(&mut info, path, options, &earlier);
+    Ok(info)
+}
+
+/// Add the targets of the files `path` includes, and of those they include,
+/// after its own; a name already defined keeps its first rule, and each
+/// target keeps the file it is in, with `path` as the Makefile to run it by
+fn follow_includes(
+    info: &mut MakefileInfo,
+    path: &Path,
+    options: &ParseOptions,
+    earlier: &HashMap<&str, &Block>,
+) {
+    let mut followed = vec![path.canonicalize().unwrap_or(path.to_path_buf())];
+    let mut pending = std::mem::take(&mut info.includes);
+    pending.reverse();
+
+    while let Some(include) = pending.pop() {
+        info.includes.push(include.clone());
+        let files = include_files(&include);
+        if files.is_empty() && !include.optional && !is_wildcard(&include.path) {
+            info.warnings.push(format!(
+                "included file {} not found",
+                include.path.display()
+            ));
+        }
+
+        for file in files {
+            let canonical = file.canonicalize().unwrap_or(file.clone());
+            if followed.contains(&canonical) || followed.len() > MAX_INCLUDES {
+                continue;
+            }
+            followed.push(canonical);
+            let Ok((content, warning)) = read_makefile(&file) else {
+                continue;
+            };
+            let Ok(included) = parse_content(&content, &file, options, earlier) else {
+                continue;
+            };
+
+            info.warnings.extend(warning);
+            info.warnings.extend(included.warnings);
+            for name in included.rule_names {
+                if !info.rule_names.contains(&name) {
+                    info.rule_names.push(name);
+                }
+            }
+            info.vpath.extend(included.vpath);
+            info.blocks.extend(included.blocks);
+            info.reused += included.reused;
+            info.phony.extend(included.phony);
+            for mut target in included.targets {
+                if info.targets.iter().any(|t| t.name == target.name) {
+                    continue;
+                }
+                target.included_from = Some(path.to_path_buf());
+                info.targets.push(target);
+            }
+            // What this file includes is read before the rest of the list
+            pending.extend(included.includes.into_iter().rev());
+        }
+    }
+
+    // `.PHONY` in one file can declare the rules of another
+    for target in &mut info.targets {
+        target.is_phony |= info.phony.contains(&target.name);
+    }
+}
+
+fn is_wildcard(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?'])
+}
+
+/// The files an include reads, in name order for a wildcard
+fn include_files(include: &Include) -> Vec<PathBuf> {
+    if !is_wildcard(&include.path) {
+        return match include.path.is_file() {
+            true => vec![include.path.clone()],
+            false => Vec::new(),
+        };
+    }
+
+    // Only the file name may have wildcards
+    let dir = include.path.parent().unwrap_or(Path::new(""));
+    let Some(pattern) = include.path.file_name().map(|n| n.to_string_lossy()) else {
+        return Vec::new();
+    };
+    let listed = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let Ok(entries) = fs::read_dir(listed) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| glob_matches(&pattern, &entry.file_name().to_string_lossy()))
+        .map(|entry| dir.join(entry.file_name()))
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+    files
+}
+
+/// A hash of what the included files hold (or that they are missing), to
+/// tell when a cached Makefile's includes have changed
+pub fn includes_hash(includes: &[Include]) -> String {
+    let mut listing = String::new();
+    for include in includes {
+        listing.push_str(&format!("{}\n", include.path.display()));
+        for file in include_files(include) {
+            let content = fs::read(&file).unwrap_or_default();
+            listing.push_str(&format!(
+                "  {} {}\n",
+                file.display(),
+                cache::compute_hash(content)
+            ));
+        }
+    }
+    cache::compute_hash(listing)
+}
+
+/// The paths of an `include` line, and whether it is `-include` or `sinclude`;
+/// paths with variables are left out
+fn include_directive(line: &str) -> Option<(bool, Vec<&str>)> {
+    let (optional, rest) = if let Some(rest) = line.strip_prefix("-include") {
+        (true, rest)
+    } else if let Some(rest) = line.strip_prefix("sinclude") {
+        (true, rest)
+    } else {
+        (false, line.strip_prefix("include")?)
+    };
+    if !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let rest = rest.split('#').next().unwrap_or_default();
+    Some((
+        optional,
+        rest.split_whitespace()
+            .filter(|p| !p.contains('$'))
+            .collect(),
+    ))
 }
 
 /// Check if a line is a variable assignment (not a target)
-fn is_variable_assignment(line: &str) -> bool {
+#[allow(clippy::collapsible_if)]
+pub(crate) fn is_variable_assignment(line: &str) -> bool {
     // Simple variable assignments: VAR := value, VAR ?= value, VAR += value, VAR = value
     // These have the form: IDENTIFIER op value (where op is :=, ?=, +=, or = without :)
 
@@ -102,43 +502,389 @@ fn is_variable_assignment(line: &str) -> bool {
     false
 }
 
-/// Check if a line is a target-specific variable (target: VAR := value)
-fn is_target_specific_variable(line: &str) -> bool {
-    // Target-specific variables have the form: target: VAR := value
-    // or target: VAR = value
-    // The key is that after the first colon and space, there's a variable assignment
+/// Parse a target-specific variable line into the targets it applies to and
+/// the assignment, e.g. `build test: CC := clang`
+fn parse_target_specific_variable(line: &str, line_num: usize) -> Option<(Vec<String>, TargetVar)> {
+    let first_colon = line.find(':')?;
+    let after_first_colon = &line[first_colon + 1..];
+
+    // `VAR := value` is a plain assignment, not a rule
+    if after_first_colon.starts_with('=') || after_first_colon.starts_with(":=") {
+        return None;
+    }
 
-    if let Some(first_colon) = line.find(':') {
-        let after_first_colon = &line[first_colon + 1..];
-        let after_trimmed = after_first_colon.trim_start();
+    let names: Vec<String> = line[..first_colon]
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    if names.is_empty() {
+        return None;
+    }
 
-        // Check if what follows looks like a variable assignment
-        // It should be: IDENTIFIER followed by :=, ?=, +=, or = (with space before it)
-        // Find the first space or assignment operator
-        if let Some(space_pos) = after_trimmed
-            .find(|c: char| c.is_whitespace() || c == ':' || c == '?' || c == '+' || c == '=')
-        {
-            let potential_var = &after_trimmed[..space_pos];
-            // Variable names are typically uppercase letters, numbers, underscores
-            if !potential_var.is_empty()
-                && potential_var
-                    .chars()
-                    .all(|c| c.is_ascii_alphanumeric() || c == '_')
-            {
-                // Check what operator follows (may have space before it)
-                let rest = after_trimmed[space_pos..].trim_start();
-                if rest.starts_with(":=")
-                    || rest.starts_with("?=")
-                    || rest.starts_with("+=")
-                    || rest.starts_with('=')
-                {
-                    return true;
+    Some((names, parse_assignment(after_first_colon, line_num)?))
+}
+
+/// The `export`, `override`, and `private` words an assignment starts with,
+/// and the rest of it
+fn split_modifiers(text: &str) -> (Vec<&str>, &str) {
+    let mut modifiers = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some((word, after)) = rest.split_once(char::is_whitespace)
+        && ["export", "override", "private"].contains(&word)
+    {
+        modifiers.push(word);
+        rest = after.trim_start();
+    }
+    (modifiers, rest)
+}
+
+/// Parse `[modifiers] IDENTIFIER op value`, e.g. `export CC := clang`
+fn parse_assignment(text: &str, line_num: usize) -> Option<TargetVar> {
+    let (_, rest) = split_modifiers(text);
+
+    let var_end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))?;
+    let var_name = &rest[..var_end];
+    if var_name.is_empty() {
+        return None;
+    }
+
+    let after_var = rest[var_end..].trim_start();
+    let op = ["::=", ":=", "?=", "+=", "!=", "="]
+        .into_iter()
+        .find(|op| after_var.starts_with(op))?;
+
+    Some(TargetVar {
+        name: var_name.to_string(),
+        op: op.to_string(),
+        value: after_var[op.len()..].trim().to_string(),
+        line: line_num,
+    })
+}
+
+/// The variables `export NAME...` or `unexport NAME...` names without
+/// assigning them, and whether it exports them
+fn parse_export(line: &str) -> Option<(bool, Vec<&str>)> {
+    let (word, rest) = line.split_once(char::is_whitespace)?;
+    let export = match word {
+        "export" => true,
+        "unexport" => false,
+        _ => return None,
+    };
+    let names: Vec<&str> = rest.split_whitespace().collect();
+    let is_name = |name: &&str| name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    names.iter().all(is_name).then_some((export, names))
+}
+
+/// The value an assignment gives, if it's known without running make
+///
+/// Shell assignments (`!=`) and values referencing other variables or
+/// functions are only known once make expands them.
+fn literal_value(var: &TargetVar) -> Option<String> {
+    (var.op != "!=" && !var.value.contains('$')).then(|| var.value.clone())
+}
+
+/// Record a global variable definition, following make's rules for `?=` and `+=`
+fn define_variable(defined: &mut HashMap<String, Option<String>>, var: &TargetVar) {
+    let value = literal_value(var);
+    match var.op.as_str() {
+        "?=" => {
+            defined.entry(var.name.clone()).or_insert(value);
+        }
+        "+=" => {
+            let combined = match (defined.get(&var.name), value) {
+                (Some(Some(existing)), Some(value)) => Some(format!("{} {}", existing, value)),
+                (None, value) => value,
+                _ => None,
+            };
+            defined.insert(var.name.clone(), combined);
+        }
+        _ => {
+            defined.insert(var.name.clone(), value);
+        }
+    }
+}
+
+/// Drop required variables the Makefile already sets
+///
+/// Variables documented with a hint are still prompted for, defaulting to
+/// the value the Makefile (or the target itself) assigns.
+fn apply_defined_variables(target: &mut Target, defined: &HashMap<String, Option<String>>) {
+    let target_vars = &target.target_vars;
+    target.required_vars.retain_mut(|var| {
+        let value = match target_vars.iter().rev().find(|tv| tv.name == var.name) {
+            Some(tv) => literal_value(tv),
+            None => match defined.get(&var.name) {
+                Some(value) => value.clone(),
+                None => return true,
+            },
+        };
+
+        if var.hint.is_none() {
+            return false;
+        }
+        var.default = value;
+        true
+    });
+}
+
+/// Whether a conditional branch is compiled in, judged from the Makefile's own
+/// definitions; variables it doesn't set count as empty
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Branch {
+    Taken,
+    Untaken,
+    /// Depends on something only make knows, e.g. `$(shell ...)`
+    Unknown,
+}
+
+impl Branch {
+    fn from_bool(taken: bool) -> Self {
+        if taken {
+            Branch::Taken
+        } else {
+            Branch::Untaken
+        }
+    }
+
+    fn not(self) -> Self {
+        match self {
+            Branch::Taken => Branch::Untaken,
+            Branch::Untaken => Branch::Taken,
+            Branch::Unknown => Branch::Unknown,
+        }
+    }
+
+    fn or(self, other: Self) -> Self {
+        match (self, other) {
+            (Branch::Taken, _) | (_, Branch::Taken) => Branch::Taken,
+            (Branch::Untaken, Branch::Untaken) => Branch::Untaken,
+            _ => Branch::Unknown,
+        }
+    }
+}
+
+/// An open `ifdef`/`ifeq` block
+struct Conditional {
+    /// Whether an earlier branch of the block was taken
+    done: Branch,
+    /// The branch the following lines are in
+    current: Branch,
+}
+
+/// Split a conditional directive line into its keyword and the rest, e.g.
+/// `else ifdef DEBUG` into `else` and `ifdef DEBUG`
+fn conditional_directive(line: &str) -> Option<(&str, &str)> {
+    // Tab-indented lines are recipe commands, whatever they say
+    if line.starts_with('\t') {
+        return None;
+    }
+    let trimmed = line.trim();
+    let keyword = ["ifdef", "ifndef", "ifeq", "ifneq", "else", "endif"]
+        .into_iter()
+        .find(|k| {
+            trimmed.strip_prefix(k).is_some_and(|rest| {
+                rest.is_empty() || rest.starts_with(|c: char| c.is_whitespace() || c == '(')
+            })
+        })?;
+    Some((keyword, trimmed[keyword.len()..].trim()))
+}
+
+/// Evaluate the condition of an `ifdef`, `ifndef`, `ifeq` or `ifneq`
+fn evaluate_condition(
+    keyword: &str,
+    condition: &str,
+    defined: &HashMap<String, Option<String>>,
+    uncertain: &HashSet<String>,
+) -> Branch {
+    match keyword {
+        "ifdef" | "ifndef" => {
+            let name = condition.split('#').next().unwrap_or("").trim();
+            let branch = if uncertain.contains(name) || BUILTIN_VARS.contains(&name) {
+                Branch::Unknown
+            } else {
+                match defined.get(name) {
+                    // Only an empty value counts as undefined; others aren't expanded
+                    Some(Some(value)) => Branch::from_bool(!value.is_empty()),
+                    Some(None) => Branch::Taken,
+                    None => Branch::Untaken,
                 }
+            };
+            if keyword == "ifdef" {
+                branch
+            } else {
+                branch.not()
+            }
+        }
+        _ => {
+            let expanded = conditional_arguments(condition).and_then(|(a, b)| {
+                Some((
+                    expand_literal(a, defined, uncertain)?,
+                    expand_literal(b, defined, uncertain)?,
+                ))
+            });
+            let branch = match expanded {
+                Some((a, b)) => Branch::from_bool(a == b),
+                None => Branch::Unknown,
+            };
+            if keyword == "ifeq" {
+                branch
+            } else {
+                branch.not()
             }
         }
     }
+}
 
-    false
+/// The two arguments of `ifeq (a,b)`, `ifeq "a" "b"` or `ifeq 'a' 'b'`
+fn conditional_arguments(condition: &str) -> Option<(&str, &str)> {
+    if let Some(inner) = condition
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        // Split at the comma outside of any `$(...)`
+        let mut depth = 0;
+        for (i, c) in inner.char_indices() {
+            match c {
+                '(' | '{' => depth += 1,
+                ')' | '}' => depth -= 1,
+                ',' if depth == 0 => return Some((inner[..i].trim(), inner[i + 1..].trim())),
+                _ => {}
+            }
+        }
+        return None;
+    }
+
+    let quote = condition
+        .chars()
+        .next()
+        .filter(|c| *c == '"' || *c == '\'')?;
+    let mut parts = condition.split(quote);
+    // "", a, " ", b, ""
+    let (Some(""), Some(a), Some(between), Some(b), Some(""), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return None;
+    };
+    between.trim().is_empty().then_some((a, b))
+}
+
+/// Expand the `$(VAR)` references in `text` with the Makefile's literal
+/// values; `None` when any of them (or a function call) is only known to make
+fn expand_literal(
+    text: &str,
+    defined: &HashMap<String, Option<String>>,
+    uncertain: &HashSet<String>,
+) -> Option<String> {
+    let reference = Regex::new(r"\$[\(\{]([A-Za-z0-9_]+)[\)\}]").unwrap();
+    let mut known = true;
+    let expanded = reference.replace_all(text, |caps: &regex::Captures| {
+        let name = &caps[1];
+        if uncertain.contains(name) || BUILTIN_VARS.contains(&name) {
+            known = false;
+            return String::new();
+        }
+        match defined.get(name) {
+            Some(Some(value)) => value.clone(),
+            Some(None) => {
+                known = false;
+                String::new()
+            }
+            None => String::new(),
+        }
+    });
+    (known && !expanded.contains('$')).then(|| expanded.into_owned())
+}
+
+/// For each line, how many of the conditional blocks around it are in a
+/// branch that isn't taken
+fn untaken_depths(lines: &[&str]) -> Vec<usize> {
+    let mut depths = Vec::with_capacity(lines.len());
+    let mut stack: Vec<Conditional> = Vec::new();
+    let mut defined: HashMap<String, Option<String>> = HashMap::new();
+    // Variables assigned in branches that may or may not be taken
+    let mut uncertain: HashSet<String> = HashSet::new();
+
+    for (line_num, line) in lines.iter().enumerate() {
+        let untaken = |stack: &[Conditional]| {
+            stack
+                .iter()
+                .filter(|c| c.current == Branch::Untaken)
+                .count()
+        };
+
+        if let Some((keyword, rest)) = conditional_directive(line) {
+            // The directive itself belongs to the enclosing block
+            let enclosing = match keyword {
+                "else" | "endif" => &stack[..stack.len().saturating_sub(1)],
+                _ => &stack[..],
+            };
+            depths.push(untaken(enclosing));
+            match keyword {
+                "endif" => {
+                    stack.pop();
+                }
+                "else" => {
+                    if let Some(block) = stack.last_mut() {
+                        let branch = match conditional_directive(rest) {
+                            Some((keyword, condition)) if keyword.starts_with("if") => {
+                                evaluate_condition(keyword, condition, &defined, &uncertain)
+                            }
+                            _ => Branch::Taken,
+                        };
+                        block.current = match block.done {
+                            Branch::Taken => Branch::Untaken,
+                            Branch::Untaken => branch,
+                            Branch::Unknown if branch == Branch::Untaken => Branch::Untaken,
+                            Branch::Unknown => Branch::Unknown,
+                        };
+                        block.done = block.done.or(block.current);
+                    }
+                }
+                _ => {
+                    let branch = evaluate_condition(keyword, rest, &defined, &uncertain);
+                    stack.push(Conditional {
+                        done: branch,
+                        current: branch,
+                    });
+                }
+            }
+            continue;
+        }
+        depths.push(untaken(&stack));
+
+        // Follow the definitions the taken branches make
+        if line.starts_with('\t') || stack.iter().any(|c| c.current == Branch::Untaken) {
+            continue;
+        }
+        let trimmed = line.trim();
+        let name = if parse_target_specific_variable(trimmed, line_num + 1).is_some() {
+            None
+        } else if is_variable_assignment(trimmed)
+            && let Some(var) = parse_assignment(trimmed, line_num + 1)
+        {
+            define_variable(&mut defined, &var);
+            Some(var.name)
+        } else if let Some(name) = trimmed
+            .strip_prefix("define ")
+            .and_then(|rest| rest.split_whitespace().next())
+        {
+            defined.insert(name.to_string(), None);
+            Some(name.to_string())
+        } else {
+            None
+        };
+        if let Some(name) = name
+            && stack.iter().any(|c| c.current == Branch::Unknown)
+        {
+            uncertain.insert(name);
+        }
+    }
+    depths
 }
 
 /// Parse Makefile content and extract targets
@@ -147,28 +893,134 @@ pub fn parse_makefile_content(
     file: &Path,
     options: &ParseOptions,
 ) -> Result<Vec<Target>> {
+    Ok(parse_makefile_info_content(content, file, options)?.targets)
+}
+
+/// Parse Makefile content and extract targets plus file-level metadata
+pub fn parse_makefile_info_content(
+    content: &str,
+    file: &Path,
+    options: &ParseOptions,
+) -> Result<MakefileInfo> {
+    parse_content(content, file, options, &HashMap::new())
+}
+
+/// Parse Makefile content, taking the targets whose block hashes to one of
+/// `earlier` from it
+fn parse_content(
+    content: &str,
+    file: &Path,
+    options: &ParseOptions,
+    earlier: &HashMap<&str, &Block>,
+) -> Result<MakefileInfo> {
+    let content = &normalize(content);
+
     // Regex to match target definitions
     // Matches: target_name: [dependencies]
+    // Names may use any letters and digits, not just ASCII (`ビルド:`)
     // Includes % for pattern rules like %.o: %.c
-    let target_regex = Regex::new(r"^([A-Za-z0-9._/\-%]+)\s*:")?;
+    let target_regex = Regex::new(r"^([\p{XID_Continue}._/\-%]+)\s*:").unwrap();
 
     // Regex for pattern rules (e.g., %.o: %.c)
-    let pattern_rule_regex = Regex::new(r"%")?;
+    let pattern_rule_regex = Regex::new(r"%").unwrap();
 
     let lines: Vec<&str> = content.lines().collect();
+    let untaken = untaken_depths(&lines);
+    let mut info = MakefileInfo::default();
     let mut targets = Vec::new();
     let mut seen_names: HashSet<String> = HashSet::new();
+    let mut target_vars: HashMap<String, Vec<TargetVar>> = HashMap::new();
+    let mut defined_vars: HashMap<String, Option<String>> = HashMap::new();
+    // Variables make puts in the environment of recipes, in file order
+    let mut exported: Vec<String> = Vec::new();
+    // A target can have several rules; only one of them needs a recipe
+    let mut with_recipe: HashSet<String> = HashSet::new();
+    // `.NOTPARALLEL` on its own covers every target, otherwise those it lists
+    let mut not_parallel: HashSet<String> = HashSet::new();
+    let mut all_serial = false;
+    // Inside a multi-line `define NAME ... endef`, whose lines are its value
+    let mut in_define = false;
 
     for (line_num, line) in lines.iter().enumerate() {
         let trimmed = line.trim();
 
+        // A definition's lines may look like rules, includes, or assignments,
+        // but they are only text until it is expanded
+        if in_define {
+            in_define = !is_endef(trimmed);
+            continue;
+        }
+        if !line.starts_with('\t')
+            && let Some(name) = define_name(trimmed)
+        {
+            defined_vars.insert(name.to_string(), None);
+            in_define = true;
+            continue;
+        }
+
         // Skip empty lines and comments
         if trimmed.is_empty() || trimmed.starts_with('#') {
             continue;
         }
 
-        // Skip variable assignments (both simple and target-specific)
-        if is_variable_assignment(trimmed) || is_target_specific_variable(trimmed) {
+        // Included files are followed once this one is parsed, unless in a
+        // conditional branch that isn't taken
+        if !line.starts_with('\t')
+            && let Some((optional, paths)) = include_directive(trimmed)
+        {
+            if untaken[line_num] == 0 {
+                let dir = file.parent().unwrap_or(Path::new(""));
+                info.includes.extend(paths.into_iter().map(|path| Include {
+                    path: dir.join(path),
+                    optional,
+                }));
+            }
+            continue;
+        }
+
+        // Record search paths before they are skipped as assignments
+        if !line.starts_with('\t')
+            && let Some(directive) = parse_vpath_directive(trimmed, line_num + 1)
+        {
+            info.vpath.push(directive);
+            continue;
+        }
+
+        // Record target-specific variables; they may come before or after the rule
+        if !line.starts_with('\t')
+            && let Some((names, var)) = parse_target_specific_variable(trimmed, line_num + 1)
+        {
+            for name in names {
+                target_vars.entry(name).or_default().push(var.clone());
+            }
+            continue;
+        }
+
+        // `export NAME` hands a variable, often one set in the environment,
+        // on to recipes; `unexport NAME` takes it back
+        if !line.starts_with('\t')
+            && let Some((export, names)) = parse_export(trimmed)
+        {
+            for name in names {
+                exported.retain(|n| n != name);
+                if export {
+                    exported.push(name.to_string());
+                }
+            }
+            continue;
+        }
+
+        // Skip simple variable assignments, remembering what they define
+        if is_variable_assignment(trimmed) {
+            if !line.starts_with('\t')
+                && let Some(var) = parse_assignment(trimmed, line_num + 1)
+            {
+                define_variable(&mut defined_vars, &var);
+                let exports = split_modifiers(trimmed).0.contains(&"export");
+                if exports && !exported.contains(&var.name) {
+                    exported.push(var.name);
+                }
+            }
             continue;
         }
 
@@ -176,6 +1028,25 @@ pub fn parse_makefile_content(
         if let Some(caps) = target_regex.captures(trimmed) {
             let target_name = caps.get(1).unwrap().as_str().to_string();
 
+            if !info.rule_names.contains(&target_name) {
+                info.rule_names.push(target_name.clone());
+            }
+            if !recipe_lines(&lines, line_num).is_empty() {
+                with_recipe.insert(target_name.clone());
+            }
+            if target_name == ".NOTPARALLEL" {
+                let (listed, _) = extract_prerequisites(&trimmed[caps.get(0).unwrap().end()..]);
+                all_serial |= listed.is_empty();
+                not_parallel.extend(listed);
+            }
+            if target_name == ".PHONY" {
+                let rest = continued(
+                    &trimmed[caps.get(0).unwrap().end()..],
+                    &lines[line_num + 1..],
+                );
+                info.phony.extend(extract_prerequisites(&rest).0);
+            }
+
             // Skip pattern rules unless enabled
             if pattern_rule_regex.is_match(&target_name) && !options.include_patterns {
                 continue;
@@ -191,39 +1062,736 @@ pub fn parse_makefile_content(
                 continue;
             }
 
-            // Extract description from comments
-            let description = extract_description(&lines, line_num);
-
-            // Extract required variables from comments
-            let required_vars = extract_required_vars(&lines, line_num);
+            let hash = block_hash(&lines, line_num, &untaken);
+            let block = match earlier.get(hash.as_str()) {
+                Some(&block) => {
+                    info.reused += 1;
+                    block.clone()
+                }
+                None => {
+                    let rest = continued(
+                        &trimmed[caps.get(0).unwrap().end()..],
+                        &lines[line_num + 1..],
+                    );
+                    let (deps, order_only) = extract_prerequisites(&rest);
+                    Block {
+                        hash,
+                        // Extract description and required variables from comments
+                        description: extract_description(&lines, line_num),
+                        required_vars: extract_required_vars(&lines, line_num, &untaken),
+                        deps,
+                        order_only,
+                    }
+                }
+            };
 
             seen_names.insert(target_name.clone());
-            targets.push(Target::with_required_vars(
+            let mut target = Target::with_required_vars(
                 target_name,
-                description,
+                block.description.clone(),
                 file.to_path_buf(),
                 line_num + 1, // 1-indexed line numbers
-                required_vars,
-            ));
+                block.required_vars.clone(),
+            );
+            target.deps = block.deps.clone();
+            target.order_only = block.order_only.clone();
+            targets.push(target);
+            info.blocks.push(block);
+        }
+    }
+
+    let generator = generated::detect(content);
+    // What generated Makefiles include is their own bookkeeping
+    if generator.is_some() {
+        info.includes.clear();
+    }
+    let custom = match generator {
+        Some(generated::Generator::Automake) => automake_source_rules(file),
+        _ => HashSet::new(),
+    };
+
+    for target in &mut targets {
+        if let Some(vars) = target_vars.remove(&target.name) {
+            target.target_vars = vars;
+        }
+        target.exported = exported
+            .iter()
+            .filter(|name| target.required_vars.iter().any(|var| var.name == **name))
+            .cloned()
+            .collect();
+        apply_defined_variables(target, &defined_vars);
+        // Special targets (.PHONY) and pattern rules without recipes mean
+        // something else to make
+        target.aggregate = !with_recipe.contains(&target.name)
+            && !target.is_pattern()
+            && !target.name.starts_with('.');
+        target.is_phony = info.phony.contains(&target.name);
+        target.boilerplate =
+            generator.is_some_and(|g| generated::is_boilerplate(g, &target.name, &custom));
+        let recipe = recipe_lines(&lines, target.line - 1);
+        target.needs_sudo = uses_sudo(&recipe);
+        target.prints_help = prints_help(&target.name, &recipe);
+        let annotations = annotations(&lines, target.line - 1);
+        target.serial = all_serial
+            || not_parallel.contains(&target.name)
+            || annotations.contains(&Annotation::Serial);
+        target.hidden = annotations.contains(&Annotation::Hidden);
+        for annotation in &annotations {
+            if let Annotation::Secret { name, command } = annotation {
+                mark_secret(target, name, command.clone());
+            }
+        }
+        target.ready = annotations.iter().rev().find_map(|a| match a {
+            Annotation::Ready(probe) => Some(probe.clone()),
+            _ => None,
+        });
+        for annotation in &annotations {
+            if let Annotation::Tags(tags) = annotation {
+                for tag in tags {
+                    if !target.has_tag(tag) {
+                        target.tags.push(tag.clone());
+                    }
+                }
+            }
+        }
+        target.label = annotations.into_iter().rev().find_map(|a| match a {
+            Annotation::Name(name) => Some(name),
+            _ => None,
+        });
+    }
+    targets.retain(|t| {
+        (options.include_generated || !t.boilerplate)
+            && (options.include_private || !(t.hidden || t.prints_help))
+    });
+
+    info.targets = targets;
+    Ok(info)
+}
+
+/// Whether recipe lines run `sudo`, e.g. `@sudo cp ...` or `cd x && sudo make`
+pub fn uses_sudo(recipe: &[&str]) -> bool {
+    recipe.iter().any(|line| {
+        line.split(|c: char| c.is_whitespace() || ";&|()`".contains(c))
+            .any(|word| word.trim_start_matches(['@', '-', '+']) == "sudo")
+    })
+}
+
+/// Whether `name` is a self-documenting `help` rule: one whose recipe reads
+/// the Makefile's `##` comments back, e.g. with
+/// `@grep -E '^[a-z-]+:.*##' $(MAKEFILE_LIST) | awk ...`
+pub fn prints_help(name: &str, recipe: &[&str]) -> bool {
+    if name != "help" {
+        return false;
+    }
+    let recipe = recipe.join("\n");
+    recipe.contains("MAKEFILE_LIST")
+        || ["awk", "grep", "sed"]
+            .iter()
+            .any(|tool| recipe.contains(tool) && recipe.contains("##"))
+}
+
+/// The rules written by hand in the `Makefile.am` next to an automake
+/// generated Makefile
+fn automake_source_rules(file: &Path) -> HashSet<String> {
+    let source = file.with_file_name("Makefile.am");
+    let Ok((content, _)) = read_makefile(&source) else {
+        return HashSet::new();
+    };
+    parse_makefile_info_content(&content, &source, &ParseOptions::everything())
+        .map(|info| info.rule_names.into_iter().collect())
+        .unwrap_or_default()
+}
+
+/// The recipe of the rule whose header is on (0-indexed) `header`: an inline
+/// `target: deps ; command` followed by the tab-indented lines, without the tab
+///
+/// Blank lines, comments and conditional directives (`ifeq`, `else`, `endif`)
+/// between recipe lines don't end the recipe; the commands of every branch
+/// are in it.
+pub fn recipe_lines<'a>(lines: &[&'a str], header: usize) -> Vec<&'a str> {
+    let mut recipe = Vec::new();
+    let Some(first) = lines.get(header) else {
+        return recipe;
+    };
+    let comment = first.find('#').unwrap_or(first.len());
+    if let Some(pos) = first.find(';')
+        && pos < comment
+        && !first[pos + 1..].trim().is_empty()
+    {
+        recipe.push(first[pos + 1..].trim());
+    }
+
+    recipe.extend(
+        recipe_block(lines, header)
+            .iter()
+            .filter_map(|line| line.strip_prefix('\t')),
+    );
+
+    // Lines holding only a tab aren't commands
+    while recipe.last().is_some_and(|l| l.trim().is_empty()) {
+        recipe.pop();
+    }
+    recipe
+}
+
+/// Whether the recipe of the rule on (0-indexed) `header` has `ifeq`/`ifdef`
+/// blocks in it, so which of its commands run is up to make
+pub fn recipe_is_conditional(lines: &[&str], header: usize) -> bool {
+    recipe_block(lines, header)
+        .iter()
+        .any(|line| conditional_directive(line).is_some())
+}
+
+/// The lines after the rule header on `header` that its recipe spans, up to
+/// its last command
+fn recipe_block<'a>(lines: &[&'a str], header: usize) -> Vec<&'a str> {
+    // The header itself may continue over several lines
+    let mut end = header;
+    while end < lines.len() && lines[end].trim_end().ends_with('\\') {
+        end += 1;
+    }
+
+    let mut block: Vec<&str> = lines
+        .iter()
+        .skip(end + 1)
+        .copied()
+        .take_while(|line| {
+            line.starts_with('\t')
+                || line.trim().is_empty()
+                || line.trim_start().starts_with('#')
+                || conditional_directive(line).is_some()
+        })
+        .collect();
+    while block.last().is_some_and(|line| !line.starts_with('\t')) {
+        block.pop();
+    }
+    block
+}
+
+/// A recipe command as make echoes it, without its `@`, `-` and `+` prefixes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecipeLine {
+    pub text: String,
+    /// Run without being echoed, because of an `@` prefix or `.SILENT`
+    pub silent: bool,
+}
+
+/// The targets whose commands make doesn't echo, from `.SILENT` or `-s` in
+/// `MAKEFLAGS`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Silenced {
+    /// `.SILENT` without prerequisites, or `MAKEFLAGS += -s`
+    all: bool,
+    targets: HashSet<String>,
+}
+
+impl Silenced {
+    /// What the Makefile `content` silences
+    pub fn of(content: &str) -> Self {
+        let mut silenced = Self::default();
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            if let Some(names) = line.strip_prefix(".SILENT")
+                && let Some(names) = names.trim_start().strip_prefix(':')
+            {
+                if names.trim().is_empty() {
+                    silenced.all = true;
+                }
+                silenced
+                    .targets
+                    .extend(names.split_whitespace().map(str::to_string));
+            } else if let Some(flags) = makeflags(line) {
+                silenced.all |= flags.split_whitespace().any(|flag| {
+                    flag == "--silent"
+                        || flag == "--quiet"
+                        || (flag.starts_with('-') && !flag.starts_with("--") && flag.contains('s'))
+                });
+            }
+        }
+        silenced
+    }
+
+    pub fn covers(&self, name: &str) -> bool {
+        self.all || self.targets.contains(name)
+    }
+}
+
+/// The value of a `MAKEFLAGS` assignment, e.g. `-s` in `MAKEFLAGS += -s`
+fn makeflags(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let (_, line) = split_modifiers(line);
+    let rest = line.strip_prefix("MAKEFLAGS")?.trim_start();
+    let value = [":=", "+=", "?=", "="]
+        .iter()
+        .find_map(|op| rest.strip_prefix(op))?;
+    Some(value.trim())
+}
+
+/// The commands of the rule whose header is on (0-indexed) `header`, each
+/// marked silent when make won't echo it; `silent` is for a rule `.SILENT`
+/// covers
+///
+/// A line continuing a command with `\` has no prefixes of its own and is
+/// silent when the command is. With `oneshell` the whole recipe is one
+/// command, silent when its first line is.
+pub fn recipe_plan(lines: &[&str], header: usize, silent: bool, oneshell: bool) -> Vec<RecipeLine> {
+    let mut plan: Vec<RecipeLine> = Vec::new();
+    if oneshell {
+        if let Some(script) = oneshell_script(&recipe_lines(lines, header)) {
+            plan.push(RecipeLine {
+                silent: silent || script.prefixes.contains('@'),
+                text: script.text,
+            });
+        }
+        return plan;
+    }
+    let mut continued = false;
+    for line in recipe_lines(lines, header) {
+        let (text, prefixed) = if continued {
+            (line.to_string(), plan.last().is_some_and(|l| l.silent))
+        } else {
+            let command = line.trim_start_matches(|c| "@-+ \t".contains(c));
+            let prefixes = &line[..line.len() - command.len()];
+            (command.to_string(), prefixes.contains('@'))
+        };
+        continued = line.trim_end().ends_with('\\');
+        plan.push(RecipeLine {
+            text,
+            silent: silent || prefixed,
+        });
+    }
+    plan
+}
+
+/// A `.ONESHELL` recipe as the one script make hands the shell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OneshellScript {
+    /// The `@`, `-` and `+` prefixes of the first line, which go for all of it
+    pub prefixes: String,
+    pub text: String,
+}
+
+/// The recipe `lines` as one script: make drops the prefixes of every
+/// command line for a POSIX shell, keeping those of the first for the whole
+/// recipe; None for an empty recipe
+pub fn oneshell_script(lines: &[&str]) -> Option<OneshellScript> {
+    let first = lines.first()?;
+    let command = first.trim_start_matches(|c| "@-+ \t".contains(c));
+    let prefixes = first[..first.len() - command.len()].to_string();
+
+    let mut text = command.to_string();
+    let mut continued = first.trim_end().ends_with('\\');
+    for line in &lines[1..] {
+        text.push('\n');
+        match continued {
+            true => text.push_str(line),
+            false => text.push_str(line.trim_start_matches(['@', '-', '+'])),
+        }
+        continued = line.trim_end().ends_with('\\');
+    }
+    Some(OneshellScript { prefixes, text })
+}
+
+/// Whether the Makefile `content` has `.ONESHELL`, so that each recipe runs
+/// in one shell rather than one per line
+pub fn is_oneshell(content: &str) -> bool {
+    content.lines().any(|line| {
+        line.strip_prefix(".ONESHELL")
+            .is_some_and(|rest| rest.trim_start().starts_with(':'))
+    })
+}
+
+/// The plan of the recipe of `rule`, whose Makefile holds `content`
+fn plan_in(content: &str, rule: &Target) -> Vec<RecipeLine> {
+    let lines: Vec<&str> = content.lines().collect();
+    let header = rule.line.saturating_sub(1);
+    let silent = Silenced::of(content).covers(&rule.name);
+    recipe_plan(&lines, header, silent, is_oneshell(content))
+}
+
+/// The commands of a Makefile target's recipe, read from its Makefile, with
+/// what make won't echo marked; empty for other providers
+pub fn target_plan(target: &Target) -> Vec<RecipeLine> {
+    if target.provider != Provider::Make {
+        return Vec::new();
+    }
+    let Ok((content, _)) = read_makefile(&target.file) else {
+        return Vec::new();
+    };
+    plan_in(&content, target)
+}
+
+/// A prerequisite of an aggregate target and the recipe it runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    pub name: String,
+    /// 0 for the aggregate target's own prerequisites, 1 for theirs, ...
+    pub depth: usize,
+    /// The prerequisite's recipe, `None` when no known target has that name
+    pub recipe: Option<Vec<RecipeLine>>,
+}
+
+/// What an aggregate target runs: each prerequisite with its recipe, in the
+/// order make visits them, with aggregate prerequisites expanded in turn
+pub fn expand_aggregate(target: &Target, targets: &[Target]) -> Vec<Expansion> {
+    let mut files: HashMap<PathBuf, Option<String>> = HashMap::new();
+    let mut seen = HashSet::from([target.name.clone()]);
+    let mut expansion = Vec::new();
+    expand_into(target, targets, 0, &mut files, &mut seen, &mut expansion);
+    expansion
+}
+
+fn expand_into(
+    target: &Target,
+    targets: &[Target],
+    depth: usize,
+    files: &mut HashMap<PathBuf, Option<String>>,
+    seen: &mut HashSet<String>,
+    expansion: &mut Vec<Expansion>,
+) {
+    for dep in target.prerequisites() {
+        let Some(rule) = find_rule(target, dep, targets) else {
+            expansion.push(Expansion {
+                name: dep.clone(),
+                depth,
+                recipe: None,
+            });
+            continue;
+        };
+
+        let content = files
+            .entry(rule.file.clone())
+            .or_insert_with(|| read_makefile(&rule.file).ok().map(|(text, _)| text));
+        let recipe = content
+            .as_deref()
+            .map(|content| plan_in(content, rule))
+            .unwrap_or_default();
+        expansion.push(Expansion {
+            name: dep.clone(),
+            depth,
+            recipe: Some(recipe),
+        });
+
+        // Make runs each prerequisite once, which also stops cycles
+        if rule.aggregate && seen.insert(dep.clone()) {
+            expand_into(rule, targets, depth + 1, files, seen, expansion);
         }
     }
+}
+
+/// The rule `target`'s prerequisite `dep` refers to, preferring one from the
+/// same Makefile, then the same project root
+pub fn find_rule<'a>(target: &Target, dep: &str, targets: &'a [Target]) -> Option<&'a Target> {
+    targets
+        .iter()
+        .filter(|t| t.provider == target.provider && t.name == dep)
+        .max_by_key(|t| (t.file == target.file, t.root == target.root))
+}
+
+/// Parse a `VPATH = dirs` or `vpath pattern dirs` line
+fn parse_vpath_directive(line: &str, line_num: usize) -> Option<VpathDirective> {
+    // Directories are separated by colons or whitespace (semicolons on Windows)
+    let split_dirs = |s: &str| -> Vec<String> {
+        s.split(|c: char| c == ':' || c == ';' || c.is_whitespace())
+            .filter(|d| !d.is_empty())
+            .map(|d| d.to_string())
+            .collect()
+    };
+
+    if let Some(rest) = line.strip_prefix("VPATH") {
+        let rest = rest.trim_start();
+        let value = ["+=", ":=", "?=", "="]
+            .iter()
+            .find_map(|op| rest.strip_prefix(op))?;
+        return Some(VpathDirective {
+            pattern: None,
+            dirs: split_dirs(value),
+            line: line_num,
+        });
+    }
+
+    let rest = line.strip_prefix("vpath")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    // `vpath pattern` without directories clears the search path for that pattern
+    let mut parts = rest.trim().splitn(2, char::is_whitespace);
+    let pattern = parts.next()?.to_string();
+    Some(VpathDirective {
+        pattern: Some(pattern),
+        dirs: parts.next().map(split_dirs).unwrap_or_default(),
+        line: line_num,
+    })
+}
 
-    Ok(targets)
+/// The variable a `define NAME`, `define NAME =`, or `override define NAME`
+/// line starts defining
+fn define_name(line: &str) -> Option<&str> {
+    let mut words = line.split_whitespace();
+    let mut word = words.next()?;
+    while matches!(word, "override" | "export" | "private") {
+        word = words.next()?;
+    }
+    if word != "define" {
+        return None;
+    }
+    let name = words.next()?.trim_end_matches([':', '=', '?', '+', '!']);
+    (!name.is_empty()).then_some(name)
+}
+
+/// Whether a line ends a `define`, as `endef` does, even with a comment
+fn is_endef(line: &str) -> bool {
+    line.strip_prefix("endef")
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t', '#']))
+}
+
+/// `first` with the lines after it that a trailing backslash runs it on to
+fn continued(first: &str, next: &[&str]) -> String {
+    let mut text = first.to_string();
+    for line in next {
+        let Some(joined) = text.strip_suffix('\\') else {
+            break;
+        };
+        text = format!("{} {}", joined.trim_end(), line.trim());
+    }
+    text
+}
+
+/// Extract prerequisite names from the text following a target's colon: the
+/// normal ones, and the order-only ones after a `|`
+fn extract_prerequisites(rest: &str) -> (Vec<String>, Vec<String>) {
+    // Double-colon rules (target:: deps)
+    let mut rest = rest.strip_prefix(':').unwrap_or(rest);
+
+    // Drop inline comments and inline recipes (target: deps ; command)
+    if let Some(pos) = rest.find('#') {
+        rest = &rest[..pos];
+    }
+    if let Some(pos) = rest.find(';') {
+        rest = &rest[..pos];
+    }
+
+    let names = |text: &str| text.split_whitespace().map(|d| d.to_string()).collect();
+    match rest.split_once('|') {
+        Some((deps, order_only)) => (names(deps), names(order_only)),
+        None => (names(rest), Vec::new()),
+    }
+}
+
+/// Check whether a make pattern (e.g., "%.c") matches a name
+fn pattern_matches(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('%') {
+        Some((prefix, suffix)) => {
+            name.len() >= prefix.len() + suffix.len()
+                && name.starts_with(prefix)
+                && name.ends_with(suffix)
+        }
+        None => pattern == name,
+    }
+}
+
+/// Work out how a prerequisite is satisfied: by a rule, a pattern rule,
+/// or a file found directly or through the VPATH/vpath search paths
+pub fn resolve_prerequisite(name: &str, info: &MakefileInfo, base_dir: &Path) -> Prerequisite {
+    if info.rule_names.iter().any(|r| r == name) {
+        return Prerequisite::Target;
+    }
+
+    let direct = base_dir.join(name);
+    if direct.exists() {
+        return Prerequisite::File(direct);
+    }
+
+    // `vpath` directives are searched in order, then the global VPATH
+    let specific = info.vpath.iter().filter(|v| {
+        v.pattern
+            .as_deref()
+            .is_some_and(|p| pattern_matches(p, name))
+    });
+    let global = info.vpath.iter().filter(|v| v.pattern.is_none());
+
+    for directive in specific.chain(global) {
+        for dir in &directive.dirs {
+            let candidate = base_dir.join(dir).join(name);
+            if candidate.exists() {
+                return Prerequisite::File(candidate);
+            }
+        }
+    }
+
+    if let Some(rule) = info
+        .rule_names
+        .iter()
+        .find(|r| r.contains('%') && pattern_matches(r, name))
+    {
+        return Prerequisite::PatternRule(rule.clone());
+    }
+
+    Prerequisite::Missing
+}
+
+/// Hash of the lines a rule's block is read from: the comments above its
+/// (0-indexed) `header`, the header, and the recipe, with which of its lines
+/// are in a conditional branch that isn't taken
+///
+/// The lines that end the comments and the recipe are left out: only their
+/// being there is read, and whatever they turn into that doesn't end the
+/// block adds lines to it.
+fn block_hash(lines: &[&str], header: usize, untaken: &[usize]) -> String {
+    // Walk up the way the comments are read
+    let mut start = header;
+    while start > 0 {
+        let line = lines[start - 1].trim();
+        let comment_above = start > 1 && lines[start - 2].trim().starts_with('#');
+        let read = line.starts_with('#') || (line.is_empty() && comment_above);
+        if !read {
+            break;
+        }
+        start -= 1;
+    }
+
+    // ... and down the way the recipe's variables are
+    let mut end = header + 1;
+    while end < lines.len() {
+        let line = lines[end];
+        let skipped = conditional_directive(line).is_some() || untaken[end] > untaken[header];
+        let continues = lines[end - 1].trim_end().ends_with('\\');
+        if !skipped
+            && !continues
+            && !line.is_empty()
+            && !line.starts_with('\t')
+            && !line.starts_with(' ')
+        {
+            break;
+        }
+        end += 1;
+    }
+
+    let skipped: Vec<bool> = (header..end)
+        .map(|i| untaken[i] > untaken[header])
+        .collect();
+    cache::compute_hash(format!("{}\n{:?}", lines[start..end].join("\n"), skipped))
 }
 
 /// Extract description from preceding comments or inline comments
 fn extract_description(lines: &[&str], target_line: usize) -> Option<String> {
-    let target = lines[target_line];
-
     // First check for inline comment after ## (common convention)
-    if let Some(pos) = target.find("##") {
-        let desc = target[pos + 2..].trim();
+    if let Some(comment) = inline_comment(lines[target_line]) {
+        let (desc, _) = split_annotations(comment);
         if !desc.is_empty() {
-            return Some(desc.to_string());
+            return Some(desc);
         }
     }
 
-    // Check for preceding comment lines
+    let comments: Vec<String> = comments_above(lines, target_line)
+        .into_iter()
+        .map(|comment| split_annotations(comment).0)
+        .filter(|comment| !comment.is_empty())
+        .collect();
+
+    if comments.is_empty() {
+        None
+    } else {
+        Some(comments.join(" "))
+    }
+}
+
+/// The comment after `##` on a rule's header line
+fn inline_comment(header: &str) -> Option<&str> {
+    header.find("##").map(|pos| header[pos + 2..].trim())
+}
+
+/// `comment` without its annotations, and the annotations
+fn split_annotations(comment: &str) -> (String, Vec<Annotation>) {
+    let words: Vec<&str> = comment.split_whitespace().collect();
+    let mut text = Vec::new();
+    let mut annotations = Vec::new();
+    let mut skip = false;
+    for (i, &word) in words.iter().enumerate() {
+        if std::mem::take(&mut skip) {
+            continue;
+        }
+        match word {
+            "@serial" => annotations.push(Annotation::Serial),
+            "@hidden" => annotations.push(Annotation::Hidden),
+            "@name" => {
+                let name = words[i + 1..].join(" ");
+                if !name.is_empty() {
+                    annotations.push(Annotation::Name(name));
+                }
+                break;
+            }
+            "@secret" => {
+                if let Some(name) = words.get(i + 1) {
+                    let command = words[i + 2..].join(" ");
+                    annotations.push(Annotation::Secret {
+                        name: name.to_string(),
+                        command: (!command.is_empty()).then_some(command),
+                    });
+                }
+                break;
+            }
+            "@ready" => {
+                if let Some(probe) = words.get(i + 1) {
+                    annotations.push(Annotation::Ready(probe.to_string()));
+                }
+                skip = true;
+            }
+            "@tags" => {
+                if let Some(list) = words.get(i + 1) {
+                    let tags = list
+                        .split(',')
+                        .filter(|t| !t.is_empty())
+                        .map(str::to_string)
+                        .collect();
+                    annotations.push(Annotation::Tags(tags));
+                }
+                skip = true;
+            }
+            _ => text.push(word),
+        }
+    }
+
+    if annotations.is_empty()
+        && !words
+            .iter()
+            .any(|w| matches!(*w, "@name" | "@secret" | "@ready" | "@tags"))
+    {
+        (comment.to_string(), annotations)
+    } else {
+        (text.join(" "), annotations)
+    }
+}
+
+/// The annotations in the comment of the rule on (0-indexed) `target_line`,
+/// e.g. `## @serial`
+fn annotations(lines: &[&str], target_line: usize) -> Vec<Annotation> {
+    inline_comment(lines[target_line])
+        .into_iter()
+        .chain(comments_above(lines, target_line))
+        .flat_map(|comment| split_annotations(comment).1)
+        .collect()
+}
+
+/// Make the variable `name` of `target` a secret, adding it to the required
+/// ones when the recipe doesn't name it, e.g. a script reads it
+fn mark_secret(target: &mut Target, name: &str, command: Option<String>) {
+    let secret = Some(Secret { command });
+    match target.required_vars.iter_mut().find(|v| v.name == name) {
+        Some(var) => var.secret = secret,
+        None => target.required_vars.push(RequiredVar {
+            name: name.to_string(),
+            hint: None,
+            default: None,
+            secret,
+        }),
+    }
+}
+
+/// The comment lines above the rule on (0-indexed) `target_line`, without
+/// their `#`, in file order
+fn comments_above<'a>(lines: &[&'a str], target_line: usize) -> Vec<&'a str> {
     let mut comments = Vec::new();
     let mut i = target_line;
 
@@ -235,7 +1803,7 @@ fn extract_description(lines: &[&str], target_line: usize) -> Option<String> {
             // Remove the # and any leading whitespace
             let comment = prev_line.trim_start_matches('#').trim();
             if !comment.is_empty() {
-                comments.push(comment.to_string());
+                comments.push(comment);
             }
         } else if prev_line.is_empty() {
             // Allow one blank line between comment and target
@@ -251,21 +1819,22 @@ fn extract_description(lines: &[&str], target_line: usize) -> Option<String> {
         }
     }
 
-    if comments.is_empty() {
-        None
-    } else {
-        comments.reverse();
-        Some(comments.join(" "))
-    }
+    comments.reverse();
+    comments
 }
 
 /// Extract required variables from comments and recipe
 /// Looks for patterns like: "usage: make target VAR=value|value2" in comments
 /// and $(VAR) or ${VAR} in the recipe
-fn extract_required_vars(lines: &[&str], target_line: usize) -> Vec<RequiredVar> {
-    let mut vars = Vec::new();
-    let mut var_hints: std::collections::HashMap<String, Option<String>> =
-        std::collections::HashMap::new();
+///
+/// Recipe lines in a conditional branch that isn't taken (`untaken`, from
+/// [`untaken_depths`]) don't make their variables required.
+fn extract_required_vars(
+    lines: &[&str],
+    target_line: usize,
+    untaken: &[usize],
+) -> Vec<RequiredVar> {
+    let mut vars: Vec<RequiredVar> = Vec::new();
 
     // Regex to match VAR=hint patterns in comments (e.g., V=patch|minor|major)
     let hint_regex = Regex::new(r"\b([A-Z][A-Z0-9_]*)=([^\s,\)]+)").unwrap();
@@ -273,37 +1842,15 @@ fn extract_required_vars(lines: &[&str], target_line: usize) -> Vec<RequiredVar>
     // Regex to match $(VAR) or ${VAR} in recipe lines
     let recipe_var_regex = Regex::new(r"\$[\(\{]([A-Z][A-Z0-9_]*)[\)\}]").unwrap();
 
-    // Built-in make variables to ignore
-    let builtin_vars: std::collections::HashSet<&str> = [
-        "CC", "CXX", "CFLAGS", "CXXFLAGS", "LDFLAGS", "LDLIBS", "AR", "AS",
-        "CPP", "FC", "M2C", "PC", "CO", "GET", "LEX", "YACC", "LINT",
-        "MAKEFLAGS", "MAKECMDGOALS", "CURDIR", "SHELL", "MAKE", "MAKELEVEL",
-        "@", "<", "^", "?", "*", "%", "+", "|",
-    ]
-    .iter()
-    .cloned()
-    .collect();
-
-    // Collect all comment text for this target
-    let mut comment_text = String::new();
-
-    // Check inline comment on the target line
-    let target = lines[target_line];
-    if let Some(pos) = target.find("##") {
-        comment_text.push_str(&target[pos + 2..]);
-        comment_text.push(' ');
-    }
-
-    // Check preceding comment lines
+    // Collect the preceding comment lines, walking up from the target
+    let mut comments: Vec<&str> = Vec::new();
     let mut i = target_line;
     while i > 0 {
         i -= 1;
         let prev_line = lines[i].trim();
 
         if prev_line.starts_with('#') {
-            let comment = prev_line.trim_start_matches('#').trim();
-            comment_text.push_str(comment);
-            comment_text.push(' ');
+            comments.push(prev_line.trim_start_matches('#').trim());
         } else if prev_line.is_empty() {
             if i > 0 && lines[i - 1].trim().starts_with('#') {
                 continue;
@@ -314,17 +1861,39 @@ fn extract_required_vars(lines: &[&str], target_line: usize) -> Vec<RequiredVar>
         }
     }
 
+    // Read the comments top to bottom, then the inline comment on the target
+    // line, so variables are prompted for in the order they are documented
+    comments.reverse();
+    let target = lines[target_line];
+    if let Some(pos) = target.find("##") {
+        comments.push(&target[pos + 2..]);
+    }
+    let comment_text = comments.join(" ");
+
     // Find all variable hints in comments (VAR=value|value2)
     for cap in hint_regex.captures_iter(&comment_text) {
         let name = cap.get(1).unwrap().as_str().to_string();
         let hint = cap.get(2).map(|m| m.as_str().to_string());
-        var_hints.insert(name, hint);
+        match vars.iter_mut().find(|v| v.name == name) {
+            Some(var) => var.hint = hint,
+            None => vars.push(RequiredVar {
+                name,
+                hint,
+                default: None,
+                secret: None,
+            }),
+        }
     }
 
     // Scan recipe lines for $(VAR) or ${VAR} patterns
     let mut j = target_line + 1;
     while j < lines.len() {
         let line = lines[j];
+        // Conditionals can wrap parts of a recipe
+        if conditional_directive(line).is_some() || untaken[j] > untaken[target_line] {
+            j += 1;
+            continue;
+        }
         // Recipe lines start with tab or spaces
         if !line.starts_with('\t') && !line.starts_with(' ') {
             // Stop at non-recipe line (next target or empty non-continuation)
@@ -338,27 +1907,24 @@ fn extract_required_vars(lines: &[&str], target_line: usize) -> Vec<RequiredVar>
             let name = cap.get(1).unwrap().as_str().to_string();
 
             // Skip built-in variables
-            if builtin_vars.contains(name.as_str()) {
+            if BUILTIN_VARS.contains(&name.as_str()) {
                 continue;
             }
 
-            // Add variable if not already tracked
-            if !var_hints.contains_key(&name) {
-                var_hints.insert(name, None);
+            // Undocumented variables follow, in the order the recipe uses them
+            if !vars.iter().any(|v| v.name == name) {
+                vars.push(RequiredVar {
+                    name,
+                    hint: None,
+                    default: None,
+                    secret: None,
+                });
             }
         }
 
         j += 1;
     }
 
-    // Convert to RequiredVar vec
-    for (name, hint) in var_hints {
-        vars.push(RequiredVar { name, hint });
-    }
-
-    // Sort for consistent ordering
-    vars.sort_by(|a, b| a.name.cmp(&b.name));
-
     vars
 }
 
@@ -369,10 +1935,10 @@ pub fn parse_all_makefiles(
     recursive: bool,
     options: &ParseOptions,
 ) -> Result<Vec<Target>> {
-    let makefiles = find_makefiles(dir, recursive);
+    let makefiles = find_makefiles(dir, recursive, &default_makefile_names());
 
     if makefiles.is_empty() {
-        anyhow::bail!("No Makefile found in {}", dir.display());
+        return Err(MakiError::no_makefile(dir));
     }
 
     let mut all_targets = Vec::new();
@@ -443,6 +2009,22 @@ test: ## Run all tests
         assert_eq!(test.description, Some("Run all tests".to_string()));
     }
 
+    #[test]
+    fn test_parse_unicode_names_and_descriptions() {
+        let content = "ビルド: ## 构建项目\n\tcargo build\n\n\
+                       déployer: ビルド ## 🚀 Ship it\n\t./deploy.sh\n";
+
+        let options = ParseOptions::default();
+        let targets = parse_makefile_content(content, Path::new("Makefile"), &options).unwrap();
+
+        let build = targets.iter().find(|t| t.name == "ビルド").unwrap();
+        assert_eq!(build.description, Some("构建项目".to_string()));
+
+        let deploy = targets.iter().find(|t| t.name == "déployer").unwrap();
+        assert_eq!(deploy.description, Some("🚀 Ship it".to_string()));
+        assert_eq!(deploy.prerequisite_list(), "ビルド");
+    }
+
     #[test]
     fn test_skip_private_targets() {
         let content = r#"
@@ -482,6 +2064,204 @@ _internal:
         assert!(targets.iter().any(|t| t.name == "_internal"));
     }
 
+    #[test]
+    fn test_cmake_makefile_keeps_entry_points() {
+        let content = "\
+# CMAKE generated file: DO NOT EDIT!
+# Generated by \"Unix Makefiles\" Generator, CMake Version 3.28
+
+all: cmake_check_build_system
+	$(MAKE) -f CMakeFiles/Makefile2 all
+
+clean:
+	$(MAKE) -f CMakeFiles/Makefile2 clean
+
+cmake_check_build_system:
+	$(CMAKE_COMMAND) -S$(CMAKE_SOURCE_DIR) -B$(CMAKE_BINARY_DIR) --check-build-system
+
+edit_cache:
+	ccmake -S$(CMAKE_SOURCE_DIR)
+
+app: cmake_check_build_system
+	$(MAKE) -f CMakeFiles/Makefile2 app
+
+app/fast:
+	$(MAKE) -f CMakeFiles/app.dir/build.make CMakeFiles/app.dir/build
+
+main.c.o:
+	$(MAKE) -f CMakeFiles/app.dir/build.make CMakeFiles/app.dir/main.c.o
+";
+
+        let targets =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::default())
+                .unwrap();
+        let names: Vec<&str> = targets.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["all", "clean", "app"]);
+
+        let options = ParseOptions {
+            include_generated: true,
+            ..Default::default()
+        };
+        let everything = parse_makefile_content(content, Path::new("Makefile"), &options).unwrap();
+        assert_eq!(everything.len(), 7);
+        assert_eq!(everything.iter().filter(|t| t.boilerplate).count(), 4);
+    }
+
+    #[test]
+    fn test_automake_makefile_keeps_source_rules() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("Makefile.am"),
+            "bin_PROGRAMS = app\n\nrelease: dist\n\t./scripts/release.sh\n",
+        )
+        .unwrap();
+        let makefile = dir.path().join("Makefile");
+        fs::write(
+            &makefile,
+            "\
+# Makefile.in generated by automake 1.16.5 from Makefile.am.
+# Makefile.  Generated from Makefile.in by configure.
+
+all: all-am
+all-am: Makefile $(PROGRAMS)
+install-binPROGRAMS: $(bin_PROGRAMS)
+	$(INSTALL_PROGRAM_ENV) $(INSTALL_PROGRAM) $$list '$(DESTDIR)$(bindir)'
+check: check-am
+release: dist
+	./scripts/release.sh
+",
+        )
+        .unwrap();
+
+        let targets = parse_makefile(&makefile, &ParseOptions::default()).unwrap();
+        let names: Vec<&str> = targets.iter().map(|t| t.name.as_str()).collect();
+
+        assert_eq!(names, vec!["all", "check", "release"]);
+    }
+
+    #[test]
+    fn test_follow_includes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("mk")).unwrap();
+        fs::write(
+            dir.path().join("mk/docker.mk"),
+            "## Build the image\nimage:\n\tdocker build .\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("mk/lint.mk"),
+            "lint:\n\truff .\ninclude ../Makefile\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("common.mk"),
+            "include mk/docker.mk\nbuild:\n\tcargo build\n",
+        )
+        .unwrap();
+        let makefile = dir.path().join("Makefile");
+        fs::write(
+            &makefile,
+            "include common.mk\n-include local.mk\nsinclude mk/*.mk\ninclude $(ENV).mk\n\
+             include missing.mk\nbuild: ## Build everything\n\ttrue\n",
+        )
+        .unwrap();
+
+        let info = parse_makefile_info(&makefile, &ParseOptions::default()).unwrap();
+        let found: Vec<(&str, &Path)> = info
+            .targets
+            .iter()
+            .map(|t| (t.name.as_str(), t.file.strip_prefix(dir.path()).unwrap()))
+            .collect();
+
+        // The Makefile's own `build` wins, and mk/docker.mk isn't read twice
+        assert_eq!(
+            found,
+            [
+                ("build", Path::new("Makefile")),
+                ("image", Path::new("mk/docker.mk")),
+                ("lint", Path::new("mk/lint.mk")),
+            ]
+        );
+        assert_eq!(info.targets[0].included_from, None);
+        assert_eq!(info.targets[1].included_from, Some(makefile.clone()));
+        assert_eq!(
+            info.targets[1].description,
+            Some("Build the image".to_string())
+        );
+        assert_eq!(info.targets[1].makefile(), makefile);
+        assert_eq!(
+            info.warnings,
+            [format!(
+                "included file {} not found",
+                dir.path().join("missing.mk").display()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_include_directive() {
+        assert_eq!(
+            include_directive("include a.mk b.mk # both"),
+            Some((false, vec!["a.mk", "b.mk"]))
+        );
+        assert_eq!(
+            include_directive("-include .env"),
+            Some((true, vec![".env"]))
+        );
+        assert_eq!(
+            include_directive("sinclude $(DEPS) deps.mk"),
+            Some((true, vec!["deps.mk"]))
+        );
+        assert_eq!(include_directive("includes: build"), None);
+        assert_eq!(include_directive("include_dirs = src"), None);
+    }
+
+    #[test]
+    fn test_find_makefiles_by_name() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("web")).unwrap();
+        for file in ["Makefile", "tasks.mk", "web/tasks.mk", "web/build.mk"] {
+            fs::write(dir.path().join(file), "all:\n").unwrap();
+        }
+        let found = |recursive: bool, names: &[&str]| {
+            let names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+            find_makefiles(dir.path(), recursive, &names)
+                .into_iter()
+                .map(|p| p.strip_prefix(dir.path()).unwrap().to_path_buf())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            find_makefiles(dir.path(), true, &default_makefile_names()).len(),
+            1
+        );
+        assert_eq!(
+            found(false, &["tasks.mk", "Makefile"]),
+            vec![PathBuf::from("tasks.mk"), PathBuf::from("Makefile")]
+        );
+        assert_eq!(
+            found(true, &["tasks.mk"]),
+            vec![PathBuf::from("tasks.mk"), PathBuf::from("web/tasks.mk")]
+        );
+        assert!(is_makefile("web/build.mk", &["build.mk".to_string()]));
+        assert!(!is_makefile("web/build.mk.bak", &["build.mk".to_string()]));
+    }
+
+    #[test]
+    fn test_parse_options_allows() {
+        let private = Target::new("_internal".to_string(), None, PathBuf::from("Makefile"), 1);
+        let pattern = Target::new("%.o".to_string(), None, PathBuf::from("Makefile"), 2);
+        let mut generated = Target::new("depend".to_string(), None, PathBuf::from("Makefile"), 3);
+        generated.boilerplate = true;
+
+        assert!(!ParseOptions::default().allows(&private));
+        assert!(!ParseOptions::default().allows(&pattern));
+        assert!(!ParseOptions::default().allows(&generated));
+        assert!(ParseOptions::everything().allows(&private));
+        assert!(ParseOptions::everything().allows(&pattern));
+        assert!(ParseOptions::everything().allows(&generated));
+    }
+
     #[test]
     fn test_skip_pattern_rules() {
         let content = r#"
@@ -622,18 +2402,59 @@ print-highest-tag: HIGHEST_TAG:=$(shell git tag | sort -V | tail -1)
 print-highest-tag:
 	@echo $(HIGHEST_TAG)
 
-build: CC := clang
-build:
-	$(CC) main.c
-"#;
+build: CC := clang
+build:
+	$(CC) main.c
+"#;
+
+        let options = ParseOptions::default();
+        let targets = parse_makefile_content(content, Path::new("Makefile"), &options).unwrap();
+
+        // Should find the actual targets, not the variable assignment lines
+        assert_eq!(targets.len(), 2);
+        assert!(targets.iter().any(|t| t.name == "print-highest-tag"));
+        assert!(targets.iter().any(|t| t.name == "build"));
+    }
+
+    #[test]
+    fn test_attach_target_specific_variables() {
+        let content = r#"
+build: CC := clang
+build:
+	$(CC) main.c
+
+build test: export CFLAGS += -g
+test:
+	$(CC) -o test main.c
+"#;
+
+        let options = ParseOptions::default();
+        let targets = parse_makefile_content(content, Path::new("Makefile"), &options).unwrap();
+
+        let build = targets.iter().find(|t| t.name == "build").unwrap();
+        assert_eq!(build.target_vars.len(), 2);
+        assert_eq!(build.target_vars[0].name, "CC");
+        assert_eq!(build.target_vars[0].op, ":=");
+        assert_eq!(build.target_vars[0].value, "clang");
+        assert_eq!(build.target_vars[0].line, 2);
+        assert_eq!(build.target_vars[1].name, "CFLAGS");
+        assert_eq!(build.target_vars[1].op, "+=");
+
+        let test = targets.iter().find(|t| t.name == "test").unwrap();
+        assert_eq!(test.target_vars.len(), 1);
+        assert_eq!(test.target_vars[0].value, "-g");
+    }
+
+    #[test]
+    fn test_recipe_lines_are_not_target_specific_variables() {
+        let content = "deploy:\n\t@echo deploy: ENV=prod is the usual\n\t./deploy.sh $(ENV)\n";
 
         let options = ParseOptions::default();
         let targets = parse_makefile_content(content, Path::new("Makefile"), &options).unwrap();
 
-        // Should find the actual targets, not the variable assignment lines
-        assert_eq!(targets.len(), 2);
-        assert!(targets.iter().any(|t| t.name == "print-highest-tag"));
-        assert!(targets.iter().any(|t| t.name == "build"));
+        assert!(targets[0].target_vars.is_empty());
+        assert_eq!(targets[0].required_vars[0].name, "ENV");
+        assert_eq!(targets[0].required_vars[0].default, None);
     }
 
     #[test]
@@ -650,13 +2471,17 @@ build:
     }
 
     #[test]
-    fn test_is_target_specific_variable() {
+    fn test_parse_target_specific_variable() {
+        let is_target_specific_variable =
+            |line: &str| parse_target_specific_variable(line, 1).is_some();
+
         assert!(is_target_specific_variable(
             "print-highest-tag: HIGHEST_TAG:=$(shell git tag)"
         ));
         assert!(is_target_specific_variable("build: CC := clang"));
         assert!(is_target_specific_variable("test: CFLAGS += -g"));
         assert!(is_target_specific_variable("foo: BAR = baz"));
+        assert!(is_target_specific_variable("foo: override BAR ?= baz"));
 
         // These are NOT target-specific variables
         assert!(!is_target_specific_variable("build:"));
@@ -799,4 +2624,867 @@ deploy:
         let version_var = deploy.required_vars.iter().find(|v| v.name == "VERSION").unwrap();
         assert_eq!(version_var.hint, None);
     }
+
+    #[test]
+    fn test_skip_vars_only_used_in_untaken_branches() {
+        let content = r#"
+DEBUG =
+PLATFORM := linux
+
+build:
+	cargo build $(PROFILE)
+ifdef VERBOSE
+	@echo "log level $(LOG_LEVEL)"
+endif
+ifeq ($(PLATFORM),windows)
+	@echo $(WIN_SDK)
+else ifneq ($(PLATFORM),linux)
+	@echo $(OTHER_SDK)
+else
+	@echo $(LINUX_SDK)
+endif
+ifndef DEBUG
+	strip $(BINARY)
+endif
+ifeq ($(shell uname),Darwin)
+	codesign $(IDENTITY)
+endif
+"#;
+
+        let targets =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::default())
+                .unwrap();
+        let names: Vec<&str> = targets[0]
+            .required_vars
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect();
+
+        // Branches that depend on make (`$(shell ...)`) still count
+        assert_eq!(names, vec!["PROFILE", "LINUX_SDK", "BINARY", "IDENTITY"]);
+    }
+
+    #[test]
+    fn test_rule_inside_conditional_keeps_its_vars() {
+        let content = "ifdef CI\ndeploy:\n\t./deploy.sh $(TOKEN)\nendif\n";
+
+        let targets =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::default())
+                .unwrap();
+
+        assert_eq!(targets[0].required_vars.len(), 1);
+        assert_eq!(targets[0].required_vars[0].name, "TOKEN");
+    }
+
+    #[test]
+    fn test_untaken_depths() {
+        let lines = [
+            "MODE ?= release",
+            "ifeq ($(MODE),debug)",
+            "\tdebug",
+            "ifdef MODE",
+            "\tnested",
+            "endif",
+            "else",
+            "\trelease",
+            "endif",
+            "ifeq \"$(MODE)\" \"release\"",
+            "\tquoted",
+            "endif",
+        ];
+
+        assert_eq!(
+            untaken_depths(&lines),
+            vec![0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_detect_sudo() {
+        assert!(uses_sudo(&["@sudo cp maki /usr/local/bin"]));
+        assert!(uses_sudo(&["cargo build", "cd dist && sudo make install"]));
+        assert!(uses_sudo(&["$(shell sudo -n true)"]));
+        assert!(!uses_sudo(&["echo pseudocode", "./no-sudo.sh"]));
+
+        let content =
+            "install:\nifdef PREFIX\n\tsudo cp maki $(PREFIX)\nendif\nbuild:\n\tcargo build\n";
+        let targets =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::default())
+                .unwrap();
+        assert!(targets[0].needs_sudo);
+        assert!(!targets[1].needs_sudo);
+    }
+
+    #[test]
+    fn test_self_documenting_help_is_hidden() {
+        let content = r#"build: ## Build it
+	cargo build
+
+help:
+	@awk 'BEGIN {FS = ":.*##"} /^[a-zA-Z_-]+:.*##/ { printf "%-15s %s\n", $$1, $$2 }' $(MAKEFILE_LIST)
+"#;
+        let names = |options: &ParseOptions| -> Vec<String> {
+            parse_makefile_content(content, Path::new("Makefile"), options)
+                .unwrap()
+                .into_iter()
+                .map(|t| t.name)
+                .collect()
+        };
+        assert_eq!(names(&ParseOptions::default()), ["build"]);
+        assert_eq!(names(&ParseOptions::everything()), ["build", "help"]);
+        // make sets MAKEFILE_LIST itself
+        let help =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::everything())
+                .unwrap()
+                .remove(1);
+        assert!(help.required_vars.is_empty());
+
+        // A help that says something of its own is a target like any other
+        assert!(!prints_help("help", &["@echo 'make build: build it'"]));
+        assert!(prints_help("help", &["@grep -E '##' Makefile"]));
+        assert!(!prints_help("usage", &["@grep -E '##' $(MAKEFILE_LIST)"]));
+    }
+
+    #[test]
+    fn test_serial_targets() {
+        let content = r#".NOTPARALLEL: migrate
+# Migrate the database
+migrate:
+	./migrate.sh
+
+## @serial
+# Deploy it
+deploy:
+	./deploy.sh
+
+bench: ## Benchmark @serial
+	cargo bench
+
+build:
+	cargo build
+"#;
+        let targets =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::default())
+                .unwrap();
+        let serial: Vec<(&str, bool)> = targets
+            .iter()
+            .map(|t| (t.name.as_str(), t.serial))
+            .collect();
+
+        assert_eq!(
+            serial,
+            vec![
+                (".NOTPARALLEL", false),
+                ("migrate", true),
+                ("deploy", true),
+                ("bench", true),
+                ("build", false),
+            ]
+        );
+        assert_eq!(targets[2].description, Some("Deploy it".to_string()));
+        assert_eq!(targets[3].description, Some("Benchmark".to_string()));
+
+        let content = ".NOTPARALLEL:
+build:
+	cargo build
+";
+        let targets =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::default())
+                .unwrap();
+        assert!(targets.iter().all(|t| t.serial));
+    }
+
+    #[test]
+    fn test_define_blocks_are_not_targets() {
+        let content = r#"define HELP
+usage: make build
+build: does the build
+include other.mk
+endef # HELP
+
+override define SCRIPT :=
+	deploy: ./run.sh
+endef
+
+export HELP
+ENDPOINT := $(if $(HELP),yes)
+
+build:
+	@echo "$$HELP"
+"#;
+        let info = parse_content(
+            content,
+            Path::new("Makefile"),
+            &ParseOptions::default(),
+            &HashMap::new(),
+        )
+        .unwrap();
+        let names: Vec<&str> = info.targets.iter().map(|t| t.name.as_str()).collect();
+
+        assert_eq!(names, vec!["build"]);
+        assert_eq!(info.targets[0].line, 14);
+        assert_eq!(info.rule_names, vec!["build"]);
+        assert!(info.includes.is_empty());
+        assert_eq!(define_name("define SCRIPT :="), Some("SCRIPT"));
+        assert_eq!(define_name("defined:"), None);
+        assert!(is_endef("endef"));
+        assert!(!is_endef("endefs:"));
+    }
+
+    #[test]
+    fn test_phony_targets() {
+        let content = r#".PHONY: build \
+	test # the commands
+build: out/app
+out/app:
+	cargo build
+test:
+	cargo test
+.PHONY: lint
+lint:
+	ruff .
+"#;
+        let targets =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::default())
+                .unwrap();
+        let phony: Vec<(&str, bool)> = targets
+            .iter()
+            .map(|t| (t.name.as_str(), t.is_phony))
+            .collect();
+
+        assert_eq!(
+            phony,
+            vec![
+                (".PHONY", false),
+                ("build", true),
+                ("out/app", false),
+                ("test", true),
+                ("lint", true),
+            ]
+        );
+
+        // `.PHONY` in an included file covers the including file's rules
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join("phony.mk"), ".PHONY: deploy\n").unwrap();
+        let makefile = dir.path().join("Makefile");
+        fs::write(&makefile, "include phony.mk\ndeploy:\n\t./deploy.sh\n").unwrap();
+        let info = parse_makefile_info(&makefile, &ParseOptions::default()).unwrap();
+        let deploy = info.targets.iter().find(|t| t.name == "deploy").unwrap();
+        assert!(deploy.is_phony);
+    }
+
+    #[test]
+    fn test_latin1_makefile_parses_with_warning() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("Makefile");
+        // "Générer" in Latin-1, as older editors saved it
+        fs::write(
+            &path,
+            b"all: build\n\n# G\xe9n\xe9rer le binaire\nbuild:\n\tcc main.c\n",
+        )
+        .unwrap();
+
+        let info = parse_makefile_info(&path, &ParseOptions::default()).unwrap();
+
+        let names: Vec<&str> = info.targets.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["all", "build"]);
+        assert_eq!(
+            info.targets[1].description,
+            Some("Générer le binaire".to_string())
+        );
+        assert_eq!(
+            info.warnings,
+            vec![format!(
+                "{} is not UTF-8 (line 3); read it as Latin-1",
+                path.display()
+            )]
+        );
+
+        fs::write(&path, "build:\n\tcc main.c\n").unwrap();
+        let info = parse_makefile_info(&path, &ParseOptions::default()).unwrap();
+        assert!(info.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_windows_line_endings_and_bom() {
+        let content = "\u{feff}# Build it\r\nbuild: gen\r\n\t@echo $(OUT)\r\n\r\n\
+                       gen: ## Generate\r\n\t@echo gen\r\n";
+        let unix = "# Build it\nbuild: gen\n\t@echo $(OUT)\n\ngen: ## Generate\n\t@echo gen\n";
+        let options = ParseOptions::default();
+
+        let targets = parse_makefile_content(content, Path::new("Makefile"), &options).unwrap();
+        let expected = parse_makefile_content(unix, Path::new("Makefile"), &options).unwrap();
+        assert_eq!(targets, expected);
+        assert_eq!(targets[0].name, "build");
+        assert_eq!(targets[0].description, Some("Build it".to_string()));
+        assert_eq!(targets[0].deps, vec!["gen"]);
+        assert_eq!(targets[1].description, Some("Generate".to_string()));
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("Makefile");
+        fs::write(&path, content).unwrap();
+        let (text, warning) = read_makefile(&path).unwrap();
+        assert_eq!(text, unix);
+        assert_eq!(warning, None);
+
+        // A BOM doesn't stop an otherwise Latin-1 file being read as one
+        fs::write(&path, b"\xef\xbb\xbf# G\xe9n\xe9rer\r\nbuild:\r\n").unwrap();
+        let (text, warning) = read_makefile(&path).unwrap();
+        assert_eq!(text, "# Générer\nbuild:\n");
+        assert!(warning.is_some());
+    }
+
+    #[test]
+    fn test_menu_annotations() {
+        let content = r#"## @name Deploy to production
+# Ship the release
+deploy-prod:
+	./deploy.sh prod
+
+## @hidden
+# Used by deploy-prod
+upload: ## Upload the bundle
+	./upload.sh
+
+release: ## Tag a release @name Cut a release
+	./release.sh
+"#;
+        let targets =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::everything())
+                .unwrap();
+
+        assert_eq!(targets[0].label, Some("Deploy to production".to_string()));
+        assert_eq!(targets[0].description, Some("Ship the release".to_string()));
+        assert!(!targets[0].hidden);
+        assert!(targets[1].hidden);
+        assert_eq!(targets[1].label, None);
+        assert_eq!(
+            targets[1].description,
+            Some("Upload the bundle".to_string())
+        );
+        assert_eq!(targets[2].label, Some("Cut a release".to_string()));
+        assert_eq!(targets[2].description, Some("Tag a release".to_string()));
+
+        let names: Vec<String> =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::default())
+                .unwrap()
+                .into_iter()
+                .map(|t| t.name)
+                .collect();
+        assert_eq!(names, vec!["deploy-prod", "release"]);
+    }
+
+    #[test]
+    fn test_ready_annotation() {
+        let content = r#"## @ready http://localhost:8080/health
+# Start the dev server
+serve:
+	./server --port 8080
+
+db: ## Start the database @ready tcp://localhost:5432 in docker
+	docker compose up db
+"#;
+        let targets =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::default())
+                .unwrap();
+
+        assert_eq!(
+            targets[0].ready,
+            Some("http://localhost:8080/health".to_string())
+        );
+        assert_eq!(
+            targets[0].description,
+            Some("Start the dev server".to_string())
+        );
+        assert_eq!(targets[1].ready, Some("tcp://localhost:5432".to_string()));
+        assert_eq!(
+            targets[1].description,
+            Some("Start the database in docker".to_string())
+        );
+    }
+
+    #[test]
+    fn test_tags_annotation() {
+        let content = r#"## @tags docker,release
+# Build the image
+image: ## Build it @tags docker,ci
+	docker build .
+
+lint: ## Lint the code
+	cargo clippy
+"#;
+        let targets =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::default())
+                .unwrap();
+
+        assert_eq!(targets[0].tags, vec!["docker", "ci", "release"]);
+        assert_eq!(targets[0].description, Some("Build it".to_string()));
+        assert!(targets[0].has_tag("Docker"));
+        assert_eq!(targets[0].tag_chips(), "#docker #ci #release");
+        assert!(targets[1].tags.is_empty());
+    }
+
+    #[test]
+    fn test_secret_annotations() {
+        let content = r#"## Deploy it @secret TOKEN pass show deploy/token
+## @secret PASSWORD
+deploy:
+	./deploy.sh --token $(TOKEN) $(REGION)
+"#;
+        let targets =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::default())
+                .unwrap();
+        let vars = &targets[0].required_vars;
+
+        assert_eq!(targets[0].description, Some("Deploy it".to_string()));
+        assert_eq!(vars.len(), 3);
+        assert_eq!(vars[0].name, "TOKEN");
+        assert_eq!(
+            vars[0].secret,
+            Some(Secret {
+                command: Some("pass show deploy/token".to_string())
+            })
+        );
+        assert!(!vars[1].is_secret());
+        // Only the recipe's scripts read it
+        assert_eq!(vars[2].name, "PASSWORD");
+        assert_eq!(vars[2].secret, Some(Secret::default()));
+    }
+
+    #[test]
+    fn test_required_vars_keep_documented_order() {
+        let content = r#"
+# Release a new version
+# usage: make release V=patch|minor|major CHANNEL=beta|stable
+release: ## also takes DRY_RUN=1
+	./release.sh $(TOKEN) $(CHANNEL) $(V) $(AUTH)
+"#;
+
+        let targets =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::default())
+                .unwrap();
+        let names: Vec<&str> = targets[0]
+            .required_vars
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect();
+
+        assert_eq!(names, vec!["V", "CHANNEL", "DRY_RUN", "TOKEN", "AUTH"]);
+    }
+
+    #[test]
+    fn test_skip_required_vars_defined_in_makefile() {
+        let content = r#"
+VERSION := 1.2.3
+export REGISTRY ?= ghcr.io
+COMMIT != git rev-parse HEAD
+
+# Deploy (usage: make deploy ENV=dev|staging|prod)
+deploy:
+	@echo "Deploying $(VERSION) from $(COMMIT) to $(ENV) via $(REGISTRY) as $(USER_NAME)"
+
+ENV ?= staging
+
+# Release (usage: make release CHANNEL=beta|stable)
+release:
+	@echo "$(CHANNEL) $(BUILD_ID)"
+
+release: CHANNEL := stable
+
+define BUILD_ID
+$(shell date +%s)
+endef
+"#;
+
+        let options = ParseOptions::default();
+        let targets = parse_makefile_content(content, Path::new("Makefile"), &options).unwrap();
+
+        // Defined variables without a hint aren't prompted for at all
+        let deploy = targets.iter().find(|t| t.name == "deploy").unwrap();
+        let names: Vec<&str> = deploy
+            .required_vars
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["ENV", "USER_NAME"]);
+
+        // Documented ones are, defaulting to the Makefile's value
+        assert_eq!(deploy.required_vars[0].default, Some("staging".to_string()));
+        assert_eq!(deploy.required_vars[1].default, None);
+
+        // A target-specific assignment counts too
+        let release = targets.iter().find(|t| t.name == "release").unwrap();
+        assert_eq!(release.required_vars.len(), 1);
+        assert_eq!(release.required_vars[0].name, "CHANNEL");
+        assert_eq!(release.required_vars[0].default, Some("stable".to_string()));
+    }
+
+    #[test]
+    fn test_export_lines() {
+        let content = "export\tREGION ?= eu
+override export LEVEL = 3
+export TOKEN HOME_DIR
+unexport HOME_DIR DEBUG
+export $(VARS)
+
+deploy:
+\t./deploy.sh $(REGION) $(LEVEL) $(TOKEN) $(HOME_DIR) $(DEBUG)
+";
+
+        let targets =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::default())
+                .unwrap();
+        assert_eq!(targets.len(), 1);
+        let names: Vec<&str> = targets[0]
+            .required_vars
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["TOKEN", "HOME_DIR", "DEBUG"]);
+        assert_eq!(targets[0].exported, vec!["REGION", "LEVEL", "TOKEN"]);
+
+        assert_eq!(parse_export("export A B"), Some((true, vec!["A", "B"])));
+        assert_eq!(parse_export("unexport A"), Some((false, vec!["A"])));
+        assert_eq!(parse_export("export A := 1"), None);
+        assert_eq!(parse_export("exports: build"), None);
+    }
+
+    #[test]
+    fn test_detect_aggregate_targets() {
+        let content = "\
+.PHONY: all build test
+all: build test
+
+build:
+\tcargo build
+
+# Documented later rule adds the recipe
+check: build
+check:
+\tcargo check
+
+test: ; cargo test
+%.o: %.c
+
+# The recipe starts inside a conditional
+cover: build
+ifeq ($(COVER),1)
+	@echo cover
+endif
+	@echo test
+";
+        let targets =
+            parse_makefile_content(content, Path::new("Makefile"), &ParseOptions::everything())
+                .unwrap();
+        let aggregates: Vec<&str> = targets
+            .iter()
+            .filter(|t| t.aggregate)
+            .map(|t| t.name.as_str())
+            .collect();
+
+        assert_eq!(aggregates, vec!["all"]);
+    }
+
+    #[test]
+    fn test_recipe_lines() {
+        let lines = vec![
+            "build: deps \\",
+            "  more ; echo inline",
+            "\tcargo build",
+            "",
+            "# a comment",
+            "\tcargo test",
+            "next:",
+            "\techo next",
+        ];
+
+        assert_eq!(recipe_lines(&lines, 0), vec!["cargo build", "cargo test"]);
+        let lines = vec![
+            "clean:",
+            "\techo start",
+            "ifeq ($(OS),Windows_NT)",
+            "\tdel /q out",
+            "else",
+            "\trm -rf out",
+            "endif",
+            "\techo done",
+            "ifdef CI",
+            "deploy:",
+        ];
+        assert_eq!(
+            recipe_lines(&lines, 0),
+            vec!["echo start", "del /q out", "rm -rf out", "echo done"]
+        );
+        assert!(recipe_is_conditional(&lines, 0));
+        // A conditional after the recipe holds other rules
+        assert!(!recipe_is_conditional(&lines[7..], 0));
+        assert_eq!(
+            recipe_lines(&["x: ; echo hi ## Say hi"], 0),
+            vec!["echo hi ## Say hi"]
+        );
+        assert!(recipe_lines(&["all: a b ## Everything; really"], 0).is_empty());
+    }
+
+    #[test]
+    fn test_recipe_plan() {
+        let lines = vec![
+            "deploy:",
+            "\t@echo Deploying",
+            "\t-rm -rf dist",
+            "\t@-./upload.sh \\",
+            "\t    --all",
+            "\t+$(MAKE) notify",
+        ];
+        let plan = |silent: bool| -> Vec<(String, bool)> {
+            recipe_plan(&lines, 0, silent, false)
+                .into_iter()
+                .map(|l| (l.text, l.silent))
+                .collect()
+        };
+        let line = |text: &str, silent: bool| (text.to_string(), silent);
+
+        assert_eq!(
+            plan(false),
+            vec![
+                line("echo Deploying", true),
+                line("rm -rf dist", false),
+                line("./upload.sh \\", true),
+                line("    --all", true),
+                line("$(MAKE) notify", false),
+            ]
+        );
+        assert!(plan(true).iter().all(|(_, silent)| *silent));
+    }
+
+    #[test]
+    fn test_oneshell_plan() {
+        let lines = vec![
+            "release:",
+            "	@cd dist",
+            "	-rm -f *.tmp",
+            "	for f in *; do \\",
+            "	-  echo $$f; \\",
+            "	done",
+        ];
+        assert_eq!(
+            recipe_plan(&lines, 0, false, true),
+            vec![RecipeLine {
+                text: "cd dist\nrm -f *.tmp\nfor f in *; do \\\n-  echo $$f; \\\ndone".to_string(),
+                silent: true,
+            }]
+        );
+        assert!(recipe_plan(&["clean:"], 0, false, true).is_empty());
+
+        assert!(is_oneshell("CC = cc\n.ONESHELL:\nbuild:\n"));
+        assert!(is_oneshell(".ONESHELL :"));
+        assert!(!is_oneshell("# .ONESHELL:\n.ONESHELLS = 1\n"));
+    }
+
+    #[test]
+    fn test_silenced() {
+        let silenced = Silenced::of(".SILENT: lint fmt # quiet ones\nbuild:\n");
+        assert!(silenced.covers("lint") && silenced.covers("fmt"));
+        assert!(!silenced.covers("build"));
+
+        assert!(Silenced::of(".SILENT:\n").covers("build"));
+        assert!(Silenced::of("MAKEFLAGS += --no-print-directory -rs\n").covers("build"));
+        assert!(!Silenced::of("MAKEFLAGS += --no-print-directory\n").covers("build"));
+        assert!(!Silenced::of("SILENT_TARGETS = x\n").covers("x"));
+    }
+
+    #[test]
+    fn test_expand_aggregate() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("Makefile");
+        fs::write(
+            &path,
+            "release: all docs\nall: build test\nbuild:\n\tcargo build\ntest:\n\tcargo test\n",
+        )
+        .unwrap();
+        let targets = parse_makefile(&path, &ParseOptions::everything()).unwrap();
+        let release = targets.iter().find(|t| t.name == "release").unwrap();
+
+        let expansion = expand_aggregate(release, &targets);
+        let steps: Vec<(&str, usize, Option<Vec<String>>)> = expansion
+            .iter()
+            .map(|e| {
+                let recipe = e.recipe.as_ref().map(|r| r.iter().map(|l| l.text.clone()));
+                (e.name.as_str(), e.depth, recipe.map(Iterator::collect))
+            })
+            .collect();
+
+        assert_eq!(
+            steps,
+            vec![
+                ("all", 0, Some(vec![])),
+                ("build", 1, Some(vec!["cargo build".to_string()])),
+                ("test", 1, Some(vec!["cargo test".to_string()])),
+                ("docs", 0, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_define_variable_follows_make_semantics() {
+        let mut defined = HashMap::new();
+        let assign = |line: &str| parse_assignment(line, 1).unwrap();
+
+        define_variable(&mut defined, &assign("FLAGS := -O2"));
+        define_variable(&mut defined, &assign("FLAGS ?= -O0"));
+        define_variable(&mut defined, &assign("FLAGS += -g"));
+        define_variable(&mut defined, &assign("OUT = $(BUILD)/out"));
+
+        assert_eq!(defined["FLAGS"], Some("-O2 -g".to_string()));
+        assert_eq!(defined["OUT"], None);
+    }
+
+    #[test]
+    fn test_extract_prerequisites() {
+        let content = r#"
+deploy: build test ## Ship it
+	./deploy.sh
+
+build:: gen ; cargo build
+
+app: main.o | out dist ## Link it
+	cc -o out/app main.o
+
+all: build \
+     test \
+app | out
+"#;
+
+        let options = ParseOptions::default();
+        let targets = parse_makefile_content(content, Path::new("Makefile"), &options).unwrap();
+
+        let deploy = targets.iter().find(|t| t.name == "deploy").unwrap();
+        assert_eq!(deploy.deps, vec!["build", "test"]);
+
+        let build = targets.iter().find(|t| t.name == "build").unwrap();
+        assert_eq!(build.deps, vec!["gen"]);
+        assert!(build.order_only.is_empty());
+
+        let app = targets.iter().find(|t| t.name == "app").unwrap();
+        assert_eq!(app.deps, vec!["main.o"]);
+        assert_eq!(app.order_only, vec!["out", "dist"]);
+
+        // A header continued with `\` lists the prerequisites of every line
+        let all = targets.iter().find(|t| t.name == "all").unwrap();
+        assert_eq!(all.deps, vec!["build", "test", "app"]);
+        assert_eq!(all.order_only, vec!["out"]);
+    }
+
+    #[test]
+    fn test_parse_vpath_directives() {
+        let content = r#"
+VPATH = src:include
+vpath %.h include headers
+vpath %.c
+
+build: main.c
+	cc main.c
+"#;
+
+        let options = ParseOptions::default();
+        let info = parse_makefile_info_content(content, Path::new("Makefile"), &options).unwrap();
+
+        assert_eq!(info.targets.len(), 1);
+        assert_eq!(
+            info.vpath,
+            vec![
+                VpathDirective {
+                    pattern: None,
+                    dirs: vec!["src".to_string(), "include".to_string()],
+                    line: 2,
+                },
+                VpathDirective {
+                    pattern: Some("%.h".to_string()),
+                    dirs: vec!["include".to_string(), "headers".to_string()],
+                    line: 3,
+                },
+                VpathDirective {
+                    pattern: Some("%.c".to_string()),
+                    dirs: vec![],
+                    line: 4,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pattern_matches() {
+        assert!(pattern_matches("%.c", "main.c"));
+        assert!(pattern_matches("lib%.a", "libfoo.a"));
+        assert!(!pattern_matches("%.c", "main.h"));
+        assert!(pattern_matches("exact", "exact"));
+    }
+
+    #[test]
+    fn test_reparse_reuses_unchanged_blocks() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("Makefile");
+        fs::write(
+            &path,
+            "# Build it\nbuild:\n\tcc $(SRC)\n\ntest: build\n\t./run\n\n# Ship it\ndeploy:\n\tscp $(HOST)\n",
+        )
+        .unwrap();
+        let first = parse_makefile_info(&path, &ParseOptions::default()).unwrap();
+        assert_eq!(first.blocks.len(), 3);
+
+        // Lines added above move every rule, and deploy's recipe changes
+        fs::write(
+            &path,
+            "# Generated, do not edit\n\nSRC = main.c\n\n# Build it\nbuild:\n\tcc $(SRC)\n\ntest: build\n\t./run\n\n# Ship it, carefully\ndeploy:\n\tscp $(HOST) $(DEST)\n",
+        )
+        .unwrap();
+        let second = reparse_makefile(&path, &ParseOptions::default(), &first.blocks).unwrap();
+        assert_eq!(second.reused, 2);
+        assert_eq!(
+            second.targets,
+            parse_makefile(&path, &ParseOptions::default()).unwrap()
+        );
+
+        let build = &second.targets[0];
+        assert_eq!(build.line, 6);
+        // SRC is now set by the Makefile, which is worked out again
+        assert!(build.required_vars.is_empty());
+        let deploy = &second.targets[2];
+        assert_eq!(deploy.description.as_deref(), Some("Ship it, carefully"));
+        let names: Vec<&str> = deploy
+            .required_vars
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["HOST", "DEST"]);
+    }
+
+    #[test]
+    fn test_resolve_prerequisite_via_global_vpath() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("include")).unwrap();
+        fs::write(dir.path().join("include/config.h"), "").unwrap();
+
+        let info = MakefileInfo {
+            rule_names: vec!["build".to_string()],
+            vpath: vec![VpathDirective {
+                pattern: None,
+                dirs: vec!["src".to_string(), "include".to_string()],
+                line: 1,
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            resolve_prerequisite("config.h", &info, dir.path()),
+            Prerequisite::File(dir.path().join("include").join("config.h"))
+        );
+        assert_eq!(
+            resolve_prerequisite("build", &info, dir.path()),
+            Prerequisite::Target
+        );
+        assert_eq!(
+            resolve_prerequisite("nope.h", &info, dir.path()),
+            Prerequisite::Missing
+        );
+    }
 }
@@ -0,0 +1,563 @@
+use clap::{Arg, Command};
+use clap_mangen::Man;
+use clap_mangen::roff::{Roff, bold, italic, roman};
+use std::io;
+
+/// Example invocations for each subcommand, shown in `--help-all` and the
+/// man page; `""` holds the ones for plain `maki`
+const EXAMPLES: &[(&str, &[(&str, &str)])] = &[
+    (
+        "",
+        &[
+            ("Pick a target in the fuzzy finder and run it", "maki"),
+            ("Pick from a custom Makefile", "maki -f build/Makefile"),
+            ("Copy the picked target's command", "maki --copy"),
+        ],
+    ),
+    ("pick", &[("Open the picker explicitly", "maki pick")]),
+    (
+        "list",
+        &[
+            ("List every target with its description", "maki list"),
+            ("Group namespaced targets into a tree", "maki list --tree"),
+            ("Only the targets tagged docker", "maki list --tag docker"),
+            ("Only the .PHONY targets", "maki list --phony-only"),
+            (
+                "Number the targets, for maki 3 or maki run @3",
+                "maki list --numbered",
+            ),
+            ("Machine-readable output", "maki list --json"),
+            (
+                "Inspect a project's targets before cloning it",
+                "maki list --repo https://github.com/org/project --ref main",
+            ),
+        ],
+    ),
+    (
+        "run",
+        &[
+            (
+                "Run a target, prompting for its variables",
+                "maki run deploy",
+            ),
+            ("Set a variable up front", "maki run deploy ENV=prod"),
+            (
+                "Run the third target of the last numbered list",
+                "maki run @3",
+            ),
+            (
+                "Run the test target of a Makefile below the project",
+                "maki -r run services/api:test",
+            ),
+            (
+                "Set it in the environment of the run instead",
+                "maki --var ENV=prod run deploy",
+            ),
+            (
+                "Re-run a flaky test until it fails",
+                "maki run test --until-failure",
+            ),
+            (
+                "Check whether a file target is up to date",
+                "maki run dist.tar.gz --question",
+            ),
+            (
+                "Run several targets at once, with a live dashboard",
+                "maki run lint test docs --parallel",
+            ),
+            (
+                "Keep a dev server running in the background",
+                "maki run serve --detach",
+            ),
+            (
+                "Start a server, then test against it once it is up",
+                "maki run serve --wait-ready && maki run e2e",
+            ),
+        ],
+    ),
+    (
+        "ps",
+        &[("List the targets running in the background", "maki ps")],
+    ),
+    (
+        "attach",
+        &[("Follow a background target's output", "maki attach serve")],
+    ),
+    (
+        "stop",
+        &[
+            ("Stop a background target", "maki stop serve"),
+            ("Stop them all", "maki stop --all"),
+        ],
+    ),
+    (
+        "show",
+        &[(
+            "Show a target's prerequisites and variables",
+            "maki show deploy",
+        )],
+    ),
+    (
+        "help-target",
+        &[(
+            "Run the Makefile's own help, which maki hides from lists",
+            "maki help-target",
+        )],
+    ),
+    (
+        "note",
+        &[
+            (
+                "Leave yourself a note on a target",
+                "maki note deploy \"needs VPN connected\"",
+            ),
+            ("Remove it again", "maki note deploy --remove"),
+        ],
+    ),
+    (
+        "queue",
+        &[
+            ("Queue targets to run later", "maki queue add build test"),
+            ("Queue targets from the picker", "maki --queue"),
+            ("Run the queue, in order", "maki queue run"),
+        ],
+    ),
+    (
+        "batch",
+        &[
+            ("Run the targets listed in a file", "maki batch release.txt"),
+            (
+                "Run every line, even after one fails",
+                "maki batch -k nightly.txt",
+            ),
+            (
+                "Read the targets from stdin",
+                "echo 'deploy ENV=staging' | maki batch -",
+            ),
+        ],
+    ),
+    (
+        "export",
+        &[
+            (
+                "Start a GitHub Actions workflow",
+                "maki export ci > .github/workflows/ci.yml",
+            ),
+            (
+                "Start a GitLab pipeline for some targets",
+                "maki export ci --format gitlab lint test > .gitlab-ci.yml",
+            ),
+            (
+                "Start a Makefile from the project's Cargo.toml or package.json",
+                "maki export makefile --write",
+            ),
+        ],
+    ),
+    (
+        "usage",
+        &[
+            ("Share your run counts with the team", "maki usage export"),
+            ("See what the team runs most", "maki usage show"),
+        ],
+    ),
+    (
+        "suggest",
+        &[(
+            "Pick among targets related to changed files",
+            "maki suggest",
+        )],
+    ),
+    (
+        "overview",
+        &[("Summarise an unfamiliar project", "maki overview")],
+    ),
+    (
+        "diff",
+        &[
+            (
+                "See what a branch changes in the targets",
+                "maki diff --ref main",
+            ),
+            (
+                "Compare every Makefile, as JSON",
+                "maki diff --ref v1.2.0 -r --json",
+            ),
+        ],
+    ),
+    (
+        "query",
+        &[
+            (
+                "Targets under docker/ that need variables",
+                "maki query name='docker/*' has_required_vars=true",
+            ),
+            (
+                "The same as a JSON document",
+                "maki query '{\"category\": \"docker\", \"provider\": \"make\"}'",
+            ),
+        ],
+    ),
+    (
+        "providers",
+        &[("See what each provider found", "maki providers --no-cache")],
+    ),
+    ("history", &[("Show the last 5 runs", "maki history -n 5")]),
+    (
+        "errors",
+        &[
+            (
+                "Run a target, collecting its errors",
+                "maki run build --errors",
+            ),
+            ("Open the first error in $EDITOR", "maki errors"),
+            ("List the collected errors", "maki errors --list"),
+            ("Open the third one", "maki errors 3"),
+        ],
+    ),
+    (
+        "replay",
+        &[
+            ("Record a run of a target", "maki run test --record"),
+            ("Play back the latest recording", "maki replay"),
+            (
+                "Play back one run, twice as fast",
+                "maki replay 20260101-120000-test --speed 2",
+            ),
+        ],
+    ),
+    (
+        "schema",
+        &[(
+            "Print the schema of maki list --json",
+            "maki schema targets",
+        )],
+    ),
+    (
+        "cache",
+        &[
+            ("Show cache size and evictions", "maki cache stats"),
+            ("Clear the cache", "maki cache clear"),
+        ],
+    ),
+    (
+        "config",
+        &[
+            ("See where each setting comes from", "maki config --origin"),
+            (
+                "Export the project settings",
+                "maki config export team.toml",
+            ),
+            (
+                "Import them, taking the bundle's values",
+                "maki config import team.toml --prefer bundle",
+            ),
+        ],
+    ),
+    (
+        "setup",
+        &[("Answer a few questions to set up maki here", "maki setup")],
+    ),
+    (
+        "hook",
+        &[("Install the zsh hook", "eval \"$(maki hook zsh)\"")],
+    ),
+    ("open", &[("Open the Makefile in $EDITOR", "maki open")]),
+    (
+        "validate",
+        &[
+            ("Check the Makefiles, e.g. in CI", "maki validate"),
+            ("Review the fixes, then write them", "maki validate --fix"),
+        ],
+    ),
+    (
+        "refactor",
+        &[(
+            "Rename a target everywhere",
+            "maki refactor rename build compile",
+        )],
+    ),
+    (
+        "man",
+        &[(
+            "Install the man page",
+            "maki man > /usr/local/share/man/man1/maki.1",
+        )],
+    ),
+    (
+        "version",
+        &[
+            ("Show the version and how it was built", "maki version"),
+            (
+                "Check the version in a script",
+                "maki version --json | jq -r .version",
+            ),
+        ],
+    ),
+];
+
+/// Examples for the subcommand `name` (`""` for plain `maki`)
+fn examples(name: &str) -> &'static [(&'static str, &'static str)] {
+    EXAMPLES
+        .iter()
+        .find(|(command, _)| *command == name)
+        .map(|(_, examples)| *examples)
+        .unwrap_or_default()
+}
+
+/// The help of maki and of every subcommand, with examples, for `--help-all`
+///
+/// Subcommands are rendered on their own, so the global options are listed
+/// once, in maki's own help, rather than under every command.
+pub fn help_all(cmd: Command) -> String {
+    let mut out = String::new();
+    let bin_name = cmd.get_name().to_string();
+    help_into(cmd, &bin_name, "", &mut out);
+    out
+}
+
+fn help_into(cmd: Command, bin_name: &str, name: &str, out: &mut String) {
+    if !name.is_empty() {
+        out.push_str(&format!("\n{}\n\n", "─".repeat(60)));
+    }
+    let mut cmd = cmd.bin_name(bin_name);
+    if !name.is_empty() {
+        cmd = cmd.mut_args(|arg| {
+            let global = arg.is_global_set();
+            arg.hide(global)
+        });
+    }
+    out.push_str(&cmd.render_long_help().to_string());
+
+    let examples = examples(name);
+    if !examples.is_empty() {
+        out.push_str("\nExamples:\n");
+        for (description, example) in examples {
+            out.push_str(&format!("  # {}\n  {}\n", description, example));
+        }
+    }
+
+    for sub in visible_subcommands(&cmd) {
+        let full_name = if name.is_empty() {
+            sub.get_name().to_string()
+        } else {
+            format!("{} {}", name, sub.get_name())
+        };
+        help_into(
+            sub.clone(),
+            &format!("{} {}", bin_name, sub.get_name()),
+            &full_name,
+            out,
+        );
+    }
+}
+
+/// Where maki keeps what it reads and writes, for the FILES section
+const FILES: [(&str, &str); 5] = [
+    (
+        "maki.toml",
+        "Shared project settings, in the working directory (read as .maki.toml too).",
+    ),
+    (
+        ".maki.local.toml",
+        "Personal overrides of maki.toml, kept out of version control.",
+    ),
+    (
+        "~/.cache/maki/maki_cache.json",
+        "Parsed targets (the platform cache directory elsewhere).",
+    ),
+    (
+        "~/.local/share/maki/history.jsonl",
+        "Run history (the platform data directory elsewhere).",
+    ),
+    (
+        "~/.local/share/maki/notes.json",
+        "Notes on targets (the platform data directory elsewhere).",
+    ),
+];
+
+/// The variables maki reads, for the ENVIRONMENT section
+const ENVIRONMENT: [(&str, &str); 4] = [
+    ("EDITOR", "The editor for --edit and maki open."),
+    (
+        "NO_COLOR",
+        "Turns colors off when set to a non-empty value.",
+    ),
+    (
+        "CI",
+        "Turns colors off when set, unless --color always is given.",
+    ),
+    (
+        "MAKI_<SETTING>",
+        "Overrides a setting, naming its key with __ between tables, e.g. MAKI_CACHE__MAX_ENTRIES=200.",
+    ),
+];
+
+const DESCRIPTION: &str = "Finds the targets of the Makefiles, Procfile, and docker compose file in \
+    the working directory and lets you pick one in a fuzzy finder, prompting for the variables it \
+    needs before running it. Without a command, the picker opens.";
+
+/// The `maki.1` man page, in roff
+pub fn man_page(cmd: Command) -> io::Result<Vec<u8>> {
+    let mut pages = man_pages(cmd)?;
+    Ok(pages.swap_remove(0).1)
+}
+
+/// Every man page with its file name: `maki.1`, then one per subcommand,
+/// like `maki-cache-stats.1`
+pub fn man_pages(cmd: Command) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut cmd = cmd.disable_help_subcommand(true);
+    cmd.build();
+    let mut pages = Vec::new();
+    let source = format!("maki {}", cmd.get_version().unwrap_or_default());
+    pages_into(&cmd, "", &source, &mut pages)?;
+    Ok(pages)
+}
+
+/// The page of `cmd`, the subcommand `name` (`""` for maki itself), then
+/// those of its subcommands
+fn pages_into(
+    cmd: &Command,
+    name: &str,
+    source: &str,
+    pages: &mut Vec<(String, Vec<u8>)>,
+) -> io::Result<()> {
+    let top = name.is_empty();
+    // Global options are described once, on maki's own page
+    let page_cmd = match top {
+        true => cmd.clone(),
+        false => cmd.clone().mut_args(|arg| {
+            let global = arg.is_global_set();
+            arg.hide(global)
+        }),
+    };
+    let man = Man::new(page_cmd.clone()).source(source);
+    let mut page = Vec::new();
+    man.render_title(&mut page)?;
+    man.render_name_section(&mut page)?;
+    man.render_synopsis_section(&mut page)?;
+    match top {
+        true => Roff::new()
+            .control("SH", ["DESCRIPTION"])
+            .text([roman(DESCRIPTION)])
+            .to_writer(&mut page)?,
+        false => man.render_description_section(&mut page)?,
+    }
+    if visible_args(&page_cmd).next().is_some() {
+        man.render_options_section(&mut page)?;
+    }
+    if visible_subcommands(&page_cmd).next().is_some() {
+        man.render_subcommands_section(&mut page)?;
+    }
+
+    let mut roff = Roff::new();
+    let examples = examples(name);
+    if !examples.is_empty() {
+        roff.control("SH", ["EXAMPLES"]);
+        for (description, example) in examples {
+            roff.control("TP", []);
+            roff.text([roman(*description)]);
+            roff.text([bold(*example)]);
+        }
+    }
+    if top {
+        roff.control("SH", ["FILES"]);
+        for (file, description) in FILES {
+            roff.control("TP", [])
+                .text([italic(file)])
+                .text([roman(description)]);
+        }
+        roff.control("SH", ["ENVIRONMENT"]);
+        for (var, description) in ENVIRONMENT {
+            roff.control("TP", [])
+                .text([bold(var)])
+                .text([roman(description)]);
+        }
+    }
+    roff.control("SH", ["SEE ALSO"]);
+    match top {
+        true => roff.control("BR", ["make", "(1)"]),
+        false => roff.control("BR", ["maki", "(1)"]),
+    };
+    roff.to_writer(&mut page)?;
+    if top {
+        man.render_version_section(&mut page)?;
+    }
+    pages.push((man.get_filename(), page));
+
+    for sub in visible_subcommands(cmd) {
+        let full_name = match top {
+            true => sub.get_name().to_string(),
+            false => format!("{} {}", name, sub.get_name()),
+        };
+        pages_into(sub, &full_name, source, pages)?;
+    }
+    Ok(())
+}
+
+fn visible_args(cmd: &Command) -> impl Iterator<Item = &Arg> {
+    cmd.get_arguments().filter(|arg| !arg.is_hide_set())
+}
+
+fn visible_subcommands(cmd: &Command) -> impl Iterator<Item = &Command> {
+    cmd.get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_every_command_has_examples() {
+        let cmd = Cli::command();
+        for sub in visible_subcommands(&cmd) {
+            assert!(!examples(sub.get_name()).is_empty(), "{}", sub.get_name());
+        }
+        // No examples for commands that don't exist
+        for (name, _) in EXAMPLES {
+            assert!(
+                name.is_empty() || cmd.find_subcommand(name).is_some(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_man_pages() {
+        let pages = man_pages(Cli::command()).unwrap();
+        let page = |name: &str| {
+            let (_, page) = pages.iter().find(|(file, _)| file == name).unwrap();
+            String::from_utf8(page.clone()).unwrap()
+        };
+
+        let maki = page("maki.1");
+        assert!(maki.contains(".TH maki 1  \"maki "));
+        assert!(maki.contains(".TP\n\\fB\\-f\\fR, \\fB\\-\\-file\\fR \\fI<FILE>\\fR\n"));
+        assert!(maki.contains(".SH FILES\n"));
+        assert_eq!(man_page(Cli::command()).unwrap(), maki.as_bytes());
+
+        let run = page("maki-run.1");
+        assert!(run.contains(".SH EXAMPLES\n"));
+        assert!(run.contains("\\fBmaki run test \\-\\-until\\-failure\\fR"));
+        // Global options are only described on maki's own page
+        assert!(!run.contains("\\-\\-dry\\-run"));
+        assert!(pages.iter().any(|(file, _)| file == "maki-cache-stats.1"));
+        assert!(!pages.iter().any(|(file, _)| file.contains("complete")));
+    }
+
+    #[test]
+    fn test_help_all() {
+        let help = help_all(Cli::command());
+
+        assert!(help.contains("Usage: maki run [OPTIONS] <TARGET>"));
+        assert!(help.contains("Usage: maki cache <COMMAND>"));
+        assert!(
+            help.contains(
+                "  # Re-run a flaky test until it fails\n  maki run test --until-failure\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,789 @@
+use clap::ValueEnum;
+use colored::{Color, Colorize};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::process::{Child, ExitStatus};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use crate::executor::{self, ExecuteOptions};
+use crate::heartbeat::format_elapsed;
+use crate::history;
+
+/// How often renderers hear that time has passed, for elapsed times
+const TICK: Duration = Duration::from_millis(100);
+
+/// How long output is still waited for once a target has exited, in case a
+/// process it started in the background keeps its output open
+const LINGER: Duration = Duration::from_millis(500);
+
+/// How long a stopped target gets to clean up before it is killed
+#[cfg(unix)]
+const GRACE: Duration = Duration::from_secs(2);
+
+/// Lines of output kept for each target; older lines are dropped
+const MAX_LINES: usize = 10_000;
+
+/// Colors that tell the targets' prefixes apart
+const COLORS: [Color; 5] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Blue,
+    Color::Green,
+];
+
+/// How a run of several targets is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputMode {
+    /// The dashboard on a terminal, prefixed lines elsewhere
+    #[default]
+    Auto,
+    /// A live view of each target's status, elapsed time, and last line
+    Dashboard,
+    /// Every line as it arrives, prefixed with its target's name
+    Prefixed,
+}
+
+impl OutputMode {
+    /// The renderer to use; `Auto` picks the dashboard only when stdin and
+    /// stdout are both a terminal
+    pub fn resolve(self) -> Self {
+        match self {
+            Self::Auto if io::stdin().is_terminal() && io::stdout().is_terminal() => {
+                Self::Dashboard
+            }
+            Self::Auto => Self::Prefixed,
+            mode => mode,
+        }
+    }
+}
+
+/// A target to run, with the options to run it with
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub target: String,
+    pub options: ExecuteOptions,
+    /// The jobs that must succeed before it starts in a parallel run, as
+    /// indices of earlier jobs
+    pub needs: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Waiting,
+    Running,
+    Finished(ExitStatus),
+    /// The command could not be started; the reason is its only output line
+    Failed,
+    /// Stopped from the dashboard before it finished
+    Stopped,
+    /// Never started, because an earlier target failed or the run was stopped
+    Skipped,
+}
+
+/// A target's progress in a run
+#[derive(Debug, Clone)]
+pub struct JobState {
+    pub target: String,
+    /// The command line, as shown before a run
+    pub command: String,
+    pub status: JobStatus,
+    /// Unix time the target started, for the history
+    pub started_at: Option<u64>,
+    started: Option<Instant>,
+    /// How long it ran, once it is done
+    pub duration: Option<Duration>,
+    /// Its stdout and stderr lines, in the order they arrived
+    pub output: VecDeque<String>,
+    /// The exit status, noted before the rest of the output has been read
+    exited: Option<(ExitStatus, Instant)>,
+    open_streams: usize,
+}
+
+impl JobState {
+    pub fn new(job: &Job) -> Self {
+        Self {
+            target: job.target.clone(),
+            command: executor::command_line(&job.target, &job.options),
+            status: JobStatus::Waiting,
+            started_at: None,
+            started: None,
+            duration: None,
+            output: VecDeque::new(),
+            exited: None,
+            open_streams: 0,
+        }
+    }
+
+    /// How long it has been running, or ran
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.duration.or(self.started.map(|s| s.elapsed()))
+    }
+
+    /// The last line it printed that isn't blank
+    pub fn last_line(&self) -> Option<&str> {
+        self.output
+            .iter()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .map(String::as_str)
+    }
+
+    pub fn succeeded(&self) -> bool {
+        matches!(self.status, JobStatus::Finished(s) if s.success())
+    }
+
+    /// Whether it ended without succeeding
+    pub fn failed(&self) -> bool {
+        match self.status {
+            JobStatus::Finished(s) => !s.success(),
+            JobStatus::Failed | JobStatus::Stopped => true,
+            _ => false,
+        }
+    }
+
+    /// Whether it is neither waiting nor running
+    pub fn is_done(&self) -> bool {
+        !matches!(self.status, JobStatus::Waiting | JobStatus::Running)
+    }
+
+    fn push_line(&mut self, line: String) {
+        if self.output.len() == MAX_LINES {
+            self.output.pop_front();
+        }
+        self.output.push_back(line);
+    }
+}
+
+/// Something that happened in a run, for its renderer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Started(usize),
+    Line {
+        job: usize,
+        text: String,
+        stderr: bool,
+    },
+    /// The target is done: finished, or failed to start
+    Finished(usize),
+    /// Time passed with nothing else happening
+    Tick,
+}
+
+/// What the run does after an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    /// Stop the targets still running, and start no more
+    Stop,
+}
+
+/// Shows a run of several targets as it happens
+pub trait Renderer {
+    /// Show `event`; `jobs` already include it
+    fn update(&mut self, jobs: &[JobState], event: Event) -> io::Result<Flow>;
+
+    /// Every target is done or the run was stopped
+    fn finish(&mut self, jobs: &[JobState]) -> io::Result<()>;
+}
+
+/// Output of a target's process, from the threads reading it
+enum Message {
+    Line {
+        job: usize,
+        text: String,
+        stderr: bool,
+    },
+    Closed(usize),
+}
+
+/// Run `jobs` one after another, stopping at the first failure, or with
+/// `parallel` each as soon as the jobs it needs have succeeded, telling
+/// `renderer` how they are doing
+pub fn run(
+    jobs: &[Job],
+    parallel: bool,
+    renderer: &mut dyn Renderer,
+) -> anyhow::Result<Vec<JobState>> {
+    let mut states: Vec<JobState> = jobs.iter().map(JobState::new).collect();
+    let mut children: Vec<Option<Child>> = jobs.iter().map(|_| None).collect();
+    let (tx, rx) = mpsc::channel();
+
+    loop {
+        let mut events = start_due(jobs, parallel, &mut states, &mut children, &tx);
+        events.extend(receive(&rx, &mut states));
+        events.extend(reap(&mut states, &mut children));
+        if events.is_empty() {
+            events.push(Event::Tick);
+        }
+
+        let mut flow = Flow::Continue;
+        for event in events {
+            if renderer.update(&states, event)? == Flow::Stop {
+                flow = Flow::Stop;
+            }
+        }
+        if flow == Flow::Stop {
+            stop(&mut states, &mut children);
+            break;
+        }
+        if states.iter().all(JobState::is_done) {
+            break;
+        }
+    }
+
+    renderer.finish(&states)?;
+    Ok(states)
+}
+
+/// Start the targets whose turn it is: in parallel, every one whose needs
+/// have succeeded, skipping those whose needs didn't; otherwise the next one
+/// once the one before has succeeded
+fn start_due(
+    jobs: &[Job],
+    parallel: bool,
+    states: &mut [JobState],
+    children: &mut [Option<Child>],
+    tx: &Sender<Message>,
+) -> Vec<Event> {
+    let mut events = Vec::new();
+    for (i, job) in jobs.iter().enumerate() {
+        if states[i].status != JobStatus::Waiting {
+            continue;
+        }
+        if parallel {
+            if job
+                .needs
+                .iter()
+                .any(|&n| states[n].is_done() && !states[n].succeeded())
+            {
+                states[i].status = JobStatus::Skipped;
+                continue;
+            }
+            if !job.needs.iter().all(|&n| states[n].succeeded()) {
+                continue;
+            }
+        } else {
+            if states.iter().any(JobState::failed) {
+                states[i].status = JobStatus::Skipped;
+                continue;
+            }
+            if states.iter().any(|s| s.status == JobStatus::Running) {
+                break;
+            }
+        }
+
+        let state = &mut states[i];
+        state.started_at = Some(history::now_secs());
+        state.started = Some(Instant::now());
+        events.push(Event::Started(i));
+        match executor::spawn_target_piped(&job.target, &job.options) {
+            Ok(mut child) => {
+                state.status = JobStatus::Running;
+                if let Some(stdout) = child.stdout.take() {
+                    forward(stdout, i, false, tx.clone());
+                    state.open_streams += 1;
+                }
+                if let Some(stderr) = child.stderr.take() {
+                    forward(stderr, i, true, tx.clone());
+                    state.open_streams += 1;
+                }
+                children[i] = Some(child);
+            }
+            Err(e) => {
+                state.status = JobStatus::Failed;
+                state.duration = Some(Duration::ZERO);
+                state.push_line(e.to_string());
+                events.push(Event::Finished(i));
+            }
+        }
+        if !parallel {
+            break;
+        }
+    }
+    events
+}
+
+/// Read a target's output stream line by line on a thread of its own
+fn forward(stream: impl Read + Send + 'static, job: usize, stderr: bool, tx: Sender<Message>) {
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(stream);
+        let mut buf = Vec::new();
+        while matches!(reader.read_until(b'\n', &mut buf), Ok(n) if n > 0) {
+            let line = String::from_utf8_lossy(&buf);
+            let line = line.trim_end_matches(['\n', '\r']);
+            // A progress bar redrawn with \r shows as its latest state
+            let text = line.rsplit('\r').next().unwrap_or(line).to_string();
+            if tx.send(Message::Line { job, text, stderr }).is_err() {
+                return;
+            }
+            buf.clear();
+        }
+        let _ = tx.send(Message::Closed(job));
+    });
+}
+
+/// The output that arrived within a tick
+fn receive(rx: &Receiver<Message>, states: &mut [JobState]) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut message = rx.recv_timeout(TICK).ok();
+    while let Some(m) = message {
+        match m {
+            Message::Line { job, text, stderr } => {
+                states[job].push_line(text.clone());
+                events.push(Event::Line { job, text, stderr });
+            }
+            Message::Closed(job) => {
+                states[job].open_streams = states[job].open_streams.saturating_sub(1);
+            }
+        }
+        message = rx.try_recv().ok();
+    }
+    events
+}
+
+/// Note the targets that have exited, and finish those whose output has all
+/// been read
+fn reap(states: &mut [JobState], children: &mut [Option<Child>]) -> Vec<Event> {
+    let mut events = Vec::new();
+    for (i, state) in states.iter_mut().enumerate() {
+        let Some(ref mut child) = children[i] else {
+            continue;
+        };
+        if state.exited.is_none()
+            && let Ok(Some(status)) = child.try_wait()
+        {
+            state.exited = Some((status, Instant::now()));
+            state.duration = state.started.map(|s| s.elapsed());
+        }
+        if let Some((status, at)) = state.exited
+            && (state.open_streams == 0 || at.elapsed() >= LINGER)
+        {
+            state.status = JobStatus::Finished(status);
+            children[i] = None;
+            events.push(Event::Finished(i));
+        }
+    }
+    events
+}
+
+/// Stop the targets still running and skip the ones not started
+fn stop(states: &mut [JobState], children: &mut [Option<Child>]) {
+    for (i, state) in states.iter_mut().enumerate() {
+        match state.status {
+            JobStatus::Waiting => state.status = JobStatus::Skipped,
+            JobStatus::Running => {
+                if let Some(mut child) = children[i].take() {
+                    terminate(&mut child);
+                }
+                state.status = JobStatus::Stopped;
+                state.duration = state.started.map(|s| s.elapsed());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Ask a target to stop, so make can pass it on to its recipes and clean up,
+/// killing it if it hasn't stopped by the end of the grace period
+fn terminate(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        // SAFETY: kill only sends a signal, to the child we started
+        unsafe {
+            libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+        }
+        let asked = Instant::now();
+        while asked.elapsed() < GRACE {
+            if let Ok(Some(_)) = child.try_wait() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// The icon for a target's status, e.g. `✓` once it succeeded
+pub fn status_icon(state: &JobState) -> String {
+    match state.status {
+        JobStatus::Waiting => "·".dimmed().to_string(),
+        JobStatus::Running => "●".cyan().to_string(),
+        JobStatus::Finished(s) if s.success() => "✓".green().to_string(),
+        JobStatus::Finished(_) | JobStatus::Failed => "✗".red().to_string(),
+        JobStatus::Stopped => "■".yellow().to_string(),
+        JobStatus::Skipped => "-".dimmed().to_string(),
+    }
+}
+
+/// How a target that is done ended, e.g. `exit 2`
+pub fn outcome(state: &JobState) -> Option<String> {
+    match state.status {
+        JobStatus::Finished(s) if s.success() => None,
+        JobStatus::Finished(s) => Some(match s.code() {
+            Some(code) => format!("exit {}", code),
+            None => "killed by a signal".to_string(),
+        }),
+        JobStatus::Failed => Some("could not start".to_string()),
+        JobStatus::Stopped => Some("stopped".to_string()),
+        JobStatus::Skipped => Some("skipped".to_string()),
+        JobStatus::Waiting | JobStatus::Running => None,
+    }
+}
+
+/// Streams every target's output as it arrives, each line prefixed with the
+/// target's name; the fallback when there is no terminal for the dashboard
+pub struct Prefixed {
+    width: usize,
+}
+
+impl Prefixed {
+    pub fn new(jobs: &[Job]) -> Self {
+        Self {
+            width: jobs
+                .iter()
+                .map(|job| job.target.chars().count())
+                .max()
+                .unwrap_or(0),
+        }
+    }
+
+    /// `[target]`, padded so everyone's output lines up
+    fn prefix(&self, jobs: &[JobState], job: usize) -> String {
+        let label = format!("[{}]", jobs[job].target);
+        format!("{:<width$}", label, width = self.width + 2)
+            .color(COLORS[job % COLORS.len()])
+            .to_string()
+    }
+
+    /// The line to show for `event`, and whether it goes to stderr
+    fn line(&self, jobs: &[JobState], event: &Event) -> Option<(String, bool)> {
+        match *event {
+            Event::Started(job) => Some((
+                format!(
+                    "{} {} {}",
+                    self.prefix(jobs, job),
+                    "Running:".green(),
+                    jobs[job].command
+                ),
+                false,
+            )),
+            Event::Line {
+                job,
+                ref text,
+                stderr,
+            } => Some((format!("{} {}", self.prefix(jobs, job), text), stderr)),
+            Event::Finished(job) => {
+                let state = &jobs[job];
+                let elapsed = format_elapsed(state.elapsed().unwrap_or_default());
+                let summary = match outcome(state) {
+                    Some(outcome) => format!("{} after {}", outcome, elapsed).red(),
+                    None => format!("done in {}", elapsed).green(),
+                };
+                Some((
+                    format!(
+                        "{} {} {}",
+                        self.prefix(jobs, job),
+                        status_icon(state),
+                        summary
+                    ),
+                    false,
+                ))
+            }
+            Event::Tick => None,
+        }
+    }
+}
+
+impl Renderer for Prefixed {
+    fn update(&mut self, jobs: &[JobState], event: Event) -> io::Result<Flow> {
+        match self.line(jobs, &event) {
+            Some((line, true)) => writeln!(io::stderr(), "{}", line)?,
+            Some((line, false)) => writeln!(io::stdout(), "{}", line)?,
+            None => {}
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn finish(&mut self, _jobs: &[JobState]) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+/// The lines summing up a run: each target's status and elapsed time, then
+/// the last `tail` lines of output of each target that failed
+pub fn summary(jobs: &[JobState], tail: usize) -> Vec<String> {
+    let width = jobs
+        .iter()
+        .map(|job| job.target.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut lines: Vec<String> = jobs
+        .iter()
+        .map(|job| {
+            let elapsed = match job.status {
+                JobStatus::Skipped => String::new(),
+                _ => format_elapsed(job.elapsed().unwrap_or_default()),
+            };
+            let mut line = format!(
+                "  {} {:<width$}  {:>7}",
+                status_icon(job),
+                job.target,
+                elapsed
+            );
+            if let Some(outcome) = outcome(job) {
+                line.push_str(&format!("  {}", outcome.dimmed()));
+            }
+            line
+        })
+        .collect();
+
+    for job in jobs
+        .iter()
+        .filter(|job| job.failed() && tail > 0 && !job.output.is_empty())
+    {
+        let start = job.output.len().saturating_sub(tail);
+        lines.push(String::new());
+        lines.push(format!("{} {}", "──".dimmed(), job.target.bold()));
+        lines.extend(job.output.iter().skip(start).cloned());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runlog::strip_ansi;
+
+    /// Records what it was told, stopping the run when told `stop_on` was
+    /// printed
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<Event>,
+        stop_on: Option<&'static str>,
+    }
+
+    impl Renderer for Recorder {
+        fn update(&mut self, _jobs: &[JobState], event: Event) -> io::Result<Flow> {
+            let stop = matches!(event, Event::Line { ref text, .. } if Some(text.as_str()) == self.stop_on);
+            if event != Event::Tick {
+                self.events.push(event);
+            }
+            Ok(if stop { Flow::Stop } else { Flow::Continue })
+        }
+
+        fn finish(&mut self, _jobs: &[JobState]) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn job(dir: &std::path::Path, target: &str) -> Job {
+        Job {
+            target: target.to_string(),
+            options: ExecuteOptions {
+                cwd: Some(dir.to_path_buf()),
+                ..Default::default()
+            },
+            needs: Vec::new(),
+        }
+    }
+
+    fn makefile() -> tempfile::TempDir {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("Makefile"),
+            "ok:\n\t@echo fine\nfail:\n\t@echo broken >&2; exit 3\nslow:\n\t@echo waiting; sleep 30\n",
+        )
+        .unwrap();
+        dir
+    }
+
+    fn statuses(states: &[JobState]) -> Vec<Option<String>> {
+        states.iter().map(outcome).collect()
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_sequential_run_stops_at_the_first_failure() {
+        let dir = makefile();
+        let jobs = [
+            job(dir.path(), "ok"),
+            job(dir.path(), "fail"),
+            job(dir.path(), "ok"),
+        ];
+        let mut recorder = Recorder::default();
+
+        let states = run(&jobs, false, &mut recorder).unwrap();
+
+        assert_eq!(
+            statuses(&states),
+            vec![
+                None,
+                Some("exit 2".to_string()),
+                Some("skipped".to_string())
+            ]
+        );
+        assert_eq!(states[0].last_line(), Some("fine"));
+        assert!(recorder.events.contains(&Event::Line {
+            job: 1,
+            text: "broken".to_string(),
+            stderr: true
+        }));
+        // Each starts once the one before it is done
+        let started: Vec<&Event> = recorder
+            .events
+            .iter()
+            .filter(|e| matches!(e, Event::Started(_) | Event::Finished(_)))
+            .collect();
+        assert_eq!(
+            started,
+            vec![
+                &Event::Started(0),
+                &Event::Finished(0),
+                &Event::Started(1),
+                &Event::Finished(1)
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_parallel_run_starts_everything() {
+        let dir = makefile();
+        let jobs = [job(dir.path(), "fail"), job(dir.path(), "ok")];
+
+        let states = run(&jobs, true, &mut Recorder::default()).unwrap();
+
+        assert_eq!(statuses(&states), vec![Some("exit 2".to_string()), None]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_parallel_run_waits_for_needs() {
+        let dir = makefile();
+        let needing = |target: &str, needs: usize| Job {
+            needs: vec![needs],
+            ..job(dir.path(), target)
+        };
+        let jobs = [
+            job(dir.path(), "ok"),
+            job(dir.path(), "fail"),
+            needing("ok", 0),
+            needing("ok", 1),
+        ];
+        let mut recorder = Recorder::default();
+
+        let states = run(&jobs, true, &mut recorder).unwrap();
+
+        assert_eq!(
+            statuses(&states),
+            vec![
+                None,
+                Some("exit 2".to_string()),
+                None,
+                Some("skipped".to_string())
+            ]
+        );
+        let position = |wanted: Event| recorder.events.iter().position(|e| *e == wanted);
+        assert!(position(Event::Finished(0)) < position(Event::Started(2)));
+        assert_eq!(position(Event::Started(3)), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_stopping_a_run() {
+        let dir = makefile();
+        let jobs = [job(dir.path(), "slow"), job(dir.path(), "ok")];
+        let mut recorder = Recorder {
+            stop_on: Some("waiting"),
+            ..Default::default()
+        };
+        let started = Instant::now();
+
+        let states = run(&jobs, false, &mut recorder).unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(
+            statuses(&states),
+            vec![Some("stopped".to_string()), Some("skipped".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_prefixed_lines() {
+        let jobs = [
+            Job {
+                target: "build".to_string(),
+                options: ExecuteOptions::default(),
+                needs: Vec::new(),
+            },
+            Job {
+                target: "lint".to_string(),
+                options: ExecuteOptions::default(),
+                needs: Vec::new(),
+            },
+        ];
+        let mut states: Vec<JobState> = jobs.iter().map(JobState::new).collect();
+        states[1].status = JobStatus::Failed;
+        states[1].duration = Some(Duration::ZERO);
+        let prefixed = Prefixed::new(&jobs);
+        let line = |event: Event| {
+            prefixed
+                .line(&states, &event)
+                .map(|(line, stderr)| (strip_ansi(&line), stderr))
+        };
+
+        assert_eq!(
+            line(Event::Started(0)),
+            Some(("[build] Running: make build".to_string(), false))
+        );
+        assert_eq!(
+            line(Event::Line {
+                job: 1,
+                text: "warning: x".to_string(),
+                stderr: true
+            }),
+            Some(("[lint]  warning: x".to_string(), true))
+        );
+        assert_eq!(
+            line(Event::Finished(1)),
+            Some(("[lint]  ✗ could not start after 0s".to_string(), false))
+        );
+        assert_eq!(line(Event::Tick), None);
+    }
+
+    #[test]
+    fn test_summary_shows_the_tail_of_failures() {
+        let job = |target: &str| Job {
+            target: target.to_string(),
+            options: ExecuteOptions::default(),
+            needs: Vec::new(),
+        };
+        let mut states: Vec<JobState> = [job("build"), job("test")]
+            .iter()
+            .map(JobState::new)
+            .collect();
+        states[1].status = JobStatus::Stopped;
+        states[1].duration = Some(Duration::from_secs(75));
+        for line in ["one", "two", "three"] {
+            states[1].push_line(line.to_string());
+        }
+        states[0].status = JobStatus::Skipped;
+
+        let lines: Vec<String> = summary(&states, 2).iter().map(|l| strip_ansi(l)).collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                "  - build           skipped",
+                "  ■ test    1m 15s  stopped",
+                "",
+                "── test",
+                "two",
+                "three",
+            ]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// The names make reports itself under, e.g. `make: Nothing to be done ...`
+const MAKE_PROGRAMS: [&str; 4] = ["make", "gmake", "make.exe", "mingw32-make.exe"];
+
+const NOTHING_TO_BE_DONE: &str = "Nothing to be done for ";
+
+/// make's messages for a goal that needed no work, from the top-level make
+/// only (a nested `make[1]:` saying so doesn't mean the whole run was a no-op)
+static NOOP_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:.*[/\\])?[\w.-]*make(?:\.exe)?: (?:Nothing to be done for [`'‘].*['’]|[`'‘].*['’] is up to date)\.$")
+        .unwrap()
+});
+
+/// Whether `line` is make saying that the goal needed no work
+pub fn is_noop_line(line: &str) -> bool {
+    NOOP_LINE.is_match(line.trim_end_matches('\r'))
+}
+
+/// Whether a run's output says that make had nothing to do
+pub fn is_noop(output: &[u8]) -> bool {
+    String::from_utf8_lossy(output).lines().any(is_noop_line)
+}
+
+/// Whether `partial`, an unfinished line, may still turn out to be one of
+/// make's "nothing to be done" messages
+fn could_be_noop_line(partial: &str) -> bool {
+    let is_program = |program: &str| {
+        let name = program.rsplit(['/', '\\']).next().unwrap_or(program);
+        MAKE_PROGRAMS.iter().any(|make| make.starts_with(name))
+    };
+    match partial.split_once(": ") {
+        None => !partial.contains(char::is_whitespace) && is_program(partial.trim_end_matches(':')),
+        Some((program, message)) => {
+            is_program(program)
+                && (NOTHING_TO_BE_DONE.starts_with(message)
+                    || message.starts_with(NOTHING_TO_BE_DONE)
+                    || message.starts_with(['`', '\'', '‘']))
+        }
+    }
+}
+
+/// Drops make's "nothing to be done" message from a run's stdout, noting
+/// that it was seen
+///
+/// Output is only held back while everything printed so far could still be
+/// that message, so other output (including prompts and progress dots) shows
+/// as soon as it arrives.
+#[derive(Debug, Default)]
+pub struct NoopFilter {
+    held: Vec<u8>,
+    passing: bool,
+    noop: bool,
+}
+
+impl NoopFilter {
+    /// The part of the output read so far that can be shown now
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<u8> {
+        if self.passing {
+            return chunk.to_vec();
+        }
+        self.held.extend_from_slice(chunk);
+
+        while let Some(end) = self.held.iter().position(|&b| b == b'\n') {
+            if !is_noop_line(&String::from_utf8_lossy(&self.held[..end])) {
+                self.passing = true;
+                return std::mem::take(&mut self.held);
+            }
+            self.noop = true;
+            self.held.drain(..=end);
+        }
+
+        if !self.held.is_empty() && !could_be_noop_line(&String::from_utf8_lossy(&self.held)) {
+            self.passing = true;
+            return std::mem::take(&mut self.held);
+        }
+        Vec::new()
+    }
+
+    /// Output still held back when the run ends
+    pub fn finish(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.held)
+    }
+
+    /// Whether make said that it had nothing to do
+    pub fn is_noop(&self) -> bool {
+        self.noop
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_noop_line() {
+        assert!(is_noop_line("make: Nothing to be done for 'all'."));
+        assert!(is_noop_line("make: Nothing to be done for `all'."));
+        assert!(is_noop_line("make: 'dist.tar.gz' is up to date."));
+        assert!(is_noop_line("/usr/bin/gmake: 'x' is up to date.\r"));
+        // A nested make having nothing to do says nothing about the run
+        assert!(!is_noop_line("make[1]: Nothing to be done for 'sub'."));
+        assert!(!is_noop_line("echo make: Nothing to be done for 'all'."));
+        assert!(!is_noop_line("cargo build"));
+    }
+
+    #[test]
+    fn test_is_noop() {
+        assert!(is_noop(b"make: Nothing to be done for 'build'.\n"));
+        assert!(!is_noop(b"cc -o app main.c\n"));
+    }
+
+    #[test]
+    fn test_filter_drops_the_message() {
+        let mut filter = NoopFilter::default();
+
+        assert!(filter.feed(b"make: Nothing to be").is_empty());
+        assert!(filter.feed(b" done for 'build'.\n").is_empty());
+        assert!(filter.finish().is_empty());
+        assert!(filter.is_noop());
+    }
+
+    #[test]
+    fn test_filter_passes_other_output_through() {
+        let mut filter = NoopFilter::default();
+
+        assert_eq!(filter.feed(b"ma"), b"");
+        assert_eq!(filter.feed(b"ke[1]: Entering"), b"make[1]: Entering");
+        assert_eq!(filter.feed(b" directory\n"), b" directory\n");
+        assert!(!filter.is_noop());
+
+        // Progress output is never held back
+        let mut filter = NoopFilter::default();
+        assert_eq!(filter.feed(b"."), b".");
+        assert_eq!(filter.feed(b"Password: "), b"Password: ");
+
+        let mut filter = NoopFilter::default();
+        assert_eq!(filter.feed(b"make"), b"");
+        assert_eq!(filter.finish(), b"make");
+        assert!(!filter.is_noop());
+    }
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::store::{Store, project_key};
+use crate::target::Target;
+
+/// Personal notes on targets, kept per user rather than in the project
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Notes {
+    /// Notes by project directory, then target name
+    projects: BTreeMap<PathBuf, BTreeMap<String, String>>,
+}
+
+impl Store for Notes {
+    const FILENAME: &'static str = "notes.json";
+    const WHAT: &'static str = "notes";
+}
+
+impl Notes {
+    /// The note on `target` in the project at `dir`
+    pub fn get(&self, dir: &Path, target: &str) -> Option<&str> {
+        self.projects
+            .get(&project_key(dir))?
+            .get(target)
+            .map(String::as_str)
+    }
+
+    pub fn set(&mut self, dir: &Path, target: &str, note: &str) {
+        self.projects
+            .entry(project_key(dir))
+            .or_default()
+            .insert(target.to_string(), note.trim().to_string());
+    }
+
+    /// Remove the note on `target`, returning whether there was one
+    pub fn remove(&mut self, dir: &Path, target: &str) -> bool {
+        let key = project_key(dir);
+        let Some(notes) = self.projects.get_mut(&key) else {
+            return false;
+        };
+        let removed = notes.remove(target).is_some();
+        if notes.is_empty() {
+            self.projects.remove(&key);
+        }
+        removed
+    }
+
+    /// Attach the notes to `targets`; those without a project root belong to
+    /// the project at `dir`
+    pub fn apply(&self, targets: &mut [Target], dir: &Path) {
+        for target in targets {
+            let root = target.root.clone().unwrap_or_else(|| dir.to_path_buf());
+            target.note = self.get(&root, &target.name).map(str::to_string);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_set_save_and_load() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("maki").join(Notes::FILENAME);
+        let project = dir.path();
+
+        let mut notes = Notes::default();
+        notes.set(project, "deploy", "  needs VPN connected\n");
+        notes.save_to(&path).unwrap();
+
+        let notes = Notes::load_from(&path).unwrap();
+        assert_eq!(notes.get(project, "deploy"), Some("needs VPN connected"));
+        // The same directory spelled differently
+        assert_eq!(
+            notes.get(&project.join("."), "deploy"),
+            Some("needs VPN connected")
+        );
+        assert_eq!(notes.get(project, "build"), None);
+        assert_eq!(notes.get(Path::new("/elsewhere"), "deploy"), None);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut notes = Notes::default();
+        notes.set(Path::new("/app"), "deploy", "needs VPN");
+
+        assert!(notes.remove(Path::new("/app"), "deploy"));
+        assert!(!notes.remove(Path::new("/app"), "deploy"));
+        assert_eq!(notes, Notes::default());
+    }
+
+    #[test]
+    fn test_apply_uses_each_targets_root() {
+        let mut notes = Notes::default();
+        notes.set(Path::new("/app"), "deploy", "needs VPN");
+        notes.set(Path::new("/api"), "deploy", "ask ops first");
+
+        let mut targets = vec![
+            Target::new("deploy".to_string(), None, PathBuf::from("Makefile"), 1),
+            Target::new("deploy".to_string(), None, PathBuf::from("Makefile"), 1),
+            Target::new("build".to_string(), None, PathBuf::from("Makefile"), 4),
+        ];
+        targets[1].root = Some(PathBuf::from("/api"));
+        targets[2].note = Some("stale".to_string());
+
+        notes.apply(&mut targets, Path::new("/app"));
+
+        assert_eq!(targets[0].note.as_deref(), Some("needs VPN"));
+        assert_eq!(targets[1].note.as_deref(), Some("ask ops first"));
+        assert_eq!(targets[2].note, None);
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::store::{Store, project_key};
+
+/// The targets `maki list --numbered` showed last, kept per user and per
+/// project so `maki 3` and `maki run @3` mean the same target until the
+/// next numbered list
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Numbering {
+    /// Target names by project directory, in the order they were numbered
+    projects: BTreeMap<PathBuf, Vec<String>>,
+}
+
+/// The number in `@3`, as `maki run` takes it
+pub fn index(text: &str) -> Option<usize> {
+    text.strip_prefix('@').and_then(bare_index)
+}
+
+/// The number in `3`, as `maki` takes it
+pub fn bare_index(text: &str) -> Option<usize> {
+    if !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok().filter(|&n| n > 0)
+}
+
+impl Store for Numbering {
+    const FILENAME: &'static str = "numbered.json";
+    const WHAT: &'static str = "numbering";
+}
+
+impl Numbering {
+    /// Number `names` from 1 for the project at `dir`, replacing what it had
+    pub fn remember(&mut self, dir: &Path, names: Vec<String>) {
+        self.projects.insert(project_key(dir), names);
+    }
+
+    /// The target numbered `index` in the project's last numbered list
+    pub fn target(&self, dir: &Path, index: usize) -> Result<&str> {
+        let Some(names) = self.projects.get(&project_key(dir)) else {
+            bail!("No numbered list here yet; run `maki list --numbered` first");
+        };
+        match index.checked_sub(1).and_then(|i| names.get(i)) {
+            Some(name) => Ok(name),
+            None => bail!(
+                "No target {} in the last numbered list (1-{}); run `maki list --numbered` again",
+                index,
+                names.len()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_index() {
+        assert_eq!(index("@3"), Some(3));
+        assert_eq!(index("3"), None);
+        assert_eq!(index("@0"), None);
+        assert_eq!(index("@+3"), None);
+        assert_eq!(bare_index("12"), Some(12));
+        assert_eq!(bare_index("build"), None);
+        assert_eq!(bare_index(""), None);
+    }
+
+    #[test]
+    fn test_remember_save_and_load() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("maki").join(Numbering::FILENAME);
+        let project = dir.path();
+
+        let mut numbering = Numbering::default();
+        assert!(numbering.target(project, 1).is_err());
+        numbering.remember(project, vec!["build".to_string(), "test".to_string()]);
+        numbering.save_to(&path).unwrap();
+
+        let numbering = Numbering::load_from(&path).unwrap();
+        assert_eq!(numbering.target(&project.join("."), 2).unwrap(), "test");
+        assert_eq!(
+            numbering.target(project, 3).unwrap_err().to_string(),
+            "No target 3 in the last numbered list (1-2); run `maki list --numbered` again"
+        );
+        assert!(numbering.target(&project.join("maki"), 1).is_err());
+    }
+}
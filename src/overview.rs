@@ -0,0 +1,294 @@
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::graph::Graph;
+use crate::target::{Provider, Target};
+use crate::width;
+
+/// How many top-level targets fit on one screen before the list is cut short
+const MAX_ENTRY_POINTS: usize = 15;
+
+/// Characters that separate a category prefix from the rest of a target name
+const CATEGORY_SEPARATORS: [char; 4] = ['-', '/', ':', '.'];
+
+/// A group of targets sharing a name prefix, e.g. `docker-build` and `docker-push`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Category {
+    pub name: String,
+    pub targets: usize,
+}
+
+/// A file that supplied targets, and how many
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProviderSummary {
+    pub provider: Provider,
+    pub file: PathBuf,
+    pub targets: usize,
+}
+
+/// A top-level target: one nothing else depends on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EntryPoint {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// A target that needs variables before it can run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NeedsVariables {
+    pub name: String,
+    pub variables: Vec<String>,
+}
+
+/// One-screen summary of a project's targets for `maki overview`
+#[derive(Debug, Clone, Serialize)]
+pub struct Overview {
+    pub makefiles: usize,
+    pub total_targets: usize,
+    pub providers: Vec<ProviderSummary>,
+    pub categories: Vec<Category>,
+    pub entry_points: Vec<EntryPoint>,
+    pub needs_variables: Vec<NeedsVariables>,
+}
+
+impl Overview {
+    /// Summarise `targets`, which should already be filtered by --all/--patterns
+    pub fn build(targets: &[Target]) -> Self {
+        let mut by_file: BTreeMap<(Provider, PathBuf), usize> = BTreeMap::new();
+        for target in targets {
+            *by_file
+                .entry((target.provider, target.file.clone()))
+                .or_default() += 1;
+        }
+        let providers: Vec<ProviderSummary> = by_file
+            .into_iter()
+            .map(|((provider, file), targets)| ProviderSummary {
+                provider,
+                file,
+                targets,
+            })
+            .collect();
+
+        let graph = Graph::new(targets);
+        let mut entry_points: Vec<EntryPoint> = targets
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| !t.is_pattern() && !is_special(t))
+            .filter(|&(i, _)| graph.dependents(i).is_empty())
+            .map(|(_, t)| EntryPoint {
+                name: t.display_name(),
+                description: t.description.clone(),
+            })
+            .collect();
+        // Documented targets are the ones a newcomer is meant to run
+        entry_points.sort_by_key(|e| e.description.is_none());
+
+        let needs_variables = targets
+            .iter()
+            .filter(|t| !t.required_vars.is_empty())
+            .map(|t| NeedsVariables {
+                name: t.name.clone(),
+                variables: t.required_vars.iter().map(|v| v.name.clone()).collect(),
+            })
+            .collect();
+
+        Self {
+            makefiles: providers
+                .iter()
+                .filter(|p| p.provider == Provider::Make)
+                .count(),
+            total_targets: targets.len(),
+            providers,
+            categories: categories(targets),
+            entry_points,
+            needs_variables,
+        }
+    }
+}
+
+/// Built-in make targets such as `.PHONY` and `.DEFAULT`
+fn is_special(target: &Target) -> bool {
+    target.provider == Provider::Make && target.name.starts_with('.')
+}
+
+/// Group targets by name prefix, keeping prefixes shared by at least two
+/// targets, largest groups first
+fn categories(targets: &[Target]) -> Vec<Category> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for target in targets {
+        if let Some(prefix) = category_of(&target.name) {
+            *counts.entry(prefix).or_default() += 1;
+        }
+    }
+
+    let mut categories: Vec<Category> = counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(name, targets)| Category {
+            name: name.to_string(),
+            targets,
+        })
+        .collect();
+    categories.sort_by_key(|c| std::cmp::Reverse(c.targets));
+    categories
+}
+
+/// The prefix of a target name before its first separator, if it has one
+pub(crate) fn category_of(name: &str) -> Option<&str> {
+    let name = name.trim_start_matches('_');
+    let (prefix, rest) = name.split_once(CATEGORY_SEPARATORS)?;
+    (!prefix.is_empty() && !rest.is_empty()).then_some(prefix)
+}
+
+/// Print the overview, with file paths shown relative to `base_dir`
+pub fn render(overview: &Overview, base_dir: &Path) {
+    println!(
+        "{} {} targets from {} Makefile(s)",
+        "Project:".bold(),
+        overview.total_targets.to_string().bold(),
+        overview.makefiles
+    );
+
+    println!();
+    println!("  {}", "sources".bold());
+    for source in &overview.providers {
+        println!(
+            "    {:<9} {}  {}",
+            source.provider.name().cyan(),
+            source
+                .file
+                .strip_prefix(base_dir)
+                .unwrap_or(&source.file)
+                .display(),
+            format!("({} targets)", source.targets).dimmed()
+        );
+    }
+
+    if !overview.categories.is_empty() {
+        let categories: Vec<String> = overview
+            .categories
+            .iter()
+            .map(|c| format!("{} ({})", c.name, c.targets))
+            .collect();
+        println!();
+        println!("  {}", "categories".bold());
+        println!("    {}", categories.join(", "));
+    }
+
+    if !overview.entry_points.is_empty() {
+        println!();
+        println!("  {}", "top-level targets".bold());
+        let shown = &overview.entry_points[..overview.entry_points.len().min(MAX_ENTRY_POINTS)];
+        let width = shown
+            .iter()
+            .map(|e| width::columns(&e.name))
+            .max()
+            .unwrap_or(0);
+        for entry in shown {
+            let name = width::pad(&entry.name, width);
+            match entry.description {
+                Some(ref desc) => println!("    {}  {}", name.green(), desc.dimmed()),
+                None => println!("    {}", name.green()),
+            }
+        }
+        let hidden = overview.entry_points.len() - shown.len();
+        if hidden > 0 {
+            println!(
+                "    {}",
+                format!("… and {} more (maki list)", hidden).dimmed()
+            );
+        }
+    }
+
+    if !overview.needs_variables.is_empty() {
+        println!();
+        println!("  {}", "need variables".bold());
+        let width = overview
+            .needs_variables
+            .iter()
+            .map(|t| width::columns(&t.name))
+            .max()
+            .unwrap_or(0);
+        for target in &overview.needs_variables {
+            let name = width::pad(&target.name, width);
+            println!(
+                "    {}  {}",
+                name.green(),
+                target.variables.join(", ").yellow()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::target::RequiredVar;
+
+    fn target(name: &str, description: Option<&str>, deps: &[&str]) -> Target {
+        let mut target = Target::with_deps(name, deps);
+        target.description = description.map(str::to_string);
+        target
+    }
+
+    #[test]
+    fn test_category_of() {
+        assert_eq!(category_of("docker-build"), Some("docker"));
+        assert_eq!(category_of("db:migrate"), Some("db"));
+        assert_eq!(category_of("_ci/setup"), Some("ci"));
+        assert_eq!(category_of("build"), None);
+        assert_eq!(category_of("-flag"), None);
+    }
+
+    #[test]
+    fn test_overview_summary() {
+        let mut deploy = target("deploy", Some("Ship it"), &["build", "test"]);
+        deploy.required_vars = vec![RequiredVar {
+            name: "ENV".to_string(),
+            hint: Some("staging|prod".to_string()),
+            default: None,
+            secret: None,
+        }];
+        let mut web = Target::new("web".to_string(), None, PathBuf::from("Procfile"), 1);
+        web.provider = Provider::Procfile;
+
+        let targets = vec![
+            target(".PHONY", None, &["build", "clean"]),
+            target("build", Some("Compile"), &[]),
+            target("clean", None, &[]),
+            deploy,
+            target("docker-build", None, &[]),
+            target("docker-push", Some("Push the image"), &["docker-build"]),
+            target("test", None, &["build"]),
+            web,
+        ];
+        let overview = Overview::build(&targets);
+
+        assert_eq!(overview.makefiles, 1);
+        assert_eq!(overview.total_targets, 8);
+        assert_eq!(overview.providers.len(), 2);
+        assert_eq!(overview.providers[1].provider, Provider::Procfile);
+        assert_eq!(
+            overview.categories,
+            vec![Category {
+                name: "docker".to_string(),
+                targets: 2
+            }]
+        );
+
+        let entry_points: Vec<&str> = overview
+            .entry_points
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(
+            entry_points,
+            vec!["deploy", "docker-push", "clean", "web [procfile]"]
+        );
+
+        assert_eq!(overview.needs_variables[0].name, "deploy");
+        assert_eq!(overview.needs_variables[0].variables, vec!["ENV"]);
+    }
+}
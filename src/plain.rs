@@ -0,0 +1,679 @@
+use anyhow::Result;
+use std::io::{self, BufRead, Write};
+
+use crate::executor::MASK;
+use crate::fuzzy::PickerOptions;
+use crate::quote;
+use crate::rank::RankMode;
+use crate::recipe::Recipe;
+use crate::target::{RequiredVar, Target};
+
+/// Pick a target from a numbered list, filtering by typed text
+///
+/// Everything is written line by line with no alternate screen, cursor
+/// movement, or colors, so it works with screen readers and dumb terminals.
+pub fn select_target(targets: &[Target], options: &PickerOptions) -> Result<Option<Target>> {
+    let stdin = io::stdin();
+    select_target_with(targets, options, &mut stdin.lock(), &mut io::stdout())
+}
+
+/// Prompt for the required variables of `target`, one line per value and
+/// each after the lines of `recipe` that use it, then offer to change any of
+/// them before running
+pub fn prompt_for_variables(
+    target: &str,
+    required_vars: &[RequiredVar],
+    recipe: &Recipe,
+) -> Result<Vec<(String, String)>> {
+    let stdin = io::stdin();
+    prompt_variables_with(
+        target,
+        required_vars,
+        recipe,
+        &mut stdin.lock(),
+        &mut io::stdout(),
+    )
+}
+
+/// Pick one of `items` by number, returning its index
+pub fn select_from(title: &str, items: &[String]) -> Result<Option<usize>> {
+    let stdin = io::stdin();
+    select_from_with(title, items, &mut stdin.lock(), &mut io::stdout())
+}
+
+/// Ask a yes/no question, defaulting to yes
+pub fn confirm(message: &str) -> Result<bool> {
+    let stdin = io::stdin();
+    confirm_with(message, &mut stdin.lock(), &mut io::stdout())
+}
+
+fn select_target_with(
+    targets: &[Target],
+    options: &PickerOptions,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<Option<Target>> {
+    let mut visible: Vec<&Target> = targets
+        .iter()
+        .filter(|t| options.filter().allows(t))
+        .collect();
+    // Filtering is a plain substring match with no score to rank by, so
+    // hybrid lists the most used targets first as well
+    if options.rank != RankMode::PureFuzzy {
+        options.frecency.sort(&mut visible);
+    }
+
+    if visible.is_empty() {
+        return Ok(None);
+    }
+
+    if let Some(ref header) = options.header {
+        writeln!(output, "{}", header)?;
+    }
+
+    let mut query = String::new();
+
+    loop {
+        let matches = filter(&visible, &query);
+
+        if matches.is_empty() {
+            writeln!(output, "No targets match \"{}\".", query)?;
+        } else {
+            let count = match matches.len() {
+                1 => "1 target".to_string(),
+                n => format!("{} targets", n),
+            };
+            if query.is_empty() {
+                writeln!(output, "{}:", count)?;
+            } else {
+                writeln!(output, "{} matching \"{}\":", count, query)?;
+            }
+            for (i, target) in matches.iter().enumerate() {
+                writeln!(output, "{}", list_line(i + 1, target))?;
+            }
+        }
+
+        let cancel_hint = if query.is_empty() {
+            "Enter to cancel"
+        } else {
+            "Enter to clear the filter"
+        };
+        write!(
+            output,
+            "Type a number to select, text to filter, or {}: ",
+            cancel_hint
+        )?;
+        output.flush()?;
+
+        let Some(line) = read_line(input)? else {
+            writeln!(output)?;
+            return Ok(None);
+        };
+
+        if line.is_empty() {
+            if query.is_empty() {
+                return Ok(None);
+            }
+            query.clear();
+            continue;
+        }
+
+        match line.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= matches.len() => return Ok(Some(matches[n - 1].clone())),
+            Ok(n) => writeln!(output, "{} is not in the list.", n)?,
+            Err(_) => query = line,
+        }
+    }
+}
+
+fn select_from_with(
+    title: &str,
+    items: &[String],
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<Option<usize>> {
+    writeln!(output, "{}:", title)?;
+    for (i, item) in items.iter().enumerate() {
+        writeln!(output, "{}. {}", i + 1, item)?;
+    }
+
+    loop {
+        write!(output, "Type a number to select, or Enter to cancel: ")?;
+        output.flush()?;
+
+        let Some(line) = read_line(input)? else {
+            writeln!(output)?;
+            return Ok(None);
+        };
+        if line.is_empty() {
+            return Ok(None);
+        }
+
+        match line.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= items.len() => return Ok(Some(n - 1)),
+            _ => writeln!(output, "{} is not in the list.", line)?,
+        }
+    }
+}
+
+/// Targets whose name or description contains `query`, ignoring case, or
+/// with the tag `query` names as `#docker`
+fn filter<'a>(targets: &[&'a Target], query: &str) -> Vec<&'a Target> {
+    if let Some(tag) = query.strip_prefix('#') {
+        return targets.iter().filter(|t| t.has_tag(tag)).copied().collect();
+    }
+    let query = query.to_lowercase();
+    targets
+        .iter()
+        .filter(|t| {
+            t.name.to_lowercase().contains(&query)
+                || t.description
+                    .as_ref()
+                    .is_some_and(|d| d.to_lowercase().contains(&query))
+        })
+        .copied()
+        .collect()
+}
+
+/// A numbered entry, e.g. `3. build - Build the project #ci`
+fn list_line(number: usize, target: &Target) -> String {
+    let mut line = match target.description {
+        Some(ref desc) => format!("{}. {} - {}", number, target.picker_name(), desc),
+        None => format!("{}. {}", number, target.picker_name()),
+    };
+    if !target.tags.is_empty() {
+        line.push(' ');
+        line.push_str(&target.tag_chips());
+    }
+    line
+}
+
+fn prompt_variables_with(
+    target: &str,
+    required_vars: &[RequiredVar],
+    recipe: &Recipe,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<Vec<(String, String)>> {
+    writeln!(output, "Variables for {}:", target)?;
+    let mut values = Vec::new();
+    for var in required_vars {
+        if let Some(usages) = recipe.render_usages(&var.name, false) {
+            writeln!(output, "{} is used in:", var.name)?;
+            write!(output, "{}", usages)?;
+        }
+        let value = prompt_variable_with(var, input, output)?;
+        values.push((var.name.clone(), value));
+    }
+
+    // A single answer was just typed; with more, one may need a second look
+    if values.len() < 2 {
+        return Ok(values);
+    }
+
+    loop {
+        for (i, (name, value)) in values.iter().enumerate() {
+            let shown = if required_vars[i].is_secret() {
+                format!("{}={}", name, MASK)
+            } else {
+                quote::assignment(name, value)
+            };
+            writeln!(output, "{}. {}", i + 1, shown)?;
+        }
+        write!(
+            output,
+            "Type a number to change that value, or Enter to run: "
+        )?;
+        output.flush()?;
+
+        let line = read_line(input)?.unwrap_or_default();
+        if line.is_empty() {
+            return Ok(values);
+        }
+        match line.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= values.len() => {
+                // Offer the previous answer as the default
+                let var = RequiredVar {
+                    default: Some(values[n - 1].1.clone()),
+                    ..required_vars[n - 1].clone()
+                };
+                values[n - 1].1 = prompt_variable_with(&var, input, output)?;
+            }
+            _ => writeln!(output, "{} is not in the list.", line)?,
+        }
+    }
+}
+
+fn prompt_variable_with(
+    var: &RequiredVar,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<String> {
+    // A secret is typed without echo, and an empty answer keeps the default
+    // without showing it
+    if var.is_secret() {
+        write!(output, "{} (hidden): ", var.name)?;
+        output.flush()?;
+        let value = read_hidden(input, output)?.unwrap_or_default();
+        return Ok(match var.default {
+            Some(ref default) if value.is_empty() => default.clone(),
+            _ => value,
+        });
+    }
+
+    let choices: Vec<&str> = var
+        .hint
+        .as_deref()
+        .map(|hint| hint.split('|').collect())
+        .unwrap_or_default();
+
+    // A pipe-separated hint lists the allowed values
+    if choices.len() > 1 {
+        let default = var
+            .default
+            .as_deref()
+            .and_then(|d| choices.iter().position(|c| *c == d))
+            .unwrap_or(0);
+
+        writeln!(output, "Values for {}:", var.name)?;
+        for (i, choice) in choices.iter().enumerate() {
+            writeln!(output, "{}. {}", i + 1, choice)?;
+        }
+
+        loop {
+            write!(
+                output,
+                "Type a number or value for {} (Enter for {}): ",
+                var.name,
+                default + 1
+            )?;
+            output.flush()?;
+
+            let line = read_line(input)?.unwrap_or_default();
+            if line.is_empty() {
+                return Ok(choices[default].to_string());
+            }
+            if let Ok(n) = line.parse::<usize>()
+                && n >= 1
+                && n <= choices.len()
+            {
+                return Ok(choices[n - 1].to_string());
+            }
+            if choices.contains(&line.as_str()) {
+                return Ok(line);
+            }
+            writeln!(output, "{} is not one of the values.", line)?;
+        }
+    }
+
+    write!(output, "{}", var.name)?;
+    if let Some(ref hint) = var.hint {
+        write!(output, " (hint: {})", hint)?;
+    }
+    if let Some(ref default) = var.default {
+        write!(output, " [{}]", default)?;
+    }
+    write!(output, ": ")?;
+    output.flush()?;
+
+    let value = read_line(input)?.unwrap_or_default();
+    match var.default {
+        Some(ref default) if value.is_empty() => Ok(default.clone()),
+        _ => Ok(value),
+    }
+}
+
+fn confirm_with(message: &str, input: &mut impl BufRead, output: &mut impl Write) -> Result<bool> {
+    loop {
+        write!(output, "{} [Y/n]: ", message)?;
+        output.flush()?;
+
+        let Some(line) = read_line(input)? else {
+            return Ok(false);
+        };
+
+        match line.to_lowercase().as_str() {
+            "" | "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => writeln!(output, "Please answer y or n.")?,
+        }
+    }
+}
+
+/// Read one trimmed line, or `None` at end of input
+fn read_line(input: &mut impl BufRead) -> Result<Option<String>> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+/// Read a line with the terminal not echoing it
+fn read_hidden(input: &mut impl BufRead, output: &mut impl Write) -> Result<Option<String>> {
+    let echo = Echo::off();
+    let line = read_line(input);
+    if echo.is_off() {
+        // Nor the Enter that ended it
+        writeln!(output)?;
+    }
+    drop(echo);
+    line
+}
+
+/// The terminal's echo turned off for as long as it is kept, when stdin is a
+/// terminal, and put back when dropped
+#[cfg(unix)]
+struct Echo(Option<libc::termios>);
+
+#[cfg(unix)]
+impl Echo {
+    fn off() -> Self {
+        use std::io::IsTerminal;
+        if !io::stdin().is_terminal() {
+            return Self(None);
+        }
+        // SAFETY: tcgetattr and tcsetattr only read and write the termios
+        // struct given, for stdin's file descriptor
+        unsafe {
+            let mut saved: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut saved) != 0 {
+                return Self(None);
+            }
+            let mut hidden = saved;
+            hidden.c_lflag &= !libc::ECHO;
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &hidden);
+            Self(Some(saved))
+        }
+    }
+
+    fn is_off(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Echo {
+    fn drop(&mut self) {
+        if let Some(ref saved) = self.0 {
+            // SAFETY: as above, putting back the settings read before
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct Echo;
+
+#[cfg(not(unix))]
+impl Echo {
+    fn off() -> Self {
+        Self
+    }
+
+    fn is_off(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rank::Frecency;
+    use crate::target::Secret;
+    use std::path::{Path, PathBuf};
+
+    fn targets() -> Vec<Target> {
+        vec![
+            Target::new(
+                "build".to_string(),
+                Some("Compile the app".to_string()),
+                PathBuf::from("Makefile"),
+                1,
+            ),
+            Target::new("test".to_string(), None, PathBuf::from("Makefile"), 4),
+            Target::new("_setup".to_string(), None, PathBuf::from("Makefile"), 7),
+            Target::new(
+                "deploy".to_string(),
+                Some("Ship the build".to_string()),
+                PathBuf::from("Makefile"),
+                9,
+            ),
+        ]
+    }
+
+    fn pick(input: &str) -> (Option<Target>, String) {
+        let mut output = Vec::new();
+        let selected = select_target_with(
+            &targets(),
+            &PickerOptions::default(),
+            &mut input.as_bytes(),
+            &mut output,
+        )
+        .unwrap();
+        (selected, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_select_by_number() {
+        let (selected, output) = pick("2\n");
+
+        assert_eq!(selected.unwrap().name, "test");
+        assert!(output.contains("1. build - Compile the app\n"));
+        assert!(!output.contains("_setup"));
+        assert!(!output.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_filter_then_select() {
+        let (selected, output) = pick("build\n2\n");
+
+        // "build" matches deploy's description too, numbered within the filter
+        assert!(output.contains("2 targets matching \"build\":\n1. build"));
+        assert_eq!(selected.unwrap().name, "deploy");
+    }
+
+    #[test]
+    fn test_filter_by_tag() {
+        let mut all = targets();
+        all[3].tags = vec!["release".to_string()];
+        let refs: Vec<&Target> = all.iter().collect();
+
+        let found = filter(&refs, "#Release");
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            list_line(1, found[0]),
+            "1. deploy - Ship the build #release"
+        );
+    }
+
+    #[test]
+    fn test_most_used_targets_come_first() {
+        let run = |target: &str| crate::history::HistoryEntry {
+            target: target.to_string(),
+            command: format!("make {}", target),
+            dir: PathBuf::from("/app"),
+            started_at: 1000,
+            duration_ms: 10,
+            exit_code: Some(0),
+            success: true,
+            repeat: None,
+        };
+        let entries = [run("deploy"), run("deploy"), run("test")];
+        let mut options = PickerOptions {
+            frecency: Frecency::from_history(&entries, Path::new("/app"), 1000, 7.0),
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        let selected =
+            select_target_with(&targets(), &options, &mut "1\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(selected.unwrap().name, "deploy");
+        assert!(
+            String::from_utf8(output)
+                .unwrap()
+                .contains("1. deploy - Ship the build\n2. test\n3. build")
+        );
+
+        options.rank = RankMode::PureFuzzy;
+        let selected =
+            select_target_with(&targets(), &options, &mut "1\n".as_bytes(), &mut Vec::new())
+                .unwrap();
+        assert_eq!(selected.unwrap().name, "build");
+    }
+
+    #[test]
+    fn test_empty_line_clears_filter_then_cancels() {
+        let (selected, output) = pick("nothing\n\n\n");
+
+        assert!(output.contains("No targets match \"nothing\"."));
+        assert!(selected.is_none());
+    }
+
+    #[test]
+    fn test_out_of_range_number_and_eof() {
+        let (selected, output) = pick("9\n");
+
+        assert!(output.contains("9 is not in the list."));
+        assert!(selected.is_none());
+    }
+
+    #[test]
+    fn test_prompt_variable_choices() {
+        let var = RequiredVar {
+            name: "BUMP".to_string(),
+            hint: Some("patch|minor|major".to_string()),
+            default: Some("minor".to_string()),
+            secret: None,
+        };
+        let mut output = Vec::new();
+
+        let value = prompt_variable_with(&var, &mut "huge\n3\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(value, "major");
+
+        let value = prompt_variable_with(&var, &mut "patch\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(value, "patch");
+
+        let value = prompt_variable_with(&var, &mut "\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(value, "minor");
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("2. minor\n"));
+        assert!(output.contains("huge is not one of the values."));
+        assert!(output.contains("(Enter for 2)"));
+    }
+
+    #[test]
+    fn test_prompt_variables_can_change_an_answer() {
+        let vars = vec![
+            RequiredVar {
+                name: "ENV".to_string(),
+                hint: None,
+                default: None,
+                secret: None,
+            },
+            RequiredVar {
+                name: "REGION".to_string(),
+                hint: None,
+                default: None,
+                secret: None,
+            },
+        ];
+        let mut output = Vec::new();
+
+        let recipe = Recipe::from_lines(&["deploy:", "\t./deploy.sh $(ENV)"], 0);
+        let values = prompt_variables_with(
+            "deploy",
+            &vars,
+            &recipe,
+            &mut "prod
+eu
+1
+staging
+
+"
+            .as_bytes(),
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(
+            values,
+            vec![
+                ("ENV".to_string(), "staging".to_string()),
+                ("REGION".to_string(), "eu".to_string())
+            ]
+        );
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with(
+            "Variables for deploy:\nENV is used in:\n  2 │ ./deploy.sh $(ENV)\nENV: "
+        ));
+        // REGION isn't used in the recipe
+        assert!(!output.contains("REGION is used in"));
+        assert!(output.contains("1. ENV=prod\n2. REGION=eu\n"));
+        assert!(output.contains("ENV [prod]: "));
+    }
+
+    #[test]
+    fn test_prompt_secret_is_never_shown() {
+        let vars = vec![
+            RequiredVar {
+                name: "TOKEN".to_string(),
+                hint: None,
+                default: None,
+                secret: Some(Secret::default()),
+            },
+            RequiredVar {
+                name: "ENV".to_string(),
+                hint: None,
+                default: None,
+                secret: None,
+            },
+        ];
+        let mut output = Vec::new();
+
+        let values = prompt_variables_with(
+            "deploy",
+            &vars,
+            &Recipe::default(),
+            &mut "s3cr3t\nprod\n1\n\n\n".as_bytes(),
+            &mut output,
+        )
+        .unwrap();
+
+        // An empty answer when changing it keeps the one before
+        assert_eq!(values[0], ("TOKEN".to_string(), "s3cr3t".to_string()));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("TOKEN (hidden): "));
+        assert!(output.contains("1. TOKEN=***\n2. ENV=prod\n"));
+        assert!(!output.contains("s3cr3t"));
+    }
+
+    #[test]
+    fn test_select_from() {
+        let items = vec!["Makefile".to_string(), "docs/Makefile".to_string()];
+        let mut output = Vec::new();
+
+        let selected =
+            select_from_with("Makefiles", &items, &mut "3\n2\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(selected, Some(1));
+        assert!(
+            String::from_utf8(output)
+                .unwrap()
+                .contains("3 is not in the list.")
+        );
+
+        let cancelled =
+            select_from_with("Makefiles", &items, &mut "\n".as_bytes(), &mut Vec::new()).unwrap();
+        assert_eq!(cancelled, None);
+    }
+
+    #[test]
+    fn test_confirm() {
+        let mut output = Vec::new();
+        assert!(confirm_with("Run?", &mut "\n".as_bytes(), &mut output).unwrap());
+        assert!(!confirm_with("Run?", &mut "maybe\nn\n".as_bytes(), &mut output).unwrap());
+    }
+}
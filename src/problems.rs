@@ -0,0 +1,394 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::runlog::strip_ansi;
+
+const PROBLEMS_FILENAME: &str = "errors.json";
+
+/// How many problems the summary after a run lists before saying how many
+/// more there are
+const MAX_SHOWN: usize = 5;
+
+/// `file:line[:col]: [error|warning:] message`, as gcc, clang, go, eslint's
+/// unix format, and make itself print
+static LOCATION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?P<file>[^\s:()]+):(?P<line>\d+)(?::(?P<col>\d+))?:\s*(?:(?P<severity>fatal error|error|warning)(?:\[[^\]]*\])?:\s*)?(?P<message>.*)$",
+    )
+    .unwrap()
+});
+
+/// `file(line,col): error CODE: message`, as tsc and msbuild print
+static PAREN_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?P<file>[^\s()]+)\((?P<line>\d+),(?P<col>\d+)\):\s*(?P<severity>error|warning)(?:\s+\w+)?:\s*(?P<message>.*)$",
+    )
+    .unwrap()
+});
+
+/// rustc's `error[E0308]: message`, whose location follows on a `-->` line
+static HEADER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?P<severity>error|warning)(?:\[\w+\])?:\s*(?P<message>.+)$").unwrap()
+});
+
+static ARROW_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^-->\s*(?P<file>[^\s:]+):(?P<line>\d+):(?P<col>\d+)$").unwrap());
+
+/// make's `make[1]: Entering directory '/app/lib'`, which later relative
+/// paths are in until it leaves again
+static DIRECTORY_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\S*make(?:\[\d+\])?: (?P<action>Entering|Leaving) directory ['`](?P<dir>.*)'$")
+        .unwrap()
+});
+
+/// How bad a reported problem is; errors sort before warnings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A file location a tool reported a problem at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Problem {
+    pub file: PathBuf,
+    pub line: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Problem {
+    /// `file:line[:col]`, with the file relative to `dir` when it is in it
+    pub fn location(&self, dir: &Path) -> String {
+        let file = self.file.strip_prefix(dir).unwrap_or(&self.file);
+        match self.column {
+            Some(column) => format!("{}:{}:{}", file.display(), self.line, column),
+            None => format!("{}:{}", file.display(), self.line),
+        }
+    }
+}
+
+/// The problems found in the output of a target's last run with `--errors`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+    pub target: String,
+    /// The directory it ran in
+    pub dir: PathBuf,
+    /// Errors first, then warnings, each in the order they were printed
+    pub problems: Vec<Problem>,
+}
+
+impl Report {
+    /// The problems in `output` that point at files that exist, with
+    /// relative paths taken from `dir`
+    pub fn new(target: &str, dir: &Path, output: &str) -> Self {
+        let mut problems: Vec<Problem> = parse(output)
+            .into_iter()
+            .map(|mut problem| {
+                problem.file = dir.join(&problem.file);
+                problem
+            })
+            .filter(|problem| problem.file.is_file())
+            .collect();
+        problems.sort_by_key(|problem| problem.severity);
+        Self {
+            target: target.to_string(),
+            dir: dir.to_path_buf(),
+            problems,
+        }
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.problems
+            .iter()
+            .filter(|p| p.severity == severity)
+            .count()
+    }
+
+    /// The summary printed after the run, or nothing when it printed no
+    /// problems
+    pub fn summary(&self) -> Vec<String> {
+        if self.problems.is_empty() {
+            return Vec::new();
+        }
+
+        let (errors, warnings) = (self.count(Severity::Error), self.count(Severity::Warning));
+        let header = match (errors, warnings) {
+            (0, w) => format!("{} {} warning(s)", "!".yellow(), w),
+            (e, 0) => format!("{} {} error(s)", "✗".red(), e),
+            (e, w) => format!("{} {} error(s), {} warning(s)", "✗".red(), e, w),
+        };
+        let mut lines = vec![format!(
+            "{} {}",
+            header,
+            "(maki errors opens the first)".dimmed()
+        )];
+        for problem in self.problems.iter().take(MAX_SHOWN) {
+            lines.push(format!(
+                "  {}  {}",
+                problem.location(&self.dir).bold(),
+                problem.message
+            ));
+        }
+        if self.problems.len() > MAX_SHOWN {
+            lines.push(format!(
+                "  {}",
+                format!(
+                    "… and {} more (maki errors --list)",
+                    self.problems.len() - MAX_SHOWN
+                )
+                .dimmed()
+            ));
+        }
+        lines
+    }
+}
+
+/// The problems `output` reports, in the order printed, with paths as
+/// printed except those under a directory make said it entered
+pub fn parse(output: &str) -> Vec<Problem> {
+    let output = strip_ansi(output);
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    let mut header: Option<(Severity, String)> = None;
+    let mut problems: Vec<Problem> = Vec::new();
+
+    for line in output.lines() {
+        let line = line.trim();
+
+        if let Some(caps) = DIRECTORY_REGEX.captures(line) {
+            match &caps["action"] {
+                "Entering" => dirs.push(PathBuf::from(&caps["dir"])),
+                _ => {
+                    dirs.pop();
+                }
+            }
+            continue;
+        }
+
+        let problem = if let Some(caps) = ARROW_REGEX.captures(line) {
+            header.take().map(|(severity, message)| Problem {
+                file: PathBuf::from(&caps["file"]),
+                line: caps["line"].parse().unwrap_or(1),
+                column: caps["col"].parse().ok(),
+                severity,
+                message,
+            })
+        } else if let Some(caps) = HEADER_REGEX.captures(line) {
+            header = Some((severity(&caps["severity"]), caps["message"].to_string()));
+            None
+        } else if let Some(caps) = PAREN_REGEX
+            .captures(line)
+            .or_else(|| LOCATION_REGEX.captures(line))
+        {
+            let column = caps.name("col").and_then(|c| c.as_str().parse().ok());
+            let message = caps["message"].trim();
+            let severity = match caps.name("severity") {
+                Some(s) => Some(severity(s.as_str())),
+                // make's own `Makefile:5: *** missing separator.  Stop.`
+                None if message.starts_with("***") => Some(Severity::Error),
+                // Without a word for it, only a column tells a problem
+                // from any other `name:1:` text, and notes aren't problems
+                None if column.is_some() && !message.starts_with("note:") => Some(Severity::Error),
+                None => None,
+            };
+            severity.map(|severity| Problem {
+                file: PathBuf::from(&caps["file"]),
+                line: caps["line"].parse().unwrap_or(1),
+                column,
+                severity,
+                message: message.trim_start_matches("*** ").to_string(),
+            })
+        } else {
+            None
+        };
+
+        if let Some(mut problem) = problem {
+            if let Some(dir) = dirs.last() {
+                problem.file = dir.join(&problem.file);
+            }
+            if !problems.contains(&problem) {
+                problems.push(problem);
+            }
+        }
+    }
+
+    problems
+}
+
+fn severity(word: &str) -> Severity {
+    match word {
+        "warning" => Severity::Warning,
+        _ => Severity::Error,
+    }
+}
+
+/// Where the last run's problems are kept: `<data dir>/maki/errors.json`
+pub fn problems_file_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|p| p.join("maki").join(PROBLEMS_FILENAME))
+}
+
+/// Keep `report` for `maki errors`, replacing the last run's
+pub fn save(report: &Report) -> Result<()> {
+    let path = problems_file_path().context("Could not determine data directory")?;
+    save_to(&path, report)
+}
+
+/// The problems of the last run with `--errors`, if there was one
+pub fn load() -> Result<Option<Report>> {
+    match problems_file_path() {
+        Some(path) => load_from(&path),
+        None => Ok(None),
+    }
+}
+
+fn save_to(path: &Path, report: &Report) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create data directory: {}", dir.display()))?;
+    }
+    let json = serde_json::to_string_pretty(report).context("Failed to serialize errors")?;
+    fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn load_from(path: &Path) -> Result<Option<Report>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let report = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    Ok(Some(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn problem(file: &str, line: usize, column: Option<usize>, severity: Severity) -> Problem {
+        Problem {
+            file: PathBuf::from(file),
+            line,
+            column,
+            severity,
+            message: String::new(),
+        }
+    }
+
+    fn locations(problems: &[Problem]) -> Vec<(PathBuf, usize, Option<usize>, Severity)> {
+        problems
+            .iter()
+            .map(|p| (p.file.clone(), p.line, p.column, p.severity))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_compiler_formats() {
+        let output = "\
+cc -c main.c
+main.c:12:5: error: use of undeclared identifier 'x'
+main.c:3:10: note: declared here
+main.c:20:1: warning: unused variable 'y' [-Wunused-variable]
+\x1b[1m\x1b[31merror[E0308]\x1b[0m: mismatched types
+  --> src/lib.rs:4:18
+warning: `app` (lib) generated 1 warning
+src/index.ts(7,3): error TS2322: Type 'string' is not assignable to type 'number'.
+./cmd/main.go:9:2: undefined: run
+Makefile:5: *** missing separator.  Stop.
+make: *** [Makefile:5: build] Error 1
+see notes.txt:12: for details
+";
+        let problems = parse(output);
+        assert_eq!(
+            locations(&problems),
+            vec![
+                (PathBuf::from("main.c"), 12, Some(5), Severity::Error),
+                (PathBuf::from("main.c"), 20, Some(1), Severity::Warning),
+                (PathBuf::from("src/lib.rs"), 4, Some(18), Severity::Error),
+                (PathBuf::from("src/index.ts"), 7, Some(3), Severity::Error),
+                (PathBuf::from("./cmd/main.go"), 9, Some(2), Severity::Error),
+                (PathBuf::from("Makefile"), 5, None, Severity::Error),
+            ]
+        );
+        assert_eq!(problems[0].message, "use of undeclared identifier 'x'");
+        assert_eq!(problems[2].message, "mismatched types");
+        assert_eq!(problems[5].message, "missing separator.  Stop.");
+    }
+
+    #[test]
+    fn test_parse_follows_make_directories() {
+        let output = "\
+make[1]: Entering directory '/app/lib'
+util.c:3:1: error: expected ';'
+make[1]: Leaving directory '/app/lib'
+main.c:8:2: error: expected ';'
+main.c:8:2: error: expected ';'
+";
+        assert_eq!(
+            locations(&parse(output)),
+            vec![
+                (
+                    PathBuf::from("/app/lib/util.c"),
+                    3,
+                    Some(1),
+                    Severity::Error
+                ),
+                (PathBuf::from("main.c"), 8, Some(2), Severity::Error),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report_keeps_existing_files_errors_first() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("main.c"), "").unwrap();
+        let output = "\
+main.c:2:1: warning: unused variable 'y'
+gone.c:1:1: error: expected ';'
+main.c:9:4: error: expected ';'
+";
+        let report = Report::new("build", dir.path(), output);
+        let main = dir.path().join("main.c");
+        assert_eq!(
+            locations(&report.problems),
+            vec![
+                (main.clone(), 9, Some(4), Severity::Error),
+                (main, 2, Some(1), Severity::Warning),
+            ]
+        );
+        assert_eq!(report.problems[0].location(dir.path()), "main.c:9:4");
+
+        colored::control::set_override(false);
+        let summary = report.summary();
+        assert_eq!(
+            summary[0],
+            "✗ 1 error(s), 1 warning(s) (maki errors opens the first)"
+        );
+        assert_eq!(summary[1], "  main.c:9:4  expected ';'");
+        assert!(Report::new("build", dir.path(), "").summary().is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("maki").join(PROBLEMS_FILENAME);
+        assert_eq!(load_from(&path).unwrap(), None);
+
+        let report = Report {
+            target: "build".to_string(),
+            dir: PathBuf::from("/app"),
+            problems: vec![problem("/app/main.c", 9, None, Severity::Error)],
+        };
+        save_to(&path, &report).unwrap();
+        assert_eq!(load_from(&path).unwrap(), Some(report));
+    }
+}
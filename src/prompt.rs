@@ -1,25 +1,95 @@
 use anyhow::Result;
 use colored::Colorize;
-use dialoguer::{FuzzySelect, Input, theme::ColorfulTheme};
+use dialoguer::theme::{ColorfulTheme, SimpleTheme, Theme};
+use dialoguer::{Confirm, FuzzySelect, Input, Password, Select};
 
+use crate::executor::MASK;
+use crate::recipe::Recipe;
 use crate::target::RequiredVar;
 
-/// Prompt the user for values for required variables
+/// Prompt the user for values for the required variables of `target`, each
+/// after the lines of `recipe` that use it, then offer to change any of them
+/// before running
 /// Returns a Vec of (name, value) tuples
-pub fn prompt_for_variables(required_vars: &[RequiredVar]) -> Result<Vec<(String, String)>> {
+pub fn prompt_for_variables(
+    target: &str,
+    required_vars: &[RequiredVar],
+    recipe: &Recipe,
+) -> Result<Vec<(String, String)>> {
+    println!("{} Variables for {}", "?".cyan().bold(), target.bold());
+
+    let color = colored::control::SHOULD_COLORIZE.should_colorize();
     let mut values = Vec::new();
-
     for var in required_vars {
+        // So the value can be checked against how it will be used
+        if let Some(usages) = recipe.render_usages(&var.name, color) {
+            println!("  {} {}", var.name.green(), "is used in:".dimmed());
+            print!("{}", usages);
+        }
         let value = prompt_single_variable(var)?;
         values.push((var.name.clone(), value));
     }
 
-    Ok(values)
+    // A single answer was just typed; with more, one may need a second look
+    if values.len() < 2 {
+        return Ok(values);
+    }
+
+    loop {
+        let mut items = vec!["Run with these values".to_string()];
+        for ((name, value), var) in values.iter().zip(required_vars) {
+            let shown = if var.is_secret() {
+                MASK
+            } else {
+                value.as_str()
+            };
+            items.push(format!("Change {} ({})", name, shown));
+        }
+
+        let selection = Select::with_theme(theme().as_ref())
+            .with_prompt("Ready to run?")
+            .items(&items)
+            .default(0)
+            .interact()?;
+        if selection == 0 {
+            return Ok(values);
+        }
+
+        // Offer the previous answer as the default
+        let index = selection - 1;
+        let var = RequiredVar {
+            default: Some(values[index].1.clone()),
+            ..required_vars[index].clone()
+        };
+        values[index].1 = prompt_single_variable(&var)?;
+    }
+}
+
+/// The prompt theme, without colors when they are turned off (--color, NO_COLOR)
+pub(crate) fn theme() -> Box<dyn Theme> {
+    if colored::control::SHOULD_COLORIZE.should_colorize() {
+        Box::new(ColorfulTheme::default())
+    } else {
+        Box::new(SimpleTheme)
+    }
 }
 
 /// Prompt for a single variable value
 fn prompt_single_variable(var: &RequiredVar) -> Result<String> {
-    let theme = ColorfulTheme::default();
+    let theme = theme();
+
+    // A secret is typed without echo, and an empty answer keeps the default
+    // without showing it
+    if var.is_secret() {
+        let value = Password::with_theme(theme.as_ref())
+            .with_prompt(format!("{}", var.name.green().bold()))
+            .allow_empty_password(var.default.is_some())
+            .interact()?;
+        return Ok(match var.default {
+            Some(ref default) if value.is_empty() => default.clone(),
+            _ => value,
+        });
+    }
 
     // If hint contains pipe-separated values, show a selection menu
     if let Some(ref hint) = var.hint {
@@ -33,9 +103,15 @@ fn prompt_single_variable(var: &RequiredVar) -> Result<String> {
                 var.name.green().bold()
             );
 
-            let selection = FuzzySelect::with_theme(&theme)
+            let default = var
+                .default
+                .as_deref()
+                .and_then(|d| options.iter().position(|o| *o == d))
+                .unwrap_or(0);
+
+            let selection = FuzzySelect::with_theme(theme.as_ref())
                 .items(&options)
-                .default(0)
+                .default(default)
                 .interact()?;
 
             return Ok(options[selection].to_string());
@@ -48,13 +124,25 @@ fn prompt_single_variable(var: &RequiredVar) -> Result<String> {
         None => format!("{}", var.name.green().bold()),
     };
 
-    let value: String = Input::with_theme(&theme)
-        .with_prompt(prompt_msg)
-        .interact_text()?;
+    let mut input = Input::with_theme(theme.as_ref()).with_prompt(prompt_msg);
+    if let Some(ref default) = var.default {
+        input = input.default(default.clone());
+    }
+    let value: String = input.interact_text()?;
 
     Ok(value)
 }
 
+/// Ask a yes/no question, defaulting to yes
+pub fn confirm(message: &str) -> Result<bool> {
+    let confirmed = Confirm::with_theme(theme().as_ref())
+        .with_prompt(message)
+        .default(true)
+        .interact()?;
+
+    Ok(confirmed)
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
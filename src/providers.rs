@@ -0,0 +1,417 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use crate::target::{Provider, Target};
+
+/// Matches `name: command` process lines
+static PROCFILE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([A-Za-z0-9_-]+):\s*(.+)$").unwrap());
+
+/// File names docker compose looks for, in its own order of preference
+const COMPOSE_FILE_NAMES: [&str; 4] = [
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yml",
+    "docker-compose.yaml",
+];
+
+/// Which providers a project uses, and whose target wins when two define
+/// the same name (`[providers]` table)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProviderPolicy {
+    /// Providers whose targets win name clashes, highest first; the unlisted
+    /// ones follow in the default order (make, procfile, compose, inferred)
+    pub priority: Vec<Provider>,
+    /// Providers to ignore in this project
+    pub disabled: Vec<Provider>,
+}
+
+impl ProviderPolicy {
+    /// Every provider, highest priority first
+    pub fn order(&self) -> Vec<Provider> {
+        let mut order = Vec::new();
+        for provider in self.priority.iter().chain(&Provider::ALL) {
+            if !order.contains(provider) {
+                order.push(*provider);
+            }
+        }
+        order
+    }
+
+    pub fn is_enabled(&self, provider: Provider) -> bool {
+        !self.disabled.contains(&provider)
+    }
+}
+
+/// What one provider found in a project directory
+#[derive(Debug, Clone)]
+pub struct Discovery {
+    pub provider: Provider,
+    /// The files the targets were read from
+    pub files: Vec<PathBuf>,
+    pub targets: Vec<Target>,
+    /// How long finding and parsing the files took
+    pub duration: Duration,
+    /// What parsing the files warned about
+    pub warnings: Vec<String>,
+}
+
+/// Find the Procfile processes or compose services in `dir`; `None` when
+/// the provider's file isn't there
+///
+/// Makefiles are found by the caller, which caches their targets.
+pub fn discover(provider: Provider, dir: &Path) -> Option<Discovery> {
+    let started = Instant::now();
+    let (file, targets) = match provider {
+        Provider::Make | Provider::Inferred => return None,
+        Provider::Procfile => {
+            let file = dir.join("Procfile");
+            let content = fs::read_to_string(&file).ok()?;
+            let targets = parse_procfile(&content, &file);
+            (file, targets)
+        }
+        Provider::Compose => {
+            let file = find_compose_file(dir)?;
+            let content = fs::read_to_string(&file).ok()?;
+            let targets = parse_compose(&content, &file);
+            (file, targets)
+        }
+    };
+
+    Some(Discovery {
+        provider,
+        files: vec![file],
+        targets,
+        duration: started.elapsed(),
+        warnings: Vec::new(),
+    })
+}
+
+/// Combine what the providers found, keeping the first target of each name
+/// in priority order
+pub fn merge(mut discoveries: Vec<Discovery>, policy: &ProviderPolicy) -> Vec<Target> {
+    let order = policy.order();
+    discoveries.sort_by_key(|d| order.iter().position(|p| *p == d.provider));
+
+    let mut seen = HashSet::new();
+    discoveries
+        .into_iter()
+        .flat_map(|d| d.targets)
+        .filter(|t| seen.insert(t.name.clone()))
+        .collect()
+}
+
+/// One provider's line in `maki providers`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProviderReport {
+    pub provider: Provider,
+    /// The project root the provider was looked for in
+    pub root: PathBuf,
+    pub enabled: bool,
+    /// The files it read; empty when it found nothing
+    pub files: Vec<PathBuf>,
+    /// Targets it found
+    pub targets: usize,
+    /// Targets it contributed, leaving out names a higher-priority provider
+    /// already defines
+    pub contributed: usize,
+    pub duration_ms: f64,
+    /// What parsing its files warned about, e.g. a Makefile read as Latin-1
+    pub warnings: Vec<String>,
+}
+
+/// Report every provider for `root`, highest priority first, given what
+/// each of them found there
+pub fn report(
+    root: &Path,
+    discoveries: &[Discovery],
+    policy: &ProviderPolicy,
+) -> Vec<ProviderReport> {
+    let mut seen = HashSet::new();
+    policy
+        .order()
+        .into_iter()
+        .map(|provider| {
+            let enabled = policy.is_enabled(provider);
+            let found = discoveries.iter().find(|d| d.provider == provider);
+            let contributed = match found {
+                Some(d) if enabled => d
+                    .targets
+                    .iter()
+                    .filter(|t| seen.insert(t.name.clone()))
+                    .count(),
+                _ => 0,
+            };
+            ProviderReport {
+                provider,
+                root: root.to_path_buf(),
+                enabled,
+                files: found.map(|d| d.files.clone()).unwrap_or_default(),
+                targets: found.map_or(0, |d| d.targets.len()),
+                contributed,
+                duration_ms: found.map_or(0.0, |d| d.duration.as_secs_f64() * 1000.0),
+                warnings: found.map(|d| d.warnings.clone()).unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+/// Find the compose file docker compose would use in `dir`
+pub fn find_compose_file(dir: &Path) -> Option<PathBuf> {
+    COMPOSE_FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|p| p.exists())
+}
+
+/// Parse Procfile entries (`web: bundle exec rails s`) into targets
+pub fn parse_procfile(content: &str, file: &Path) -> Vec<Target> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim_start().starts_with('#'))
+        .filter_map(|(i, line)| {
+            let caps = PROCFILE_REGEX.captures(line.trim_end())?;
+            let mut target = Target::new(
+                caps[1].to_string(),
+                Some(caps[2].trim().to_string()),
+                file.to_path_buf(),
+                i + 1,
+            );
+            target.provider = Provider::Procfile;
+            Some(target)
+        })
+        .collect()
+}
+
+/// Parse the service names from a compose file into targets
+///
+/// Only the `services:` mapping is read: each key one level below it is a
+/// service, and its `image:` (if any) becomes the description.
+pub fn parse_compose(content: &str, file: &Path) -> Vec<Target> {
+    let mut targets: Vec<Target> = Vec::new();
+    let mut in_services = false;
+    let mut service_indent: Option<usize> = None;
+
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let indent = line.len() - line.trim_start().len();
+
+        if indent == 0 {
+            in_services = trimmed == "services:";
+            service_indent = None;
+            continue;
+        }
+
+        if !in_services {
+            continue;
+        }
+
+        let service_indent = *service_indent.get_or_insert(indent);
+
+        if indent == service_indent {
+            if let Some(name) = trimmed.strip_suffix(':') {
+                let name = name.trim_matches(|c| c == '"' || c == '\'');
+                let mut target = Target::new(name.to_string(), None, file.to_path_buf(), i + 1);
+                target.provider = Provider::Compose;
+                targets.push(target);
+            }
+        } else if indent > service_indent
+            && let Some(image) = trimmed.strip_prefix("image:")
+            && let Some(service) = targets.last_mut()
+            && service.description.is_none()
+        {
+            let image = image.trim().trim_matches(|c| c == '"' || c == '\'');
+            service.description = Some(format!("image {}", image));
+        }
+    }
+
+    targets
+}
+
+/// Process manager used to start Procfile entries: overmind if installed,
+/// otherwise foreman
+pub fn procfile_runner() -> &'static str {
+    if is_on_path("overmind") {
+        "overmind"
+    } else {
+        "foreman"
+    }
+}
+
+fn is_on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_procfile() {
+        let content = "web: bundle exec rails s -p $PORT\n# worker: disabled\nworker:  sidekiq\n\n";
+        let targets = parse_procfile(content, Path::new("Procfile"));
+
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].name, "web");
+        assert_eq!(
+            targets[0].description,
+            Some("bundle exec rails s -p $PORT".to_string())
+        );
+        assert_eq!(targets[0].provider, Provider::Procfile);
+        assert_eq!(targets[1].name, "worker");
+        assert_eq!(targets[1].line, 3);
+    }
+
+    #[test]
+    fn test_parse_compose_services() {
+        let content = r#"
+version: "3.8"
+services:
+  web:
+    build: .
+    ports:
+      - "3000:3000"
+    depends_on:
+      - db
+  db:
+    image: "postgres:16"
+    environment:
+      POSTGRES_PASSWORD: secret
+volumes:
+  data:
+"#;
+        let targets = parse_compose(content, Path::new("compose.yaml"));
+
+        let names: Vec<&str> = targets.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["web", "db"]);
+        assert_eq!(targets[0].description, None);
+        assert_eq!(
+            targets[1].description,
+            Some("image postgres:16".to_string())
+        );
+        assert_eq!(targets[1].line, 10);
+        assert!(targets.iter().all(|t| t.provider == Provider::Compose));
+    }
+
+    #[test]
+    fn test_discover_prefers_compose_yaml() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("Procfile"), "web: npm start\n").unwrap();
+        fs::write(dir.path().join("docker-compose.yml"), "services:\n  old:\n").unwrap();
+        fs::write(dir.path().join("compose.yaml"), "services:\n  api:\n").unwrap();
+
+        let procfile = discover(Provider::Procfile, dir.path()).unwrap();
+        let compose = discover(Provider::Compose, dir.path()).unwrap();
+
+        assert_eq!(procfile.files, vec![dir.path().join("Procfile")]);
+        assert_eq!(procfile.targets[0].name, "web");
+        assert_eq!(compose.files, vec![dir.path().join("compose.yaml")]);
+        assert_eq!(compose.targets[0].name, "api");
+        assert!(discover(Provider::Make, dir.path()).is_none());
+    }
+
+    fn discovery(provider: Provider, names: &[&str]) -> Discovery {
+        Discovery {
+            provider,
+            files: vec![PathBuf::from(provider.name())],
+            targets: names
+                .iter()
+                .map(|name| {
+                    let mut t = Target::new(name.to_string(), None, PathBuf::from("f"), 1);
+                    t.provider = provider;
+                    t
+                })
+                .collect(),
+            duration: Duration::from_millis(2),
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_policy_order() {
+        let policy = ProviderPolicy {
+            priority: vec![Provider::Compose],
+            disabled: vec![Provider::Procfile],
+        };
+
+        assert_eq!(
+            policy.order(),
+            vec![
+                Provider::Compose,
+                Provider::Make,
+                Provider::Procfile,
+                Provider::Inferred
+            ]
+        );
+        assert!(!policy.is_enabled(Provider::Procfile));
+        assert_eq!(ProviderPolicy::default().order(), Provider::ALL);
+    }
+
+    #[test]
+    fn test_merge_follows_priority() {
+        let found = || {
+            vec![
+                discovery(Provider::Make, &["web", "build"]),
+                discovery(Provider::Compose, &["web", "db"]),
+            ]
+        };
+
+        let targets = merge(found(), &ProviderPolicy::default());
+        assert_eq!(targets[0].provider, Provider::Make);
+        assert_eq!(targets.len(), 3);
+
+        let policy = ProviderPolicy {
+            priority: vec![Provider::Compose],
+            ..Default::default()
+        };
+        let targets = merge(found(), &policy);
+        assert_eq!(targets[0].name, "web");
+        assert_eq!(targets[0].provider, Provider::Compose);
+    }
+
+    #[test]
+    fn test_report() {
+        let found = vec![
+            discovery(Provider::Make, &["web", "build"]),
+            discovery(Provider::Compose, &["web", "db"]),
+        ];
+        let policy = ProviderPolicy {
+            disabled: vec![Provider::Procfile],
+            ..Default::default()
+        };
+
+        let report = report(Path::new("/project"), &found, &policy);
+
+        assert_eq!(report.len(), 4);
+        assert_eq!((report[0].targets, report[0].contributed), (2, 2));
+        assert!(!report[1].enabled);
+        assert!(report[1].files.is_empty());
+        assert_eq!(report[2].provider, Provider::Compose);
+        assert_eq!((report[2].targets, report[2].contributed), (2, 1));
+        assert_eq!(report[2].duration_ms, 2.0);
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        let policy: ProviderPolicy =
+            toml::from_str("priority = [\"compose\"]\ndisabled = [\"procfile\"]\n").unwrap();
+        assert_eq!(policy.priority, vec![Provider::Compose]);
+        assert_eq!(policy.disabled, vec![Provider::Procfile]);
+
+        assert!(toml::from_str::<ProviderPolicy>("disabled = [\"npm\"]\n").is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+
+use crate::terminal;
+
+/// How long the keyboard is waited on before checking whether the run is over
+const KEYBOARD_POLL_MS: i32 = 100;
+
+/// Start `cmd` in a session of its own, with a new pseudo-terminal the size
+/// of maki's as its controlling terminal and stdio; returns the terminal's
+/// other end, which reads what it prints and takes what it is typed
+pub fn spawn(mut cmd: Command) -> io::Result<(OwnedFd, Child)> {
+    let (width, height) = terminal::size();
+    let mut size = libc::winsize {
+        ws_row: height,
+        ws_col: width,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let (mut master, mut slave) = (0, 0);
+    // SAFETY: openpty only writes the two descriptors it opens
+    let opened = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &raw mut size,
+        )
+    };
+    if opened != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: openpty succeeded, so both are open and nothing else owns them
+    let (master, slave) = unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+
+    cmd.stdin(Stdio::from(slave.try_clone()?))
+        .stdout(Stdio::from(slave.try_clone()?))
+        .stderr(Stdio::from(slave));
+    // SAFETY: setsid and ioctl are async-signal-safe, as pre_exec requires
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = cmd.spawn()?;
+    // The command holds maki's copies of the terminal's end; reading the
+    // output only ends once they are closed
+    drop(cmd);
+    Ok((master, child))
+}
+
+/// Whether a read of the terminal's output failed because the run is over:
+/// Linux reports EIO once every process on the terminal has closed it
+pub fn is_closed(err: &io::Error) -> bool {
+    err.kind() != io::ErrorKind::Interrupted
+}
+
+/// The keyboard passed through to a terminal's target until dropped, so the
+/// next thing to read stdin gets what is typed after the run
+pub struct Keyboard {
+    done: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Keyboard {
+    /// Pass what is typed on to `terminal`, its other end; at the end of
+    /// stdin, the target is sent end of input (Ctrl-D)
+    pub fn forward(terminal: &OwnedFd) -> io::Result<Self> {
+        let mut terminal = File::from(terminal.try_clone()?);
+        let done = Arc::new(AtomicBool::new(false));
+        let stop = done.clone();
+        let thread = std::thread::spawn(move || {
+            let mut stdin = io::stdin();
+            let mut buf = [0u8; 1024];
+            while !stop.load(Ordering::Relaxed) {
+                let mut ready = libc::pollfd {
+                    fd: stdin.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                // SAFETY: poll only writes revents of the one pollfd given
+                if unsafe { libc::poll(&mut ready, 1, KEYBOARD_POLL_MS) } <= 0 {
+                    continue;
+                }
+                match stdin.read(&mut buf) {
+                    Ok(0) | Err(_) => {
+                        let _ = terminal.write_all(&[4]);
+                        break;
+                    }
+                    Ok(n) => {
+                        if terminal.write_all(&buf[..n]).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            done,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spawn_presents_a_terminal() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg("test -t 0 && test -t 1 && test -t 2 && printf 'tty\\n'; exit 4");
+
+        let (terminal, mut child) = spawn(cmd).unwrap();
+        let mut screen = File::from(terminal);
+        let mut output = Vec::new();
+        let mut buf = [0u8; 256];
+        loop {
+            match screen.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => output.extend_from_slice(&buf[..n]),
+                Err(ref e) if is_closed(e) => break,
+                Err(_) => continue,
+            }
+        }
+
+        assert_eq!(child.wait().unwrap().code(), Some(4));
+        // The terminal turns `\n` into `\r\n`, as a real one does
+        assert_eq!(output, b"tty\r\n");
+    }
+}
@@ -0,0 +1,229 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::glob::glob_matches;
+use crate::overview::category_of;
+use crate::target::{Provider, Target};
+
+/// The fields a query can filter on, as `key=value` words or JSON keys
+pub const FIELDS: [&str; 6] = [
+    "name",
+    "category",
+    "tag",
+    "provider",
+    "has_required_vars",
+    "file",
+];
+
+/// Which targets `maki query` prints; every condition given must hold, and
+/// an empty query matches every target
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Query {
+    /// A glob over the target name, e.g. `docker/*`
+    pub name: Option<String>,
+    /// The prefix before the name's first separator, as in `maki overview`
+    pub category: Option<String>,
+    /// One of its `@tags`, in any case
+    pub tag: Option<String>,
+    pub provider: Option<Provider>,
+    pub has_required_vars: Option<bool>,
+    /// A glob over the file the target is in, relative to the project
+    pub file: Option<String>,
+}
+
+impl Query {
+    /// A query from the words after `maki query`: either one JSON document,
+    /// e.g. `{"name": "docker/*"}`, or `key=value` words like
+    /// `provider=make has_required_vars=true`
+    pub fn parse(words: &[String]) -> Result<Self> {
+        if let [document] = words
+            && document.trim_start().starts_with('{')
+        {
+            return serde_json::from_str(document).context("Invalid JSON query");
+        }
+
+        let mut query = Self::default();
+        for word in words {
+            let Some((key, value)) = word.split_once('=') else {
+                anyhow::bail!(
+                    "Expected key=value in the query, not `{}` (keys: {})",
+                    word,
+                    FIELDS.join(", ")
+                );
+            };
+            let value = value.to_string();
+            match key {
+                "name" => query.name = Some(value),
+                "category" => query.category = Some(value),
+                "tag" => query.tag = Some(value),
+                "file" => query.file = Some(value),
+                "provider" => {
+                    let provider = Provider::ALL
+                        .into_iter()
+                        .find(|p| p.name() == value)
+                        .with_context(|| {
+                            format!(
+                                "Unknown provider `{}` in the query (make, procfile, compose, inferred)",
+                                value
+                            )
+                        })?;
+                    query.provider = Some(provider);
+                }
+                "has_required_vars" => {
+                    let wanted = value.parse().with_context(|| {
+                        format!("has_required_vars is true or false, not `{}`", value)
+                    })?;
+                    query.has_required_vars = Some(wanted);
+                }
+                _ => anyhow::bail!("Unknown query key `{}` (keys: {})", key, FIELDS.join(", ")),
+            }
+        }
+        Ok(query)
+    }
+
+    /// Whether `target` meets every condition, with its file matched
+    /// relative to `base_dir`
+    pub fn matches(&self, target: &Target, base_dir: &Path) -> bool {
+        if let Some(ref pattern) = self.name
+            && !glob_matches(pattern, &target.name)
+        {
+            return false;
+        }
+        if let Some(ref category) = self.category
+            && category_of(&target.name) != Some(category.as_str())
+        {
+            return false;
+        }
+        if let Some(ref tag) = self.tag
+            && !target.has_tag(tag)
+        {
+            return false;
+        }
+        if let Some(provider) = self.provider
+            && target.provider != provider
+        {
+            return false;
+        }
+        if let Some(wanted) = self.has_required_vars
+            && target.has_required_vars() != wanted
+        {
+            return false;
+        }
+        if let Some(ref pattern) = self.file {
+            let file = target.file.strip_prefix(base_dir).unwrap_or(&target.file);
+            if !glob_matches(pattern, &file.to_string_lossy()) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// The targets the query matches, in the order given
+    pub fn filter<'a>(&self, targets: &'a [Target], base_dir: &Path) -> Vec<&'a Target> {
+        targets
+            .iter()
+            .filter(|t| self.matches(t, base_dir))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::target::RequiredVar;
+    use std::path::PathBuf;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    fn target(name: &str, file: &str) -> Target {
+        Target::new(name.to_string(), None, PathBuf::from(file), 1)
+    }
+
+    fn names(targets: &[&Target]) -> Vec<String> {
+        targets.iter().map(|t| t.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_parse_words_and_json() {
+        let expected = Query {
+            name: Some("docker/*".to_string()),
+            provider: Some(Provider::Make),
+            has_required_vars: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            Query::parse(&words(&[
+                "name=docker/*",
+                "provider=make",
+                "has_required_vars=true"
+            ]))
+            .unwrap(),
+            expected
+        );
+        assert_eq!(
+            Query::parse(&words(&[
+                r#"{"name": "docker/*", "provider": "make", "has_required_vars": true}"#
+            ]))
+            .unwrap(),
+            expected
+        );
+        assert_eq!(Query::parse(&[]).unwrap(), Query::default());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = |w: &[&str]| Query::parse(&words(w)).unwrap_err().to_string();
+
+        assert!(err(&["docker"]).contains("Expected key=value"));
+        assert!(err(&["owner=me"]).contains("Unknown query key `owner`"));
+        assert!(err(&["provider=cargo"]).contains("Unknown provider `cargo`"));
+        assert!(err(&["has_required_vars=yes"]).contains("true or false"));
+        assert!(err(&[r#"{"owner": "me"}"#]).contains("Invalid JSON query"));
+    }
+
+    #[test]
+    fn test_matches() {
+        let base = Path::new("/app");
+        let mut bump = target("bump", "/app/Makefile");
+        bump.required_vars.push(RequiredVar {
+            name: "V".to_string(),
+            hint: None,
+            default: None,
+            secret: None,
+        });
+        let mut web = target("web", "/app/Procfile");
+        web.provider = Provider::Procfile;
+        let mut image = target("docker/build", "/app/Makefile");
+        image.tags = vec!["release".to_string()];
+        let targets = vec![
+            image,
+            target("docker-push", "/app/Makefile"),
+            target("docs/serve", "/app/docs/Makefile"),
+            bump,
+            web,
+        ];
+        let query = |w: &[&str]| {
+            let query = Query::parse(&words(w)).unwrap();
+            names(&query.filter(&targets, base))
+        };
+
+        assert_eq!(query(&["name=docker/*"]), vec!["docker/build"]);
+        assert_eq!(
+            query(&["category=docker"]),
+            vec!["docker/build", "docker-push"]
+        );
+        assert_eq!(query(&["tag=Release"]), vec!["docker/build"]);
+        assert_eq!(query(&["provider=procfile"]), vec!["web"]);
+        assert_eq!(query(&["has_required_vars=true"]), vec!["bump"]);
+        assert_eq!(query(&["file=docs/*"]), vec!["docs/serve"]);
+        assert_eq!(
+            query(&["file=**/Makefile", "has_required_vars=false", "name=d*"]),
+            vec!["docker-push"]
+        );
+        assert_eq!(query(&[]).len(), targets.len());
+    }
+}
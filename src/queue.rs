@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::store::{Store, project_key};
+
+/// A target waiting in the queue, with the variables it was queued with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedTarget {
+    pub target: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, String>,
+}
+
+impl QueuedTarget {
+    pub fn new(target: &str, variables: &[(String, String)]) -> Self {
+        Self {
+            target: target.to_string(),
+            variables: variables.iter().cloned().collect(),
+        }
+    }
+
+    /// The target as it would be typed after `maki run`, e.g. `deploy ENV=prod`
+    pub fn label(&self) -> String {
+        std::iter::once(self.target.clone())
+            .chain(self.variables.iter().map(|(k, v)| format!("{}={}", k, v)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Targets marked to run later, kept per user and per project so they
+/// survive the session
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Queue {
+    /// Queued targets by project directory, first to run first
+    projects: BTreeMap<PathBuf, Vec<QueuedTarget>>,
+}
+
+impl Store for Queue {
+    const FILENAME: &'static str = "queue.json";
+    const WHAT: &'static str = "queue";
+}
+
+impl Queue {
+    /// The targets queued in the project at `dir`, in the order they run
+    pub fn items(&self, dir: &Path) -> &[QueuedTarget] {
+        self.projects
+            .get(&project_key(dir))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Queue `item` last, returning false when the same target with the same
+    /// variables is already waiting
+    pub fn push(&mut self, dir: &Path, item: QueuedTarget) -> bool {
+        let items = self.projects.entry(project_key(dir)).or_default();
+        if items.contains(&item) {
+            return false;
+        }
+        items.push(item);
+        true
+    }
+
+    /// Take every entry for `target` off the queue, returning how many there
+    /// were
+    pub fn remove(&mut self, dir: &Path, target: &str) -> usize {
+        self.retain(dir, |item| item.target != target)
+    }
+
+    /// Take the first entry equal to `item` off the queue, once it has run
+    pub fn finish(&mut self, dir: &Path, item: &QueuedTarget) {
+        let mut done = false;
+        self.retain(dir, |queued| {
+            let keep = done || queued != item;
+            done |= !keep;
+            keep
+        });
+    }
+
+    /// Empty the project's queue, returning how many targets it held
+    pub fn clear(&mut self, dir: &Path) -> usize {
+        self.projects
+            .remove(&project_key(dir))
+            .map_or(0, |items| items.len())
+    }
+
+    fn retain(&mut self, dir: &Path, keep: impl FnMut(&QueuedTarget) -> bool) -> usize {
+        let key = project_key(dir);
+        let Some(items) = self.projects.get_mut(&key) else {
+            return 0;
+        };
+        let before = items.len();
+        items.retain(keep);
+        let removed = before - items.len();
+        if items.is_empty() {
+            self.projects.remove(&key);
+        }
+        removed
+    }
+}
+
+/// The picker header while queueing targets, e.g. `Queued: build  test`
+pub fn status_header(items: &[QueuedTarget]) -> Option<String> {
+    if items.is_empty() {
+        return None;
+    }
+
+    let labels: Vec<String> = items.iter().map(QueuedTarget::label).collect();
+    Some(format!("Queued: {}", labels.join("  ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_push_save_and_load() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("maki").join(Queue::FILENAME);
+        let project = dir.path();
+
+        let mut queue = Queue::default();
+        assert!(queue.push(project, QueuedTarget::new("build", &[])));
+        assert!(queue.push(
+            project,
+            QueuedTarget::new("deploy", &vars(&[("ENV", "prod")]))
+        ));
+        // The same target again is only queued with other variables
+        assert!(!queue.push(project, QueuedTarget::new("build", &[])));
+        assert!(queue.push(
+            project,
+            QueuedTarget::new("deploy", &vars(&[("ENV", "staging")]))
+        ));
+        queue.save_to(&path).unwrap();
+
+        let queue = Queue::load_from(&path).unwrap();
+        let labels: Vec<String> = queue
+            .items(&project.join("."))
+            .iter()
+            .map(QueuedTarget::label)
+            .collect();
+        assert_eq!(
+            labels,
+            vec!["build", "deploy ENV=prod", "deploy ENV=staging"]
+        );
+        assert!(queue.items(&project.join("maki")).is_empty());
+    }
+
+    #[test]
+    fn test_remove_finish_and_clear() {
+        let dir = TempDir::new().unwrap();
+        let project = dir.path();
+        let build = QueuedTarget::new("build", &[]);
+        let mut queue = Queue::default();
+        queue.push(project, build.clone());
+        queue.push(project, QueuedTarget::new("test", &[]));
+        queue.push(project, QueuedTarget::new("test", &vars(&[("V", "1")])));
+
+        assert_eq!(queue.remove(project, "test"), 2);
+        assert_eq!(queue.remove(project, "test"), 0);
+
+        queue.push(project, QueuedTarget::new("lint", &[]));
+        queue.finish(project, &build);
+        assert_eq!(queue.items(project), [QueuedTarget::new("lint", &[])]);
+
+        assert_eq!(queue.clear(project), 1);
+        assert_eq!(queue, Queue::default());
+    }
+
+    #[test]
+    fn test_status_header() {
+        assert_eq!(status_header(&[]), None);
+        assert_eq!(
+            status_header(&[
+                QueuedTarget::new("build", &[]),
+                QueuedTarget::new("deploy", &vars(&[("ENV", "prod")])),
+            ])
+            .as_deref(),
+            Some("Queued: build  deploy ENV=prod")
+        );
+    }
+}
@@ -0,0 +1,103 @@
+/// Characters that mean nothing special to a POSIX shell, anywhere in a word
+fn is_safe(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "-_./=:@%+,".contains(c)
+}
+
+/// Whether `name` could be a shell or make variable name
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// `word` as a POSIX shell reads it back unchanged: as it is when made of
+/// safe characters, otherwise in single quotes
+///
+/// Inside single quotes nothing is special, spaces and newlines included, so
+/// the only thing to escape is a single quote itself, which ends the quotes,
+/// adds an escaped quote, and opens them again: `it's` becomes `'it'\''s'`.
+pub fn word(word: &str) -> String {
+    if !word.is_empty() && word.chars().all(is_safe) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+/// `name=value`, with only the value quoted so it reads as typed, e.g.
+/// `ARGS='--flag '\''a b'\'''`
+pub fn assignment(name: &str, value: &str) -> String {
+    format!("{}={}", name, self::word(value))
+}
+
+/// An argument, treating `NAME=value` as an assignment so that only its
+/// value is quoted
+pub fn arg(arg: &str) -> String {
+    match arg.split_once('=') {
+        Some((name, value)) if is_name(name) => assignment(name, value),
+        _ => word(arg),
+    }
+}
+
+/// A command line that a POSIX shell splits back into exactly `args`
+pub fn command(args: &[String]) -> String {
+    args.iter().map(|a| arg(a)).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    /// Values that a shell would split, expand, or stop reading at
+    const TRICKY: [&str; 10] = [
+        "--flag 'a b'",
+        "it's",
+        "",
+        "two\nlines",
+        "$HOME and `date` and $(id)",
+        "a\\b",
+        "\"double\"",
+        "*.rs ~ #not a comment",
+        "tab\there; echo hi &",
+        "''",
+    ];
+
+    #[test]
+    fn test_word() {
+        assert_eq!(word("build"), "build");
+        assert_eq!(word("src/main.rs"), "src/main.rs");
+        assert_eq!(word(""), "''");
+        assert_eq!(word("a b"), "'a b'");
+        assert_eq!(word("it's"), r"'it'\''s'");
+        assert_eq!(word("$HOME"), "'$HOME'");
+    }
+
+    #[test]
+    fn test_arg_quotes_only_the_value() {
+        assert_eq!(arg("ENV=prod"), "ENV=prod");
+        assert_eq!(arg(r#"ARGS=--flag 'a b'"#), r"ARGS='--flag '\''a b'\'''");
+        assert_eq!(arg("MSG="), "MSG=''");
+        assert_eq!(arg("MSG=two\nlines"), "MSG='two\nlines'");
+        // Not a variable name, so the whole word is quoted
+        assert_eq!(arg("a b=c"), "'a b=c'");
+        assert_eq!(arg("--opt=x y"), "'--opt=x y'");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_shell_reads_back_the_same_words() {
+        let mut words = Vec::new();
+        for (i, value) in TRICKY.iter().enumerate() {
+            words.push(format!("V{}={}", i, value));
+            words.push(value.to_string());
+        }
+
+        // printf prints each word it is given between NULs
+        let script = format!("printf '%s\\0' {}", command(&words));
+        let output = Command::new("sh").args(["-c", &script]).output().unwrap();
+        let printed = String::from_utf8(output.stdout).unwrap();
+
+        let read_back: Vec<&str> = printed.split_terminator('\0').collect();
+        assert_eq!(read_back, words);
+    }
+}
@@ -0,0 +1,247 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::history::{self, HistoryEntry};
+use crate::target::Target;
+use crate::usage::TeamUsage;
+
+const SECS_PER_DAY: f64 = 86_400.0;
+
+/// How the picker orders targets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RankMode {
+    /// Fuzzy match score only; targets keep the --sort order otherwise
+    PureFuzzy,
+    /// Most used first; typing only filters the list
+    Frecency,
+    /// Fuzzy match score first, frecency among equally good matches
+    #[default]
+    Hybrid,
+}
+
+/// How the picker ranks and shows targets (`[picker]` table)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RankPolicy {
+    pub rank: RankMode,
+    /// Days after which a run counts half as much as one made just now
+    pub half_life_days: f64,
+    /// Show each target's Makefile beside it in recursive mode
+    pub paths: bool,
+    /// Syntax highlighting theme of the preview, one of `fuzzy::themes()`
+    pub theme: String,
+}
+
+impl Default for RankPolicy {
+    fn default() -> Self {
+        Self {
+            rank: RankMode::default(),
+            half_life_days: 7.0,
+            paths: true,
+            theme: crate::fuzzy::DEFAULT_THEME.to_string(),
+        }
+    }
+}
+
+/// How often and how recently each target was run, from the run history
+#[derive(Debug, Clone, Default)]
+pub struct Frecency {
+    /// Summed run weights by directory and target name
+    weights: HashMap<(PathBuf, String), f64>,
+    /// Weights from the team's shared usage file, by project root and target
+    team: HashMap<(PathBuf, String), f64>,
+    /// Directory the runs of targets without a project root happen in
+    dir: PathBuf,
+}
+
+/// The weight of a run `age_secs` old: 1 for a run made now, halving every
+/// `half_life_days`
+pub fn decay(age_secs: u64, half_life_days: f64) -> f64 {
+    if half_life_days <= 0.0 {
+        return 0.0;
+    }
+    0.5_f64.powf(age_secs as f64 / SECS_PER_DAY / half_life_days)
+}
+
+impl Frecency {
+    /// Weigh the recorded runs; history is best-effort, so a missing or
+    /// unreadable file ranks every target the same
+    pub fn load(dir: &Path, half_life_days: f64) -> Self {
+        let entries = history::load(usize::MAX).unwrap_or_default();
+        Self::from_history(&entries, dir, history::now_secs(), half_life_days)
+    }
+
+    /// Sum the weights of the runs in `entries` at time `now`, each counted
+    /// for the directory it was started from
+    pub fn from_history(
+        entries: &[HistoryEntry],
+        dir: &Path,
+        now: u64,
+        half_life_days: f64,
+    ) -> Self {
+        let mut weights = HashMap::new();
+        for entry in entries {
+            let weight = decay(now.saturating_sub(entry.started_at), half_life_days);
+            *weights
+                .entry((entry.dir.clone(), entry.target.clone()))
+                .or_insert(0.0) += weight;
+        }
+        Self {
+            weights,
+            team: HashMap::new(),
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    /// Add the counts of each root's usage file; a broken file is skipped
+    pub fn with_team_usage(mut self, roots: &[PathBuf]) -> Self {
+        for root in roots {
+            if let Ok(usage) = TeamUsage::load(root) {
+                self.add_team_usage(root, &usage);
+            }
+        }
+        self
+    }
+
+    /// Weigh the team's runs of the targets of `root` so that its most run
+    /// target counts as much as one run of yours made now: enough to order
+    /// a newcomer's list, and soon outweighed by their own runs
+    fn add_team_usage(&mut self, root: &Path, usage: &TeamUsage) {
+        let totals = usage.totals();
+        let most = totals.values().copied().max().unwrap_or(0);
+        for (name, count) in totals {
+            let weight = count as f64 / most as f64;
+            self.team
+                .insert((root.to_path_buf(), name.to_string()), weight);
+        }
+    }
+
+    pub fn weight(&self, target: &Target) -> f64 {
+        let dir = target.root.clone().unwrap_or_else(|| self.dir.clone());
+        let key = (dir, target.name.clone());
+        let own = self.weights.get(&key).copied().unwrap_or(0.0);
+        own + self.team.get(&key).copied().unwrap_or(0.0)
+    }
+
+    /// Order `targets` by weight, heaviest first; targets of equal weight
+    /// (such as ones never run) keep their order
+    pub fn sort(&self, targets: &mut [&Target]) {
+        targets.sort_by(|a, b| self.weight(b).total_cmp(&self.weight(a)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    const DAY: u64 = 86_400;
+    const NOW: u64 = 100 * DAY;
+
+    fn run(target: &str, dir: &str, started_at: u64) -> HistoryEntry {
+        HistoryEntry {
+            target: target.to_string(),
+            command: format!("make {}", target),
+            dir: PathBuf::from(dir),
+            started_at,
+            duration_ms: 10,
+            exit_code: Some(0),
+            success: true,
+            repeat: None,
+        }
+    }
+
+    fn target(name: &str) -> Target {
+        Target::new(name.to_string(), None, PathBuf::from("Makefile"), 1)
+    }
+
+    #[test]
+    fn test_decay_halves_every_half_life() {
+        assert_eq!(decay(0, 7.0), 1.0);
+        assert_eq!(decay(7 * DAY, 7.0), 0.5);
+        assert_eq!(decay(14 * DAY, 7.0), 0.25);
+        assert_eq!(decay(DAY, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_weight_sums_runs_in_the_same_dir() {
+        let entries = [
+            run("build", "/app", NOW),
+            run("build", "/app", NOW - 7 * DAY),
+            run("build", "/other", NOW),
+            run("test", "/app", NOW - 14 * DAY),
+        ];
+
+        let frecency = Frecency::from_history(&entries, Path::new("/app"), NOW, 7.0);
+
+        assert_eq!(frecency.weight(&target("build")), 1.5);
+        assert_eq!(frecency.weight(&target("test")), 0.25);
+        assert_eq!(frecency.weight(&target("lint")), 0.0);
+
+        let mut other = target("build");
+        other.root = Some(PathBuf::from("/other"));
+        assert_eq!(frecency.weight(&other), 1.0);
+    }
+
+    #[test]
+    fn test_sort_keeps_unused_targets_in_order() {
+        // Three old runs outweigh a single recent one
+        let entries = [
+            run("test", "/app", NOW - 10 * DAY),
+            run("test", "/app", NOW - 10 * DAY),
+            run("test", "/app", NOW - 10 * DAY),
+            run("deploy", "/app", NOW),
+        ];
+        let frecency = Frecency::from_history(&entries, Path::new("/app"), NOW, 7.0);
+        let targets = [
+            target("build"),
+            target("deploy"),
+            target("lint"),
+            target("test"),
+        ];
+        let mut ordered: Vec<&Target> = targets.iter().collect();
+
+        frecency.sort(&mut ordered);
+
+        let names: Vec<&str> = ordered.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["test", "deploy", "build", "lint"]);
+    }
+
+    #[test]
+    fn test_team_usage_orders_targets_not_run_yet() {
+        let mut usage = TeamUsage::default();
+        usage.record(
+            "a1",
+            BTreeMap::from([("test".to_string(), 40), ("lint".to_string(), 10)]),
+        );
+        usage.record("b2", BTreeMap::from([("lint".to_string(), 10)]));
+        let entries = [run("build", "/app", NOW), run("build", "/app", NOW)];
+        let mut frecency = Frecency::from_history(&entries, Path::new("/app"), NOW, 7.0);
+        frecency.add_team_usage(Path::new("/app"), &usage);
+
+        assert_eq!(frecency.weight(&target("test")), 1.0);
+        assert_eq!(frecency.weight(&target("lint")), 0.5);
+        let targets = [
+            target("deploy"),
+            target("lint"),
+            target("test"),
+            target("build"),
+        ];
+        let mut ordered: Vec<&Target> = targets.iter().collect();
+        frecency.sort(&mut ordered);
+        let names: Vec<&str> = ordered.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["build", "test", "lint", "deploy"]);
+    }
+
+    #[test]
+    fn test_parse_rank_policy() {
+        let policy: RankPolicy =
+            toml::from_str("rank = \"pure-fuzzy\"\nhalf_life_days = 30\n").unwrap();
+        assert_eq!(policy.rank, RankMode::PureFuzzy);
+        assert_eq!(policy.half_life_days, 30.0);
+        assert!(toml::from_str::<RankPolicy>("rank = \"random\"\n").is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use anyhow::{Result, bail};
+use std::fmt;
+use std::io::ErrorKind;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// How long one check of a probe may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often a probe that hasn't answered yet is checked again
+const INTERVAL: Duration = Duration::from_millis(500);
+
+/// What answers once a service-like target is up, from its `@ready` comment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Probe {
+    /// An http(s) URL that responds with a success status
+    Http(String),
+    /// A `host:port` that accepts connections
+    Tcp(String),
+}
+
+/// How waiting for a target to be ready ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The probe answered after this long
+    Ready(Duration),
+    /// The target stopped running before it answered
+    Exited,
+    /// It was still not answering when the time was up
+    TimedOut,
+}
+
+impl Probe {
+    /// A probe from `@ready`: an `http://` or `https://` URL, or
+    /// `tcp://host:port`, where the host may be left out for localhost
+    pub fn parse(text: &str) -> Result<Self> {
+        if text.starts_with("http://") || text.starts_with("https://") {
+            return Ok(Self::Http(text.to_string()));
+        }
+
+        let address = text.strip_prefix("tcp://").unwrap_or(text);
+        let address = match address.strip_prefix(':') {
+            Some(port) => format!("localhost:{}", port),
+            None => address.to_string(),
+        };
+        match address.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                Ok(Self::Tcp(address))
+            }
+            _ => bail!(
+                "Unknown @ready probe `{}` (an http:// or https:// URL, or tcp://host:port)",
+                text
+            ),
+        }
+    }
+
+    /// Whether the probe answers now
+    pub fn check(&self) -> Result<bool> {
+        match self {
+            Self::Http(url) => {
+                let status = Command::new("curl")
+                    .args(["--silent", "--fail", "--output"])
+                    .arg(if cfg!(windows) { "NUL" } else { "/dev/null" })
+                    .arg("--max-time")
+                    .arg(CHECK_TIMEOUT.as_secs().to_string())
+                    .arg(url)
+                    .stdin(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status();
+                match status {
+                    Ok(status) => Ok(status.success()),
+                    Err(e) if e.kind() == ErrorKind::NotFound => {
+                        bail!("curl is not installed, and http probes need it")
+                    }
+                    Err(e) => bail!("Failed to run curl: {}", e),
+                }
+            }
+            Self::Tcp(address) => {
+                // A name that doesn't resolve yet (a container still
+                // starting) is as good as a closed port
+                let Ok(addresses) = address.to_socket_addrs() else {
+                    return Ok(false);
+                };
+                Ok(addresses
+                    .into_iter()
+                    .any(|a| TcpStream::connect_timeout(&a, CHECK_TIMEOUT).is_ok()))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Http(url) => write!(f, "{}", url),
+            Self::Tcp(address) => write!(f, "tcp://{}", address),
+        }
+    }
+}
+
+/// Check `probe` until it answers, `running` says the target has stopped, or
+/// `timeout` is up
+pub fn wait(probe: &Probe, timeout: Duration, running: impl Fn() -> bool) -> Result<Outcome> {
+    let started = Instant::now();
+    loop {
+        if probe.check()? {
+            return Ok(Outcome::Ready(started.elapsed()));
+        }
+        if !running() {
+            return Ok(Outcome::Exited);
+        }
+        if started.elapsed() >= timeout {
+            return Ok(Outcome::TimedOut);
+        }
+        std::thread::sleep(INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// A local port nothing listens on
+    fn closed_port() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    #[test]
+    fn test_parse_probes() {
+        assert_eq!(
+            Probe::parse("http://localhost:8080/health").unwrap(),
+            Probe::Http("http://localhost:8080/health".to_string())
+        );
+        assert_eq!(
+            Probe::parse("tcp://db:5432").unwrap(),
+            Probe::Tcp("db:5432".to_string())
+        );
+        assert_eq!(
+            Probe::parse(":6379").unwrap(),
+            Probe::Tcp("localhost:6379".to_string())
+        );
+        assert_eq!(
+            Probe::parse(":6379").unwrap().to_string(),
+            "tcp://localhost:6379"
+        );
+
+        for bad in ["localhost", "tcp://db:port", "ftp://host/file", ":99999"] {
+            let err = Probe::parse(bad).unwrap_err().to_string();
+            assert!(err.contains("Unknown @ready probe"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_tcp_probe() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let probe = Probe::Tcp(format!("127.0.0.1:{}", port));
+        assert!(probe.check().unwrap());
+
+        let probe = Probe::Tcp(format!("127.0.0.1:{}", closed_port()));
+        assert!(!probe.check().unwrap());
+    }
+
+    #[test]
+    fn test_wait() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = Probe::Tcp(listener.local_addr().unwrap().to_string());
+        assert!(matches!(
+            wait(&open, Duration::ZERO, || true).unwrap(),
+            Outcome::Ready(_)
+        ));
+
+        let closed = Probe::Tcp(format!("127.0.0.1:{}", closed_port()));
+        assert_eq!(
+            wait(&closed, Duration::from_secs(60), || false).unwrap(),
+            Outcome::Exited
+        );
+        assert_eq!(
+            wait(&closed, Duration::ZERO, || true).unwrap(),
+            Outcome::TimedOut
+        );
+    }
+}
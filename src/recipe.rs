@@ -0,0 +1,256 @@
+use regex::Regex;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Style, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::as_24_bit_terminal_escaped;
+
+use crate::makefile;
+use crate::target::{Provider, Target};
+
+/// How many usages of a variable are shown before the rest are only counted
+const MAX_USAGES: usize = 5;
+
+/// A line of a target's rule, with its line number in the Makefile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub number: usize,
+    pub text: String,
+}
+
+/// A target's rule as written: the header and the recipe below it, for
+/// showing where a variable being prompted for ends up
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recipe {
+    lines: Vec<Line>,
+}
+
+impl Recipe {
+    /// The rule of `target`, empty when it isn't a Makefile target or the
+    /// Makefile can't be read
+    pub fn of(target: &Target) -> Self {
+        if target.provider != Provider::Make {
+            return Self::default();
+        }
+        match makefile::read_makefile(&target.file) {
+            Ok((content, _)) => {
+                let lines: Vec<&str> = content.lines().collect();
+                Self::from_lines(&lines, target.line.saturating_sub(1))
+            }
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// The rule whose header is on (0-indexed) `header`, with the header's
+    /// continuation lines and the tab-indented lines after it
+    pub(crate) fn from_lines(lines: &[&str], header: usize) -> Self {
+        let mut rule = Vec::new();
+        if header >= lines.len() {
+            return Self::default();
+        }
+
+        let mut end = header;
+        while end + 1 < lines.len() && lines[end].trim_end().ends_with('\\') {
+            end += 1;
+        }
+        rule.extend(header..=end);
+
+        // Blank lines and comments between recipe lines don't end the recipe
+        for (i, line) in lines.iter().enumerate().skip(end + 1) {
+            if line.starts_with('\t') {
+                rule.push(i);
+            } else if !line.trim().is_empty() && !line.trim_start().starts_with('#') {
+                break;
+            }
+        }
+
+        Self {
+            lines: rule
+                .into_iter()
+                .map(|i| Line {
+                    number: i + 1,
+                    text: lines[i].to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The lines that use the variable `name`, as `$(NAME)`, `${NAME}`, a
+    /// substitution like `$(NAME:.c=.o)`, or `$N` for a one-letter name
+    pub fn usages(&self, name: &str) -> Vec<&Line> {
+        let name = regex::escape(name);
+        let single = if name.len() == 1 {
+            format!(r"|\${}", name)
+        } else {
+            String::new()
+        };
+        let reference = Regex::new(&format!(r"\$[\(\{{]{}[\)\}}:]{}", name, single)).unwrap();
+        self.lines
+            .iter()
+            .filter(|line| {
+                // `$$` is a literal dollar for the shell, not a reference
+                let text = line.text.replace("$$", "");
+                reference.is_match(&text)
+            })
+            .collect()
+    }
+
+    /// Where `name` is used, one numbered line each and syntax highlighted
+    /// when `color` is set, or None when the rule doesn't use it
+    pub fn render_usages(&self, name: &str, color: bool) -> Option<String> {
+        let usages = self.usages(name);
+        if usages.is_empty() {
+            return None;
+        }
+
+        let highlighted = if color {
+            self.highlighted()
+        } else {
+            Vec::new()
+        };
+        let width = usages.iter().map(|l| l.number).max().unwrap_or(0);
+        let width = width.to_string().len();
+
+        let mut out = String::new();
+        for line in usages.iter().take(MAX_USAGES) {
+            let text = highlighted
+                .iter()
+                .find(|(number, _)| *number == line.number)
+                .map_or_else(|| line.text.trim().to_string(), |(_, text)| text.clone());
+            out.push_str(&format!("  {:>width$} │ {}\n", line.number, text));
+        }
+        if usages.len() > MAX_USAGES {
+            out.push_str(&format!(
+                "  {:>width$}   … and {} more\n",
+                "",
+                usages.len() - MAX_USAGES
+            ));
+        }
+        Some(out)
+    }
+
+    /// The rule's lines with Makefile syntax highlighting, highlighted
+    /// together so recipe lines are colored as recipe lines
+    fn highlighted(&self) -> Vec<(usize, String)> {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let theme_set = ThemeSet::load_defaults();
+        let syntax = syntax_set
+            .find_syntax_by_extension("mk")
+            .or_else(|| syntax_set.find_syntax_by_name("Makefile"))
+            .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+        let mut highlighter = HighlightLines::new(syntax, &theme_set.themes["base16-ocean.dark"]);
+
+        self.lines
+            .iter()
+            .map(|line| {
+                let text = format!("{}\n", line.text);
+                let mut ranges: Vec<(Style, &str)> = highlighter
+                    .highlight_line(&text, &syntax_set)
+                    .unwrap_or_default();
+                trim_ranges(&mut ranges);
+                let escaped = as_24_bit_terminal_escaped(&ranges[..], false);
+                (line.number, format!("{}\x1b[0m", escaped))
+            })
+            .collect()
+    }
+}
+
+/// Drop the whitespace around highlighted text, as the plain lines do
+fn trim_ranges(ranges: &mut Vec<(Style, &str)>) {
+    while let Some((_, text)) = ranges.first_mut() {
+        *text = text.trim_start();
+        if !text.is_empty() {
+            break;
+        }
+        ranges.remove(0);
+    }
+    while let Some((_, text)) = ranges.last_mut() {
+        *text = text.trim_end();
+        if !text.is_empty() {
+            break;
+        }
+        ranges.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runlog::strip_ansi;
+
+    const MAKEFILE: &str = "\
+VERSION ?= 0.1.0
+
+## Tag and push a release
+release: build \\
+\t\tcheck-$(CHANNEL)
+\tgit tag v$(VERSION)
+
+\t# Push the tag too
+\tgit push origin v${VERSION}
+\techo $$VERSION $(VERSION_FILE) $(V)
+\tcp app dist/$(VERSION:.0=)
+
+other:
+\techo $(VERSION)
+";
+
+    fn release() -> Recipe {
+        let lines: Vec<&str> = MAKEFILE.lines().collect();
+        Recipe::from_lines(&lines, 3)
+    }
+
+    fn numbers(lines: &[&Line]) -> Vec<usize> {
+        lines.iter().map(|l| l.number).collect()
+    }
+
+    #[test]
+    fn test_usages() {
+        let recipe = release();
+
+        assert_eq!(numbers(&recipe.usages("VERSION")), vec![6, 9, 11]);
+        assert_eq!(numbers(&recipe.usages("CHANNEL")), vec![5]);
+        assert_eq!(numbers(&recipe.usages("V")), vec![10]);
+        assert!(recipe.usages("RELEASE").is_empty());
+    }
+
+    #[test]
+    fn test_single_letter_names() {
+        let lines = ["x:", "\techo $V $Vx $(V)", "\techo $$V"];
+        let recipe = Recipe::from_lines(&lines, 0);
+
+        assert_eq!(numbers(&recipe.usages("V")), vec![2]);
+        assert!(recipe.usages("Vx").is_empty());
+    }
+
+    #[test]
+    fn test_render_usages() {
+        let recipe = release();
+
+        assert_eq!(
+            recipe.render_usages("VERSION", false).as_deref(),
+            Some(
+                "   6 │ git tag v$(VERSION)\n   \
+                 9 │ git push origin v${VERSION}\n  \
+                 11 │ cp app dist/$(VERSION:.0=)\n"
+            )
+        );
+        // Highlighting only adds colors
+        assert_eq!(
+            strip_ansi(&recipe.render_usages("VERSION", true).unwrap()),
+            recipe.render_usages("VERSION", false).unwrap()
+        );
+        assert_eq!(recipe.render_usages("RELEASE", false), None);
+    }
+
+    #[test]
+    fn test_render_counts_the_rest() {
+        let mut lines = vec!["x:"];
+        lines.extend(["\techo $(N)"; 7]);
+        let rendered = Recipe::from_lines(&lines, 0)
+            .render_usages("N", false)
+            .unwrap();
+
+        assert_eq!(rendered.lines().count(), MAX_USAGES + 1);
+        assert!(rendered.ends_with("… and 2 more\n"));
+    }
+}
@@ -0,0 +1,352 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use crate::pty;
+#[cfg(unix)]
+use crate::terminal::{self, RawMode};
+
+const RECORDINGS_DIRNAME: &str = "recordings";
+
+/// The extension of recordings, which asciinema plays too
+pub const CAST_EXTENSION: &str = "cast";
+
+/// The first line of an asciinema (v2) cast file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CastHeader {
+    pub version: u32,
+    pub width: u16,
+    pub height: u16,
+    /// When the recording started, in seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+    /// The command that was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// A recorded run: its header, and what it printed when, in seconds since
+/// it started
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub header: CastHeader,
+    pub output: Vec<(f64, String)>,
+}
+
+/// Where recordings go: next to the run logs with --log-dir, otherwise
+/// `<data dir>/maki/recordings`
+pub fn recordings_dir(log_dir: Option<&Path>) -> Option<PathBuf> {
+    match log_dir {
+        Some(dir) => Some(dir.to_path_buf()),
+        None => dirs::data_dir().map(|p| p.join("maki").join(RECORDINGS_DIRNAME)),
+    }
+}
+
+/// A cast file being written as the run prints
+struct CastWriter {
+    file: BufWriter<File>,
+    started: Instant,
+}
+
+impl CastWriter {
+    fn create(path: &Path, header: &CastHeader) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "{}", serde_json::to_string(header)?)?;
+        Ok(Self {
+            file,
+            started: Instant::now(),
+        })
+    }
+
+    fn output(&mut self, text: &str) -> io::Result<()> {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let event = (elapsed, "o", text);
+        writeln!(self.file, "{}", serde_json::to_string(&event)?)
+    }
+}
+
+/// Take what `pending` holds up to any character it has only part of, which
+/// stays for the next read to complete; invalid bytes become U+FFFD
+fn take_utf8(pending: &mut Vec<u8>) -> String {
+    let complete = match std::str::from_utf8(pending) {
+        Ok(_) => pending.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => pending.len(),
+    };
+    let rest = pending.split_off(complete);
+    let text = String::from_utf8_lossy(pending).into_owned();
+    *pending = rest;
+    text
+}
+
+/// Run `cmd` on a terminal of its own, passing the keyboard through to it
+/// and its output to maki's terminal, and record the output, colors and
+/// redraws included, as a cast file at `path` titled `title`
+#[cfg(unix)]
+pub fn run(cmd: Command, path: &Path, title: &str) -> io::Result<ExitStatus> {
+    record_to(cmd, path, title, &mut io::stdout())
+}
+
+#[cfg(unix)]
+fn record_to(
+    cmd: Command,
+    path: &Path,
+    title: &str,
+    screen_out: &mut impl Write,
+) -> io::Result<ExitStatus> {
+    use std::io::Read;
+
+    let (width, height) = terminal::size();
+    let mut cast = CastWriter::create(
+        path,
+        &CastHeader {
+            version: 2,
+            width,
+            height,
+            timestamp: Some(chrono::Local::now().timestamp()),
+            title: Some(title.to_string()),
+        },
+    )?;
+
+    let (terminal, mut child) = pty::spawn(cmd)?;
+    let raw = RawMode::enable().ok().map(RawMode::with_newlines);
+    let keyboard = pty::Keyboard::forward(&terminal)?;
+
+    let mut screen = File::from(terminal);
+    let mut pending = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        match screen.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                screen_out.write_all(&buf[..n])?;
+                screen_out.flush()?;
+                pending.extend_from_slice(&buf[..n]);
+                let text = take_utf8(&mut pending);
+                if !text.is_empty() {
+                    cast.output(&text)?;
+                }
+            }
+            Err(ref e) if pty::is_closed(e) => break,
+            Err(_) => continue,
+        }
+    }
+    if !pending.is_empty() {
+        cast.output(&String::from_utf8_lossy(&pending))?;
+    }
+    cast.file.flush()?;
+
+    let status = child.wait();
+    drop(keyboard);
+    drop(raw);
+    status
+}
+
+#[cfg(not(unix))]
+pub fn run(_cmd: Command, _path: &Path, _title: &str) -> io::Result<ExitStatus> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--record needs a Unix terminal",
+    ))
+}
+
+/// Read a cast file; only the output of its events is kept
+pub fn load(path: &Path) -> Result<Recording> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read recording: {}", path.display()))?;
+    parse(&content).with_context(|| format!("Invalid recording: {}", path.display()))
+}
+
+fn parse(content: &str) -> Result<Recording> {
+    let mut lines = content.lines().filter(|l| !l.trim().is_empty());
+    let header: CastHeader = serde_json::from_str(lines.next().context("The recording is empty")?)?;
+    if header.version != 2 {
+        anyhow::bail!("Unsupported cast version {}", header.version);
+    }
+
+    let mut output = Vec::new();
+    for line in lines {
+        let (time, kind, data): (f64, String, String) = serde_json::from_str(line)?;
+        if kind == "o" {
+            output.push((time, data));
+        }
+    }
+    Ok(Recording { header, output })
+}
+
+/// The recording of `run` in `dir`: a run ID as `--record` prints it, or the
+/// path of a cast file; the latest recording when no run is given
+pub fn find(dir: &Path, run: Option<&str>) -> Result<PathBuf> {
+    if let Some(run) = run {
+        let path = Path::new(run);
+        if path.is_file() {
+            return Ok(path.to_path_buf());
+        }
+        let path = dir.join(format!("{}.{}", run, CAST_EXTENSION));
+        if path.is_file() {
+            return Ok(path);
+        }
+        let recent = recordings(dir)
+            .into_iter()
+            .rev()
+            .take(5)
+            .collect::<Vec<_>>();
+        match recent.is_empty() {
+            true => anyhow::bail!("No recording {} in {}", run, dir.display()),
+            false => anyhow::bail!(
+                "No recording {} in {}; the latest are: {}",
+                run,
+                dir.display(),
+                recent.join(", ")
+            ),
+        }
+    }
+    match recordings(dir).pop() {
+        Some(latest) => Ok(dir.join(format!("{}.{}", latest, CAST_EXTENSION))),
+        None => anyhow::bail!(
+            "No recordings in {} yet; record a run with --record",
+            dir.display()
+        ),
+    }
+}
+
+/// The run IDs of the recordings in `dir`, oldest first: they start with the
+/// time the run started
+fn recordings(dir: &Path) -> Vec<String> {
+    let mut ids: Vec<String> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            match path.extension().is_some_and(|e| e == CAST_EXTENSION) {
+                true => path.file_stem().map(|s| s.to_string_lossy().to_string()),
+                false => None,
+            }
+        })
+        .collect();
+    ids.sort();
+    ids
+}
+
+/// Parse a replay speed, as given to `maki replay --speed`
+pub fn parse_speed(s: &str) -> std::result::Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        _ => Err(format!("expected a speed above 0, got '{}'", s)),
+    }
+}
+
+/// Print the recording's output to `out` with the pauses it had, `speed`
+/// times as fast
+pub fn replay(recording: &Recording, speed: f64, out: &mut impl Write) -> io::Result<()> {
+    let mut last = 0.0;
+    for (time, data) in &recording.output {
+        let pause = (time - last).max(0.0) / speed;
+        if pause > 0.0 {
+            std::thread::sleep(Duration::from_secs_f64(pause));
+        }
+        last = *time;
+        out.write_all(data.as_bytes())?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_utf8_keeps_partial_characters() {
+        let mut pending = "ok ✓".as_bytes().to_vec();
+        let tail = pending.split_off(pending.len() - 1);
+        assert_eq!(take_utf8(&mut pending), "ok ");
+        assert_eq!(pending.len(), 2);
+
+        pending.extend(tail);
+        assert_eq!(take_utf8(&mut pending), "✓");
+        assert!(pending.is_empty());
+
+        let mut invalid = vec![b'a', 0xff, b'b'];
+        assert_eq!(take_utf8(&mut invalid), "a\u{fffd}b");
+    }
+
+    #[test]
+    fn test_cast_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("20260101-120000-build.cast");
+        let header = CastHeader {
+            version: 2,
+            width: 100,
+            height: 30,
+            timestamp: None,
+            title: Some("make build".to_string()),
+        };
+        let mut cast = CastWriter::create(&path, &header).unwrap();
+        cast.output("\x1b[32mok\x1b[0m\r\n").unwrap();
+        cast.output("done\r\n").unwrap();
+        cast.file.flush().unwrap();
+
+        let recording = load(&path).unwrap();
+        assert_eq!(recording.header, header);
+        let texts: Vec<&str> = recording.output.iter().map(|(_, t)| t.as_str()).collect();
+        assert_eq!(texts, ["\x1b[32mok\x1b[0m\r\n", "done\r\n"]);
+
+        let mut out = Vec::new();
+        replay(&recording, 1000.0, &mut out).unwrap();
+        assert_eq!(out, b"\x1b[32mok\x1b[0m\r\ndone\r\n");
+    }
+
+    #[test]
+    fn test_parse_speed() {
+        assert_eq!(parse_speed("2"), Ok(2.0));
+        assert_eq!(parse_speed("0.5"), Ok(0.5));
+        assert!(parse_speed("0").is_err());
+        assert!(parse_speed("fast").is_err());
+    }
+
+    #[test]
+    fn test_find_recordings() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(find(dir.path(), None).is_err());
+
+        for id in ["20260101-120000-build", "20260102-090000-test"] {
+            fs::write(dir.path().join(format!("{}.cast", id)), "").unwrap();
+        }
+        fs::write(dir.path().join("20260103-090000-lint.log"), "").unwrap();
+
+        let latest = find(dir.path(), None).unwrap();
+        assert!(latest.ends_with("20260102-090000-test.cast"));
+        let build = find(dir.path(), Some("20260101-120000-build")).unwrap();
+        assert!(build.ends_with("20260101-120000-build.cast"));
+        let missing = find(dir.path(), Some("nope")).unwrap_err().to_string();
+        assert!(missing.contains("20260102-090000-test, 20260101-120000-build"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_records_the_terminal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.cast");
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg("test -t 1 && printf 'on a terminal\\n'; exit 3");
+
+        let mut screen = Vec::new();
+        let status = record_to(cmd, &path, "sh", &mut screen).unwrap();
+        assert_eq!(screen, b"on a terminal\r\n");
+        assert_eq!(status.code(), Some(3));
+        let recording = load(&path).unwrap();
+        let output: String = recording.output.iter().map(|(_, t)| t.as_str()).collect();
+        assert_eq!(output, "on a terminal\r\n");
+    }
+}
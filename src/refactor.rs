@@ -0,0 +1,544 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::diff::{self, DiffLine};
+use crate::makefile;
+
+/// Lines of unchanged context shown around each change in a diff
+const DIFF_CONTEXT: usize = 3;
+
+/// A Makefile whose content a refactoring changes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEdit {
+    pub path: PathBuf,
+    pub original: String,
+    pub updated: String,
+}
+
+impl FileEdit {
+    /// The change as a unified diff, naming the file relative to `base_dir`
+    pub fn diff(&self, base_dir: &Path) -> String {
+        let path = self.path.strip_prefix(base_dir).unwrap_or(&self.path);
+        unified_diff(path, &self.original, &self.updated)
+    }
+
+    /// Write the updated content over the file
+    pub fn apply(&self) -> Result<()> {
+        fs::write(&self.path, &self.updated)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+/// Where the target being renamed is defined
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Definition {
+    /// The Makefile make reads for it, the one including the others
+    pub makefile: PathBuf,
+    /// The files sharing its rules: the Makefile and the fragments it includes
+    pub files: Vec<PathBuf>,
+}
+
+/// Which of a file's mentions of the target are the one being renamed
+struct Scope<'a> {
+    /// Whether the file is one of the definition's, where every rule,
+    /// prerequisite and `.DEFAULT_GOAL` naming it does
+    local: bool,
+    /// The directory its recipes run in
+    dir: &'a Path,
+    /// The Makefile defining the target, for `$(MAKE) -C dir` calls
+    makefile: &'a Path,
+}
+
+/// The edits renaming target `old` to `new`: everywhere in the files of
+/// `definition`, and in `$(MAKE) -C dir old` calls of the other `makefiles`
+/// that run the defining one; files that don't mention the target are left
+/// out
+pub fn rename(
+    makefiles: &[PathBuf],
+    definition: &Definition,
+    old: &str,
+    new: &str,
+) -> Result<Vec<FileEdit>> {
+    let mut paths: Vec<&PathBuf> = definition.files.iter().collect();
+    paths.extend(makefiles.iter().filter(|p| !definition.files.contains(p)));
+    let top_dir = parent(&definition.makefile);
+
+    let mut edits = Vec::new();
+    for path in paths {
+        let original = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let local = definition.files.contains(path);
+        let scope = Scope {
+            local,
+            // Included fragments run in the including Makefile's directory
+            dir: if local { top_dir } else { parent(path) },
+            makefile: &definition.makefile,
+        };
+        let updated = rename_in(&original, old, new, &scope);
+        if updated != original {
+            edits.push(FileEdit {
+                path: path.clone(),
+                original,
+                updated,
+            });
+        }
+    }
+    Ok(edits)
+}
+
+/// Whether `name` can be used as a target name
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains('%')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || "._/-".contains(c))
+}
+
+/// The directory of a Makefile given by path, `.` for a bare file name
+fn parent(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Whether two paths name the same file or directory
+fn same_path(a: &Path, b: &Path) -> bool {
+    let resolve = |p: &Path| fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+    resolve(a) == resolve(b)
+}
+
+/// Rename target `old` to `new` in Makefile content: in a file of the
+/// definition the rules (including target-specific variables and `.PHONY`),
+/// prerequisite references and `.DEFAULT_GOAL`; in any file the `$(MAKE) old`
+/// calls of recipes that run the defining Makefile
+///
+/// Comments and `define` blocks are left alone. Lines are rewritten in place,
+/// so the line count never changes.
+fn rename_in(content: &str, old: &str, new: &str, scope: &Scope) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut in_define = false;
+    // Whether the previous line ended with `\` inside a rule header or recipe
+    let mut continues_header = false;
+    let mut continues_recipe = false;
+
+    for line in content.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\n', '\r']);
+        let ending = &line[text.len()..];
+        let trimmed = text.trim();
+        let continued = text.ends_with('\\');
+
+        let rewritten = if continues_header {
+            continues_header = continued;
+            match scope.local {
+                true => replace_words(text, old, new),
+                false => text.to_string(),
+            }
+        } else if continues_recipe || text.starts_with('\t') {
+            continues_recipe = continued;
+            rename_make_invocations(text, old, new, scope)
+        } else if in_define {
+            in_define = trimmed != "endef";
+            text.to_string()
+        } else if trimmed.starts_with("define ") {
+            in_define = true;
+            text.to_string()
+        } else if trimmed.is_empty() || trimmed.starts_with('#') {
+            text.to_string()
+        } else if makefile::is_variable_assignment(trimmed) {
+            match scope.local {
+                true => rename_default_goal(text, old, new),
+                false => text.to_string(),
+            }
+        } else if let Some(colon) = text.find(':') {
+            continues_header = continued;
+            rename_in_rule(text, colon, old, new, scope)
+        } else {
+            text.to_string()
+        };
+
+        result.push_str(&rewritten);
+        result.push_str(ending);
+    }
+
+    result
+}
+
+/// Rename in a rule header such as `old other: deps ; recipe`, given the
+/// position of its colon
+fn rename_in_rule(line: &str, colon: usize, old: &str, new: &str, scope: &Scope) -> String {
+    let (names, rest) = line.split_at(colon);
+    let mut result = match scope.local {
+        true => replace_words(names, old, new),
+        false => names.to_string(),
+    };
+
+    // Keep the comment as written
+    let (rest, comment) = rest.split_at(rest.find('#').unwrap_or(rest.len()));
+    let (prerequisites, recipe) = rest.split_at(rest.find(';').unwrap_or(rest.len()));
+
+    // `old: VAR := value` assigns a variable rather than listing prerequisites
+    if prerequisites.contains('=') || !scope.local {
+        result.push_str(prerequisites);
+    } else {
+        result.push_str(&replace_words(prerequisites, old, new));
+    }
+    result.push_str(&rename_make_invocations(recipe, old, new, scope));
+    result.push_str(comment);
+    result
+}
+
+/// Rename in `.DEFAULT_GOAL := old`; other assignments are left as they are
+fn rename_default_goal(line: &str, old: &str, new: &str) -> String {
+    if !line.trim_start().starts_with(".DEFAULT_GOAL") {
+        return line.to_string();
+    }
+    match line.find('=') {
+        Some(pos) => {
+            let (name, value) = line.split_at(pos + 1);
+            format!("{}{}", name, replace_words(value, old, new))
+        }
+        None => line.to_string(),
+    }
+}
+
+/// Rename the targets passed to recursive `$(MAKE)` / `${MAKE}` calls
+fn rename_make_invocations(line: &str, old: &str, new: &str, scope: &Scope) -> String {
+    let mut result = String::new();
+    let mut rest = line;
+
+    while let Some(pos) = ["$(MAKE)", "${MAKE}"]
+        .iter()
+        .filter_map(|call| rest.find(call))
+        .min()
+    {
+        let after = pos + "$(MAKE)".len();
+        result.push_str(&rest[..after]);
+        rest = &rest[after..];
+
+        // The call's arguments run up to the next shell separator
+        let end = [";", "&&", "||", "|", ")", "`"]
+            .iter()
+            .filter_map(|sep| rest.find(sep))
+            .min()
+            .unwrap_or(rest.len());
+        let (args, remaining) = rest.split_at(end);
+        match runs_definition(args, scope) {
+            true => result.push_str(&rename_make_args(args, old, new)),
+            false => result.push_str(args),
+        }
+        rest = remaining;
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Whether a `$(MAKE)` call with `args` reads the defining Makefile: the
+/// recipe's own when there's no `-C` or `-f` in a file of the definition,
+/// or the one they point at
+fn runs_definition(args: &str, scope: &Scope) -> bool {
+    let mut directory = None;
+    let mut file = None;
+    let mut words = args.split_whitespace();
+    while let Some(word) = words.next() {
+        match word {
+            "-C" | "--directory" => directory = words.next(),
+            "-f" | "--file" => file = words.next(),
+            _ => {
+                if let Some(dir) = word.strip_prefix("--directory=") {
+                    directory = Some(dir);
+                } else if let Some(path) = word.strip_prefix("--file=") {
+                    file = Some(path);
+                }
+            }
+        }
+    }
+
+    let dir = match directory {
+        Some(dir) => scope.dir.join(dir),
+        None if file.is_none() => return scope.local,
+        None => scope.dir.to_path_buf(),
+    };
+    match file {
+        Some(file) => same_path(&dir.join(file), scope.makefile),
+        None => same_path(&dir, parent(scope.makefile)),
+    }
+}
+
+/// Rename the goals among make's arguments, skipping the values of options
+/// such as `-C dir` and `-f file`
+fn rename_make_args(args: &str, old: &str, new: &str) -> String {
+    let mut result = String::new();
+    let mut takes_value = false;
+    for piece in args.split_inclusive(char::is_whitespace) {
+        let word = piece.trim_end();
+        if word.is_empty() {
+            result.push_str(piece);
+            continue;
+        }
+        if word == old && !takes_value {
+            result.push_str(new);
+            result.push_str(&piece[word.len()..]);
+        } else {
+            result.push_str(piece);
+        }
+        takes_value = matches!(
+            word,
+            "-C" | "-f" | "-I" | "-o" | "-W" | "--directory" | "--file"
+        );
+    }
+    result
+}
+
+/// Replace whole whitespace-separated words equal to `old`, keeping spacing
+fn replace_words(text: &str, old: &str, new: &str) -> String {
+    text.split_inclusive(char::is_whitespace)
+        .map(|piece| {
+            let word = piece.trim_end();
+            if word == old {
+                format!("{}{}", new, &piece[word.len()..])
+            } else {
+                piece.to_string()
+            }
+        })
+        .collect()
+}
+
+/// A unified diff of two versions of a file with the same number of lines
+fn unified_diff(path: &Path, original: &str, updated: &str) -> String {
+    let before: Vec<String> = original.lines().map(str::to_string).collect();
+    let after: Vec<String> = updated.lines().map(str::to_string).collect();
+    let lines = diff::line_diff(&before, &after);
+    let changed: Vec<usize> = (0..lines.len())
+        .filter(|&i| !matches!(lines[i], DiffLine::Same(_)))
+        .collect();
+    // Where lines[..i] leaves off in the original and in the update
+    let position = |i: usize| {
+        let old = lines[..i]
+            .iter()
+            .filter(|l| !matches!(l, DiffLine::Added(_)))
+            .count();
+        let new = lines[..i]
+            .iter()
+            .filter(|l| !matches!(l, DiffLine::Removed(_)))
+            .count();
+        (old, new)
+    };
+    // A range as a hunk header gives it, naming the line before an empty one
+    let range = |start: usize, count: usize| match count {
+        0 => format!("{},0", start),
+        _ => format!("{},{}", start + 1, count),
+    };
+
+    let mut diff = format!("--- a/{}\n+++ b/{}\n", path.display(), path.display());
+    let mut i = 0;
+    while i < changed.len() {
+        // Merge changes whose context would overlap into one hunk
+        let mut j = i;
+        while j + 1 < changed.len() && changed[j + 1] - changed[j] <= DIFF_CONTEXT * 2 {
+            j += 1;
+        }
+        let start = changed[i].saturating_sub(DIFF_CONTEXT);
+        let end = (changed[j] + DIFF_CONTEXT + 1).min(lines.len());
+
+        let (old_start, new_start) = position(start);
+        let (old_end, new_end) = position(end);
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_end - old_start),
+            range(new_start, new_end - new_start)
+        ));
+        for line in &lines[start..end] {
+            match line {
+                DiffLine::Same(l) => diff.push_str(&format!(" {}\n", l)),
+                DiffLine::Removed(l) => diff.push_str(&format!("-{}\n", l)),
+                DiffLine::Added(l) => diff.push_str(&format!("+{}\n", l)),
+            }
+        }
+        i = j + 1;
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const MAKEFILE: &str = "\
+.PHONY: build test ci
+.DEFAULT_GOAL := build
+
+# build compiles everything
+build: fmt ## Build the project
+\tcargo build
+
+build: CC := clang
+
+test: build
+\t$(MAKE) build
+\t$(MAKE) -C build lint && echo done
+
+rebuild: ; $(MAKE) clean build
+
+ci: test \\
+\tbuild
+";
+
+    /// Rename in the content of the Makefile defining the target
+    fn rename_here(content: &str, old: &str, new: &str) -> String {
+        let scope = Scope {
+            local: true,
+            dir: Path::new("."),
+            makefile: Path::new("Makefile"),
+        };
+        rename_in(content, old, new, &scope)
+    }
+
+    #[test]
+    fn test_rename_in() {
+        let renamed = rename_here(MAKEFILE, "build", "compile");
+
+        assert_eq!(
+            renamed,
+            "\
+.PHONY: compile test ci
+.DEFAULT_GOAL := compile
+
+# build compiles everything
+compile: fmt ## Build the project
+\tcargo build
+
+compile: CC := clang
+
+test: compile
+\t$(MAKE) compile
+\t$(MAKE) -C build lint && echo done
+
+rebuild: ; $(MAKE) clean compile
+
+ci: test \\
+\tcompile
+"
+        );
+    }
+
+    #[test]
+    fn test_rename_leaves_similar_names() {
+        let content = "build-docs: build_tools\n\techo build\nVERSION = build\n";
+        assert_eq!(rename_here(content, "build", "compile"), content);
+    }
+
+    #[test]
+    fn test_unified_diff_of_added_lines() {
+        let original = "build:\n\tcargo build\n";
+        let updated = ".PHONY: build\n\nbuild:\n\tcargo build\n";
+
+        assert_eq!(
+            unified_diff(Path::new("Makefile"), original, updated),
+            "\
+--- a/Makefile
++++ b/Makefile
+@@ -1,2 +1,4 @@
++.PHONY: build
++
+ build:
+ \tcargo build
+"
+        );
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let updated = "a\nB\nc\nd\ne\nf\ng\nh\ni\nJ\n";
+
+        assert_eq!(
+            unified_diff(Path::new("Makefile"), original, updated),
+            "\
+--- a/Makefile
++++ b/Makefile
+@@ -1,5 +1,5 @@
+ a
+-b
++B
+ c
+ d
+ e
+@@ -7,4 +7,4 @@
+ g
+ h
+ i
+-j
++J
+"
+        );
+    }
+
+    #[test]
+    fn test_rename_skips_unrelated_files() {
+        let dir = TempDir::new().unwrap();
+        let main = dir.path().join("Makefile");
+        let other = dir.path().join("docs.mk");
+        fs::write(&main, "all: build\nbuild:\n\tcargo build\n").unwrap();
+        fs::write(&other, "docs:\n\tmkdocs build\n").unwrap();
+
+        let definition = Definition {
+            makefile: main.clone(),
+            files: vec![main.clone()],
+        };
+        let edits = rename(&[main.clone(), other], &definition, "build", "compile").unwrap();
+
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].path, main);
+        assert_eq!(edits[0].updated, "all: compile\ncompile:\n\tcargo build\n");
+    }
+
+    #[test]
+    fn test_rename_leaves_other_makefiles_rules() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        let main = dir.path().join("Makefile");
+        let sub = dir.path().join("sub/Makefile");
+        fs::write(
+            &main,
+            "all: build\nbuild:\n\tcargo build\nsub:\n\t$(MAKE) -C sub build\n\t$(MAKE) build\n",
+        )
+        .unwrap();
+        fs::write(
+            &sub,
+            "build:\n\techo sub\nup: build\n\t$(MAKE) -C .. build\n",
+        )
+        .unwrap();
+
+        let definition = Definition {
+            makefile: main.clone(),
+            files: vec![main.clone()],
+        };
+        let edits = rename(&[main, sub.clone()], &definition, "build", "compile").unwrap();
+
+        // sub's own build, and the call running it, keep their name
+        assert_eq!(
+            edits[0].updated,
+            "all: compile\ncompile:\n\tcargo build\nsub:\n\t$(MAKE) -C sub build\n\t$(MAKE) compile\n"
+        );
+        assert_eq!(edits[1].path, sub);
+        assert_eq!(
+            edits[1].updated,
+            "build:\n\techo sub\nup: build\n\t$(MAKE) -C .. compile\n"
+        );
+    }
+
+    #[test]
+    fn test_is_valid_name() {
+        assert!(is_valid_name("docker-build"));
+        assert!(is_valid_name("ci/lint"));
+        assert!(!is_valid_name("%.o"));
+        assert!(!is_valid_name("two words"));
+        assert!(!is_valid_name(""));
+    }
+}
@@ -0,0 +1,211 @@
+use anyhow::{Context, Result, anyhow, bail};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::makefile::{self, ParseOptions};
+use crate::target::Target;
+
+/// The Makefiles of a remote repository, fetched into a temporary directory
+/// that is removed when dropped
+///
+/// Only the latest commit's file list is fetched, then just the contents of
+/// the Makefiles in it; the rest of the repository never is.
+#[derive(Debug)]
+pub struct Checkout {
+    dir: PathBuf,
+    /// The file names taken for Makefiles
+    names: Vec<String>,
+}
+
+impl Checkout {
+    /// Fetch the Makefiles, the files called one of `names`, of `url` at
+    /// `reference` (a branch or tag), or at the default branch
+    pub fn fetch(url: &str, reference: Option<&str>, names: &[String]) -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!(
+            "maki-repo-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let checkout = Self {
+            dir,
+            names: names.to_vec(),
+        };
+
+        let dir = checkout.dir.to_string_lossy().into_owned();
+        let mut clone = vec![
+            "clone",
+            "--quiet",
+            "--depth=1",
+            "--filter=blob:none",
+            "--no-checkout",
+        ];
+        if let Some(reference) = reference {
+            clone.extend(["--branch", reference]);
+        }
+        clone.extend(["--", url, &dir]);
+        git(None, &clone).map_err(|e| anyhow!("Failed to fetch {} ({})", url, e))?;
+
+        let files = git(
+            Some(&checkout.dir),
+            &["ls-tree", "-r", "-z", "--name-only", "HEAD"],
+        )?;
+        let makefiles: Vec<&str> = files
+            .split('\0')
+            .filter(|path| makefile::is_makefile(path, names))
+            .collect();
+        if makefiles.is_empty() {
+            bail!("No Makefile found in {}", url);
+        }
+
+        let mut checkout_files = vec!["checkout", "--quiet", "HEAD", "--"];
+        checkout_files.extend(makefiles);
+        git(Some(&checkout.dir), &checkout_files)?;
+
+        Ok(checkout)
+    }
+
+    /// The directory the Makefiles were fetched into
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The targets of the top-level Makefile, or with `recursive` of every
+    /// Makefile, with their files relative to the repository
+    pub fn targets(&self, recursive: bool, options: &ParseOptions) -> Result<Vec<Target>> {
+        let makefiles = makefile::find_makefiles(&self.dir, recursive, &self.names);
+        if makefiles.is_empty() {
+            bail!("No Makefile at the top of the repository; pass --recursive for the others");
+        }
+
+        let mut seen = HashSet::new();
+        let mut targets = Vec::new();
+        for path in makefiles {
+            for mut target in makefile::parse_makefile(&path, options)? {
+                if let Ok(relative) = target.file.strip_prefix(&self.dir) {
+                    target.file = relative.to_path_buf();
+                }
+                if seen.insert(target.name.clone()) {
+                    targets.push(target);
+                }
+            }
+        }
+        Ok(targets)
+    }
+}
+
+impl Drop for Checkout {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Run git in `dir`, returning what it printed
+pub(crate) fn git(dir: Option<&Path>, args: &[&str]) -> Result<String> {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.arg("-C").arg(dir);
+    }
+    // Fail instead of waiting for credentials no one will type
+    let output = command
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .context("Failed to run git")?;
+
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::makefile::default_makefile_names;
+    use tempfile::TempDir;
+
+    /// A repository with a Makefile at the top and one in `api/`, on a `main`
+    /// and a `next` branch
+    fn repository() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let run = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(dir.path())
+                .args(["-c", "user.name=maki", "-c", "user.email=maki@example.com"])
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        fs::create_dir(dir.path().join("api")).unwrap();
+        fs::write(
+            dir.path().join("api/Makefile"),
+            "# Serve it\nserve:\n\t@true\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("Makefile"), "# Build it\nbuild:\n\t@true\n").unwrap();
+        fs::write(dir.path().join("README.md"), "# app\n").unwrap();
+        run(&["init", "--quiet", "--initial-branch", "main"]);
+        run(&["add", "."]);
+        run(&["commit", "--quiet", "-m", "first"]);
+        run(&["checkout", "--quiet", "-b", "next"]);
+        fs::write(
+            dir.path().join("Makefile"),
+            "# Release it\nrelease:\n\t@true\n",
+        )
+        .unwrap();
+        run(&["commit", "--quiet", "-am", "second"]);
+        run(&["checkout", "--quiet", "main"]);
+        dir
+    }
+
+    fn url(dir: &TempDir) -> String {
+        format!("file://{}", dir.path().display())
+    }
+
+    fn names(targets: &[Target]) -> Vec<&str> {
+        targets.iter().map(|t| t.name.as_str()).collect()
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_fetch_only_the_makefiles() {
+        let repo = repository();
+
+        let checkout = Checkout::fetch(&url(&repo), None, &default_makefile_names()).unwrap();
+        let targets = checkout.targets(false, &ParseOptions::default()).unwrap();
+        assert_eq!(names(&targets), vec!["build"]);
+        assert_eq!(targets[0].file, PathBuf::from("Makefile"));
+        assert!(!checkout.path().join("README.md").exists());
+
+        let targets = checkout.targets(true, &ParseOptions::default()).unwrap();
+        assert_eq!(names(&targets), vec!["build", "serve"]);
+        assert_eq!(targets[1].file, PathBuf::from("api/Makefile"));
+
+        let dir = checkout.path().to_path_buf();
+        drop(checkout);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_fetch_a_branch() {
+        let repo = repository();
+
+        let checkout =
+            Checkout::fetch(&url(&repo), Some("next"), &default_makefile_names()).unwrap();
+        let targets = checkout.targets(false, &ParseOptions::default()).unwrap();
+
+        assert_eq!(names(&targets), vec!["release"]);
+        assert!(Checkout::fetch(&url(&repo), Some("missing"), &default_makefile_names()).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use anyhow::Result;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+/// When a repeated run stops
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepeatMode {
+    /// Keep running while the target passes (`--until-failure`)
+    UntilFailure,
+    /// Keep running while the target fails (`--until-success`)
+    UntilSuccess,
+}
+
+impl RepeatMode {
+    /// Whether a run with this outcome ends the loop
+    fn stops_on(self, success: bool) -> bool {
+        match self {
+            RepeatMode::UntilFailure => !success,
+            RepeatMode::UntilSuccess => success,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            RepeatMode::UntilFailure => "until it fails",
+            RepeatMode::UntilSuccess => "until it succeeds",
+        }
+    }
+}
+
+/// Why a repeated run stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    Failed,
+    Succeeded,
+    MaxIterations,
+}
+
+/// Outcome of a repeated run, printed at the end and stored in history
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepeatSummary {
+    pub mode: RepeatMode,
+    pub iterations: u32,
+    pub passed: u32,
+    pub failed: u32,
+    pub max_iterations: Option<u32>,
+    pub stopped: StopReason,
+}
+
+/// Call `run` with the iteration number (from 1) until `mode` says to stop
+/// or `max_iterations` runs have been made
+///
+/// `run` returns whether that iteration passed.
+pub fn repeat(
+    target: &str,
+    mode: RepeatMode,
+    max_iterations: Option<u32>,
+    mut run: impl FnMut(u32) -> Result<bool>,
+) -> Result<RepeatSummary> {
+    let limit = match max_iterations {
+        Some(max) => format!(", at most {} times", max),
+        None => String::new(),
+    };
+    println!(
+        "{} Running {} {}{}",
+        "→".blue(),
+        target.bold(),
+        mode.describe(),
+        limit
+    );
+
+    let mut summary = RepeatSummary {
+        mode,
+        iterations: 0,
+        passed: 0,
+        failed: 0,
+        max_iterations,
+        stopped: StopReason::MaxIterations,
+    };
+
+    while max_iterations.is_none_or(|max| summary.iterations < max) {
+        summary.iterations += 1;
+        let counter = match max_iterations {
+            Some(max) => format!("iteration {}/{}", summary.iterations, max),
+            None => format!("iteration {}", summary.iterations),
+        };
+        println!("{}", format!("── {} ──", counter).cyan());
+
+        let success = run(summary.iterations)?;
+        if success {
+            summary.passed += 1;
+        } else {
+            summary.failed += 1;
+        }
+
+        if mode.stops_on(success) {
+            summary.stopped = if success {
+                StopReason::Succeeded
+            } else {
+                StopReason::Failed
+            };
+            break;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// One-line description of a repeated run, e.g. `failed on iteration 7 (6 passed)`
+pub fn describe(summary: &RepeatSummary) -> String {
+    match summary.stopped {
+        StopReason::Failed => format!(
+            "failed on iteration {} ({} passed)",
+            summary.iterations, summary.passed
+        ),
+        StopReason::Succeeded => format!(
+            "succeeded on iteration {} ({} failed)",
+            summary.iterations, summary.failed
+        ),
+        StopReason::MaxIterations => format!(
+            "stopped after {} iterations ({} passed, {} failed)",
+            summary.iterations, summary.passed, summary.failed
+        ),
+    }
+}
+
+/// Print the summary once the loop is over
+pub fn print_summary(target: &str, summary: &RepeatSummary) {
+    let found = match summary.mode {
+        RepeatMode::UntilFailure => summary.stopped == StopReason::Failed,
+        RepeatMode::UntilSuccess => summary.stopped == StopReason::Succeeded,
+    };
+    let marker = if found { "✓".green() } else { "✗".red() };
+
+    println!();
+    println!("{} {} {}", marker, target.bold(), describe(summary));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_until_failure_stops_on_first_failure() {
+        let summary = repeat("test", RepeatMode::UntilFailure, None, |i| Ok(i < 4)).unwrap();
+
+        assert_eq!(summary.iterations, 4);
+        assert_eq!(summary.passed, 3);
+        assert_eq!(summary.failed, 1);
+        assert_eq!(summary.stopped, StopReason::Failed);
+        assert_eq!(describe(&summary), "failed on iteration 4 (3 passed)");
+    }
+
+    #[test]
+    fn test_until_success_respects_max_iterations() {
+        let summary = repeat("test", RepeatMode::UntilSuccess, Some(3), |_| Ok(false)).unwrap();
+
+        assert_eq!(summary.iterations, 3);
+        assert_eq!(summary.failed, 3);
+        assert_eq!(summary.stopped, StopReason::MaxIterations);
+    }
+
+    #[test]
+    fn test_until_success_stops_on_success() {
+        let summary = repeat("test", RepeatMode::UntilSuccess, Some(5), |i| Ok(i == 2)).unwrap();
+
+        assert_eq!(summary.iterations, 2);
+        assert_eq!(summary.stopped, StopReason::Succeeded);
+    }
+}
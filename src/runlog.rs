@@ -0,0 +1,339 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::sync::LazyLock;
+
+use crate::artifact::Artifact;
+use crate::quote;
+use crate::secret::Redactor;
+
+/// Matches CSI (colors, cursor movement), OSC (titles, links), and other
+/// two-byte escape sequences
+static ANSI_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)|\x1b[@-Z\\-_]").unwrap()
+});
+
+/// Remove ANSI escape sequences (and carriage-return redraws) from output
+pub fn strip_ansi(text: &str) -> String {
+    let stripped = ANSI_REGEX.replace_all(text, "");
+
+    // Progress bars redraw a line with `\r`; keep only what was last drawn
+    stripped
+        .split('\n')
+        .map(|line| {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            line.rsplit('\r').next().unwrap_or(line)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Machine-readable summary of a logged run, written next to its logs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub target: String,
+    /// The command that was run, e.g. `make deploy ENV=prod`
+    pub command: String,
+    /// Variables passed on the command line
+    pub variables: BTreeMap<String, String>,
+    /// RFC 3339 timestamps
+    pub started: String,
+    pub finished: String,
+    pub duration_ms: u64,
+    /// Exit code, `None` if the run was killed by a signal
+    pub exit_code: Option<i32>,
+    pub success: bool,
+    /// The raw and plain-text logs
+    pub log: PathBuf,
+    pub text_log: PathBuf,
+    /// Files the run wrote and mentioned in its output
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+}
+
+/// Log files for a single run: the raw output plus a plain-text copy
+pub struct RunLog {
+    /// Output exactly as make wrote it, ANSI escapes included
+    pub raw_path: PathBuf,
+    /// The same output with escapes stripped, for attaching to tickets
+    pub text_path: PathBuf,
+    /// The run's receipt, written once it finishes
+    pub receipt_path: PathBuf,
+    /// The name its files share, e.g. `20260101-120000-deploy`
+    pub stem: String,
+    header: String,
+    target: String,
+    command: String,
+    variables: BTreeMap<String, String>,
+    started: DateTime<Local>,
+    file: File,
+    redactor: Redactor,
+}
+
+impl RunLog {
+    /// Create the log files for a run of `target` inside `dir`
+    pub fn create(
+        dir: &Path,
+        target: &str,
+        command: &str,
+        variables: &[(String, String)],
+    ) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create log directory: {}", dir.display()))?;
+
+        let started = Local::now();
+        let (stem, mut file) = claim_stem(&started, target, |stem| {
+            File::create_new(dir.join(format!("{}.log", stem)))
+        })
+        .with_context(|| format!("Failed to create log file in {}", dir.display()))?;
+        let raw_path = dir.join(format!("{}.log", stem));
+        let text_path = dir.join(format!("{}.txt", stem));
+        let receipt_path = dir.join(format!("{}.json", stem));
+
+        let header = header(command, variables, &started);
+        file.write_all(header.as_bytes())?;
+
+        Ok(Self {
+            raw_path,
+            text_path,
+            receipt_path,
+            stem,
+            header,
+            target: target.to_string(),
+            command: command.to_string(),
+            variables: variables.iter().cloned().collect(),
+            started,
+            file,
+            redactor: Redactor::default(),
+        })
+    }
+
+    /// Mask the values `redactor` knows in the output written from now on
+    pub fn with_redactor(self, redactor: Redactor) -> Self {
+        Self { redactor, ..self }
+    }
+
+    /// Append a chunk of the run's output
+    pub fn write(&mut self, chunk: &[u8]) -> Result<()> {
+        let chunk = self.redactor.feed(chunk);
+        self.file.write_all(&chunk)?;
+        Ok(())
+    }
+
+    /// Write the footer, the plain-text copy, and the receipt once the run is over
+    pub fn finish(mut self, status: &ExitStatus, artifacts: Vec<Artifact>) -> Result<()> {
+        let finished = Local::now();
+        let held = self.redactor.finish();
+        self.file.write_all(&held)?;
+        let footer = format!(
+            "\n# finished: {}\n# duration: {:.1}s\n# exit status: {}\n",
+            finished.to_rfc3339(),
+            (finished - self.started).as_seconds_f64(),
+            status
+                .code()
+                .map(|c| c.to_string())
+                .unwrap_or_else(|| "killed".to_string())
+        );
+        self.file.write_all(footer.as_bytes())?;
+        self.file.flush()?;
+
+        let raw = fs::read(&self.raw_path)?;
+        let output = String::from_utf8_lossy(&raw[self.header.len()..]);
+        fs::write(
+            &self.text_path,
+            format!("{}{}", self.header, strip_ansi(&output)),
+        )
+        .with_context(|| format!("Failed to write log file: {}", self.text_path.display()))?;
+
+        let receipt = Receipt {
+            target: self.target,
+            command: self.command,
+            variables: self.variables,
+            started: self.started.to_rfc3339(),
+            finished: finished.to_rfc3339(),
+            duration_ms: (finished - self.started).num_milliseconds().max(0) as u64,
+            exit_code: status.code(),
+            success: status.success(),
+            log: self.raw_path,
+            text_log: self.text_path,
+            artifacts,
+        };
+        let json = serde_json::to_string_pretty(&receipt)?;
+        fs::write(&self.receipt_path, json).with_context(|| {
+            format!(
+                "Failed to write run receipt: {}",
+                self.receipt_path.display()
+            )
+        })?;
+
+        Ok(())
+    }
+}
+
+/// The name of a run of `target` started at `started`, shared by its log
+/// files and its artifacts directory
+pub fn stem(started: &DateTime<Local>, target: &str) -> String {
+    format!(
+        "{}-{}",
+        started.format("%Y%m%d-%H%M%S"),
+        sanitize_file_name(target)
+    )
+}
+
+/// The [`stem`] of a run no other run has, once `claim` has created what is
+/// named after it (failing with `AlreadyExists` when a run has): a run of the
+/// same target started in the same second gets `-2`, `-3`, ...
+pub fn claim_stem<T>(
+    started: &DateTime<Local>,
+    target: &str,
+    mut claim: impl FnMut(&str) -> io::Result<T>,
+) -> io::Result<(String, T)> {
+    let base = stem(started, target);
+    let mut count = 1;
+    loop {
+        let stem = match count {
+            1 => base.clone(),
+            _ => format!("{}-{}", base, count),
+        };
+        match claim(&stem) {
+            Ok(claimed) => return Ok((stem, claimed)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => count += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn header(command: &str, variables: &[(String, String)], started: &DateTime<Local>) -> String {
+    let mut header = format!("# maki run log\n# command: {}\n", comment(command));
+    if !variables.is_empty() {
+        let vars: Vec<String> = variables
+            .iter()
+            .map(|(name, value)| quote::assignment(name, value))
+            .collect();
+        header.push_str(&format!("# variables: {}\n", comment(&vars.join(" "))));
+    }
+    header.push_str(&format!("# started: {}\n\n", started.to_rfc3339()));
+    header
+}
+
+/// `text` for a header line, continuing its lines (from a quoted value with
+/// a newline) as comments too
+fn comment(text: &str) -> String {
+    text.replace('\n', "\n#   ")
+}
+
+/// Target names may contain `/` or other characters that don't belong in file names
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_strip_ansi_colors_and_links() {
+        let text = "\x1b[1;32mok\x1b[0m \x1b]8;;https://x.test\x07link\x1b]8;;\x07";
+        assert_eq!(strip_ansi(text), "ok link");
+    }
+
+    #[test]
+    fn test_strip_ansi_carriage_returns() {
+        let text = "Downloading 10%\rDownloading 100%\r\ndone\n";
+        assert_eq!(strip_ansi(text), "Downloading 100%\ndone\n");
+    }
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("docker/build"), "docker_build");
+        assert_eq!(sanitize_file_name("test-1.2"), "test-1.2");
+    }
+
+    #[test]
+    fn test_run_log_writes_raw_and_plain_copies() {
+        let dir = TempDir::new().unwrap();
+        let vars = vec![("ENV".to_string(), "prod".to_string())];
+
+        let mut log = RunLog::create(dir.path(), "deploy", "make deploy ENV=prod", &vars).unwrap();
+        log.write(b"\x1b[31merror\x1b[0m\n").unwrap();
+        let raw_path = log.raw_path.clone();
+        let text_path = log.text_path.clone();
+        let receipt_path = log.receipt_path.clone();
+
+        let artifact = Artifact {
+            path: PathBuf::from("dist/app.tar.gz"),
+            bytes: 3,
+            copy: None,
+        };
+        log.finish(&ExitStatus::default(), vec![artifact.clone()])
+            .unwrap();
+
+        let receipt: Receipt =
+            serde_json::from_str(&fs::read_to_string(receipt_path).unwrap()).unwrap();
+        assert_eq!(receipt.command, "make deploy ENV=prod");
+        assert_eq!(receipt.variables["ENV"], "prod");
+        assert_eq!(receipt.exit_code, Some(0));
+        assert_eq!(receipt.text_log, text_path);
+        assert_eq!(receipt.artifacts, vec![artifact]);
+
+        let raw = fs::read_to_string(raw_path).unwrap();
+        let text = fs::read_to_string(text_path).unwrap();
+
+        assert!(raw.contains("# command: make deploy ENV=prod"));
+        assert!(raw.contains("# variables: ENV=prod"));
+        assert!(raw.contains("\x1b[31merror"));
+        assert!(text.contains("\nerror\n"));
+        assert!(!text.contains('\x1b'));
+        assert!(text.contains("# exit status: 0"));
+    }
+
+    #[test]
+    fn test_runs_in_the_same_second_keep_their_own_logs() {
+        let dir = TempDir::new().unwrap();
+
+        let first = RunLog::create(dir.path(), "deploy", "make deploy", &[]).unwrap();
+        let second = RunLog::create(dir.path(), "deploy", "make deploy", &[]).unwrap();
+
+        assert_ne!(first.raw_path, second.raw_path);
+        assert_ne!(first.receipt_path, second.receipt_path);
+        let started = Local::now();
+        let (stem, ()) = claim_stem(&started, "deploy", |stem| match stem.ends_with("-3") {
+            true => Ok(()),
+            false => Err(io::ErrorKind::AlreadyExists.into()),
+        })
+        .unwrap();
+        assert_eq!(stem, format!("{}-3", super::stem(&started, "deploy")));
+    }
+
+    #[test]
+    fn test_header_quotes_values() {
+        let vars = vec![
+            ("ARGS".to_string(), "--flag 'a b'".to_string()),
+            ("MSG".to_string(), "two\nlines".to_string()),
+        ];
+        let started = Local::now();
+
+        let header = header("make deploy MSG='two\nlines'", &vars, &started);
+
+        let lines: Vec<&str> = header.lines().collect();
+        assert_eq!(lines[1], "# command: make deploy MSG='two");
+        assert_eq!(lines[2], "#   lines'");
+        assert_eq!(lines[3], r"# variables: ARGS='--flag '\''a b'\''' MSG='two");
+        assert_eq!(lines[4], "#   lines'");
+    }
+}
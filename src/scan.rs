@@ -0,0 +1,171 @@
+use colored::Colorize;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error::{MakiError, Result};
+use crate::makefile;
+
+/// How long a scan runs before its progress is shown, so quick ones draw
+/// nothing
+const SHOW_AFTER: Duration = Duration::from_millis(500);
+
+/// How often the count of visited directories is redrawn
+const REDRAW: Duration = Duration::from_millis(100);
+
+/// Move to the start of the line and clear it
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// Set by Ctrl-C while a scan runs
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether a recursive scan of `dir` would walk most of the machine: it is
+/// the home directory or a filesystem root
+pub fn is_too_broad(dir: &Path, home: Option<&Path>) -> bool {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let dir = canonical(dir);
+    dir.parent().is_none() || home.is_some_and(|home| canonical(home) == dir)
+}
+
+/// The status line of a scan that has entered `dirs` directories
+pub fn progress_line(dirs: usize) -> String {
+    format!(
+        "Scanning for Makefiles… {} directories (Ctrl-C to stop)",
+        dirs
+    )
+}
+
+/// Find the Makefiles, the files called one of `names`, in `dir`, and with
+/// `recursive` below it, showing how many directories the walk has entered
+/// when it takes a while
+///
+/// Ctrl-C stops the walk with an error instead of quitting maki halfway
+/// through the status line.
+pub fn find_makefiles(dir: &Path, recursive: bool, names: &[String]) -> Result<Vec<PathBuf>> {
+    if !recursive {
+        return Ok(makefile::find_makefiles(dir, false, names));
+    }
+
+    let terminal = std::io::stderr().is_terminal();
+    let started = Instant::now();
+    let mut drawn: Option<Instant> = None;
+    let mut visited = 0;
+
+    let interrupt = Interrupt::catch();
+    let makefiles = makefile::find_makefiles_with(dir, true, names, |dirs| {
+        visited = dirs;
+        let now = Instant::now();
+        if terminal && now - started >= SHOW_AFTER && drawn.is_none_or(|at| now - at >= REDRAW) {
+            eprint!("{}{}", CLEAR_LINE, progress_line(dirs).dimmed());
+            drawn = Some(now);
+        }
+        !INTERRUPTED.load(Ordering::SeqCst)
+    });
+    drop(interrupt);
+
+    if drawn.is_some() {
+        eprint!("{}", CLEAR_LINE);
+    }
+    if INTERRUPTED.swap(false, Ordering::SeqCst) {
+        return Err(MakiError::Discovery {
+            message: format!(
+                "Stopped scanning {} after {} directories",
+                dir.display(),
+                visited
+            ),
+            hint: None,
+        });
+    }
+    Ok(makefiles)
+}
+
+/// Ctrl-C caught for as long as it is kept, putting back what it did before
+/// when dropped
+#[cfg(unix)]
+struct Interrupt(libc::sighandler_t);
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+#[cfg(unix)]
+impl Interrupt {
+    fn catch() -> Self {
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        let handler = on_interrupt as extern "C" fn(libc::c_int);
+        // SAFETY: the handler only stores to an atomic, which is
+        // async-signal-safe
+        Self(unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Interrupt {
+    fn drop(&mut self) {
+        // SAFETY: as above, putting back the handler this one replaced
+        unsafe {
+            libc::signal(libc::SIGINT, self.0);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct Interrupt;
+
+#[cfg(not(unix))]
+impl Interrupt {
+    fn catch() -> Self {
+        Self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_is_too_broad() {
+        let dir = TempDir::new().unwrap();
+        let home = dir.path().join("home");
+        fs::create_dir_all(home.join("src")).unwrap();
+
+        assert!(is_too_broad(Path::new("/"), None));
+        assert!(is_too_broad(&home, Some(&home)));
+        assert!(is_too_broad(&home.join("src/.."), Some(&home)));
+        assert!(!is_too_broad(&home.join("src"), Some(&home)));
+        assert!(!is_too_broad(dir.path(), None));
+    }
+
+    #[test]
+    fn test_walk_stops_when_asked() {
+        let dir = TempDir::new().unwrap();
+        for sub in ["a", "b", "c"] {
+            fs::create_dir_all(dir.path().join(sub)).unwrap();
+            fs::write(dir.path().join(sub).join("Makefile"), "all:\n").unwrap();
+        }
+
+        let names = makefile::default_makefile_names();
+        let mut entered = Vec::new();
+        let found = makefile::find_makefiles_with(dir.path(), true, &names, |dirs| {
+            entered.push(dirs);
+            dirs < 3
+        });
+
+        // The top directory, then a; b is where it stops
+        assert_eq!(entered, vec![1, 2, 3]);
+        assert_eq!(found, vec![dir.path().join("a/Makefile")]);
+        assert_eq!(find_makefiles(dir.path(), true, &names).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_progress_line() {
+        assert_eq!(
+            progress_line(1200),
+            "Scanning for Makefiles… 1200 directories (Ctrl-C to stop)"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::graph::Graph;
+use crate::target::Target;
+
+/// A target in the combined prerequisite graph of a run, which comes after
+/// every step it needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub target: String,
+    /// The steps that must succeed before it starts, as indices of earlier
+    /// steps
+    pub needs: Vec<usize>,
+    /// Every target below it in the graph; maki runs them itself, so make is
+    /// told not to remake them
+    pub below: Vec<String>,
+    /// The requested targets it is run for, as indices into them
+    pub wanted_by: Vec<usize>,
+}
+
+/// Builds the steps, visiting each target's prerequisites before it
+struct Planner<'a> {
+    graph: Graph<'a>,
+    steps: Vec<Step>,
+    index: HashMap<String, usize>,
+    visiting: HashSet<String>,
+}
+
+/// The combined graph of `names` and those of their prerequisites that are
+/// targets, each only once, in an order that runs prerequisites first
+///
+/// Prerequisites that are files or pattern rules are left to make. One that
+/// leads back to a target still being visited is dropped, as make drops
+/// circular dependencies.
+pub fn plan(names: &[&str], targets: &[Target]) -> Vec<Step> {
+    let mut planner = Planner {
+        graph: Graph::new(targets),
+        steps: Vec::new(),
+        index: HashMap::new(),
+        visiting: HashSet::new(),
+    };
+    for (wanted, name) in names.iter().enumerate() {
+        let node = planner.graph.find(name);
+        if let Some(step) = planner.visit(name, node) {
+            planner.mark(step, wanted);
+        }
+    }
+    planner.steps
+}
+
+impl Planner<'_> {
+    /// The step for `name`, the graph's `node` when it is a target, planning
+    /// what it needs first; None when it is already being visited
+    fn visit(&mut self, name: &str, node: Option<usize>) -> Option<usize> {
+        if let Some(&step) = self.index.get(name) {
+            return Some(step);
+        }
+        if !self.visiting.insert(name.to_string()) {
+            return None;
+        }
+
+        let targets = self.graph.targets();
+        let prerequisites: Vec<usize> = node
+            .into_iter()
+            .flat_map(|node| self.graph.needs(node).map(|(need, _)| need))
+            .collect();
+        let mut needs = Vec::new();
+        for need in prerequisites {
+            if let Some(step) = self.visit(&targets[need].name, Some(need))
+                && !needs.contains(&step)
+            {
+                needs.push(step);
+            }
+        }
+        self.visiting.remove(name);
+
+        let mut below: Vec<String> = Vec::new();
+        for &step in &needs {
+            let step = &self.steps[step];
+            for name in std::iter::once(&step.target).chain(&step.below) {
+                if !below.contains(name) {
+                    below.push(name.clone());
+                }
+            }
+        }
+        self.steps.push(Step {
+            target: name.to_string(),
+            needs,
+            below,
+            wanted_by: Vec::new(),
+        });
+        self.index.insert(name.to_string(), self.steps.len() - 1);
+        Some(self.steps.len() - 1)
+    }
+
+    /// Note that `step` and everything it needs are run for the requested
+    /// target `wanted`
+    fn mark(&mut self, step: usize, wanted: usize) {
+        if self.steps[step].wanted_by.contains(&wanted) {
+            return;
+        }
+        self.steps[step].wanted_by.push(wanted);
+        for need in self.steps[step].needs.clone() {
+            self.mark(need, wanted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(steps: &[Step]) -> Vec<&str> {
+        steps.iter().map(|s| s.target.as_str()).collect()
+    }
+
+    #[test]
+    fn test_plan_runs_shared_prerequisites_once() {
+        let targets = [
+            Target::with_deps("gen", &[]),
+            Target::with_deps("build", &["gen", "src/main.c"]),
+            Target::with_deps("test", &["build"]),
+            Target::with_deps("lint", &["build"]),
+            Target::with_deps("docs", &[]),
+        ];
+
+        let steps = plan(&["test", "lint", "docs"], &targets);
+
+        assert_eq!(names(&steps), vec!["gen", "build", "test", "lint", "docs"]);
+        assert_eq!(steps[1].needs, vec![0]);
+        assert_eq!(steps[2].needs, vec![1]);
+        assert_eq!(steps[3].needs, vec![1]);
+        assert!(steps[4].needs.is_empty());
+        assert_eq!(steps[2].below, vec!["build", "gen"]);
+        assert_eq!(steps[0].wanted_by, vec![0, 1]);
+        assert_eq!(steps[4].wanted_by, vec![2]);
+    }
+
+    #[test]
+    fn test_plan_drops_circular_prerequisites() {
+        let targets = [
+            Target::with_deps("a", &["b"]),
+            Target::with_deps("b", &["a"]),
+        ];
+
+        let steps = plan(&["a", "b"], &targets);
+
+        assert_eq!(names(&steps), vec!["b", "a"]);
+        assert!(steps[0].needs.is_empty());
+        assert_eq!(steps[1].needs, vec![0]);
+    }
+
+    #[test]
+    fn test_plan_keeps_unknown_targets() {
+        let steps = plan(&["dist/app.tar.gz"], &[]);
+
+        assert_eq!(names(&steps), vec!["dist/app.tar.gz"]);
+        assert_eq!(steps[0].wanted_by, vec![0]);
+    }
+}
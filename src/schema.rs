@@ -0,0 +1,268 @@
+use clap::ValueEnum;
+
+/// The JSON formats maki publishes a schema for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SchemaKind {
+    /// `maki list --json`
+    Targets,
+    /// `maki history --json` and the history file
+    History,
+    /// Run receipts written with `--log-dir`
+    Receipt,
+    /// `maki run <target> --json`
+    Run,
+    /// `maki version --json`
+    Version,
+}
+
+/// The JSON Schema (draft 2020-12) for a format
+pub fn schema(kind: SchemaKind) -> &'static str {
+    match kind {
+        SchemaKind::Targets => include_str!("../schemas/targets.schema.json"),
+        SchemaKind::History => include_str!("../schemas/history.schema.json"),
+        SchemaKind::Receipt => include_str!("../schemas/receipt.schema.json"),
+        SchemaKind::Run => include_str!("../schemas/run.schema.json"),
+        SchemaKind::Version => include_str!("../schemas/version.schema.json"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact::Artifact;
+    use crate::capture::{CapturedOutput, RunResult};
+    use crate::history::HistoryEntry;
+    use crate::providers::ProviderPolicy;
+    use crate::repeat::{RepeatMode, RepeatSummary, StopReason};
+    use crate::runlog::Receipt;
+    use crate::target::{Provider, RequiredVar, Secret, Target, TargetVar};
+    use crate::version::BuildInfo;
+    use serde_json::Value;
+    use std::path::PathBuf;
+
+    /// Check `value` against the subset of JSON Schema the published schemas
+    /// use, returning the path of the first mismatch
+    fn check(root: &Value, schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/$defs/");
+            return check(root, &root["$defs"][name], value, path);
+        }
+
+        if let Some(options) = schema["oneOf"].as_array() {
+            let matches = options
+                .iter()
+                .filter(|option| check(root, option, value, path).is_ok())
+                .count();
+            return match matches {
+                1 => Ok(()),
+                n => Err(format!("{}: matches {} of oneOf", path, n)),
+            };
+        }
+
+        if let Some(allowed) = schema["enum"].as_array()
+            && !allowed.contains(value)
+        {
+            return Err(format!("{}: {} not in enum", path, value));
+        }
+
+        if let Some(types) = schema.get("type") {
+            let types: Vec<&str> = match types {
+                Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+                t => vec![t.as_str().unwrap()],
+            };
+            let actual = match value {
+                Value::Null => "null",
+                Value::Bool(_) => "boolean",
+                Value::Number(n) if n.is_u64() || n.is_i64() => "integer",
+                Value::Number(_) => "number",
+                Value::String(_) => "string",
+                Value::Array(_) => "array",
+                Value::Object(_) => "object",
+            };
+            if !types.contains(&actual) {
+                return Err(format!("{}: expected {:?}, got {}", path, types, actual));
+            }
+        }
+
+        if let Value::Array(items) = value {
+            for (i, item) in items.iter().enumerate() {
+                check(root, &schema["items"], item, &format!("{}[{}]", path, i))?;
+            }
+        }
+
+        if let Value::Object(fields) = value {
+            for required in schema["required"].as_array().into_iter().flatten() {
+                let name = required.as_str().unwrap();
+                if !fields.contains_key(name) {
+                    return Err(format!("{}: missing {}", path, name));
+                }
+            }
+            for (name, field) in fields {
+                let field_path = format!("{}.{}", path, name);
+                match schema["properties"].get(name) {
+                    Some(property) => check(root, property, field, &field_path)?,
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            return Err(format!("{}: not in schema", field_path));
+                        }
+                        Some(additional) => check(root, additional, field, &field_path)?,
+                        None => {}
+                    },
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn assert_matches(kind: SchemaKind, value: impl serde::Serialize) {
+        let schema: Value = serde_json::from_str(schema(kind)).unwrap();
+        let value = serde_json::to_value(value).unwrap();
+        if let Err(e) = check(&schema, &schema, &value, "$") {
+            panic!("{:?} output doesn't match its schema: {}", kind, e);
+        }
+    }
+
+    #[test]
+    fn test_schemas_are_valid_json() {
+        for kind in SchemaKind::value_variants() {
+            let schema: Value = serde_json::from_str(schema(*kind)).unwrap();
+            assert_eq!(
+                schema["$schema"],
+                "https://json-schema.org/draft/2020-12/schema"
+            );
+        }
+    }
+
+    #[test]
+    fn test_targets_match_schema() {
+        let mut target = Target::with_required_vars(
+            "bump".to_string(),
+            Some("Bump version".to_string()),
+            PathBuf::from("Makefile"),
+            3,
+            vec![
+                RequiredVar {
+                    name: "V".to_string(),
+                    hint: Some("patch|minor|major".to_string()),
+                    default: Some("patch".to_string()),
+                    secret: None,
+                },
+                RequiredVar {
+                    name: "TOKEN".to_string(),
+                    hint: None,
+                    default: None,
+                    secret: Some(Secret {
+                        command: Some("pass show registry/token".to_string()),
+                    }),
+                },
+            ],
+        );
+        target.deps = vec!["build".to_string()];
+        target.order_only = vec!["out".to_string()];
+        target.target_vars = vec![TargetVar {
+            name: "CC".to_string(),
+            op: ":=".to_string(),
+            value: "clang".to_string(),
+            line: 9,
+        }];
+        let mut web = Target::new("web".to_string(), None, PathBuf::from("Procfile"), 1);
+        web.provider = Provider::Procfile;
+        web.root = Some(PathBuf::from("/src/web"));
+
+        assert_matches(SchemaKind::Targets, vec![target, web]);
+    }
+
+    #[test]
+    fn test_history_matches_schema() {
+        let entry = HistoryEntry {
+            target: "test".to_string(),
+            command: "make test".to_string(),
+            dir: PathBuf::from("/project"),
+            started_at: 1_700_000_000,
+            duration_ms: 5300,
+            exit_code: Some(2),
+            success: false,
+            repeat: None,
+        };
+        let repeated = HistoryEntry {
+            repeat: Some(RepeatSummary {
+                mode: RepeatMode::UntilFailure,
+                iterations: 7,
+                passed: 6,
+                failed: 1,
+                max_iterations: None,
+                stopped: StopReason::Failed,
+            }),
+            ..entry.clone()
+        };
+
+        assert_matches(SchemaKind::History, vec![entry, repeated]);
+    }
+
+    #[test]
+    fn test_receipt_matches_schema() {
+        let receipt = Receipt {
+            target: "deploy".to_string(),
+            command: "make deploy ENV=prod".to_string(),
+            variables: [("ENV".to_string(), "prod".to_string())].into(),
+            started: "2026-01-01T10:00:00+00:00".to_string(),
+            finished: "2026-01-01T10:00:04+00:00".to_string(),
+            duration_ms: 4000,
+            exit_code: None,
+            success: false,
+            log: PathBuf::from("logs/20260101-100000-deploy.log"),
+            text_log: PathBuf::from("logs/20260101-100000-deploy.txt"),
+            artifacts: vec![Artifact {
+                path: PathBuf::from("dist/app.tar.gz"),
+                bytes: 1024,
+                copy: Some(PathBuf::from(
+                    "artifacts/20260101-100000-deploy/dist/app.tar.gz",
+                )),
+            }],
+        };
+
+        assert_matches(SchemaKind::Receipt, receipt);
+    }
+
+    #[test]
+    fn test_run_result_matches_schema() {
+        let result = RunResult {
+            target: "build".to_string(),
+            command: "make build".to_string(),
+            exit_code: Some(0),
+            success: true,
+            noop: false,
+            duration_ms: 1200,
+            stdout: CapturedOutput::new(b"compiled\n", None),
+            stderr: CapturedOutput::new(b"warning: unused\n", Some(8)),
+        };
+
+        assert_matches(SchemaKind::Run, result);
+    }
+
+    #[test]
+    fn test_version_matches_schema() {
+        let mut info = BuildInfo::current(&ProviderPolicy::default());
+        assert_matches(SchemaKind::Version, &info);
+
+        info.git_sha = None;
+        info.build_date = None;
+        info.cache_path = None;
+        assert_matches(SchemaKind::Version, info);
+    }
+
+    #[test]
+    fn test_check_catches_drift() {
+        let schema: Value = serde_json::from_str(schema(SchemaKind::Targets)).unwrap();
+        let extra = serde_json::json!([{
+            "name": "x", "description": null, "file": "Makefile", "line": 1,
+            "required_vars": [], "deps": [], "target_vars": [], "provider": "make",
+            "root": null, "aggregate": false, "boilerplate": false, "needs_sudo": false, "serial": false,
+            "hidden": false, "label": null, "note": null,
+            "recipe": "echo"
+        }]);
+
+        assert!(check(&schema, &schema, &extra, "$").is_err());
+    }
+}
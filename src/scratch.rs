@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::env::{EnvSource, EnvVar};
+
+/// The variable a run's scratch directory is exported as
+pub const SCRATCH_VAR: &str = "MAKI_SCRATCH";
+
+/// A fresh temporary directory of one run (`--scratch`), removed when it is
+/// dropped unless it is kept
+#[derive(Debug)]
+pub struct Scratch {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl Scratch {
+    /// Create a new scratch directory under the system's temporary directory
+    pub fn create(keep: bool) -> Result<Self> {
+        Self::create_in(&std::env::temp_dir(), keep)
+    }
+
+    fn create_in(parent: &Path, keep: bool) -> Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let path = parent.join(format!("maki-scratch-{}-{}", std::process::id(), nanos));
+        // create_dir rather than create_dir_all: it must not be in use already
+        fs::create_dir(&path)
+            .with_context(|| format!("Failed to create scratch directory: {}", path.display()))?;
+        Ok(Self { path, keep })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the directory stays behind after the run
+    pub fn is_kept(&self) -> bool {
+        self.keep
+    }
+
+    /// The variables pointing the run at the directory: `TMPDIR`, so
+    /// temporary files land in it, and `MAKI_SCRATCH`
+    pub fn env(&self) -> Vec<EnvVar> {
+        let value = self.path.to_string_lossy().to_string();
+        ["TMPDIR", SCRATCH_VAR]
+            .into_iter()
+            .map(|name| EnvVar {
+                name: name.to_string(),
+                value: value.clone(),
+                source: EnvSource::Scratch,
+            })
+            .collect()
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+/// `env` with the scratch directory's variables in place of any of the same
+/// name
+pub fn with_scratch(mut env: Vec<EnvVar>, scratch: &Scratch) -> Vec<EnvVar> {
+    let vars = scratch.env();
+    env.retain(|e| !vars.iter().any(|v| v.name == e.name));
+    env.extend(vars);
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scratch_is_removed_unless_kept() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let scratch = Scratch::create_in(dir.path(), false).unwrap();
+        let path = scratch.path().to_path_buf();
+        fs::write(path.join("artifact"), "x").unwrap();
+        drop(scratch);
+        assert!(!path.exists());
+
+        let scratch = Scratch::create_in(dir.path(), true).unwrap();
+        let path = scratch.path().to_path_buf();
+        drop(scratch);
+        assert!(path.is_dir());
+    }
+
+    #[test]
+    fn test_with_scratch_overrides_tmpdir() {
+        let dir = tempfile::tempdir().unwrap();
+        let scratch = Scratch::create_in(dir.path(), false).unwrap();
+        let env = vec![EnvVar {
+            name: "TMPDIR".to_string(),
+            value: "/tmp".to_string(),
+            source: EnvSource::Config,
+        }];
+
+        let env = with_scratch(env, &scratch);
+        let path = scratch.path().to_string_lossy();
+        assert_eq!(env.len(), 2);
+        assert!(env.iter().all(|e| e.value == path));
+        assert!(env.iter().any(|e| e.name == SCRATCH_VAR));
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{Context, Result};
+use std::process::{Command, Stdio};
+
+use crate::executor::MASK;
+
+/// The value of the secret `name` from its `command`, e.g.
+/// `pass show deploy/token`: what it prints, without the final newline
+///
+/// The command runs in a shell with the terminal's stdin and stderr, so a
+/// password manager can ask to be unlocked.
+pub fn resolve(name: &str, command: &str) -> Result<String> {
+    let mut shell = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.arg("/C").arg(command);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.arg("-c").arg(command);
+        c
+    };
+    let output = shell
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("Failed to run `{}` for the secret {}", command, name))?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "`{}` failed ({}), so the secret {} has no value",
+            command,
+            output.status,
+            name
+        );
+    }
+    let value = String::from_utf8(output.stdout)
+        .with_context(|| format!("`{}` printed a secret {} that isn't UTF-8", command, name))?;
+    Ok(value.trim_end_matches(['\n', '\r']).to_string())
+}
+
+/// Masks the values of secrets in output read a chunk at a time, holding
+/// back the end of a chunk that may be the start of one
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    /// The values, longest first so one inside another is masked whole
+    secrets: Vec<Vec<u8>>,
+    held: Vec<u8>,
+}
+
+impl Redactor {
+    pub fn new(values: impl IntoIterator<Item = String>) -> Self {
+        let mut secrets: Vec<Vec<u8>> = values
+            .into_iter()
+            .filter(|v| !v.is_empty())
+            .map(String::into_bytes)
+            .collect();
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        secrets.dedup();
+        Self {
+            secrets,
+            held: Vec::new(),
+        }
+    }
+
+    /// `output` with every secret in it masked, for output read all at once
+    pub fn redact(&self, output: &[u8]) -> Vec<u8> {
+        let mut redacted = Vec::with_capacity(output.len());
+        let mut rest = output;
+        while !rest.is_empty() {
+            match self.secrets.iter().find(|s| rest.starts_with(s)) {
+                Some(secret) => {
+                    redacted.extend_from_slice(MASK.as_bytes());
+                    rest = &rest[secret.len()..];
+                }
+                None => {
+                    redacted.push(rest[0]);
+                    rest = &rest[1..];
+                }
+            }
+        }
+        redacted
+    }
+
+    /// The part of the output read so far that can be written now
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<u8> {
+        if self.secrets.is_empty() {
+            return chunk.to_vec();
+        }
+        self.held.extend_from_slice(chunk);
+        let held = std::mem::take(&mut self.held);
+        let mut redacted = self.redact(&held);
+
+        // A secret the next chunk may finish
+        let longest = self.secrets[0].len();
+        let start = redacted.len().saturating_sub(longest - 1);
+        let partial = (start..redacted.len())
+            .find(|&at| self.secrets.iter().any(|s| s.starts_with(&redacted[at..])));
+        if let Some(at) = partial {
+            self.held = redacted.split_off(at);
+        }
+        redacted
+    }
+
+    /// Output still held back when the run ends
+    pub fn finish(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.held)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redactor_masks_secrets_split_across_chunks() {
+        let mut redactor = Redactor::new(["hunter2".to_string(), String::new()]);
+        let mut output = redactor.feed(b"token: hun");
+        output.extend(redactor.feed(b"ter2\nhunt"));
+        output.extend(redactor.feed(b"ing season\nlast: hunter"));
+        output.extend(redactor.finish());
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "token: ***\nhunting season\nlast: hunter"
+        );
+        assert_eq!(Redactor::default().feed(b"as is"), b"as is");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_resolve() {
+        assert_eq!(resolve("TOKEN", "printf 's3cr3t\\n'").unwrap(), "s3cr3t");
+        // Trailing newlines go, other whitespace stays
+        assert_eq!(resolve("KEY", "printf ' a b \\n\\n'").unwrap(), " a b ");
+
+        let err = resolve("TOKEN", "exit 3").unwrap_err();
+        assert!(err.to_string().contains("the secret TOKEN has no value"));
+    }
+}
@@ -0,0 +1,392 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::cache::Cache;
+use crate::executor::{self, ExecuteOptions};
+use crate::history;
+
+/// How long a stopped session gets to clean up before it is killed
+const GRACE: Duration = Duration::from_secs(5);
+
+/// How often a followed log is checked for more output
+const POLL: Duration = Duration::from_millis(200);
+
+/// A target started with `maki run --detach`, running in a session of its own
+/// so that it outlives the terminal it was started from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// One more than the highest id in use when it started
+    pub id: u32,
+    pub target: String,
+    /// The command line it runs
+    pub command: String,
+    /// The directory it runs in
+    pub dir: PathBuf,
+    /// make's process id, which is also the id of its process group
+    pub pid: u32,
+    /// Unix time it started
+    pub started_at: u64,
+}
+
+impl Session {
+    /// Whether make or anything it started is still running
+    pub fn is_running(&self) -> bool {
+        alive(self.pid)
+    }
+}
+
+/// A session as `maki ps --json` shows it
+#[derive(Debug, Serialize)]
+pub struct SessionStatus<'a> {
+    #[serde(flatten)]
+    pub session: &'a Session,
+    pub running: bool,
+    /// The file its output goes to
+    pub log: PathBuf,
+}
+
+/// The sessions started on this machine, each kept as a `<id>.json` pidfile
+/// and a `<id>.log` of its output
+#[derive(Debug, Clone)]
+pub struct Sessions {
+    dir: PathBuf,
+}
+
+impl Sessions {
+    /// The sessions kept in the cache directory
+    pub fn open() -> Result<Self> {
+        let dir = Cache::cache_dir().context("Could not determine cache directory")?;
+        Ok(Self::at(dir.join("sessions")))
+    }
+
+    /// The sessions kept in `dir`
+    pub fn at(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Start `target` in a new session
+    pub fn start(&self, target: &str, options: &ExecuteOptions) -> Result<Session> {
+        if !cfg!(unix) {
+            bail!("--detach is only available on Unix");
+        }
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+
+        // Claim an id by creating its log, in case another maki starts one too
+        let mut id = self.list()?.iter().map(|s| s.id).max().unwrap_or(0) + 1;
+        let log = loop {
+            match File::create_new(self.log_path(id)) {
+                Ok(log) => break log,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => id += 1,
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Failed to create {}", self.log_path(id).display())
+                    });
+                }
+            }
+        };
+
+        let child = match executor::spawn_target_detached(target, options, &log) {
+            Ok(child) => child,
+            Err(e) => {
+                let _ = fs::remove_file(self.log_path(id));
+                return Err(e.into());
+            }
+        };
+        let dir = match options.cwd {
+            Some(ref cwd) => cwd.canonicalize().unwrap_or_else(|_| cwd.clone()),
+            None => std::env::current_dir().unwrap_or_default(),
+        };
+        let session = Session {
+            id,
+            target: target.to_string(),
+            command: executor::command_line(target, options),
+            dir,
+            pid: child.id(),
+            started_at: history::now_secs(),
+        };
+
+        let json = serde_json::to_string_pretty(&session).context("Failed to serialize session")?;
+        fs::write(self.pid_path(id), json)
+            .with_context(|| format!("Failed to write {}", self.pid_path(id).display()))?;
+        Ok(session)
+    }
+
+    /// Every session, oldest first, whether or not it is still running
+    pub fn list(&self) -> Result<Vec<Session>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", self.dir.display()));
+            }
+        };
+
+        let mut sessions: Vec<Session> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .filter_map(|json| serde_json::from_str(&json).ok())
+            .collect();
+        sessions.sort_by_key(|s| s.id);
+        Ok(sessions)
+    }
+
+    /// The session with id `key`, or the only one running the target `key`
+    pub fn find(&self, key: &str) -> Result<Session> {
+        let sessions = self.list()?;
+        if let Ok(id) = key.parse::<u32>()
+            && let Some(session) = sessions.iter().find(|s| s.id == id)
+        {
+            return Ok(session.clone());
+        }
+
+        let matching: Vec<&Session> = sessions.iter().filter(|s| s.target == key).collect();
+        match matching.as_slice() {
+            [session] => Ok((*session).clone()),
+            [] => bail!("No session {} (see maki ps)", key),
+            several => {
+                let ids: Vec<String> = several.iter().map(|s| s.id.to_string()).collect();
+                bail!(
+                    "Several sessions run {}: {} (pass an id)",
+                    key,
+                    ids.join(", ")
+                )
+            }
+        }
+    }
+
+    pub fn status<'a>(&self, session: &'a Session) -> SessionStatus<'a> {
+        SessionStatus {
+            session,
+            running: session.is_running(),
+            log: self.log_path(session.id),
+        }
+    }
+
+    /// The file a session's output goes to
+    pub fn log_path(&self, id: u32) -> PathBuf {
+        self.dir.join(format!("{}.log", id))
+    }
+
+    fn pid_path(&self, id: u32) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    /// Write the last `lines` lines of a session's output to `out`, then with
+    /// `follow` whatever it prints next, until it exits
+    pub fn attach(
+        &self,
+        session: &Session,
+        lines: usize,
+        follow: bool,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        let path = self.log_path(session.id);
+        let mut log = File::open(&path)
+            .with_context(|| format!("Failed to open the log {}", path.display()))?;
+        let mut text = Vec::new();
+        log.read_to_end(&mut text)?;
+        out.write_all(tail(&text, lines))?;
+        out.flush()?;
+
+        let mut running = follow;
+        while running {
+            // Read what it printed before noticing it exited, too
+            running = session.is_running();
+            let mut more = Vec::new();
+            log.read_to_end(&mut more)?;
+            out.write_all(&more)?;
+            out.flush()?;
+            if running {
+                std::thread::sleep(POLL);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop a session, killing it if it hasn't stopped by the end of a grace
+    /// period, and forget it; returns whether it was still running
+    pub fn stop(&self, session: &Session) -> Result<bool> {
+        let running = session.is_running();
+        if running {
+            terminate(session.pid);
+        }
+
+        for path in [self.pid_path(session.id), self.log_path(session.id)] {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(e).with_context(|| format!("Failed to remove {}", path.display()));
+                }
+                _ => {}
+            }
+        }
+        Ok(running)
+    }
+}
+
+/// The last `lines` lines of `text`
+pub fn tail(text: &[u8], lines: usize) -> &[u8] {
+    if lines == 0 {
+        return &[];
+    }
+    let body = text.strip_suffix(b"\n").unwrap_or(text);
+    let start = body
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|&(_, &b)| b == b'\n')
+        .nth(lines.saturating_sub(1))
+        .map_or(0, |(i, _)| i + 1);
+    &text[start..]
+}
+
+/// The process group led by `pid`, or None for ids that would signal more
+/// than one process
+#[cfg(unix)]
+fn group(pid: u32) -> Option<libc::pid_t> {
+    libc::pid_t::try_from(pid).ok().filter(|&pid| pid > 1)
+}
+
+/// Whether anything in the process group led by `pid` is still running
+#[cfg(unix)]
+fn alive(pid: u32) -> bool {
+    let Some(pid) = group(pid) else {
+        return false;
+    };
+    // SAFETY: waitpid only reaps make if it is our own child that has exited
+    // (when it was started by this process), and kill with signal 0 only
+    // checks that the group exists
+    unsafe {
+        let mut status = 0;
+        libc::waitpid(pid, &mut status, libc::WNOHANG);
+        libc::kill(-pid, 0) == 0
+    }
+}
+
+#[cfg(not(unix))]
+fn alive(_pid: u32) -> bool {
+    false
+}
+
+/// Ask the process group led by `pid` to stop, and kill what is left of it
+/// after the grace period
+#[cfg(unix)]
+fn terminate(pid: u32) {
+    let Some(group_id) = group(pid) else {
+        return;
+    };
+    // SAFETY: kill only sends a signal, to the group the session started
+    unsafe {
+        libc::kill(-group_id, libc::SIGTERM);
+    }
+    let asked = Instant::now();
+    while asked.elapsed() < GRACE {
+        if !alive(pid) {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    // SAFETY: as above
+    unsafe {
+        libc::kill(-group_id, libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn terminate(_pid: u32) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn session(id: u32, target: &str) -> Session {
+        Session {
+            id,
+            target: target.to_string(),
+            command: format!("make {}", target),
+            dir: PathBuf::from("/project"),
+            pid: 0,
+            started_at: 0,
+        }
+    }
+
+    fn save(sessions: &Sessions, session: &Session) {
+        fs::create_dir_all(&sessions.dir).unwrap();
+        fs::write(
+            sessions.pid_path(session.id),
+            serde_json::to_string(session).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_tail() {
+        assert_eq!(tail(b"a\nb\nc\n", 2), b"b\nc\n");
+        assert_eq!(tail(b"a\nb\nc", 2), b"b\nc");
+        assert_eq!(tail(b"a\nb\n", 5), b"a\nb\n");
+        assert_eq!(tail(b"a\nb\n", 0), b"");
+        assert_eq!(tail(b"", 3), b"");
+    }
+
+    #[test]
+    fn test_find_by_id_or_target() {
+        let dir = TempDir::new().unwrap();
+        let sessions = Sessions::at(dir.path().join("sessions"));
+        assert!(sessions.list().unwrap().is_empty());
+        save(&sessions, &session(2, "watch"));
+        save(&sessions, &session(1, "serve"));
+        save(&sessions, &session(3, "watch"));
+
+        let ids: Vec<u32> = sessions.list().unwrap().iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(sessions.find("3").unwrap().target, "watch");
+        assert_eq!(sessions.find("serve").unwrap().id, 1);
+
+        let err = sessions.find("watch").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Several sessions run watch: 2, 3 (pass an id)"
+        );
+        assert!(sessions.find("docs").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_start_attach_and_stop() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("Makefile"),
+            "serve:\n\t@echo listening\n\t@sleep 30\n",
+        )
+        .unwrap();
+        let sessions = Sessions::at(dir.path().join("sessions"));
+        let options = ExecuteOptions {
+            cwd: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let session = sessions.start("serve", &options).unwrap();
+        assert_eq!(session.id, 1);
+        assert_eq!(sessions.list().unwrap(), vec![session.clone()]);
+        assert!(session.is_running());
+
+        let started = Instant::now();
+        let mut out = Vec::new();
+        while out.is_empty() && started.elapsed() < Duration::from_secs(5) {
+            sessions.attach(&session, 20, false, &mut out).unwrap();
+        }
+        assert_eq!(out, b"listening\n");
+
+        assert!(sessions.stop(&session).unwrap());
+        assert!(!session.is_running());
+        assert!(sessions.list().unwrap().is_empty());
+        assert!(!sessions.log_path(session.id).exists());
+    }
+}
@@ -0,0 +1,389 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use colored::Colorize;
+use dialoguer::{Confirm, Input, Select};
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+use crate::config::{Config, Layer, Layered, Origin};
+use crate::fuzzy;
+use crate::generated::{self, Generator};
+use crate::hook::Shell;
+use crate::makefile;
+use crate::prompt::theme;
+use crate::providers;
+use crate::rank::RankMode;
+use crate::scan;
+use crate::target::Provider;
+
+/// How many directories the search for nested Makefiles enters at most, so
+/// setting up a huge tree doesn't stall
+const MAX_DIRS: usize = 2000;
+
+/// What `maki setup` finds in the project
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Project {
+    /// Providers with something to run at the top of the project
+    pub providers: Vec<Provider>,
+    /// Makefiles in subdirectories
+    pub nested_makefiles: usize,
+    /// The tool that wrote the top-level Makefile, e.g. CMake
+    pub generator: Option<Generator>,
+}
+
+impl Project {
+    /// Look at `dir` for Makefiles called one of `names`, a Procfile, and a
+    /// compose file
+    pub fn detect(dir: &Path, names: &[String]) -> Self {
+        let mut project = Self::default();
+
+        let top = makefile::find_makefiles(dir, false, names);
+        if let Some(first) = top.first() {
+            project.providers.push(Provider::Make);
+            project.generator = makefile::read_makefile(first)
+                .ok()
+                .and_then(|(content, _)| generated::detect(&content));
+        }
+        if !scan::is_too_broad(dir, dirs::home_dir().as_deref()) {
+            let all = makefile::find_makefiles_with(dir, true, names, |dirs| dirs <= MAX_DIRS);
+            project.nested_makefiles = all.len().saturating_sub(top.len());
+        }
+        for provider in [Provider::Procfile, Provider::Compose] {
+            if providers::discover(provider, dir).is_some() {
+                project.providers.push(provider);
+            }
+        }
+        project
+    }
+
+    /// Whether there is anything for the picker to show
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty() && self.nested_makefiles == 0
+    }
+
+    /// What was found, e.g. "a Makefile generated by CMake, 2 more Makefiles
+    /// in subdirectories and a Procfile"
+    pub fn describe(&self) -> String {
+        let has_make = self.providers.contains(&Provider::Make);
+        let mut parts = Vec::new();
+        if has_make {
+            parts.push(match self.generator {
+                Some(Generator::CMake) => "a Makefile generated by CMake".to_string(),
+                Some(Generator::Automake) => "a Makefile generated by automake".to_string(),
+                None => "a Makefile".to_string(),
+            });
+        }
+        if self.nested_makefiles > 0 {
+            let more = if has_make { " more" } else { "" };
+            parts.push(format!(
+                "{}{} Makefile(s) in subdirectories",
+                self.nested_makefiles, more
+            ));
+        }
+        if self.providers.contains(&Provider::Procfile) {
+            parts.push("a Procfile".to_string());
+        }
+        if self.providers.contains(&Provider::Compose) {
+            parts.push("a compose file".to_string());
+        }
+
+        match parts.split_last() {
+            None => "no Makefile, Procfile, or compose file".to_string(),
+            Some((last, [])) => last.clone(),
+            Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+        }
+    }
+}
+
+/// The answers to `maki setup`'s questions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Answers {
+    pub rank: RankMode,
+    /// The preview's highlighting theme
+    pub theme: String,
+    pub recursive: bool,
+    /// Flags for every make run, e.g. `-j8`, set as MAKEFLAGS
+    pub make_flags: String,
+}
+
+impl Answers {
+    /// The `maki.toml` settings the answers make, by dotted key
+    pub fn settings(&self) -> Vec<(String, Value)> {
+        let mut settings = vec![
+            (
+                "picker.rank".to_string(),
+                Value::try_from(self.rank).unwrap_or_else(|_| Value::from("hybrid")),
+            ),
+            ("picker.theme".to_string(), Value::from(self.theme.as_str())),
+            ("recursive".to_string(), Value::from(self.recursive)),
+        ];
+        let flags = self.make_flags.trim();
+        if !flags.is_empty() {
+            settings.push(("env.MAKEFLAGS".to_string(), Value::from(flags)));
+        }
+        settings
+    }
+}
+
+/// `table`, a config file's settings, with `settings` set and the rest kept
+pub fn apply(table: Table, settings: &[(String, Value)]) -> Table {
+    let mut layers = vec![Layer {
+        origin: Origin::Default,
+        table,
+    }];
+    for (key, value) in settings {
+        layers.push(Layer::setting(Origin::Default, key, value.clone()));
+    }
+    Layered::merge(&layers).table
+}
+
+/// Add the line loading the hook to `shell`'s startup file under `home`,
+/// unless it loads it already; the file it went into, if it was added
+pub fn install_hook(shell: Shell, home: &Path) -> Result<Option<PathBuf>> {
+    let path = home.join(shell.startup_file());
+    let content = fs::read_to_string(&path).unwrap_or_default();
+    if content.contains("maki hook") {
+        return Ok(None);
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let separator = match content.is_empty() || content.ends_with('\n') {
+        true => "",
+        false => "\n",
+    };
+    let line = format!(
+        "{}\n# maki: mk and target completion\n{}\n",
+        separator,
+        shell.install_line()
+    );
+    fs::write(&path, format!("{}{}", content, line))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(Some(path))
+}
+
+/// Ask how maki should behave in `dir` and write the answers to its
+/// `maki.toml`, returning whether there are targets to pick from
+pub fn run(dir: &Path, names: &[String]) -> Result<bool> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        bail!("maki setup asks questions, so it needs a terminal");
+    }
+    let config = Config::load(dir)?;
+    let theme = theme();
+
+    let project = Project::detect(dir, names);
+    println!("{} Found {}", "→".blue(), project.describe().bold());
+    println!();
+
+    let modes = RankMode::value_variants();
+    let items: Vec<String> = modes
+        .iter()
+        .filter_map(|mode| mode.to_possible_value())
+        .map(|value| match value.get_help() {
+            Some(help) => format!("{}  {}", value.get_name(), help.to_string().dimmed()),
+            None => value.get_name().to_string(),
+        })
+        .collect();
+    let rank = Select::with_theme(theme.as_ref())
+        .with_prompt("How should the picker order targets?")
+        .items(&items)
+        .default(
+            modes
+                .iter()
+                .position(|m| *m == config.picker.rank)
+                .unwrap_or(0),
+        )
+        .interact()?;
+
+    let themes = fuzzy::themes();
+    let highlighting = Select::with_theme(theme.as_ref())
+        .with_prompt("Theme for the recipe preview")
+        .items(&themes)
+        .default(
+            themes
+                .iter()
+                .position(|t| *t == config.picker.theme)
+                .unwrap_or(0),
+        )
+        .interact()?;
+
+    let recursive = Confirm::with_theme(theme.as_ref())
+        .with_prompt(match project.nested_makefiles {
+            0 => "Look for Makefiles in subdirectories too?".to_string(),
+            n => format!("Look for Makefiles in subdirectories too? ({} found)", n),
+        })
+        .default(config.recursive || project.nested_makefiles > 0)
+        .interact()?;
+
+    let make_flags: String = Input::with_theme(theme.as_ref())
+        .with_prompt("Flags for every make run (MAKEFLAGS, e.g. -j8), if any")
+        .default(config.env.get("MAKEFLAGS").cloned().unwrap_or_default())
+        .allow_empty(true)
+        .interact_text()?;
+
+    let answers = Answers {
+        rank: modes[rank],
+        theme: themes[highlighting].clone(),
+        recursive,
+        make_flags,
+    };
+
+    let home = dirs::home_dir();
+    let shell = std::env::var("SHELL").ok().and_then(|s| Shell::detect(&s));
+    let hook = match (shell, home.as_deref()) {
+        (Some(shell), Some(home)) => Confirm::with_theme(theme.as_ref())
+            .with_prompt(format!(
+                "Add the shell hook (mk, with target completion) to ~/{}?",
+                shell.startup_file()
+            ))
+            .default(false)
+            .interact()?
+            .then_some((shell, home)),
+        _ => None,
+    };
+
+    let path = Config::config_path(dir);
+    let existing = match path.exists() {
+        true => {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let keep = Confirm::with_theme(theme.as_ref())
+                .with_prompt(format!(
+                    "Update {}? Its other settings stay, but not its comments",
+                    path.display()
+                ))
+                .default(true)
+                .interact()?;
+            if !keep {
+                println!("{}", "Nothing written.".yellow());
+                return Ok(false);
+            }
+            toml::from_str(&content)
+                .with_context(|| format!("Invalid config file: {}", path.display()))?
+        }
+        false => Table::new(),
+    };
+
+    let settings = answers.settings();
+    let content = toml::to_string(&apply(existing, &settings))
+        .context("Failed to write the settings as TOML")?;
+    fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    println!();
+    println!("{} Wrote {}", "✓".green(), path.display());
+    for (key, value) in &settings {
+        println!("  {} = {}", key, value);
+    }
+
+    if let Some((shell, home)) = hook {
+        match install_hook(shell, home)? {
+            Some(file) => println!(
+                "{} Added the hook to {}; it takes effect in a new shell",
+                "✓".green(),
+                file.display()
+            ),
+            None => println!(
+                "{} ~/{} already loads the hook",
+                "✓".green(),
+                shell.startup_file()
+            ),
+        }
+    }
+
+    if project.is_empty() {
+        println!(
+            "{} No targets to pick from yet; add a Makefile and run maki",
+            "→".blue()
+        );
+    }
+    Ok(!project.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detect_and_describe() {
+        let dir = TempDir::new().unwrap();
+        let names = makefile::default_makefile_names();
+        assert!(Project::detect(dir.path(), &names).is_empty());
+        assert_eq!(
+            Project::default().describe(),
+            "no Makefile, Procfile, or compose file"
+        );
+
+        fs::create_dir_all(dir.path().join("api")).unwrap();
+        fs::write(dir.path().join("api/Makefile"), "serve:\n").unwrap();
+        let project = Project::detect(dir.path(), &names);
+        assert_eq!(project.describe(), "1 Makefile(s) in subdirectories");
+
+        fs::write(
+            dir.path().join("Makefile"),
+            "# CMAKE generated file: DO NOT EDIT!\nall:\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("Procfile"), "web: ./serve\n").unwrap();
+        let project = Project::detect(dir.path(), &names);
+        assert_eq!(project.providers, vec![Provider::Make, Provider::Procfile]);
+        assert_eq!(
+            project.describe(),
+            "a Makefile generated by CMake, 1 more Makefile(s) in subdirectories and a Procfile"
+        );
+    }
+
+    #[test]
+    fn test_apply_keeps_other_settings() {
+        let answers = Answers {
+            rank: RankMode::Frecency,
+            theme: "InspiredGitHub".to_string(),
+            recursive: true,
+            make_flags: " -j8 ".to_string(),
+        };
+        let existing: Table =
+            toml::from_str("single_target_shortcut = false\n[env]\nRUST_LOG = \"debug\"\n")
+                .unwrap();
+
+        let content = toml::to_string(&apply(existing, &answers.settings())).unwrap();
+        let config = Config::parse(&content).unwrap();
+        assert!(!config.single_target_shortcut);
+        assert!(config.recursive);
+        assert_eq!(config.picker.rank, RankMode::Frecency);
+        assert_eq!(config.picker.theme, "InspiredGitHub");
+        assert_eq!(config.env["RUST_LOG"], "debug");
+        assert_eq!(config.env["MAKEFLAGS"], "-j8");
+
+        let quiet = Answers {
+            make_flags: String::new(),
+            ..answers
+        };
+        assert!(
+            !quiet
+                .settings()
+                .iter()
+                .any(|(key, _)| key == "env.MAKEFLAGS")
+        );
+    }
+
+    #[test]
+    fn test_install_hook_once() {
+        let home = TempDir::new().unwrap();
+        fs::write(home.path().join(".zshrc"), "export EDITOR=vim").unwrap();
+
+        let file = install_hook(Shell::Zsh, home.path()).unwrap();
+        assert_eq!(file, Some(home.path().join(".zshrc")));
+        assert_eq!(
+            fs::read_to_string(home.path().join(".zshrc")).unwrap(),
+            "export EDITOR=vim\n\n# maki: mk and target completion\neval \"$(maki hook zsh)\"\n"
+        );
+        assert_eq!(install_hook(Shell::Zsh, home.path()).unwrap(), None);
+
+        // Fish's startup file is in a directory that may not exist yet
+        assert!(install_hook(Shell::Fish, home.path()).unwrap().is_some());
+        assert!(home.path().join(".config/fish/config.fish").exists());
+    }
+}
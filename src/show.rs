@@ -0,0 +1,280 @@
+use anyhow::Result;
+use colored::Colorize;
+use serde::Serialize;
+use std::path::Path;
+
+use crate::makefile::{self, ParseOptions, Prerequisite, VpathDirective};
+use crate::target::{Provider, Target};
+use crate::width;
+
+/// A prerequisite and how it is satisfied
+#[derive(Debug, Clone, Serialize)]
+pub struct PrerequisiteDetail {
+    pub name: String,
+    /// Listed after a `|`: made first, but never a reason to remake the
+    /// target
+    pub order_only: bool,
+    #[serde(flatten)]
+    pub resolved: Prerequisite,
+}
+
+/// Everything `maki show` knows about a target
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetDetail {
+    #[serde(flatten)]
+    pub target: Target,
+    pub prerequisites: Vec<PrerequisiteDetail>,
+    /// Search path declarations from the target's Makefile
+    pub vpath: Vec<VpathDirective>,
+}
+
+impl TargetDetail {
+    /// Re-parse the target's Makefile to resolve its prerequisites
+    pub fn build(target: &Target) -> Result<Self> {
+        if target.provider != Provider::Make {
+            return Ok(Self {
+                target: target.clone(),
+                prerequisites: Vec::new(),
+                vpath: Vec::new(),
+            });
+        }
+
+        let info = makefile::parse_makefile_info(&target.file, &ParseOptions::everything())?;
+        let base_dir = base_dir(target);
+
+        let prerequisites = target
+            .prerequisites()
+            .enumerate()
+            .map(|(i, name)| PrerequisiteDetail {
+                name: name.clone(),
+                order_only: i >= target.deps.len(),
+                resolved: makefile::resolve_prerequisite(name, &info, base_dir),
+            })
+            .collect();
+
+        Ok(Self {
+            target: target.clone(),
+            prerequisites,
+            vpath: info.vpath,
+        })
+    }
+}
+
+/// Directory prerequisites and search paths are resolved against
+fn base_dir(target: &Target) -> &Path {
+    target
+        .file
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+}
+
+/// Print the detail view for a target
+pub fn render(detail: &TargetDetail) {
+    let target = &detail.target;
+    let base_dir = base_dir(target);
+
+    println!("{}", target.name.green().bold());
+    if let Some(ref label) = target.label {
+        println!("  {}", format!("shown as {}", label).dimmed());
+    }
+    if let Some(ref desc) = target.description {
+        println!("  {}", desc);
+    }
+    if let Some(ref note) = target.note {
+        println!("  {}", format!("✎ {}", note).yellow());
+    }
+    println!();
+    println!(
+        "  {} {}:{}",
+        "defined in".dimmed(),
+        target.file.display(),
+        target.line
+    );
+    if let Some(ref makefile) = target.included_from {
+        println!("  {} {}", "included by".dimmed(), makefile.display());
+    }
+    if target.provider != Provider::Make {
+        println!("  {} {}", "provider".dimmed(), target.provider.name());
+    }
+    if target.is_phony {
+        println!("  {} declared in .PHONY", "phony".dimmed());
+    }
+    if !target.tags.is_empty() {
+        println!("  {} {}", "tags".dimmed(), target.tag_chips().magenta());
+    }
+    if target.needs_sudo {
+        println!(
+            "  {} {}",
+            "runs".dimmed(),
+            "sudo (asks for a password)".yellow()
+        );
+    }
+    if target.serial {
+        println!(
+            "  {} {}",
+            "runs".dimmed(),
+            "on its own (never with --parallel)".yellow()
+        );
+    }
+    if let Some(ref probe) = target.ready {
+        println!("  {} {} answers", "ready when".dimmed(), probe);
+    }
+
+    if !detail.prerequisites.is_empty() {
+        println!();
+        println!("  {}", "prerequisites".bold());
+        let width = detail
+            .prerequisites
+            .iter()
+            .map(|p| width::columns(&p.name))
+            .max()
+            .unwrap_or(0);
+
+        for prereq in &detail.prerequisites {
+            let name = width::pad(&prereq.name, width);
+            let kind = match &prereq.resolved {
+                Prerequisite::Target => "target".dimmed().to_string(),
+                Prerequisite::PatternRule(rule) => format!("{} {}", "pattern rule".dimmed(), rule),
+                Prerequisite::File(path) => {
+                    let shown = path.strip_prefix(base_dir).unwrap_or(path);
+                    format!("{} {}", "file".dimmed(), shown.display())
+                }
+                Prerequisite::Missing => "missing".red().to_string(),
+            };
+            match prereq.order_only {
+                true => println!("    {}  {} {}", name.cyan(), kind, "(order-only)".dimmed()),
+                false => println!("    {}  {}", name.cyan(), kind),
+            }
+        }
+    }
+
+    if !target.required_vars.is_empty() {
+        println!();
+        println!("  {}", "required variables".bold());
+        for var in &target.required_vars {
+            let mut line = format!("    {}", var.name.yellow());
+            if let Some(ref hint) = var.hint {
+                line.push_str(&format!("  {}", hint.dimmed()));
+            }
+            if let Some(ref default) = var.default {
+                line.push_str(&format!("  {}", format!("(default {})", default).dimmed()));
+            }
+            println!("{}", line);
+        }
+    }
+
+    if !target.exported.is_empty() {
+        println!();
+        println!("  {}", "exported variables".bold());
+        println!("    {}", target.exported.join(" ").yellow());
+    }
+
+    if !target.target_vars.is_empty() {
+        println!();
+        println!("  {}", "target-specific variables".bold());
+        for var in &target.target_vars {
+            println!(
+                "    {}  {}",
+                var.assignment().yellow(),
+                format!("(line {})", var.line).dimmed()
+            );
+        }
+    }
+
+    if !detail.vpath.is_empty() {
+        println!();
+        println!("  {}", "search paths".bold());
+        for directive in &detail.vpath {
+            let label = match &directive.pattern {
+                Some(pattern) => format!("vpath {}", pattern),
+                None => "VPATH".to_string(),
+            };
+            println!(
+                "    {}  {}  {}",
+                label.cyan(),
+                directive.dirs.join(" "),
+                format!("(line {})", directive.line).dimmed()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_build_resolves_prerequisites_through_vpath() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/main.c"), "int main() {}").unwrap();
+
+        let makefile_path = dir.path().join("Makefile");
+        fs::write(
+            &makefile_path,
+            "vpath %.c src\n\napp: compile main.c main.o missing.h | out\n\tcc -o out/app main.o\n\ncompile:\n\ttrue\n\nout:\n\tmkdir out\n\n%.o: %.c\n\tcc -c $<\n",
+        )
+        .unwrap();
+
+        let targets = makefile::parse_makefile(&makefile_path, &ParseOptions::default()).unwrap();
+        let app = targets.iter().find(|t| t.name == "app").unwrap();
+        let detail = TargetDetail::build(app).unwrap();
+
+        let kinds: Vec<&Prerequisite> = detail.prerequisites.iter().map(|p| &p.resolved).collect();
+        assert_eq!(kinds[0], &Prerequisite::Target);
+        assert_eq!(kinds[1], &Prerequisite::File(dir.path().join("src").join("main.c")));
+        assert_eq!(kinds[2], &Prerequisite::PatternRule("%.o".to_string()));
+        assert_eq!(kinds[3], &Prerequisite::Missing);
+        assert_eq!(kinds[4], &Prerequisite::Target);
+        let order_only: Vec<bool> = detail.prerequisites.iter().map(|p| p.order_only).collect();
+        assert_eq!(order_only, vec![false, false, false, false, true]);
+        assert_eq!(detail.vpath.len(), 1);
+    }
+
+    #[test]
+    fn test_detail_json_shape() {
+        let mut target = Target::new("app".to_string(), None, "Makefile".into(), 1);
+        target.deps = vec!["build".to_string()];
+
+        let detail = TargetDetail {
+            target,
+            prerequisites: vec![PrerequisiteDetail {
+                name: "build".to_string(),
+                order_only: false,
+                resolved: Prerequisite::Target,
+            }],
+            vpath: vec![],
+        };
+
+        let json = serde_json::to_value(&detail).unwrap();
+        assert_eq!(json["name"], "app");
+        assert_eq!(json["prerequisites"][0]["name"], "build");
+        assert_eq!(json["prerequisites"][0]["kind"], "target");
+    }
+
+    #[test]
+    fn test_detail_includes_target_specific_variables() {
+        let dir = TempDir::new().unwrap();
+        let makefile_path = dir.path().join("Makefile");
+        fs::write(
+            &makefile_path,
+            "build:
+	$(CC) main.c
+
+build: CC := clang
+",
+        )
+        .unwrap();
+
+        let targets = makefile::parse_makefile(&makefile_path, &ParseOptions::default()).unwrap();
+        let detail = TargetDetail::build(&targets[0]).unwrap();
+
+        let json = serde_json::to_value(&detail).unwrap();
+        assert_eq!(json["target_vars"][0]["name"], "CC");
+        assert_eq!(json["target_vars"][0]["value"], "clang");
+        assert_eq!(detail.target.target_vars[0].assignment(), "CC := clang");
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cache::Cache;
+use crate::quote;
+
+/// Terminal multiplexers that maki can open split panes in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplexer {
+    Tmux,
+    Zellij,
+}
+
+impl Multiplexer {
+    /// Detect the multiplexer maki is currently running inside of
+    pub fn detect() -> Option<Self> {
+        Self::from_env(
+            std::env::var_os("TMUX").is_some(),
+            std::env::var_os("ZELLIJ").is_some(),
+        )
+    }
+
+    fn from_env(tmux: bool, zellij: bool) -> Option<Self> {
+        if tmux {
+            Some(Self::Tmux)
+        } else if zellij {
+            Some(Self::Zellij)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Tmux => "tmux",
+            Self::Zellij => "zellij",
+        }
+    }
+}
+
+/// Completion state of a target launched in a split pane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitStatus {
+    Running,
+    Finished(i32),
+}
+
+/// A target that was launched in a split pane
+#[derive(Debug, Clone)]
+pub struct SplitRun {
+    pub target: String,
+    /// File the pane writes the exit code to once the target finishes
+    pub status_file: PathBuf,
+}
+
+impl SplitRun {
+    /// Read the current status from the status file
+    pub fn status(&self) -> SplitStatus {
+        fs::read_to_string(&self.status_file)
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .map(SplitStatus::Finished)
+            .unwrap_or(SplitStatus::Running)
+    }
+
+    /// Short status label for headers and summaries
+    pub fn label(&self) -> String {
+        match self.status() {
+            SplitStatus::Running => format!("{} …", self.target),
+            SplitStatus::Finished(0) => format!("{} ✓", self.target),
+            SplitStatus::Finished(code) => format!("{} ✗ ({})", self.target, code),
+        }
+    }
+}
+
+/// Launch a make command in a new pane, returning a handle to track it
+pub fn launch(
+    mux: Multiplexer,
+    target: &str,
+    command: &[String],
+    cwd: &Path,
+) -> Result<SplitRun> {
+    let status_dir = Cache::cache_dir()
+        .context("Could not determine cache directory")?
+        .join("splits");
+    fs::create_dir_all(&status_dir).with_context(|| {
+        format!("Failed to create status directory: {}", status_dir.display())
+    })?;
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let status_file = status_dir.join(format!("{}-{}.status", std::process::id(), nanos));
+    let script = pane_script(command, &status_file);
+
+    let mut cmd = match mux {
+        Multiplexer::Tmux => {
+            let mut c = Command::new("tmux");
+            c.args(["split-window", "-d", "-h", "-c"])
+                .arg(cwd)
+                .args(["sh", "-c", &script]);
+            c
+        }
+        Multiplexer::Zellij => {
+            let mut c = Command::new("zellij");
+            c.args(["run", "--name", &format!("maki: {}", target), "--cwd"])
+                .arg(cwd)
+                .args(["--", "sh", "-c", &script]);
+            c
+        }
+    };
+
+    let status = cmd
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .status()
+        .with_context(|| format!("Failed to open a {} pane", mux.name()))?;
+
+    if !status.success() {
+        anyhow::bail!("{} refused to open a new pane", mux.name());
+    }
+
+    println!(
+        "{} {} in a new {} pane",
+        "Launched:".green(),
+        target.bold(),
+        mux.name()
+    );
+
+    Ok(SplitRun {
+        target: target.to_string(),
+        status_file,
+    })
+}
+
+/// Build the shell script a pane runs: the command, then record its exit code
+fn pane_script(command: &[String], status_file: &Path) -> String {
+    format!(
+        "{}; code=$?; echo $code > {}; printf '\\n[maki] exited with %s, press Enter to close' $code; read _",
+        quote::command(command),
+        quote::word(&status_file.display().to_string())
+    )
+}
+
+/// Header line summarising the panes launched in this session
+pub fn status_header(runs: &[SplitRun]) -> Option<String> {
+    if runs.is_empty() {
+        return None;
+    }
+
+    let labels: Vec<String> = runs.iter().map(|r| r.label()).collect();
+    Some(format!("Panes: {}", labels.join("  ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detect_prefers_tmux() {
+        assert_eq!(Multiplexer::from_env(true, true), Some(Multiplexer::Tmux));
+        assert_eq!(Multiplexer::from_env(false, true), Some(Multiplexer::Zellij));
+        assert_eq!(Multiplexer::from_env(false, false), None);
+    }
+
+    #[test]
+    fn test_pane_script_records_exit_code() {
+        let cmd = vec!["make".to_string(), "build".to_string()];
+        let script = pane_script(&cmd, Path::new("/tmp/it's.status"));
+
+        assert!(script.starts_with("make build; code=$?; echo $code > '/tmp/it'\\''s.status'"));
+    }
+
+    #[test]
+    fn test_split_run_status() {
+        let dir = TempDir::new().unwrap();
+        let run = SplitRun {
+            target: "build".to_string(),
+            status_file: dir.path().join("run.status"),
+        };
+
+        assert_eq!(run.status(), SplitStatus::Running);
+        assert_eq!(run.label(), "build …");
+
+        fs::write(&run.status_file, "0\n").unwrap();
+        assert_eq!(run.status(), SplitStatus::Finished(0));
+        assert_eq!(run.label(), "build ✓");
+
+        fs::write(&run.status_file, "2\n").unwrap();
+        assert_eq!(run.label(), "build ✗ (2)");
+    }
+
+    #[test]
+    fn test_status_header() {
+        assert_eq!(status_header(&[]), None);
+
+        let run = SplitRun {
+            target: "test".to_string(),
+            status_file: PathBuf::from("/nonexistent/status"),
+        };
+        assert_eq!(status_header(&[run]), Some("Panes: test …".to_string()));
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The directory a project's entries are filed under, the same however it
+/// was spelled
+pub fn project_key(dir: &Path) -> PathBuf {
+    fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf())
+}
+
+/// What maki keeps per user and per project in a JSON file of its data
+/// directory, like the queue and the notes
+pub trait Store: Serialize + DeserializeOwned + Default {
+    /// The file's name in `<data dir>/maki`
+    const FILENAME: &'static str;
+    /// What the file holds, for errors, e.g. `queue`
+    const WHAT: &'static str;
+
+    /// Where the file is kept: `<data dir>/maki/<FILENAME>`
+    fn file_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|p| p.join("maki").join(Self::FILENAME))
+    }
+
+    /// Load the file, empty when there is none
+    fn load() -> Result<Self> {
+        match Self::file_path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = Self::file_path().context("Could not determine data directory")?;
+        self.save_to(&path)
+    }
+
+    fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {} file: {}", Self::WHAT, path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Invalid {} file: {}", Self::WHAT, path.display()))
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| {
+                format!(
+                    "Failed to create {} directory: {}",
+                    Self::WHAT,
+                    dir.display()
+                )
+            })?;
+        }
+        let content = serde_json::to_string_pretty(self)
+            .with_context(|| format!("Failed to serialize {}", Self::WHAT))?;
+        fs::write(path, content)
+            .with_context(|| format!("Failed to write {} file: {}", Self::WHAT, path.display()))
+    }
+}
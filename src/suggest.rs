@@ -0,0 +1,260 @@
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::glob::glob_matches;
+use crate::makefile;
+use crate::target::{Provider, Target};
+
+/// A path the target references exactly, or a glob that matches it
+const EXACT_SCORE: u32 = 3;
+/// A directory the changed file is in
+const DIRECTORY_SCORE: u32 = 2;
+/// A glob for the changed file's extension, e.g. `$(PROTO_DIR)/*.proto`
+const EXTENSION_SCORE: u32 = 1;
+
+/// A target worth running given the working tree's changes
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    #[serde(flatten)]
+    pub target: Target,
+    /// Higher means more of the changes are relevant to the target
+    pub score: u32,
+    /// Why the target was suggested, e.g. `matches proto/*.proto`
+    pub reasons: Vec<String>,
+}
+
+/// Files with uncommitted changes (including untracked ones) in the git
+/// repository containing `dir`, as absolute paths
+pub fn changed_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let root = git(dir, &["rev-parse", "--show-toplevel"])?;
+    let root = PathBuf::from(root.trim());
+    let status = git(dir, &["status", "--porcelain", "--untracked-files=all"])?;
+
+    Ok(parse_porcelain(&status)
+        .into_iter()
+        .map(|path| root.join(path))
+        .collect())
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Paths from `git status --porcelain`, relative to the repository root
+fn parse_porcelain(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter(|line| line.len() > 3)
+        .map(|line| {
+            let path = &line[3..];
+            // Renames are reported as `old -> new`
+            let path = path.rsplit(" -> ").next().unwrap_or(path);
+            path.trim_matches('"').to_string()
+        })
+        .collect()
+}
+
+/// Rank `targets` by how much their prerequisites and recipes reference the
+/// `changed` files, most relevant first; unrelated targets are left out
+pub fn suggest(targets: &[Target], changed: &[PathBuf]) -> Vec<Suggestion> {
+    let mut contents: HashMap<&Path, Option<String>> = HashMap::new();
+    let mut suggestions = Vec::new();
+
+    // Only Makefile rules have recipes to inspect; special targets like
+    // .PHONY and pattern rules aren't run directly
+    let runnable = targets
+        .iter()
+        .filter(|t| t.provider == Provider::Make && !t.is_pattern() && !t.name.starts_with('.'));
+
+    for target in runnable {
+        let content = contents.entry(target.file.as_path()).or_insert_with(|| {
+            makefile::read_makefile(&target.file)
+                .ok()
+                .map(|(text, _)| text)
+        });
+        let Some(content) = content else {
+            continue;
+        };
+
+        let lines: Vec<&str> = content.lines().collect();
+        let recipe = makefile::recipe_lines(&lines, target.line.saturating_sub(1));
+        let mut words: Vec<String> = target.prerequisites().cloned().collect();
+        words.extend(tokens(&recipe.join("\n")));
+
+        let base_dir = base_dir(&target.file);
+        let relative: Vec<String> = changed
+            .iter()
+            .filter_map(|path| path.strip_prefix(&base_dir).ok())
+            .map(|path| path.to_string_lossy().replace('\\', "/"))
+            .collect();
+
+        let (score, reasons) = score(&words, &relative);
+        if score > 0 {
+            suggestions.push(Suggestion {
+                target: target.clone(),
+                score,
+                reasons,
+            });
+        }
+    }
+
+    suggestions.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| a.target.name.cmp(&b.target.name))
+    });
+    suggestions
+}
+
+/// The Makefile's directory, canonicalized so it compares with git's paths
+fn base_dir(file: &Path) -> PathBuf {
+    let dir = file
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf())
+}
+
+/// Split recipe text into the words that might name files
+fn tokens(text: &str) -> Vec<String> {
+    text.split(|c: char| c.is_whitespace() || "\"'(),;=|&<>`{}".contains(c))
+        .map(|word| word.trim_start_matches("./"))
+        .filter(|word| !word.is_empty() && *word != "$")
+        .map(str::to_string)
+        .collect()
+}
+
+/// Score the words of a target against changed paths (relative to its Makefile)
+///
+/// Each changed path counts once, with its strongest match.
+fn score(words: &[String], changed: &[String]) -> (u32, Vec<String>) {
+    let mut total = 0;
+    let mut reasons: Vec<String> = Vec::new();
+
+    for path in changed {
+        let best = words
+            .iter()
+            .filter_map(|word| match_word(word, path))
+            .max_by_key(|(score, _)| *score);
+
+        if let Some((score, reason)) = best {
+            total += score;
+            if !reasons.contains(&reason) {
+                reasons.push(reason);
+            }
+        }
+    }
+
+    (total, reasons)
+}
+
+fn match_word(word: &str, path: &str) -> Option<(u32, String)> {
+    if word == path {
+        return Some((EXACT_SCORE, format!("references {}", path)));
+    }
+
+    if word.contains('*') || word.contains('%') {
+        if glob_matches(word, path) {
+            return Some((EXACT_SCORE, format!("matches {}", word)));
+        }
+        let extension = Path::new(path).extension()?.to_string_lossy();
+        let ext_glob = word.ends_with(&format!("*.{}", extension))
+            || word.ends_with(&format!("%.{}", extension));
+        return ext_glob.then(|| (EXTENSION_SCORE, format!("matches {}", word)));
+    }
+
+    let dir = word.trim_end_matches('/');
+    if !dir.is_empty() && dir != "." && path.starts_with(&format!("{}/", dir)) {
+        return Some((DIRECTORY_SCORE, format!("uses {}/", dir)));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_porcelain() {
+        let output = " M src/main.rs\n?? proto/api.proto\nR  old.txt -> docs/new.txt\n";
+        assert_eq!(
+            parse_porcelain(output),
+            vec!["src/main.rs", "proto/api.proto", "docs/new.txt"]
+        );
+    }
+
+    #[test]
+    fn test_match_word() {
+        assert_eq!(
+            match_word("docs/index.md", "docs/index.md").unwrap().0,
+            EXACT_SCORE
+        );
+        assert_eq!(
+            match_word("docs/", "docs/index.md").unwrap().0,
+            DIRECTORY_SCORE
+        );
+        assert_eq!(
+            match_word("/*.proto", "proto/api.proto").unwrap().0,
+            EXTENSION_SCORE
+        );
+        assert!(match_word("docs", "docsite/index.md").is_none());
+        assert!(match_word("*.go", "proto/api.proto").is_none());
+    }
+
+    #[test]
+    fn test_suggest_ranks_targets() {
+        let dir = TempDir::new().unwrap();
+        let makefile = dir.path().join("Makefile");
+        fs::write(
+            &makefile,
+            "\
+proto-gen:
+\tprotoc --go_out=gen $(wildcard proto/*.proto)
+
+lint: proto-gen
+\tgolangci-lint run $(PROTO_DIR)/*.proto ./...
+
+docs: docs/index.md
+\tmkdocs build
+
+test:
+\tgo test ./...
+",
+        )
+        .unwrap();
+
+        let targets =
+            crate::makefile::parse_makefile(&makefile, &crate::makefile::ParseOptions::default())
+                .unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let changed = vec![root.join("proto/api.proto"), root.join("docs/index.md")];
+
+        let suggestions = suggest(&targets, &changed);
+        let names: Vec<&str> = suggestions.iter().map(|s| s.target.name.as_str()).collect();
+
+        assert_eq!(names, vec!["docs", "proto-gen", "lint"]);
+        assert_eq!(suggestions[1].reasons, vec!["matches proto/*.proto"]);
+        assert_eq!(suggestions[2].score, EXTENSION_SCORE);
+    }
+}
@@ -1,5 +1,9 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::artifact::ArtifactPolicy;
+use crate::guard::Guard;
+use crate::webhook::Webhook;
 
 /// Represents a required variable for a Makefile target
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -8,6 +12,80 @@ pub struct RequiredVar {
     pub name: String,
     /// Optional hint for possible values (e.g., "patch|minor|major")
     pub hint: Option<String>,
+    /// Value the Makefile already assigns, offered as the prompt default
+    #[serde(default)]
+    pub default: Option<String>,
+    /// Set for a secret (`@secret`), whose value is typed without echo and
+    /// masked wherever maki shows it
+    #[serde(default)]
+    pub secret: Option<Secret>,
+}
+
+/// How the value of a secret variable is found
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Secret {
+    /// A command printing the value, e.g. `pass show deploy/token`; without
+    /// one the value is asked for
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+impl RequiredVar {
+    pub fn is_secret(&self) -> bool {
+        self.secret.is_some()
+    }
+}
+
+/// A target-specific variable assignment (e.g., `build: CC := clang`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TargetVar {
+    /// The name of the variable (e.g., "CC")
+    pub name: String,
+    /// The assignment operator (e.g., ":=", "+=")
+    pub op: String,
+    /// The assigned value, unexpanded
+    pub value: String,
+    /// The line number where the assignment is made
+    pub line: usize,
+}
+
+impl TargetVar {
+    /// The assignment as written, e.g. "CC := clang"
+    pub fn assignment(&self) -> String {
+        format!("{} {} {}", self.name, self.op, self.value)
+    }
+}
+
+/// Where a target comes from, which decides how it is run
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Provider {
+    /// A Makefile rule, run with `make`
+    #[default]
+    Make,
+    /// A `Procfile` process, run with overmind or foreman
+    Procfile,
+    /// A docker-compose service, run with `docker compose up`
+    Compose,
+    /// A common task inferred from a project manifest (Cargo.toml,
+    /// package.json, go.mod, pyproject.toml) when nothing else is found
+    Inferred,
+}
+
+impl Provider {
+    /// Every provider, in the default priority order
+    pub const ALL: [Provider; 4] = [Self::Make, Self::Procfile, Self::Compose, Self::Inferred];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Make => "make",
+            Self::Procfile => "procfile",
+            Self::Compose => "compose",
+            Self::Inferred => "inferred",
+        }
+    }
 }
 
 /// Represents a single Makefile target with its metadata
@@ -21,9 +99,84 @@ pub struct Target {
     pub file: PathBuf,
     /// The line number where the target is defined
     pub line: usize,
+    /// The Makefile that includes `file`, when the target comes from an
+    /// included fragment
+    #[serde(default)]
+    pub included_from: Option<PathBuf>,
     /// Required variables that must be provided (e.g., V=patch|minor|major)
     #[serde(default)]
     pub required_vars: Vec<RequiredVar>,
+    /// Prerequisites listed after the colon (e.g., `deploy: build test`)
+    #[serde(default)]
+    pub deps: Vec<String>,
+    /// Order-only prerequisites, after a `|` (e.g., `build: src | out`):
+    /// made first, but never a reason to remake the target
+    #[serde(default)]
+    pub order_only: Vec<String>,
+    /// Target-specific variable assignments for this target
+    #[serde(default)]
+    pub target_vars: Vec<TargetVar>,
+    /// Variables the recipe uses that the Makefile exports to its
+    /// environment (`export TOKEN`), which the environment may already set
+    #[serde(default)]
+    pub exported: Vec<String>,
+    /// Where the target comes from
+    #[serde(default)]
+    pub provider: Provider,
+    /// The project root it was found in, when aggregating several roots
+    #[serde(default)]
+    pub root: Option<PathBuf>,
+    /// A Makefile rule with no recipe, which only runs its prerequisites
+    /// (e.g., `all: build test`)
+    #[serde(default)]
+    pub aggregate: bool,
+    /// Declared with `.PHONY`, so it names a command rather than a file and
+    /// always runs
+    #[serde(default)]
+    pub is_phony: bool,
+    /// A bookkeeping rule of a CMake or automake generated Makefile, hidden
+    /// unless `--generated` is given
+    #[serde(default)]
+    pub boilerplate: bool,
+    /// The recipe runs `sudo`, so running it asks for a password
+    #[serde(default)]
+    pub needs_sudo: bool,
+    /// Must not run alongside other targets, as the Makefile says with
+    /// `.NOTPARALLEL` or a `@serial` comment
+    #[serde(default)]
+    pub serial: bool,
+    /// Left out of lists and the picker unless `--all`, as its `@hidden`
+    /// comment asks
+    #[serde(default)]
+    pub hidden: bool,
+    /// A `help` rule that prints the Makefile's own `##` comments (an awk or
+    /// grep over `$(MAKEFILE_LIST)`), which maki lists already; left out
+    /// like a hidden target, and run with `maki help-target`
+    #[serde(default)]
+    pub prints_help: bool,
+    /// What the picker calls it, from an `@name` comment
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Tags grouping it across name prefixes, from `@tags docker,release`
+    /// comments
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The user's own note on the target (`maki note`)
+    #[serde(default)]
+    pub note: Option<String>,
+    /// The probe that answers once it is up (`@ready`), e.g.
+    /// `http://localhost:8080/health`, for `maki run --wait-ready`
+    #[serde(default)]
+    pub ready: Option<String>,
+    /// Files to keep from its runs, from the config
+    #[serde(skip)]
+    pub artifacts: ArtifactPolicy,
+    /// Where to post summaries of its runs, from the config
+    #[serde(skip)]
+    pub webhooks: Vec<Webhook>,
+    /// Rules on when it should run, from the config
+    #[serde(skip)]
+    pub guards: Vec<Guard>,
 }
 
 impl Target {
@@ -35,7 +188,28 @@ impl Target {
             description,
             file,
             line,
+            included_from: None,
             required_vars: Vec::new(),
+            deps: Vec::new(),
+            order_only: Vec::new(),
+            target_vars: Vec::new(),
+            exported: Vec::new(),
+            provider: Provider::Make,
+            root: None,
+            aggregate: false,
+            is_phony: false,
+            boilerplate: false,
+            needs_sudo: false,
+            serial: false,
+            hidden: false,
+            prints_help: false,
+            label: None,
+            tags: Vec::new(),
+            note: None,
+            ready: None,
+            artifacts: ArtifactPolicy::default(),
+            webhooks: Vec::new(),
+            guards: Vec::new(),
         }
     }
 
@@ -52,7 +226,28 @@ impl Target {
             description,
             file,
             line,
+            included_from: None,
             required_vars,
+            deps: Vec::new(),
+            order_only: Vec::new(),
+            target_vars: Vec::new(),
+            exported: Vec::new(),
+            provider: Provider::Make,
+            root: None,
+            aggregate: false,
+            is_phony: false,
+            boilerplate: false,
+            needs_sudo: false,
+            serial: false,
+            hidden: false,
+            prints_help: false,
+            label: None,
+            tags: Vec::new(),
+            note: None,
+            ready: None,
+            artifacts: ArtifactPolicy::default(),
+            webhooks: Vec::new(),
+            guards: Vec::new(),
         }
     }
 
@@ -61,15 +256,103 @@ impl Target {
         !self.required_vars.is_empty()
     }
 
+    /// Every prerequisite, the normal ones then the order-only ones, in the
+    /// order make visits them
+    pub fn prerequisites(&self) -> impl Iterator<Item = &String> {
+        self.deps.iter().chain(&self.order_only)
+    }
+
+    /// The prerequisites as written after the colon, e.g. `src | out`
+    pub fn prerequisite_list(&self) -> String {
+        match (self.deps.is_empty(), self.order_only.is_empty()) {
+            (_, true) => self.deps.join(" "),
+            (true, false) => format!("| {}", self.order_only.join(" ")),
+            (false, false) => format!("{} | {}", self.deps.join(" "), self.order_only.join(" ")),
+        }
+    }
+
+    /// Whether one of its `@tags` is `tag`, in any case
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    /// Its tags as the list and picker show them, e.g. `#docker #release`
+    pub fn tag_chips(&self) -> String {
+        let chips: Vec<String> = self.tags.iter().map(|t| format!("#{}", t)).collect();
+        chips.join(" ")
+    }
+
+    /// The names of its secret variables (`@secret`)
+    pub fn secret_names(&self) -> Vec<String> {
+        self.required_vars
+            .iter()
+            .filter(|v| v.is_secret())
+            .map(|v| v.name.clone())
+            .collect()
+    }
+
     /// Returns a display string for the fuzzy finder
     pub fn display_name(&self) -> String {
-        self.name.clone()
+        let mut tags = Vec::new();
+        if let Some(label) = self.root_label() {
+            tags.push(label);
+        }
+        if self.provider != Provider::Make {
+            tags.push(self.provider.name().to_string());
+        }
+
+        if tags.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} [{}]", self.name, tags.join(", "))
+        }
     }
 
-    /// Check if this is a private target (starts with underscore)
-    #[allow(dead_code)]
+    /// The name the picker shows: its `@name` label followed by the target,
+    /// or else `display_name`
+    pub fn picker_name(&self) -> String {
+        let display = self.display_name();
+        match self.label {
+            Some(ref label) => format!("{} ({})", label, display),
+            None => display,
+        }
+    }
+
+    /// The Makefile make reads the target from: the one that includes its
+    /// file, or else the file itself
+    pub fn makefile(&self) -> &Path {
+        self.included_from.as_deref().unwrap_or(&self.file)
+    }
+
+    /// Short name of the project root, e.g. `api` for `~/src/api`
+    pub fn root_label(&self) -> Option<String> {
+        let root = self.root.as_ref()?;
+        let label = root
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| root.display().to_string());
+        Some(label)
+    }
+
+    /// Check if this is a private target (starts with underscore, is marked
+    /// `@hidden`, or is a self-documenting `help` rule)
     pub fn is_private(&self) -> bool {
-        self.name.starts_with('_')
+        self.name.starts_with('_') || self.hidden || self.prints_help
+    }
+
+    /// Check if this is a pattern rule (e.g., %.o)
+    pub fn is_pattern(&self) -> bool {
+        self.name.contains('%')
+    }
+}
+
+#[cfg(test)]
+impl Target {
+    /// A target of `Makefile` with prerequisites `deps`, for tests
+    pub(crate) fn with_deps(name: &str, deps: &[&str]) -> Self {
+        let mut target = Self::new(name.to_string(), None, PathBuf::from("Makefile"), 1);
+        target.deps = deps.iter().map(|d| d.to_string()).collect();
+        target
     }
 }
 
@@ -108,6 +391,8 @@ mod tests {
             RequiredVar {
                 name: "V".to_string(),
                 hint: Some("patch|minor|major".to_string()),
+                default: None,
+                secret: None,
             },
         ];
         let target = Target::with_required_vars(
@@ -133,6 +418,23 @@ mod tests {
 
         assert!(private_target.is_private());
         assert!(!public_target.is_private());
+
+        let mut hidden_target = public_target.clone();
+        hidden_target.hidden = true;
+        assert!(hidden_target.is_private());
+
+        let mut help_target = public_target.clone();
+        help_target.prints_help = true;
+        assert!(help_target.is_private());
+    }
+
+    #[test]
+    fn test_is_pattern() {
+        let pattern = Target::new("%.o".to_string(), None, PathBuf::from("Makefile"), 1);
+        let plain = Target::new("main.o".to_string(), None, PathBuf::from("Makefile"), 1);
+
+        assert!(pattern.is_pattern());
+        assert!(!plain.is_pattern());
     }
 
     #[test]
@@ -148,10 +450,53 @@ mod tests {
         assert_eq!(target.display_name(), "test");
     }
 
+    #[test]
+    fn test_display_name_shows_provider() {
+        let mut target = Target::new("web".to_string(), None, PathBuf::from("Procfile"), 1);
+        target.provider = Provider::Procfile;
+
+        assert_eq!(target.display_name(), "web [procfile]");
+
+        target.root = Some(PathBuf::from("/src/api"));
+        assert_eq!(target.display_name(), "web [api, procfile]");
+    }
+
     #[test]
     fn test_display_name_without_description() {
         let target = Target::new("clean".to_string(), None, PathBuf::from("Makefile"), 3);
 
         assert_eq!(target.display_name(), "clean");
     }
+
+    #[test]
+    fn test_picker_name_shows_label() {
+        let mut target = Target::new(
+            "deploy-prod".to_string(),
+            None,
+            PathBuf::from("Makefile"),
+            1,
+        );
+        assert_eq!(target.picker_name(), "deploy-prod");
+
+        target.label = Some("Deploy to production".to_string());
+        assert_eq!(target.picker_name(), "Deploy to production (deploy-prod)");
+        assert_eq!(target.display_name(), "deploy-prod");
+    }
+
+    #[test]
+    fn test_prerequisite_list() {
+        let mut target = Target::new("app".to_string(), None, PathBuf::from("Makefile"), 1);
+        target.deps = vec!["main.o".to_string()];
+        assert_eq!(target.prerequisite_list(), "main.o");
+
+        target.order_only = vec!["out".to_string()];
+        assert_eq!(target.prerequisite_list(), "main.o | out");
+        assert_eq!(
+            target.prerequisites().collect::<Vec<_>>(),
+            vec!["main.o", "out"]
+        );
+
+        target.deps.clear();
+        assert_eq!(target.prerequisite_list(), "| out");
+    }
 }
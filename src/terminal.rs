@@ -0,0 +1,51 @@
+use ratatui::crossterm::terminal;
+use std::io::{self, IsTerminal};
+
+/// The size used when maki isn't on a terminal
+const FALLBACK_SIZE: (u16, u16) = (80, 24);
+
+/// The size of maki's terminal in columns and rows, or 80x24 when it isn't
+/// on one
+pub fn size() -> (u16, u16) {
+    match terminal::size() {
+        Ok((cols, rows)) if cols > 0 && rows > 0 => (cols, rows),
+        _ => FALLBACK_SIZE,
+    }
+}
+
+/// maki's terminal taken out of line mode, so each key is read as it is
+/// typed, Ctrl-C included; put back as it was when dropped
+pub struct RawMode(());
+
+impl RawMode {
+    /// Fails when stdin isn't a terminal
+    pub fn enable() -> io::Result<Self> {
+        if !io::stdin().is_terminal() {
+            return Err(io::Error::other("stdin is not a terminal"));
+        }
+        terminal::enable_raw_mode()?;
+        Ok(Self(()))
+    }
+
+    /// Turn `\n` back into a new line on output, for what maki prints
+    /// itself (statuses, the heartbeat) while the keys go elsewhere
+    #[cfg(unix)]
+    pub fn with_newlines(self) -> Self {
+        // SAFETY: tcgetattr only writes the termios it is given, and
+        // tcsetattr only reads it
+        unsafe {
+            let mut raw: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut raw) == 0 {
+                raw.c_oflag |= libc::OPOST;
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw);
+            }
+        }
+        self
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
@@ -0,0 +1,246 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::heartbeat::format_elapsed;
+
+/// What maki spent its time on before it could show or run anything, and how
+/// well the cache served it, for `--timings`
+static TIMINGS: Mutex<Timings> = Mutex::new(Timings::new());
+
+/// Whether the timings are to be reported, and whether they already were
+static WANTED: AtomicBool = AtomicBool::new(false);
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// A step of maki's startup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Reading `maki.toml` and the other settings layers
+    Config,
+    /// Looking for Makefiles and the other providers' files
+    Discovery,
+    CacheLoad,
+    /// Parsing the Makefiles the cache didn't have, and reading those it did
+    Parse,
+    CacheSave,
+    /// Loading the picker's highlighting and building its items
+    UiInit,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 6] = [
+        Self::Config,
+        Self::Discovery,
+        Self::CacheLoad,
+        Self::Parse,
+        Self::CacheSave,
+        Self::UiInit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Config => "config",
+            Self::Discovery => "discovery",
+            Self::CacheLoad => "cache load",
+            Self::Parse => "parse",
+            Self::CacheSave => "cache save",
+            Self::UiInit => "ui init",
+        }
+    }
+}
+
+/// Time spent in each phase, summed over every time it ran (once per project
+/// root, say), and the Makefiles the cache had or didn't
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Timings {
+    phases: Vec<(Phase, Duration)>,
+    pub cache_hits: usize,
+    pub cache_misses: usize,
+}
+
+impl Timings {
+    const fn new() -> Self {
+        Self {
+            phases: Vec::new(),
+            cache_hits: 0,
+            cache_misses: 0,
+        }
+    }
+
+    pub fn add(&mut self, phase: Phase, duration: Duration) {
+        match self.phases.iter_mut().find(|(p, _)| *p == phase) {
+            Some((_, total)) => *total += duration,
+            None => self.phases.push((phase, duration)),
+        }
+    }
+
+    /// The time spent in `phase`, or None when it didn't run
+    pub fn phase(&self, phase: Phase) -> Option<Duration> {
+        self.phases
+            .iter()
+            .find(|(p, _)| *p == phase)
+            .map(|(_, d)| *d)
+    }
+
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, d)| *d).sum()
+    }
+
+    /// The phases that ran, in startup order, and the cache counts, as lines
+    /// for stderr
+    pub fn render(&self) -> Vec<String> {
+        let mut lines = vec!["Timings:".to_string()];
+        for phase in Phase::ALL {
+            if let Some(duration) = self.phase(phase) {
+                lines.push(format!(
+                    "  {:<11} {}",
+                    phase.name(),
+                    format_duration(duration)
+                ));
+            }
+        }
+        lines.push(format!(
+            "  {:<11} {}",
+            "total",
+            format_duration(self.total())
+        ));
+
+        let lookups = self.cache_hits + self.cache_misses;
+        if let Some(rate) = (self.cache_hits * 100).checked_div(lookups) {
+            lines.push(format!(
+                "Cache: {} hit(s), {} miss(es) ({}% hit rate)",
+                self.cache_hits, self.cache_misses, rate
+            ));
+        }
+        lines
+    }
+
+    /// The `metrics` object of `--json --timings`, in milliseconds
+    pub fn to_json(&self) -> serde_json::Value {
+        let phases: serde_json::Map<String, serde_json::Value> = Phase::ALL
+            .into_iter()
+            .filter_map(|phase| {
+                let ms = self.phase(phase)?.as_secs_f64() * 1000.0;
+                let name = serde_json::to_value(phase).ok()?.as_str()?.to_string();
+                Some((name, serde_json::json!(round(ms))))
+            })
+            .collect();
+        serde_json::json!({
+            "phases_ms": phases,
+            "total_ms": round(self.total().as_secs_f64() * 1000.0),
+            "cache": { "hits": self.cache_hits, "misses": self.cache_misses },
+        })
+    }
+
+    /// A command's `--json` output with the metrics in it: as its `metrics`
+    /// key, or next to it as `items` when it is a list
+    pub fn with_metrics(&self, payload: serde_json::Value) -> serde_json::Value {
+        match payload {
+            serde_json::Value::Object(mut object) => {
+                object.insert("metrics".to_string(), self.to_json());
+                serde_json::Value::Object(object)
+            }
+            items => serde_json::json!({ "items": items, "metrics": self.to_json() }),
+        }
+    }
+}
+
+fn round(ms: f64) -> f64 {
+    (ms * 100.0).round() / 100.0
+}
+
+/// A phase's time, to the tenth of a millisecond below a second
+fn format_duration(duration: Duration) -> String {
+    match duration.as_secs() {
+        0 => format!("{:.1}ms", duration.as_secs_f64() * 1000.0),
+        _ => format_elapsed(duration),
+    }
+}
+
+/// Add the time since `started` to `phase`
+pub fn record(phase: Phase, started: Instant) {
+    if let Ok(mut timings) = TIMINGS.lock() {
+        timings.add(phase, started.elapsed());
+    }
+}
+
+/// Count a Makefile read from the cache or parsed afresh
+pub fn cache_lookup(hit: bool) {
+    if let Ok(mut timings) = TIMINGS.lock() {
+        match hit {
+            true => timings.cache_hits += 1,
+            false => timings.cache_misses += 1,
+        }
+    }
+}
+
+/// What has been recorded so far
+pub fn snapshot() -> Timings {
+    TIMINGS.lock().map(|t| t.clone()).unwrap_or_default()
+}
+
+/// Ask for the timings to be reported, once
+pub fn want_report() {
+    WANTED.store(true, Ordering::Relaxed);
+}
+
+/// The timings to report, if they are wanted and this is the first time
+/// they are asked for
+pub fn take_report() -> Option<Timings> {
+    (WANTED.load(Ordering::Relaxed) && !REPORTED.swap(true, Ordering::Relaxed)).then(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phases_add_up() {
+        let mut timings = Timings::default();
+        timings.add(Phase::Parse, Duration::from_millis(12));
+        timings.add(Phase::Discovery, Duration::from_millis(3));
+        timings.add(Phase::Parse, Duration::from_millis(8));
+        timings.cache_hits = 3;
+        timings.cache_misses = 1;
+
+        assert_eq!(timings.phase(Phase::Parse), Some(Duration::from_millis(20)));
+        assert_eq!(timings.phase(Phase::UiInit), None);
+        assert_eq!(
+            timings.render(),
+            [
+                "Timings:",
+                "  discovery   3.0ms",
+                "  parse       20.0ms",
+                "  total       23.0ms",
+                "Cache: 3 hit(s), 1 miss(es) (75% hit rate)",
+            ]
+        );
+        assert_eq!(
+            timings.to_json(),
+            serde_json::json!({
+                "phases_ms": { "discovery": 3.0, "parse": 20.0 },
+                "total_ms": 23.0,
+                "cache": { "hits": 3, "misses": 1 },
+            })
+        );
+    }
+
+    #[test]
+    fn test_with_metrics() {
+        let timings = Timings::default();
+        let metrics = timings.to_json();
+
+        let payload = timings.with_metrics(serde_json::json!({ "target": "build" }));
+        assert_eq!(
+            payload,
+            serde_json::json!({ "target": "build", "metrics": metrics })
+        );
+        let payload = timings.with_metrics(serde_json::json!([1, 2]));
+        assert_eq!(
+            payload,
+            serde_json::json!({ "items": [1, 2], "metrics": metrics })
+        );
+    }
+}
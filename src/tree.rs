@@ -0,0 +1,260 @@
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::target::Target;
+
+/// Characters that split a target name into namespace segments
+const SEPARATORS: [char; 2] = ['/', '.'];
+
+/// A namespace segment of target names, e.g. `docker` in `docker/build`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Node {
+    /// The segment, or several joined when a group was collapsed
+    pub name: String,
+    /// The full name up to this segment, e.g. `docker/build`
+    pub path: String,
+    /// The target with exactly this name, if there is one
+    pub target: Option<Target>,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    fn new(name: &str, path: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            path: path.to_string(),
+            target: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// Number of targets in and below this node
+    pub fn count(&self) -> usize {
+        usize::from(self.target.is_some()) + self.children.iter().map(Node::count).sum::<usize>()
+    }
+}
+
+/// Group targets by the segments of their names, keeping the order in which
+/// groups first appear in `targets`
+pub fn build(targets: &[Target]) -> Vec<Node> {
+    let mut roots: Vec<Node> = Vec::new();
+    for target in targets {
+        let mut nodes = &mut roots;
+        let segments = segments(&target.name);
+        for (i, &(name, path)) in segments.iter().enumerate() {
+            let last = i + 1 == segments.len();
+            // A name defined in several roots gets a node for each
+            let pos = match nodes
+                .iter()
+                .position(|n| n.name == name && !(last && n.target.is_some()))
+            {
+                Some(pos) => pos,
+                None => {
+                    nodes.push(Node::new(name, path));
+                    nodes.len() - 1
+                }
+            };
+            if last {
+                nodes[pos].target = Some(target.clone());
+            }
+            nodes = &mut nodes[pos].children;
+        }
+    }
+    roots
+}
+
+/// Each segment of `name` with the full name up to it; separators at the
+/// start or end of the name (`.env`) stay part of their segment
+fn segments(name: &str) -> Vec<(&str, &str)> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for (i, c) in name.char_indices() {
+        if SEPARATORS.contains(&c) && i > start && i + 1 < name.len() {
+            segments.push((&name[start..i], &name[..i]));
+            start = i + 1;
+        }
+    }
+    segments.push((&name[start..], name));
+    segments
+}
+
+/// Merge each group holding a single entry and no target of its own into
+/// that entry, so `ci/lint` alone shows as one line
+pub fn collapse(nodes: Vec<Node>) -> Vec<Node> {
+    nodes
+        .into_iter()
+        .map(|mut node| {
+            node.children = collapse(node.children);
+            if node.target.is_none() && node.children.len() == 1 {
+                let child = node.children.remove(0);
+                Node {
+                    name: format!("{}{}", node.name, &child.path[node.path.len()..]),
+                    ..child
+                }
+            } else {
+                node
+            }
+        })
+        .collect()
+}
+
+/// The tree as lines for `maki list --tree`, descriptions aligned
+pub fn render(nodes: &[Node], with_deps: bool) -> Vec<String> {
+    let mut rows = Vec::new();
+    rows_into(nodes, None, &mut rows);
+    let width = rows
+        .iter()
+        .map(|(prefix, label, _)| prefix.chars().count() + label.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    rows.into_iter()
+        .map(|(prefix, label, node)| {
+            let pad = width - prefix.chars().count();
+            let Some(ref target) = node.target else {
+                let count = format!("({})", node.count());
+                return format!(
+                    "  {}{}  {}",
+                    prefix.dimmed(),
+                    format!("{:<pad$}", label).blue().bold(),
+                    count.dimmed()
+                );
+            };
+            let name = format!("{:<pad$}", label);
+            let name = if target.aggregate {
+                name.cyan()
+            } else {
+                name.green()
+            };
+            let mut line = match target.description {
+                Some(ref desc) => format!("  {}{}  {}", prefix.dimmed(), name, desc.dimmed()),
+                None => format!("  {}{}", prefix.dimmed(), name),
+            };
+            let prerequisites = target.prerequisite_list();
+            if (with_deps || target.aggregate) && !prerequisites.is_empty() {
+                let deps = format!("← {}", prerequisites);
+                line.push_str(&format!("  {}", deps.dimmed()));
+            }
+            line
+        })
+        .collect()
+}
+
+/// Flatten the tree into (branch prefix, label, node) rows; top-level
+/// entries (`indent` of `None`) aren't drawn as branches
+fn rows_into<'a>(
+    nodes: &'a [Node],
+    indent: Option<&str>,
+    rows: &mut Vec<(String, String, &'a Node)>,
+) {
+    for (i, node) in nodes.iter().enumerate() {
+        let last = i + 1 == nodes.len();
+        let (prefix, child_indent) = match indent {
+            None => (String::new(), String::new()),
+            Some(indent) if last => (format!("{}└── ", indent), format!("{}    ", indent)),
+            Some(indent) => (format!("{}├── ", indent), format!("{}│   ", indent)),
+        };
+        let label = match node.target {
+            // Keep the root and provider tags of `display_name`
+            Some(ref target) => format!(
+                "{}{}",
+                node.name,
+                &target.display_name()[target.name.len()..]
+            ),
+            None => node.name.clone(),
+        };
+        rows.push((prefix, label, node));
+        rows_into(&node.children, Some(&child_indent), rows);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runlog::strip_ansi;
+    use std::path::PathBuf;
+
+    fn target(name: &str, desc: Option<&str>) -> Target {
+        Target::new(
+            name.to_string(),
+            desc.map(str::to_string),
+            PathBuf::from("Makefile"),
+            1,
+        )
+    }
+
+    fn names(nodes: &[Node]) -> Vec<&str> {
+        nodes.iter().map(|n| n.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_segments() {
+        assert_eq!(
+            segments("docker/build.release"),
+            vec![
+                ("docker", "docker"),
+                ("build", "docker/build"),
+                ("release", "docker/build.release")
+            ]
+        );
+        assert_eq!(segments(".env"), vec![(".env", ".env")]);
+        assert_eq!(segments("dist/"), vec![("dist/", "dist/")]);
+    }
+
+    #[test]
+    fn test_build_groups_by_segment() {
+        let targets = [
+            target("docker", None),
+            target("docker/build", None),
+            target("test", None),
+            target("docker/push", None),
+        ];
+
+        let tree = build(&targets);
+
+        assert_eq!(names(&tree), vec!["docker", "test"]);
+        assert!(tree[0].target.is_some());
+        assert_eq!(names(&tree[0].children), vec!["build", "push"]);
+        assert_eq!(tree[0].children[1].path, "docker/push");
+        assert_eq!(tree[0].count(), 3);
+    }
+
+    #[test]
+    fn test_collapse_single_entry_groups() {
+        let targets = [
+            target("ci/lint", None),
+            target("docker/build", None),
+            target("docker/push", None),
+        ];
+
+        let tree = collapse(build(&targets));
+
+        assert_eq!(names(&tree), vec!["ci/lint", "docker"]);
+        assert_eq!(tree[0].path, "ci/lint");
+        assert!(tree[0].target.is_some());
+    }
+
+    #[test]
+    fn test_render() {
+        let targets = [
+            target("docker/build", Some("Build the image")),
+            target("docker/push", Some("Push it")),
+            target("test", None),
+        ];
+
+        let lines: Vec<String> = render(&collapse(build(&targets)), false)
+            .iter()
+            .map(|line| strip_ansi(line))
+            .collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                "  docker     (2)",
+                "  ├── build  Build the image",
+                "  └── push   Push it",
+                "  test     ",
+            ]
+        );
+    }
+}
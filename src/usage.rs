@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::history::HistoryEntry;
+
+/// The shared usage file, relative to the project root, meant to be committed
+pub const USAGE_FILE: &str = ".maki/usage.json";
+
+const CONTRIBUTOR_FILENAME: &str = "contributor-id";
+
+/// How often the team runs each target, as `maki usage export` writes it
+///
+/// Each teammate's counts are filed under a random ID rather than a name, and
+/// hold only target names and run counts: no times, commands, or paths. An
+/// export replaces the exporter's own counts, so exporting again doesn't
+/// count the same runs twice.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TeamUsage {
+    /// Run counts by contributor ID, then target name
+    contributors: BTreeMap<String, BTreeMap<String, u64>>,
+}
+
+/// Where the usage file of the project at `root` is
+pub fn usage_file_path(root: &Path) -> PathBuf {
+    root.join(USAGE_FILE)
+}
+
+/// Where this user's contributor ID is kept: `<data dir>/maki/contributor-id`
+pub fn contributor_file_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|p| p.join("maki").join(CONTRIBUTOR_FILENAME))
+}
+
+/// This user's contributor ID, made up on first use: random, so it can't be
+/// traced back to a name or email the way a hash of one could
+pub fn contributor_id() -> Result<String> {
+    let path = contributor_file_path().context("Could not determine data directory")?;
+    if let Ok(id) = fs::read_to_string(&path)
+        && !id.trim().is_empty()
+    {
+        return Ok(id.trim().to_string());
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{:?}", SystemTime::now()));
+    hasher.update(std::process::id().to_le_bytes());
+    let id = hex::encode(&hasher.finalize()[..6]);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create data directory: {}", dir.display()))?;
+    }
+    fs::write(&path, &id)
+        .with_context(|| format!("Failed to write contributor ID: {}", path.display()))?;
+    Ok(id)
+}
+
+/// How many times each of `names` was run in `dir`, leaving out those never
+/// run
+pub fn count_runs(entries: &[HistoryEntry], dir: &Path, names: &[&str]) -> BTreeMap<String, u64> {
+    let mut counts = BTreeMap::new();
+    for entry in entries {
+        if entry.dir == dir && names.contains(&entry.target.as_str()) {
+            *counts.entry(entry.target.clone()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+impl TeamUsage {
+    /// Load the usage file of the project at `root`, empty when there is none
+    pub fn load(root: &Path) -> Result<Self> {
+        Self::load_from(&usage_file_path(root))
+    }
+
+    pub fn save(&self, root: &Path) -> Result<()> {
+        self.save_to(&usage_file_path(root))
+    }
+
+    fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read usage file: {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Invalid usage file: {}", path.display()))
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+        }
+        let content = serde_json::to_string_pretty(self).context("Failed to serialize usage")?;
+        fs::write(path, content + "\n")
+            .with_context(|| format!("Failed to write usage file: {}", path.display()))
+    }
+
+    /// Put `counts` in place of what `contributor` exported before; with no
+    /// counts, drop the contributor
+    pub fn record(&mut self, contributor: &str, counts: BTreeMap<String, u64>) {
+        if counts.is_empty() {
+            self.contributors.remove(contributor);
+        } else {
+            self.contributors.insert(contributor.to_string(), counts);
+        }
+    }
+
+    /// Everyone's run counts added up, by target name
+    pub fn totals(&self) -> BTreeMap<&str, u64> {
+        let mut totals = BTreeMap::new();
+        for counts in self.contributors.values() {
+            for (name, count) in counts {
+                *totals.entry(name.as_str()).or_insert(0) += count;
+            }
+        }
+        totals
+    }
+
+    /// Whether `contributor` exported counts before
+    pub fn has(&self, contributor: &str) -> bool {
+        self.contributors.contains_key(contributor)
+    }
+
+    /// How many teammates exported their counts
+    pub fn contributors(&self) -> usize {
+        self.contributors.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(target: &str, dir: &str) -> HistoryEntry {
+        HistoryEntry {
+            target: target.to_string(),
+            command: format!("make {}", target),
+            dir: PathBuf::from(dir),
+            started_at: 0,
+            duration_ms: 10,
+            exit_code: Some(0),
+            success: true,
+            repeat: None,
+        }
+    }
+
+    #[test]
+    fn test_count_runs() {
+        let entries = [
+            run("build", "/app"),
+            run("build", "/app"),
+            run("build", "/other"),
+            run("test", "/app"),
+            run("dist/app.tar.gz", "/app"),
+        ];
+        let counts = count_runs(&entries, Path::new("/app"), &["build", "test", "lint"]);
+        assert_eq!(
+            counts,
+            BTreeMap::from([("build".to_string(), 2), ("test".to_string(), 1)])
+        );
+    }
+
+    #[test]
+    fn test_record_replaces_the_contributors_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(USAGE_FILE);
+
+        let mut usage = TeamUsage::load_from(&path).unwrap();
+        usage.record("a1", BTreeMap::from([("build".to_string(), 3)]));
+        usage.record("b2", BTreeMap::from([("build".to_string(), 1)]));
+        usage.record(
+            "a1",
+            BTreeMap::from([("build".to_string(), 4), ("test".to_string(), 2)]),
+        );
+        usage.save_to(&path).unwrap();
+
+        let mut usage = TeamUsage::load_from(&path).unwrap();
+        assert_eq!(usage.contributors(), 2);
+        assert!(usage.has("b2"));
+        assert_eq!(usage.totals(), BTreeMap::from([("build", 5), ("test", 2)]));
+
+        usage.record("b2", BTreeMap::new());
+        assert_eq!(usage.contributors(), 1);
+    }
+}
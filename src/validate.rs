@@ -0,0 +1,437 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::generated;
+use crate::makefile::{self, ParseOptions};
+use crate::refactor::FileEdit;
+
+/// Words that start a make directive rather than a rule or recipe line
+const DIRECTIVES: [&str; 15] = [
+    "ifeq", "ifneq", "ifdef", "ifndef", "else", "endif", "include", "-include", "sinclude",
+    "export", "unexport", "override", "define", "endef", "vpath",
+];
+
+/// What `maki validate` looks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    /// A target that makes no file of its name but isn't declared `.PHONY`
+    MissingPhony,
+    /// A `##` description without exactly one space after the `##`
+    DescriptionSpacing,
+    /// A recipe line indented with spaces, which make doesn't take
+    SpaceIndent,
+}
+
+/// Something `maki validate --fix` can put right
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub file: PathBuf,
+    pub line: usize,
+    pub check: Check,
+    pub message: String,
+}
+
+/// What validating a Makefile found, and the Makefile with all of it fixed
+#[derive(Debug, Clone)]
+pub struct Validation {
+    pub findings: Vec<Finding>,
+    pub fix: FileEdit,
+}
+
+/// Check the Makefile at `path`; generated ones are left alone, as the next
+/// generation would undo any fix
+pub fn validate(path: &Path) -> Result<Validation> {
+    let original =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let (mut findings, updated) = match generated::detect(&original) {
+        Some(_) => (Vec::new(), original.clone()),
+        None => check(&original, path),
+    };
+    findings.sort_by_key(|f| f.line);
+    Ok(Validation {
+        findings,
+        fix: FileEdit {
+            path: path.to_path_buf(),
+            original,
+            updated,
+        },
+    })
+}
+
+/// What `content` has to fix, and the content with it fixed
+fn check(content: &str, path: &Path) -> (Vec<Finding>, String) {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut fixed: Vec<String> = Vec::with_capacity(lines.len() + 2);
+    let mut findings = Vec::new();
+    let mut found = |line: usize, check: Check, message: String| {
+        findings.push(Finding {
+            file: path.to_path_buf(),
+            line,
+            check,
+            message,
+        })
+    };
+
+    let mut in_define = false;
+    let mut in_rule = false;
+    let mut continued = false;
+    for (i, line) in lines.iter().enumerate() {
+        let text = line.trim_end_matches(['\n', '\r']);
+        let ending = &line[text.len()..];
+        let trimmed = text.trim();
+        let continues = std::mem::replace(&mut continued, text.ends_with('\\'));
+
+        let rewritten = if in_define {
+            in_define = trimmed != "endef";
+            None
+        } else if continues || text.starts_with('\t') || trimmed.is_empty() {
+            None
+        } else if trimmed.starts_with("##") && !text.starts_with(' ') {
+            spaced_description(text)
+        } else if trimmed.starts_with('#') {
+            None
+        } else if is_directive(trimmed) || makefile::is_variable_assignment(trimmed) {
+            in_define = trimmed.starts_with("define ");
+            in_rule = in_rule && text.starts_with(' ');
+            None
+        } else if text.starts_with(' ') {
+            // Outside a rule, make reads it as another rule or assignment
+            in_rule.then(|| format!("\t{}", text.trim_start()))
+        } else {
+            in_rule = text.contains(':');
+            match text.find(" ##").filter(|_| in_rule) {
+                Some(pos) => spaced_description(&text[pos + 1..])
+                    .map(|comment| format!("{} {}", &text[..pos], comment)),
+                None => None,
+            }
+        };
+
+        match rewritten {
+            Some(rewritten) => {
+                let (check, message) = match rewritten.starts_with('\t') {
+                    true => (
+                        Check::SpaceIndent,
+                        "recipe line is indented with spaces, and make needs a tab",
+                    ),
+                    false => (
+                        Check::DescriptionSpacing,
+                        "a `##` description takes one space after the `##`",
+                    ),
+                };
+                found(i + 1, check, message.to_string());
+                fixed.push(format!("{}{}", rewritten, ending));
+            }
+            None => fixed.push(line.to_string()),
+        }
+    }
+
+    // With recipes tab-indented, make sees what each target runs
+    let fixed_content = fixed.concat();
+    let lines: Vec<&str> = fixed_content.split_inclusive('\n').collect();
+    let missing = missing_phony(&fixed_content, &lines, path);
+    for (line, name) in &missing {
+        found(
+            *line,
+            Check::MissingPhony,
+            format!(
+                "{} makes no file of that name, but isn't declared .PHONY",
+                name
+            ),
+        );
+    }
+    if missing.is_empty() {
+        return (findings, fixed_content);
+    }
+    let names: Vec<&str> = missing.iter().map(|(_, name)| name.as_str()).collect();
+    declare_phony(&mut fixed, &lines, &names.join(" "));
+    (findings, fixed.concat())
+}
+
+/// Whether `line` is a directive like `ifdef` or `include`
+fn is_directive(line: &str) -> bool {
+    let word = line.split_whitespace().next().unwrap_or("");
+    DIRECTIVES.contains(&word)
+}
+
+/// `comment`, which starts with `##`, with one space after the `##`, or None
+/// when it already has one; `###` headings and `##@` groups are left alone
+fn spaced_description(comment: &str) -> Option<String> {
+    let after = comment.strip_prefix("##")?;
+    let text = after.trim_start_matches([' ', '\t']);
+    if text.is_empty() || after.starts_with(['#', '@']) {
+        return None;
+    }
+    if after.len() == text.len() + 1 && after.starts_with(' ') {
+        return None;
+    }
+    Some(format!("## {}", text))
+}
+
+/// The targets, with their lines, that look like tasks rather than files
+/// but aren't declared `.PHONY`
+///
+/// A target counts when its name has no extension or directory, it has a
+/// recipe or prerequisites, its recipe writes no file (through `$@`, its own
+/// name or a `>` redirect), and no file of its name is next to the Makefile.
+fn missing_phony(content: &str, lines: &[&str], path: &Path) -> Vec<(usize, String)> {
+    let Some(declared) = declared_phony(lines) else {
+        return Vec::new();
+    };
+    let Ok(targets) = makefile::parse_makefile_content(content, path, &ParseOptions::everything())
+    else {
+        return Vec::new();
+    };
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let plain: Vec<&str> = lines
+        .iter()
+        .map(|l| l.trim_end_matches(['\n', '\r']))
+        .collect();
+
+    targets
+        .iter()
+        .filter(|t| {
+            t.name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        })
+        .filter(|t| !declared.contains(&t.name.as_str()))
+        .filter(|t| {
+            let recipe = makefile::recipe_lines(&plain, t.line.saturating_sub(1));
+            (!recipe.is_empty() || t.prerequisites().next().is_some())
+                && !recipe.iter().any(|line| writes_a_file(line, &t.name))
+        })
+        .filter(|t| !dir.join(&t.name).exists())
+        .map(|t| (t.line, t.name.clone()))
+        .collect()
+}
+
+/// Whether a recipe line looks like it makes a file: it names its target,
+/// as `$@` or spelled out, or sends output somewhere other than `/dev/`
+fn writes_a_file(line: &str, name: &str) -> bool {
+    let names_target = line
+        .split(|c: char| c.is_whitespace() || ";|&<>'\"".contains(c))
+        .any(|word| word.trim_start_matches("./") == name);
+    let redirects = line.match_indices('>').any(|(i, _)| {
+        let rest = line[i + 1..].trim_start_matches('>').trim_start();
+        !rest.starts_with('&') && !rest.starts_with("/dev/")
+    });
+    line.contains("$@") || names_target || redirects
+}
+
+/// The names `.PHONY` lines declare, or None when one names them through a
+/// variable, so which they are isn't known
+fn declared_phony<'a>(lines: &[&'a str]) -> Option<Vec<&'a str>> {
+    let mut names = Vec::new();
+    let mut continued = false;
+    for line in lines {
+        let text = line.trim_end_matches(['\n', '\r']);
+        let words = match text.strip_prefix(".PHONY:") {
+            Some(words) => words,
+            None if continued => text,
+            None => continue,
+        };
+        continued = text.ends_with('\\');
+        let words = words.split('#').next().unwrap_or("");
+        for word in words.split_whitespace().filter(|w| *w != "\\") {
+            if word.contains('$') {
+                return None;
+            }
+            names.push(word);
+        }
+    }
+    Some(names)
+}
+
+/// Declare `names` at the end of the first `.PHONY` line, or on a new one
+/// above the first rule and its comments
+fn declare_phony(fixed: &mut Vec<String>, lines: &[&str], names: &str) {
+    let ending = match lines.first().is_some_and(|l| l.ends_with("\r\n")) {
+        true => "\r\n",
+        false => "\n",
+    };
+
+    let first = lines.iter().position(|l| l.starts_with(".PHONY:"));
+    if let Some(first) = first {
+        let last = (first..lines.len())
+            .find(|&i| !lines[i].trim_end().ends_with('\\'))
+            .unwrap_or(first);
+        let text = fixed[last].trim_end_matches(['\n', '\r']);
+        let line_ending = &fixed[last][text.len()..];
+        fixed[last] = format!("{} {}{}", text, names, line_ending);
+        return;
+    }
+
+    let rule = lines
+        .iter()
+        .position(|l| {
+            !l.starts_with(['\t', ' ', '#', '.'])
+                && l.contains(':')
+                && !makefile::is_variable_assignment(l.trim())
+                && !is_directive(l.trim())
+        })
+        .unwrap_or(0);
+    let mut at = rule;
+    while at > 0 && lines[at - 1].starts_with('#') {
+        at -= 1;
+    }
+    fixed.insert(at, format!(".PHONY: {}{}{}", names, ending, ending));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_in(dir: &Path, content: &str) -> (Vec<(usize, Check)>, String) {
+        let (findings, fixed) = check(content, &dir.join("Makefile"));
+        let mut found: Vec<(usize, Check)> = findings.iter().map(|f| (f.line, f.check)).collect();
+        found.sort_by_key(|&(line, _)| line);
+        (found, fixed)
+    }
+
+    #[test]
+    fn test_description_spacing() {
+        assert_eq!(
+            spaced_description("##Build it"),
+            Some("## Build it".to_string())
+        );
+        assert_eq!(
+            spaced_description("##   Build it"),
+            Some("## Build it".to_string())
+        );
+        assert_eq!(
+            spaced_description("##\tBuild"),
+            Some("## Build".to_string())
+        );
+        assert_eq!(spaced_description("## Build it"), None);
+        assert_eq!(spaced_description("### Section"), None);
+        assert_eq!(spaced_description("##@ Group"), None);
+        assert_eq!(spaced_description("##"), None);
+    }
+
+    #[test]
+    fn test_fixes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("dist"), "").unwrap();
+        let content = "\
+CC := cc
+
+##Build the binary
+build: main.o
+    $(CC) -o app main.o
+
+test: build   ##   Run the tests
+\t./test.sh \\
+    --verbose
+
+lint:
+    cargo clippy
+
+app.o: main.c
+\t$(CC) -c -o $@ $<
+
+dist:
+\ttar czf dist app
+
+out:
+\t$(CC) -o $@ main.c
+
+gen:
+\ttouch gen
+
+report:
+\t./collect.sh > report.txt 2>&1
+
+quiet:
+\t./check.sh >/dev/null 2>&1
+";
+        let (found, fixed) = check_in(dir.path(), content);
+        assert_eq!(
+            found,
+            vec![
+                (3, Check::DescriptionSpacing),
+                (4, Check::MissingPhony),
+                (5, Check::SpaceIndent),
+                (7, Check::DescriptionSpacing),
+                (7, Check::MissingPhony),
+                (11, Check::MissingPhony),
+                (12, Check::SpaceIndent),
+                (29, Check::MissingPhony),
+            ]
+        );
+        assert_eq!(
+            fixed,
+            "\
+CC := cc
+
+.PHONY: build test lint quiet
+
+## Build the binary
+build: main.o
+\t$(CC) -o app main.o
+
+test: build   ## Run the tests
+\t./test.sh \\
+    --verbose
+
+lint:
+\tcargo clippy
+
+app.o: main.c
+\t$(CC) -c -o $@ $<
+
+dist:
+\ttar czf dist app
+
+out:
+\t$(CC) -o $@ main.c
+
+gen:
+\ttouch gen
+
+report:
+\t./collect.sh > report.txt 2>&1
+
+quiet:
+\t./check.sh >/dev/null 2>&1
+"
+        );
+        // Fixed, there is nothing left to fix
+        let (found, again) = check_in(dir.path(), &fixed);
+        assert!(found.is_empty(), "{:?}", found);
+        assert_eq!(again, fixed);
+    }
+
+    #[test]
+    fn test_phony_joins_the_existing_declaration() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = ".PHONY: build \\\n\ttest\nbuild:\n\tcargo build\ntest:\n\tcargo test\nlint:\n\tcargo clippy\n";
+        let (found, fixed) = check_in(dir.path(), content);
+        assert_eq!(found, vec![(7, Check::MissingPhony)]);
+        assert!(
+            fixed.starts_with(".PHONY: build \\\n\ttest lint\nbuild:"),
+            "{}",
+            fixed
+        );
+
+        // Declared through a variable, which targets are phony isn't known
+        let content = ".PHONY: $(TASKS)\nlint:\n\tcargo clippy\n";
+        assert!(check_in(dir.path(), content).0.is_empty());
+    }
+
+    #[test]
+    fn test_validate_leaves_generated_makefiles_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Makefile");
+        fs::write(
+            &path,
+            "# CMAKE generated file: DO NOT EDIT!\n# Generated by \"Unix Makefiles\" Generator, CMake Version 3.28\n\nall:\n\t$(MAKE) -f CMakeFiles/Makefile2 all\n",
+        )
+        .unwrap();
+        let validation = validate(&path).unwrap();
+        assert!(validation.findings.is_empty());
+        assert_eq!(validation.fix.original, validation.fix.updated);
+    }
+}
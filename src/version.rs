@@ -0,0 +1,136 @@
+use chrono::{DateTime, SecondsFormat};
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::cache::Cache;
+use crate::providers::ProviderPolicy;
+use crate::target::Provider;
+
+/// Version of this build
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Capabilities that depend on the platform maki was built for
+const FEATURES: &[&str] = if cfg!(unix) { &["detach"] } else { &[] };
+
+/// What `maki version` reports about this build, for bug reports and for
+/// tools that need a minimum version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildInfo {
+    pub version: String,
+    /// The commit it was built from, when built from a git checkout
+    pub git_sha: Option<String>,
+    /// When it was built, in RFC 3339
+    pub build_date: Option<String>,
+    /// The platform it was built for, e.g. `x86_64-unknown-linux-gnu`
+    pub target: Option<String>,
+    pub features: Vec<String>,
+    /// The providers used in the current project, highest priority first
+    pub providers: Vec<Provider>,
+    /// Where the parse cache is kept
+    pub cache_path: Option<PathBuf>,
+}
+
+impl BuildInfo {
+    /// This build, with the providers `policy` enables
+    pub fn current(policy: &ProviderPolicy) -> Self {
+        let build_date = option_env!("MAKI_BUILD_EPOCH")
+            .and_then(|secs| secs.parse().ok())
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .map(|date| date.to_rfc3339_opts(SecondsFormat::Secs, true));
+        Self {
+            version: VERSION.to_string(),
+            git_sha: option_env!("MAKI_GIT_SHA").map(String::from),
+            build_date,
+            target: option_env!("MAKI_BUILD_TARGET").map(String::from),
+            features: FEATURES.iter().map(|f| f.to_string()).collect(),
+            providers: policy
+                .order()
+                .into_iter()
+                .filter(|p| policy.is_enabled(*p))
+                .collect(),
+            cache_path: Cache::cache_file_path(),
+        }
+    }
+
+    /// The report `maki version` prints, one field per line
+    pub fn render(&self) -> String {
+        let unknown = || "unknown".to_string();
+        let providers: Vec<&str> = self.providers.iter().map(|p| p.name()).collect();
+        let fields = [
+            ("commit", self.git_sha.clone().unwrap_or_else(unknown)),
+            ("built", self.build_date.clone().unwrap_or_else(unknown)),
+            ("target", self.target.clone().unwrap_or_else(unknown)),
+            ("features", list(&self.features)),
+            ("providers", list(&providers)),
+            (
+                "cache",
+                self.cache_path
+                    .as_ref()
+                    .map_or_else(unknown, |p| p.display().to_string()),
+            ),
+        ];
+
+        let mut out = format!("maki {}\n", self.version);
+        for (label, value) in fields {
+            out.push_str(&format!("  {:<9} {}\n", label, value));
+        }
+        out
+    }
+}
+
+/// `items` separated by commas, or `none`
+fn list(items: &[impl AsRef<str>]) -> String {
+    if items.is_empty() {
+        return "none".to_string();
+    }
+    items
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_providers_follow_the_policy() {
+        let policy = ProviderPolicy {
+            priority: vec![Provider::Compose],
+            disabled: vec![Provider::Procfile],
+        };
+
+        let info = BuildInfo::current(&policy);
+
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            info.providers,
+            vec![Provider::Compose, Provider::Make, Provider::Inferred]
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let info = BuildInfo {
+            version: "1.2.0".to_string(),
+            git_sha: Some("6e36bf0".to_string()),
+            build_date: None,
+            target: Some("aarch64-apple-darwin".to_string()),
+            features: Vec::new(),
+            providers: vec![Provider::Make, Provider::Procfile],
+            cache_path: Some(PathBuf::from("/cache/maki/cache.json")),
+        };
+
+        assert_eq!(
+            info.render(),
+            "maki 1.2.0\n  \
+             commit    6e36bf0\n  \
+             built     unknown\n  \
+             target    aarch64-apple-darwin\n  \
+             features  none\n  \
+             providers make, procfile\n  \
+             cache     /cache/maki/cache.json\n"
+        );
+    }
+}
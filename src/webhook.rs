@@ -0,0 +1,276 @@
+use anyhow::{Result, bail};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::glob::glob_matches;
+use crate::heartbeat::format_elapsed;
+
+/// How long a webhook may take before maki gives up on it
+const TIMEOUT_SECS: &str = "10";
+
+/// The shape of the message a webhook expects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// `{"text": ...}`, for Slack's incoming webhooks
+    Slack,
+    /// `{"content": ...}`, for Discord's
+    Discord,
+    /// The run summary itself, as JSON
+    #[default]
+    Generic,
+}
+
+/// Which finished runs a webhook is told about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum When {
+    #[default]
+    Failure,
+    Success,
+    Always,
+}
+
+/// A URL to post run summaries to (`[[webhook]]` tables)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default)]
+    pub kind: Kind,
+    #[serde(default)]
+    pub on: When,
+    /// Globs over target names, e.g. `deploy*`; without any, every target
+    #[serde(default)]
+    pub targets: Vec<String>,
+}
+
+impl Webhook {
+    /// Whether runs of `target` are posted at all
+    pub fn covers(&self, target: &str) -> bool {
+        self.targets.is_empty() || self.targets.iter().any(|p| glob_matches(p, target))
+    }
+
+    /// Whether a run that ended this way is posted
+    pub fn fires_on(&self, success: bool) -> bool {
+        match self.on {
+            When::Failure => !success,
+            When::Success => success,
+            When::Always => true,
+        }
+    }
+
+    /// The host part of the URL, to name the webhook without showing the
+    /// token many webhook URLs hold
+    pub fn host(&self) -> &str {
+        let rest = self
+            .url
+            .split_once("://")
+            .map_or(self.url.as_str(), |(_, rest)| rest);
+        rest.split(['/', '?']).next().unwrap_or(rest)
+    }
+}
+
+/// What a webhook is told about a finished run; the command has the values
+/// of secret variables masked and leaves out the environment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Summary {
+    pub target: String,
+    pub success: bool,
+    /// None when the run was ended by a signal
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    /// The machine it ran on
+    pub host: String,
+    /// The directory it ran in
+    pub dir: PathBuf,
+    pub command: String,
+}
+
+impl Summary {
+    /// One line for chat, e.g. "✗ deploy failed (exit 2) after 45s on ci-1:
+    /// `make deploy`"
+    pub fn text(&self) -> String {
+        let elapsed = format_elapsed(Duration::from_millis(self.duration_ms));
+        let outcome = match (self.success, self.exit_code) {
+            (true, _) => format!("✓ {} succeeded in {}", self.target, elapsed),
+            (false, Some(code)) => {
+                format!("✗ {} failed (exit {}) after {}", self.target, code, elapsed)
+            }
+            (false, None) => format!("✗ {} was killed after {}", self.target, elapsed),
+        };
+        format!("{} on {}: `{}`", outcome, self.host, self.command)
+    }
+
+    /// The body to post to a webhook of `kind`
+    pub fn payload(&self, kind: Kind) -> serde_json::Value {
+        match kind {
+            Kind::Slack => json!({ "text": self.text() }),
+            Kind::Discord => json!({ "content": self.text() }),
+            Kind::Generic => json!(self),
+        }
+    }
+}
+
+/// Post `summary` to each of `hooks` that fires for it, warning about those
+/// that fail; a webhook never changes how the run ended
+pub fn notify(hooks: &[Webhook], summary: &Summary) {
+    for hook in hooks.iter().filter(|h| h.fires_on(summary.success)) {
+        let body = summary.payload(hook.kind).to_string();
+        if let Err(e) = post(&hook.url, &body) {
+            eprintln!(
+                "{} webhook to {} failed: {}",
+                "warning:".yellow(),
+                hook.host(),
+                e
+            );
+        }
+    }
+}
+
+/// POST `body` as JSON to `url` with curl
+fn post(url: &str, body: &str) -> Result<()> {
+    let child = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--max-time",
+            TIMEOUT_SECS,
+            "--header",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+            url,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) if e.kind() == ErrorKind::NotFound => bail!("curl is not installed"),
+        Err(e) => bail!("Failed to run curl: {}", e),
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(body.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// The machine's name, for saying where a run happened
+pub fn host() -> String {
+    #[cfg(unix)]
+    {
+        let mut buf = [0u8; 256];
+        // SAFETY: gethostname writes at most `buf.len()` bytes into `buf`
+        if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } == 0 {
+            let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+            return String::from_utf8_lossy(&buf[..end]).into_owned();
+        }
+    }
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(on: When, targets: &[&str]) -> Webhook {
+        Webhook {
+            url: "https://hooks.slack.com/services/T000/B000/XXXX".to_string(),
+            kind: Kind::Slack,
+            on,
+            targets: targets.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    fn summary(success: bool, exit_code: Option<i32>) -> Summary {
+        Summary {
+            target: "deploy".to_string(),
+            success,
+            exit_code,
+            duration_ms: 45_000,
+            host: "ci-1".to_string(),
+            dir: PathBuf::from("/app"),
+            command: "make deploy TOKEN=***".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_webhook() {
+        let hook: Webhook = toml::from_str("url = \"https://example.com/hook\"\n").unwrap();
+        assert_eq!(hook.kind, Kind::Generic);
+        assert_eq!(hook.on, When::Failure);
+        assert!(hook.targets.is_empty());
+
+        let hook: Webhook = toml::from_str(
+            "url = \"https://example.com\"\nkind = \"discord\"\non = \"always\"\ntargets = [\"deploy*\"]\n",
+        )
+        .unwrap();
+        assert_eq!(hook.kind, Kind::Discord);
+        assert_eq!(hook.on, When::Always);
+        assert!(toml::from_str::<Webhook>("url = \"x\"\nchannel = \"ops\"\n").is_err());
+    }
+
+    #[test]
+    fn test_which_runs_fire() {
+        assert!(hook(When::Failure, &[]).fires_on(false));
+        assert!(!hook(When::Failure, &[]).fires_on(true));
+        assert!(hook(When::Success, &[]).fires_on(true));
+        assert!(hook(When::Always, &[]).fires_on(false));
+
+        assert!(hook(When::Always, &[]).covers("build"));
+        assert!(hook(When::Always, &["deploy*"]).covers("deploy-prod"));
+        assert!(!hook(When::Always, &["deploy*"]).covers("build"));
+    }
+
+    #[test]
+    fn test_payloads() {
+        assert_eq!(
+            summary(false, Some(2)).text(),
+            "✗ deploy failed (exit 2) after 45s on ci-1: `make deploy TOKEN=***`"
+        );
+        assert_eq!(
+            summary(true, Some(0)).text(),
+            "✓ deploy succeeded in 45s on ci-1: `make deploy TOKEN=***`"
+        );
+        assert!(
+            summary(false, None)
+                .text()
+                .starts_with("✗ deploy was killed")
+        );
+
+        let failed = summary(false, Some(2));
+        assert_eq!(
+            failed.payload(Kind::Slack),
+            json!({ "text": failed.text() })
+        );
+        assert_eq!(
+            failed.payload(Kind::Discord),
+            json!({ "content": failed.text() })
+        );
+        let generic = failed.payload(Kind::Generic);
+        assert_eq!(generic["exit_code"], 2);
+        assert_eq!(generic["host"], "ci-1");
+        assert_eq!(generic["duration_ms"], 45_000);
+    }
+
+    #[test]
+    fn test_host_hides_the_path() {
+        assert_eq!(hook(When::Always, &[]).host(), "hooks.slack.com");
+        assert!(!host().is_empty());
+    }
+}
@@ -0,0 +1,34 @@
+use unicode_width::UnicodeWidthStr;
+
+/// How many terminal columns `text` takes: two for wide characters such as
+/// CJK and most emoji, none for combining marks
+pub fn columns(text: &str) -> usize {
+    text.width()
+}
+
+/// `text` padded with spaces to `width` terminal columns, the way
+/// `{:<width$}` pads text whose characters each take one
+pub fn pad(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(columns(text));
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columns_of_wide_characters() {
+        assert_eq!(columns("build"), 5);
+        assert_eq!(columns("构建"), 4);
+        assert_eq!(columns("🚀 ship"), 7);
+        assert_eq!(columns("cafe\u{301}"), 4);
+    }
+
+    #[test]
+    fn test_pad_to_columns() {
+        assert_eq!(pad("ビルド", 8), "ビルド  ");
+        assert_eq!(pad("test", 8), "test    ");
+        assert_eq!(pad("デプロイ", 4), "デプロイ");
+    }
+}
@@ -0,0 +1,32 @@
+use anyhow::{Context, Result, bail};
+use clap::CommandFactory;
+use std::fs;
+use std::path::PathBuf;
+
+use maki_cli::cli::Cli;
+use maki_cli::manual;
+
+/// Release chores, run with `cargo xtask <task>`
+///
+/// `man [DIR]` writes maki's man pages, one per command, to DIR
+/// (`target/man` by default), for packages to install.
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("man") => man(args.next().map(PathBuf::from)),
+        Some(task) => bail!("Unknown task {}; the tasks are: man", task),
+        None => bail!("Usage: cargo xtask man [DIR]"),
+    }
+}
+
+fn man(dir: Option<PathBuf>) -> Result<()> {
+    let dir = dir.unwrap_or_else(|| PathBuf::from("target").join("man"));
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let pages = manual::man_pages(Cli::command()).context("Failed to render the man pages")?;
+    for (name, page) in &pages {
+        let path = dir.join(name);
+        fs::write(&path, page).with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    println!("Wrote {} man pages to {}", pages.len(), dir.display());
+    Ok(())
+}